@@ -0,0 +1,143 @@
+// times full-grid passes over the cells stored flat, as the ecosystem stores them, and stored as
+// columns indexed by x then y, as they were before
+// cargo bench --no-default-features --bench cell_layout
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use vegetables_and_hummus::{
+    config::BoundaryMode,
+    constants,
+    ecology::{Cell, CellIndex, Ecosystem},
+};
+
+const SEED: u64 = 7;
+// number of times each pass is repeated, keeping the fastest
+const RUNS: u32 = 200;
+
+// the cells as they were stored before, one Vec per column
+struct Columns {
+    cells: Vec<Vec<Cell>>,
+}
+
+impl Columns {
+    fn from_ecosystem(ecosystem: &Ecosystem) -> Self {
+        let cells = (0..constants::AREA_SIDE_LENGTH)
+            .map(|x| {
+                (0..constants::AREA_SIDE_LENGTH)
+                    .map(|y| ecosystem[CellIndex::new(x, y)].clone())
+                    .collect()
+            })
+            .collect();
+        Columns { cells }
+    }
+}
+
+trait Layout {
+    fn get(&self, index: CellIndex) -> &Cell;
+    fn get_mut(&mut self, index: CellIndex) -> &mut Cell;
+}
+
+impl Layout for Ecosystem {
+    fn get(&self, index: CellIndex) -> &Cell {
+        &self[index]
+    }
+    fn get_mut(&mut self, index: CellIndex) -> &mut Cell {
+        &mut self[index]
+    }
+}
+
+impl Layout for Columns {
+    fn get(&self, index: CellIndex) -> &Cell {
+        &self.cells[index.x][index.y]
+    }
+    fn get_mut(&mut self, index: CellIndex) -> &mut Cell {
+        &mut self.cells[index.x][index.y]
+    }
+}
+
+// heights in row-major order, as gathered for the wind convolution and the vertices
+fn gather_heights(layout: &impl Layout) -> Vec<f32> {
+    (0..constants::NUM_CELLS)
+        .map(|i| layout.get(CellIndex::get_from_flat_index(i)).get_height())
+        .collect()
+}
+
+// steepest drop to a neighbor of every cell, like the passes that route water and sediment
+fn compute_slopes(layout: &impl Layout, boundary: BoundaryMode) -> Vec<f32> {
+    (0..constants::NUM_CELLS)
+        .map(|i| {
+            let index = CellIndex::get_from_flat_index(i);
+            let height = layout.get(index).get_height();
+            Cell::get_neighbors(&index, boundary)
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|neighbor| height - layout.get(neighbor).get_height())
+                .fold(0.0, f32::max)
+        })
+        .collect()
+}
+
+// writes the hours of sunlight of every cell, like recomputing constant sunlight
+fn set_sunlight(layout: &mut impl Layout, hours: [f32; 12]) {
+    for i in 0..constants::NUM_CELLS {
+        layout
+            .get_mut(CellIndex::get_from_flat_index(i))
+            .hours_of_sunlight = hours;
+    }
+}
+
+fn time(mut pass: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            pass();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, flat: Duration, columns: Duration) {
+    println!(
+        "{name:<16} flat {:>9.1?}  columns {:>9.1?}  speedup {:.2}x",
+        flat,
+        columns,
+        columns.as_secs_f64() / flat.as_secs_f64()
+    );
+}
+
+fn main() {
+    let mut ecosystem = Ecosystem::init_standard(SEED);
+    let mut columns = Columns::from_ecosystem(&ecosystem);
+    assert_eq!(gather_heights(&ecosystem), gather_heights(&columns));
+
+    report(
+        "gather heights",
+        time(|| {
+            black_box(gather_heights(&ecosystem));
+        }),
+        time(|| {
+            black_box(gather_heights(&columns));
+        }),
+    );
+    let boundary = ecosystem.config.boundary;
+    report(
+        "slopes",
+        time(|| {
+            black_box(compute_slopes(&ecosystem, boundary));
+        }),
+        time(|| {
+            black_box(compute_slopes(&columns, boundary));
+        }),
+    );
+    let hours = ecosystem.config.climate.monthly_sunlight_hours;
+    report(
+        "set sunlight",
+        time(|| set_sunlight(black_box(&mut ecosystem), hours)),
+        time(|| set_sunlight(black_box(&mut columns), hours)),
+    );
+}
@@ -5,8 +5,37 @@ out vec4 fragColor;
 in vec4 normal_worldSpace;
 in vec4 position_worldSpace;
 in vec4 vColor;
+in float vGrass;
+in vec4 position_lightSpace;
 
 uniform int wire = 0;
+// height between contour lines, none are drawn if 0
+uniform float contourInterval = 0.0;
+// 0 without shadows, 1 looked up in the shadow map, 2 in the dark of night
+uniform int shadows = 0;
+uniform sampler2D shadowMap;
+// 1 to light the terrain with the normals of the sand ripples in the ripple map
+uniform int ripples = 0;
+uniform sampler2D rippleMap;
+// texture coordinates per render unit
+uniform float rippleScale = 0.0;
+
+// share of the 3x3 texels around the fragment in the shadow map that are closer to the sun than it
+float getShadow() {
+    vec3 p = position_lightSpace.xyz / position_lightSpace.w * 0.5 + 0.5;
+    if (p.z > 1.0) {
+        return 0.0;
+    }
+    vec2 texel = 1.0 / vec2(textureSize(shadowMap, 0));
+    float shadow = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            float depth = texture(shadowMap, p.xy + vec2(x, y) * texel).r;
+            shadow += p.z > depth ? 1.0 : 0.0;
+        }
+    }
+    return shadow / 9.0;
+}
 
 
 void main() {
@@ -17,12 +46,45 @@ void main() {
     vec4 lightPos   = vec4(30.0, 0.0, 200.0, 1.0);
     vec3 lightColor = vec3(1.5f, 1.5f, 1.5f);
     vec4 lightDir   = normalize(-lightPos + position_worldSpace);
-    float c = clamp(dot(-normal_worldSpace, lightDir), 0, 1);
+    vec4 normal = normal_worldSpace;
+    if (ripples == 1) {
+        // cells are centered on their vertices, and the map flips y like the exported normal map
+        vec3 ripple = texture(rippleMap, (position_worldSpace.xy + 0.5) * rippleScale).rgb * 2.0 - 1.0;
+        normal = vec4(normalize(normal.xyz + vec3(ripple.x, -ripple.y, 0.0)), 0.0);
+    }
+    float c = clamp(dot(-normal, lightDir), 0, 1);
     float k = 0.2;
     float r = vColor[0] * (1.0 - k) + (c * lightColor[0] * k);
     float g = vColor[1] * (1.0 - k) + (c * lightColor[1] * k);
     float b = vColor[2] * (1.0 - k) + (c * lightColor[2] * k);
     fragColor = vec4(r, g, b, 1.0);
+
+    // speckle grass blades over the ground, as many as the coverage of the grass
+    if (vGrass > 0.0) {
+        vec2 blade = floor(position_worldSpace.xy * 12.0);
+        float h = fract(sin(dot(blade, vec2(12.9898, 78.233))) * 43758.5453);
+        if (h < vGrass) {
+            vec3 bladeColor = vec3(0.30, 0.55, 0.18) * (0.7 + 0.6 * fract(h * 17.0));
+            fragColor = vec4(mix(fragColor.rgb, bladeColor, 0.6), 1.0);
+        }
+    }
+
+    // darken what the sun does not reach
+    float shadow = shadows == 2 ? 1.0 : (shadows == 1 ? getShadow() : 0.0);
+    fragColor = vec4(fragColor.rgb * mix(1.0, 0.45, shadow), fragColor.a);
+
+    // darken a line about a pixel wide wherever the height crosses a multiple of the interval
+    if (contourInterval > 0.0) {
+        float level = position_worldSpace.z / contourInterval;
+        float distance = abs(fract(level - 0.5) - 0.5) / fwidth(level);
+        fragColor = vec4(fragColor.rgb * mix(0.35, 1.0, clamp(distance, 0.0, 1.0)), 1.0);
+    }
+
+    // keep the alpha of the water surface so it is blended over the terrain, skipping dry cells
+    if (vColor.a <= 0.0) {
+        discard;
+    }
+    fragColor.a = vColor.a;
     // fragColor = vec4(vColor[0] * c * lightColor[0], vColor[1] * c * lightColor[0], vColor[2] * c * lightColor[0], 1);
     // fragColor = vColor;
     // fragColor = vec4(normal_worldSpace[0], normal_worldSpace[1], normal_worldSpace[2], 1);
@@ -4,20 +4,54 @@ layout(location = 0) in vec3 position; // Position of the vertex
 layout(location = 1) in vec3 normal;   // Normal of the vertex
 layout(location = 2) in vec3 vcolor;   // Color of the vertex
 
+// plants are instances of a cylinder of unit diameter and length
+layout(location = 3) in vec3 instancePosition; // Center of the base of the plant
+layout(location = 4) in vec2 instanceSize;     // Diameter and length of the plant
+layout(location = 5) in vec3 instanceColor;    // Color of the plant
+
+layout(location = 6) in float grass; // Grass coverage of the terrain vertex
+layout(location = 7) in float alpha; // Opacity of the water surface vertex
+
 uniform mat4 proj;
 uniform mat4 view;
 uniform mat4 model;
 
 uniform mat3 inverseTransposeModel;
+// projection into the shadow map, as seen from the sun
+uniform mat4 lightSpace;
+
+uniform int instanced = 0;
+// lying plants extend along x instead of z
+uniform int lying = 0;
+// the water surface is blended over the terrain by its alpha
+uniform int translucent = 0;
 
 out vec4 normal_worldSpace;
 out vec4 position_worldSpace;
 out vec4 vColor;
+out float vGrass;
+out vec4 position_lightSpace;
 
 void main() {
-    vColor = vec4(vcolor, 1.0);
-    normal_worldSpace   = vec4(normalize(inverseTransposeModel * normal), 0);
-    position_worldSpace = vec4(position, 1.0);
+    vec3 p = position;
+    vec3 n = normal;
+    vec3 c = vcolor;
+    vGrass = grass;
+    if (instanced == 1) {
+        p = vec3(position.xy * instanceSize.x, position.z * instanceSize.y);
+        if (lying == 1) {
+            p = vec3(p.z - 0.5, p.x, p.y + instanceSize.x * 0.5);
+            n = vec3(n.z, n.x, n.y);
+        }
+        p += instancePosition;
+        c = instanceColor;
+        vGrass = 0.0;
+    }
+
+    vColor = vec4(c, translucent == 1 ? alpha : 1.0);
+    normal_worldSpace   = vec4(normalize(inverseTransposeModel * n), 0);
+    position_worldSpace = vec4(p, 1.0);
+    position_lightSpace = lightSpace * model * vec4(p, 1.0);
 
-    gl_Position = proj * view * model * vec4(position, 1.0);
+    gl_Position = proj * view * model * vec4(p, 1.0);
 }
@@ -0,0 +1,77 @@
+// terrain of the wgpu renderer, lit and colored like shader.vert and shader.frag
+
+struct Uniforms {
+    viewProj: mat4x4<f32>,
+    // height between contour lines, none are drawn if 0
+    contourInterval: f32,
+    // 1 in the dark of night
+    night: u32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec3<f32>,
+    // grass coverage of the terrain vertex
+    @location(3) grass: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clipPosition: vec4<f32>,
+    @location(0) positionWorldSpace: vec3<f32>,
+    @location(1) normalWorldSpace: vec3<f32>,
+    @location(2) color: vec3<f32>,
+    @location(3) grass: f32,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clipPosition = uniforms.viewProj * vec4<f32>(in.position, 1.0);
+    out.positionWorldSpace = in.position;
+    out.normalWorldSpace = normalize(in.normal);
+    out.color = in.color;
+    out.grass = in.grass;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let lightPos = vec3<f32>(30.0, 0.0, 200.0);
+    let lightColor = vec3<f32>(1.5, 1.5, 1.5);
+    let lightDir = normalize(in.positionWorldSpace - lightPos);
+    let c = clamp(dot(-in.normalWorldSpace, lightDir), 0.0, 1.0);
+    let k = 0.2;
+    var color = in.color * (1.0 - k) + c * lightColor * k;
+
+    // speckle grass blades over the ground, as many as the coverage of the grass
+    if (in.grass > 0.0) {
+        let blade = floor(in.positionWorldSpace.xy * 12.0);
+        let h = fract(sin(dot(blade, vec2<f32>(12.9898, 78.233))) * 43758.5453);
+        if (h < in.grass) {
+            let bladeColor = vec3<f32>(0.30, 0.55, 0.18) * (0.7 + 0.6 * fract(h * 17.0));
+            color = mix(color, bladeColor, 0.6);
+        }
+    }
+
+    // darken the whole terrain at night
+    if (uniforms.night == 1u) {
+        color = color * 0.45;
+    }
+
+    // darken a line about a pixel wide wherever the height crosses a multiple of the interval
+    if (uniforms.contourInterval > 0.0) {
+        let level = in.positionWorldSpace.z / uniforms.contourInterval;
+        let distance = abs(fract(level - 0.5) - 0.5) / fwidth(level);
+        color = color * mix(0.35, 1.0, clamp(distance, 0.0, 1.0));
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+// grid lines of the wireframe
+@fragment
+fn fs_wire(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+}
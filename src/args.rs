@@ -0,0 +1,457 @@
+// the options of the viewer, wgpu viewer, and batch binaries, parsed with clap
+// they share one set of options so the same command line runs in any of them, and each binary
+// reads the ones it needs
+
+use std::{fs::File, path::Path};
+
+use clap::{error::ErrorKind, Command, CommandFactory, Parser};
+
+use crate::{
+    config::{BoundaryMode, Config, Initializer, TimeResolution},
+    export::TimeLapse,
+    render_data::{ColorMode, Hillshade},
+    script::Script,
+    simulation::Simulation,
+};
+
+#[derive(Parser, Debug)]
+#[command(about = "simulates the geology and ecology of a terrain over the years")]
+pub struct Args {
+    #[arg(long, value_name = "PATH", help = "load a scenario config file")]
+    pub config: Option<String>,
+    #[arg(long, value_name = "PATH", help = "resume from a checkpoint")]
+    pub load: Option<String>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "apply the interventions listed in a toml file at their time steps"
+    )]
+    pub script: Option<String>,
+    #[arg(long, value_name = "N", help = "seed of the run")]
+    pub seed: Option<u64>,
+    #[arg(
+        long,
+        num_args = 1..=2,
+        value_names = ["NAME", "PATH"],
+        help = "initializer, e.g. dunes, piles, standard, or heightmap <path>"
+    )]
+    pub scenario: Vec<String>,
+    #[arg(long, value_name = "N", help = "number of time steps to run")]
+    pub steps: Option<u32>,
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "length of a time step, year, month, or day"
+    )]
+    pub time_resolution: Option<TimeResolution>,
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "edges of the map, torus, open, or reflective"
+    )]
+    pub boundary: Option<BoundaryMode>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "export maps and checkpoints every n time steps, 0 to disable"
+    )]
+    pub export_every: Option<u32>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "directory for exports, a timestamped one by default"
+    )]
+    pub output_dir: Option<String>,
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "standard, hypsometric, sunlight, soil-moisture, wind, bedrock, fire, fire-danger, \
+                vegetation, temperature, microclimate, hillshade, shadows, provenance, runoff, or biome"
+    )]
+    pub color_mode: Option<ColorMode>,
+    #[arg(
+        long,
+        value_name = "AZIMUTH,ELEVATION",
+        value_parser = parse_light,
+        help = "degrees the hillshade light comes from, 315,45 by default"
+    )]
+    pub hillshade: Option<(f32, f32)>,
+    #[arg(
+        long,
+        value_name = "M",
+        help = "height between the contour lines drawn over the hillshade, none by default"
+    )]
+    pub contour_interval: Option<f32>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "number of time steps the viewer can undo with backspace, 20 by default"
+    )]
+    pub history: Option<usize>,
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = parse_steps_per_second,
+        help = "time steps the viewer takes per second while running, 10 by default"
+    )]
+    pub steps_per_second: Option<f32>,
+    #[arg(
+        long,
+        value_name = "WIDTHxHEIGHT",
+        value_parser = parse_size,
+        help = "size of screenshots taken with F12, 3840x2160 by default"
+    )]
+    pub screenshot_size: Option<(u32, u32)>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "append per time step event counts to a csv"
+    )]
+    pub event_log: Option<String>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "append per time step biomass, soil, and plant totals to a csv"
+    )]
+    pub metrics: Option<String>,
+    #[arg(
+        long,
+        num_args = 1..=2,
+        value_names = ["PATH", "N"],
+        help = "record an animated gif of the maps every n time steps, 1 by default"
+    )]
+    pub timelapse: Vec<String>,
+    #[arg(long, help = "panic when an event creates or destroys material")]
+    pub mass_audit: bool,
+    #[arg(
+        long,
+        help = "tag sand and humus with the region of the map they came from"
+    )]
+    pub provenance: bool,
+}
+
+fn parse_value<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {value:?}"))
+}
+
+// returns the azimuth and elevation of a light like 315,45
+fn parse_light(value: &str) -> Result<(f32, f32), String> {
+    let (azimuth, elevation) = value
+        .split_once(',')
+        .ok_or_else(|| format!("invalid value {value:?}, expected <azimuth>,<elevation>"))?;
+    Ok((parse_value(azimuth)?, parse_value(elevation)?))
+}
+
+// returns the width and height of a size like 1920x1080
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("invalid value {value:?}, expected <width>x<height>"))?;
+    Ok((parse_value(width)?, parse_value(height)?))
+}
+
+// the time between steps is one over this, so it has to be a positive number
+fn parse_steps_per_second(value: &str) -> Result<f32, String> {
+    let steps_per_second: f32 = parse_value(value)?;
+    if !steps_per_second.is_finite() || steps_per_second <= 0.0 {
+        return Err(format!("{value} is not a positive number"));
+    }
+    Ok(steps_per_second)
+}
+
+// returns the options, or prints the error and the usage and exits if they are invalid
+pub fn or_exit_with_usage<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|error| exit_with_usage(Args::command(), error))
+}
+
+// prints the error and the usage of the command and exits, as the parser does for bad options
+pub fn exit_with_usage(mut command: Command, error: String) -> ! {
+    // named after the binary like the errors of the parser
+    if let Some(name) = std::env::args()
+        .next()
+        .and_then(|path| Some(Path::new(&path).file_name()?.to_str()?.to_string()))
+    {
+        command = command.bin_name(name);
+    }
+    command.error(ErrorKind::ValueValidation, error).exit()
+}
+
+// returns the initializer given by `--scenario` and, for height maps, the path following it
+pub fn parse_scenario(args: &Args) -> Result<Option<(Initializer, Option<String>)>, String> {
+    let Some(name) = args.scenario.first() else {
+        return Ok(None);
+    };
+    let initializer: Initializer = name.parse()?;
+    let path = args.scenario.get(1).cloned();
+    if path.is_some() && initializer != Initializer::HeightMap {
+        return Err(format!("only heightmap scenarios take a path, not {name}"));
+    }
+    Ok(Some((initializer, path)))
+}
+
+// returns the color mode given by `--color-mode`, lit as given by `get_hillshade` for hillshade
+pub fn get_color_mode(args: &Args) -> Option<ColorMode> {
+    args.color_mode.map(|color_mode| match color_mode {
+        ColorMode::Hillshade(_) => ColorMode::Hillshade(get_hillshade(args)),
+        color_mode => color_mode,
+    })
+}
+
+// returns the light given by `--hillshade`, e.g. 315,45, and the contour lines given by `--contour-interval`
+pub fn get_hillshade(args: &Args) -> Hillshade {
+    let mut hillshade = Hillshade::default();
+    if let Some((azimuth, elevation)) = args.hillshade {
+        hillshade.azimuth = azimuth;
+        hillshade.elevation = elevation;
+    }
+    if let Some(contour_interval) = args.contour_interval {
+        hillshade.contour_interval = contour_interval;
+    }
+    hillshade
+}
+
+// returns the directory given by `--output-dir`, creating it if needed
+pub fn get_output_dir(path: Option<&str>) -> Result<Option<String>, String> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    std::fs::create_dir_all(path).map_err(|error| format!("cannot create {path}: {error}"))?;
+    Ok(Some(path.to_string()))
+}
+
+// returns the gif path given by `--timelapse` and the time steps between frames following it
+pub fn parse_time_lapse(args: &Args) -> Result<Option<(&str, u32)>, String> {
+    let Some(path) = args.timelapse.first() else {
+        return Ok(None);
+    };
+    let every = match args.timelapse.get(1) {
+        Some(every) => parse_value(every)?,
+        None => 1,
+    };
+    Ok(Some((path, every)))
+}
+
+// builds the simulation described by `--config`, `--scenario`, `--load`, `--seed`, `--time-resolution`,
+// `--boundary`, `--script`, `--event-log`, `--metrics`, `--timelapse`, `--mass-audit`,
+// and `--provenance`
+pub fn init_simulation(args: &Args) -> Result<Simulation, String> {
+    // every option is checked before the slow work of building the simulation
+    let scenario = parse_scenario(args)?;
+    let time_lapse = parse_time_lapse(args)?;
+    // so are the files they name
+    let script = args.script.as_deref().map(Script::load).transpose()?;
+    let config = match (&args.load, &args.config) {
+        (None, Some(path)) => Some(Config::load(path)?),
+        (None, None) => Some(Config::default()),
+        (Some(_), _) => None,
+    };
+
+    let mut simulation = match config {
+        None => Simulation::init_from_checkpoint(args.load.as_ref().unwrap())?,
+        Some(mut config) => {
+            if args.seed.is_some() {
+                config.seed = args.seed;
+            }
+            // the scenario on the command line overrides the one in the config
+            if let Some((initializer, path)) = scenario {
+                config.initializer = initializer;
+                if let Some(path) = path {
+                    config.height_map_path = path;
+                }
+            }
+            if config.initializer == Initializer::HeightMap {
+                File::open(&config.height_map_path).map_err(|error| {
+                    format!("cannot read height map {}: {error}", config.height_map_path)
+                })?;
+            }
+            Simulation::init_with_config(&config)?
+        }
+    };
+    // a seed given on the command line also overrides the one saved in a checkpoint
+    if let Some(seed) = args.seed {
+        simulation.ecosystem.set_seed(seed);
+    }
+    if let Some(time_resolution) = args.time_resolution {
+        simulation.ecosystem.config.time_resolution = time_resolution;
+    }
+    if let Some(boundary) = args.boundary {
+        simulation.ecosystem.config.boundary = boundary;
+    }
+    simulation.script = script;
+    simulation.event_log_path = args.event_log.clone();
+    simulation.metrics_path = args.metrics.clone();
+    if let Some((path, every)) = time_lapse {
+        simulation.time_lapse = Some(TimeLapse::create(path, every)?);
+    }
+    if args.mass_audit {
+        simulation = simulation.with_mass_audit();
+    }
+    // a loaded checkpoint keeps the provenance it was saved with
+    if args.provenance && simulation.ecosystem.provenance.is_none() {
+        simulation.ecosystem.enable_provenance();
+    }
+    Ok(simulation)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::{
+        get_color_mode, get_hillshade, init_simulation, parse_scenario, parse_time_lapse, Args,
+    };
+    use crate::{
+        config::Initializer,
+        render_data::{ColorMode, Hillshade},
+    };
+
+    fn parse(args: &[&str]) -> Args {
+        Args::try_parse_from(args).unwrap()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse(&["batch", "--steps", "20"]);
+        assert_eq!(args.steps, Some(20));
+        assert_eq!(args.load, None);
+        // invalid and missing values are errors
+        assert!(Args::try_parse_from(["batch", "--seed", "x"]).is_err());
+        assert!(Args::try_parse_from(["batch", "--config"]).is_err());
+        assert!(Args::try_parse_from(["batch", "--history", "--steps", "20"]).is_err());
+        // and so are misspelled options
+        assert!(Args::try_parse_from(["batch", "--step", "100"]).is_err());
+        assert!(Args::try_parse_from(["batch", "100"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_steps_per_second() {
+        let args = parse(&["viewer", "--steps-per-second", "2.5"]);
+        assert_eq!(args.steps_per_second, Some(2.5));
+        for value in ["0", "-1", "NaN", "inf"] {
+            let args = ["viewer", "--steps-per-second", value];
+            assert!(Args::try_parse_from(args).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_parse_scenario() {
+        let args = parse(&[
+            "viewer",
+            "--scenario",
+            "heightmap",
+            "terrain.hgt",
+            "--steps",
+            "5",
+        ]);
+        assert_eq!(
+            parse_scenario(&args),
+            Ok(Some((
+                Initializer::HeightMap,
+                Some("terrain.hgt".to_string())
+            )))
+        );
+        assert_eq!(args.steps, Some(5));
+        // only height maps take a path
+        let args = parse(&["viewer", "--scenario", "dunes", "terrain.hgt"]);
+        assert!(parse_scenario(&args).is_err());
+        let args = parse(&["viewer", "--scenario", "height_map", "--steps", "5"]);
+        assert_eq!(
+            parse_scenario(&args),
+            Ok(Some((Initializer::HeightMap, None)))
+        );
+        assert_eq!(parse_scenario(&parse(&["viewer"])), Ok(None));
+        let args = parse(&["viewer", "--scenario", "moon"]);
+        assert!(parse_scenario(&args).is_err());
+    }
+
+    #[test]
+    fn test_parse_color_mode() {
+        let color_mode = |name: &str| get_color_mode(&parse(&["viewer", "--color-mode", name]));
+        assert_eq!(
+            color_mode("soil-moisture"),
+            Some(ColorMode::SoilMoisture(0))
+        );
+        assert_eq!(color_mode("temperature"), Some(ColorMode::Temperature(0)));
+        assert_eq!(color_mode("fire-danger"), Some(ColorMode::FireDanger));
+        assert_eq!(color_mode("microclimate"), Some(ColorMode::Microclimate(0)));
+        assert_eq!(color_mode("shadows"), Some(ColorMode::Shadows(0, 12)));
+        assert_eq!(get_color_mode(&parse(&["viewer"])), None);
+        assert!(Args::try_parse_from(["viewer", "--color-mode", "plaid"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_hillshade() {
+        let args = parse(&[
+            "viewer",
+            "--color-mode",
+            "hillshade",
+            "--hillshade",
+            "270,30",
+            "--contour-interval",
+            "5",
+        ]);
+        let expected = Hillshade {
+            azimuth: 270.0,
+            elevation: 30.0,
+            contour_interval: 5.0,
+        };
+        assert_eq!(get_hillshade(&args), expected);
+        assert_eq!(get_color_mode(&args), Some(ColorMode::Hillshade(expected)));
+        // lit from the northwest without contour lines by default
+        let args = parse(&["viewer", "--color-mode", "hillshade"]);
+        assert_eq!(
+            get_color_mode(&args),
+            Some(ColorMode::Hillshade(Hillshade::default()))
+        );
+        assert!(Args::try_parse_from(["viewer", "--hillshade", "270"]).is_err());
+        assert!(Args::try_parse_from(["viewer", "--hillshade", "270,high"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_time_lapse() {
+        let args = parse(&["batch", "--timelapse", "run.gif", "10"]);
+        assert_eq!(parse_time_lapse(&args), Ok(Some(("run.gif", 10))));
+        // every time step unless a number follows the path
+        let args = parse(&["batch", "--timelapse", "run.gif", "--steps", "5"]);
+        assert_eq!(parse_time_lapse(&args), Ok(Some(("run.gif", 1))));
+        assert!(Args::try_parse_from(["batch", "--timelapse"]).is_err());
+        let args = parse(&["batch", "--timelapse", "run.gif", "often"]);
+        assert!(parse_time_lapse(&args).is_err());
+    }
+
+    #[test]
+    fn test_parse_screenshot_size() {
+        let args = parse(&["viewer", "--screenshot-size", "1920x1080"]);
+        assert_eq!(args.screenshot_size, Some((1920, 1080)));
+        assert!(Args::try_parse_from(["viewer", "--screenshot-size", "1920"]).is_err());
+        assert_eq!(parse(&["viewer"]).screenshot_size, None);
+    }
+
+    #[test]
+    fn test_init_simulation_errors() {
+        // files that cannot be read are errors instead of panics, before any terrain is built
+        let missing = std::env::temp_dir().join("test_init_simulation_errors.missing");
+        let missing = missing.to_str().unwrap();
+        for flag in ["--config", "--load", "--script"] {
+            let args = parse(&["batch", flag, missing]);
+            assert!(init_simulation(&args).is_err(), "{flag}");
+        }
+        let args = parse(&["batch", "--scenario", "heightmap", missing]);
+        assert!(init_simulation(&args).is_err());
+
+        // so are DEM tiles that can be read but not understood
+        let renamed = std::env::temp_dir().join("test_init_simulation_errors.hgt");
+        std::fs::write(&renamed, [0; 8]).unwrap();
+        let args = parse(&[
+            "batch",
+            "--scenario",
+            "heightmap",
+            renamed.to_str().unwrap(),
+        ]);
+        let result = init_simulation(&args);
+        std::fs::remove_file(&renamed).unwrap();
+        assert!(result.is_err());
+    }
+}
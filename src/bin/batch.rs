@@ -0,0 +1,17 @@
+use clap::Parser;
+use vegetables_and_hummus::{
+    args::{get_output_dir, init_simulation, or_exit_with_usage, Args},
+    export::create_export_directory,
+};
+
+// runs the simulation without creating a window or OpenGL context
+fn main() {
+    let args = Args::parse();
+    let steps = args.steps.unwrap_or(100);
+    let export_every = args.export_every.unwrap_or(10);
+    let path = or_exit_with_usage(get_output_dir(args.output_dir.as_deref()));
+
+    let mut simulation = or_exit_with_usage(init_simulation(&args));
+    let path = path.unwrap_or_else(create_export_directory);
+    simulation.run_headless(steps, export_every, &path);
+}
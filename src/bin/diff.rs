@@ -0,0 +1,41 @@
+use clap::{CommandFactory, Parser};
+use vegetables_and_hummus::{
+    args::{exit_with_usage, get_output_dir},
+    ecology::Ecosystem,
+    export::export_diff_maps,
+};
+
+// the two checkpoints to compare and where to write the heat maps of their changes
+#[derive(Parser)]
+#[command(about = "prints the change of every layer between two checkpoints of the same terrain")]
+struct DiffArgs {
+    #[arg(help = "checkpoint of the terrain before")]
+    before: String,
+    #[arg(help = "checkpoint of the terrain after")]
+    after: String,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "directory to write the changes to as heat maps"
+    )]
+    output_dir: Option<String>,
+}
+
+// returns the result, or prints the error and the usage and exits
+fn or_exit_with_usage<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|error| exit_with_usage(DiffArgs::command(), error))
+}
+
+// compares two checkpoints, e.g. to see what a century of simulation changed
+fn main() {
+    let args = DiffArgs::parse();
+    let path = or_exit_with_usage(get_output_dir(args.output_dir.as_deref()));
+    let [before, after] =
+        [&args.before, &args.after].map(|path| or_exit_with_usage(Ecosystem::load(path)));
+
+    let diff = before.diff(&after);
+    print!("{}", diff.summarize());
+    if let Some(path) = path {
+        export_diff_maps(&diff, &path);
+    }
+}
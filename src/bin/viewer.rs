@@ -0,0 +1,559 @@
+use clap::Parser;
+use nalgebra::Vector3;
+use sdl2::{
+    keyboard::{Keycode, Mod},
+    mouse::MouseButton,
+    sys::{SDL_GetPerformanceCounter, SDL_GetPerformanceFrequency},
+};
+use std::{collections::HashSet, ffi::CString};
+use vegetables_and_hummus::{
+    args::{
+        get_color_mode, get_hillshade, get_output_dir, init_simulation, or_exit_with_usage, Args,
+    },
+    brush::Brush,
+    constants,
+    events::DEBUG_EVENTS,
+    export::{
+        create_export_directory, export_checkpoint, export_maps, export_scene_bundle,
+        export_screenshot,
+    },
+    hud::Hud,
+    pacing::Pacing,
+    render::{sunlight_gpu::GpuSunlight, EcosystemRenderable},
+    render_backend::RenderBackend,
+    render_data::{ColorMode, RenderMode},
+    render_gl,
+    worker::{SimulationWorker, WorkerCommand},
+    Ecosystem,
+};
+
+#[derive(PartialEq, Eq, Hash)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+    Forward,
+    Back,
+}
+
+fn main() {
+    // parse command line options
+    let args = Args::parse();
+    // runs without pausing until this time step if given
+    let steps = args.steps;
+    let export_every = args.export_every.unwrap_or(0);
+    let (screenshot_width, screenshot_height) = args
+        .screenshot_size
+        .unwrap_or((constants::SCREENSHOT_WIDTH, constants::SCREENSHOT_HEIGHT));
+    let history = args.history.unwrap_or(constants::HISTORY_LENGTH);
+    let initial_color_mode = get_color_mode(&args).unwrap_or(ColorMode::Standard);
+    let initial_path =
+        or_exit_with_usage(get_output_dir(args.output_dir.as_deref())).unwrap_or_default();
+    let steps_per_second = args
+        .steps_per_second
+        .unwrap_or(constants::DEFAULT_STEPS_PER_SECOND);
+    // light of the hillshade color mode when switching to it
+    let hillshade = get_hillshade(&args);
+
+    // https://nercury.github.io/rust/opengl/tutorial/2018/02/08/opengl-in-rust-from-scratch-00-setup.html
+    let sdl = sdl2::init().unwrap();
+    let video_subsystem = sdl.video().unwrap();
+
+    let gl_attr = video_subsystem.gl_attr();
+
+    gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
+    gl_attr.set_context_version(4, 1);
+
+    let mut window = video_subsystem
+        .window(
+            "Hummus",
+            constants::SCREEN_WIDTH as u32,
+            constants::SCREEN_HEIGHT as u32,
+        )
+        .opengl()
+        .resizable()
+        .build()
+        .unwrap();
+
+    let _gl_context = window.gl_create_context().unwrap();
+    gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as *const std::os::raw::c_void);
+    // wait for the display between frames instead of drawing as fast as possible
+    if let Err(error) = video_subsystem.gl_set_swap_interval(sdl2::video::SwapInterval::VSync) {
+        println!("{error}, drawing without vsync");
+    }
+
+    // set up shared state for window
+    unsafe {
+        gl::Viewport(
+            0,
+            0,
+            constants::SCREEN_WIDTH as i32,
+            constants::SCREEN_HEIGHT as i32,
+        );
+        gl::ClearColor(1.0, 1.0, 1.0, 1.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        gl::Enable(gl::DEPTH_TEST);
+    }
+
+    let vert_shader = render_gl::Shader::from_vert_source(
+        &CString::new(include_str!("../../resources/shaders/shader.vert")).unwrap(),
+    )
+    .unwrap();
+    let frag_shader = render_gl::Shader::from_frag_source(
+        &CString::new(include_str!("../../resources/shaders/shader.frag")).unwrap(),
+    )
+    .unwrap();
+    let shader_program = render_gl::Program::from_shaders(&[vert_shader, frag_shader]).unwrap();
+
+    // Set up simulation and tracking variables
+    // let mut simulation = Simulation::init();
+    let mut simulation = or_exit_with_usage(init_simulation(&args));
+    // ray traces sunlight on the gpu on this thread if the context has compute shaders,
+    // otherwise the worker ray traces it on the cpu over the frames after terrain changes
+    let sunlight_gpu = match GpuSunlight::init() {
+        Ok(sunlight_gpu) => {
+            simulation.enable_gpu_sunlight();
+            Some(sunlight_gpu)
+        }
+        Err(error) => {
+            println!("{error}, ray tracing sunlight on the cpu");
+            simulation.enable_progressive_sunlight();
+            None
+        }
+    };
+    simulation.enable_history(history);
+    let mut hud = Hud::init(&window);
+
+    let mut render_mode = RenderMode::Solid;
+    let mut color_mode = initial_color_mode;
+    // month shown by the color modes that change with the seasons, kept when switching between them
+    let mut display_month = color_mode.get_month().unwrap_or(0);
+    let mut path = initial_path;
+    let mut paused = steps.is_none();
+    let mut pacing = Pacing::new(steps_per_second);
+    // this thread only draws while the simulation runs on the worker thread,
+    // so long time steps and sunlight updates do not freeze the window
+    let mut renderable = EcosystemRenderable::init(&simulation.ecosystem);
+    renderable.update_vertices(&simulation.ecosystem, &color_mode);
+    let mut worker = SimulationWorker::spawn(simulation, color_mode, pacing.steps_per_second);
+    if !paused {
+        worker.send(WorkerCommand::Run(steps));
+    }
+    let mut prev_keys = HashSet::new();
+    // whether the mouse moved since the left button was pressed, to tell clicks from drags
+    let mut dragged = false;
+    // edits the terrain while alt is held
+    let mut brush = Brush::default();
+    // index of the event forced on the cell under the cursor
+    let mut debug_event = 0;
+    // arrows of the local wind drawn over the terrain
+    let mut wind_arrows = false;
+    let mut ripples = false;
+    let mut loop_end = unsafe { SDL_GetPerformanceCounter() };
+    let mut event_pump = sdl.event_pump().unwrap();
+    'main: loop {
+        for event in event_pump.poll_iter() {
+            hud.process_event(&window, event.clone());
+            match event {
+                sdl2::event::Event::Quit { .. } => break 'main,
+                sdl2::event::Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } => {
+                    dragged = false;
+                    if is_alt_held(&sdl) && !hud.wants_pointer() {
+                        // brushing is not a click on a cell
+                        dragged = true;
+                        if let Some(index) = renderable.pick_cell(x, y) {
+                            worker.send(WorkerCommand::ApplyBrush(brush, index));
+                        }
+                    }
+                }
+                sdl2::event::Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } if !hud.wants_pointer() && !dragged => {
+                    // print everything stored in the clicked cell
+                    if let Some(index) = renderable.pick_cell(x, y) {
+                        println!("{}", worker.ecosystem.describe_cell(index));
+                    }
+                }
+                sdl2::event::Event::MouseMotion {
+                    mousestate, x, y, ..
+                } if !hud.wants_pointer() && mousestate.left() && is_alt_held(&sdl) => {
+                    if let Some(index) = renderable.pick_cell(x, y) {
+                        worker.send(WorkerCommand::ApplyBrush(brush, index));
+                    }
+                }
+                sdl2::event::Event::MouseMotion {
+                    mousestate,
+                    xrel,
+                    yrel,
+                    ..
+                } if !hud.wants_pointer() => {
+                    let delta_yaw = -xrel as f32 * constants::MOUSE_SENSITIVITY;
+                    let delta_pitch = -yrel as f32 * constants::MOUSE_SENSITIVITY;
+                    // left drag orbits around the terrain, right drag looks around
+                    if mousestate.left() {
+                        dragged = true;
+                        renderable.m_camera.orbit_camera(delta_yaw, delta_pitch);
+                    } else if mousestate.right() {
+                        renderable.m_camera.rotate_camera(delta_yaw, delta_pitch);
+                    }
+                }
+                sdl2::event::Event::MouseWheel { y, .. } if !hud.wants_pointer() => {
+                    renderable
+                        .m_camera
+                        .zoom_camera(y as f32 * constants::ZOOM_SPEED);
+                }
+                _ => {}
+            }
+        }
+
+        // upload what the worker changed since the last frame
+        let mut sunlight_stale = false;
+        let updated = worker.poll(|update| {
+            renderable.upload_vertex_data(&update.vertex_data);
+            sunlight_stale |= update.sunlight_stale;
+            if update.stepped {
+                export_if_due(&update.ecosystem, export_every, &mut path);
+            }
+            if update.stopped {
+                paused = true;
+            }
+        });
+        if updated && wind_arrows {
+            renderable.update_wind_arrows(&worker.ecosystem);
+        }
+        if updated && ripples {
+            renderable.update_ripple_map(&worker.ecosystem);
+        }
+        // only the heights are needed, so the copy of the ecosystem is enough to trace sunlight on
+        if sunlight_stale {
+            if let Some(sunlight_gpu) = &sunlight_gpu {
+                let cell_hours = sunlight_gpu.compute_hours_of_sunlight(&worker.ecosystem);
+                worker.send(WorkerCommand::SetSunlight(cell_hours));
+            }
+        }
+
+        // draw
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        shader_program.set_used();
+        renderable.draw_frame(
+            &shader_program.id(),
+            &worker.ecosystem,
+            &color_mode,
+            &render_mode,
+        );
+        let hud_actions = hud.draw(
+            &window,
+            &worker.ecosystem,
+            &color_mode,
+            paused,
+            DEBUG_EVENTS[debug_event],
+            &renderable.m_camera,
+        );
+
+        unsafe {
+            let mut err: gl::types::GLenum = gl::GetError();
+            while err != gl::NO_ERROR {
+                // Process/log the error.
+                println!("loop error {err}");
+                err = gl::GetError();
+            }
+        }
+
+        // handle ticks
+        let elapsed_secs;
+        unsafe {
+            let loop_start = SDL_GetPerformanceCounter();
+            elapsed_secs = (loop_start - loop_end) as f64 / SDL_GetPerformanceFrequency() as f64;
+            loop_end = loop_start;
+        }
+
+        // Handle key input
+        // Create a set of pressed Keys.
+        let keys: HashSet<Keycode> = event_pump
+            .keyboard_state()
+            .pressed_scancodes()
+            .filter_map(Keycode::from_scancode)
+            .collect();
+
+        // Get the difference between the new and old sets.
+        let new_keys = &keys - &prev_keys;
+        prev_keys = keys.clone();
+        if new_keys.contains(&Keycode::Space) || hud_actions.step {
+            // take one time step
+            worker.send(WorkerCommand::Step);
+        } else if new_keys.contains(&Keycode::Backspace) {
+            // undo the last time step and stop so it can be inspected
+            paused = true;
+            worker.send(WorkerCommand::Pause);
+            worker.send(WorkerCommand::StepBack);
+        } else if new_keys.contains(&Keycode::T) || hud_actions.toggle_pause {
+            // continuously take time steps
+            paused = !paused;
+            worker.send(if paused {
+                WorkerCommand::Pause
+            } else {
+                WorkerCommand::Run(steps)
+            });
+        } else if new_keys.contains(&Keycode::P) || hud_actions.export {
+            // export current data
+            if path.is_empty() {
+                path = create_export_directory();
+            }
+            export_maps(&worker.ecosystem, worker.ecosystem.time_step, &path);
+        } else if new_keys.contains(&Keycode::N) {
+            // export everything the blender import needs in one directory
+            if path.is_empty() {
+                path = create_export_directory();
+            }
+            export_scene_bundle(&worker.ecosystem, worker.ecosystem.time_step, &path);
+        } else if new_keys.contains(&Keycode::C) {
+            // save checkpoint of the current state
+            if path.is_empty() {
+                path = create_export_directory();
+            }
+            export_checkpoint(&worker.ecosystem, worker.ecosystem.time_step, &path);
+        } else if new_keys.contains(&Keycode::F12) {
+            // render the current view at screenshot size and save it
+            if path.is_empty() {
+                path = create_export_directory();
+            }
+            match renderable.render_offscreen(
+                shader_program.id(),
+                &render_mode,
+                screenshot_width,
+                screenshot_height,
+            ) {
+                Ok(pixels) => export_screenshot(
+                    &pixels,
+                    screenshot_width,
+                    screenshot_height,
+                    worker.ecosystem.time_step,
+                    &path,
+                ),
+                Err(error) => println!("{error}"),
+            }
+        } else if new_keys.contains(&Keycode::F) {
+            // orbit around the center of the terrain again
+            renderable.refocus_camera(&worker.ecosystem);
+        } else if new_keys.contains(&Keycode::B) {
+            // cycle between the tools of the brush used with alt and the left mouse button
+            brush.tool = brush.tool.next();
+            println!("Brush tool {:?}", brush.tool);
+        } else if new_keys.contains(&Keycode::Minus) || new_keys.contains(&Keycode::Equals) {
+            // shrink or grow the brush
+            let step = if new_keys.contains(&Keycode::Minus) {
+                -1.0
+            } else {
+                1.0
+            };
+            brush.radius = f32::max(brush.radius + step, 0.0);
+            println!("Brush radius {}", brush.radius);
+        } else if new_keys.contains(&Keycode::G) || hud_actions.next_debug_event {
+            // cycle between the events forced with E
+            debug_event = (debug_event + 1) % DEBUG_EVENTS.len();
+            println!("Debug event {:?}", DEBUG_EVENTS[debug_event]);
+        } else if new_keys.contains(&Keycode::E) {
+            // apply the debug event at the cell under the cursor
+            let mouse = event_pump.mouse_state();
+            if let Some(index) = renderable.pick_cell(mouse.x(), mouse.y()) {
+                let event = DEBUG_EVENTS[debug_event];
+                println!("Forcing {event:?} at {index}");
+                worker.send(WorkerCommand::ForceEvent(event, index));
+            }
+        } else if new_keys.contains(&Keycode::Period) || new_keys.contains(&Keycode::KpPlus) {
+            // take time steps faster while running
+            pacing.speed_up();
+            worker.send(WorkerCommand::SetStepsPerSecond(pacing.steps_per_second));
+            println!("Steps per second {}", pacing.steps_per_second);
+        } else if new_keys.contains(&Keycode::Comma) || new_keys.contains(&Keycode::KpMinus) {
+            // take time steps slower while running
+            pacing.slow_down();
+            worker.send(WorkerCommand::SetStepsPerSecond(pacing.steps_per_second));
+            println!("Steps per second {}", pacing.steps_per_second);
+        } else if new_keys.contains(&Keycode::V) {
+            // show or hide the wind vectors
+            wind_arrows = !wind_arrows;
+            if wind_arrows {
+                renderable.update_wind_arrows(&worker.ecosystem);
+            } else {
+                renderable.hide_wind_arrows();
+            }
+        } else if new_keys.contains(&Keycode::I) {
+            // show or hide the sand ripples
+            ripples = !ripples;
+            if ripples {
+                renderable.update_ripple_map(&worker.ecosystem);
+            } else {
+                renderable.hide_ripples();
+            }
+        } else if new_keys.contains(&Keycode::L) {
+            // cycle between solid, wireframe over solid, and wireframe only
+            render_mode = render_mode.next();
+        } else if new_keys.contains(&Keycode::Num1) {
+            // change color mode
+            color_mode = ColorMode::Standard;
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::Num2) {
+            // change color mode
+            color_mode = ColorMode::HypsometricTint;
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::Num3) {
+            // change color mode
+            color_mode = ColorMode::Sunlight(display_month);
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::Num4) {
+            // change color mode
+            color_mode = ColorMode::SoilMoisture(display_month);
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::Num5) {
+            // change color mode
+            color_mode = ColorMode::WindField;
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::Num6) {
+            // change color mode
+            color_mode = ColorMode::OnlyBedrock;
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::Num7) {
+            // change color mode
+            color_mode = ColorMode::Fire;
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::K) {
+            // change color mode, k for keetch-byram
+            color_mode = ColorMode::FireDanger;
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::H) {
+            // change color mode, lit as given on the command line
+            color_mode = ColorMode::Hillshade(hillshade);
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::O) {
+            // change color mode, o for origin of the sediment
+            color_mode = ColorMode::Provenance;
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::R) {
+            // change color mode, r for runoff
+            color_mode = ColorMode::Runoff;
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::U) {
+            // change color mode, starting from noon
+            color_mode = ColorMode::Shadows(display_month, 12);
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::M) {
+            // change color mode, m for the map of biomes
+            color_mode = ColorMode::Biome;
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::Num8) {
+            // change color mode
+            color_mode = ColorMode::VegetationDensity;
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::Num9) {
+            // change color mode
+            color_mode = ColorMode::Temperature(display_month);
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if new_keys.contains(&Keycode::Num0) {
+            // change color mode
+            color_mode = ColorMode::Microclimate(display_month);
+            worker.send(WorkerCommand::ChangeColorMode(color_mode));
+        } else if let Some(month) = color_mode.get_month() {
+            // step the month shown by the color modes that change with the seasons
+            if new_keys.contains(&Keycode::LeftBracket) {
+                display_month = (month + 11) % 12;
+                color_mode = color_mode.with_month(display_month);
+                worker.send(WorkerCommand::ChangeColorMode(color_mode));
+            } else if new_keys.contains(&Keycode::RightBracket) {
+                display_month = (month + 1) % 12;
+                color_mode = color_mode.with_month(display_month);
+                worker.send(WorkerCommand::ChangeColorMode(color_mode));
+            } else if let ColorMode::Shadows(month, hour) = color_mode {
+                // cycle the hour of the sun casting the shadows
+                // which does not change the sunlight the terrain is colored by
+                if new_keys.contains(&Keycode::Semicolon) {
+                    color_mode = ColorMode::Shadows(month, (hour + 23) % 24);
+                } else if new_keys.contains(&Keycode::Quote) {
+                    color_mode = ColorMode::Shadows(month, (hour + 1) % 24);
+                }
+            }
+        }
+        let title = get_window_title(&color_mode);
+        if window.title() != title {
+            window.set_title(&title).unwrap();
+        }
+        let dirs = keys.into_iter().filter_map(convert_key_to_dir).collect();
+        move_camera(&mut renderable, dirs, elapsed_secs as f32);
+
+        window.gl_swap_window();
+    }
+}
+
+// names the month shown by the color mode, if it changes with the seasons
+fn get_window_title(color_mode: &ColorMode) -> String {
+    match color_mode.get_month() {
+        Some(month) => format!("Hummus - {}", constants::MONTH_NAMES[month]),
+        None => "Hummus".to_string(),
+    }
+}
+
+fn is_alt_held(sdl: &sdl2::Sdl) -> bool {
+    sdl.keyboard()
+        .mod_state()
+        .intersects(Mod::LALTMOD | Mod::RALTMOD)
+}
+
+// exports maps and a checkpoint every `export_every` time steps
+fn export_if_due(ecosystem: &Ecosystem, export_every: u32, path: &mut String) {
+    let time_step = ecosystem.time_step;
+    if export_every > 0 && time_step.is_multiple_of(export_every) {
+        if path.is_empty() {
+            *path = create_export_directory();
+        }
+        export_maps(ecosystem, time_step, path);
+        export_checkpoint(ecosystem, time_step, path);
+    }
+}
+
+fn convert_key_to_dir(key: Keycode) -> Option<Direction> {
+    match key {
+        Keycode::W => Some(Direction::Up),
+        Keycode::S => Some(Direction::Down),
+        Keycode::A => Some(Direction::Left),
+        Keycode::D => Some(Direction::Right),
+        Keycode::LShift => Some(Direction::Forward),
+        Keycode::LCtrl => Some(Direction::Back),
+        _ => None,
+    }
+}
+
+fn move_camera(backend: &mut impl RenderBackend, dirs: HashSet<Direction>, delta_seconds: f32) {
+    let mut m_forward = 0.0;
+    let mut m_sideways = 0.0;
+    let mut m_vertical = 0.0;
+
+    for dir in dirs {
+        match dir {
+            Direction::Up => m_vertical -= constants::SPEED,
+            Direction::Down => m_vertical += constants::SPEED,
+            Direction::Left => m_sideways += constants::SPEED,
+            Direction::Right => m_sideways -= constants::SPEED,
+            Direction::Forward => m_forward += constants::SPEED,
+            Direction::Back => m_forward -= constants::SPEED,
+        }
+    }
+    let mut look = backend.camera().m_look;
+    look.y = 0.0;
+    look = look.normalize();
+    let perp: Vector3<f32> = Vector3::new(-look.z, 0.0, look.x).normalize();
+    let mut move_vec: Vector3<f32> =
+        m_forward * look + m_sideways * perp + m_vertical * Vector3::new(0.0, 1.0, 0.0);
+    move_vec *= delta_seconds;
+    backend.camera().move_camera(move_vec);
+}
@@ -0,0 +1,210 @@
+// viewer drawn with wgpu into a winit window, without the hud, shadows, plants, or water of the
+// OpenGL viewer
+// left drag orbits, right drag looks around, the wheel zooms, and a click prints the cell
+// space takes a time step, t runs or pauses, l cycles the render mode, and escape quits
+
+use std::sync::Arc;
+
+use clap::Parser;
+use vegetables_and_hummus::{
+    args::{get_color_mode, init_simulation, or_exit_with_usage, Args},
+    constants,
+    render_backend::RenderBackend,
+    render_data::{ColorMode, RenderMode},
+    render_wgpu::WgpuRenderable,
+    simulation::Simulation,
+    worker::{SimulationWorker, WorkerCommand},
+};
+use winit::{
+    application::ApplicationHandler,
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent},
+    event_loop::{ActiveEventLoop, EventLoop},
+    keyboard::{Key, NamedKey},
+    window::{Window, WindowId},
+};
+
+struct App {
+    // moved onto the worker once the window is created
+    simulation: Option<Simulation>,
+    steps: Option<u32>,
+    paused: bool,
+    color_mode: ColorMode,
+    render_mode: RenderMode,
+    window: Option<Arc<Window>>,
+    renderable: Option<WgpuRenderable>,
+    worker: Option<SimulationWorker>,
+    cursor: PhysicalPosition<f64>,
+    left_held: bool,
+    right_held: bool,
+    // whether the mouse moved since the left button was pressed, to tell clicks from drags
+    dragged: bool,
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(simulation) = self.simulation.take() else {
+            return;
+        };
+        let attributes = Window::default_attributes()
+            .with_title("Hummus")
+            .with_inner_size(PhysicalSize::new(
+                constants::SCREEN_WIDTH as u32,
+                constants::SCREEN_HEIGHT as u32,
+            ));
+        let window = Arc::new(event_loop.create_window(attributes).unwrap());
+        let mut renderable = match WgpuRenderable::init(
+            window.clone(),
+            event_loop.owned_display_handle(),
+            &simulation.ecosystem,
+        ) {
+            Ok(renderable) => renderable,
+            Err(error) => {
+                println!("{error}");
+                event_loop.exit();
+                return;
+            }
+        };
+        renderable.update_vertices(&simulation.ecosystem, &self.color_mode);
+        let worker = SimulationWorker::spawn(
+            simulation,
+            self.color_mode,
+            constants::DEFAULT_STEPS_PER_SECOND,
+        );
+        if !self.paused {
+            worker.send(WorkerCommand::Run(self.steps));
+        }
+        window.request_redraw();
+        self.window = Some(window);
+        self.renderable = Some(renderable);
+        self.worker = Some(worker);
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        let (Some(window), Some(renderable), Some(worker)) =
+            (&self.window, &mut self.renderable, &mut self.worker)
+        else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => renderable.resize(size.width, size.height),
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                match event.logical_key.as_ref() {
+                    Key::Named(NamedKey::Escape) => event_loop.exit(),
+                    // take one time step
+                    Key::Named(NamedKey::Space) => worker.send(WorkerCommand::Step),
+                    // continuously take time steps
+                    Key::Character("t") => {
+                        self.paused = !self.paused;
+                        worker.send(if self.paused {
+                            WorkerCommand::Pause
+                        } else {
+                            WorkerCommand::Run(self.steps)
+                        });
+                    }
+                    // cycle between solid, wireframe over solid, and wireframe only
+                    Key::Character("l") => self.render_mode = self.render_mode.next(),
+                    _ => {}
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let pressed = state == ElementState::Pressed;
+                match button {
+                    MouseButton::Left => {
+                        if pressed {
+                            self.dragged = false;
+                        } else if !self.dragged {
+                            // print everything stored in the clicked cell
+                            let (x, y) = (self.cursor.x as i32, self.cursor.y as i32);
+                            if let Some(index) = renderable.pick_cell(x, y) {
+                                println!("{}", worker.ecosystem.describe_cell(index));
+                            }
+                        }
+                        self.left_held = pressed;
+                    }
+                    MouseButton::Right => self.right_held = pressed,
+                    _ => {}
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let delta_yaw = -(position.x - self.cursor.x) as f32 * constants::MOUSE_SENSITIVITY;
+                let delta_pitch =
+                    -(position.y - self.cursor.y) as f32 * constants::MOUSE_SENSITIVITY;
+                self.cursor = position;
+                // left drag orbits around the terrain, right drag looks around
+                if self.left_held {
+                    self.dragged = true;
+                    renderable.camera().orbit_camera(delta_yaw, delta_pitch);
+                } else if self.right_held {
+                    renderable.camera().rotate_camera(delta_yaw, delta_pitch);
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+                renderable
+                    .camera()
+                    .zoom_camera(lines * constants::ZOOM_SPEED);
+            }
+            WindowEvent::RedrawRequested => {
+                // upload what the worker changed since the last frame
+                let mut paused = self.paused;
+                worker.poll(|update| {
+                    renderable.upload_vertex_data(&update.vertex_data);
+                    if update.stopped {
+                        paused = true;
+                    }
+                });
+                self.paused = paused;
+
+                if let Some(texture) = renderable.get_current_texture() {
+                    let view = texture
+                        .texture
+                        .create_view(&wgpu::TextureViewDescriptor::default());
+                    renderable.draw_frame(
+                        &view,
+                        &worker.ecosystem,
+                        &self.color_mode,
+                        &self.render_mode,
+                    );
+                    window.pre_present_notify();
+                    renderable.present(texture);
+                }
+                window.request_redraw();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    // parse command line options
+    let args = Args::parse();
+    // runs without pausing until this time step if given
+    let steps = args.steps;
+    let color_mode = get_color_mode(&args).unwrap_or(ColorMode::Standard);
+    let mut simulation = or_exit_with_usage(init_simulation(&args));
+    // there are no compute shaders to ray trace sunlight with yet, so the worker traces it on the
+    // cpu over the frames after terrain changes
+    simulation.enable_progressive_sunlight();
+
+    let event_loop = EventLoop::new().unwrap();
+    let mut app = App {
+        simulation: Some(simulation),
+        steps,
+        paused: steps.is_none(),
+        color_mode,
+        render_mode: RenderMode::Solid,
+        window: None,
+        renderable: None,
+        worker: None,
+        cursor: PhysicalPosition::default(),
+        left_held: false,
+        right_held: false,
+        dragged: false,
+    };
+    event_loop.run_app(&mut app).unwrap();
+}
@@ -0,0 +1,179 @@
+// TERRAIN BRUSH
+// edits the terrain and vegetation around a cell, e.g. to dig a channel before running an experiment
+// 1) every cell within the radius is edited by the strength of the brush, fading linearly towards its edge
+// 2) edited cells are marked so the tets, sunlight, and renderer catch up with them
+
+use crate::{
+    constants,
+    ecology::{CellIndex, Ecosystem, Trees},
+    land_use,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrushTool {
+    RaiseBedrock,
+    // digs through the loose layers before the bedrock under them
+    Dig,
+    PaintSand,
+    PaintHumus,
+    PlantTrees,
+    ClearTrees,
+    // cuts the bedrock into steps as high as the strength of the brush
+    Terrace,
+    // compacts the soil by the strength of the brush, e.g. dragged along a path
+    CompactSoil,
+}
+
+impl BrushTool {
+    pub fn next(self) -> Self {
+        match self {
+            BrushTool::RaiseBedrock => BrushTool::Dig,
+            BrushTool::Dig => BrushTool::PaintSand,
+            BrushTool::PaintSand => BrushTool::PaintHumus,
+            BrushTool::PaintHumus => BrushTool::PlantTrees,
+            BrushTool::PlantTrees => BrushTool::ClearTrees,
+            BrushTool::ClearTrees => BrushTool::Terrace,
+            BrushTool::Terrace => BrushTool::CompactSoil,
+            BrushTool::CompactSoil => BrushTool::RaiseBedrock,
+        }
+    }
+
+    fn changes_terrain(self) -> bool {
+        !matches!(self, BrushTool::PlantTrees | BrushTool::ClearTrees)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Brush {
+    pub tool: BrushTool,
+    // in cells
+    pub radius: f32,
+    // m of material added or removed at the center per application
+    pub strength: f32,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Brush {
+            tool: BrushTool::RaiseBedrock,
+            radius: 3.0,
+            strength: 0.5,
+        }
+    }
+}
+
+impl Brush {
+    // edits the cells around the center and updates the terrain they are part of
+    pub fn apply(&self, ecosystem: &mut Ecosystem, center: CellIndex) {
+        let reach = self.radius.floor() as usize;
+        let side = constants::AREA_SIDE_LENGTH;
+        for x in center.x.saturating_sub(reach)..usize::min(center.x + reach + 1, side) {
+            for y in center.y.saturating_sub(reach)..usize::min(center.y + reach + 1, side) {
+                let (dx, dy) = (x as f32 - center.x as f32, y as f32 - center.y as f32);
+                let distance = (dx * dx + dy * dy).sqrt();
+                if distance > self.radius {
+                    continue;
+                }
+                // the edge of the brush is still edited a little
+                let weight = 1.0 - distance / (self.radius + 1.0);
+                let index = CellIndex::new(x, y);
+                self.apply_to_cell(ecosystem, index, self.strength * weight);
+                if self.tool.changes_terrain() {
+                    ecosystem.mark_dirty(index);
+                } else {
+                    ecosystem.changed_cells.insert(index);
+                }
+            }
+        }
+        ecosystem.update_dirty_terrain();
+    }
+
+    fn apply_to_cell(&self, ecosystem: &mut Ecosystem, index: CellIndex, amount: f32) {
+        let cell = &mut ecosystem[index];
+        match self.tool {
+            BrushTool::RaiseBedrock => cell.add_bedrock(amount),
+            BrushTool::Dig => {
+                let mut remaining = amount;
+                remaining -= cell.remove_humus(remaining);
+                remaining -= cell.remove_sand(remaining);
+                remaining -= cell.remove_rocks(remaining);
+                cell.remove_bedrock(remaining);
+            }
+            BrushTool::PaintSand => cell.add_sand(amount),
+            BrushTool::PaintHumus => cell.add_humus(amount),
+            // young trees of the first species where there are none yet
+            BrushTool::PlantTrees => {
+                if cell.trees.is_empty() {
+                    cell.set_trees(Trees::with_plants(0, 2, 20.0, 40.0));
+                }
+            }
+            BrushTool::ClearTrees => land_use::clear_cut(cell, false),
+            // every cell is cut to steps of the same height
+            BrushTool::Terrace => {
+                land_use::terrace(cell, self.strength);
+            }
+            BrushTool::CompactSoil => land_use::compact_soil(cell, amount),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{Brush, BrushTool};
+    use crate::ecology::{CellIndex, Ecosystem};
+
+    #[test]
+    fn test_apply_brush() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.update_tets();
+        let center = CellIndex::new(50, 50);
+        let edge = CellIndex::new(53, 50);
+        let outside = CellIndex::new(54, 50);
+        let heights = |ecosystem: &Ecosystem| {
+            [center, edge, outside].map(|index| ecosystem[index].get_height())
+        };
+        let before = heights(&ecosystem);
+
+        // raised most at the center and not at all outside the radius
+        let mut brush = Brush::default();
+        brush.apply(&mut ecosystem, center);
+        let after = heights(&ecosystem);
+        assert_eq!(after[0] - before[0], brush.strength);
+        assert!(after[1] > before[1] && after[1] - before[1] < brush.strength);
+        assert_eq!(after[2], before[2]);
+        // the tets follow the terrain
+        assert!(ecosystem.dirty_cells.is_empty());
+        assert!(ecosystem.changed_cells.contains(&edge));
+
+        // digging takes the loose layers first
+        ecosystem[center].add_sand(0.2);
+        brush.tool = BrushTool::Dig;
+        brush.apply(&mut ecosystem, center);
+        assert_eq!(ecosystem[center].get_sand_height(), 0.0);
+        let expected = before[0] + 0.2;
+        let actual = ecosystem[center].get_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // trees are planted and cleared without changing the terrain
+        brush.tool = BrushTool::PlantTrees;
+        brush.apply(&mut ecosystem, center);
+        assert_eq!(ecosystem[edge].trees.len(), 1);
+        assert!(ecosystem[outside].trees.is_empty());
+        brush.tool = BrushTool::ClearTrees;
+        brush.apply(&mut ecosystem, center);
+        assert!(ecosystem[edge].trees.is_empty());
+
+        // terraces are cut to the same steps across the brush
+        brush.tool = BrushTool::Terrace;
+        brush.apply(&mut ecosystem, center);
+        for index in [center, edge] {
+            let bedrock = ecosystem[index].get_bedrock_height();
+            assert_eq!(bedrock % brush.strength, 0.0);
+        }
+    }
+}
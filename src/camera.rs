@@ -1,12 +1,18 @@
-use nalgebra::{Matrix4, Rotation3, Vector3};
+use nalgebra::{Matrix4, Vector3};
 
-pub(crate) struct Camera {
-    pub(crate) m_position: Vector3<f32>,
+// keeps the camera from flipping over when looking straight up or down
+const MAX_PITCH: f32 = 1.5;
+// closest the camera can zoom in to the orbit point
+const MIN_ZOOM: f32 = 1.0;
+
+pub struct Camera {
+    pub m_position: Vector3<f32>,
     m_pitch: f32,
     m_yaw: f32,
-    pub(crate) m_look: Vector3<f32>,
+    pub m_look: Vector3<f32>,
     m_orbit_point: Vector3<f32>,
-    _m_is_orbiting: bool,
+    // whether the camera is looking at the orbit point
+    m_is_orbiting: bool,
     m_view: Matrix4<f32>,
     m_proj: Matrix4<f32>,
     m_view_dirty: bool,
@@ -15,7 +21,8 @@ pub(crate) struct Camera {
     m_aspect: f32,
     m_near: f32,
     m_far: f32,
-    _m_zoom: f32,
+    // distance to the orbit point
+    m_zoom: f32,
 }
 
 impl Camera {
@@ -26,7 +33,7 @@ impl Camera {
             m_yaw: 0.0,
             m_look: Vector3::zeros(),
             m_orbit_point: Vector3::zeros(),
-            _m_is_orbiting: false,
+            m_is_orbiting: false,
             m_view: Matrix4::identity(),
             m_proj: Matrix4::identity(),
             m_view_dirty: false,
@@ -35,23 +42,31 @@ impl Camera {
             m_aspect: 0.0,
             m_near: 0.0,
             m_far: 0.0,
-            _m_zoom: 0.0,
+            m_zoom: 0.0,
         }
     }
 
-    pub(crate) fn look_at(&mut self, eye: Vector3<f32>, target: Vector3<f32>) {
+    pub fn look_at(&mut self, eye: Vector3<f32>, target: Vector3<f32>) {
         self.m_position = eye;
         self.m_look = (target - eye).normalize();
         self.m_view_dirty = true;
         self.update_pitch_and_yaw();
     }
 
-    pub(crate) fn set_orbit_point(&mut self, orbit_point: Vector3<f32>) {
+    pub fn set_orbit_point(&mut self, orbit_point: Vector3<f32>) {
         self.m_orbit_point = orbit_point;
         self.m_view_dirty = true;
     }
 
-    pub(crate) fn set_perspective(&mut self, fov_y: f32, aspect: f32, near: f32, far: f32) {
+    // moves the orbit point and turns the camera to look at it from where it is
+    pub fn focus(&mut self, orbit_point: Vector3<f32>) {
+        self.set_orbit_point(orbit_point);
+        self.look_at(self.m_position, orbit_point);
+        self.m_zoom = (self.m_position - orbit_point).norm();
+        self.m_is_orbiting = true;
+    }
+
+    pub fn set_perspective(&mut self, fov_y: f32, aspect: f32, near: f32, far: f32) {
         self.m_fov_y = fov_y;
         self.m_aspect = aspect;
         self.m_near = near;
@@ -59,12 +74,30 @@ impl Camera {
         self.m_proj_dirty = true;
     }
 
+    pub fn get_aspect(&self) -> f32 {
+        self.m_aspect
+    }
+
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.m_aspect = aspect;
+        self.m_proj_dirty = true;
+    }
+
+    // z is up
     fn update_pitch_and_yaw(&mut self) {
-        self.m_pitch = f32::asin(-self.m_look.y);
-        self.m_yaw = f32::atan2(self.m_look.x, self.m_look.z);
+        self.m_pitch = f32::asin(self.m_look.z.clamp(-1.0, 1.0));
+        self.m_yaw = f32::atan2(self.m_look.y, self.m_look.x);
+    }
+
+    fn get_direction(pitch: f32, yaw: f32) -> Vector3<f32> {
+        Vector3::new(
+            pitch.cos() * yaw.cos(),
+            pitch.cos() * yaw.sin(),
+            pitch.sin(),
+        )
     }
 
-    pub(crate) fn get_view(&mut self) -> Matrix4<f32> {
+    pub fn get_view(&mut self) -> Matrix4<f32> {
         if self.m_view_dirty {
             let pos: Vector3<f32> = self.m_position;
             let look: Vector3<f32> = self.m_look;
@@ -86,7 +119,7 @@ impl Camera {
         self.m_view
     }
 
-    pub(crate) fn get_projection(&mut self) -> Matrix4<f32> {
+    pub fn get_projection(&mut self) -> Matrix4<f32> {
         if self.m_proj_dirty {
             let theta = self.m_fov_y * 0.5;
             let inv_range = 1.0 / (self.m_far - self.m_near);
@@ -102,27 +135,134 @@ impl Camera {
         self.m_proj
     }
 
-    pub(crate) fn move_camera(&mut self, delta_pos: Vector3<f32>) {
+    // returns the direction of the ray from the camera through the given point on the screen
+    // in normalized device coordinates, where (-1, -1) is the bottom left corner
+    pub fn get_ray_direction(&mut self, ndc_x: f32, ndc_y: f32) -> Vector3<f32> {
+        // inverse of the scaling done by the projection matrix
+        let tan = f32::tan(self.m_fov_y * 0.5);
+        let view_direction = Vector3::new(ndc_x * tan * self.m_aspect, ndc_y * tan, -1.0);
+        // the view rotation is orthonormal so its inverse is its transpose
+        let rotation = self.get_view().fixed_view::<3, 3>(0, 0).transpose();
+        (rotation * view_direction).normalize()
+    }
+
+    pub fn move_camera(&mut self, delta_pos: Vector3<f32>) {
         if delta_pos.norm_squared() == 0.0 {
             return;
         }
 
         self.m_position += delta_pos;
 
-        // if (m_isOrbiting) {
-        //     m_orbitPoint += deltaPosition;
-        // }
+        if self.m_is_orbiting {
+            self.m_orbit_point += delta_pos;
+        }
+
+        self.m_view_dirty = true;
+    }
+
+    // turns the camera in place, which stops it from orbiting
+    pub fn rotate_camera(&mut self, delta_yaw: f32, delta_pitch: f32) {
+        self.m_yaw += delta_yaw;
+        self.m_pitch = (self.m_pitch + delta_pitch).clamp(-MAX_PITCH, MAX_PITCH);
+        self.m_look = Self::get_direction(self.m_pitch, self.m_yaw);
+        self.m_is_orbiting = false;
+        self.m_view_dirty = true;
+    }
 
+    // moves the camera around the orbit point, keeping its distance and looking at it
+    pub fn orbit_camera(&mut self, delta_yaw: f32, delta_pitch: f32) {
+        if !self.m_is_orbiting {
+            self.focus(self.m_orbit_point);
+        }
+        self.m_yaw += delta_yaw;
+        self.m_pitch = (self.m_pitch + delta_pitch).clamp(-MAX_PITCH, MAX_PITCH);
+        self.m_look = Self::get_direction(self.m_pitch, self.m_yaw);
+        self.m_position = self.m_orbit_point - self.m_look * self.m_zoom;
         self.m_view_dirty = true;
     }
 
-    pub(crate) fn rotate_camera(&mut self, angle: f32) {
-        // println!("rotate by {}", angle);
-        // rotate around z-axis (z-up)
-        // let axis = Vector3::z_axis();
-        // let rot = Rotation3::from_axis_angle(&axis, angle);
-        // self.m_look = rot * self.m_look;
-        // self.update_pitch_and_yaw();
-        // self.m_view_dirty = true;
+    // moves the camera towards the orbit point for positive amounts and away for negative ones
+    pub fn zoom_camera(&mut self, amount: f32) {
+        if !self.m_is_orbiting {
+            self.focus(self.m_orbit_point);
+        }
+        self.m_zoom = (self.m_zoom * (1.0 - amount)).max(MIN_ZOOM);
+        self.m_position = self.m_orbit_point - self.m_look * self.m_zoom;
+        self.m_view_dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+    use nalgebra::Vector3;
+
+    use super::Camera;
+
+    #[test]
+    fn test_get_ray_direction() {
+        let mut camera = Camera::init();
+        camera.look_at(Vector3::new(0.0, -10.0, 10.0), Vector3::zeros());
+        camera.set_perspective(1.0, 1.5, 0.1, 100.0);
+
+        // ray through the center of the screen is the look direction
+        let actual = camera.get_ray_direction(0.0, 0.0);
+        let expected = camera.m_look;
+        for i in 0..3 {
+            assert!(
+                approx_eq!(f32, actual[i], expected[i], epsilon = 0.0001),
+                "Expected {expected}, actual {actual}"
+            );
+        }
+
+        // ray through the corner of the screen projects back onto the corner
+        let direction = camera.get_ray_direction(1.0, 1.0);
+        let point = camera.m_position + direction * 5.0;
+        let clip = camera.get_projection() * camera.get_view() * point.push(1.0);
+        for actual in [clip.x / clip.w, clip.y / clip.w] {
+            assert!(
+                approx_eq!(f32, actual, 1.0, epsilon = 0.0001),
+                "Expected 1.0, actual {actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_orbit_and_zoom_camera() {
+        let mut camera = Camera::init();
+        let orbit_point = Vector3::new(50.0, 50.0, 100.0);
+        camera.look_at(Vector3::new(50.0, -50.0, 200.0), orbit_point);
+        camera.focus(orbit_point);
+        let distance = (camera.m_position - orbit_point).norm();
+
+        // orbiting keeps the distance and the orbit point in view
+        camera.orbit_camera(1.0, -0.2);
+        let actual = (camera.m_position - orbit_point).norm();
+        assert!(
+            approx_eq!(f32, actual, distance, epsilon = 0.01),
+            "Expected {distance}, actual {actual}"
+        );
+        let expected = (orbit_point - camera.m_position).normalize();
+        let actual = camera.m_look;
+        for i in 0..3 {
+            assert!(
+                approx_eq!(f32, actual[i], expected[i], epsilon = 0.0001),
+                "Expected {expected}, actual {actual}"
+            );
+        }
+
+        // zooming moves along the look direction
+        camera.zoom_camera(0.5);
+        let expected = distance * 0.5;
+        let actual = (camera.m_position - orbit_point).norm();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.01),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // looking around leaves the camera in place
+        let position = camera.m_position;
+        camera.rotate_camera(0.5, 0.1);
+        assert_eq!(camera.m_position, position);
     }
 }
@@ -0,0 +1,1302 @@
+use nalgebra::Vector3;
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants,
+    ecology::{CellIndex, Ecosystem, RockSize},
+    events::{
+        fauna::populate_grazers,
+        wind::{WindRose, WindState},
+    },
+    import::{import_dem, import_height_map, is_dem},
+};
+
+// per-run scenario configuration loaded from a TOML file
+// any field missing from the file falls back to the defaults in constants.rs
+// grid dimensions and most rendering constants remain compile-time
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // runs with the same seed produce identical results, a random seed is used if not given
+    pub seed: Option<u64>,
+    pub initializer: Initializer,
+    // grayscale image, or an SRTM .hgt or GeoTIFF DEM tile
+    pub height_map_path: String,
+    pub location: Location,
+    pub climate: Climate,
+    pub wind: WindConfig,
+    pub critical_angles: CriticalAngles,
+    pub sediment: Sediment,
+    // cells that feed a constant flow of water into the terrain, e.g. to grow river valleys
+    pub springs: Vec<Spring>,
+    // cells below the sea level are flooded and waves work on the shore, disabled if not given
+    pub sea: Option<Sea>,
+    // horizontal layers of bedrock of differing hardness, uniform bedrock if not given
+    pub strata: Option<Strata>,
+    // browsing by deer or sheep that are not simulated as grazers, disabled if not given
+    pub browsing: Option<Browsing>,
+    // thunderstorms that bring the lightning and heavy rain along tracks, lightning strikes evenly if not given
+    pub storms: Option<Storms>,
+    // rain falling unevenly in cells that drift with the wind, rain falls evenly if not given
+    pub rain_cells: Option<RainCells>,
+    pub species: SpeciesConfig,
+    pub fauna: Fauna,
+    pub time_resolution: TimeResolution,
+    pub illumination_method: IlluminationMethod,
+    pub boundary: BoundaryMode,
+    pub color_ramps: ColorRamps,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Initializer {
+    Standard,
+    StandardF,
+    IanTerrain,
+    Test,
+    Piles,
+    Dunes,
+    Sand,
+    #[default]
+    HeightMap,
+}
+
+impl std::str::FromStr for Initializer {
+    type Err = String;
+
+    // accepts the names used in config files, with or without underscores
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.replace('_', "").as_str() {
+            "standard" => Ok(Initializer::Standard),
+            "standardf" => Ok(Initializer::StandardF),
+            "ianterrain" => Ok(Initializer::IanTerrain),
+            "test" => Ok(Initializer::Test),
+            "piles" => Ok(Initializer::Piles),
+            "dunes" => Ok(Initializer::Dunes),
+            "sand" => Ok(Initializer::Sand),
+            "heightmap" => Ok(Initializer::HeightMap),
+            _ => Err(format!("Unknown scenario {name}")),
+        }
+    }
+}
+
+// what happens at the edges of the map, for neighbor lookups, wind, and runoff alike
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundaryMode {
+    // opposite edges are next to each other
+    Torus,
+    // cells at the edges have fewer neighbors, and sand and water leaving the map are lost
+    #[default]
+    Open,
+    // cells at the edges have fewer neighbors, and sand and water are turned back into the map
+    Reflective,
+}
+
+impl std::str::FromStr for BoundaryMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "torus" => Ok(BoundaryMode::Torus),
+            "open" => Ok(BoundaryMode::Open),
+            "reflective" => Ok(BoundaryMode::Reflective),
+            _ => Err(format!("Unknown boundary mode {name}")),
+        }
+    }
+}
+
+// length of a time step
+// climate is monthly, so shorter time steps only see the months they cover
+// and rates given per year are prorated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeResolution {
+    #[default]
+    Year,
+    Month,
+    Day,
+}
+
+impl std::str::FromStr for TimeResolution {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "year" => Ok(TimeResolution::Year),
+            "month" => Ok(TimeResolution::Month),
+            "day" => Ok(TimeResolution::Day),
+            _ => Err(format!("Unknown time resolution {name}")),
+        }
+    }
+}
+
+// how the hours of direct sunlight of each cell are computed
+// ray tracing is the most accurate and horizon maps much faster on large maps,
+// while a constant ignores the terrain and uses the climate's sunlight everywhere
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IlluminationMethod {
+    #[default]
+    RayTraced,
+    HorizonMap,
+    Constant,
+}
+
+impl std::str::FromStr for IlluminationMethod {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "ray_traced" => Ok(IlluminationMethod::RayTraced),
+            "horizon_map" => Ok(IlluminationMethod::HorizonMap),
+            "constant" => Ok(IlluminationMethod::Constant),
+            _ => Err(format!("Unknown illumination method {name}")),
+        }
+    }
+}
+
+pub const DAYS_IN_MONTH: [u32; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+impl TimeResolution {
+    // fraction of a year covered by one time step
+    pub fn get_fraction_of_year(&self) -> f32 {
+        match self {
+            TimeResolution::Year => 1.0,
+            TimeResolution::Month => 1.0 / 12.0,
+            TimeResolution::Day => 1.0 / 365.0,
+        }
+    }
+
+    // returns the months covered by the given time step, starting from 1,
+    // each with the fraction of the month that is covered
+    pub fn get_months(&self, time_step: u32) -> Vec<(usize, f32)> {
+        let elapsed = time_step.saturating_sub(1);
+        match self {
+            TimeResolution::Year => (0..12).map(|month| (month, 1.0)).collect(),
+            TimeResolution::Month => vec![(elapsed as usize % 12, 1.0)],
+            TimeResolution::Day => {
+                let mut day = elapsed % 365;
+                let mut month = 0;
+                while day >= DAYS_IN_MONTH[month] {
+                    day -= DAYS_IN_MONTH[month];
+                    month += 1;
+                }
+                vec![(month, 1.0 / DAYS_IN_MONTH[month] as f32)]
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Location {
+    pub latitude: f32,
+    pub longitude: f32,
+    pub timezone: i32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Climate {
+    // in celsius
+    pub monthly_temperatures: [f32; 12],
+    pub monthly_sunlight_hours: [f32; 12],
+    // in mm per month
+    pub monthly_rainfall: [f32; 12],
+    // modifier on sunlight hours when ray-traced to account for cloud coverage
+    pub percent_sunny_days: f32,
+    // lightning strikes per square km per year on the cells most exposed to them
+    pub lightning_strikes: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindConfig {
+    // degrees from north
+    pub direction: f32,
+    pub strength: f32,
+    // if non-empty, replaces the wind rose set by the initializer
+    pub rose: Vec<WindRoseEntry>,
+    // wind roses replacing the yearly one in some months of the year
+    pub seasons: Vec<WindSeason>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WindSeason {
+    // 0 is January
+    pub months: Vec<usize>,
+    pub rose: Vec<WindRoseEntry>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct WindRoseEntry {
+    pub direction: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+// in degrees
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CriticalAngles {
+    // of scree
+    pub rock: f32,
+    pub boulders: f32,
+    pub gravel: f32,
+    pub sand: f32,
+    pub sand_with_vegetation: f32,
+    pub humus: f32,
+    pub snow: f32,
+    // share of the angles of sand and humus lost when saturated, from 0 to 1
+    pub saturated_loss: f32,
+}
+
+// sediment constants used by rainfall runoff
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sediment {
+    // capacity
+    pub kc: f32,
+    // deposition
+    pub kd: f32,
+    // soil erosion
+    pub ks: f32,
+    // bedrock incision by concentrated flow, following the stream power law
+    pub ki: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Spring {
+    // x and y of the cell the water comes out of
+    pub cell: [usize; 2],
+    // in m^3 per month
+    pub flow: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sea {
+    // in m
+    pub level: f32,
+    // m of bedrock broken into sand per year on cells facing the open sea
+    pub wave_erosion: f32,
+    // m above the sea level below which salt spray keeps vegetation from establishing
+    pub salt_spray_height: f32,
+}
+
+// stratified bedrock, e.g. a hard caprock over soft shale, so erosion carves mesas and ledges
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Strata {
+    // layers from the bottom up, starting at an elevation of 0 and repeating above the last one
+    pub layers: Vec<Stratum>,
+    // when no layers are listed, layers of this thickness (in m) get their hardness from 3d noise
+    pub noise_thickness: f32,
+    // range of the hardness of the noise layers
+    pub noise_hardness: [f32; 2],
+    // m that noise moves the boundaries between layers up or down, 0 for flat layers
+    pub warp: f32,
+    // m over which the noise varies across the map
+    pub noise_scale: f32,
+    pub seed: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stratum {
+    // in m
+    pub thickness: f32,
+    // how many times slower than the default bedrock the layer weathers and erodes
+    pub hardness: f32,
+}
+
+// browsing pressure, from 0 for none to 1 where every tree and bush seedling is eaten
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Browsing {
+    // pressure on the cells outside of every region
+    pub pressure: f32,
+    // rectangles of cells with a pressure of their own, e.g. 0 for fenced exclosures
+    // later regions override earlier ones where they overlap
+    pub regions: Vec<BrowsingRegion>,
+    // grass coverage eaten per year under full pressure
+    pub grass_consumption: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BrowsingRegion {
+    // x and y of the corners of the region (inclusive)
+    pub min: [usize; 2],
+    pub max: [usize; 2],
+    pub pressure: f32,
+}
+
+// thunderstorms crossing the map along the wind
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Storms {
+    // days with a thunderstorm in each month, 0 is January
+    pub monthly_storm_days: [f32; 12],
+    // in m
+    pub track_width: f32,
+    // mm of rain on the cells under each storm, on top of the rainfall of the month
+    pub rainfall: f32,
+}
+
+// perlin noise that shares the rainfall of the months out over the map
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RainCells {
+    // in m
+    pub size: f32,
+    // how much wetter or drier than the average the cells are, 0 for even rainfall
+    pub contrast: f32,
+    // m the cells move downwind per time step at the default wind strength
+    pub drift: f32,
+}
+
+// grazing herbivores moving across the map
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fauna {
+    // grazers placed on random cells when the ecosystem is built
+    pub initial_grazers: usize,
+    // grazers only reproduce while the population is below this
+    pub max_grazers: usize,
+    // grass coverage eaten by a grazer per time step
+    pub grass_appetite: f32,
+    // kg of bushes eaten by a grazer per time step when there is not enough grass
+    pub bush_appetite: f32,
+    // m of humus compacted each time a grazer enters a cell
+    pub trampling: f32,
+}
+
+// colors of the color modes that shade a value from low to high
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorRamps {
+    // from the lowest to the highest cell of the terrain
+    pub hypsometric: ColorRamp,
+    // from dry to saturated soil
+    pub soil_moisture: ColorRamp,
+    // from the coldest to the hottest temperature displayed
+    pub temperature: ColorRamp,
+}
+
+// colors at values from 0 to 1, blended in between
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorRamp {
+    // in order of increasing value
+    pub stops: Vec<ColorStop>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop {
+    pub value: f32,
+    // rgb from 0 to 1
+    pub color: [f32; 3],
+}
+
+impl ColorRamp {
+    // values outside of the stops take the color of the closest one
+    pub fn sample(&self, value: f32) -> Vector3<f32> {
+        let (Some(first), Some(last)) = (self.stops.first(), self.stops.last()) else {
+            return Vector3::zeros();
+        };
+        if value <= first.value {
+            return Vector3::from(first.color);
+        }
+        for pair in self.stops.windows(2) {
+            let (low, high) = (pair[0], pair[1]);
+            if value < high.value {
+                let alpha = (value - low.value) / (high.value - low.value);
+                return Vector3::from(low.color).lerp(&Vector3::from(high.color), alpha);
+            }
+        }
+        Vector3::from(last.color)
+    }
+}
+
+// species table of the run, which defaults to the species in events/vegetation.rs
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeciesConfig {
+    // a cell can carry trees of every species in this list, which compete for the same space
+    pub trees: Vec<SpeciesParams>,
+    pub bushes: SpeciesParams,
+    pub grasses: SpeciesParams,
+}
+
+// viability ranges and growth parameters of a plant species
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpeciesParams {
+    #[serde(default)]
+    pub name: String,
+    // temperature in celsius
+    pub temperature_limit_min: f32,
+    pub temperature_ideal_min: f32,
+    pub temperature_ideal_max: f32,
+    pub temperature_limit_max: f32,
+    // % soil moisture, which is the % by weight or volume of soil
+    // e.g. 10% moisture means 10% volume (or weight) of soil is water
+    pub moisture_limit_min: f32,
+    pub moisture_ideal_min: f32,
+    pub moisture_ideal_max: f32,
+    pub moisture_limit_max: f32,
+    // hours of daily sunlight
+    pub illumination_limit_min: f32,
+    pub illumination_ideal_min: f32,
+    pub illumination_ideal_max: f32,
+    pub illumination_limit_max: f32,
+    // share of the light lost to the canopy above that the species makes up for
+    // from 0 for species that need full sun to 1 for species that grow as well in the shade
+    #[serde(default)]
+    pub shade_tolerance: f32,
+    // depth (in m) below the ground that the roots reach for soil moisture
+    // the topmost soil layer is always reached, so 0 only draws on the topsoil
+    #[serde(default)]
+    pub rooting_depth: f32,
+
+    // the rest are only used by individualized vegetation (trees and bushes)
+    // number of new plants per square meter per year
+    #[serde(default)]
+    pub establishment_rate: f32,
+    // impact of density on seedling count
+    #[serde(default)]
+    pub seedling_density_constant: f32,
+    // impact of vigor on seedling count
+    #[serde(default)]
+    pub seedling_vigor_constant: f32,
+    // meter per plant per year
+    #[serde(default)]
+    pub growth_rate: f32,
+    // in years
+    #[serde(default)]
+    pub life_expectancy: f32,
+    // impact of stress on number of plants
+    #[serde(default)]
+    pub stress_death_constant: f32,
+    // months in a row of soil too dry for the species that its plants get through before dying off
+    #[serde(default = "default_drought_tolerance")]
+    pub drought_tolerance: f32,
+    // impact of age on number of plants
+    #[serde(default)]
+    pub senescence_death_constant: f32,
+    // wind speed (in m/s) that fells trees of the reference height rooted in deep soil
+    #[serde(default = "default_windthrow_threshold")]
+    pub windthrow_threshold: f32,
+    // only used by grasses, sets the temperatures at which they green up and cure
+    #[serde(default)]
+    pub photosynthesis: Photosynthesis,
+}
+
+// photosynthetic pathway of grasses
+// cool-season (c3) grasses green up early and cure in the summer heat
+// while warm-season (c4) grasses stay brown until late spring and green through the summer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Photosynthesis {
+    #[default]
+    C3,
+    C4,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+fn default_windthrow_threshold() -> f32 {
+    30.0
+}
+
+fn default_drought_tolerance() -> f32 {
+    3.0
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Self, String> {
+        println!("Reading config at {path}");
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("cannot read config {path}: {error}"))?;
+        Self::parse(&contents).map_err(|error| format!("invalid config {path}: {error}"))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let config: Config = toml::from_str(contents).map_err(|error| error.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // catches values that parse but would index out of range during the simulation
+    fn validate(&self) -> Result<(), String> {
+        let side = constants::AREA_SIDE_LENGTH;
+        for spring in &self.springs {
+            let [x, y] = spring.cell;
+            if x >= side || y >= side {
+                return Err(format!(
+                    "spring at cell [{x}, {y}] is outside the grid of {side}x{side} cells"
+                ));
+            }
+        }
+        for season in &self.wind.seasons {
+            if let Some(month) = season.months.iter().find(|&&month| month >= 12) {
+                return Err(format!("wind season month {month} is not between 0 and 11"));
+            }
+        }
+        Ok(())
+    }
+
+    // builds the ecosystem described by this config
+    pub fn build_ecosystem(&self) -> Result<Ecosystem, String> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        println!("Using seed {seed}");
+        // DEM tiles also carry the location of the terrain
+        let mut location = None;
+        // m imported terrain was raised by to keep it above 0
+        let mut elevation_offset = 0.0;
+        let mut ecosystem = match self.initializer {
+            Initializer::Standard => Ecosystem::init_standard(seed),
+            Initializer::StandardF => Ecosystem::init_standard_f(),
+            Initializer::IanTerrain => Ecosystem::init_standard_ianterrain(seed),
+            Initializer::Test => Ecosystem::init_test(),
+            Initializer::Piles => Ecosystem::init_piles(),
+            Initializer::Dunes => Ecosystem::init_dunes(),
+            Initializer::Sand => Ecosystem::init_sand(),
+            Initializer::HeightMap => {
+                let terrain = if is_dem(&self.height_map_path) {
+                    import_dem(&self.height_map_path)?
+                } else {
+                    import_height_map(&self.height_map_path)?
+                };
+                location = terrain.location;
+                elevation_offset = terrain.elevation_offset;
+                terrain.ecosystem
+            }
+        };
+        if !self.wind.rose.is_empty() {
+            let mut wind_state = WindState::new();
+            wind_state.wind_rose = self.wind.build_wind_rose();
+            ecosystem.wind_state = Some(wind_state);
+        }
+        if !self.wind.seasons.is_empty() {
+            let wind_state = ecosystem.wind_state.get_or_insert_with(WindState::new);
+            wind_state.monthly_wind_roses =
+                self.wind.build_monthly_wind_roses(&wind_state.wind_rose);
+        }
+        ecosystem.config = self.clone();
+        if let Some(location) = location {
+            println!(
+                "Using location {}, {} from the DEM",
+                location.latitude, location.longitude
+            );
+            ecosystem.config.location = location;
+        }
+        // the sea level is given in real elevations, so it is raised with the terrain
+        if elevation_offset > 0.0 {
+            println!("Raising the terrain by {elevation_offset} m to keep it above 0");
+            if let Some(sea) = &mut ecosystem.config.sea {
+                sea.level += elevation_offset;
+            }
+        }
+        ecosystem.set_seed(seed);
+        populate_grazers(&mut ecosystem);
+        Ok(ecosystem)
+    }
+}
+
+impl WindConfig {
+    pub fn build_wind_rose(&self) -> WindRose {
+        build_wind_rose(&self.rose)
+    }
+
+    // returns the wind rose of each month, using the yearly rose for months without a season
+    pub fn build_monthly_wind_roses(&self, yearly: &WindRose) -> Vec<WindRose> {
+        let mut wind_roses = vec![yearly.clone(); 12];
+        for season in &self.seasons {
+            for &month in &season.months {
+                assert!(
+                    month < 12,
+                    "Wind season month {month} is not between 0 and 11"
+                );
+                wind_roses[month] = build_wind_rose(&season.rose);
+            }
+        }
+        wind_roses
+    }
+}
+
+pub fn build_wind_rose(entries: &[WindRoseEntry]) -> WindRose {
+    let mut wind_rose = WindRose {
+        min_speed: [0.0; 8],
+        max_speed: [0.0; 8],
+        weights: [0.0; 8],
+    };
+    for entry in entries {
+        // directions of a full turn or more, or below 0, wrap around into the 8 buckets
+        wind_rose.update_wind(
+            entry.direction.rem_euclid(360.0),
+            entry.min_speed,
+            entry.max_speed,
+            entry.weight,
+        );
+    }
+    wind_rose
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            seed: None,
+            initializer: Initializer::default(),
+            height_map_path: constants::IMPORT_FILE_PATH.to_string(),
+            location: Location::default(),
+            climate: Climate::default(),
+            wind: WindConfig::default(),
+            critical_angles: CriticalAngles::default(),
+            sediment: Sediment::default(),
+            springs: vec![],
+            sea: None,
+            strata: None,
+            browsing: None,
+            storms: None,
+            rain_cells: None,
+            species: SpeciesConfig::default(),
+            fauna: Fauna::default(),
+            time_resolution: TimeResolution::default(),
+            illumination_method: IlluminationMethod::default(),
+            boundary: BoundaryMode::default(),
+            color_ramps: ColorRamps::default(),
+        }
+    }
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Location {
+            latitude: constants::LATITUDE,
+            longitude: constants::LONGITUDE,
+            timezone: constants::TIMEZONE,
+        }
+    }
+}
+
+impl Climate {
+    // returns rainfall (in mm) over the given months, weighted by the fraction of each month
+    pub fn get_rainfall(&self, months: &[(usize, f32)]) -> f32 {
+        months
+            .iter()
+            .map(|(month, fraction)| self.monthly_rainfall[*month] * fraction)
+            .sum()
+    }
+}
+
+impl Default for Climate {
+    fn default() -> Self {
+        Climate {
+            monthly_temperatures: constants::AVERAGE_MONTHLY_TEMPERATURES,
+            monthly_sunlight_hours: constants::AVERAGE_SUNLIGHT_HOURS,
+            monthly_rainfall: constants::AVERAGE_MONTHLY_RAINFALL,
+            percent_sunny_days: constants::PERCENT_SUNNY_DAYS,
+            lightning_strikes: constants::LIGHTNING_STRIKES,
+        }
+    }
+}
+
+impl Default for WindConfig {
+    fn default() -> Self {
+        WindConfig {
+            direction: constants::WIND_DIRECTION,
+            strength: constants::WIND_STRENGTH,
+            rose: vec![],
+            seasons: vec![],
+        }
+    }
+}
+
+impl CriticalAngles {
+    pub fn get_rock(&self, size: RockSize) -> f32 {
+        match size {
+            RockSize::Boulders => self.boulders,
+            RockSize::Scree => self.rock,
+            RockSize::Gravel => self.gravel,
+        }
+    }
+}
+
+impl Default for CriticalAngles {
+    fn default() -> Self {
+        CriticalAngles {
+            rock: constants::CRITICAL_ANGLE_ROCK,
+            boulders: constants::CRITICAL_ANGLE_BOULDERS,
+            gravel: constants::CRITICAL_ANGLE_GRAVEL,
+            sand: constants::CRITICAL_ANGLE_SAND,
+            sand_with_vegetation: constants::CRITICAL_ANGLE_SAND_WITH_VEGETATION,
+            humus: constants::CRITICAL_ANGLE_HUMUS,
+            snow: constants::CRITICAL_ANGLE_SNOW,
+            saturated_loss: constants::CRITICAL_ANGLE_SATURATED_LOSS,
+        }
+    }
+}
+
+impl Default for Sediment {
+    fn default() -> Self {
+        Sediment {
+            kc: constants::KC,
+            kd: constants::KD,
+            ks: constants::KS,
+            ki: constants::KI,
+        }
+    }
+}
+
+impl Default for Sea {
+    fn default() -> Self {
+        Sea {
+            level: 0.0,
+            wave_erosion: constants::WAVE_EROSION,
+            salt_spray_height: constants::SALT_SPRAY_HEIGHT,
+        }
+    }
+}
+
+impl Strata {
+    pub fn get_noise(&self) -> Perlin {
+        Perlin::new(self.seed)
+    }
+
+    // hardness of the bedrock of the cell at the given elevation (in m)
+    pub fn get_hardness(&self, noise: &Perlin, index: CellIndex, elevation: f32) -> f32 {
+        let x = (index.x as f32 * constants::CELL_SIDE_LENGTH / self.noise_scale) as f64;
+        let y = (index.y as f32 * constants::CELL_SIDE_LENGTH / self.noise_scale) as f64;
+        let z = (elevation / self.noise_scale) as f64;
+        let elevation = elevation + self.warp * noise.get([x, y, z]) as f32;
+
+        if self.layers.is_empty() {
+            if self.noise_thickness <= 0.0 {
+                return 1.0;
+            }
+            // sampled between lattice points, where perlin noise is always 0
+            let layer = (elevation / self.noise_thickness).floor() as f64 + 0.5;
+            let t = (noise.get([x, y, layer]) as f32 * 0.5 + 0.5).clamp(0.0, 1.0);
+            let [min, max] = self.noise_hardness;
+            return min + t * (max - min);
+        }
+
+        let total: f32 = self.layers.iter().map(|layer| layer.thickness).sum();
+        if total <= 0.0 {
+            return 1.0;
+        }
+        let mut depth = elevation.rem_euclid(total);
+        for layer in &self.layers {
+            if depth < layer.thickness {
+                return layer.hardness;
+            }
+            depth -= layer.thickness;
+        }
+        // only reached through rounding at the top of the last layer
+        self.layers[self.layers.len() - 1].hardness
+    }
+}
+
+impl Default for Strata {
+    fn default() -> Self {
+        Strata {
+            layers: vec![],
+            noise_thickness: constants::STRATUM_THICKNESS,
+            noise_hardness: constants::STRATUM_HARDNESS,
+            warp: 0.0,
+            noise_scale: constants::STRATA_NOISE_SCALE,
+            seed: 0,
+        }
+    }
+}
+
+impl Browsing {
+    // pressure on the cell, from 0 to 1
+    pub fn get_pressure(&self, index: CellIndex) -> f32 {
+        self.regions
+            .iter()
+            .rev()
+            .find(|region| {
+                (region.min[0]..=region.max[0]).contains(&index.x)
+                    && (region.min[1]..=region.max[1]).contains(&index.y)
+            })
+            .map_or(self.pressure, |region| region.pressure)
+            .clamp(0.0, 1.0)
+    }
+}
+
+impl Default for Browsing {
+    fn default() -> Self {
+        Browsing {
+            pressure: constants::BROWSING_PRESSURE,
+            regions: vec![],
+            grass_consumption: constants::BROWSING_GRASS_CONSUMPTION,
+        }
+    }
+}
+
+impl Default for Storms {
+    fn default() -> Self {
+        Storms {
+            monthly_storm_days: constants::MONTHLY_STORM_DAYS,
+            track_width: constants::STORM_TRACK_WIDTH,
+            rainfall: constants::STORM_RAINFALL,
+        }
+    }
+}
+
+impl Default for RainCells {
+    fn default() -> Self {
+        RainCells {
+            size: constants::RAIN_CELL_SIZE,
+            contrast: constants::RAIN_CELL_CONTRAST,
+            drift: constants::RAIN_CELL_DRIFT,
+        }
+    }
+}
+
+impl Default for Fauna {
+    fn default() -> Self {
+        Fauna {
+            initial_grazers: constants::INITIAL_GRAZERS,
+            max_grazers: constants::MAX_GRAZERS,
+            grass_appetite: constants::GRASS_APPETITE,
+            bush_appetite: constants::BUSH_APPETITE,
+            trampling: constants::TRAMPLING,
+        }
+    }
+}
+
+impl Default for ColorRamps {
+    fn default() -> Self {
+        let stop = |value: f32, color: Vector3<f32>| ColorStop {
+            value,
+            color: color.into(),
+        };
+        ColorRamps {
+            hypsometric: ColorRamp {
+                stops: (0..constants::TINTS.len())
+                    .map(|i| {
+                        stop(
+                            constants::TINT_THRESHOLD[i] / 255.0,
+                            constants::TINTS[i].cast::<f32>() / 255.0,
+                        )
+                    })
+                    .collect(),
+            },
+            soil_moisture: ColorRamp {
+                stops: vec![
+                    stop(0.0, constants::BARE_GROUND_COLOR),
+                    stop(1.0, constants::WATER_COLOR),
+                ],
+            },
+            temperature: ColorRamp {
+                stops: vec![
+                    stop(0.0, constants::COLD_COLOR),
+                    stop(1.0, constants::HOT_COLOR),
+                ],
+            },
+        }
+    }
+}
+
+impl Default for SpeciesConfig {
+    fn default() -> Self {
+        SpeciesConfig {
+            trees: vec![SpeciesParams::red_maple()],
+            bushes: SpeciesParams::rhododendron(),
+            grasses: SpeciesParams::switchgrass(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_wind_rose, BoundaryMode, Browsing, BrowsingRegion, ColorRamp, ColorRamps, ColorStop,
+        Config, IlluminationMethod, Initializer, SpeciesParams, Spring, Strata, Stratum,
+        TimeResolution, WindRoseEntry,
+    };
+    use nalgebra::Vector3;
+
+    use crate::{constants, ecology::CellIndex};
+
+    #[test]
+    fn test_parse_empty_config() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.seed, None);
+        assert_eq!(config.initializer, Initializer::HeightMap);
+        assert_eq!(config.height_map_path, constants::IMPORT_FILE_PATH);
+        assert_eq!(config.location.latitude, constants::LATITUDE);
+        assert_eq!(
+            config.climate.monthly_temperatures,
+            constants::AVERAGE_MONTHLY_TEMPERATURES
+        );
+        assert_eq!(config.critical_angles.sand, constants::CRITICAL_ANGLE_SAND);
+        assert_eq!(config.species.trees, vec![SpeciesParams::red_maple()]);
+        assert!(config.wind.rose.is_empty());
+        assert_eq!(config.time_resolution, TimeResolution::Year);
+        assert_eq!(config.illumination_method, IlluminationMethod::RayTraced);
+        assert_eq!(config.boundary, BoundaryMode::Open);
+        assert!(config.strata.is_none());
+        assert!(config.browsing.is_none());
+    }
+
+    #[test]
+    fn test_parse_spring_outside_grid() {
+        let spring = |x: usize, y: usize| {
+            Config::parse(&format!(
+                r#"
+                [[springs]]
+                cell = [{x}, {y}]
+                flow = 500.0
+                "#
+            ))
+        };
+        let last = constants::AREA_SIDE_LENGTH - 1;
+        assert!(spring(last, last).is_ok());
+        assert!(spring(last + 1, 0).is_err());
+        assert!(spring(0, last + 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_wind_season_month_out_of_range() {
+        let season = |month: usize| {
+            Config::parse(&format!(
+                r#"
+                [[wind.seasons]]
+                months = [0, {month}]
+                rose = []
+                "#
+            ))
+        };
+        assert!(season(11).is_ok());
+        assert!(season(12).is_err());
+    }
+
+    #[test]
+    fn test_get_months() {
+        let year = TimeResolution::Year.get_months(3);
+        assert_eq!(year.len(), 12);
+        assert!(year.iter().all(|(_, fraction)| *fraction == 1.0));
+
+        // time steps start at 1 and wrap around every year
+        assert_eq!(TimeResolution::Month.get_months(1), vec![(0, 1.0)]);
+        assert_eq!(TimeResolution::Month.get_months(14), vec![(1, 1.0)]);
+
+        assert_eq!(TimeResolution::Day.get_months(31), vec![(0, 1.0 / 31.0)]);
+        assert_eq!(TimeResolution::Day.get_months(32), vec![(1, 1.0 / 28.0)]);
+        assert_eq!(TimeResolution::Day.get_months(365), vec![(11, 1.0 / 31.0)]);
+        assert_eq!(TimeResolution::Day.get_months(366), vec![(0, 1.0 / 31.0)]);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            r#"
+            seed = 7
+            initializer = "dunes"
+            time_resolution = "month"
+            illumination_method = "horizon_map"
+            boundary = "torus"
+
+            [location]
+            latitude = 64.1
+            longitude = -21.9
+            timezone = 0
+
+            [climate]
+            monthly_rainfall = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0]
+
+            [[wind.rose]]
+            direction = 90.0
+            min_speed = 5.0
+            max_speed = 15.0
+
+            [[wind.rose]]
+            direction = 180.0
+            min_speed = 10.0
+            max_speed = 20.0
+            weight = 0.5
+
+            [[wind.seasons]]
+            months = [11, 0, 1]
+
+            [[wind.seasons.rose]]
+            direction = 0.0
+            min_speed = 20.0
+            max_speed = 30.0
+
+            [fauna]
+            initial_grazers = 40
+
+            [[strata.layers]]
+            thickness = 20.0
+            hardness = 0.5
+
+            [[strata.layers]]
+            thickness = 5.0
+            hardness = 8.0
+
+            [browsing]
+            pressure = 0.4
+
+            [[browsing.regions]]
+            min = [10, 10]
+            max = [19, 19]
+            pressure = 0.0
+
+            [storms]
+            track_width = 300.0
+
+            [rain_cells]
+            contrast = 0.5
+
+            [[springs]]
+            cell = [20, 80]
+            flow = 500.0
+
+            [[color_ramps.temperature.stops]]
+            value = 0.0
+            color = [0.0, 0.0, 1.0]
+
+            [[color_ramps.temperature.stops]]
+            value = 0.5
+            color = [1.0, 1.0, 1.0]
+
+            [[color_ramps.temperature.stops]]
+            value = 1.0
+            color = [1.0, 0.0, 0.0]
+
+            [species.grasses]
+            temperature_limit_min = -20.0
+            temperature_ideal_min = -5.0
+            temperature_ideal_max = 15.0
+            temperature_limit_max = 25.0
+            moisture_limit_min = 0.05
+            moisture_ideal_min = 0.2
+            moisture_ideal_max = 0.6
+            moisture_limit_max = 0.8
+            illumination_limit_min = 4.0
+            illumination_ideal_min = 6.0
+            illumination_ideal_max = 8.0
+            illumination_limit_max = 14.0
+
+            [[species.trees]]
+            name = "oak"
+            temperature_limit_min = -20.0
+            temperature_ideal_min = 5.0
+            temperature_ideal_max = 30.0
+            temperature_limit_max = 40.0
+            moisture_limit_min = 0.1
+            moisture_ideal_min = 0.2
+            moisture_ideal_max = 0.4
+            moisture_limit_max = 0.6
+            illumination_limit_min = 2.0
+            illumination_ideal_min = 6.0
+            illumination_ideal_max = 12.0
+            illumination_limit_max = 14.0
+            establishment_rate = 0.1
+            growth_rate = 0.25
+
+            [[species.trees]]
+            name = "pine"
+            temperature_limit_min = -40.0
+            temperature_ideal_min = -5.0
+            temperature_ideal_max = 25.0
+            temperature_limit_max = 35.0
+            moisture_limit_min = 0.05
+            moisture_ideal_min = 0.1
+            moisture_ideal_max = 0.3
+            moisture_limit_max = 0.5
+            illumination_limit_min = 3.0
+            illumination_ideal_min = 6.0
+            illumination_ideal_max = 12.0
+            illumination_limit_max = 14.0
+            shade_tolerance = 0.2
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.seed, Some(7));
+        assert_eq!(config.initializer, Initializer::Dunes);
+        assert_eq!(config.time_resolution, TimeResolution::Month);
+        assert_eq!(config.illumination_method, IlluminationMethod::HorizonMap);
+        assert_eq!(config.boundary, BoundaryMode::Torus);
+        assert_eq!(config.location.latitude, 64.1);
+        assert_eq!(config.location.timezone, 0);
+        assert_eq!(config.climate.monthly_rainfall[11], 12.0);
+        // unspecified fields keep their defaults
+        assert_eq!(
+            config.climate.monthly_temperatures,
+            constants::AVERAGE_MONTHLY_TEMPERATURES
+        );
+        assert_eq!(config.species.grasses.temperature_limit_min, -20.0);
+        // tree species replace the default table
+        assert_eq!(config.species.trees.len(), 2);
+        assert_eq!(config.species.trees[0].name, "oak");
+        assert_eq!(config.species.trees[0].growth_rate, 0.25);
+        assert_eq!(config.species.trees[1].temperature_limit_min, -40.0);
+        assert_eq!(config.species.trees[1].establishment_rate, 0.0);
+        assert_eq!(config.species.trees[1].windthrow_threshold, 30.0);
+        assert_eq!(config.species.trees[1].shade_tolerance, 0.2);
+        assert_eq!(config.species.trees[0].shade_tolerance, 0.0);
+        assert_eq!(config.fauna.initial_grazers, 40);
+        let strata = config.strata.unwrap();
+        assert_eq!(strata.layers.len(), 2);
+        assert_eq!(strata.warp, 0.0);
+        let browsing = config.browsing.unwrap();
+        assert_eq!(browsing.pressure, 0.4);
+        assert_eq!(
+            browsing.grass_consumption,
+            constants::BROWSING_GRASS_CONSUMPTION
+        );
+        assert_eq!(browsing.regions.len(), 1);
+        let storms = config.storms.unwrap();
+        assert_eq!(storms.track_width, 300.0);
+        assert_eq!(storms.monthly_storm_days, constants::MONTHLY_STORM_DAYS);
+        assert_eq!(
+            config.climate.lightning_strikes,
+            constants::LIGHTNING_STRIKES
+        );
+        let rain_cells = config.rain_cells.unwrap();
+        assert_eq!(rain_cells.contrast, 0.5);
+        assert_eq!(rain_cells.size, constants::RAIN_CELL_SIZE);
+        assert_eq!(config.fauna.max_grazers, constants::MAX_GRAZERS);
+        assert_eq!(
+            config.springs,
+            vec![Spring {
+                cell: [20, 80],
+                flow: 500.0
+            }]
+        );
+
+        let wind_rose = config.wind.build_wind_rose();
+        assert_eq!(wind_rose.weights, [0.0, 0.0, 1.0, 0.0, 0.5, 0.0, 0.0, 0.0]);
+        assert_eq!(wind_rose.min_speed[2], 5.0);
+        assert_eq!(wind_rose.max_speed[4], 20.0);
+
+        // winter months use the season's rose and the others the yearly one
+        let monthly_wind_roses = config.wind.build_monthly_wind_roses(&wind_rose);
+        assert_eq!(monthly_wind_roses.len(), 12);
+        assert_eq!(
+            monthly_wind_roses[0].weights,
+            [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(monthly_wind_roses[11].min_speed[0], 20.0);
+        assert_eq!(monthly_wind_roses[6], wind_rose);
+
+        // color ramps replace their defaults one by one
+        assert_eq!(config.color_ramps.temperature.stops.len(), 3);
+        assert_eq!(
+            config.color_ramps.hypsometric,
+            ColorRamps::default().hypsometric
+        );
+    }
+
+    #[test]
+    fn test_build_wind_rose() {
+        let entry = |direction| WindRoseEntry {
+            direction,
+            min_speed: 1.0,
+            max_speed: 2.0,
+            weight: 1.0,
+        };
+        let wind_rose = build_wind_rose(&[entry(360.0), entry(-90.0), entry(765.0)]);
+        assert_eq!(wind_rose.weights, [1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_get_browsing_pressure() {
+        let browsing = Browsing {
+            pressure: 0.6,
+            regions: vec![
+                BrowsingRegion {
+                    min: [0, 0],
+                    max: [9, 9],
+                    pressure: 0.0,
+                },
+                // a pasture inside the exclosure
+                BrowsingRegion {
+                    min: [5, 5],
+                    max: [6, 6],
+                    pressure: 2.0,
+                },
+            ],
+            ..Browsing::default()
+        };
+        assert_eq!(browsing.get_pressure(CellIndex::new(20, 20)), 0.6);
+        assert_eq!(browsing.get_pressure(CellIndex::new(9, 0)), 0.0);
+        assert_eq!(browsing.get_pressure(CellIndex::new(10, 0)), 0.6);
+        // later regions win and pressure is capped at 1
+        assert_eq!(browsing.get_pressure(CellIndex::new(5, 6)), 1.0);
+    }
+
+    #[test]
+    fn test_get_strata_hardness() {
+        let index = CellIndex::new(10, 10);
+        let strata = Strata {
+            layers: vec![
+                Stratum {
+                    thickness: 20.0,
+                    hardness: 0.5,
+                },
+                Stratum {
+                    thickness: 5.0,
+                    hardness: 8.0,
+                },
+            ],
+            ..Strata::default()
+        };
+        let noise = strata.get_noise();
+        assert_eq!(strata.get_hardness(&noise, index, 10.0), 0.5);
+        assert_eq!(strata.get_hardness(&noise, index, 22.0), 8.0);
+        // the layers repeat upwards
+        assert_eq!(strata.get_hardness(&noise, index, 35.0), 0.5);
+        assert_eq!(strata.get_hardness(&noise, index, 124.0), 8.0);
+
+        // noise layers are uniform within a layer and stay within the hardness range
+        let strata = Strata {
+            warp: 0.0,
+            ..Strata::default()
+        };
+        let noise = strata.get_noise();
+        let [min, max] = strata.noise_hardness;
+        let layer = strata.noise_thickness;
+        for i in 0..20 {
+            let bottom = strata.get_hardness(&noise, index, i as f32 * layer + 0.1);
+            let top = strata.get_hardness(&noise, index, (i + 1) as f32 * layer - 0.1);
+            assert_eq!(bottom, top);
+            assert!((min..=max).contains(&bottom), "{bottom} out of range");
+        }
+    }
+
+    #[test]
+    fn test_sample_color_ramp() {
+        let ramp = ColorRamp {
+            stops: vec![
+                ColorStop {
+                    value: 0.25,
+                    color: [0.0, 0.0, 0.0],
+                },
+                ColorStop {
+                    value: 0.5,
+                    color: [1.0, 0.5, 0.0],
+                },
+                ColorStop {
+                    value: 1.0,
+                    color: [1.0, 1.0, 1.0],
+                },
+            ],
+        };
+        assert_eq!(ramp.sample(0.375), Vector3::new(0.5, 0.25, 0.0));
+        assert_eq!(ramp.sample(0.75), Vector3::new(1.0, 0.75, 0.5));
+        // values past the ends take the color of the closest stop
+        assert_eq!(ramp.sample(-1.0), Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(ramp.sample(2.0), Vector3::new(1.0, 1.0, 1.0));
+        assert_eq!(ColorRamp { stops: vec![] }.sample(0.5), Vector3::zeros());
+    }
+}
@@ -1,83 +1,220 @@
 use nalgebra::Vector3;
 
-pub(crate) const SCREEN_WIDTH: usize = 900;
-pub(crate) const SCREEN_HEIGHT: usize = 700;
-pub(crate) const SPEED: f32 = AREA_SIDE_LENGTH as f32;
-
-pub(crate) const AREA_SIDE_LENGTH: usize = 100; // in cells
-pub(crate) const CELL_SIDE_LENGTH: f32 = 10.0; // in meters
-pub(crate) const DEFAULT_BEDROCK_HEIGHT: f32 = 100.0; // in meters
-
-pub(crate) const IMPORT_FILE_PATH: &str = "./resources/height_maps/berkshires_2-100.png";
+use crate::units::{KilogramsPerCubicMeter, KilogramsPerSquareMeter};
+
+pub const SCREEN_WIDTH: usize = 900;
+pub const SCREEN_HEIGHT: usize = 700;
+// default size of screenshots, rendered off-screen regardless of the window size
+pub const SCREENSHOT_WIDTH: u32 = 3840;
+pub const SCREENSHOT_HEIGHT: u32 = 2160;
+pub const SPEED: f32 = AREA_SIDE_LENGTH as f32;
+// radians of rotation per pixel the mouse is dragged
+pub const MOUSE_SENSITIVITY: f32 = 0.005;
+// fraction of the distance to the orbit point covered by one scroll
+pub const ZOOM_SPEED: f32 = 0.1;
+// milliseconds of each frame the viewer spends ray tracing sunlight after terrain changes
+pub const SUNLIGHT_MILLIS_PER_FRAME: u64 = 10;
+// number of time steps the viewer can undo
+pub const HISTORY_LENGTH: usize = 20;
+// time steps per second the viewer takes while running, and the range the speed keys change it within
+pub const DEFAULT_STEPS_PER_SECOND: f32 = 10.0;
+pub const MIN_STEPS_PER_SECOND: f32 = 0.125;
+pub const MAX_STEPS_PER_SECOND: f32 = 80.0;
+
+pub const AREA_SIDE_LENGTH: usize = 100; // in cells
+pub const CELL_SIDE_LENGTH: f32 = 10.0; // in meters
+pub const DEFAULT_BEDROCK_HEIGHT: f32 = 100.0; // in meters
+
+pub const IMPORT_FILE_PATH: &str = "./resources/height_maps/berkshires_2-100.png";
 // how to convert from import pixel intensity to height
-pub(crate) const HEIGHT_SCALING_FACTOR: f32 = AREA_SIDE_LENGTH as f32 / 255.0 / 5.0;
+pub const HEIGHT_SCALING_FACTOR: f32 = AREA_SIDE_LENGTH as f32 / 255.0 / 5.0;
 
 // how many units of height correspond to one unit in the z direction
-pub(crate) const HEIGHT_RENDER_SCALE: f32 = 1.0;
+pub const HEIGHT_RENDER_SCALE: f32 = 1.0;
 
 // Providence RI
-pub(crate) const LATITUDE: f32 = 41.8;
-pub(crate) const LONGITUDE: f32 = -71.4;
-pub(crate) const TIMEZONE: i32 = -5;
+pub const LATITUDE: f32 = 41.8;
+pub const LONGITUDE: f32 = -71.4;
+pub const TIMEZONE: i32 = -5;
 
 // https://en.wikipedia.org/wiki/Angle_of_repose#Of_various_materials
-pub(crate) const CRITICAL_ANGLE_ROCK: f32 = 45.0;
-pub(crate) const CRITICAL_ANGLE_SAND: f32 = 34.0;
-pub(crate) const CRITICAL_ANGLE_SAND_WITH_VEGETATION: f32 = 45.0;
-pub(crate) const CRITICAL_ANGLE_HUMUS: f32 = 40.0;
-
-pub(crate) const SIDE_LENGTH: f32 = CELL_SIDE_LENGTH * AREA_SIDE_LENGTH as f32 / 1000.0; // in km
-pub(crate) const AREA: f32 = SIDE_LENGTH * SIDE_LENGTH; // in km^2
-pub(crate) const NUM_CELLS: usize = AREA_SIDE_LENGTH * AREA_SIDE_LENGTH;
+// angle of scree, boulders interlock into steeper piles and gravel rolls out into gentler ones
+pub const CRITICAL_ANGLE_ROCK: f32 = 45.0;
+pub const CRITICAL_ANGLE_BOULDERS: f32 = 55.0;
+pub const CRITICAL_ANGLE_GRAVEL: f32 = 38.0;
+pub const CRITICAL_ANGLE_SAND: f32 = 34.0;
+pub const CRITICAL_ANGLE_SAND_WITH_VEGETATION: f32 = 45.0;
+pub const CRITICAL_ANGLE_HUMUS: f32 = 40.0;
+// angle of a settled snowpack, most slab avalanches release between 30 and 45 degrees
+pub const CRITICAL_ANGLE_SNOW: f32 = 40.0;
+// share of the critical angles of sand and humus lost once water fills their pores
+pub const CRITICAL_ANGLE_SATURATED_LOSS: f32 = 0.25;
+
+pub const SIDE_LENGTH: f32 = CELL_SIDE_LENGTH * AREA_SIDE_LENGTH as f32 / 1000.0; // in km
+pub const AREA: f32 = SIDE_LENGTH * SIDE_LENGTH; // in km^2
+pub const NUM_CELLS: usize = AREA_SIDE_LENGTH * AREA_SIDE_LENGTH;
 // const AREA_SIZE: f32 = (CELL_SIDE_LENGTH * CELL_SIDE_LENGTH) * NUM_CELLS as f32 / 1000000.0; // in km^3
 
 // https://www.sciencedirect.com/science/article/pii/S2351989421002973
-// density of highland grasses
-pub(crate) const GRASS_DENSITY: f32 = 1.0; // kg/m^3
+// biomass of highland grasses fully covering the ground
+pub const GRASS_DENSITY: KilogramsPerSquareMeter = KilogramsPerSquareMeter(1.0);
+pub const WATER_DENSITY: KilogramsPerCubicMeter = KilogramsPerCubicMeter(1000.0);
 
 // constants used for simple renderer
-pub(crate) const BEDROCK_COLOR: Vector3<f32> = Vector3::new(0.2, 0.2, 0.2);
-pub(crate) const ROCK_COLOR: Vector3<f32> = Vector3::new(0.4, 0.4, 0.4);
-pub(crate) const SAND_COLOR: Vector3<f32> = Vector3::new(0.76078, 0.69804, 0.50196);
-pub(crate) const HUMUS_COLOR: Vector3<f32> = Vector3::new(0.46274, 0.33333, 0.16863);
-pub(crate) const TREES_COLOR: Vector3<f32> = Vector3::new(0.22745, 0.30980, 0.24706);
-pub(crate) const BUSHES_COLOR: Vector3<f32> = Vector3::new(0.2, 0.2, 0.2);
-pub(crate) const GRASS_COLOR: Vector3<f32> = Vector3::new(0.0, 0.4, 0.1); //150,190,101
-pub(crate) const DEAD_COLOR: Vector3<f32> = Vector3::new(0.25098, 0.16078, 0.01961);
+pub const BEDROCK_COLOR: Vector3<f32> = Vector3::new(0.2, 0.2, 0.2);
+pub const ROCK_COLOR: Vector3<f32> = Vector3::new(0.4, 0.4, 0.4);
+pub const BOULDER_COLOR: Vector3<f32> = Vector3::new(0.3, 0.3, 0.32);
+pub const GRAVEL_COLOR: Vector3<f32> = Vector3::new(0.5, 0.48, 0.45);
+pub const SAND_COLOR: Vector3<f32> = Vector3::new(0.76078, 0.69804, 0.50196);
+pub const HUMUS_COLOR: Vector3<f32> = Vector3::new(0.46274, 0.33333, 0.16863);
+pub const TREES_COLOR: Vector3<f32> = Vector3::new(0.22745, 0.30980, 0.24706);
+pub const BUSHES_COLOR: Vector3<f32> = Vector3::new(0.2, 0.2, 0.2);
+pub const GRASS_COLOR: Vector3<f32> = Vector3::new(0.0, 0.4, 0.1); //150,190,101
+pub const CURED_GRASS_COLOR: Vector3<f32> = Vector3::new(0.6, 0.5, 0.25);
+pub const DEAD_COLOR: Vector3<f32> = Vector3::new(0.25098, 0.16078, 0.01961);
+pub const FIRE_COLOR: Vector3<f32> = Vector3::new(0.9, 0.25, 0.0);
+pub const WATER_COLOR: Vector3<f32> = Vector3::new(0.15, 0.35, 0.6);
+pub const SNOW_COLOR: Vector3<f32> = Vector3::new(0.95, 0.95, 0.98);
+// translucency of the water surface drawn over the terrain
+pub const MIN_WATER_ALPHA: f32 = 0.35; // of the shallowest water
+pub const MAX_WATER_ALPHA: f32 = 0.8;
+pub const WATER_OPAQUE_DEPTH: f32 = 1.0; // in m, depth at which water reaches the max alpha
+pub const WET_SOIL_ALPHA: f32 = 0.25; // of the film over saturated soil
+pub const WET_SOIL_MOISTURE: f32 = 35.0; // % by volume, where the film starts to show
+pub const SATURATED_SOIL_MOISTURE: f32 = 50.0; // % by volume
+pub const WATER_SURFACE_OFFSET: f32 = 0.02; // in render units above the water or ground
+pub const BARE_GROUND_COLOR: Vector3<f32> = Vector3::new(0.85, 0.8, 0.65);
+pub const DENSE_CANOPY_COLOR: Vector3<f32> = Vector3::new(0.0, 0.2, 0.05);
+pub const COLD_COLOR: Vector3<f32> = Vector3::new(0.1, 0.2, 0.9);
+pub const HOT_COLOR: Vector3<f32> = Vector3::new(0.9, 0.1, 0.1);
+// temperatures (in celsius) shown as fully cold and fully hot
+pub const MIN_DISPLAYED_TEMPERATURE: f32 = -20.0;
+pub const MAX_DISPLAYED_TEMPERATURE: f32 = 35.0;
+// difference (in celsius) from the lapse rate temperature shown as fully cold or hot
+pub const MAX_DISPLAYED_MICROCLIMATE_OFFSET: f32 = 5.0;
+pub const RUNOFF_COLOR: Vector3<f32> = Vector3::new(0.1, 0.5, 1.0);
+
+// sediment provenance is tracked by region, in a square grid of regions over the map
+pub const PROVENANCE_REGIONS_PER_SIDE: usize = 2;
+// color of each region, row by row from the origin
+pub const PROVENANCE_COLORS: [Vector3<f32>; PROVENANCE_REGIONS_PER_SIDE
+    * PROVENANCE_REGIONS_PER_SIDE] = [
+    Vector3::new(0.9, 0.2, 0.2),
+    Vector3::new(0.2, 0.7, 0.2),
+    Vector3::new(0.2, 0.3, 0.9),
+    Vector3::new(0.9, 0.8, 0.1),
+];
+
+// arrows of the wind overlay, one per block of cells of this side
+pub const WIND_ARROW_BLOCK_SIZE: usize = 8;
+// wind strength of the longest arrow
+pub const MAX_DISPLAYED_WIND_STRENGTH: f32 = 30.0;
+// render units the arrows float above the terrain
+pub const WIND_ARROW_OFFSET: f32 = 0.5;
+
+pub const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+// sun drawn by the shadows color mode, in render units
+pub const SUN_COLOR: Vector3<f32> = Vector3::new(1.0, 0.85, 0.3);
+pub const SUN_DISTANCE: f32 = AREA_SIDE_LENGTH as f32;
+pub const SUN_DIAMETER: f32 = AREA_SIDE_LENGTH as f32 / 16.0;
+// texels on each side of the depth texture the terrain is drawn into from the sun
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+pub const SHADOW_MAP_TEXTURE_UNIT: u32 = 1;
+pub const RIPPLE_MAP_TEXTURE_UNIT: u32 = 2;
+
+// light of the hillshade color mode, from the northwest like on most maps
+pub const DEFAULT_HILLSHADE_AZIMUTH: f32 = 315.0; // degrees clockwise from north
+pub const DEFAULT_HILLSHADE_ELEVATION: f32 = 45.0; // degrees above the horizon
 
 // constants used for hypsometric tint
-pub(crate) const TINTS: [Vector3<u8>; 4] = [
+pub const TINTS: [Vector3<u8>; 4] = [
     Vector3::new(150, 170, 101),
     Vector3::new(234, 225, 148),
     Vector3::new(146, 109, 61),
     Vector3::new(199, 196, 195),
 ];
-pub(crate) const TINT_THRESHOLD: [f32; 4] = [0.0, 60.0, 180.0, 255.0];
+pub const TINT_THRESHOLD: [f32; 4] = [0.0, 60.0, 180.0, 255.0];
 
-//pub(crate) const AVERAGE_TEMPERATURE: f32 = 15.0; // in celsius
+//pub const AVERAGE_TEMPERATURE: f32 = 15.0; // in celsius
 // https://en.climate-data.org/north-america/united-states-of-america/rhode-island/providence-1723/
-pub(crate) const AVERAGE_MONTHLY_TEMPERATURES: [f32; 12] = [
+pub const AVERAGE_MONTHLY_TEMPERATURES: [f32; 12] = [
     -2.0, -0.8, 2.8, 8.8, 14.3, 19.2, 23.0, 22.3, 18.7, 12.5, 6.7, 1.5,
 ]; // in celsius
-pub(crate) const AVERAGE_SUNLIGHT_HOURS: [f32; 12] = [
+pub const AVERAGE_SUNLIGHT_HOURS: [f32; 12] = [
     6.75, 6.75, 8.25, 9.75, 10.5, 11.25, 11.25, 10.5, 9.75, 9.0, 7.5, 7.5,
 ];
-pub(crate) const AVERAGE_MONTHLY_RAINFALL: [f32; 12] = [
+pub const AVERAGE_MONTHLY_RAINFALL: [f32; 12] = [
     96.0, 81.0, 111.0, 99.0, 86.0, 91.0, 87.0, 103.0, 93.0, 106.0, 88.0, 110.0,
 ]; // in mm per month
    // modifier on sunlight hours when ray-traced to account for cloud coverage
-pub(crate) const PERCENT_SUNNY_DAYS: f32 = 0.75;
+pub const PERCENT_SUNNY_DAYS: f32 = 0.75;
+// based on ~10 lightning strikes per km per year
+// https://www.sciencedirect.com/science/article/pii/S0169555X13003929
+pub const LIGHTNING_STRIKES: f32 = 20.0; // most strikes per square km per year
 
-pub(crate) const DEFAULT_HUMUS_HEIGHT: f32 = 0.5;
+pub const DEFAULT_HUMUS_HEIGHT: f32 = 0.5;
 
 
-pub(crate) const PER_CELL_RAINFALL: f32 = 1151.0;
+pub const PER_CELL_RAINFALL: f32 = 1151.0;
 
 //Sediment constants idk ask stupid Musgrave
-pub(crate) const KC: f32 = 5.0;
-pub(crate) const KD: f32 = 0.1;
-pub(crate) const KS: f32 = 0.3;
-
-pub(crate) const WIND_DIRECTION: f32 = 45.0; // degrees from north
-pub(crate) const WIND_STRENGTH: f32 = 10.0;
-
+pub const KC: f32 = 5.0;
+pub const KD: f32 = 0.1;
+pub const KS: f32 = 0.3;
+// bedrock incision per pass per sqrt(m^3) of discharge at a slope of 1
+pub const KI: f32 = 0.0005;
+
+// coast, used when the config sets a sea level
+pub const WAVE_EROSION: f32 = 0.1; // in m per year
+pub const SALT_SPRAY_HEIGHT: f32 = 2.0; // in m above sea level
+
+// stratified bedrock, used when the config has strata
+pub const STRATUM_THICKNESS: f32 = 5.0; // in m
+pub const STRATUM_HARDNESS: [f32; 2] = [0.5, 4.0];
+pub const STRATA_NOISE_SCALE: f32 = 200.0; // in m
+
+// browsing deer or sheep, used when the config has browsing
+pub const BROWSING_PRESSURE: f32 = 0.5;
+pub const BROWSING_GRASS_CONSUMPTION: f32 = 0.2; // grass coverage per year under full pressure
+
+// thunderstorms, used when the config has storms
+pub const MONTHLY_STORM_DAYS: [f32; 12] = [0.0, 0.0, 0.5, 1.0, 2.0, 4.0, 5.0, 4.0, 2.0, 0.5, 0.0, 0.0];
+pub const STORM_TRACK_WIDTH: f32 = 200.0; // in m
+pub const STORM_RAINFALL: f32 = 20.0; // in mm per storm
+
+// uneven rainfall, used when the config has rain cells
+pub const RAIN_CELL_SIZE: f32 = 300.0; // in m
+pub const RAIN_CELL_CONTRAST: f32 = 2.0;
+pub const RAIN_CELL_DRIFT: f32 = 500.0; // in m per time step at the default wind strength
+
+pub const WIND_DIRECTION: f32 = 45.0; // degrees from north
+pub const WIND_STRENGTH: f32 = 10.0;
+
+
+// grazing herbivores, none unless set in the config
+pub const INITIAL_GRAZERS: usize = 0;
+pub const MAX_GRAZERS: usize = 500;
+pub const GRASS_APPETITE: f32 = 0.5; // grass coverage eaten per grazer per time step
+pub const BUSH_APPETITE: f32 = 20.0; // kg of bushes eaten per grazer per time step
+pub const TRAMPLING: f32 = 0.002; // m of humus compacted per grazer visit
+
+// level of detail of the terrain mesh
+pub const LOD_PATCH_SIZE: usize = 16; // in cells along each side
+pub const MAX_LOD_LEVEL: usize = 3; // every 8th vertex
+pub const LOD_DISTANCE: f32 = 150.0; // in render units from the camera where patches coarsen
+// how much the variance of the heights of a patch keeps it fine, per render unit squared
+pub const LOD_VARIANCE_WEIGHT: f32 = 1.0;
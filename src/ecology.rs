@@ -1,34 +1,113 @@
 use bvh::bvh::Bvh;
 use nalgebra::Vector3;
 use rand::Rng;
+use rand::{rngs::StdRng, SeedableRng};
 use noise::{core::perlin, NoiseFn, Perlin, Seedable};
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    config::{BoundaryMode, Climate, Config},
     constants,
-    events::wind::{WindRose, WindState},
+    events::{
+        audit::MassAudit,
+        fauna::Grazer,
+        log::EventLog,
+        nutrients,
+        rainfall::RunoffTrace,
+        storm::Storm,
+        vegetation::Individualized,
+        wind::{WindRose, WindState},
+        Events,
+    },
+    units::{KilogramsPerCubicMeter, Liters, Meters},
 };
 use std::{
+    collections::HashSet,
     fmt,
     ops::{Index, IndexMut},
+    sync::OnceLock,
 };
 
-use self::illumination::CellTetrahedron;
+use self::{
+    illumination::CellTetrahedron, progressive_sunlight::ProgressiveSunlight,
+    provenance::Provenance, succession::Succession,
+};
 
-mod illumination;
+pub mod biome;
+mod checkpoint;
+pub mod diff;
+pub mod horizon_map;
+pub mod illumination;
 mod initializer;
+mod microclimate;
+pub mod progressive_sunlight;
+pub mod provenance;
+pub mod succession;
 
+#[derive(Serialize, Deserialize)]
 pub struct Ecosystem {
-    // Array of structs
-    pub(crate) cells: Vec<Vec<Cell>>,
-    pub(crate) tets: Vec<CellTetrahedron>,
-    pub(crate) bvh: Option<Bvh<f32, 3>>,
-    pub(crate) wind_state: Option<WindState>,
+    // Array of structs, stored row by row so cell (x, y) is at x + y * AREA_SIDE_LENGTH
+    pub cells: Vec<Cell>,
+    // tets and bvh are derived from the cells and rebuilt on load
+    #[serde(skip)]
+    pub tets: Vec<CellTetrahedron>,
+    #[serde(skip)]
+    pub bvh: Option<Bvh<f32, 3>>,
+    // cells whose terrain may have changed since the tets were last updated
+    #[serde(skip)]
+    pub dirty_cells: HashSet<CellIndex>,
+    // cells whose rendering may have changed since the renderer last updated them
+    #[serde(skip)]
+    pub changed_cells: HashSet<CellIndex>,
+    // what the events did during the current time step
+    #[serde(skip)]
+    pub event_log: EventLog,
+    // checks that events conserve material when present
+    #[serde(skip)]
+    pub mass_audit: Option<MassAudit>,
+    // ray traced sunlight comes from the gpu on the render thread when set,
+    // so terrain changes only mark it as stale for that thread to recompute
+    #[serde(skip)]
+    pub sunlight_from_gpu: bool,
+    // whether the terrain changed since ray traced sunlight was last sent by the render thread
+    #[serde(skip)]
+    pub sunlight_stale: bool,
+    // spreads ray tracing sunlight after terrain changes over frames when present
+    #[serde(skip)]
+    pub progressive_sunlight: Option<ProgressiveSunlight>,
+    // water and sediment moved by hydraulic erosion during the last time step
+    #[serde(skip)]
+    pub runoff_trace: Option<RunoffTrace>,
+    // thunderstorms crossing the map during the current time step
+    #[serde(skip)]
+    pub storms: Vec<Storm>,
+    // share of the rainfall of the months each cell gets this time step, empty if rain falls evenly
+    #[serde(skip)]
+    pub rainfall_field: Vec<f32>,
+    // noise of the strata, built the first time the hardness of the bedrock is needed
+    // the strata are fixed for the run, so it never has to be rebuilt
+    #[serde(skip)]
+    pub strata_noise: OnceLock<Perlin>,
+    pub wind_state: Option<WindState>,
+    // region each cell's sand and humus came from, when tracked
+    pub provenance: Option<Provenance>,
+    // biomes each cell went through
+    pub succession: Succession,
+    pub grazers: Vec<Grazer>,
+    pub config: Config,
+    // number of time steps simulated so far
+    pub time_step: u32,
+    // all randomness in the simulation is drawn from rng so runs with the same seed are identical
+    pub seed: u64,
+    // reseeded from seed at the start of every time step, so it does not need to be saved
+    #[serde(skip, default = "init_rng")]
+    pub rng: StdRng,
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
-pub(crate) struct CellIndex {
-    pub(crate) x: usize,
-    pub(crate) y: usize,
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize)]
+pub struct CellIndex {
+    pub x: usize,
+    pub y: usize,
 }
 
 impl fmt::Display for CellIndex {
@@ -53,37 +132,61 @@ impl CellIndex {
         let x = i % constants::AREA_SIDE_LENGTH;
         CellIndex::new(x, y)
     }
+
+    pub fn get_flat_index(&self) -> usize {
+        self.x + self.y * constants::AREA_SIDE_LENGTH
+    }
 }
 
 impl Index<CellIndex> for Ecosystem {
     type Output = Cell;
     fn index(&self, index: CellIndex) -> &Self::Output {
-        &self.cells[index.x][index.y]
+        &self.cells[index.get_flat_index()]
     }
 }
 impl IndexMut<CellIndex> for Ecosystem {
     fn index_mut(&mut self, index: CellIndex) -> &mut Self::Output {
-        &mut self.cells[index.x][index.y]
+        &mut self.cells[index.get_flat_index()]
     }
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct Cell {
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Cell {
     bedrock: Option<Bedrock>,
     rock: Option<Rock>,
     sand: Option<Sand>,
     humus: Option<Humus>,
-    pub(crate) trees: Option<Trees>,
-    pub(crate) bushes: Option<Bushes>,
-    pub(crate) grasses: Option<Grasses>,
+    // at most one entry per species, ordered by species
+    pub trees: Vec<Trees>,
+    pub bushes: Option<Bushes>,
+    pub grasses: Option<Grasses>,
     dead_vegetation: Option<DeadVegetation>,
-
-    pub(crate) soil_moisture: f32,
-    pub(crate) hours_of_sunlight: [f32; 12],
+    // standing surface water, which sits on top of the terrain and is not included in its height
+    water: Option<Water>,
+    // snowpack, which like water is not included in the terrain height
+    snow: Option<Snow>,
+
+    pub soil_moisture: f32,
+    // share of the soil moisture held in the humus, sand, and fractured rock layers, from the top down
+    pub moisture_profile: [f32; 3],
+    pub hours_of_sunlight: [f32; 12],
+    // 1.0 when the cell has just burned, fading back to 0.0 over time
+    pub burn_severity: f32,
+    // volume of surface water that flowed out of this cell during the last time step, in m^3
+    pub water_flow: f32,
+    // plant-available nitrogen in the soil, in kg
+    pub nutrients: f32,
+    // keetch-byram drought index, the soil moisture deficit (in mm) that drives fire danger
+    pub drought_index: f32,
+    // years left of the pest or disease outbreak among the trees of the cell, 0.0 if there is none
+    pub outbreak: f32,
+    // events applied to this cell during the last time step, for debugging
+    #[serde(skip)]
+    pub last_events: Vec<Events>,
 }
 
 #[derive(Clone)]
-pub(crate) enum CellLayer {
+pub enum CellLayer {
     Bedrock(Option<Bedrock>),
     Rock(Option<Rock>),
     Sand(Option<Sand>),
@@ -92,69 +195,208 @@ pub(crate) enum CellLayer {
     Bushes(Option<Bushes>),
     Grasses(Option<Grasses>),
     DeadVegetation(Option<DeadVegetation>),
+    Water(Option<Water>),
+    Snow(Option<Snow>),
 }
 
 // use the methods to access and modify height of these layers
-#[derive(Clone, Debug)]
-pub(crate) struct Bedrock {
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bedrock {
     height: f32,
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct Rock {
-    height: f32,
+// heights of each size class, indexed by RockSize
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rock {
+    heights: [f32; 3],
+}
+
+// size classes of the loose rock, which slide, wash away, and weather differently
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RockSize {
+    Boulders,
+    Scree,
+    Gravel,
+}
+
+pub const ROCK_SIZES: [RockSize; 3] = [RockSize::Boulders, RockSize::Scree, RockSize::Gravel];
+
+impl Rock {
+    fn get_height(&self) -> f32 {
+        self.heights.iter().sum()
+    }
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct Sand {
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sand {
     height: f32,
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct Humus {
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Humus {
     height: f32,
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct Trees {
-    pub(crate) number_of_plants: u32,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trees {
+    // index into the tree species table of the config
+    pub species: usize,
+    // age classes, from the youngest to the oldest
+    pub cohorts: Vec<Cohort>,
+    // months in a row the soil the roots reach has been too dry for the species
+    pub dry_months: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bushes {
+    // age classes, from the youngest to the oldest
+    pub cohorts: Vec<Cohort>,
+    // months in a row the soil the roots reach has been too dry for bushes
+    pub dry_months: f32,
+}
+
+// plants of a cell that established around the same time, which grow and age together
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cohort {
+    pub number_of_plants: u32,
     // height ∝ diameter ^ (2/3) apparently
-    pub(crate) plant_height_sum: f32,
-    pub(crate) plant_age_sum: f32,
+    pub plant_height_sum: f32,
+    pub plant_age_sum: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Grasses {
+    pub coverage_density: f32,
+    // % of the coverage that is green, while the rest is cured for the season
+    pub green_fraction: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeadVegetation {
+    pub biomass: f32, // in kg
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct Bushes {
-    pub(crate) number_of_plants: u32,
-    pub(crate) plant_height_sum: f32,
-    pub(crate) plant_age_sum: f32,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Water {
+    height: f32,
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct Grasses {
-    pub(crate) coverage_density: f32,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snow {
+    height: f32,
+    density: f32, // in kg/m^3
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct DeadVegetation {
-    pub(crate) biomass: f32, // in kg
+fn init_rng() -> StdRng {
+    StdRng::seed_from_u64(0)
 }
 
 impl Ecosystem {
     pub fn init() -> Self {
+        Self::init_with_seed(rand::random())
+    }
+
+    pub fn init_with_seed(seed: u64) -> Self {
         let mut ecosystem = Ecosystem {
-            cells: vec![
-                vec![Cell::init(); constants::AREA_SIDE_LENGTH];
-                constants::AREA_SIDE_LENGTH
-            ],
+            cells: vec![Cell::init(); constants::NUM_CELLS],
             tets: vec![],
             bvh: None,
+            dirty_cells: HashSet::new(),
+            changed_cells: HashSet::new(),
+            event_log: EventLog::default(),
+            mass_audit: None,
+            sunlight_from_gpu: false,
+            sunlight_stale: false,
+            progressive_sunlight: None,
+            runoff_trace: None,
+            storms: vec![],
+            rainfall_field: vec![],
+            strata_noise: OnceLock::new(),
             wind_state: None,
+            provenance: None,
+            succession: Succession::default(),
+            grazers: vec![],
+            config: Config::default(),
+            time_step: 0,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
         };
         ecosystem.init_cell_tets();
         ecosystem
     }
 
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // derives the rng for the current time step from the seed
+    // so a run resumed from a checkpoint continues identically
+    pub fn reseed_for_time_step(&mut self) {
+        self.rng = StdRng::seed_from_u64(self.seed ^ ((self.time_step as u64) << 32));
+    }
+
+    // returns the months covered by the current time step, with the fraction of each month covered
+    pub fn get_months_of_time_step(&self) -> Vec<(usize, f32)> {
+        self.config.time_resolution.get_months(self.time_step)
+    }
+
+    // fraction of a year covered by a time step, which prorates rates given per year
+    pub fn get_fraction_of_year(&self) -> f32 {
+        self.config.time_resolution.get_fraction_of_year()
+    }
+
+    // how many times slower than the default bedrock the top of the bedrock of the cell weathers and erodes
+    pub fn get_bedrock_hardness(&self, index: CellIndex) -> f32 {
+        match &self.config.strata {
+            Some(strata) => {
+                let noise = self.strata_noise.get_or_init(|| strata.get_noise());
+                strata.get_hardness(noise, index, self[index].get_bedrock_height())
+            }
+            None => 1.0,
+        }
+    }
+
+    // returns a summary of everything stored in the cell
+    pub fn describe_cell(&self, index: CellIndex) -> String {
+        let cell = &self[index];
+        let mut description = format!("Cell {index}\n");
+        description += &format!("  height {}\n", cell.get_height());
+        description += &format!("  bedrock {}\n", cell.get_bedrock_height());
+        description += &format!("  rock {}\n", cell.get_rock_height());
+        description += &format!("  sand {}\n", cell.get_sand_height());
+        description += &format!("  humus {}\n", cell.get_humus_height());
+        description += &format!("  water {}\n", cell.get_water_height());
+        description += &format!("  snow {}\n", cell.get_snow_height());
+        description += &format!("  soil moisture {}\n", cell.soil_moisture);
+        description += &format!("  sunlight {:?}\n", cell.hours_of_sunlight);
+        if let Some(fractions) = self.get_provenance_fractions(index) {
+            description += &format!("  sediment from regions {fractions:?}\n");
+        }
+        for trees in &cell.trees {
+            let name = &self.config.species.trees[trees.species].name;
+            for cohort in &trees.cohorts {
+                description += &format!("  trees {name} {}\n", cohort.describe());
+            }
+        }
+        if let Some(bushes) = &cell.bushes {
+            for cohort in &bushes.cohorts {
+                description += &format!("  bushes {}\n", cohort.describe());
+            }
+        }
+        if let Some(grasses) = &cell.grasses {
+            description += &format!("  grass coverage {}\n", grasses.coverage_density);
+        }
+        description += &format!("  dead vegetation {}\n", cell.get_dead_vegetation_biomass());
+        description += &format!("  nutrients {}\n", cell.nutrients);
+        description += &format!("  moisture profile {:?}\n", cell.moisture_profile);
+        description += &format!("  drought index {}\n", cell.drought_index);
+        description += &format!("  burn severity {}\n", cell.burn_severity);
+        description += &format!("  outbreak {}\n", cell.outbreak);
+        description += &format!("  last events {:?}", cell.last_events);
+        description
+    }
+
     fn init_cell_tets(&mut self) {
         for i in 0..constants::AREA_SIDE_LENGTH - 1 {
             for j in 0..constants::AREA_SIDE_LENGTH - 1 {
@@ -165,12 +407,12 @@ impl Ecosystem {
         }
     }
 
-    pub(crate) fn get_normal(&self, index: CellIndex) -> Vector3<f32> {
+    pub fn get_normal(&self, index: CellIndex) -> Vector3<f32> {
         // normal of a vertex is the normalized sum of the normals of the adjacent faces
         // cells are vertices and the triangles formed between the cell and its 4 adjacent cells are faces
 
-        // get neighbors
-        let neighbors = Cell::get_neighbors(&index);
+        // get neighbors, the mesh does not wrap around the edges
+        let neighbors = Cell::get_neighbors(&index, BoundaryMode::Open);
         // get normals of these triangles
         // triangles/faces are center-up-left, center-left-down, center-right-up, center-down-right (ccw winding)
         let mut face_normals = vec![];
@@ -203,9 +445,9 @@ impl Ecosystem {
         normal_sum.normalize()
     }
 
-    pub(crate) fn estimate_curvature(&self, index: CellIndex) -> f32 {
+    pub fn estimate_curvature(&self, index: CellIndex) -> f32 {
         let mut curvatures = vec![];
-        let neighbors = Cell::get_neighbors(&index);
+        let neighbors = Cell::get_neighbors(&index, BoundaryMode::Open);
 
         // get curvature along each edge
         if let Some(up) = neighbors.north {
@@ -232,7 +474,7 @@ impl Ecosystem {
         let n1 = self.get_normal(i1);
         let n2 = self.get_normal(i2);
         let p1 = self.get_position_of_cell(&i1);
-        let p2 = self.get_position_of_cell(&i2);
+        let p2 = self.get_position_of_neighbor(&i1, &i2);
 
         // println!("normals {n1}, {n2}");
         // println!("positions {p1}, {p2}");
@@ -243,23 +485,50 @@ impl Ecosystem {
         // (n2 - n1).dot(&(p2-p1)) / (f32::powf((p2 - p1).norm(),2.0))
     }
 
-    pub(crate) fn get_position_of_cell(&self, index: &CellIndex) -> Vector3<f32> {
+    // returns terrain height of every cell, in the same order as the cells
+    pub fn get_heights(&self) -> Vec<f32> {
+        self.cells.iter().map(|cell| cell.get_height()).collect()
+    }
+
+    pub fn get_position_of_cell(&self, index: &CellIndex) -> Vector3<f32> {
         let cell = &self[*index];
         let height = cell.get_height();
         Vector3::new(index.x as f32, index.y as f32, height)
     }
 
-    pub(crate) fn get_slope_between_points(&self, i1: CellIndex, i2: CellIndex) -> f32 {
+    // position of the neighbor, moved across the edge of a torus to be beside the origin
+    pub fn get_position_of_neighbor(
+        &self,
+        origin: &CellIndex,
+        neighbor: &CellIndex,
+    ) -> Vector3<f32> {
+        let origin_pos = self.get_position_of_cell(origin);
+        let mut pos = self.get_position_of_cell(neighbor);
+        if self.config.boundary == BoundaryMode::Torus {
+            // neighbors across an edge are one cell apart
+            let side = constants::AREA_SIDE_LENGTH as f32;
+            for axis in 0..2 {
+                if pos[axis] - origin_pos[axis] > side / 2.0 {
+                    pos[axis] -= side;
+                } else if origin_pos[axis] - pos[axis] > side / 2.0 {
+                    pos[axis] += side;
+                }
+            }
+        }
+        pos
+    }
+
+    pub fn get_slope_between_points(&self, i1: CellIndex, i2: CellIndex) -> f32 {
         //s(q)=(E(p)−E(q))/∥p−q∥
         let height_1 = self[i1].get_height();
         let height_2 = self[i2].get_height();
         let pos_1 = self.get_position_of_cell(&i1);
-        let pos_2 = self.get_position_of_cell(&i2);
+        let pos_2 = self.get_position_of_neighbor(&i1, &i2);
         (height_1 - height_2) / (pos_1 - pos_2).norm()
     }
 
     // returns angle in degrees
-    pub(crate) fn get_angle(slope: f32) -> f32 {
+    pub fn get_angle(slope: f32) -> f32 {
         if slope < 0.0 {
             let slope = -slope;
             -f32::asin(slope).to_degrees()
@@ -269,10 +538,10 @@ impl Ecosystem {
     }
 
     // gradient at this point
-    pub(crate) fn get_slope_at_point(&self, index: CellIndex) -> f32 {
+    pub fn get_slope_at_point(&self, index: CellIndex) -> f32 {
         // negative slope between points means point 1 is lower than point 2
         // looking for largest slope
-        let neighbors = Cell::get_neighbors(&index);
+        let neighbors = Cell::get_neighbors(&index, self.config.boundary);
         let mut max_slope = f32::MIN;
         for neighbor_index in neighbors.as_array().into_iter().flatten() {
             let slope = self.get_slope_between_points(index, neighbor_index);
@@ -285,7 +554,7 @@ impl Ecosystem {
     }
 }
 
-pub(crate) struct Neighbors {
+pub struct Neighbors {
     northwest: Option<CellIndex>,
     north: Option<CellIndex>,
     northeast: Option<CellIndex>,
@@ -327,10 +596,14 @@ impl Neighbors {
     pub fn len(&self) -> usize {
         self.as_array().iter().filter(|n| n.is_some()).count()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 impl Cell {
-    pub(crate) fn init() -> Self {
+    pub fn init() -> Self {
         Cell {
             soil_moisture: 1.8E5,
             bedrock: Some(Bedrock {
@@ -339,48 +612,52 @@ impl Cell {
             rock: None,
             sand: None,
             humus: None,
-            trees: None,
+            trees: vec![],
             bushes: None,
             grasses: None,
             dead_vegetation: None,
+            water: None,
+            snow: None,
             hours_of_sunlight: constants::AVERAGE_SUNLIGHT_HOURS,
+            burn_severity: 0.0,
+            water_flow: 0.0,
+            nutrients: nutrients::IDEAL_NUTRIENT_DENSITY
+                * constants::CELL_SIDE_LENGTH
+                * constants::CELL_SIDE_LENGTH,
+            drought_index: 0.0,
+            outbreak: 0.0,
+            moisture_profile: [1.0, 0.0, 0.0],
+            last_events: vec![],
         }
     }
-    pub(crate) fn get_neighbors(index: &CellIndex) -> Neighbors {
-        let x = index.x;
-        let y = index.y;
-
-        let mut neighbors = Neighbors::init();
-
-        if x > 0 {
-            neighbors.west = Some(CellIndex { x: x - 1, y });
-            if y > 0 {
-                neighbors.northwest = Some(CellIndex { x: x - 1, y: y - 1 });
-            }
-            if y < constants::AREA_SIDE_LENGTH - 1 {
-                neighbors.southwest = Some(CellIndex { x: x - 1, y: y + 1 });
-            }
-        }
-        if x < constants::AREA_SIDE_LENGTH - 1 {
-            neighbors.east = Some(CellIndex { x: x + 1, y });
-            if y > 0 {
-                neighbors.northeast = Some(CellIndex { x: x + 1, y: y - 1 });
-            }
-            if y < constants::AREA_SIDE_LENGTH - 1 {
-                neighbors.southeast = Some(CellIndex { x: x + 1, y: y + 1 });
+    pub fn get_neighbors(index: &CellIndex, boundary: BoundaryMode) -> Neighbors {
+        let side = constants::AREA_SIDE_LENGTH as i32;
+        // cell offset by dx and dy, wrapping around the edges of a torus
+        let get_neighbor = |dx: i32, dy: i32| {
+            let x = index.x as i32 + dx;
+            let y = index.y as i32 + dy;
+            if boundary == BoundaryMode::Torus {
+                Some(CellIndex::new(x.rem_euclid(side) as usize, y.rem_euclid(side) as usize))
+            } else if (0..side).contains(&x) && (0..side).contains(&y) {
+                Some(CellIndex::new(x as usize, y as usize))
+            } else {
+                None
             }
         };
-        if y > 0 {
-            neighbors.north = Some(CellIndex { x, y: y - 1 });
-        }
-        if y < constants::AREA_SIDE_LENGTH - 1 {
-            neighbors.south = Some(CellIndex { x, y: y + 1 });
-        }
 
-        neighbors
+        Neighbors {
+            northwest: get_neighbor(-1, -1),
+            north: get_neighbor(0, -1),
+            northeast: get_neighbor(1, -1),
+            west: get_neighbor(-1, 0),
+            east: get_neighbor(1, 0),
+            southwest: get_neighbor(-1, 1),
+            south: get_neighbor(0, 1),
+            southeast: get_neighbor(1, 1),
+        }
     }
 
-    pub(crate) fn get_normal_of_triangle(
+    pub fn get_normal_of_triangle(
         ecosystem: &Ecosystem,
         i1: CellIndex,
         i2: CellIndex,
@@ -399,14 +676,14 @@ impl Cell {
         ac.cross(&ab).normalize()
     }
 
-    pub(crate) fn get_height(self: &Cell) -> f32 {
+    pub fn get_height(self: &Cell) -> f32 {
         let mut height = 0.0;
         if let Some(bedrock) = &self.bedrock {
             // println!("bedrock height {}", bedrock.height);
             height += bedrock.height;
         }
         if let Some(rock) = &self.rock {
-            height += rock.height;
+            height += rock.get_height();
         }
         if let Some(sand) = &self.sand {
             height += sand.height;
@@ -417,22 +694,50 @@ impl Cell {
         height
     }
 
-    pub(crate) fn get_monthly_temperature(self: &Cell, month: usize) -> f32 {
+    pub fn get_monthly_temperature(self: &Cell, climate: &Climate, month: usize) -> f32 {
         // modulate temperature with height
         let height = self.get_height();
-        constants::AVERAGE_MONTHLY_TEMPERATURES[month] - 0.0065 * height
+        climate.monthly_temperatures[month] - 0.0065 * height
     }
 
-    pub(crate) fn get_monthly_soil_moisture(self: &Cell, month: usize) -> f32 {
+    pub fn get_monthly_soil_moisture(self: &Cell, climate: &Climate, month: usize) -> f32 {
         // distribute cell moisture by monthly rainfall patterns
         // cell moisture is volume of water in a cell
-        let rainfall = constants::AVERAGE_MONTHLY_RAINFALL[month];
-        let annual_rainfall: f32 = constants::AVERAGE_MONTHLY_RAINFALL.into_iter().sum();
+        let rainfall = climate.monthly_rainfall[month];
+        let annual_rainfall: f32 = climate.monthly_rainfall.into_iter().sum();
         self.soil_moisture * (rainfall / annual_rainfall)
     }
 
+    // returns heights of the humus, sand, and rock layers, which make up the soil profile
+    pub fn get_soil_layer_heights(&self) -> [f32; 3] {
+        [
+            self.get_humus_height(),
+            self.get_sand_height(),
+            self.get_rock_height(),
+        ]
+    }
+
+    // returns monthly soil moisture of the humus, sand, and rock layers as % by volume
+    // the humus follows the monthly rainfall, while the deeper layers hold their water through the year
+    pub fn get_monthly_moisture_profile(&self, climate: &Climate, month: usize) -> [f32; 3] {
+        let seasonal = self.get_monthly_soil_moisture(climate, month);
+        let buffered = self.soil_moisture / 12.0;
+        let heights = self.get_soil_layer_heights();
+        std::array::from_fn(|i| {
+            let moisture = if i == 0 { seasonal } else { buffered } * self.moisture_profile[i];
+            let volume = Meters(heights[i]).over_cell().to_liters();
+            if volume.0 == 0.0 {
+                0.0
+            } else {
+                f32::min(Liters(moisture) / volume, 1.0)
+            }
+        })
+    }
+
     // *** LAYER ADDERS ***
-    pub(crate) fn add_bedrock(&mut self, height: f32) {
+    // amounts added and removed must not be negative, and layers never have negative heights
+    pub fn add_bedrock(&mut self, height: f32) {
+        debug_assert!(height >= 0.0, "Adding negative bedrock height {height}");
         if let Some(bedrock) = &mut self.bedrock {
             bedrock.height += height;
         } else {
@@ -440,15 +745,31 @@ impl Cell {
         }
     }
 
-    pub(crate) fn add_rocks(&mut self, height: f32) {
+    // bedrock fractures into scree unless another size is given
+    pub fn add_rocks(&mut self, height: f32) {
+        self.add_rocks_of_size(RockSize::Scree, height);
+    }
+
+    pub fn add_rocks_of_size(&mut self, size: RockSize, height: f32) {
+        debug_assert!(height >= 0.0, "Adding negative rock height {height}");
+        let mut heights = [0.0; 3];
+        heights[size as usize] = height;
+        self.add_rock_heights(heights);
+    }
+
+    // adds the given height of each size class
+    pub fn add_rock_heights(&mut self, heights: [f32; 3]) {
         if let Some(rocks) = &mut self.rock {
-            rocks.height += height;
-        } else {
-            self.rock = Some(Rock { height });
+            for (rock_height, height) in rocks.heights.iter_mut().zip(heights) {
+                *rock_height += height;
+            }
+        } else if heights.iter().sum::<f32>() > 0.0 {
+            self.rock = Some(Rock { heights });
         }
     }
 
-    pub(crate) fn add_sand(&mut self, height: f32) {
+    pub fn add_sand(&mut self, height: f32) {
+        debug_assert!(height >= 0.0, "Adding negative sand height {height}");
         if let Some(sand) = &mut self.sand {
             sand.height += height;
         } else {
@@ -456,7 +777,8 @@ impl Cell {
         }
     }
 
-    pub(crate) fn add_humus(&mut self, height: f32) {
+    pub fn add_humus(&mut self, height: f32) {
+        debug_assert!(height >= 0.0, "Adding negative humus height {height}");
         if let Some(humus) = &mut self.humus {
             humus.height += height;
         } else {
@@ -464,7 +786,8 @@ impl Cell {
         }
     }
 
-    pub(crate) fn add_dead_vegetation(&mut self, biomass: f32) {
+    pub fn add_dead_vegetation(&mut self, biomass: f32) {
+        debug_assert!(biomass >= 0.0, "Adding negative dead vegetation biomass {biomass}");
         if let Some(dead_vegetation) = &mut self.dead_vegetation {
             dead_vegetation.biomass += biomass;
         } else {
@@ -472,59 +795,168 @@ impl Cell {
         }
     }
 
+    pub fn add_water(&mut self, height: f32) {
+        debug_assert!(height >= 0.0, "Adding negative water height {height}");
+        if let Some(water) = &mut self.water {
+            water.height += height;
+        } else {
+            self.water = Some(Water { height });
+        }
+    }
+
+    // adds snow with the given water equivalent height (in m) and density
+    pub fn add_snow(&mut self, water_equivalent: f32, density: f32) {
+        debug_assert!(
+            water_equivalent >= 0.0,
+            "Adding negative snow water equivalent {water_equivalent}"
+        );
+        let height =
+            water_equivalent * (constants::WATER_DENSITY / KilogramsPerCubicMeter(density));
+        if let Some(snow) = &mut self.snow {
+            // new snow mixes with the existing snowpack by mass
+            let mass = snow.height * snow.density + height * density;
+            snow.height += height;
+            snow.density = mass / snow.height;
+        } else {
+            self.snow = Some(Snow { height, density });
+        }
+    }
+
     // *** LAYER REMOVERS ***
-    pub(crate) fn remove_bedrock(&mut self, height: f32) {
-        if let Some(bedrock) = &mut self.bedrock {
-            bedrock.height -= height;
-            if bedrock.height <= 0.0 {
-                self.bedrock = None;
-            }
+    // removers take at most what the layer has and return the amount actually removed
+    pub fn remove_bedrock(&mut self, height: f32) -> f32 {
+        debug_assert!(height >= 0.0, "Removing negative bedrock height {height}");
+        let Some(bedrock) = &mut self.bedrock else {
+            return 0.0;
+        };
+        let removed = f32::min(height, bedrock.height);
+        bedrock.height -= removed;
+        if bedrock.height <= 0.0 {
+            self.bedrock = None;
         }
+        removed
     }
 
-    pub(crate) fn remove_sand(&mut self, height: f32) {
-        if let Some(sand) = &mut self.sand {
-            sand.height -= height;
-            if sand.height <= 0.0 {
-                self.sand = None;
-            }
+    // removes rocks of every size in proportion to their heights
+    pub fn remove_rocks(&mut self, height: f32) -> f32 {
+        self.take_rocks(height).iter().sum()
+    }
+
+    // removes rocks of every size in proportion to their heights, returning the height removed of each
+    pub fn take_rocks(&mut self, height: f32) -> [f32; 3] {
+        debug_assert!(height >= 0.0, "Removing negative rock height {height}");
+        let Some(rock) = &mut self.rock else {
+            return [0.0; 3];
+        };
+        let total = rock.get_height();
+        if height >= total {
+            let removed = rock.heights;
+            self.rock = None;
+            return removed;
+        }
+        let fraction = height / total;
+        let removed = rock.heights.map(|h| h * fraction);
+        for (rock_height, removed) in rock.heights.iter_mut().zip(removed) {
+            *rock_height = f32::max(*rock_height - removed, 0.0);
         }
+        if rock.get_height() <= 0.0 {
+            self.rock = None;
+        }
+        removed
     }
 
-    pub(crate) fn remove_rocks(&mut self, height: f32) {
-        if let Some(rock) = &mut self.rock {
-            rock.height -= height;
-            if rock.height <= 0.0 {
-                self.rock = None;
-            }
+    pub fn remove_rocks_of_size(&mut self, size: RockSize, height: f32) -> f32 {
+        debug_assert!(height >= 0.0, "Removing negative rock height {height}");
+        let Some(rock) = &mut self.rock else {
+            return 0.0;
+        };
+        let rock_height = &mut rock.heights[size as usize];
+        let removed = f32::min(height, *rock_height);
+        *rock_height -= removed;
+        if rock.get_height() <= 0.0 {
+            self.rock = None;
         }
+        removed
     }
 
-    pub(crate) fn remove_humus(&mut self, height: f32) {
-        if let Some(humus) = &mut self.humus {
-            humus.height -= height;
-            if humus.height <= 0.0 {
-                self.humus = None;
-            }
+    pub fn remove_sand(&mut self, height: f32) -> f32 {
+        debug_assert!(height >= 0.0, "Removing negative sand height {height}");
+        let Some(sand) = &mut self.sand else {
+            return 0.0;
+        };
+        let removed = f32::min(height, sand.height);
+        sand.height -= removed;
+        if sand.height <= 0.0 {
+            self.sand = None;
         }
+        removed
     }
 
-    pub(crate) fn remove_dead_vegetation(&mut self, biomass: f32) {
-        if let Some(dead_vegetation) = &mut self.dead_vegetation {
-            dead_vegetation.biomass -= biomass;
-            if dead_vegetation.biomass <= 0.0 {
-                self.dead_vegetation = None;
-            }
+    pub fn remove_humus(&mut self, height: f32) -> f32 {
+        debug_assert!(height >= 0.0, "Removing negative humus height {height}");
+        let Some(humus) = &mut self.humus else {
+            return 0.0;
+        };
+        let removed = f32::min(height, humus.height);
+        humus.height -= removed;
+        if humus.height <= 0.0 {
+            self.humus = None;
         }
+        removed
     }
 
-    pub(crate) fn remove_all_dead_vegetation(&mut self) {
+    pub fn remove_dead_vegetation(&mut self, biomass: f32) -> f32 {
+        debug_assert!(biomass >= 0.0, "Removing negative dead vegetation biomass {biomass}");
+        let Some(dead_vegetation) = &mut self.dead_vegetation else {
+            return 0.0;
+        };
+        let removed = f32::min(biomass, dead_vegetation.biomass);
+        dead_vegetation.biomass -= removed;
+        if dead_vegetation.biomass <= 0.0 {
+            self.dead_vegetation = None;
+        }
+        removed
+    }
+
+    pub fn remove_all_dead_vegetation(&mut self) {
         self.dead_vegetation = None;
     }
 
+    pub fn remove_water(&mut self, height: f32) -> f32 {
+        debug_assert!(height >= 0.0, "Removing negative water height {height}");
+        let Some(water) = &mut self.water else {
+            return 0.0;
+        };
+        let removed = f32::min(height, water.height);
+        water.height -= removed;
+        if water.height <= 0.0 {
+            self.water = None;
+        }
+        removed
+    }
+
+    // removes snow with the given water equivalent height (in m)
+    pub fn remove_snow(&mut self, water_equivalent: f32) -> f32 {
+        debug_assert!(
+            water_equivalent >= 0.0,
+            "Removing negative snow water equivalent {water_equivalent}"
+        );
+        let Some(snow) = &mut self.snow else {
+            return 0.0;
+        };
+        let density = KilogramsPerCubicMeter(snow.density);
+        let snow_water_equivalent = snow.height * (density / constants::WATER_DENSITY);
+        let removed = f32::min(water_equivalent, snow_water_equivalent);
+        snow.height -= removed * (constants::WATER_DENSITY / density);
+        if removed >= snow_water_equivalent || snow.height <= 0.0 {
+            self.snow = None;
+        }
+        removed
+    }
+
     // *** HEIGHT GETTERS ***
 
-    pub(crate) fn get_bedrock_height(&self) -> f32 {
+    pub fn get_bedrock_height(&self) -> f32 {
         if let Some(bedrock) = &self.bedrock {
             bedrock.height
         } else {
@@ -532,7 +964,7 @@ impl Cell {
         }
     }
 
-    pub(crate) fn get_sand_height(&self) -> f32 {
+    pub fn get_sand_height(&self) -> f32 {
         if let Some(sand) = &self.sand {
             sand.height
         } else {
@@ -540,7 +972,7 @@ impl Cell {
         }
     }
 
-    pub(crate) fn get_humus_height(&self) -> f32 {
+    pub fn get_humus_height(&self) -> f32 {
         if let Some(humus) = &self.humus {
             humus.height
         } else {
@@ -548,23 +980,51 @@ impl Cell {
         }
     }
 
-    pub(crate) fn get_rock_height(&self) -> f32 {
+    pub fn get_rock_height(&self) -> f32 {
         if let Some(rock) = &self.rock {
-            rock.height
+            rock.get_height()
         } else {
             0.0
         }
     }
 
-    pub(crate) fn get_height_of_trees(&self) -> f32 {
-        if let Some(tree) = &self.trees {
-            tree.plant_height_sum / (tree.number_of_plants as f32)
+    // heights of the boulders, scree, and gravel
+    pub fn get_rock_heights(&self) -> [f32; 3] {
+        if let Some(rock) = &self.rock {
+            rock.heights
         } else {
-            0.0
+            [0.0; 3]
+        }
+    }
+
+    pub fn get_rock_height_of_size(&self, size: RockSize) -> f32 {
+        self.get_rock_heights()[size as usize]
+    }
+
+    // average height over trees of all species
+    pub fn get_height_of_trees(&self) -> f32 {
+        let number_of_plants: u32 = self.trees.iter().map(|t| t.get_number_of_plants()).sum();
+        if number_of_plants == 0 {
+            return 0.0;
+        }
+        let plant_height_sum: f32 = self.trees.iter().map(|t| t.get_plant_height_sum()).sum();
+        plant_height_sum / number_of_plants as f32
+    }
+
+    pub fn get_trees(&self, species: usize) -> Option<&Trees> {
+        self.trees.iter().find(|trees| trees.species == species)
+    }
+
+    // replaces the trees of the same species, removing them if there are no plants left
+    pub fn set_trees(&mut self, trees: Trees) {
+        self.trees.retain(|t| t.species != trees.species);
+        if trees.get_number_of_plants() > 0 {
+            let position = self.trees.partition_point(|t| t.species < trees.species);
+            self.trees.insert(position, trees);
         }
     }
 
-    pub(crate) fn get_dead_vegetation_biomass(&self) -> f32 {
+    pub fn get_dead_vegetation_biomass(&self) -> f32 {
         if let Some(dead_vegetation) = &self.dead_vegetation {
             dead_vegetation.biomass
         } else {
@@ -572,8 +1032,42 @@ impl Cell {
         }
     }
 
+    pub fn get_water_height(&self) -> f32 {
+        if let Some(water) = &self.water {
+            water.height
+        } else {
+            0.0
+        }
+    }
+
+    pub fn get_snow_height(&self) -> f32 {
+        if let Some(snow) = &self.snow {
+            snow.height
+        } else {
+            0.0
+        }
+    }
+
+    pub fn get_snow_density(&self) -> f32 {
+        if let Some(snow) = &self.snow {
+            snow.density
+        } else {
+            0.0
+        }
+    }
+
+    // height of liquid water the snowpack would melt into
+    pub fn get_snow_water_equivalent(&self) -> f32 {
+        if let Some(snow) = &self.snow {
+            snow.height * (KilogramsPerCubicMeter(snow.density) / constants::WATER_DENSITY)
+        } else {
+            0.0
+        }
+    }
+
     // *** HEIGHT SETTERS ***
-    pub(crate) fn set_height_of_bedrock(&mut self, height: f32) {
+    pub fn set_height_of_bedrock(&mut self, height: f32) {
+        debug_assert!(height >= 0.0, "Setting negative bedrock height {height}");
         if let Some(bedrock) = &mut self.bedrock {
             bedrock.height = height;
         } else {
@@ -581,18 +1075,25 @@ impl Cell {
         }
     }
 
+    // compacts the snowpack to the given density, keeping its mass
+    pub fn set_snow_density(&mut self, density: f32) {
+        if let Some(snow) = &mut self.snow {
+            snow.height *= snow.density / density;
+            snow.density = density;
+        }
+    }
+
     // *** ECOLOGICAL ESTIMATERS ***
 
-    pub(crate) fn estimate_tree_biomass(&self) -> f32 {
-        let mut biomass = 0.0;
-        // assume max one tree layer
-        if let Some(trees) = &self.trees {
-            biomass += trees.estimate_biomass();
-        }
-        biomass
+    pub fn estimate_tree_biomass(&self) -> f32 {
+        // one tree layer per species
+        self.trees
+            .iter()
+            .map(|trees| trees.estimate_biomass())
+            .sum()
     }
 
-    pub(crate) fn estimate_bush_biomass(&self) -> f32 {
+    pub fn estimate_bush_biomass(&self) -> f32 {
         let mut biomass = 0.0;
         // assume max one bush layer
         if let Some(bushes) = &self.bushes {
@@ -601,7 +1102,7 @@ impl Cell {
         biomass
     }
 
-    pub(crate) fn estimate_grasses_biomass(&self) -> f32 {
+    pub fn estimate_grasses_biomass(&self) -> f32 {
         let mut biomass = 0.0;
         // assume max one bush layer
         if let Some(grasses) = &self.grasses {
@@ -610,12 +1111,10 @@ impl Cell {
         biomass
     }
 
-    pub(crate) fn estimate_vegetation_density(&self) -> f32 {
+    pub fn estimate_vegetation_density(&self) -> f32 {
         // sum density of trees, bushes, and grasses
         let mut density = 0.0;
-        if let Some(trees) = &self.trees {
-            density += Self::estimate_tree_density(trees);
-        }
+        density += self.estimate_total_tree_density();
         if let Some(bushes) = &self.bushes {
             density += Self::estimate_bushes_density(bushes);
         }
@@ -626,71 +1125,114 @@ impl Cell {
         density
     }
 
-    pub(crate) fn estimate_tree_density(trees: &Trees) -> f32 {
-        let n = trees.number_of_plants;
-        let h = trees.plant_height_sum;
-        let average_height = if n == 0 { 0.0 } else { h / n as f32 };
-        let average_diameter = Trees::estimate_diameter_from_height(average_height);
-        let average_crown_area = Trees::estimate_crown_area_from_diameter(average_diameter);
-        let crown_area_sum = average_crown_area * n as f32;
+    // trees of all species share the canopy
+    pub fn estimate_total_tree_density(&self) -> f32 {
+        self.trees.iter().map(Self::estimate_tree_density).sum()
+    }
+
+    // each age class spreads crowns of its own average size
+    pub fn estimate_tree_density(trees: &Trees) -> f32 {
+        let crown_area_sum: f32 = trees
+            .cohorts
+            .iter()
+            .map(|cohort| {
+                let average_diameter =
+                    Trees::estimate_diameter_from_height(cohort.get_average_height());
+                let average_crown_area = Trees::estimate_crown_area_from_diameter(average_diameter);
+                average_crown_area * cohort.number_of_plants as f32
+            })
+            .sum();
         crown_area_sum / (constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH)
     }
 
-    pub(crate) fn estimate_bushes_density(bushes: &Bushes) -> f32 {
-        let n = bushes.number_of_plants;
-        let biomass = bushes.estimate_biomass();
-        let average_biomass = if n == 0 { 0.0 } else { biomass / n as f32 };
-        let average_crown_area = Bushes::estimate_crown_area_from_biomass(average_biomass);
-        let crown_area_sum = average_crown_area * n as f32;
+    pub fn estimate_bushes_density(bushes: &Bushes) -> f32 {
+        let crown_area_sum: f32 = bushes
+            .cohorts
+            .iter()
+            .map(|cohort| {
+                let n = cohort.number_of_plants;
+                let biomass = Bushes::estimate_cohort_biomass(cohort);
+                let average_biomass = if n == 0 { 0.0 } else { biomass / n as f32 };
+                let average_crown_area = Bushes::estimate_crown_area_from_biomass(average_biomass);
+                average_crown_area * n as f32
+            })
+            .sum();
         crown_area_sum / (constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH)
     }
 }
 
 impl CellLayer {
-    pub(crate) fn get_height(&self) -> f32 {
+    pub fn get_height(&self) -> f32 {
         match self {
             CellLayer::Bedrock(Some(bedrock)) => bedrock.height,
-            CellLayer::Rock(Some(rock)) => rock.height,
+            CellLayer::Rock(Some(rock)) => rock.get_height(),
             CellLayer::Sand(Some(sand)) => sand.height,
             CellLayer::Humus(Some(humus)) => humus.height,
+            CellLayer::Water(Some(water)) => water.height,
+            CellLayer::Snow(Some(snow)) => snow.height,
             _ => 0.0,
         }
     }
 }
 
 impl Trees {
-    pub(crate) fn new() -> Self {
+    pub fn new(species: usize) -> Self {
         Trees {
-            number_of_plants: 0,
-            plant_height_sum: 0.0,
-            plant_age_sum: 0.0,
+            species,
+            cohorts: vec![],
+            dry_months: 0.0,
         }
     }
-    pub(crate) fn estimate_biomass(&self) -> f32 {
+
+    // trees of a single age class
+    pub fn with_plants(
+        species: usize,
+        number_of_plants: u32,
+        plant_height_sum: f32,
+        plant_age_sum: f32,
+    ) -> Self {
+        Trees {
+            species,
+            cohorts: Cohort::init_single(number_of_plants, plant_height_sum, plant_age_sum),
+            dry_months: 0.0,
+        }
+    }
+
+    // if cell contains trees of this species, return them, otherwise init an empty one
+    pub fn clone_from_cell(cell: &Cell, species: usize) -> Self {
+        cell.get_trees(species)
+            .cloned()
+            .unwrap_or_else(|| Trees::new(species))
+    }
+    pub fn estimate_biomass(&self) -> f32 {
+        self.cohorts.iter().map(Self::estimate_cohort_biomass).sum()
+    }
+
+    pub fn estimate_cohort_biomass(cohort: &Cohort) -> f32 {
         // based on allometric equation for red maples
         // source: https://academic.oup.com/forestry/article/87/1/129/602137#9934369
         // ln(biomass in kg) = -2.0470 + 2.3852 * ln(diameter in cm)
-        if self.number_of_plants == 0 {
+        if cohort.number_of_plants == 0 {
             return 0.0;
         }
-        let average_height = self.plant_height_sum / self.number_of_plants as f32;
+        let average_height = cohort.get_average_height();
         let average_diameter = Trees::estimate_diameter_from_height(average_height);
         assert!(average_diameter > 0.0);
         let average_biomass = f32::powf(
             std::f32::consts::E,
             -2.0470 + 2.3852 * f32::ln(average_diameter),
         );
-        average_biomass * self.number_of_plants as f32
+        average_biomass * cohort.number_of_plants as f32
     }
 
-    pub(crate) fn estimate_diameter_from_height(height: f32) -> f32 {
+    pub fn estimate_diameter_from_height(height: f32) -> f32 {
         // based on red maples
         // source: https://www.ccsenet.org/journal/index.php/jps/article/view/69956
         // log(height in m) = 0.6 * log(diameter in cm) - 0.4
         f32::powf(10.0, (f32::log10(height) - 0.4) / 0.6)
     }
 
-    pub(crate) fn estimate_crown_area_from_diameter(diameter: f32) -> f32 {
+    pub fn estimate_crown_area_from_diameter(diameter: f32) -> f32 {
         // based on red maples
         // source: https://www.fs.usda.gov/rds/archive/Catalog/RDS-2016-0005
         // crown diameter in m = a + b * (dbh in cm) + c * dhb^2
@@ -701,33 +1243,103 @@ impl Trees {
     }
 }
 
+impl Cohort {
+    // a single age class holding all the plants, or none if there are no plants
+    pub fn init_single(
+        number_of_plants: u32,
+        plant_height_sum: f32,
+        plant_age_sum: f32,
+    ) -> Vec<Self> {
+        if number_of_plants == 0 {
+            return vec![];
+        }
+        vec![Cohort {
+            number_of_plants,
+            plant_height_sum,
+            plant_age_sum,
+        }]
+    }
+
+    pub fn get_average_height(&self) -> f32 {
+        if self.number_of_plants == 0 {
+            return 0.0;
+        }
+        self.plant_height_sum / self.number_of_plants as f32
+    }
+
+    pub fn get_average_age(&self) -> f32 {
+        if self.number_of_plants == 0 {
+            return 0.0;
+        }
+        self.plant_age_sum / self.number_of_plants as f32
+    }
+
+    // removes plants of average height and age
+    pub fn remove_plants(&mut self, amount: u32) {
+        let amount = amount.min(self.number_of_plants);
+        let average_height = self.get_average_height();
+        let average_age = self.get_average_age();
+        self.number_of_plants -= amount;
+        self.plant_height_sum = average_height * self.number_of_plants as f32;
+        self.plant_age_sum = average_age * self.number_of_plants as f32;
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} (height sum {}, age sum {})",
+            self.number_of_plants, self.plant_height_sum, self.plant_age_sum
+        )
+    }
+}
+
+impl Default for Bushes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Bushes {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
+        Bushes {
+            cohorts: vec![],
+            dry_months: 0.0,
+        }
+    }
+
+    // bushes of a single age class
+    pub fn with_plants(number_of_plants: u32, plant_height_sum: f32, plant_age_sum: f32) -> Self {
         Bushes {
-            number_of_plants: 0,
-            plant_height_sum: 0.0,
-            plant_age_sum: 0.0,
+            cohorts: Cohort::init_single(number_of_plants, plant_height_sum, plant_age_sum),
+            dry_months: 0.0,
         }
     }
 
-    pub(crate) fn estimate_biomass(&self) -> f32 {
+    // if cell contains bushes, return them, otherwise init an empty one
+    pub fn clone_from_cell(cell: &Cell) -> Self {
+        cell.bushes.clone().unwrap_or_default()
+    }
+
+    pub fn estimate_biomass(&self) -> f32 {
+        self.cohorts.iter().map(Self::estimate_cohort_biomass).sum()
+    }
+
+    pub fn estimate_cohort_biomass(cohort: &Cohort) -> f32 {
         // based on allometric equation for rhododendron mariesii
         // source: https://link.springer.com/article/10.1007/s11056-023-09963-z
         // ln(biomass in kg) = -2.635 + 3.614 * ln(height in m)
-        if self.number_of_plants == 0 {
+        if cohort.number_of_plants == 0 {
             return 0.0;
         }
-        let average_height = self.plant_height_sum / self.number_of_plants as f32;
-        assert!(self.number_of_plants > 0, "{self:?}");
-        assert!(average_height > 0.0, "{self:?}");
+        let average_height = cohort.get_average_height();
+        assert!(average_height > 0.0, "{cohort:?}");
         let average_biomass = f32::powf(
             std::f32::consts::E,
             -2.635 + 3.614 * f32::ln(average_height),
         );
-        average_biomass * self.number_of_plants as f32
+        average_biomass * cohort.number_of_plants as f32
     }
 
-    pub(crate) fn estimate_crown_area_from_biomass(biomass: f32) -> f32 {
+    pub fn estimate_crown_area_from_biomass(biomass: f32) -> f32 {
         // based on allometric equation for rhododendron mariesii
         // source: https://link.springer.com/article/10.1007/s11056-023-09963-z
         // ln(crown area in m^2) = (ln(biomass in kg) + 0.435) / 1.324
@@ -735,43 +1347,64 @@ impl Bushes {
     }
 }
 
+impl Default for Grasses {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Grasses {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         Grasses {
             coverage_density: 0.0,
+            green_fraction: 1.0,
         }
     }
 
+    // if cell contains grasses, return them, otherwise init an empty one
+    pub fn clone_from_cell(cell: &Cell) -> Self {
+        cell.grasses.clone().unwrap_or_default()
+    }
+
     // source: http://switchgrass.okstate.edu/what-is-switchgrass
     // 2 tons/acre/year ≈ 0.45 kg/square meter/year
-    pub(crate) fn estimate_biomass(&self) -> f32 {
+    pub fn estimate_biomass(&self) -> f32 {
         Self::estimate_biomass_for_coverage_density(self.coverage_density)
     }
 
-    pub(crate) fn estimate_biomass_for_coverage_density(density: f32) -> f32 {
+    pub fn estimate_biomass_for_coverage_density(density: f32) -> f32 {
         density * 0.45
     }
+
+    // coverage of grass that has cured and dries out as fuel
+    pub fn get_cured_coverage(&self) -> f32 {
+        self.coverage_density * (1.0 - self.green_fraction)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use float_cmp::approx_eq;
     use nalgebra::Vector3;
+    use proptest::{
+        collection::vec,
+        prelude::{any, prop_assert, proptest, ProptestConfig},
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
 
-    use super::{Bedrock, CellIndex, Ecosystem, Humus, Rock, Sand};
+    use super::{Bedrock, CellIndex, Ecosystem, Humus, Rock, RockSize, Sand};
     use crate::{
+        config::{BoundaryMode, Climate},
         constants,
         ecology::{self, Bushes, Cell, Trees},
+        events::Events,
     };
 
     #[test]
     fn test_ecosystem_init() {
         let ecosystem = Ecosystem::init();
         let cells = &ecosystem.cells;
-        assert!(cells.len() == constants::AREA_SIDE_LENGTH);
-        for cell_row in cells {
-            assert!(cell_row.len() == constants::AREA_SIDE_LENGTH);
-        }
+        assert!(cells.len() == constants::NUM_CELLS);
 
         for i in 0..constants::AREA_SIDE_LENGTH {
             for j in 0..constants::AREA_SIDE_LENGTH {
@@ -787,7 +1420,7 @@ mod tests {
         let x = 2;
         let y = 3;
         let index = CellIndex::new(x, y);
-        let neighbors = Cell::get_neighbors(&index);
+        let neighbors = Cell::get_neighbors(&index, BoundaryMode::Open);
         assert!(neighbors.west == Some(CellIndex::new(x - 1, y)));
         assert!(neighbors.north == Some(CellIndex::new(x, y - 1)));
         assert!(neighbors.south == Some(CellIndex::new(x, y + 1)));
@@ -798,7 +1431,7 @@ mod tests {
         assert!(neighbors.southwest == Some(CellIndex::new(x - 1, y + 1)));
 
         let index = CellIndex::new(0, 0);
-        let neighbors = Cell::get_neighbors(&index);
+        let neighbors = Cell::get_neighbors(&index, BoundaryMode::Open);
         assert!(neighbors.south == Some(CellIndex::new(0, 1)));
         assert!(neighbors.east == Some(CellIndex::new(1, 0)));
         assert!(neighbors.north.is_none());
@@ -811,7 +1444,7 @@ mod tests {
         let x = 2;
         let y = 0;
         let index = CellIndex::new(x, y);
-        let neighbors = Cell::get_neighbors(&index);
+        let neighbors = Cell::get_neighbors(&index, BoundaryMode::Open);
         assert!(neighbors.north.is_none());
         assert_eq!(neighbors.east, Some(CellIndex::new(x + 1, y)));
         assert_eq!(neighbors.west, Some(CellIndex::new(x - 1, y)));
@@ -820,34 +1453,72 @@ mod tests {
         assert_eq!(neighbors.southeast, Some(CellIndex::new(x + 1, y + 1)));
         assert!(neighbors.northwest.is_none());
         assert_eq!(neighbors.southwest, Some(CellIndex::new(x - 1, y + 1)));
+    
+        // on a torus the edges wrap around
+        let neighbors = Cell::get_neighbors(&index, BoundaryMode::Torus);
+        assert_eq!(neighbors.len(), 8);
+        let last = constants::AREA_SIDE_LENGTH - 1;
+        assert_eq!(neighbors.north, Some(CellIndex::new(x, last)));
+        assert_eq!(neighbors.northwest, Some(CellIndex::new(x - 1, last)));
+        let neighbors = Cell::get_neighbors(&CellIndex::new(last, 0), BoundaryMode::Torus);
+        assert_eq!(neighbors.northeast, Some(CellIndex::new(0, last)));
     }
 
     #[test]
     fn test_get_height() {
         let bedrock = Bedrock { height: 100.0 };
-        let rock = Rock { height: 10.0 };
+        let rock = Rock {
+            heights: [0.0, 10.0, 0.0],
+        };
         let sand = Sand { height: 5.0 };
         let humus = Humus { height: 1.1 };
-        let trees = Trees {
-            number_of_plants: 1,
-            plant_height_sum: 10.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 1, 10.0, 10.0);
         let cell = Cell {
             soil_moisture: 0.0,
             bedrock: Some(bedrock),
             rock: Some(rock),
             sand: Some(sand),
             humus: Some(humus),
-            trees: Some(trees),
+            trees: vec![trees],
             bushes: None,
             grasses: None,
             dead_vegetation: None,
+            water: None,
+            snow: None,
             hours_of_sunlight: constants::AVERAGE_SUNLIGHT_HOURS,
+            burn_severity: 0.0,
+            water_flow: 0.0,
+            nutrients: 0.0,
+            drought_index: 0.0,
+            outbreak: 0.0,
+            moisture_profile: [1.0, 0.0, 0.0],
+            last_events: vec![],
         };
         assert_eq!(cell.get_height(), 116.1);
     }
 
+    #[test]
+    fn test_rock_sizes() {
+        let mut cell = Cell::init();
+        cell.add_rocks_of_size(RockSize::Boulders, 1.0);
+        cell.add_rocks(2.0);
+        cell.add_rocks_of_size(RockSize::Gravel, 1.0);
+        assert_eq!(cell.get_rock_heights(), [1.0, 2.0, 1.0]);
+        assert_eq!(cell.get_rock_height(), 4.0);
+
+        // rocks of every size are removed in proportion
+        let removed = cell.take_rocks(2.0);
+        assert_eq!(removed, [0.5, 1.0, 0.5]);
+        assert_eq!(cell.get_rock_heights(), [0.5, 1.0, 0.5]);
+
+        // or of a single size, at most what the cell has
+        let removed = cell.remove_rocks_of_size(RockSize::Gravel, 1.0);
+        assert_eq!(removed, 0.5);
+        assert_eq!(cell.get_rock_height_of_size(RockSize::Gravel), 0.0);
+        assert_eq!(cell.remove_rocks(10.0), 1.5);
+        assert_eq!(cell.get_rock_height(), 0.0);
+    }
+
     #[test]
     fn test_get_temperature() {
         let mut cell = Cell {
@@ -856,24 +1527,34 @@ mod tests {
             rock: None,
             sand: None,
             humus: None,
-            trees: None,
+            trees: vec![],
             bushes: None,
             grasses: None,
             dead_vegetation: None,
+            water: None,
+            snow: None,
             hours_of_sunlight: constants::AVERAGE_SUNLIGHT_HOURS,
+            burn_severity: 0.0,
+            water_flow: 0.0,
+            nutrients: 0.0,
+            drought_index: 0.0,
+            outbreak: 0.0,
+            moisture_profile: [1.0, 0.0, 0.0],
+            last_events: vec![],
         };
+        let climate = Climate::default();
         assert_eq!(
-            cell.get_monthly_temperature(0),
+            cell.get_monthly_temperature(&climate, 0),
             constants::AVERAGE_MONTHLY_TEMPERATURES[0]
         );
         assert_eq!(
-            cell.get_monthly_temperature(11),
+            cell.get_monthly_temperature(&climate, 11),
             constants::AVERAGE_MONTHLY_TEMPERATURES[11]
         );
 
         cell.add_bedrock(100.0);
         assert_eq!(
-            cell.get_monthly_temperature(0),
+            cell.get_monthly_temperature(&climate, 0),
             constants::AVERAGE_MONTHLY_TEMPERATURES[0] - 0.0065 * 100.0
         );
 
@@ -881,7 +1562,7 @@ mod tests {
         cell.add_sand(10.0);
         cell.add_dead_vegetation(10.0);
         assert_eq!(
-            cell.get_monthly_temperature(0),
+            cell.get_monthly_temperature(&climate, 0),
             constants::AVERAGE_MONTHLY_TEMPERATURES[0] - 0.0065 * 120.0
         );
     }
@@ -1000,6 +1681,19 @@ mod tests {
             45.0,
             Ecosystem::get_angle(slope)
         );
+
+        // neighbors across the edge of a torus are as close as any others
+        ecosystem.config.boundary = BoundaryMode::Torus;
+        let last = constants::AREA_SIDE_LENGTH - 1;
+        let edge = &mut ecosystem[CellIndex::new(3, last)];
+        let bedrock = &mut edge.bedrock.as_mut().unwrap();
+        bedrock.height = constants::DEFAULT_BEDROCK_HEIGHT + 1.0;
+        let slope = ecosystem.get_slope_between_points(CellIndex::new(3, last), CellIndex::new(3, 0));
+        let expected = 0.707;
+        assert!(
+            approx_eq!(f32, slope, expected, epsilon = 0.001),
+            "Expected {expected}, actual {slope}"
+        );
     }
 
     #[test]
@@ -1011,22 +1705,27 @@ mod tests {
 
     #[test]
     fn test_estimate_tree_biomass() {
-        let trees = Trees {
-            number_of_plants: 1,
-            plant_height_sum: 10.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 1, 10.0, 10.0);
         let mut cell = Cell {
             soil_moisture: 0.0,
             bedrock: None,
             rock: None,
             sand: None,
             humus: None,
-            trees: Some(trees),
+            trees: vec![trees],
             bushes: None,
             grasses: None,
             dead_vegetation: None,
+            water: None,
+            snow: None,
             hours_of_sunlight: constants::AVERAGE_SUNLIGHT_HOURS,
+            burn_severity: 0.0,
+            water_flow: 0.0,
+            nutrients: 0.0,
+            drought_index: 0.0,
+            outbreak: 0.0,
+            moisture_profile: [1.0, 0.0, 0.0],
+            last_events: vec![],
         };
         let biomass = cell.estimate_tree_biomass();
         let expected = 31.3472;
@@ -1035,9 +1734,9 @@ mod tests {
             "Expected biomass {expected}, actual biomass {biomass}"
         );
 
-        if let Some(trees) = &mut cell.trees {
-            trees.number_of_plants = 5;
-            trees.plant_height_sum = 50.0;
+        if let Some(trees) = cell.trees.first_mut() {
+            trees.cohorts[0].number_of_plants = 5;
+            trees.cohorts[0].plant_height_sum = 50.0;
         }
         let biomass = cell.estimate_tree_biomass();
         let expected = 156.7362;
@@ -1060,11 +1759,7 @@ mod tests {
     #[test]
     fn test_estimate_tree_density() {
         // one tree
-        let trees = Trees {
-            number_of_plants: 1,
-            plant_height_sum: 10.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 1, 10.0, 10.0);
         let density = Cell::estimate_tree_density(&trees);
         let expected = 0.0774;
         assert!(
@@ -1073,11 +1768,7 @@ mod tests {
         );
 
         // two trees
-        let trees = Trees {
-            number_of_plants: 2,
-            plant_height_sum: 20.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 2, 20.0, 10.0);
         let density = Cell::estimate_tree_density(&trees);
         let expected = 0.0774 * 2.0;
         assert!(
@@ -1086,11 +1777,7 @@ mod tests {
         );
 
         // many trees
-        let trees = Trees {
-            number_of_plants: 15,
-            plant_height_sum: 150.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 15, 150.0, 10.0);
         let density = Cell::estimate_tree_density(&trees);
         let expected = 0.0774 * 15.0;
         assert!(
@@ -1101,22 +1788,27 @@ mod tests {
 
     #[test]
     fn test_estimate_bush_biomass() {
-        let bushes = Bushes {
-            number_of_plants: 1,
-            plant_height_sum: 1.5,
-            plant_age_sum: 1.0,
-        };
+        let bushes = Bushes::with_plants(1, 1.5, 1.0);
         let mut cell = Cell {
             soil_moisture: 0.0,
             bedrock: None,
             rock: None,
             sand: None,
             humus: None,
-            trees: None,
+            trees: vec![],
             bushes: Some(bushes),
             grasses: None,
             dead_vegetation: None,
+            water: None,
+            snow: None,
             hours_of_sunlight: constants::AVERAGE_SUNLIGHT_HOURS,
+            burn_severity: 0.0,
+            water_flow: 0.0,
+            nutrients: 0.0,
+            drought_index: 0.0,
+            outbreak: 0.0,
+            moisture_profile: [1.0, 0.0, 0.0],
+            last_events: vec![],
         };
         let volume = cell.estimate_bush_biomass();
         let expected = 0.3104;
@@ -1126,8 +1818,8 @@ mod tests {
         );
 
         if let Some(bushes) = &mut cell.bushes {
-            bushes.number_of_plants = 5;
-            bushes.plant_height_sum = 7.5;
+            bushes.cohorts[0].number_of_plants = 5;
+            bushes.cohorts[0].plant_height_sum = 7.5;
         }
         let volume = cell.estimate_bush_biomass();
         let expected = 1.5523;
@@ -1137,8 +1829,7 @@ mod tests {
         );
 
         if let Some(bushes) = &mut cell.bushes {
-            bushes.number_of_plants = 0;
-            bushes.plant_height_sum = 0.0;
+            bushes.cohorts.clear();
         }
         let volume = cell.estimate_bush_biomass();
         let expected = 0.0;
@@ -1151,11 +1842,7 @@ mod tests {
     #[test]
     fn test_estimate_bushes_density() {
         // one bush
-        let bushes = Bushes {
-            number_of_plants: 1,
-            plant_height_sum: 2.0,
-            plant_age_sum: 10.0,
-        };
+        let bushes = Bushes::with_plants(1, 2.0, 10.0);
         let density = Cell::estimate_bushes_density(&bushes);
         let expected = 0.0126;
         assert!(
@@ -1164,11 +1851,7 @@ mod tests {
         );
 
         // many bushes
-        let bushes = Bushes {
-            number_of_plants: 10,
-            plant_height_sum: 20.0,
-            plant_age_sum: 10.0,
-        };
+        let bushes = Bushes::with_plants(10, 20.0, 10.0);
         let density = Cell::estimate_bushes_density(&bushes);
         let expected = 0.126;
         assert!(
@@ -1181,25 +1864,153 @@ mod tests {
     fn test_get_monthly_soil_moisture() {
         let mut ecosystem = Ecosystem::init();
         let index = CellIndex::new(2, 2);
+        let climate = ecosystem.config.climate;
         let cell = &mut ecosystem[index];
 
         // January
         cell.soil_moisture = 0.0;
-        let moisture = cell.get_monthly_soil_moisture(0);
+        let moisture = cell.get_monthly_soil_moisture(&climate, 0);
         assert_eq!(moisture, 0.0);
 
         // 1 L of moisture
         cell.soil_moisture = 1.0;
-        let moisture = cell.get_monthly_soil_moisture(0);
+        let moisture = cell.get_monthly_soil_moisture(&climate, 0);
         assert_eq!(moisture, 96.0 / 1151.0);
 
         // 50 L of moisture
         cell.soil_moisture = 50.0;
-        let moisture = cell.get_monthly_soil_moisture(0);
+        let moisture = cell.get_monthly_soil_moisture(&climate, 0);
         assert_eq!(moisture, 50.0 * 96.0 / 1151.0);
 
         // July
-        let moisture = cell.get_monthly_soil_moisture(6);
+        let moisture = cell.get_monthly_soil_moisture(&climate, 6);
         assert_eq!(moisture, 50.0 * 87.0 / 1151.0);
     }
+
+    #[test]
+    fn test_describe_cell() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(2, 3);
+        ecosystem[index].add_sand(1.5);
+        ecosystem[index].trees = vec![Trees::with_plants(0, 4, 8.0, 12.0)];
+        Events::apply_event(Events::ThermalStress, &mut ecosystem, index);
+
+        let description = ecosystem.describe_cell(index);
+        assert!(description.starts_with("Cell (2, 3)"), "{description}");
+        assert!(description.contains("sand 1.5"), "{description}");
+        assert!(description.contains("trees red maple 4"), "{description}");
+        assert!(
+            description.contains("last events [ThermalStress]"),
+            "{description}"
+        );
+    }
+
+    #[test]
+    fn test_remove_layers() {
+        let mut cell = Cell::init();
+        cell.add_sand(1.0);
+
+        // removing part of a layer leaves the rest
+        assert_eq!(cell.remove_sand(0.25), 0.25);
+        assert_eq!(cell.get_sand_height(), 0.75);
+
+        // removing more than the layer has removes only what is there
+        assert_eq!(cell.remove_sand(2.0), 0.75);
+        assert_eq!(cell.get_sand_height(), 0.0);
+        assert!(cell.sand.is_none());
+        assert_eq!(cell.remove_sand(1.0), 0.0);
+        assert_eq!(cell.remove_humus(1.0), 0.0);
+
+        cell.add_snow(0.1, 200.0);
+        let actual = cell.remove_snow(1.0);
+        assert!(
+            approx_eq!(f32, actual, 0.1, epsilon = 0.0001),
+            "Expected 0.1, actual {actual}"
+        );
+        assert_eq!(cell.get_snow_height(), 0.0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Removing negative humus height")]
+    fn test_remove_negative_height() {
+        let mut cell = Cell::init();
+        cell.add_humus(1.0);
+        cell.remove_humus(-1.0);
+    }
+
+    // events that add or remove the layers of the cells they are applied to
+    const LAYER_EVENTS: [Events; 18] = [
+        Events::ThermalStress,
+        Events::FreezeThaw,
+        Events::Lightning,
+        Events::RockSlide,
+        Events::RockWeathering,
+        Events::SandSlide,
+        Events::HumusSlide,
+        Events::Landslide,
+        Events::Fire,
+        Events::SnowFall,
+        Events::SnowMelt,
+        Events::VegetationTrees,
+        Events::VegetationBushes,
+        Events::VegetationGrasses,
+        Events::Wind,
+        Events::Windthrow,
+        Events::Avalanche,
+        Events::Outbreak,
+    ];
+
+    proptest! {
+        // each case builds a whole ecosystem, so only a few are run
+        #![proptest_config(ProptestConfig::with_cases(4))]
+
+        #[test]
+        fn test_layers_stay_non_negative(
+            seed in any::<u64>(),
+            applied in vec(
+                (
+                    0..LAYER_EVENTS.len(),
+                    0..constants::AREA_SIDE_LENGTH,
+                    0..constants::AREA_SIDE_LENGTH,
+                ),
+                2000,
+            ),
+        ) {
+            // random sequences of events on rough terrain with thin and missing layers
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut ecosystem = Ecosystem::init_with_seed(seed);
+            for cell in ecosystem.cells.iter_mut() {
+                cell.set_height_of_bedrock(rng.gen_range(0.0..20.0));
+                for add in [Cell::add_rocks, Cell::add_sand, Cell::add_humus] {
+                    if rng.gen_bool(0.5) {
+                        add(cell, rng.gen_range(0.0..1.0));
+                    }
+                }
+                if rng.gen_bool(0.2) {
+                    cell.add_snow(rng.gen_range(0.0..0.1), 200.0);
+                }
+                cell.add_dead_vegetation(rng.gen_range(0.0..10.0));
+                cell.soil_moisture = rng.gen_range(0.0..1.0E5);
+            }
+
+            for (event, x, y) in applied {
+                LAYER_EVENTS[event].apply_event(&mut ecosystem, CellIndex::new(x, y));
+            }
+
+            for cell in ecosystem.cells.iter() {
+                for height in [
+                    cell.get_bedrock_height(),
+                    cell.get_rock_height(),
+                    cell.get_sand_height(),
+                    cell.get_humus_height(),
+                    cell.get_dead_vegetation_biomass(),
+                    cell.get_water_height(),
+                    cell.get_snow_height(),
+                ] {
+                    prop_assert!(height >= 0.0, "Expected non-negative, actual {}", height);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,166 @@
+// BIOMES
+// each cell is classified into one biome by what covers it, checked in order
+// 1) wetland where water stands or the soil stays wet through the year
+// 2) alpine above the treeline, where even the warmest month is too cold for trees
+// 3) deciduous forest, shrubland, or grassland by the vegetation covering the cell
+// 4) dune where sand covers the ground nothing grows on, and bare rock everywhere else
+
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use super::{Cell, CellIndex, Ecosystem};
+use crate::{constants, events::Events};
+
+// m of standing water that makes a wetland
+const WETLAND_WATER_DEPTH: f32 = 0.05;
+// °C the mean temperature of the warmest month stays below above the treeline
+const TREELINE_TEMPERATURE: f32 = 10.0;
+// share of the cell covered by tree crowns that makes a forest
+const FOREST_CANOPY_COVER: f32 = 0.3;
+// share of the cell covered by bushes or grasses that makes a shrubland or grassland
+const VEGETATION_COVER: f32 = 0.2;
+// m of sand that makes a dune
+const DUNE_SAND_DEPTH: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Biome {
+    // also soil that nothing grows on
+    BareRock,
+    Dune,
+    Grassland,
+    Shrubland,
+    DeciduousForest,
+    Wetland,
+    Alpine,
+}
+
+pub const BIOMES: [Biome; 7] = [
+    Biome::BareRock,
+    Biome::Dune,
+    Biome::Grassland,
+    Biome::Shrubland,
+    Biome::DeciduousForest,
+    Biome::Wetland,
+    Biome::Alpine,
+];
+
+impl Biome {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Biome::BareRock => "bare rock",
+            Biome::Dune => "dune",
+            Biome::Grassland => "grassland",
+            Biome::Shrubland => "shrubland",
+            Biome::DeciduousForest => "deciduous forest",
+            Biome::Wetland => "wetland",
+            Biome::Alpine => "alpine",
+        }
+    }
+
+    // color of the biome in the exported map and its legend
+    pub fn get_rgb(&self) -> [u8; 3] {
+        match self {
+            Biome::BareRock => [128, 128, 128],
+            Biome::Dune => [230, 210, 140],
+            Biome::Grassland => [170, 210, 90],
+            Biome::Shrubland => [120, 150, 60],
+            Biome::DeciduousForest => [30, 100, 40],
+            Biome::Wetland => [60, 130, 170],
+            Biome::Alpine => [240, 240, 250],
+        }
+    }
+
+    pub fn get_color(&self) -> Vector3<f32> {
+        Vector3::from(self.get_rgb()).cast::<f32>() / 255.0
+    }
+}
+
+impl Ecosystem {
+    pub fn classify_biome(&self, index: CellIndex) -> Biome {
+        let cell = &self[index];
+
+        // 1) wetland
+        let annual_moisture = (0..12)
+            .map(|month| Events::compute_moisture(self, index, month))
+            .sum::<f32>()
+            / 12.0;
+        if cell.get_water_height() >= WETLAND_WATER_DEPTH
+            || annual_moisture >= constants::WET_SOIL_MOISTURE
+        {
+            return Biome::Wetland;
+        }
+
+        // 2) alpine
+        let warmest = (0..12)
+            .map(|month| self.get_local_temperature(index, month))
+            .fold(f32::MIN, f32::max);
+        if warmest < TREELINE_TEMPERATURE {
+            return Biome::Alpine;
+        }
+
+        // 3) vegetation
+        if cell.estimate_total_tree_density() >= FOREST_CANOPY_COVER {
+            return Biome::DeciduousForest;
+        }
+        let bushes = cell
+            .bushes
+            .as_ref()
+            .map_or(0.0, Cell::estimate_bushes_density);
+        let grasses = cell
+            .grasses
+            .as_ref()
+            .map_or(0.0, |grasses| grasses.coverage_density);
+        if f32::max(bushes, grasses) >= VEGETATION_COVER {
+            return if bushes >= grasses {
+                Biome::Shrubland
+            } else {
+                Biome::Grassland
+            };
+        }
+
+        // 4) bare ground
+        if cell.get_sand_height() >= DUNE_SAND_DEPTH {
+            Biome::Dune
+        } else {
+            Biome::BareRock
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Biome, WETLAND_WATER_DEPTH};
+    use crate::ecology::{Bushes, CellIndex, Ecosystem, Grasses, Trees};
+
+    #[test]
+    fn test_classify_biome() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(10, 10);
+        assert_eq!(ecosystem.classify_biome(index), Biome::BareRock);
+
+        ecosystem[index].add_sand(1.0);
+        assert_eq!(ecosystem.classify_biome(index), Biome::Dune);
+
+        ecosystem[index].grasses = Some(Grasses {
+            coverage_density: 0.5,
+            green_fraction: 1.0,
+        });
+        assert_eq!(ecosystem.classify_biome(index), Biome::Grassland);
+
+        ecosystem[index].bushes = Some(Bushes::with_plants(100, 200.0, 1000.0));
+        assert_eq!(ecosystem.classify_biome(index), Biome::Shrubland);
+
+        ecosystem[index].trees = vec![Trees::with_plants(0, 100, 2000.0, 5000.0)];
+        assert_eq!(ecosystem.classify_biome(index), Biome::DeciduousForest);
+
+        // too cold for trees in the warmest month
+        let temperatures = ecosystem.config.climate.monthly_temperatures;
+        ecosystem.config.climate.monthly_temperatures = [5.0; 12];
+        assert_eq!(ecosystem.classify_biome(index), Biome::Alpine);
+        ecosystem.config.climate.monthly_temperatures = temperatures;
+
+        // standing water floods everything else
+        ecosystem[index].add_water(WETLAND_WATER_DEPTH);
+        assert_eq!(ecosystem.classify_biome(index), Biome::Wetland);
+    }
+}
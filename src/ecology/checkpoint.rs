@@ -0,0 +1,175 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use super::Ecosystem;
+
+// start of every checkpoint file
+const CHECKPOINT_MAGIC: &[u8; 8] = b"HUMMUSCK";
+// bincode is not self-describing, so a checkpoint can only be read by the version that wrote it
+// bump this whenever a saved field is added, removed, reordered, or changes type
+const CHECKPOINT_VERSION: u32 = 1;
+
+impl Ecosystem {
+    // writes the full ecosystem state to disk so a simulation can be resumed later,
+    // after a header with the version of the format
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        println!("Saving checkpoint to {path}");
+        let write = || -> Result<(), Box<dyn std::error::Error>> {
+            let mut writer = BufWriter::new(File::create(path)?);
+            writer.write_all(CHECKPOINT_MAGIC)?;
+            writer.write_all(&CHECKPOINT_VERSION.to_le_bytes())?;
+            bincode::serialize_into(&mut writer, self)?;
+            writer.flush()?;
+            Ok(())
+        };
+        write().map_err(|error| format!("cannot save checkpoint {path}: {error}"))
+    }
+
+    // reads an ecosystem saved with `save` by the same version of the format
+    pub fn load(path: &str) -> Result<Self, String> {
+        println!("Loading checkpoint from {path}");
+        let file =
+            File::open(path).map_err(|error| format!("cannot open checkpoint {path}: {error}"))?;
+        let mut reader = BufReader::new(file);
+        let mut header = [0; CHECKPOINT_MAGIC.len() + 4];
+        reader
+            .read_exact(&mut header)
+            .map_err(|error| format!("invalid checkpoint {path}: {error}"))?;
+        let (magic, version) = header.split_at(CHECKPOINT_MAGIC.len());
+        if magic != CHECKPOINT_MAGIC {
+            return Err(format!("{path} is not a checkpoint"));
+        }
+        let version = u32::from_le_bytes(version.try_into().unwrap());
+        if version != CHECKPOINT_VERSION {
+            return Err(format!(
+                "checkpoint {path} has format version {version}, expected {CHECKPOINT_VERSION}"
+            ));
+        }
+        let mut ecosystem: Ecosystem = bincode::deserialize_from(reader)
+            .map_err(|error| format!("invalid checkpoint {path}: {error}"))?;
+        // the ray tracing geometry is not saved, so rebuild it from the loaded terrain
+        ecosystem.init_cell_tets();
+        Ok(ecosystem)
+    }
+
+    // compresses the full ecosystem state in memory, e.g. to rewind the simulation to it later
+    pub fn to_snapshot(&self) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(vec![], Compression::fast());
+        // one large write is much faster than compressing each field as it is serialized
+        encoder.write_all(&bincode::serialize(self).unwrap()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    // decompresses an ecosystem taken with `to_snapshot`
+    pub fn from_snapshot(snapshot: &[u8]) -> Self {
+        let mut ecosystem: Ecosystem =
+            bincode::deserialize_from(DeflateDecoder::new(snapshot)).unwrap();
+        ecosystem.init_cell_tets();
+        ecosystem
+    }
+
+    // copies the saved state without the ray tracing geometry,
+    // for showing, inspecting, and exporting the ecosystem away from the thread simulating it
+    pub fn copy_state(&self) -> Self {
+        bincode::deserialize(&bincode::serialize(self).unwrap()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CHECKPOINT_MAGIC, CHECKPOINT_VERSION};
+    use crate::{
+        config::Initializer,
+        ecology::{CellIndex, Ecosystem, Grasses, Trees},
+        events::wind::WindState,
+    };
+
+    #[test]
+    fn test_save_and_load() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(3, 4);
+        let cell = &mut ecosystem[index];
+        cell.add_rocks(1.5);
+        cell.add_sand(2.5);
+        cell.add_humus(0.5);
+        cell.add_dead_vegetation(10.0);
+        cell.trees = vec![Trees::with_plants(0, 3, 30.0, 40.0)];
+        cell.grasses = Some(Grasses {
+            coverage_density: 0.7,
+            green_fraction: 0.4,
+        });
+        cell.soil_moisture = 123.0;
+        cell.hours_of_sunlight[5] = 2.0;
+        let mut wind_state = WindState::new();
+        wind_state.wind_rose.update_wind(180.0, 5.0, 10.0, 0.5);
+        wind_state.wind_direction = 180.0;
+        ecosystem.wind_state = Some(wind_state);
+        ecosystem.config.initializer = Initializer::Dunes;
+        ecosystem.time_step = 42;
+
+        let path = std::env::temp_dir().join("test_save_and_load.bin");
+        let path = path.to_str().unwrap();
+        ecosystem.save(path).unwrap();
+        let loaded = Ecosystem::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let expected = &ecosystem[index];
+        let actual = &loaded[index];
+        assert_eq!(actual.get_bedrock_height(), expected.get_bedrock_height());
+        assert_eq!(actual.get_rock_height(), expected.get_rock_height());
+        assert_eq!(actual.get_sand_height(), expected.get_sand_height());
+        assert_eq!(actual.get_humus_height(), expected.get_humus_height());
+        assert_eq!(
+            actual.get_dead_vegetation_biomass(),
+            expected.get_dead_vegetation_biomass()
+        );
+        assert_eq!(actual.get_trees(0).unwrap().cohorts[0].number_of_plants, 3);
+        assert_eq!(actual.grasses.as_ref().unwrap().coverage_density, 0.7);
+        assert_eq!(actual.grasses.as_ref().unwrap().green_fraction, 0.4);
+        assert!(actual.bushes.is_none());
+        assert_eq!(actual.soil_moisture, 123.0);
+        assert_eq!(actual.hours_of_sunlight, expected.hours_of_sunlight);
+
+        let wind_state = loaded.wind_state.as_ref().unwrap();
+        assert_eq!(wind_state.wind_direction, 180.0);
+        assert_eq!(wind_state.wind_rose.weights[4], 0.5);
+        assert_eq!(loaded.config.initializer, Initializer::Dunes);
+        assert_eq!(loaded.time_step, 42);
+        assert_eq!(loaded.tets.len(), ecosystem.tets.len());
+    }
+
+    #[test]
+    fn test_load_other_version() {
+        let path = std::env::temp_dir().join("test_load_other_version.bin");
+        let path = path.to_str().unwrap();
+        Ecosystem::init().save(path).unwrap();
+
+        // checkpoints of other versions of the format and other files are errors
+        let mut bytes = std::fs::read(path).unwrap();
+        bytes[CHECKPOINT_MAGIC.len()..CHECKPOINT_MAGIC.len() + 4]
+            .copy_from_slice(&(CHECKPOINT_VERSION + 1).to_le_bytes());
+        std::fs::write(path, &bytes).unwrap();
+        assert!(Ecosystem::load(path).is_err());
+        std::fs::write(path, b"not a checkpoint").unwrap();
+        assert!(Ecosystem::load(path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(3, 4);
+        ecosystem[index].add_sand(2.5);
+        ecosystem.time_step = 7;
+
+        let snapshot = ecosystem.to_snapshot();
+        let restored = Ecosystem::from_snapshot(&snapshot);
+        assert_eq!(restored[index].get_sand_height(), 2.5);
+        assert_eq!(restored.time_step, 7);
+        assert_eq!(restored.tets.len(), ecosystem.tets.len());
+    }
+}
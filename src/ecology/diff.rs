@@ -0,0 +1,203 @@
+// DIFF
+// summarizes what changed between two states of the same terrain, e.g. two checkpoints a century apart
+// 1) the value of every layer is read from each cell of both states
+// 2) the change of each layer is kept per cell, stored row by row like the cells,
+//    positive where the later state has more
+// 3) the changes can be summed over the map or exported as signed heat maps
+
+use super::{Cell, Ecosystem};
+use crate::{constants, events::vegetation::Individualized};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DiffLayer {
+    // total height of the ground and everything on it, in m
+    Height,
+    Bedrock,
+    Rocks,
+    Sand,
+    Humus,
+    Water,
+    // biomass in kg
+    TreeBiomass,
+    BushBiomass,
+    GrassBiomass,
+    DeadVegetation,
+    // number of trees of all species and bushes
+    Plants,
+    SoilMoisture,
+}
+
+impl DiffLayer {
+    pub const ALL: [DiffLayer; 12] = [
+        DiffLayer::Height,
+        DiffLayer::Bedrock,
+        DiffLayer::Rocks,
+        DiffLayer::Sand,
+        DiffLayer::Humus,
+        DiffLayer::Water,
+        DiffLayer::TreeBiomass,
+        DiffLayer::BushBiomass,
+        DiffLayer::GrassBiomass,
+        DiffLayer::DeadVegetation,
+        DiffLayer::Plants,
+        DiffLayer::SoilMoisture,
+    ];
+
+    pub fn get_name(self) -> &'static str {
+        match self {
+            DiffLayer::Height => "height",
+            DiffLayer::Bedrock => "bedrock",
+            DiffLayer::Rocks => "rocks",
+            DiffLayer::Sand => "sand",
+            DiffLayer::Humus => "humus",
+            DiffLayer::Water => "water",
+            DiffLayer::TreeBiomass => "tree-biomass",
+            DiffLayer::BushBiomass => "bush-biomass",
+            DiffLayer::GrassBiomass => "grass-biomass",
+            DiffLayer::DeadVegetation => "dead-vegetation",
+            DiffLayer::Plants => "plants",
+            DiffLayer::SoilMoisture => "soil-moisture",
+        }
+    }
+
+    pub fn get_value(self, cell: &Cell) -> f32 {
+        match self {
+            DiffLayer::Height => cell.get_height(),
+            DiffLayer::Bedrock => cell.get_bedrock_height(),
+            DiffLayer::Rocks => cell.get_rock_height(),
+            DiffLayer::Sand => cell.get_sand_height(),
+            DiffLayer::Humus => cell.get_humus_height(),
+            DiffLayer::Water => cell.get_water_height(),
+            DiffLayer::TreeBiomass => cell.estimate_tree_biomass(),
+            DiffLayer::BushBiomass => cell.estimate_bush_biomass(),
+            DiffLayer::GrassBiomass => cell.estimate_grasses_biomass(),
+            DiffLayer::DeadVegetation => cell.get_dead_vegetation_biomass(),
+            DiffLayer::Plants => {
+                let trees: u32 = cell
+                    .trees
+                    .iter()
+                    .map(|trees| trees.get_number_of_plants())
+                    .sum();
+                let bushes = cell
+                    .bushes
+                    .as_ref()
+                    .map_or(0, |bushes| bushes.get_number_of_plants());
+                (trees + bushes) as f32
+            }
+            DiffLayer::SoilMoisture => cell.soil_moisture,
+        }
+    }
+}
+
+pub struct EcosystemDiff {
+    // change of every cell, one list per layer in the order of DiffLayer::ALL
+    pub deltas: Vec<Vec<f32>>,
+}
+
+impl EcosystemDiff {
+    pub fn get_deltas(&self, layer: DiffLayer) -> &[f32] {
+        let position = DiffLayer::ALL.iter().position(|l| *l == layer).unwrap();
+        &self.deltas[position]
+    }
+
+    // net change over the whole map
+    pub fn get_total(&self, layer: DiffLayer) -> f32 {
+        self.get_deltas(layer).iter().sum()
+    }
+
+    // largest loss and gain of a single cell
+    pub fn get_range(&self, layer: DiffLayer) -> (f32, f32) {
+        self.get_deltas(layer)
+            .iter()
+            .fold((0.0, 0.0), |(min, max), delta| {
+                (f32::min(min, *delta), f32::max(max, *delta))
+            })
+    }
+
+    // number of cells where the layer changed by more than the threshold either way
+    pub fn count_changed_cells(&self, layer: DiffLayer, threshold: f32) -> usize {
+        self.get_deltas(layer)
+            .iter()
+            .filter(|delta| delta.abs() > threshold)
+            .count()
+    }
+
+    // one line per layer with its net change and the extremes of single cells
+    pub fn summarize(&self) -> String {
+        let mut summary = String::new();
+        for layer in DiffLayer::ALL {
+            let (min, max) = self.get_range(layer);
+            summary += &format!(
+                "{:<16} net {:>12.3}  min {:>10.3}  max {:>10.3}  changed cells {}\n",
+                layer.get_name(),
+                self.get_total(layer),
+                min,
+                max,
+                self.count_changed_cells(layer, 0.0),
+            );
+        }
+        summary
+    }
+}
+
+impl Ecosystem {
+    // changes from this state to the other one, which must be on a grid of the same size
+    pub fn diff(&self, other: &Ecosystem) -> EcosystemDiff {
+        let deltas = DiffLayer::ALL
+            .iter()
+            .map(|layer| {
+                (0..constants::NUM_CELLS)
+                    .map(|i| layer.get_value(&other.cells[i]) - layer.get_value(&self.cells[i]))
+                    .collect()
+            })
+            .collect();
+        EcosystemDiff { deltas }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::DiffLayer;
+    use crate::ecology::{CellIndex, Ecosystem, Trees};
+
+    #[test]
+    fn test_diff() {
+        let before = Ecosystem::init();
+        let mut after = Ecosystem::init();
+        let eroded = CellIndex::new(10, 10);
+        let deposited = CellIndex::new(11, 10);
+        after[eroded].remove_bedrock(1.5);
+        after[deposited].add_sand(1.5);
+        after[deposited].set_trees(Trees::with_plants(0, 3, 10.0, 5.0));
+        after[eroded].soil_moisture += 0.2;
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.deltas.len(), DiffLayer::ALL.len());
+        let bedrock = diff.get_deltas(DiffLayer::Bedrock);
+        assert_eq!(bedrock[eroded.get_flat_index()], -1.5);
+        assert_eq!(diff.get_range(DiffLayer::Height), (-1.5, 1.5));
+        assert_eq!(diff.count_changed_cells(DiffLayer::Height, 0.0), 2);
+
+        // material moved between cells nets out over the map
+        let actual = diff.get_total(DiffLayer::Height);
+        assert!(
+            approx_eq!(f32, actual, 0.0, epsilon = 0.0001),
+            "Expected 0.0, actual {actual}"
+        );
+        assert_eq!(diff.get_total(DiffLayer::Plants), 3.0);
+        let expected = after[eroded].soil_moisture - before[eroded].soil_moisture;
+        let actual = diff.get_total(DiffLayer::SoilMoisture);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // nothing changes between a state and itself
+        let diff = after.diff(&after);
+        assert!(DiffLayer::ALL
+            .iter()
+            .all(|layer| diff.count_changed_cells(*layer, 0.0) == 0));
+    }
+}
@@ -0,0 +1,264 @@
+// HORIZON MAP
+// approximates ray traced sunlight by comparing the elevation of the sun with the horizon of each cell
+// 1) the angle up to the horizon is computed for every cell in a fixed set of directions by sweeping
+//    each line of cells in that direction once, keeping the convex hull of the terrain ahead
+// 2) the sun shines on the cell whenever it is higher than the horizon in its direction,
+//    interpolated between the two closest directions
+// each sweep is linear in the number of cells, unlike tracing a ray per cell, hour, and month
+
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::{illumination::get_azimuth_and_elevation, CellIndex, Ecosystem};
+use crate::constants;
+
+// steps between cells of the lines swept for each direction, ordered clockwise from north
+// like the azimuth of the sun, with positive X east and positive Y north
+pub const HORIZON_DIRECTIONS: [(i32, i32); 16] = [
+    (0, 1),
+    (1, 2),
+    (1, 1),
+    (2, 1),
+    (1, 0),
+    (2, -1),
+    (1, -1),
+    (1, -2),
+    (0, -1),
+    (-1, -2),
+    (-1, -1),
+    (-2, -1),
+    (-1, 0),
+    (-2, 1),
+    (-1, 1),
+    (-1, 2),
+];
+
+// angles (in radians) up to the horizon of a cell, one per horizon direction
+pub type Horizons = [f32; HORIZON_DIRECTIONS.len()];
+
+impl Ecosystem {
+    // recomputes sunlight for all cells from their horizons
+    pub fn recompute_sunlight_horizon_map(&mut self) {
+        let horizons = self.compute_horizon_map();
+        let sun_positions = get_sun_positions(&self.config.location);
+        let percent_sunny_days = self.config.climate.percent_sunny_days;
+        let cell_hours: Vec<[f32; 12]> = horizons
+            .into_par_iter()
+            .map(|horizons| {
+                compute_hours_of_sunlight(&horizons, &sun_positions)
+                    .map(|hours| hours * percent_sunny_days)
+            })
+            .collect();
+        for (i, hours) in cell_hours.into_iter().enumerate() {
+            self[CellIndex::get_from_flat_index(i)].hours_of_sunlight = hours;
+        }
+    }
+
+    // returns the horizons of every cell, at their flat index
+    pub fn compute_horizon_map(&self) -> Vec<Horizons> {
+        let side = constants::AREA_SIDE_LENGTH as i32;
+        let mut horizon_map = vec![[0.0; HORIZON_DIRECTIONS.len()]; constants::NUM_CELLS];
+        for (direction, &(dx, dy)) in HORIZON_DIRECTIONS.iter().enumerate() {
+            let step_length = ((dx * dx + dy * dy) as f32).sqrt();
+            // every cell is on exactly one line, which starts at the first cell whose
+            // previous cell along the line is off the map
+            for x in 0..side {
+                for y in 0..side {
+                    let (prev_x, prev_y) = (x - dx, y - dy);
+                    if (0..side).contains(&prev_x) && (0..side).contains(&prev_y) {
+                        continue;
+                    }
+                    let mut line = vec![];
+                    let (mut cx, mut cy) = (x, y);
+                    while (0..side).contains(&cx) && (0..side).contains(&cy) {
+                        line.push(CellIndex::new(cx as usize, cy as usize));
+                        cx += dx;
+                        cy += dy;
+                    }
+                    self.sweep_line(&line, step_length, direction, &mut horizon_map);
+                }
+            }
+        }
+        horizon_map
+    }
+
+    // sets the horizons in the direction of the line for all of its cells
+    // the line is swept from its far end, keeping the upper convex hull of the terrain
+    // ahead of the current cell, whose tangent from the cell is its horizon
+    fn sweep_line(
+        &self,
+        line: &[CellIndex],
+        step_length: f32,
+        direction: usize,
+        horizon_map: &mut [Horizons],
+    ) {
+        // distances (in cells, like the ray tracing) along the line and heights of the hull
+        let mut hull: Vec<(f32, f32)> = vec![];
+        for (i, index) in line.iter().enumerate().rev() {
+            let point = (i as f32 * step_length, self[*index].get_height());
+            let slope_to =
+                |(distance, height): (f32, f32)| (height - point.1) / (distance - point.0);
+            // hull points below the line to the point after them cannot be the tangent of this
+            // or any later cell
+            while hull.len() >= 2
+                && slope_to(hull[hull.len() - 1]) <= slope_to(hull[hull.len() - 2])
+            {
+                hull.pop();
+            }
+            // cells at the edge of the map see the open sky
+            let horizon = match hull.last() {
+                Some(&tangent) => slope_to(tangent).atan(),
+                None => -FRAC_PI_2,
+            };
+            horizon_map[index.x + index.y * constants::AREA_SIDE_LENGTH][direction] = horizon;
+            hull.push(point);
+        }
+    }
+}
+
+// returns the azimuth and elevation (in radians) of the sun for every hour of every month,
+// or None when the sun is below the horizon
+fn get_sun_positions(location: &crate::config::Location) -> Vec<Option<(f32, f32)>> {
+    let mut positions = Vec::with_capacity(12 * 24);
+    for month in 0..12 {
+        for hour in 0..24 {
+            let (azimuth, elevation) = get_azimuth_and_elevation(location, month, hour as f32);
+            positions.push((elevation >= 0.0).then_some((azimuth, elevation)));
+        }
+    }
+    positions
+}
+
+// returns the hours of direct sunlight of each month before the weather modifier
+fn compute_hours_of_sunlight(
+    horizons: &Horizons,
+    sun_positions: &[Option<(f32, f32)>],
+) -> [f32; 12] {
+    let mut monthly_hours = [0.0; 12];
+    for (month, hours) in monthly_hours.iter_mut().enumerate() {
+        for (azimuth, elevation) in sun_positions[month * 24..(month + 1) * 24].iter().flatten() {
+            if *elevation >= get_horizon(horizons, *azimuth) {
+                *hours += 1.0;
+            }
+        }
+    }
+    monthly_hours
+}
+
+// returns the horizon in the given azimuth, interpolated between the closest directions
+pub fn get_horizon(horizons: &Horizons, azimuth: f32) -> f32 {
+    let azimuth = azimuth.rem_euclid(2.0 * PI);
+    let get_azimuth = |direction: usize| {
+        let (dx, dy) = HORIZON_DIRECTIONS[direction % HORIZON_DIRECTIONS.len()];
+        (dx as f32).atan2(dy as f32).rem_euclid(2.0 * PI)
+    };
+    for direction in 0..HORIZON_DIRECTIONS.len() {
+        let start = get_azimuth(direction);
+        let mut end = get_azimuth(direction + 1);
+        // the last direction wraps around to north
+        if end <= start {
+            end += 2.0 * PI;
+        }
+        if azimuth >= start && azimuth < end {
+            let t = (azimuth - start) / (end - start);
+            let next = horizons[(direction + 1) % HORIZON_DIRECTIONS.len()];
+            return horizons[direction] * (1.0 - t) + next * t;
+        }
+    }
+    horizons[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    use float_cmp::approx_eq;
+
+    use super::{get_horizon, HORIZON_DIRECTIONS};
+    use crate::{
+        constants,
+        ecology::{CellIndex, Ecosystem},
+    };
+
+    #[test]
+    fn test_get_horizon() {
+        let mut horizons = [0.0; HORIZON_DIRECTIONS.len()];
+        horizons[0] = 1.0;
+        // exactly north
+        assert_eq!(get_horizon(&horizons, 0.0), 1.0);
+        // halfway between north and the next direction
+        let actual = get_horizon(&horizons, 0.5_f32.atan2(1.0) / 2.0);
+        assert!(
+            approx_eq!(f32, actual, 0.5, epsilon = 0.0001),
+            "Expected 0.5, actual {actual}"
+        );
+        // and back around from the west
+        assert_eq!(get_horizon(&horizons, 2.0 * PI - 0.0001).round(), 1.0);
+        assert_eq!(get_horizon(&horizons, PI), 0.0);
+    }
+
+    #[test]
+    fn test_compute_horizon_map() {
+        let mut ecosystem = Ecosystem::init();
+        let wall = CellIndex::new(50, 50);
+        ecosystem[wall].add_bedrock(2.0);
+        let horizon_map = ecosystem.compute_horizon_map();
+        let flat_index = |index: CellIndex| index.x + index.y * constants::AREA_SIDE_LENGTH;
+
+        // the cell south of the raised cell sees it at 45° to the north
+        let south = horizon_map[flat_index(CellIndex::new(50, 48))];
+        let expected = FRAC_PI_4;
+        let actual = south[0];
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+        // but not to the south, where the ground is flat
+        assert_eq!(south[8], 0.0);
+
+        // cells at the edge of the map see the open sky
+        let edge = horizon_map[flat_index(CellIndex::new(50, constants::AREA_SIDE_LENGTH - 1))];
+        assert_eq!(edge[0], -FRAC_PI_2);
+    }
+
+    #[test]
+    fn test_recompute_sunlight_horizon_map() {
+        // walls casting shadows in every direction over flat ground
+        let mut ecosystem = Ecosystem::init();
+        for i in 20..60 {
+            ecosystem[CellIndex::new(i, 30)].add_bedrock(5.0);
+            ecosystem[CellIndex::new(70, i)].add_bedrock(5.0);
+            ecosystem[CellIndex::new(i, i + 20)].add_bedrock(5.0);
+        }
+        ecosystem.update_tets();
+        ecosystem.recompute_sunlight();
+        let ray_traced: Vec<[f32; 12]> = ecosystem
+            .cells
+            .iter()
+            .map(|cell| cell.hours_of_sunlight)
+            .collect();
+
+        // the approximation stays close to the ray traced sunlight on average
+        ecosystem.recompute_sunlight_horizon_map();
+        let side = constants::AREA_SIDE_LENGTH - 1;
+        let mut total_difference = 0.0;
+        for x in 0..side {
+            for y in 0..side {
+                let index = CellIndex::new(x, y);
+                let expected = ray_traced[index.get_flat_index()];
+                let actual = ecosystem[index].hours_of_sunlight;
+                total_difference += expected
+                    .iter()
+                    .zip(actual)
+                    .map(|(expected, actual)| (expected - actual).abs())
+                    .sum::<f32>();
+            }
+        }
+        let mean_difference = total_difference / (side * side * 12) as f32;
+        assert!(
+            mean_difference < 0.5,
+            "Expected under 0.5 hours on average, actual {mean_difference}"
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use bvh::{
     aabb::{Aabb, Bounded},
     bounding_hierarchy::{BHShape, BoundingHierarchy},
@@ -8,14 +10,24 @@ use nalgebra::{Point3, Vector3, Vector4};
 use ordered_float::OrderedFloat;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use crate::constants;
+use crate::{
+    config::{IlluminationMethod, Location},
+    constants,
+};
 
 use super::{Cell, CellIndex, Ecosystem};
 
+// tets are only rebuilt once a corner moved by more than this (in m), so slow humus buildup does not
+// trigger ray tracing every time step
+const MIN_TERRAIN_CHANGE: f32 = 0.05;
+// sunlight is recomputed for cells at most this many cells away from changed terrain
+// shadows cast further by a low sun are picked up at the next full recompute
+const SHADOW_RADIUS: usize = 10;
+
 // a three dimensional rectangle representing the two planes constructed from a cell index and its neighboring three points
 // for index (x,y), rectangle is formed with (x,y), (x+1, y), (x, y+1), and (x+1, y+1)
 // planes are (x,y), (x+1, y), (x, y+1) and (x+1, y), (x, y+1), (x+1, y+1)
-pub(crate) struct CellTetrahedron {
+pub struct CellTetrahedron {
     coordinates: Vector4<Vector3<f32>>,
     top_left: CellIndex,
     top_right: CellIndex,
@@ -29,7 +41,7 @@ pub(crate) struct CellTetrahedron {
 }
 
 impl CellTetrahedron {
-    pub(crate) fn new(index: CellIndex, ecosystem: &Ecosystem) -> Self {
+    pub fn new(index: CellIndex, ecosystem: &Ecosystem) -> Self {
         let mut tet = CellTetrahedron {
             coordinates: Vector4::zeros(),
             top_left: index,
@@ -46,7 +58,7 @@ impl CellTetrahedron {
         tet
     }
 
-    pub(crate) fn update(&mut self, ecosystem: &Ecosystem) {
+    pub fn update(&mut self, ecosystem: &Ecosystem) {
         let height = ecosystem[self.top_left].get_height();
         let a = Vector3::new(self.top_left.x as f32, self.top_left.y as f32, height);
         self.coordinates[0] = a;
@@ -89,6 +101,21 @@ impl CellTetrahedron {
         self.scalar_two = scalar_two;
     }
 
+    // whether any corner of the tet moved since it was last updated
+    fn is_stale(&self, ecosystem: &Ecosystem) -> bool {
+        [
+            self.top_left,
+            self.top_right,
+            self.bottom_left,
+            self.bottom_right,
+        ]
+        .into_iter()
+        .zip(self.coordinates.iter())
+        .any(|(index, corner)| {
+            (ecosystem[index].get_height() - corner.z).abs() > MIN_TERRAIN_CHANGE
+        })
+    }
+
     // if intersection, returns t of intersect
     fn has_intersection(&self, pos: Vector3<f32>, dir: Vector3<f32>) -> Option<f32> {
         let height_top_left = self.coordinates[0][2];
@@ -228,33 +255,49 @@ impl BHShape<f32, 3> for CellTetrahedron {
 impl Ecosystem {
     // estimates the illumination of the cell based on traced rays from the sun moving across the sky
     // returns average daily hours of direct sunlight
-    pub(crate) fn estimate_illumination_simple(&self, _index: &CellIndex, month: usize) -> f32 {
-        constants::AVERAGE_SUNLIGHT_HOURS[month]
+    pub fn estimate_illumination_simple(&self, _index: &CellIndex, month: usize) -> f32 {
+        self.config.climate.monthly_sunlight_hours[month]
     }
 
-    pub(crate) fn get_precomputed_illumination_ray_traced(
-        &self,
-        index: &CellIndex,
-        month: usize,
-    ) -> f32 {
+    pub fn get_precomputed_illumination_ray_traced(&self, index: &CellIndex, month: usize) -> f32 {
         let cell = &self[*index];
         cell.hours_of_sunlight[month]
     }
 
-    pub(crate) fn build_bvh(&mut self) {
+    pub fn build_bvh(&mut self) {
         // build bvh
         let bvh = Bvh::build_par(&mut self.tets);
         self.bvh = Some(bvh);
     }
 
-    // recomputes ray traced sunlight for all cells
-    pub(crate) fn recompute_sunlight(&mut self) {
+    // recomputes sunlight for all cells with the illumination method of the config
+    pub fn recompute_sunlight(&mut self) {
+        match self.config.illumination_method {
+            IlluminationMethod::RayTraced => self.recompute_sunlight_ray_traced(),
+            IlluminationMethod::HorizonMap => self.recompute_sunlight_horizon_map(),
+            IlluminationMethod::Constant => {
+                let hours = self.config.climate.monthly_sunlight_hours;
+                for cell in self.cells.iter_mut() {
+                    cell.hours_of_sunlight = hours;
+                }
+            }
+        }
+    }
+
+    // recomputes ray traced sunlight for all cells,
+    // or leaves it to the render thread when it ray traces sunlight on the gpu
+    pub fn recompute_sunlight_ray_traced(&mut self) {
+        if self.sunlight_from_gpu {
+            self.sunlight_stale = true;
+            return;
+        }
         self.build_bvh();
 
         // two of the edges don't have ray traced computation due to lacking the triangles required
+        // ordered by rows like the hours read back below
         let mut indices = vec![];
-        for i in 0..constants::AREA_SIDE_LENGTH - 1 {
-            for j in 0..constants::AREA_SIDE_LENGTH - 1 {
+        for j in 0..constants::AREA_SIDE_LENGTH - 1 {
+            for i in 0..constants::AREA_SIDE_LENGTH - 1 {
                 let index = CellIndex::new(i, j);
                 indices.push(index);
             }
@@ -264,18 +307,26 @@ impl Ecosystem {
             .into_par_iter()
             .map(|index| self.compute_hours_of_sunlight_for_cell(&index))
             .collect();
-        for i in 0..constants::AREA_SIDE_LENGTH - 1 {
-            for j in 0..constants::AREA_SIDE_LENGTH - 1 {
-                let index = i + j * (constants::AREA_SIDE_LENGTH - 1);
-                let hours = cell_hours[index];
-                let cell = &mut self[CellIndex::new(i, j)];
-                cell.hours_of_sunlight = hours;
+        self.set_hours_of_sunlight(&cell_hours);
+    }
+
+    // moves hours of sunlight ray traced for every cell with a tet of its own, ordered by rows, into the cells
+    pub fn set_hours_of_sunlight(&mut self, cell_hours: &[[f32; 12]]) {
+        for j in 0..constants::AREA_SIDE_LENGTH - 1 {
+            for i in 0..constants::AREA_SIDE_LENGTH - 1 {
+                let index = CellIndex::new(i, j);
+                let hours = cell_hours[i + j * (constants::AREA_SIDE_LENGTH - 1)];
+                if self[index].hours_of_sunlight != hours {
+                    self[index].hours_of_sunlight = hours;
+                    // the sunlight color mode shows the new hours
+                    self.changed_cells.insert(index);
+                }
             }
         }
     }
 
     // recomputes the hours of sunlight a cell receives based on ray tracing the sun
-    pub(crate) fn compute_hours_of_sunlight_for_cell(&self, index: &CellIndex) -> [f32; 12] {
+    pub fn compute_hours_of_sunlight_for_cell(&self, index: &CellIndex) -> [f32; 12] {
         let mut monthly_hours = [0.0; 12];
         for (i, entry) in monthly_hours.iter_mut().enumerate() {
             let hours = self.ray_trace_illumination(index, i);
@@ -289,19 +340,15 @@ impl Ecosystem {
     }
 
     // estimate illumination of given cell using rays traced from sun's position across the sky over the year
-    pub(crate) fn ray_trace_illumination(&self, index: &CellIndex, month: usize) -> f32 {
+    pub fn ray_trace_illumination(&self, index: &CellIndex, month: usize) -> f32 {
         // compute sun arc for 1st of every month
         let mut hours_of_sun = 0;
         'outer: for i in 0..24 {
             // for every hour, determine if sun is above horizon
-            let (azimuth, elevation) = get_azimuth_and_elevation(month, i as f32);
-            if elevation < 0.0 {
+            let Some(sun_dir) = get_sun_direction(&self.config.location, month, i) else {
                 continue;
-            }
+            };
             // if so, trace rays to determine hours of light
-            // direction towards the sun in the sky
-            // positive X is east, positive Y is north
-            let sun_dir = convert_from_spherical_to_cartesian(azimuth, elevation);
             // println!("sun_dir {sun_dir}");
             // center of the target cell
             let center = self.get_position_of_cell(index) + Vector3::new(0.5, 0.5, 0.0);
@@ -310,11 +357,7 @@ impl Ecosystem {
             let pos = center + sun_dir * 0.01;
             // direction is the unit vector from the position of the sun to the target
             let dir = sun_dir;
-            let ray = Ray {
-                origin: pos.into(),
-                direction: dir,
-                inv_direction: -dir,
-            };
+            let ray = Ray::new(pos.into(), dir);
             // println!("{index} month {month}");
             // println!("pos {pos}, dir {dir}");
             let bvh = self.bvh.as_ref().unwrap();
@@ -332,13 +375,86 @@ impl Ecosystem {
 
         // apply weather modifier
 
-        hours_of_sun as f32 * constants::PERCENT_SUNNY_DAYS
+        hours_of_sun as f32 * self.config.climate.percent_sunny_days
     }
 
     // call this function to update the topography for illumination ray tracing
-    pub(crate) fn update_tets(&mut self) {
-        // todo make more efficient than completely rebuilding
-        self.init_cell_tets();
+    pub fn update_tets(&mut self) {
+        let mut tets = std::mem::take(&mut self.tets);
+        for tet in &mut tets {
+            tet.update(self);
+        }
+        self.tets = tets;
+        self.dirty_cells.clear();
+        // bounding boxes of the tets changed
+        if self.bvh.is_some() {
+            self.build_bvh();
+        }
+    }
+
+    // marks the cell so its tets and the sunlight around it are updated at the end of the time step
+    pub fn mark_dirty(&mut self, index: CellIndex) {
+        self.dirty_cells.insert(index);
+        self.changed_cells.insert(index);
+    }
+
+    // updates the tets touching dirty cells and recomputes sunlight of the cells they can shade,
+    // or queues them when sunlight is progressive
+    pub fn update_dirty_terrain(&mut self) {
+        let side = constants::AREA_SIDE_LENGTH - 1;
+        // each cell is a corner of up to four tets
+        let mut stale = HashSet::new();
+        for index in std::mem::take(&mut self.dirty_cells) {
+            for x in index.x.saturating_sub(1)..=usize::min(index.x, side - 1) {
+                for y in index.y.saturating_sub(1)..=usize::min(index.y, side - 1) {
+                    let tet_index = x + y * side;
+                    if self.tets[tet_index].is_stale(self) {
+                        stale.insert(tet_index);
+                    }
+                }
+            }
+        }
+        if stale.is_empty() {
+            return;
+        }
+
+        let mut tets = std::mem::take(&mut self.tets);
+        for &tet_index in &stale {
+            tets[tet_index].update(self);
+        }
+        self.tets = tets;
+
+        // the gpu retraces the whole map at once, which also catches shadows cast beyond the radius,
+        // and the other methods are fast enough to always recompute everything
+        if self.sunlight_from_gpu
+            || self.config.illumination_method != IlluminationMethod::RayTraced
+        {
+            self.recompute_sunlight();
+            return;
+        }
+        self.build_bvh();
+
+        // cells without a tet of their own are never ray traced, as in recompute_sunlight
+        let mut receivers = HashSet::new();
+        for tet_index in stale {
+            let (x, y) = (tet_index % side, tet_index / side);
+            for x in x.saturating_sub(SHADOW_RADIUS)..usize::min(x + SHADOW_RADIUS + 2, side) {
+                for y in y.saturating_sub(SHADOW_RADIUS)..usize::min(y + SHADOW_RADIUS + 2, side) {
+                    receivers.insert(CellIndex::new(x, y));
+                }
+            }
+        }
+        if self.progressive_sunlight.is_some() {
+            self.queue_sunlight(receivers);
+            return;
+        }
+        let cell_hours: Vec<(CellIndex, [f32; 12])> = receivers
+            .into_par_iter()
+            .map(|index| (index, self.compute_hours_of_sunlight_for_cell(&index)))
+            .collect();
+        for (index, hours) in cell_hours {
+            self[index].hours_of_sunlight = hours;
+        }
     }
 }
 
@@ -370,26 +486,26 @@ fn days_since_start_of_year(month: usize) -> i32 {
 }
 
 // in degrees
-fn get_local_standard_time_meridian() -> i32 {
-    15 * constants::TIMEZONE
+fn get_local_standard_time_meridian(location: &Location) -> i32 {
+    15 * location.timezone
 }
 
-fn get_time_correction_factor(month: usize) -> f32 {
-    4.0 * (constants::LONGITUDE - get_local_standard_time_meridian() as f32)
+fn get_time_correction_factor(location: &Location, month: usize) -> f32 {
+    4.0 * (location.longitude - get_local_standard_time_meridian(location) as f32)
         + compute_equation_of_time(month)
 }
 
 // local time is in hours since midnight
 // returns the adjusted time based on sun's position
-fn get_local_solar_time(month: usize, local_time: f32) -> f32 {
-    let time_correction_factor = get_time_correction_factor(month);
+fn get_local_solar_time(location: &Location, month: usize, local_time: f32) -> f32 {
+    let time_correction_factor = get_time_correction_factor(location, month);
     local_time + time_correction_factor / 60.0
 }
 
 // converts local solar time (LST) to number of degrees which the sun moves across the sky
 // hour angle is 0° at noon
-fn get_hour_angle(month: usize, local_time: f32) -> f32 {
-    15.0 * (get_local_solar_time(month, local_time) - 12.0)
+fn get_hour_angle(location: &Location, month: usize, local_time: f32) -> f32 {
+    15.0 * (get_local_solar_time(location, month, local_time) - 12.0)
 }
 
 fn get_declination(month: usize) -> f32 {
@@ -397,19 +513,19 @@ fn get_declination(month: usize) -> f32 {
     23.45 * f32::sin((360.0 / 365.0 * (days - 81) as f32).to_radians())
 }
 
-fn get_elevation(month: usize, local_time: f32) -> f32 {
+fn get_elevation(location: &Location, month: usize, local_time: f32) -> f32 {
     let declination = get_declination(month).to_radians();
-    let hra = get_hour_angle(month, local_time).to_radians();
-    let latitude = constants::LATITUDE.to_radians();
+    let hra = get_hour_angle(location, month, local_time).to_radians();
+    let latitude = location.latitude.to_radians();
     f32::asin(declination.sin() * latitude.sin() + declination.cos() * latitude.cos() * hra.cos())
 }
 
-fn get_azimuth_and_elevation(month: usize, local_time: f32) -> (f32, f32) {
+pub fn get_azimuth_and_elevation(location: &Location, month: usize, local_time: f32) -> (f32, f32) {
     // return (f32::to_radians(180.0), f32::to_radians(10.0));
-    let elevation = get_elevation(month, local_time);
+    let elevation = get_elevation(location, month, local_time);
     let declination = get_declination(month).to_radians();
-    let hra = get_hour_angle(month, local_time).to_radians();
-    let latitude = constants::LATITUDE.to_radians();
+    let hra = get_hour_angle(location, month, local_time).to_radians();
+    let latitude = location.latitude.to_radians();
     // angle between 0-π radians
     let angle = f32::acos(
         (declination.sin() * latitude.cos() - declination.cos() * latitude.sin() * hra.cos())
@@ -423,6 +539,17 @@ fn get_azimuth_and_elevation(month: usize, local_time: f32) -> (f32, f32) {
     }
 }
 
+// returns the direction towards the sun in the sky at the given hour of the first day of the month,
+// or None if the sun is below the horizon
+// positive X is east, positive Y is north
+pub fn get_sun_direction(location: &Location, month: usize, hour: usize) -> Option<Vector3<f32>> {
+    let (azimuth, elevation) = get_azimuth_and_elevation(location, month, hour as f32);
+    if elevation < 0.0 {
+        return None;
+    }
+    Some(convert_from_spherical_to_cartesian(azimuth, elevation))
+}
+
 // convert from angles given in the azimuth-altitude/elevation system to x,y,z cartesian (z up)
 fn convert_from_spherical_to_cartesian(azimuth: f32, elevation: f32) -> Vector3<f32> {
     let x = azimuth.sin() * elevation.cos();
@@ -437,6 +564,7 @@ mod tests {
     use nalgebra::Vector3;
 
     use crate::{
+        config::Location,
         constants,
         ecology::{
             illumination::{compute_equation_of_time, get_azimuth_and_elevation, get_declination},
@@ -503,7 +631,8 @@ mod tests {
 
     #[test]
     fn test_get_azimuth_and_elevation() {
-        let (azimuth, elevation) = get_azimuth_and_elevation(0, 12.0);
+        let location = Location::default();
+        let (azimuth, elevation) = get_azimuth_and_elevation(&location, 0, 12.0);
         let azimuth = azimuth.to_degrees();
         let elevation = elevation.to_degrees();
         let expected = 183.1;
@@ -517,7 +646,7 @@ mod tests {
             "Expected {expected}, actual {elevation}"
         );
 
-        let (azimuth, elevation) = get_azimuth_and_elevation(0, 15.0);
+        let (azimuth, elevation) = get_azimuth_and_elevation(&location, 0, 15.0);
         let azimuth = azimuth.to_degrees();
         let elevation = elevation.to_degrees();
         let expected = 224.4;
@@ -531,7 +660,7 @@ mod tests {
             "Expected {expected}, actual {elevation}"
         );
 
-        let (azimuth, elevation) = get_azimuth_and_elevation(6, 9.0);
+        let (azimuth, elevation) = get_azimuth_and_elevation(&location, 6, 9.0);
         let azimuth = azimuth.to_degrees();
         let elevation = elevation.to_degrees();
         let expected = 104.06;
@@ -704,10 +833,8 @@ mod tests {
     #[test]
     fn test_compute_hours_of_sunlight_for_cell() {
         let mut ecosystem = Ecosystem::init();
-        for row in &mut ecosystem.cells {
-            for cell in row {
-                cell.add_humus(1.0);
-            }
+        for cell in &mut ecosystem.cells {
+            cell.add_humus(1.0);
         }
         let index = CellIndex::new(2, 2);
         let cell = &ecosystem[index];
@@ -730,4 +857,84 @@ mod tests {
         assert_eq!(ecosystem[CellIndex::new(3, 3)].hours_of_sunlight, expected);
         assert_eq!(ecosystem[CellIndex::new(4, 4)].hours_of_sunlight, expected);
     }
+
+    #[test]
+    fn test_recompute_sunlight_keeps_cells_in_place() {
+        // a wall shading the cells north of it, but not the ones mirrored across the diagonal
+        let mut ecosystem = Ecosystem::init();
+        for x in 20..30 {
+            ecosystem[CellIndex::new(x, 40)].add_bedrock(100.0);
+        }
+        ecosystem.update_tets();
+        ecosystem.recompute_sunlight();
+        for index in [CellIndex::new(25, 41), CellIndex::new(41, 25)] {
+            let expected = ecosystem.compute_hours_of_sunlight_for_cell(&index);
+            assert_eq!(ecosystem[index].hours_of_sunlight, expected);
+        }
+    }
+
+    #[test]
+    fn test_update_dirty_terrain() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.recompute_sunlight();
+        let num_tets = ecosystem.tets.len();
+        let shaded = CellIndex::new(50, 52);
+        let far = CellIndex::new(10, 10);
+        let expected_far = ecosystem[far].hours_of_sunlight;
+        let unshaded: f32 = ecosystem[shaded].hours_of_sunlight.iter().sum();
+
+        // small changes do not trigger any updates
+        ecosystem[shaded].add_humus(0.01);
+        ecosystem.mark_dirty(shaded);
+        ecosystem.update_dirty_terrain();
+        assert!(ecosystem.dirty_cells.is_empty());
+        assert_eq!(
+            ecosystem[shaded].hours_of_sunlight.iter().sum::<f32>(),
+            unshaded
+        );
+
+        // wall to the south
+        for x in 45..56 {
+            let index = CellIndex::new(x, 50);
+            ecosystem[index].add_bedrock(100.0);
+            ecosystem.mark_dirty(index);
+        }
+        ecosystem.update_dirty_terrain();
+
+        // cells near the wall match a full ray trace and lose sunlight
+        let expected = ecosystem.compute_hours_of_sunlight_for_cell(&shaded);
+        assert_eq!(ecosystem[shaded].hours_of_sunlight, expected);
+        assert!(expected.iter().sum::<f32>() < unshaded);
+        // cells far away are left alone
+        assert_eq!(ecosystem[far].hours_of_sunlight, expected_far);
+        assert_eq!(ecosystem.tets.len(), num_tets);
+    }
+
+    #[test]
+    fn test_sunlight_from_gpu() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.recompute_sunlight();
+        ecosystem.sunlight_from_gpu = true;
+        let shaded = CellIndex::new(50, 52);
+        let unshaded = ecosystem[shaded].hours_of_sunlight;
+
+        // terrain changes leave sunlight to the render thread
+        for x in 45..56 {
+            let index = CellIndex::new(x, 50);
+            ecosystem[index].add_bedrock(100.0);
+            ecosystem.mark_dirty(index);
+        }
+        ecosystem.update_dirty_terrain();
+        assert!(ecosystem.sunlight_stale);
+        assert_eq!(ecosystem[shaded].hours_of_sunlight, unshaded);
+
+        // the hours it sends back are moved into the cells
+        ecosystem.changed_cells.clear();
+        let side = constants::AREA_SIDE_LENGTH - 1;
+        let mut cell_hours = vec![unshaded; side * side];
+        cell_hours[shaded.x + shaded.y * side] = [1.0; 12];
+        ecosystem.set_hours_of_sunlight(&cell_hours);
+        assert_eq!(ecosystem[shaded].hours_of_sunlight, [1.0; 12]);
+        assert!(ecosystem.changed_cells.contains(&shaded));
+    }
 }
@@ -64,22 +64,17 @@ impl Ecosystem {
         ecosystem
     }
 
-    pub fn init_standard() -> Self {
-        let mut ecosystem = Self::init();
+    pub fn init_standard(seed: u64) -> Self {
+        let mut ecosystem = Self::init_with_seed(seed);
 
-        let trees = Trees {
-            number_of_plants: 15,
-            plant_height_sum: 150.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 15, 150.0, 10.0);
 
         let noise = Perlin::new(1);
         let mut perlin_overlay: [[f32; 100]; 100] = [[0.0; 100]; 100];
 
         for i in 0..100 {
             for j in 0..100 {
-                let mut rng = rand::thread_rng();
-                let choice: f32 = rng.gen();
+                let choice: f32 = ecosystem.rng.gen();
 
                 let cell = &mut ecosystem[CellIndex::new(i, j)];
                 let bedrock = cell.bedrock.as_mut().unwrap();
@@ -92,7 +87,7 @@ impl Ecosystem {
 
                 perlin_overlay[i][j] = sample_noise as f32;
 
-                cell.trees = None;
+                cell.trees.clear();
                 cell.add_humus(0.1);
             }
         }
@@ -125,8 +120,42 @@ impl Ecosystem {
             cell.add_bedrock(*height);
         }
         ecosystem.update_tets();
+        Self::add_initial_humus(&mut ecosystem);
 
-        // add humus
+        // add sand for fun
+        // Self::add_blanket_sand(&mut ecosystem, 10.0);
+        // ecosystem.wind_state = Some(Self::init_wind_rose());
+
+        ecosystem
+    }
+
+    // builds terrain whose total height matches the given elevations (in m)
+    // terrain cannot be below 0, so elevations below sea level raise all of it by the lowest one
+    // returns the ecosystem and the m it was raised by
+    pub fn init_with_elevations(elevations: &[f32]) -> (Self, f32) {
+        let lowest = elevations.iter().copied().fold(0.0, f32::min);
+        let offset = -lowest;
+        let mut ecosystem = Self::init();
+        for (index, elevation) in elevations.iter().enumerate() {
+            let j = index / constants::AREA_SIDE_LENGTH;
+            let i = index - j * constants::AREA_SIDE_LENGTH;
+            let cell = &mut ecosystem[CellIndex::new(i, j)];
+            cell.remove_bedrock(cell.get_bedrock_height());
+            cell.add_bedrock(*elevation + offset);
+        }
+        ecosystem.update_tets();
+        Self::add_initial_humus(&mut ecosystem);
+
+        // humus replaces the top of the bedrock so the elevations are kept
+        for cell in ecosystem.cells.iter_mut() {
+            cell.remove_bedrock(cell.get_humus_height());
+        }
+        ecosystem.update_tets();
+        (ecosystem, offset)
+    }
+
+    // covers the terrain with humus based on its slope
+    fn add_initial_humus(ecosystem: &mut Ecosystem) {
         let mut humus_heights = [[0.0; constants::AREA_SIDE_LENGTH]; constants::AREA_SIDE_LENGTH];
         for (i, heights) in humus_heights.iter_mut().enumerate() {
             for (j, height) in heights.iter_mut().enumerate() {
@@ -143,30 +172,19 @@ impl Ecosystem {
                 cell.add_humus(*height);
             }
         }
-
-        // add sand for fun
-        // Self::add_blanket_sand(&mut ecosystem, 10.0);
-        // ecosystem.wind_state = Some(Self::init_wind_rose());
-
-        ecosystem
     }
 
-    pub fn init_standard_ianterrain() -> Self {
-        let mut ecosystem = Self::init();
+    pub fn init_standard_ianterrain(seed: u64) -> Self {
+        let mut ecosystem = Self::init_with_seed(seed);
 
-        let trees = Trees {
-            number_of_plants: 2,
-            plant_height_sum: 50.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 2, 50.0, 10.0);
 
         // let noise = Perlin::new(1);
         // let mut perlin_overlay: [[f32; 100]; 100] = [[0.0; 100]; 100];
 
         for i in 0..100 {
             for j in 0..100 {
-                let mut rng = rand::thread_rng();
-                let choice: f32 = rng.gen();
+                let choice: f32 = ecosystem.rng.gen();
 
                 let cell = &mut ecosystem[CellIndex::new(i, j)];
                 let bedrock = cell.bedrock.as_mut().unwrap();
@@ -180,7 +198,7 @@ impl Ecosystem {
                 // perlin_overlay[i][j] = sample_noise as f32;
 
                 if (100 - i) + j < 100 {
-                    cell.trees = Some(trees.clone());
+                    cell.trees = vec![trees.clone()];
                     // cell.grasses = Some(Grasses { coverage_density: 1.0 });
                 }
                 // cell.add_humus(0.1);
@@ -209,11 +227,7 @@ impl Ecosystem {
         let mut ecosystem = Self::init();
         let c_i = 2;
 
-        let trees = Trees {
-            number_of_plants: 2,
-            plant_height_sum: 20.0,
-            plant_age_sum: 40.0,
-        };
+        let trees = Trees::with_plants(0, 2, 20.0, 40.0);
 
         let center = &mut ecosystem[CellIndex::new(c_i, c_i)];
         center.add_bedrock(2.0);
@@ -237,7 +251,7 @@ impl Ecosystem {
         left.add_bedrock(1.0);
         left.add_humus(0.5);
         left.soil_moisture = 1.8E5;
-        left.trees = Some(trees.clone());
+        left.trees = vec![trees.clone()];
 
         let right = &mut ecosystem[CellIndex::new(c_i + 1, c_i)];
         right.add_bedrock(1.0);
@@ -249,7 +263,7 @@ impl Ecosystem {
         up_left.add_bedrock(1.0);
         up_left.add_humus(0.5);
         up_left.soil_moisture = 1.8E5;
-        up_left.trees = Some(trees.clone());
+        up_left.trees = vec![trees.clone()];
 
         let up_right = &mut ecosystem[CellIndex::new(c_i + 1, c_i - 1)];
         up_right.add_bedrock(1.0);
@@ -261,7 +275,7 @@ impl Ecosystem {
         down_left.add_bedrock(1.0);
         down_left.add_humus(0.5);
         // down_left.soil_moisture = 1.8E5;
-        down_left.trees = Some(trees.clone());
+        down_left.trees = vec![trees.clone()];
 
         let down_right = &mut ecosystem[CellIndex::new(c_i + 1, c_i + 1)];
         down_right.add_bedrock(1.0);
@@ -0,0 +1,104 @@
+// MICROCLIMATE
+// the climate's temperatures are adjusted for each cell beyond the lapse rate with elevation
+// 1) slopes facing the equator receive more direct sun and are warmer, slopes facing the pole colder
+// 2) cells shaded by the surrounding terrain are colder than open ground
+// the adjusted temperature is used for vegetation viability and snow
+
+// °C warmer a vertical wall facing the equator is than flat ground
+const ASPECT_WARMING: f32 = 3.0;
+// °C warmer per hour of daily direct sunlight above the climate's average
+const SUNLIGHT_WARMING: f32 = 0.5;
+
+use super::{CellIndex, Ecosystem};
+
+impl Ecosystem {
+    // returns the mean temperature of the month at the cell, in celsius
+    pub fn get_local_temperature(&self, index: CellIndex, month: usize) -> f32 {
+        self[index].get_monthly_temperature(&self.config.climate, month)
+            + self.get_microclimate_offset(index, month)
+    }
+
+    // returns how much warmer (or colder, if negative) the cell is than its elevation alone implies
+    pub fn get_microclimate_offset(&self, index: CellIndex, month: usize) -> f32 {
+        // positive Y is north, so the equator is south in the northern hemisphere
+        let normal = self.get_normal(index);
+        let towards_equator = if self.config.location.latitude >= 0.0 {
+            -normal.y
+        } else {
+            normal.y
+        };
+        let aspect = ASPECT_WARMING * towards_equator;
+
+        // ray traced sunlight is compared to the climate's sunlight of unshaded flat ground
+        let sunlight = self[index].hours_of_sunlight[month]
+            - self.config.climate.monthly_sunlight_hours[month];
+        aspect + SUNLIGHT_WARMING * sunlight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::SUNLIGHT_WARMING;
+    use crate::{
+        constants,
+        ecology::{CellIndex, Ecosystem},
+    };
+
+    // a plane whose height rises by `rise` m per cell towards the north
+    fn init_slope(rise: f32) -> Ecosystem {
+        let mut ecosystem = Ecosystem::init();
+        for x in 0..constants::AREA_SIDE_LENGTH {
+            for y in 0..constants::AREA_SIDE_LENGTH {
+                let cell = &mut ecosystem[CellIndex::new(x, y)];
+                cell.set_height_of_bedrock(100.0 + rise * y as f32);
+            }
+        }
+        ecosystem
+    }
+
+    #[test]
+    fn test_get_microclimate_offset() {
+        let index = CellIndex::new(50, 50);
+
+        // flat open ground only follows the lapse rate
+        let ecosystem = init_slope(0.0);
+        let actual = ecosystem.get_microclimate_offset(index, 0);
+        assert!(
+            approx_eq!(f32, actual, 0.0, epsilon = 0.0001),
+            "Expected 0, actual {actual}"
+        );
+
+        // slopes facing south are warmer in the northern hemisphere
+        let ecosystem = init_slope(0.5);
+        let south_facing = ecosystem.get_microclimate_offset(index, 0);
+        assert!(south_facing > 0.0, "Expected warmer, actual {south_facing}");
+        let mut ecosystem = init_slope(-0.5);
+        let north_facing = ecosystem.get_microclimate_offset(index, 0);
+        assert!(north_facing < 0.0, "Expected colder, actual {north_facing}");
+
+        // and the other way around in the southern hemisphere
+        ecosystem.config.location.latitude = -30.0;
+        let actual = ecosystem.get_microclimate_offset(index, 0);
+        assert!(
+            approx_eq!(f32, actual, south_facing, epsilon = 0.0001),
+            "Expected {south_facing}, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_get_local_temperature() {
+        // a shaded valley is colder than open ground at the same elevation
+        let mut ecosystem = init_slope(0.0);
+        let index = CellIndex::new(50, 50);
+        let open = ecosystem.get_local_temperature(index, 0);
+        ecosystem[index].hours_of_sunlight[0] -= 2.0;
+        let expected = open - 2.0 * SUNLIGHT_WARMING;
+        let actual = ecosystem.get_local_temperature(index, 0);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+}
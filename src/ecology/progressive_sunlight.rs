@@ -0,0 +1,149 @@
+// PROGRESSIVE SUNLIGHT
+// spreads ray tracing sunlight after terrain changes over frames so the viewer stays interactive
+// 1) instead of being ray traced right away, the cells whose sunlight may have changed are queued
+// 2) every frame ray traces the next chunks of queued cells into a back buffer until its time budget runs out
+// 3) once the queue is empty, the back buffer is swapped into the cells all at once,
+//    so the renderer and events never see a half updated map
+// cells finished before the terrain changes again are swapped in right away so they are never starved
+
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use super::{CellIndex, Ecosystem};
+
+// number of cells ray traced in parallel between checks of the time budget
+const CHUNK_SIZE: usize = 256;
+
+#[derive(Default)]
+pub struct ProgressiveSunlight {
+    // cells left to ray trace
+    pending: Vec<CellIndex>,
+    // hours of sunlight ray traced since the last swap
+    back_buffer: Vec<(CellIndex, [f32; 12])>,
+}
+
+impl ProgressiveSunlight {
+    pub fn is_converged(&self) -> bool {
+        self.pending.is_empty() && self.back_buffer.is_empty()
+    }
+
+    pub fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+impl Ecosystem {
+    // queues the cells to be ray traced over the next frames instead of right away
+    // requires the tets and bvh to be up to date with the terrain
+    pub fn queue_sunlight(&mut self, receivers: impl IntoIterator<Item = CellIndex>) {
+        let Some(progressive) = &mut self.progressive_sunlight else {
+            return;
+        };
+        let back_buffer = std::mem::take(&mut progressive.back_buffer);
+        let mut queued: HashSet<CellIndex> = progressive.pending.drain(..).collect();
+        queued.extend(receivers);
+        progressive.pending = queued.into_iter().collect();
+        // sorted so the chunks sweep the map the same way every time
+        progressive.pending.sort();
+        self.swap_sunlight(back_buffer);
+    }
+
+    // ray traces queued cells until the budget runs out, doing at least one chunk
+    // returns whether sunlight has converged with the terrain
+    pub fn advance_sunlight(&mut self, budget: Duration) -> bool {
+        let Some(progressive) = &mut self.progressive_sunlight else {
+            return true;
+        };
+        if progressive.is_converged() {
+            return true;
+        }
+        let mut pending = std::mem::take(&mut progressive.pending);
+        let mut back_buffer = std::mem::take(&mut progressive.back_buffer);
+
+        let start = Instant::now();
+        while !pending.is_empty() {
+            let chunk = pending.split_off(pending.len().saturating_sub(CHUNK_SIZE));
+            let hours: Vec<(CellIndex, [f32; 12])> = chunk
+                .par_iter()
+                .map(|index| (*index, self.compute_hours_of_sunlight_for_cell(index)))
+                .collect();
+            back_buffer.extend(hours);
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+
+        if pending.is_empty() {
+            self.swap_sunlight(back_buffer);
+            return true;
+        }
+        if let Some(progressive) = &mut self.progressive_sunlight {
+            progressive.pending = pending;
+            progressive.back_buffer = back_buffer;
+        }
+        false
+    }
+
+    // moves the ray traced hours of sunlight into the cells
+    fn swap_sunlight(&mut self, back_buffer: Vec<(CellIndex, [f32; 12])>) {
+        for (index, hours) in back_buffer {
+            if self[index].hours_of_sunlight != hours {
+                self[index].hours_of_sunlight = hours;
+                // the sunlight color mode shows the new hours
+                self.changed_cells.insert(index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::ProgressiveSunlight;
+    use crate::ecology::{CellIndex, Ecosystem};
+
+    #[test]
+    fn test_advance_sunlight() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.recompute_sunlight();
+        ecosystem.progressive_sunlight = Some(ProgressiveSunlight::default());
+        let shaded = CellIndex::new(50, 52);
+        let unshaded = ecosystem[shaded].hours_of_sunlight;
+
+        // wall to the south
+        for x in 45..56 {
+            let index = CellIndex::new(x, 50);
+            ecosystem[index].add_bedrock(100.0);
+            ecosystem.mark_dirty(index);
+        }
+        ecosystem.update_dirty_terrain();
+
+        // nothing changes until all queued cells are ray traced
+        assert!(!ecosystem.advance_sunlight(Duration::ZERO));
+        assert_eq!(ecosystem[shaded].hours_of_sunlight, unshaded);
+        let progressive = ecosystem.progressive_sunlight.as_ref().unwrap();
+        assert!(progressive.num_pending() > 0);
+
+        let mut frames = 1;
+        while !ecosystem.advance_sunlight(Duration::ZERO) {
+            frames += 1;
+        }
+        assert!(frames > 1, "Expected several frames, actual {frames}");
+
+        // then the cells match a full ray trace at once
+        let expected = ecosystem.compute_hours_of_sunlight_for_cell(&shaded);
+        assert_eq!(ecosystem[shaded].hours_of_sunlight, expected);
+        assert!(expected.iter().sum::<f32>() < unshaded.iter().sum::<f32>());
+        assert!(ecosystem.changed_cells.contains(&shaded));
+        assert!(ecosystem
+            .progressive_sunlight
+            .as_ref()
+            .unwrap()
+            .is_converged());
+    }
+}
@@ -0,0 +1,250 @@
+// PROVENANCE
+// optional tagging of sand and humus with the region of the map they came from, to study sediment pathways
+// 1) the map is split into a square grid of regions, and when tracking starts the sand and humus
+//    of every cell are tagged with the region of the cell
+// 2) each cell keeps the height of its sand and of its humus that came from each region
+// 3) events that move material between cells (wind, slides, landslides, waves, and runoff)
+//    move the tags with it, in proportion to the tags of the cell the material leaves
+// 4) material gained or lost in place, e.g. by weathering or decay, is reconciled lazily:
+//    height the tags do not account for is tagged with the region of the cell,
+//    and tags in excess of the height are scaled down
+
+use serde::{Deserialize, Serialize};
+
+use super::{Cell, CellIndex, Ecosystem};
+use crate::constants;
+
+pub const NUM_REGIONS: usize =
+    constants::PROVENANCE_REGIONS_PER_SIDE * constants::PROVENANCE_REGIONS_PER_SIDE;
+
+// height (in m) of material from each region
+pub type Tags = [f32; NUM_REGIONS];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TaggedLayer {
+    Sand,
+    Humus,
+}
+
+impl TaggedLayer {
+    pub fn get_height(self, cell: &Cell) -> f32 {
+        match self {
+            TaggedLayer::Sand => cell.get_sand_height(),
+            TaggedLayer::Humus => cell.get_humus_height(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    // stored row by row like the cells
+    pub sand: Vec<Tags>,
+    pub humus: Vec<Tags>,
+}
+
+impl Provenance {
+    // tags all current material with the region of its cell
+    pub fn new(ecosystem: &Ecosystem) -> Self {
+        let mut provenance = Provenance {
+            sand: vec![[0.0; NUM_REGIONS]; constants::NUM_CELLS],
+            humus: vec![[0.0; NUM_REGIONS]; constants::NUM_CELLS],
+        };
+        for i in 0..constants::NUM_CELLS {
+            let index = CellIndex::get_from_flat_index(i);
+            for layer in [TaggedLayer::Sand, TaggedLayer::Humus] {
+                let height = layer.get_height(&ecosystem[index]);
+                provenance.reconcile(layer, index, height);
+            }
+        }
+        provenance
+    }
+
+    pub fn get_region(index: CellIndex) -> usize {
+        let region_side =
+            constants::AREA_SIDE_LENGTH.div_ceil(constants::PROVENANCE_REGIONS_PER_SIDE);
+        index.x / region_side + index.y / region_side * constants::PROVENANCE_REGIONS_PER_SIDE
+    }
+
+    fn get_tags_mut(&mut self, layer: TaggedLayer, index: CellIndex) -> &mut Tags {
+        let i = index.get_flat_index();
+        match layer {
+            TaggedLayer::Sand => &mut self.sand[i],
+            TaggedLayer::Humus => &mut self.humus[i],
+        }
+    }
+
+    // tags of the cell accounting for exactly the given height of the layer
+    pub fn get_reconciled_tags(&self, layer: TaggedLayer, index: CellIndex, height: f32) -> Tags {
+        let i = index.get_flat_index();
+        let mut tags = match layer {
+            TaggedLayer::Sand => self.sand[i],
+            TaggedLayer::Humus => self.humus[i],
+        };
+        let tagged: f32 = tags.iter().sum();
+        if tagged > height {
+            tags = scale(&tags, if tagged > 0.0 { height / tagged } else { 0.0 });
+        } else {
+            tags[Self::get_region(index)] += height - tagged;
+        }
+        tags
+    }
+
+    pub fn reconcile(&mut self, layer: TaggedLayer, index: CellIndex, height: f32) {
+        *self.get_tags_mut(layer, index) = self.get_reconciled_tags(layer, index, height);
+    }
+
+    // share of the layer of the cell from each region, all from the region of the cell if it is empty
+    pub fn get_fractions(&self, layer: TaggedLayer, index: CellIndex, height: f32) -> Tags {
+        if height <= 0.0 {
+            let mut fractions = [0.0; NUM_REGIONS];
+            fractions[Self::get_region(index)] = 1.0;
+            return fractions;
+        }
+        scale(
+            &self.get_reconciled_tags(layer, index, height),
+            1.0 / height,
+        )
+    }
+
+    // removes the tags of the given height of the layer, out of the given height of the cell
+    pub fn take(&mut self, layer: TaggedLayer, index: CellIndex, height: f32, amount: f32) -> Tags {
+        let amount = amount.clamp(0.0, height.max(0.0));
+        let taken = scale(&self.get_fractions(layer, index, height), amount);
+        let tags = self.get_reconciled_tags(layer, index, height);
+        *self.get_tags_mut(layer, index) =
+            std::array::from_fn(|r| f32::max(tags[r] - taken[r], 0.0));
+        taken
+    }
+
+    // adds taken tags to the layer of the cell, which has the given height before they arrive
+    pub fn give(&mut self, layer: TaggedLayer, index: CellIndex, height: f32, taken: &Tags) {
+        let tags = self.get_reconciled_tags(layer, index, height);
+        *self.get_tags_mut(layer, index) = std::array::from_fn(|r| tags[r] + taken[r]);
+    }
+
+    // replaces the tags of the cell, e.g. by those worked out by a pass over the whole grid
+    pub fn set_tags(&mut self, layer: TaggedLayer, index: CellIndex, tags: Tags) {
+        *self.get_tags_mut(layer, index) = tags;
+    }
+}
+
+pub fn scale(tags: &Tags, factor: f32) -> Tags {
+    tags.map(|tag| tag * factor)
+}
+
+impl Ecosystem {
+    // starts tagging sand and humus with the region they came from
+    pub fn enable_provenance(&mut self) {
+        self.provenance = Some(Provenance::new(self));
+    }
+
+    // removes the tags of material leaving the cell, if provenance is tracked
+    // must be called before the height of the layer changes
+    pub fn take_provenance(&mut self, layer: TaggedLayer, index: CellIndex, amount: f32) -> Tags {
+        let height = layer.get_height(&self[index]);
+        match self.provenance.as_mut() {
+            Some(provenance) => provenance.take(layer, index, height, amount),
+            None => [0.0; NUM_REGIONS],
+        }
+    }
+
+    // adds the tags of material arriving in the cell, if provenance is tracked
+    // must be called before the height of the layer changes
+    pub fn give_provenance(&mut self, layer: TaggedLayer, index: CellIndex, taken: &Tags) {
+        let height = layer.get_height(&self[index]);
+        if let Some(provenance) = self.provenance.as_mut() {
+            provenance.give(layer, index, height, taken);
+        }
+    }
+
+    // moves the tags of material moving between two cells, if provenance is tracked
+    // must be called before the heights of the layer change
+    pub fn track_transport(
+        &mut self,
+        layer: TaggedLayer,
+        from: CellIndex,
+        to: CellIndex,
+        amount: f32,
+    ) {
+        if self.provenance.is_none() {
+            return;
+        }
+        let taken = self.take_provenance(layer, from, amount);
+        self.give_provenance(layer, to, &taken);
+    }
+
+    // share of the sand and humus of the cell from each region, or none if it has neither
+    pub fn get_provenance_fractions(&self, index: CellIndex) -> Option<Tags> {
+        let provenance = self.provenance.as_ref()?;
+        let cell = &self[index];
+        let mut tags = [0.0; NUM_REGIONS];
+        for layer in [TaggedLayer::Sand, TaggedLayer::Humus] {
+            let height = layer.get_height(cell);
+            let layer_tags = provenance.get_reconciled_tags(layer, index, height);
+            tags = std::array::from_fn(|r| tags[r] + layer_tags[r]);
+        }
+        let total: f32 = tags.iter().sum();
+        (total > 0.0).then(|| scale(&tags, 1.0 / total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{Provenance, TaggedLayer, NUM_REGIONS};
+    use crate::{
+        constants,
+        ecology::{CellIndex, Ecosystem},
+    };
+
+    #[test]
+    fn test_get_region() {
+        assert_eq!(Provenance::get_region(CellIndex::new(0, 0)), 0);
+        let last = constants::AREA_SIDE_LENGTH - 1;
+        assert_eq!(
+            Provenance::get_region(CellIndex::new(last, last)),
+            NUM_REGIONS - 1
+        );
+    }
+
+    #[test]
+    fn test_track_transport() {
+        let mut ecosystem = Ecosystem::init();
+        let last = constants::AREA_SIDE_LENGTH - 1;
+        let from = CellIndex::new(0, 0);
+        let to = CellIndex::new(last, last);
+        ecosystem[from].add_sand(2.0);
+        ecosystem[to].add_sand(2.0);
+        ecosystem.enable_provenance();
+
+        // half of the sand of one corner moves to the other
+        ecosystem.track_transport(TaggedLayer::Sand, from, to, 1.0);
+        ecosystem[from].remove_sand(1.0);
+        ecosystem[to].add_sand(1.0);
+        let fractions = ecosystem.get_provenance_fractions(to).unwrap();
+        let expected = 1.0 / 3.0;
+        let actual = fractions[Provenance::get_region(from)];
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+        let fractions = ecosystem.get_provenance_fractions(from).unwrap();
+        assert_eq!(fractions[Provenance::get_region(from)], 1.0);
+
+        // sand weathered in place belongs to the region of the cell
+        ecosystem[to].add_sand(3.0);
+        let fractions = ecosystem.get_provenance_fractions(to).unwrap();
+        let expected = 1.0 / 6.0;
+        let actual = fractions[Provenance::get_region(from)];
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // nothing to tag without sand or humus
+        assert!(ecosystem
+            .get_provenance_fractions(CellIndex::new(5, 5))
+            .is_none());
+    }
+}
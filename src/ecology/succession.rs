@@ -0,0 +1,157 @@
+// SUCCESSION
+// tracks how the biome of each cell changes over time, to study how disturbances reset succession
+// 1) at the end of every time step each cell is classified into its biome
+// 2) each cell keeps a compact log of the time steps its biome changed, so a cell that stays
+//    the same biome costs one entry
+// 3) bare ground, grassland, shrubland, and forest are successive stages, and a change to an
+//    earlier stage, e.g. forest burnt back to grassland, is a disturbance
+// 4) wetland and alpine cells are held by water and cold rather than succession, so they have no stage
+
+use serde::{Deserialize, Serialize};
+
+use super::{biome::Biome, CellIndex, Ecosystem};
+use crate::constants;
+
+// time step the cell became the biome
+pub type BiomeChange = (u32, Biome);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Succession {
+    // stored row by row like the cells, oldest change first
+    pub logs: Vec<Vec<BiomeChange>>,
+}
+
+impl Default for Succession {
+    fn default() -> Self {
+        Succession {
+            logs: vec![vec![]; constants::NUM_CELLS],
+        }
+    }
+}
+
+impl Biome {
+    // stage of succession from bare ground to forest, if the biome is one
+    pub fn get_successional_stage(&self) -> Option<u8> {
+        match self {
+            Biome::BareRock | Biome::Dune => Some(0),
+            Biome::Grassland => Some(1),
+            Biome::Shrubland => Some(2),
+            Biome::DeciduousForest => Some(3),
+            Biome::Wetland | Biome::Alpine => None,
+        }
+    }
+}
+
+impl Succession {
+    // logs the biome of the cell if it changed, returns whether it did
+    pub fn record(&mut self, index: CellIndex, time_step: u32, biome: Biome) -> bool {
+        let log = &mut self.logs[index.get_flat_index()];
+        if log.last().is_some_and(|(_, last)| *last == biome) {
+            return false;
+        }
+        log.push((time_step, biome));
+        true
+    }
+
+    pub fn get_log(&self, index: CellIndex) -> &[BiomeChange] {
+        &self.logs[index.get_flat_index()]
+    }
+
+    pub fn get_current_biome(&self, index: CellIndex) -> Option<Biome> {
+        self.get_log(index).last().map(|(_, biome)| *biome)
+    }
+
+    // time step of the last change to an earlier stage of succession, if any
+    // biomes without a stage in between are skipped, so forest flooded back to grassland is disturbed
+    pub fn get_last_disturbance(&self, index: CellIndex) -> Option<u32> {
+        let mut last_stage = None;
+        let mut disturbance = None;
+        for (time_step, biome) in self.get_log(index) {
+            let Some(stage) = biome.get_successional_stage() else {
+                continue;
+            };
+            if last_stage.is_some_and(|last_stage| stage < last_stage) {
+                disturbance = Some(*time_step);
+            }
+            last_stage = Some(stage);
+        }
+        disturbance
+    }
+
+    // time steps since the last disturbance, or since tracking started if the cell was never disturbed
+    pub fn get_time_since_disturbance(&self, index: CellIndex, time_step: u32) -> u32 {
+        let since = self
+            .get_last_disturbance(index)
+            .or_else(|| self.get_log(index).first().map(|(time_step, _)| *time_step))
+            .unwrap_or(time_step);
+        time_step.saturating_sub(since)
+    }
+}
+
+impl Ecosystem {
+    // classifies every cell and logs the biomes that changed
+    pub fn update_succession(&mut self) {
+        for i in 0..constants::NUM_CELLS {
+            let index = CellIndex::get_from_flat_index(i);
+            let biome = self.classify_biome(index);
+            self.succession.record(index, self.time_step, biome);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Succession;
+    use crate::ecology::{biome::Biome, CellIndex, Ecosystem, Grasses};
+
+    #[test]
+    fn test_record_succession() {
+        let mut succession = Succession::default();
+        let index = CellIndex::new(4, 7);
+        assert_eq!(succession.get_current_biome(index), None);
+        assert_eq!(succession.get_time_since_disturbance(index, 5), 0);
+
+        // bare -> grass -> shrub -> forest, staying the same biome is not logged
+        assert!(succession.record(index, 0, Biome::BareRock));
+        assert!(!succession.record(index, 1, Biome::BareRock));
+        assert!(succession.record(index, 2, Biome::Grassland));
+        assert!(succession.record(index, 5, Biome::Shrubland));
+        assert!(succession.record(index, 9, Biome::DeciduousForest));
+        assert_eq!(succession.get_log(index).len(), 4);
+        assert_eq!(succession.get_last_disturbance(index), None);
+        assert_eq!(succession.get_time_since_disturbance(index, 12), 12);
+
+        // a fire resets the forest to grassland
+        succession.record(index, 14, Biome::Grassland);
+        assert_eq!(succession.get_last_disturbance(index), Some(14));
+        assert_eq!(succession.get_time_since_disturbance(index, 20), 6);
+
+        // flooding is not a stage of succession, but the grassland drying out to bare ground is
+        succession.record(index, 21, Biome::Wetland);
+        assert_eq!(succession.get_last_disturbance(index), Some(14));
+        succession.record(index, 22, Biome::BareRock);
+        assert_eq!(succession.get_last_disturbance(index), Some(22));
+        assert_eq!(succession.get_current_biome(index), Some(Biome::BareRock));
+    }
+
+    #[test]
+    fn test_update_succession() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(10, 10);
+        ecosystem.update_succession();
+        assert_eq!(ecosystem.succession.get_log(index), &[(0, Biome::BareRock)]);
+
+        ecosystem.time_step = 3;
+        ecosystem[index].grasses = Some(Grasses {
+            coverage_density: 0.5,
+            green_fraction: 1.0,
+        });
+        ecosystem.update_succession();
+        assert_eq!(
+            ecosystem.succession.get_log(index),
+            &[(0, Biome::BareRock), (3, Biome::Grassland)]
+        );
+        // other cells did not change
+        assert_eq!(ecosystem.succession.get_log(CellIndex::new(0, 0)).len(), 1);
+    }
+}
@@ -1,52 +1,134 @@
+pub mod audit;
+pub mod avalanche;
+pub mod coast;
+pub mod delta;
+pub mod fauna;
+pub mod fire;
+pub mod fire_danger;
+mod freeze_thaw;
 mod humus_slide;
+pub mod hydrology;
+pub mod interception;
+mod landslide;
 mod lightning;
+pub mod log;
+pub mod nutrients;
+pub mod outbreak;
+pub mod parallel;
+pub mod rain_cells;
+pub mod rainfall;
 mod rock_slide;
+mod rock_weathering;
 mod sand_slide;
+pub mod scheduler;
+mod snow;
+pub mod soil_moisture;
+pub mod storm;
 mod thermal_stress;
-mod vegetation;
-mod rainfall;
-pub(crate) mod wind;
+pub mod vegetation;
+pub mod wind;
+mod windthrow;
 
 use nalgebra::Vector3;
 
+use self::{log::CellSnapshot, parallel::PrecomputedEvents};
 use crate::{
-    constants::{self, CELL_SIDE_LENGTH},
+    constants,
     ecology::{Cell, CellIndex, Ecosystem},
+    units,
 };
 
-#[derive(PartialEq, Debug)]
-pub(crate) enum Events {
-    Rainfall,
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Events {
     ThermalStress,
+    FreezeThaw,
     Lightning,
     RockSlide,
+    RockWeathering,
     SandSlide,
     HumusSlide,
+    Landslide,
     Fire,
+    SnowFall,
+    SnowMelt,
     VegetationTrees,
     VegetationBushes,
     VegetationGrasses,
     Wind,
+    Windthrow,
+    Avalanche,
+    Outbreak,
 }
 
+// events that can be forced on a cell from the viewer, in the order they are cycled through
+pub const DEBUG_EVENTS: [Events; 4] = [
+    Events::Lightning,
+    Events::Fire,
+    Events::RockSlide,
+    Events::Windthrow,
+];
+
 impl Events {
     // performs and propagates the event until it is finished
     pub fn apply_event(self, ecosystem: &mut Ecosystem, index: CellIndex) {
         let mut event_option = Some((self, index));
         while let Some((event, index)) = event_option {
+            ecosystem[index].last_events.push(event);
+            ecosystem.changed_cells.insert(index);
+            event.mark_terrain_change(ecosystem, index);
+            let before = CellSnapshot::take(&ecosystem[index]);
+            let audited = event.audit_before(ecosystem, index);
             event_option = match event {
-                Events::Rainfall => Self::apply_rainfall_event(ecosystem, index),
                 Events::ThermalStress => Self::apply_thermal_stress_event(ecosystem, index),
+                Events::FreezeThaw => Self::apply_freeze_thaw_event(ecosystem, index),
                 Events::Lightning => Self::apply_lightning_event(ecosystem, index),
                 Events::RockSlide => Self::apply_rock_slide_event(ecosystem, index),
+                Events::RockWeathering => Self::apply_rock_weathering_event(ecosystem, index),
                 Events::SandSlide => Self::apply_sand_slide_event(ecosystem, index),
                 Events::HumusSlide => Self::apply_humus_slide_event(ecosystem, index),
-                Events::Fire => todo!(),
+                Events::Landslide => Self::apply_landslide_event(ecosystem, index),
+                Events::Fire => Self::apply_fire_event(ecosystem, index),
+                Events::SnowFall => Self::apply_snowfall_event(ecosystem, index),
+                Events::SnowMelt => Self::apply_snow_melt_event(ecosystem, index),
                 Events::VegetationTrees => Self::apply_trees_event(ecosystem, index),
                 Events::VegetationBushes => Self::apply_bushes_event(ecosystem, index),
                 Events::VegetationGrasses => Self::apply_grasses_event(ecosystem, index),
                 Events::Wind => Self::apply_wind_event(ecosystem, index),
+                Events::Windthrow => Self::apply_windthrow_event(ecosystem, index),
+                Events::Avalanche => Self::apply_avalanche_event(ecosystem, index),
+                Events::Outbreak => Self::apply_outbreak_event(ecosystem, index),
             };
+            event.audit_after(ecosystem, index, audited);
+            event.log(ecosystem, index, &before);
+        }
+    }
+
+    // applies the event at the cell as if it had been scheduled there, to exercise it while debugging
+    // lightning always strikes, while the other events still depend on the state of the cell
+    pub fn force_event(self, ecosystem: &mut Ecosystem, index: CellIndex) {
+        if self == Events::Lightning {
+            self.apply_precomputed_event(ecosystem, index, &PrecomputedEvents::default());
+        } else {
+            self.apply_event(ecosystem, index);
+        }
+    }
+
+    // marks the cell and the neighbors it can move material to as dirty
+    // if the event can change the height of the terrain
+    fn mark_terrain_change(self, ecosystem: &mut Ecosystem, index: CellIndex) {
+        if matches!(
+            self,
+            Events::SnowFall | Events::SnowMelt | Events::Windthrow | Events::RockWeathering
+        ) {
+            return;
+        }
+        ecosystem.mark_dirty(index);
+        for neighbor in Cell::get_neighbors(&index, ecosystem.config.boundary)
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            ecosystem.mark_dirty(neighbor);
         }
     }
 
@@ -66,13 +148,10 @@ impl Events {
 
     // converts all trees in a cell into dead vegetation
     fn kill_trees(cell: &mut Cell) {
-        if let Some(trees) = &mut cell.trees {
-            let biomass = trees.estimate_biomass();
-            trees.number_of_plants = 0;
-            trees.plant_height_sum = 0.0;
-            trees.plant_age_sum = 0.0;
+        let biomass = cell.estimate_tree_biomass();
+        if !cell.trees.is_empty() {
             cell.add_dead_vegetation(biomass);
-            cell.trees = None;
+            cell.trees.clear();
         }
     }
 
@@ -80,9 +159,7 @@ impl Events {
     fn kill_bushes(cell: &mut Cell) {
         if let Some(bushes) = &mut cell.bushes {
             let biomass = bushes.estimate_biomass();
-            bushes.number_of_plants = 0;
-            bushes.plant_height_sum = 0.0;
-            bushes.plant_age_sum = 0.0;
+            bushes.cohorts.clear();
             cell.add_dead_vegetation(biomass);
             cell.bushes = None;
         }
@@ -92,9 +169,8 @@ impl Events {
     fn kill_grasses(cell: &mut Cell) {
         if let Some(grasses) = &mut cell.grasses {
             let coverage_density = grasses.coverage_density;
-            cell.add_dead_vegetation(
-                coverage_density * CELL_SIDE_LENGTH * CELL_SIDE_LENGTH * constants::GRASS_DENSITY,
-            );
+            let biomass = units::CELL_AREA * constants::GRASS_DENSITY * coverage_density;
+            cell.add_dead_vegetation(biomass.0);
             cell.grasses = None;
         }
     }
@@ -102,29 +178,155 @@ impl Events {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::RefCell;
+
     use float_cmp::approx_eq;
     use nalgebra::Vector3;
+    use proptest::{
+        collection::vec,
+        prelude::{prop_assert, prop_assume, proptest, ProptestConfig, Strategy},
+        sample::select,
+    };
 
     use crate::{
-        ecology::{Cell, Trees},
+        ecology::{Cell, CellIndex, Ecosystem, Trees},
         events::Events,
     };
 
+    // random cases each property is checked on
+    const PROPERTY_CASES: u32 = 200;
+    // side of the patch of random terrain in the corner of the map
+    const PATCH_SIDE: usize = 6;
+    // slides followed before a chain of propagation counts as endless
+    const MAX_PROPAGATION: usize = 1000;
+    // tolerance of angles in degrees
+    const ANGLE_EPSILON: f32 = 0.05;
+
+    #[derive(Clone, Copy, Debug)]
+    enum SlideLayer {
+        Rock,
+        Sand,
+        Humus,
+    }
+
+    const SLIDE_LAYERS: [SlideLayer; 3] = [SlideLayer::Rock, SlideLayer::Sand, SlideLayer::Humus];
+
+    impl SlideLayer {
+        fn get_height(self, cell: &Cell) -> f32 {
+            match self {
+                SlideLayer::Rock => cell.get_rock_height(),
+                SlideLayer::Sand => cell.get_sand_height(),
+                SlideLayer::Humus => cell.get_humus_height(),
+            }
+        }
+
+        fn add(self, cell: &mut Cell, height: f32) {
+            match self {
+                SlideLayer::Rock => cell.add_rocks(height),
+                SlideLayer::Sand => cell.add_sand(height),
+                SlideLayer::Humus => cell.add_humus(height),
+            }
+        }
+
+        fn apply(self, ecosystem: &mut Ecosystem, index: CellIndex) -> Option<(Events, CellIndex)> {
+            match self {
+                SlideLayer::Rock => Events::apply_rock_slide_event(ecosystem, index),
+                SlideLayer::Sand => Events::apply_sand_slide_event(ecosystem, index),
+                SlideLayer::Humus => Events::apply_humus_slide_event(ecosystem, index),
+            }
+        }
+
+        fn get_critical_angle(self, ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+            match self {
+                SlideLayer::Rock => Events::compute_rock_critical_angle(ecosystem, index),
+                SlideLayer::Sand => Events::compute_sand_critical_angle(ecosystem, index),
+                SlideLayer::Humus => Events::compute_humus_critical_angle(ecosystem, index),
+            }
+        }
+    }
+
+    thread_local! {
+        // reused by the cases of a property since building an ecosystem is slow
+        static ECOSYSTEM: RefCell<Ecosystem> = RefCell::new(Ecosystem::init());
+    }
+
+    // heights of the bedrock and the sliding material of each cell of the patch
+    fn terrain_patch() -> impl Strategy<Value = Vec<(f32, f32)>> {
+        vec((95.0f32..105.0, 0.0f32..3.0), PATCH_SIDE * PATCH_SIDE)
+    }
+
+    fn patch_cell() -> impl Strategy<Value = CellIndex> {
+        (0..PATCH_SIDE, 0..PATCH_SIDE).prop_map(|(x, y)| CellIndex::new(x, y))
+    }
+
+    fn slide_layer() -> impl Strategy<Value = SlideLayer> {
+        select(SLIDE_LAYERS.to_vec())
+    }
+
+    // offset of a target cell from its origin, which are always neighbors
+    fn neighbor_offset() -> impl Strategy<Value = (i32, i32)> {
+        (-1..=1, -1..=1).prop_filter("the origin is not its own neighbor", |&offset| {
+            offset != (0, 0)
+        })
+    }
+
+    // rough bedrock covered by an uneven layer of the sliding material in the corner of the map
+    fn set_terrain(ecosystem: &mut Ecosystem, layer: SlideLayer, patch: &[(f32, f32)]) {
+        // the cells a single slide from the patch can reach are reset too
+        for x in 0..=PATCH_SIDE {
+            for y in 0..=PATCH_SIDE {
+                ecosystem[CellIndex::new(x, y)] = Cell::init();
+            }
+        }
+        for (i, &(bedrock, height)) in patch.iter().enumerate() {
+            let cell = &mut ecosystem[CellIndex::new(i % PATCH_SIDE, i / PATCH_SIDE)];
+            cell.set_height_of_bedrock(bedrock);
+            layer.add(cell, height);
+        }
+    }
+
+    fn get_angle_between(ecosystem: &Ecosystem, origin: CellIndex, target: CellIndex) -> f32 {
+        Ecosystem::get_angle(ecosystem.get_slope_between_points(origin, target))
+    }
+
+    // same as get_slope_between_points, on a copy of the cells
+    fn get_slope(cells: &[Cell], origin: CellIndex, target: CellIndex) -> f32 {
+        let position = |index: CellIndex| {
+            let height = cells[index.get_flat_index()].get_height();
+            Vector3::new(index.x as f32, index.y as f32, height)
+        };
+        let (pos_1, pos_2) = (position(origin), position(target));
+        (pos_1.z - pos_2.z) / (pos_1 - pos_2).norm()
+    }
+
+    // follows the propagation of a slide, returning the height of material it moved
+    fn apply_slide_chain(layer: SlideLayer, ecosystem: &mut Ecosystem, index: CellIndex) -> f32 {
+        let mut moved = 0.0;
+        let mut index = index;
+        for _ in 0..MAX_PROPAGATION {
+            let before = layer.get_height(&ecosystem[index]);
+            match layer.apply(ecosystem, index) {
+                Some((_, next)) => {
+                    moved += before - layer.get_height(&ecosystem[index]);
+                    index = next;
+                }
+                None => return moved,
+            }
+        }
+        panic!("{layer:?} slide from {index} propagated more than {MAX_PROPAGATION} times");
+    }
+
     #[test]
     fn kill_trees() {
-        let trees = Trees {
-            number_of_plants: 1,
-            plant_height_sum: 30.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 1, 30.0, 10.0);
         let mut cell = Cell::init();
-        cell.trees = Some(trees);
+        cell.trees = vec![trees];
         let biomass = cell.estimate_tree_biomass();
 
         Events::kill_trees(&mut cell);
 
         let trees = &cell.trees;
-        assert!(trees.is_none());
+        assert!(trees.is_empty());
 
         let dead_vegetation_biomass = cell.get_dead_vegetation_biomass();
         let actual = biomass;
@@ -134,18 +336,14 @@ mod tests {
         );
 
         // add more trees and kill them
-        let trees = Trees {
-            number_of_plants: 5,
-            plant_height_sum: 150.0,
-            plant_age_sum: 10.0,
-        };
-        cell.trees = Some(trees);
+        let trees = Trees::with_plants(0, 5, 150.0, 10.0);
+        cell.trees = vec![trees];
         let biomass_2 = cell.estimate_tree_biomass();
 
         Events::kill_trees(&mut cell);
 
         let trees = &mut cell.trees;
-        assert!(trees.is_none());
+        assert!(trees.is_empty());
 
         let dead_vegetation_biomass = cell.get_dead_vegetation_biomass();
         let actual = biomass + biomass_2;
@@ -167,4 +365,145 @@ mod tests {
             "Expected {expected}, actual {new_height}"
         );
     }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(PROPERTY_CASES))]
+
+        #[test]
+        fn test_compute_ideal_slide_height_properties(
+            origin_height in 90.0f32..110.0,
+            target_height in 90.0f32..110.0,
+            (dx, dy) in neighbor_offset(),
+            critical_angle in 1.0f32..85.0,
+        ) {
+            let pos_1 = Vector3::new(0.0, 0.0, origin_height);
+            let pos_2 = Vector3::new(dx as f32, dy as f32, target_height);
+
+            let ideal_height = Events::compute_ideal_slide_height(pos_1, pos_2, critical_angle);
+            prop_assert!(ideal_height >= pos_2.z, "{ideal_height} below target");
+
+            // material down to the ideal height rests exactly at the critical angle
+            let ideal_pos = Vector3::new(pos_1.x, pos_1.y, ideal_height);
+            let slope = (ideal_height - pos_2.z) / (ideal_pos - pos_2).norm();
+            let actual = Ecosystem::get_angle(slope);
+            prop_assert!(
+                approx_eq!(f32, actual, critical_angle, epsilon = ANGLE_EPSILON),
+                "Expected {critical_angle}, actual {actual}"
+            );
+        }
+
+        #[test]
+        fn test_slides_respect_critical_angle(
+            layer in slide_layer(),
+            patch in terrain_patch(),
+            origin in patch_cell(),
+        ) {
+            ECOSYSTEM.with_borrow_mut(|ecosystem| {
+                set_terrain(ecosystem, layer, &patch);
+                let critical_angle = layer.get_critical_angle(ecosystem, origin);
+                let before = ecosystem.cells.clone();
+
+                let slide = layer.apply(ecosystem, origin);
+                prop_assume!(slide.is_some(), "the material is stable");
+                let (_, target) = slide.unwrap();
+                let pre_angle = Ecosystem::get_angle(get_slope(&before, origin, target));
+                let post_angle = get_angle_between(ecosystem, origin, target);
+                let label = format!("{layer:?} from {origin} to {target}");
+
+                // the material moved is never negative and never more than the origin had
+                let moved = layer.get_height(&before[origin.get_flat_index()])
+                    - layer.get_height(&ecosystem[origin]);
+                let layer_height = layer.get_height(&before[origin.get_flat_index()]);
+                prop_assert!(
+                    (0.0..=layer_height).contains(&moved),
+                    "{label}: moved {moved} of {layer_height}"
+                );
+                let gained = layer.get_height(&ecosystem[target])
+                    - layer.get_height(&before[target.get_flat_index()]);
+                prop_assert!(
+                    approx_eq!(f32, gained, moved, epsilon = 0.0001),
+                    "{label}: Expected {moved}, actual {gained}"
+                );
+                for index in [origin, target] {
+                    prop_assert!(layer.get_height(&ecosystem[index]) >= 0.0, "{label}");
+                }
+
+                // the slope flattens without overshooting below the critical angle
+                prop_assert!(
+                    post_angle <= pre_angle + ANGLE_EPSILON,
+                    "{label}: steepened from {pre_angle} to {post_angle}"
+                );
+                prop_assert!(
+                    post_angle >= f32::min(critical_angle, pre_angle) - ANGLE_EPSILON,
+                    "{label}: overshot to {post_angle} below {critical_angle}"
+                );
+                // and ends at the critical angle unless the material ran out first
+                let cell = &before[origin.get_flat_index()];
+                let base_height = cell.get_height() - layer_height;
+                let target_height = before[target.get_flat_index()].get_height();
+                let distance = Vector3::new(
+                    origin.x as f32 - target.x as f32,
+                    origin.y as f32 - target.y as f32,
+                    0.0,
+                )
+                .norm();
+                let ideal_height = target_height + distance * critical_angle.to_radians().tan();
+                if base_height < ideal_height {
+                    prop_assert!(
+                        post_angle <= critical_angle + ANGLE_EPSILON,
+                        "{label}: {post_angle} above {critical_angle}"
+                    );
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    proptest! {
+        // each case builds a whole ecosystem, so fewer are run
+        #![proptest_config(ProptestConfig::with_cases(PROPERTY_CASES / 4))]
+
+        #[test]
+        fn test_repeated_slides_converge(layer in slide_layer(), patch in terrain_patch()) {
+            // propagation can carry material past the cells that are reset
+            let mut ecosystem = Ecosystem::init();
+            set_terrain(&mut ecosystem, layer, &patch);
+
+            // sweeps over the patch move less and less until the terrain is stable
+            let mut moved = f32::MAX;
+            let mut sweeps = 0;
+            while moved > 0.0001 {
+                prop_assert!(
+                    sweeps < MAX_PROPAGATION,
+                    "{layer:?} still moving {moved} after {sweeps} sweeps"
+                );
+                moved = 0.0;
+                for x in 0..=PATCH_SIDE {
+                    for y in 0..=PATCH_SIDE {
+                        let index = CellIndex::new(x, y);
+                        moved += apply_slide_chain(layer, &mut ecosystem, index);
+                    }
+                }
+                sweeps += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn test_force_event() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(50, 50);
+        let bedrock = ecosystem[index].get_bedrock_height();
+
+        // lightning strikes the cell however unlikely it was
+        Events::Lightning.force_event(&mut ecosystem, index);
+        assert!(ecosystem[index].get_bedrock_height() < bedrock);
+        assert_eq!(ecosystem[index].last_events, vec![Events::Lightning]);
+
+        // other events are applied as usual
+        ecosystem[index].set_trees(Trees::with_plants(0, 10, 100.0, 300.0));
+        Events::Fire.force_event(&mut ecosystem, index);
+        assert!(ecosystem[index].trees.is_empty());
+        assert!(ecosystem[index].last_events.contains(&Events::Fire));
+    }
 }
@@ -0,0 +1,287 @@
+// MASS AUDIT
+// debug check that events only create or destroy material through the conversions they model
+// totals are compared over the cells an event can reach, before and after each step of the event
+// the grid-wide passes are not audited since water and sediment drain off the edges of the map,
+// and neither is wind on an open map, which blows sand off it
+
+use super::{avalanche, landslide::MAX_RUNOUT_LENGTH, Events};
+use crate::{
+    config::BoundaryMode,
+    constants,
+    ecology::{Cell, CellIndex, Ecosystem},
+};
+
+// differences smaller than this % of the totals are rounding errors
+const RELATIVE_TOLERANCE: f32 = 1e-5;
+
+// heights (in m) of the layers and biomass (in kg) of dead vegetation summed over some cells
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MassTotals {
+    pub bedrock: f32,
+    pub rocks: f32,
+    pub sand: f32,
+    pub humus: f32,
+    pub dead_vegetation: f32,
+}
+
+impl MassTotals {
+    // returns the totals over the cells at most `reach` cells away from the index in x and y
+    pub fn around(ecosystem: &Ecosystem, index: CellIndex, reach: usize) -> Self {
+        let mut totals = MassTotals::default();
+        let torus = ecosystem.config.boundary == BoundaryMode::Torus;
+        for x in get_window(index.x, reach, torus) {
+            for y in get_window(index.y, reach, torus) {
+                totals.add_cell(&ecosystem[CellIndex::new(x, y)]);
+            }
+        }
+        totals
+    }
+
+    fn add_cell(&mut self, cell: &Cell) {
+        self.bedrock += cell.get_bedrock_height();
+        self.rocks += cell.get_rock_height();
+        self.sand += cell.get_sand_height();
+        self.humus += cell.get_humus_height();
+        self.dead_vegetation += cell.get_dead_vegetation_biomass();
+    }
+
+    // bedrock breaks down into rocks and sand, so only their sum is conserved
+    pub fn get_mineral(&self) -> f32 {
+        self.bedrock + self.rocks + self.sand
+    }
+}
+
+// returns the coordinates at most `reach` away from the center, each once,
+// clipped at the edges of the map or wrapping around them on a torus
+fn get_window(center: usize, reach: usize, torus: bool) -> Vec<usize> {
+    let side = constants::AREA_SIDE_LENGTH;
+    if !torus {
+        return (center.saturating_sub(reach)..=usize::min(center + reach, side - 1)).collect();
+    }
+    if 2 * reach + 1 >= side {
+        return (0..side).collect();
+    }
+    (0..=2 * reach)
+        .map(|offset| (center + side + offset - reach) % side)
+        .collect()
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MassAudit {
+    // prints violations instead of panicking
+    pub log_only: bool,
+    // number of event steps that created or destroyed material
+    pub violations: u32,
+}
+
+impl MassAudit {
+    // checks that the event did not create or destroy material it does not convert
+    pub fn check(
+        &mut self,
+        event: Events,
+        index: CellIndex,
+        before: &MassTotals,
+        after: &MassTotals,
+    ) {
+        let mut changes = vec![];
+        if !is_conserved(before.get_mineral(), after.get_mineral()) {
+            changes.push(format!(
+                "bedrock, rocks, and sand {} -> {}",
+                before.get_mineral(),
+                after.get_mineral()
+            ));
+        }
+        if !event.converts_humus() && !is_conserved(before.humus, after.humus) {
+            changes.push(format!("humus {} -> {}", before.humus, after.humus));
+        }
+        if !event.converts_dead_vegetation()
+            && !is_conserved(before.dead_vegetation, after.dead_vegetation)
+        {
+            changes.push(format!(
+                "dead vegetation {} -> {}",
+                before.dead_vegetation, after.dead_vegetation
+            ));
+        }
+        if changes.is_empty() {
+            return;
+        }
+
+        self.violations += 1;
+        let message = format!(
+            "Mass audit: {event:?} at {index} changed {}",
+            changes.join(", ")
+        );
+        if self.log_only {
+            println!("{message}");
+        } else {
+            panic!("{message}");
+        }
+    }
+}
+
+fn is_conserved(before: f32, after: f32) -> bool {
+    let tolerance = RELATIVE_TOLERANCE * f32::max(before.abs(), 1.0);
+    (after - before).abs() <= tolerance
+}
+
+impl Events {
+    // returns the totals the audit compares against after the event, if the ecosystem is audited
+    pub fn audit_before(self, ecosystem: &Ecosystem, index: CellIndex) -> Option<MassTotals> {
+        ecosystem.mass_audit.as_ref()?;
+        // sand blown off an open map is lost on purpose
+        if self == Events::Wind && ecosystem.config.boundary == BoundaryMode::Open {
+            return None;
+        }
+        Some(MassTotals::around(ecosystem, index, self.get_reach()))
+    }
+
+    // checks the totals after the event against the ones from `audit_before`
+    pub fn audit_after(
+        self,
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+        before: Option<MassTotals>,
+    ) {
+        let Some(before) = before else {
+            return;
+        };
+        let after = MassTotals::around(ecosystem, index, self.get_reach());
+        if let Some(mass_audit) = &mut ecosystem.mass_audit {
+            mass_audit.check(self, index, &before, &after);
+        }
+    }
+
+    // cells further away than this from the cell an event is applied to are never changed by it
+    fn get_reach(self) -> usize {
+        match self {
+            Events::Landslide => MAX_RUNOUT_LENGTH + 1,
+            Events::Avalanche => avalanche::MAX_RUNOUT_LENGTH + 1,
+            // saltating sand can land anywhere
+            Events::Wind => constants::AREA_SIDE_LENGTH,
+            _ => 1,
+        }
+    }
+
+    // dead vegetation decomposes into humus and burns into ash
+    fn converts_humus(self) -> bool {
+        matches!(
+            self,
+            Events::VegetationTrees
+                | Events::VegetationBushes
+                | Events::VegetationGrasses
+                | Events::Fire
+        )
+    }
+
+    // events that kill, burn, or decompose vegetation
+    fn converts_dead_vegetation(self) -> bool {
+        matches!(
+            self,
+            Events::VegetationTrees
+                | Events::VegetationBushes
+                | Events::VegetationGrasses
+                | Events::Fire
+                | Events::Lightning
+                | Events::Landslide
+                | Events::Windthrow
+                | Events::Avalanche
+                | Events::Outbreak
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MassAudit, MassTotals};
+    use crate::{
+        config::BoundaryMode,
+        ecology::{CellIndex, Ecosystem},
+        events::Events,
+    };
+
+    #[test]
+    fn test_mass_totals_around() {
+        let mut ecosystem = Ecosystem::init();
+        for cell in ecosystem.cells.iter_mut() {
+            cell.add_sand(1.0);
+        }
+        // the window is clipped at the edges of the map
+        assert_eq!(
+            MassTotals::around(&ecosystem, CellIndex::new(5, 5), 1).sand,
+            9.0
+        );
+        assert_eq!(
+            MassTotals::around(&ecosystem, CellIndex::new(0, 0), 1).sand,
+            4.0
+        );
+        // or wraps around them on a torus
+        ecosystem.config.boundary = BoundaryMode::Torus;
+        assert_eq!(
+            MassTotals::around(&ecosystem, CellIndex::new(0, 0), 1).sand,
+            9.0
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let mut mass_audit = MassAudit {
+            log_only: true,
+            violations: 0,
+        };
+        let index = CellIndex::new(5, 5);
+        let before = MassTotals {
+            bedrock: 10.0,
+            humus: 1.0,
+            ..Default::default()
+        };
+
+        // bedrock fracturing into rocks conserves mass
+        let after = MassTotals {
+            bedrock: 9.0,
+            rocks: 1.0,
+            humus: 1.0,
+            ..Default::default()
+        };
+        mass_audit.check(Events::ThermalStress, index, &before, &after);
+        assert_eq!(mass_audit.violations, 0);
+
+        // fire turns vegetation into humus, but slides only move it
+        let after = MassTotals {
+            bedrock: 10.0,
+            humus: 1.5,
+            ..Default::default()
+        };
+        mass_audit.check(Events::Fire, index, &before, &after);
+        assert_eq!(mass_audit.violations, 0);
+        mass_audit.check(Events::HumusSlide, index, &before, &after);
+        assert_eq!(mass_audit.violations, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mass audit: SandSlide")]
+    fn test_check_panics() {
+        let mut mass_audit = MassAudit::default();
+        let after = MassTotals {
+            sand: 1.0,
+            ..Default::default()
+        };
+        mass_audit.check(
+            Events::SandSlide,
+            CellIndex::new(5, 5),
+            &MassTotals::default(),
+            &after,
+        );
+    }
+
+    #[test]
+    fn test_audited_event() {
+        // a sand pile sliding onto flat ground passes the audit
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.mass_audit = Some(MassAudit::default());
+        let index = CellIndex::new(5, 5);
+        ecosystem[index].add_sand(5.0);
+        Events::SandSlide.apply_event(&mut ecosystem, index);
+        assert!(ecosystem[index].get_sand_height() < 5.0);
+        assert_eq!(ecosystem.mass_audit.unwrap().violations, 0);
+    }
+}
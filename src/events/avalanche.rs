@@ -0,0 +1,234 @@
+// AVALANCHE
+// a snowpack on a steep slope releases as a slab and runs down the slope like a landslide of snow
+// 1) freshly fallen snow has not bonded to the snowpack yet, so snow fails at a lower angle
+//    the lighter it is, and avalanches mostly follow heavy snowfall
+// 2) the whole snowpack of the release cell slides, taking along part of the snow on its path
+// 3) the avalanche runs downhill along the steepest descent, breaking the vegetation and scouring
+//    the sand and humus of the cells it crosses, until the slope is too gentle for it to keep moving
+// 4) snow and debris pile up in a cone over the cell where it stops and its neighbors
+// cells are checked for avalanches after their snowfall and melt, while enough snow remains
+
+// snowpack thinner than this (in m) is anchored by the roughness of the ground
+pub const MIN_RELEASE_HEIGHT: f32 = 0.3;
+// share of the critical angle lost by a snowpack of only fresh snow
+const FRESH_SNOW_LOSS: f32 = 0.3;
+// the avalanche stops on slopes gentler than this angle (in degrees)
+const RUNOUT_ANGLE: f32 = 15.0;
+// maximum number of cells the avalanche runs over
+pub const MAX_RUNOUT_LENGTH: usize = 20;
+// % of the snow of the cells along the path that is carried along
+const SNOW_ENTRAINMENT_RATE: f32 = 0.5;
+// % of the sand and humus of the cells along the path that is scoured
+const SCOUR_RATE: f32 = 0.05;
+// the cell where the avalanche stops gets this many times the debris of each of its neighbors
+const CONE_CENTER_WEIGHT: f32 = 4.0;
+
+use super::{
+    snow::{FRESH_SNOW_DENSITY, MAX_SNOW_DENSITY},
+    Events,
+};
+use crate::ecology::{
+    provenance::{scale, TaggedLayer, Tags},
+    Cell, CellIndex, Ecosystem,
+};
+
+// snow (as water equivalent height in m) and heights (in m) of the debris moved by the avalanche
+#[derive(Debug, Default, PartialEq)]
+struct Debris {
+    snow: f32,
+    sand: f32,
+    humus: f32,
+    // regions the sand and humus came from, when provenance is tracked
+    sand_tags: Tags,
+    humus_tags: Tags,
+}
+
+impl Events {
+    pub fn apply_avalanche_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+    ) -> Option<(Events, CellIndex)> {
+        if ecosystem[index].get_snow_height() < MIN_RELEASE_HEIGHT {
+            return None;
+        }
+        let (first, angle) = Self::get_steepest_descent(ecosystem, index, &[])?;
+        if angle < Self::compute_snow_critical_angle(ecosystem, index) {
+            return None;
+        }
+
+        // the whole snowpack of the release cell slides
+        let mut debris = Debris::default();
+        Self::scour(ecosystem, index, &mut debris, 1.0);
+
+        // the avalanche runs downhill until the slope is too gentle, never back into its own path
+        let mut path = vec![index];
+        let mut end = first;
+        for _ in 1..MAX_RUNOUT_LENGTH {
+            match Self::get_steepest_descent(ecosystem, end, &path) {
+                Some((next, angle)) if angle >= RUNOUT_ANGLE => {
+                    Self::scour(ecosystem, end, &mut debris, SNOW_ENTRAINMENT_RATE);
+                    path.push(end);
+                    end = next;
+                }
+                _ => break,
+            }
+        }
+
+        Self::deposit_cone(ecosystem, end, &debris);
+
+        // does not propagate
+        None
+    }
+
+    // returns the angle (in degrees) at which the snowpack of the cell fails
+    pub fn compute_snow_critical_angle(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        let density = ecosystem[index].get_snow_density();
+        // share of the snowpack that is still fresh, from its density
+        let freshness = ((MAX_SNOW_DENSITY - density) / (MAX_SNOW_DENSITY - FRESH_SNOW_DENSITY))
+            .clamp(0.0, 1.0);
+        ecosystem.config.critical_angles.snow * (1.0 - FRESH_SNOW_LOSS * freshness)
+    }
+
+    // moves the given % of the snow of the cell into the debris, breaking its vegetation
+    // and scouring its sand and humus
+    fn scour(ecosystem: &mut Ecosystem, index: CellIndex, debris: &mut Debris, snow_rate: f32) {
+        ecosystem.mark_dirty(index);
+        let cell = &mut ecosystem[index];
+        Self::kill_trees(cell);
+        Self::kill_bushes(cell);
+        Self::kill_grasses(cell);
+
+        let sand = cell.get_sand_height() * SCOUR_RATE;
+        let humus = cell.get_humus_height() * SCOUR_RATE;
+        let snow = cell.remove_snow(cell.get_snow_water_equivalent() * snow_rate);
+        let sand_tags = ecosystem.take_provenance(TaggedLayer::Sand, index, sand);
+        let humus_tags = ecosystem.take_provenance(TaggedLayer::Humus, index, humus);
+        let cell = &mut ecosystem[index];
+        cell.remove_sand(sand);
+        cell.remove_humus(humus);
+
+        debris.snow += snow;
+        debris.sand += sand;
+        debris.humus += humus;
+        debris.sand_tags = std::array::from_fn(|r| debris.sand_tags[r] + sand_tags[r]);
+        debris.humus_tags = std::array::from_fn(|r| debris.humus_tags[r] + humus_tags[r]);
+    }
+
+    // piles the debris over the cell and its neighbors, highest on the cell itself
+    fn deposit_cone(ecosystem: &mut Ecosystem, index: CellIndex, debris: &Debris) {
+        let neighbors: Vec<CellIndex> = Cell::get_neighbors(&index, ecosystem.config.boundary)
+            .as_array()
+            .into_iter()
+            .flatten()
+            .collect();
+        let total_weight = CONE_CENTER_WEIGHT + neighbors.len() as f32;
+        let zone = std::iter::once((index, CONE_CENTER_WEIGHT))
+            .chain(neighbors.into_iter().map(|neighbor| (neighbor, 1.0)));
+
+        for (index, weight) in zone {
+            let share = weight / total_weight;
+            ecosystem.mark_dirty(index);
+            ecosystem.give_provenance(TaggedLayer::Sand, index, &scale(&debris.sand_tags, share));
+            ecosystem.give_provenance(TaggedLayer::Humus, index, &scale(&debris.humus_tags, share));
+            let cell = &mut ecosystem[index];
+            cell.add_sand(debris.sand * share);
+            cell.add_humus(debris.humus * share);
+            // avalanche debris is packed as dense as a settled snowpack
+            if debris.snow > 0.0 {
+                cell.add_snow(debris.snow * share, MAX_SNOW_DENSITY);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{MAX_SNOW_DENSITY, MIN_RELEASE_HEIGHT};
+    use crate::{
+        constants,
+        ecology::{CellIndex, Ecosystem, Trees},
+        events::{snow::FRESH_SNOW_DENSITY, Events},
+    };
+
+    // a slope of 45° rising towards x = 0 with a flat valley floor from x = 10
+    fn init_snowy_slope(snow: f32, density: f32) -> Ecosystem {
+        let mut ecosystem = Ecosystem::init();
+        for x in 0..constants::AREA_SIDE_LENGTH {
+            for y in 0..constants::AREA_SIDE_LENGTH {
+                let cell = &mut ecosystem[CellIndex::new(x, y)];
+                cell.set_height_of_bedrock(100.0 + f32::max(10.0 - x as f32, 0.0));
+                cell.add_humus(0.5);
+                cell.add_snow(snow, density);
+            }
+        }
+        ecosystem
+    }
+
+    fn sum_snow(ecosystem: &Ecosystem) -> f32 {
+        ecosystem
+            .cells
+            .iter()
+            .map(|cell| cell.get_snow_water_equivalent())
+            .sum()
+    }
+
+    #[test]
+    fn test_compute_snow_critical_angle() {
+        let ecosystem = init_snowy_slope(0.1, MAX_SNOW_DENSITY);
+        let index = CellIndex::new(5, 5);
+        let settled = Events::compute_snow_critical_angle(&ecosystem, index);
+        assert_eq!(settled, ecosystem.config.critical_angles.snow);
+
+        // fresh snow fails at a gentler angle
+        let ecosystem = init_snowy_slope(0.1, FRESH_SNOW_DENSITY);
+        let actual = Events::compute_snow_critical_angle(&ecosystem, index);
+        let expected = settled * (1.0 - super::FRESH_SNOW_LOSS);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_apply_avalanche_event() {
+        // too little snow is anchored to the ground
+        let mut ecosystem = init_snowy_slope(0.01, FRESH_SNOW_DENSITY);
+        let source = CellIndex::new(3, 50);
+        assert!(ecosystem[source].get_snow_height() < MIN_RELEASE_HEIGHT);
+        Events::apply_avalanche_event(&mut ecosystem, source);
+        assert_eq!(ecosystem[source].get_snow_water_equivalent(), 0.01);
+
+        // but a heavy snowfall releases
+        let mut ecosystem = init_snowy_slope(0.1, FRESH_SNOW_DENSITY);
+        let path = CellIndex::new(6, 50);
+        let end = CellIndex::new(10, 50);
+        ecosystem[path].trees = vec![Trees::with_plants(0, 5, 50.0, 100.0)];
+        let snow = sum_snow(&ecosystem);
+        let humus_height = ecosystem[path].get_humus_height();
+
+        Events::apply_avalanche_event(&mut ecosystem, source);
+
+        // the release cell is swept clean and the trees along the path are broken
+        assert_eq!(ecosystem[source].get_snow_height(), 0.0);
+        assert!(ecosystem[path].trees.is_empty());
+        assert!(ecosystem[path].get_dead_vegetation_biomass() > 0.0);
+        assert!(ecosystem[path].get_humus_height() < humus_height);
+
+        // snow and debris pile up at the foot of the slope
+        let center = &ecosystem[end];
+        let side = &ecosystem[CellIndex::new(10, 52)];
+        assert!(center.get_snow_water_equivalent() > 0.1);
+        assert!(center.get_humus_height() > 0.5);
+        assert!(center.get_snow_height() > ecosystem[CellIndex::new(11, 50)].get_snow_height());
+        assert_eq!(side.get_snow_water_equivalent(), 0.1);
+
+        // without losing any snow
+        let actual = sum_snow(&ecosystem);
+        assert!(
+            approx_eq!(f32, actual, snow, epsilon = 0.01),
+            "Expected {snow}, actual {actual}"
+        );
+    }
+}
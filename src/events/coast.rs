@@ -0,0 +1,164 @@
+// COAST
+// when the config sets a sea level, the cells below it are flooded and the shore is shaped by waves
+// once per time step over the whole grid:
+// 1) waves wash sand from the shallow sea onto the shore cells facing it
+// 2) waves break up the rocks and bedrock of the shore into sand, except where a beach covers them
+// hydrology keeps the sea at its level, wind only lifts sand from dry cells,
+// and salt spray keeps vegetation from establishing on the lowest ground
+
+use crate::{
+    constants,
+    ecology::{provenance::TaggedLayer, Cell, CellIndex, Ecosystem},
+};
+
+// returns whether the ground of the cell is below the sea level
+pub fn is_under_sea(ecosystem: &Ecosystem, index: CellIndex) -> bool {
+    ecosystem
+        .config
+        .sea
+        .is_some_and(|sea| ecosystem[index].get_height() < sea.level)
+}
+
+// returns whether the cell is low enough for salt spray to keep seedlings from establishing
+pub fn is_in_salt_spray(ecosystem: &Ecosystem, index: CellIndex) -> bool {
+    ecosystem
+        .config
+        .sea
+        .is_some_and(|sea| ecosystem[index].get_height() < sea.level + sea.salt_spray_height)
+}
+
+pub fn update_coast(ecosystem: &mut Ecosystem) {
+    let Some(sea) = ecosystem.config.sea else {
+        return;
+    };
+    let wave_height = sea.wave_erosion * ecosystem.get_fraction_of_year();
+
+    // the shore is found before any cell changes so waves reach the same cells in every order
+    let mut shore = vec![];
+    for i in 0..constants::NUM_CELLS {
+        let index = CellIndex::get_from_flat_index(i);
+        if is_under_sea(ecosystem, index) {
+            continue;
+        }
+        let sea_neighbors: Vec<CellIndex> = Cell::get_neighbors(&index, ecosystem.config.boundary)
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|neighbor| is_under_sea(ecosystem, *neighbor))
+            .collect();
+        if !sea_neighbors.is_empty() {
+            shore.push((index, sea_neighbors));
+        }
+    }
+
+    for (index, sea_neighbors) in shore {
+        // 1) sand is washed ashore
+        let share = wave_height / sea_neighbors.len() as f32;
+        let mut washed = 0.0;
+        for neighbor in sea_neighbors {
+            ecosystem.track_transport(TaggedLayer::Sand, neighbor, index, share);
+            let removed = ecosystem[neighbor].remove_sand(share);
+            if removed > 0.0 {
+                washed += removed;
+                ecosystem.mark_dirty(neighbor);
+            }
+        }
+
+        // 2) the exposed shore is broken up
+        let cell = &mut ecosystem[index];
+        let exposed = f32::max(wave_height - cell.get_sand_height(), 0.0);
+        let mut broken = cell.remove_rocks(exposed);
+        broken += cell.remove_bedrock(exposed - broken);
+
+        if washed + broken > 0.0 {
+            cell.add_sand(washed + broken);
+            ecosystem.mark_dirty(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{is_in_salt_spray, is_under_sea, update_coast};
+    use crate::{
+        config::Sea,
+        constants,
+        ecology::{CellIndex, Ecosystem},
+        events::Events,
+    };
+
+    // flat land with the sea covering the cells with y < 50
+    fn init_coast_ecosystem() -> Ecosystem {
+        let mut ecosystem = Ecosystem::init();
+        for x in 0..constants::AREA_SIDE_LENGTH {
+            for y in 0..50 {
+                ecosystem[CellIndex::new(x, y)].remove_bedrock(5.0);
+            }
+        }
+        ecosystem.config.sea = Some(Sea {
+            level: constants::DEFAULT_BEDROCK_HEIGHT - 1.0,
+            ..Sea::default()
+        });
+        ecosystem
+    }
+
+    #[test]
+    fn test_sea_and_salt_spray() {
+        let mut ecosystem = init_coast_ecosystem();
+        let sea = CellIndex::new(50, 49);
+        let shore = CellIndex::new(50, 50);
+        assert!(is_under_sea(&ecosystem, sea));
+        assert!(!is_under_sea(&ecosystem, shore));
+        // the shore is only a meter above the sea
+        assert!(is_in_salt_spray(&ecosystem, shore));
+        ecosystem[shore].add_bedrock(5.0);
+        assert!(!is_in_salt_spray(&ecosystem, shore));
+
+        // without a sea level nothing is flooded
+        ecosystem.config.sea = None;
+        assert!(!is_under_sea(&ecosystem, sea));
+        assert!(!is_in_salt_spray(&ecosystem, sea));
+    }
+
+    #[test]
+    fn test_update_coast_forms_beach() {
+        let mut ecosystem = init_coast_ecosystem();
+        let sea = CellIndex::new(50, 49);
+        let shore = CellIndex::new(50, 50);
+        let inland = CellIndex::new(50, 51);
+        ecosystem[sea].add_sand(1.0);
+        let shore_height = ecosystem[shore].get_height();
+        let wave_height = Sea::default().wave_erosion;
+
+        update_coast(&mut ecosystem);
+
+        // the shore is broken up into sand and sand is washed onto it from the sea
+        let expected = shore_height - wave_height;
+        let actual = ecosystem[shore].get_bedrock_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+        assert!(ecosystem[shore].get_sand_height() > wave_height);
+        assert!(ecosystem[sea].get_sand_height() < 1.0);
+        // waves do not reach further inland
+        assert_eq!(ecosystem[inland].get_sand_height(), 0.0);
+        assert!(ecosystem.dirty_cells.contains(&shore));
+
+        // the beach protects the bedrock behind it
+        let bedrock_height = ecosystem[shore].get_bedrock_height();
+        update_coast(&mut ecosystem);
+        assert_eq!(ecosystem[shore].get_bedrock_height(), bedrock_height);
+    }
+
+    #[test]
+    fn test_wind_does_not_lift_sand_under_sea() {
+        let mut ecosystem = init_coast_ecosystem();
+        let sea = CellIndex::new(50, 40);
+        ecosystem[sea].add_sand(1.0);
+        Events::Wind.apply_event(&mut ecosystem, sea);
+        assert_eq!(ecosystem[sea].get_sand_height(), 1.0);
+    }
+}
@@ -0,0 +1,213 @@
+// CELL DELTAS
+// events that can be written as changes to the cells they touch are applied in two phases,
+// so a pass of such an event over the whole grid runs across cores
+// 1) every cell computes the deltas its event makes to itself and its neighbors, in parallel,
+//    while the ecosystem is only read
+// 2) the deltas are applied serially, cell by cell, along with the audit, log, and dirty cells
+//    of the event
+// events that depend on what their neighbors did in the same pass, e.g. slides that propagate,
+// are still applied one cell at a time
+// a new event follows the pattern by returning its deltas from `Events::compute_deltas`
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::{log::CellSnapshot, parallel::PrecomputedEvents, Events};
+use crate::{
+    constants,
+    ecology::{Cell, CellIndex, Ecosystem, ROCK_SIZES},
+};
+
+// change to the layers of a cell, negative for what is removed
+// removals take at most what the cell has, like the layer removers
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CellDelta {
+    // heights in m
+    pub bedrock: f32,
+    // indexed by rock size
+    pub rocks: [f32; 3],
+    pub sand: f32,
+    pub humus: f32,
+    // in kg
+    pub dead_vegetation: f32,
+    // in L
+    pub soil_moisture: f32,
+}
+
+impl CellDelta {
+    pub fn apply(&self, cell: &mut Cell) {
+        if self.bedrock > 0.0 {
+            cell.add_bedrock(self.bedrock);
+        } else if self.bedrock < 0.0 {
+            cell.remove_bedrock(-self.bedrock);
+        }
+        for size in ROCK_SIZES {
+            let rocks = self.rocks[size as usize];
+            if rocks > 0.0 {
+                cell.add_rocks_of_size(size, rocks);
+            } else if rocks < 0.0 {
+                cell.remove_rocks_of_size(size, -rocks);
+            }
+        }
+        if self.sand > 0.0 {
+            cell.add_sand(self.sand);
+        } else if self.sand < 0.0 {
+            cell.remove_sand(-self.sand);
+        }
+        if self.humus > 0.0 {
+            cell.add_humus(self.humus);
+        } else if self.humus < 0.0 {
+            cell.remove_humus(-self.humus);
+        }
+        if self.dead_vegetation > 0.0 {
+            cell.add_dead_vegetation(self.dead_vegetation);
+        } else if self.dead_vegetation < 0.0 {
+            cell.remove_dead_vegetation(-self.dead_vegetation);
+        }
+        cell.soil_moisture = f32::max(cell.soil_moisture + self.soil_moisture, 0.0);
+    }
+}
+
+impl Events {
+    // returns the deltas the event makes to each cell it touches, without changing the ecosystem,
+    // or None if the event has to be applied directly
+    pub fn compute_deltas(
+        self,
+        ecosystem: &Ecosystem,
+        index: CellIndex,
+        precomputed: &PrecomputedEvents,
+    ) -> Option<Vec<(CellIndex, CellDelta)>> {
+        let delta = match self {
+            Events::ThermalStress => {
+                Self::compute_thermal_stress_delta(ecosystem, index, precomputed.thermal_fracture)
+            }
+            Events::RockWeathering => Self::compute_rock_weathering_delta(ecosystem, index),
+            _ => return None,
+        };
+        Some(vec![(index, delta)])
+    }
+
+    // applies the deltas the event computed at the cell, keeping the same records as applying it directly
+    pub fn apply_deltas(
+        self,
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+        deltas: &[(CellIndex, CellDelta)],
+    ) {
+        let before = CellSnapshot::take(&ecosystem[index]);
+        let audited = self.audit_before(ecosystem, index);
+        for (target, delta) in deltas {
+            delta.apply(&mut ecosystem[*target]);
+            ecosystem.changed_cells.insert(*target);
+        }
+        ecosystem[index].last_events.push(self);
+        ecosystem.changed_cells.insert(index);
+        self.audit_after(ecosystem, index, audited);
+        self.log(ecosystem, index, &before);
+        self.mark_terrain_change(ecosystem, index);
+    }
+}
+
+// applies the event to every cell in two phases if it can be written as deltas
+// returns whether it could, otherwise the event has to be applied cell by cell
+pub fn apply_delta_pass(
+    ecosystem: &mut Ecosystem,
+    event: Events,
+    precomputed: &[PrecomputedEvents],
+) -> bool {
+    // 1) compute
+    let shared: &Ecosystem = ecosystem;
+    let deltas: Option<Vec<Vec<(CellIndex, CellDelta)>>> = (0..constants::NUM_CELLS)
+        .into_par_iter()
+        .map(|i| event.compute_deltas(shared, CellIndex::get_from_flat_index(i), &precomputed[i]))
+        .collect();
+    let Some(deltas) = deltas else {
+        return false;
+    };
+
+    // 2) apply
+    for (i, cell_deltas) in deltas.iter().enumerate() {
+        event.apply_deltas(ecosystem, CellIndex::get_from_flat_index(i), cell_deltas);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_delta_pass, CellDelta};
+    use crate::{
+        constants,
+        ecology::{Cell, CellIndex, Ecosystem, RockSize},
+        events::{parallel::precompute_events, Events},
+    };
+
+    #[test]
+    fn test_apply_cell_delta() {
+        let mut cell = Cell::init();
+        cell.add_sand(1.0);
+        let bedrock = cell.get_bedrock_height();
+        let delta = CellDelta {
+            bedrock: -1.0,
+            rocks: [0.0, 1.0, 0.0],
+            sand: -2.0,
+            humus: 0.5,
+            ..CellDelta::default()
+        };
+
+        delta.apply(&mut cell);
+
+        // removals take at most what the cell has
+        assert_eq!(cell.get_bedrock_height(), bedrock - 1.0);
+        assert_eq!(cell.get_rock_height_of_size(RockSize::Scree), 1.0);
+        assert_eq!(cell.get_sand_height(), 0.0);
+        assert_eq!(cell.get_humus_height(), 0.5);
+    }
+
+    #[test]
+    fn test_apply_delta_pass() {
+        // a pass of deltas gives the same ecosystem as applying the event cell by cell
+        let init = || {
+            let mut ecosystem = Ecosystem::init();
+            for (i, cell) in ecosystem.cells.iter_mut().enumerate() {
+                cell.add_rocks(0.1 * (i % 7) as f32);
+                cell.add_sand(0.2 * (i % 3) as f32);
+            }
+            ecosystem
+        };
+        for event in [Events::ThermalStress, Events::RockWeathering] {
+            let mut serial = init();
+            let mut parallel = init();
+            let precomputed = precompute_events(&parallel, 0);
+            for (i, cell_precomputed) in precomputed.iter().enumerate() {
+                let index = CellIndex::get_from_flat_index(i);
+                event.apply_precomputed_event(&mut serial, index, cell_precomputed);
+            }
+            assert!(apply_delta_pass(&mut parallel, event, &precomputed));
+
+            for i in 0..constants::NUM_CELLS {
+                let index = CellIndex::get_from_flat_index(i);
+                let (expected, actual) = (&serial[index], &parallel[index]);
+                assert_eq!(
+                    actual.get_rock_heights(),
+                    expected.get_rock_heights(),
+                    "{index}"
+                );
+                assert_eq!(
+                    actual.get_sand_height(),
+                    expected.get_sand_height(),
+                    "{index}"
+                );
+                assert_eq!(actual.get_height(), expected.get_height(), "{index}");
+                assert_eq!(actual.last_events, expected.last_events, "{index}");
+            }
+        }
+
+        // events that depend on their neighbors are not applied
+        let mut ecosystem = init();
+        let precomputed = precompute_events(&ecosystem, 0);
+        assert!(!apply_delta_pass(
+            &mut ecosystem,
+            Events::SandSlide,
+            &precomputed
+        ));
+    }
+}
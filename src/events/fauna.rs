@@ -0,0 +1,333 @@
+// GRAZING FAUNA
+// herds of grazers are simulated as individual agents once per time step:
+// 1) each grazer takes a number of steps, moving to the neighboring cell with the most food,
+//    or towards water when it is thirsty
+// 2) it eats grass in the cell it enters, and browses bushes when the grass is not enough
+// 3) its hooves compact part of the humus of the cell
+// 4) grazers that ran out of food or water die, and well fed grazers reproduce
+// food is weighted by the vigor of the grass so grazers follow the best pasture
+
+// moves each grazer takes per time step
+const MOVES_PER_TIME_STEP: usize = 10;
+// energy gained from a full time step of eating, which is also the energy used per time step
+const METABOLISM: f32 = 1.0;
+// energy of a new grazer
+const INITIAL_ENERGY: f32 = 1.0;
+// grazers with at least this much energy split off a new grazer
+const REPRODUCTION_ENERGY: f32 = 2.0;
+const MAX_ENERGY: f32 = 3.0;
+// moves a grazer survives without drinking
+const MOVES_WITHOUT_WATER: f32 = 20.0;
+// height (in m) of surface water a grazer can drink from
+const MIN_DRINKING_WATER: f32 = 0.01;
+// kg of bushes worth as much to a grazer as a fully covered cell of grass
+const BUSH_FOOD_EQUIVALENT: f32 = 100.0;
+// random variation in how grazers score cells so that herds spread out
+const SCORE_NOISE: f32 = 0.05;
+
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use super::{parallel::PrecomputedEvents, vegetation::Individualized};
+use crate::{
+    config::Fauna,
+    constants,
+    ecology::{Cell, CellIndex, Ecosystem},
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Grazer {
+    pub index: CellIndex,
+    // time steps of food stored
+    pub energy: f32,
+    // 1 right after drinking, dies at 0
+    pub hydration: f32,
+}
+
+// places the initial grazers of the config on random cells
+pub fn populate_grazers(ecosystem: &mut Ecosystem) {
+    let grazers: Vec<Grazer> = (0..ecosystem.config.fauna.initial_grazers)
+        .map(|_| {
+            let x = ecosystem.rng.gen_range(0..constants::AREA_SIDE_LENGTH);
+            let y = ecosystem.rng.gen_range(0..constants::AREA_SIDE_LENGTH);
+            Grazer {
+                index: CellIndex::new(x, y),
+                energy: INITIAL_ENERGY,
+                hydration: 1.0,
+            }
+        })
+        .collect();
+    ecosystem.grazers = grazers;
+}
+
+// moves, feeds, and updates the population of all grazers
+// grass vigor is taken from the events precomputed for this time step
+pub fn update_fauna(ecosystem: &mut Ecosystem, precomputed: &[PrecomputedEvents]) {
+    let fauna = ecosystem.config.fauna;
+    let mut grazers = std::mem::take(&mut ecosystem.grazers);
+    // grazers that move first get the best pasture
+    grazers.shuffle(&mut ecosystem.rng);
+
+    for grazer in &mut grazers {
+        for _ in 0..MOVES_PER_TIME_STEP {
+            grazer.index = choose_next_cell(ecosystem, precomputed, grazer);
+            graze(ecosystem, grazer, &fauna);
+            trample(ecosystem, grazer.index, fauna.trampling);
+            if ecosystem[grazer.index].get_water_height() > MIN_DRINKING_WATER {
+                grazer.hydration = 1.0;
+            } else {
+                grazer.hydration -= 1.0 / MOVES_WITHOUT_WATER;
+            }
+            if grazer.hydration <= 0.0 {
+                break;
+            }
+        }
+        grazer.energy -= METABOLISM;
+    }
+
+    // starved and dehydrated grazers die
+    grazers.retain(|grazer| grazer.energy > 0.0 && grazer.hydration > 0.0);
+
+    let population = grazers.len();
+    let mut offspring = vec![];
+    for grazer in &mut grazers {
+        if population + offspring.len() >= fauna.max_grazers {
+            break;
+        }
+        if grazer.energy >= REPRODUCTION_ENERGY {
+            grazer.energy -= INITIAL_ENERGY;
+            offspring.push(Grazer {
+                index: grazer.index,
+                energy: INITIAL_ENERGY,
+                hydration: grazer.hydration,
+            });
+        }
+    }
+    grazers.append(&mut offspring);
+    ecosystem.grazers = grazers;
+}
+
+// returns the cell the grazer moves to, which may be the cell it is in
+fn choose_next_cell(
+    ecosystem: &mut Ecosystem,
+    precomputed: &[PrecomputedEvents],
+    grazer: &Grazer,
+) -> CellIndex {
+    let neighbors = Cell::get_neighbors(&grazer.index, ecosystem.config.boundary).as_array();
+    let candidates = std::iter::once(grazer.index).chain(neighbors.into_iter().flatten());
+    let thirst = 1.0 - grazer.hydration;
+
+    let mut best = (grazer.index, f32::MIN);
+    for index in candidates {
+        let cell = &ecosystem[index];
+        let (vigor, _) = precomputed[index.get_flat_index()].grasses_vigor_and_stress;
+        let grass = cell.grasses.as_ref().map_or(0.0, |g| g.coverage_density);
+        let food =
+            grass * (1.0 + vigor.max(0.0)) + cell.estimate_bush_biomass() / BUSH_FOOD_EQUIVALENT;
+        let water = if cell.get_water_height() > MIN_DRINKING_WATER {
+            1.0
+        } else {
+            0.0
+        };
+        // thirsty grazers care more about water than food
+        let score =
+            food * (1.0 - thirst) + water * thirst * 2.0 + ecosystem.rng.gen::<f32>() * SCORE_NOISE;
+        if score > best.1 {
+            best = (index, score);
+        }
+    }
+    best.0
+}
+
+// eats grass in the grazer's cell, then bushes for whatever grass could not provide
+fn graze(ecosystem: &mut Ecosystem, grazer: &mut Grazer, fauna: &Fauna) {
+    ecosystem.changed_cells.insert(grazer.index);
+    let cell = &mut ecosystem[grazer.index];
+    let wanted = fauna.grass_appetite / MOVES_PER_TIME_STEP as f32;
+    let mut hunger = 1.0;
+    // herds with no appetite for grass only browse bushes
+    if wanted > 0.0 {
+        if let Some(grasses) = &mut cell.grasses {
+            let eaten = f32::min(grasses.coverage_density, wanted);
+            grasses.coverage_density -= eaten;
+            hunger -= eaten / wanted;
+        }
+    }
+
+    if hunger > 0.0 {
+        if let Some(bushes) = &mut cell.bushes {
+            let number_of_plants = bushes.get_number_of_plants();
+            let wanted = fauna.bush_appetite / MOVES_PER_TIME_STEP as f32 * hunger;
+            if number_of_plants > 0 && wanted > 0.0 {
+                // bushes are eaten whole, starting with the young and tender ones
+                let biomass = bushes.estimate_biomass();
+                let average_biomass = biomass / number_of_plants as f32;
+                let eaten_plants =
+                    u32::min((wanted / average_biomass).ceil() as u32, number_of_plants);
+                bushes.kill_plants(eaten_plants);
+                let eaten = f32::min(biomass - bushes.estimate_biomass(), wanted);
+                hunger -= eaten / wanted * hunger;
+            }
+        }
+    }
+
+    let eaten = 1.0 - hunger;
+    grazer.energy = f32::min(
+        grazer.energy + eaten * METABOLISM / MOVES_PER_TIME_STEP as f32,
+        MAX_ENERGY,
+    );
+}
+
+// hooves compact and wear away the humus
+fn trample(ecosystem: &mut Ecosystem, index: CellIndex, trampling: f32) {
+    let cell = &mut ecosystem[index];
+    if cell.remove_humus(trampling) > 0.0 {
+        ecosystem.mark_dirty(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{graze, update_fauna, Grazer, MOVES_PER_TIME_STEP};
+    use crate::{
+        config::Fauna,
+        constants,
+        ecology::{Bushes, CellIndex, Ecosystem, Grasses},
+        events::{parallel::PrecomputedEvents, vegetation::Individualized},
+    };
+
+    // meadow with water everywhere so grazers only depend on food
+    fn init_meadow(coverage: f32) -> Ecosystem {
+        let mut ecosystem = Ecosystem::init();
+        for cell in ecosystem.cells.iter_mut() {
+            cell.grasses = Some(Grasses {
+                coverage_density: coverage,
+                green_fraction: 1.0,
+            });
+            cell.add_water(1.0);
+        }
+        ecosystem
+    }
+
+    fn init_precomputed() -> Vec<PrecomputedEvents> {
+        vec![
+            PrecomputedEvents {
+                thermal_fracture: false,
+                freeze_thaw: false,
+                trees_vigor_and_stress: vec![],
+                bushes_vigor_and_stress: (0.0, 0.0),
+                grasses_vigor_and_stress: (0.0, 0.0),
+            };
+            constants::NUM_CELLS
+        ]
+    }
+
+    fn init_grazer(energy: f32) -> Grazer {
+        Grazer {
+            index: CellIndex::new(50, 50),
+            energy,
+            hydration: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_update_fauna_prefers_vigorous_grass() {
+        let mut ecosystem = init_meadow(1.0);
+        ecosystem.grazers = vec![init_grazer(1.0)];
+        let mut precomputed = init_precomputed();
+        let vigorous = CellIndex::new(51, 50);
+        precomputed[vigorous.get_flat_index()].grasses_vigor_and_stress = (1.0, 0.0);
+
+        update_fauna(&mut ecosystem, &precomputed);
+
+        let coverage =
+            |index: CellIndex| ecosystem[index].grasses.as_ref().unwrap().coverage_density;
+        assert!(coverage(vigorous) < 1.0);
+        assert_eq!(coverage(CellIndex::new(49, 50)), 1.0);
+        // a full time step of eating makes up for the energy used
+        let actual = ecosystem.grazers[0].energy;
+        assert!(actual >= 0.99, "Expected at least 0.99, actual {actual}");
+    }
+
+    #[test]
+    fn test_update_fauna_population() {
+        // well fed grazers reproduce
+        let mut ecosystem = init_meadow(1.0);
+        ecosystem.grazers = vec![init_grazer(2.5)];
+        update_fauna(&mut ecosystem, &init_precomputed());
+        assert_eq!(ecosystem.grazers.len(), 2);
+
+        // and starve without food
+        let mut ecosystem = init_meadow(0.0);
+        ecosystem.grazers = vec![init_grazer(1.0)];
+        update_fauna(&mut ecosystem, &init_precomputed());
+        assert!(ecosystem.grazers.is_empty());
+
+        // or water
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.grazers = vec![init_grazer(3.0)];
+        update_fauna(&mut ecosystem, &init_precomputed());
+        update_fauna(&mut ecosystem, &init_precomputed());
+        assert!(ecosystem.grazers.is_empty());
+    }
+
+    #[test]
+    fn test_graze_without_appetite() {
+        // a herd that only browses eats the bushes whatever grass there is
+        let mut ecosystem = init_meadow(1.0);
+        let mut grazer = init_grazer(0.5);
+        ecosystem[grazer.index].bushes = Some(Bushes::with_plants(10, 10.0, 10.0));
+        let browsers = Fauna {
+            grass_appetite: 0.0,
+            ..Fauna::default()
+        };
+        graze(&mut ecosystem, &mut grazer, &browsers);
+        let cell = &ecosystem[grazer.index];
+        assert_eq!(cell.grasses.as_ref().unwrap().coverage_density, 1.0);
+        assert!(cell.bushes.as_ref().unwrap().get_number_of_plants() < 10);
+        assert!(grazer.energy > 0.5, "actual {}", grazer.energy);
+
+        // and one that only grazes leaves the bushes alone when the grass runs out
+        let mut ecosystem = init_meadow(0.0);
+        let mut grazer = init_grazer(0.5);
+        ecosystem[grazer.index].bushes = Some(Bushes::with_plants(10, 10.0, 10.0));
+        let grazers = Fauna {
+            bush_appetite: 0.0,
+            ..Fauna::default()
+        };
+        graze(&mut ecosystem, &mut grazer, &grazers);
+        let cell = &ecosystem[grazer.index];
+        assert_eq!(cell.bushes.as_ref().unwrap().get_number_of_plants(), 10);
+        assert_eq!(grazer.energy, 0.5);
+    }
+
+    #[test]
+    fn test_update_fauna_trampling() {
+        let mut ecosystem = init_meadow(1.0);
+        for cell in ecosystem.cells.iter_mut() {
+            cell.add_humus(1.0);
+        }
+        ecosystem.grazers = vec![init_grazer(1.0)];
+        let humus = |ecosystem: &Ecosystem| -> f32 {
+            ecosystem
+                .cells
+                .iter()
+                .map(|cell| cell.get_humus_height())
+                .sum()
+        };
+        let before = humus(&ecosystem);
+
+        update_fauna(&mut ecosystem, &init_precomputed());
+
+        // every move compacts the humus of the cell entered
+        let expected = MOVES_PER_TIME_STEP as f32 * ecosystem.config.fauna.trampling;
+        let actual = before - humus(&ecosystem);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        assert!(!ecosystem.dirty_cells.is_empty());
+    }
+}
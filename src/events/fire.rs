@@ -0,0 +1,324 @@
+// FIRE
+// spread probabilities based on the cellular automata wildfire model from
+// "A cellular automata model for forest fire spread prediction" by Alexandridis, A. et al.
+// p_burn = p_h * fuel * dryness * p_wind * p_slope
+const BASE_SPREAD_PROBABILITY: f32 = 0.58;
+// wind constants, wind speed in m/s
+const WIND_SPREAD_CONSTANT_ONE: f32 = 0.045;
+const WIND_SPREAD_CONSTANT_TWO: f32 = 0.131;
+// slope constant, slope angle in degrees
+const SLOPE_SPREAD_CONSTANT: f32 = 0.078;
+
+// fuel biomass in kg per square meter at which a cell is considered fully fueled
+const FUEL_LOAD_SATURATION: f32 = 2.0;
+// soil moisture (% by volume) above which fuel no longer burns
+const MOISTURE_OF_EXTINCTION: f32 = 0.3;
+// % of dead vegetation consumed by the fire, while the rest remains as charred debris
+const FUEL_CONSUMPTION_RATE: f32 = 0.8;
+// % of consumed biomass left behind as ash, which is incorporated into humus
+const ASH_RATE: f32 = 0.05;
+// how much of the burn scar remains after each time step
+const BURN_SEVERITY_DECAY: f32 = 0.5;
+const MIN_BURN_SEVERITY: f32 = 0.01;
+
+use super::Events;
+use crate::{
+    constants,
+    ecology::{Cell, CellIndex, Ecosystem},
+    events::{vegetation::HUMUS_DENSITY, wind::get_local_wind},
+    units::{self, Kilograms},
+};
+use rand::Rng;
+
+impl Events {
+    pub fn apply_fire_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+    ) -> Option<(Events, CellIndex)> {
+        let cell = &mut ecosystem[index];
+        // burn out if there is nothing left to burn
+        if Self::estimate_fuel_load(cell) == 0.0 {
+            return None;
+        }
+
+        // all living vegetation is killed and most of the resulting fuel is consumed
+        Self::kill_trees(cell);
+        Self::kill_bushes(cell);
+        Self::kill_grasses(cell);
+        let burned_biomass = cell.get_dead_vegetation_biomass() * FUEL_CONSUMPTION_RATE;
+        cell.remove_dead_vegetation(burned_biomass);
+        cell.add_humus(Self::convert_ash_to_humus(burned_biomass * ASH_RATE));
+        cell.burn_severity = 1.0;
+
+        // spread to at most one neighbor, chosen by its probability of catching fire
+        let (wind_dir, wind_str) = Self::get_fire_wind(ecosystem, index);
+        let mut candidates = vec![];
+        let mut no_spread_probability = 1.0;
+        for neighbor in Cell::get_neighbors(&index, ecosystem.config.boundary)
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let probability = Self::compute_fire_spread_probability(
+                ecosystem, index, neighbor, wind_dir, wind_str,
+            );
+            if probability > 0.0 {
+                no_spread_probability *= 1.0 - probability;
+                candidates.push((neighbor, probability));
+            }
+        }
+
+        let rand: f32 = ecosystem.rng.gen();
+        if rand >= 1.0 - no_spread_probability {
+            // fire burns out
+            return None;
+        }
+        let probability_sum: f32 = candidates.iter().map(|(_, p)| p).sum();
+        let mut rand: f32 = ecosystem.rng.gen::<f32>() * probability_sum;
+        for (neighbor, probability) in candidates {
+            rand -= probability;
+            if rand < 0.0 {
+                return Some((Events::Fire, neighbor));
+            }
+        }
+        None
+    }
+
+    // probability that fire spreads from the burning origin to the target
+    pub fn compute_fire_spread_probability(
+        ecosystem: &Ecosystem,
+        origin: CellIndex,
+        target: CellIndex,
+        wind_dir: f32,
+        wind_str: f32,
+    ) -> f32 {
+        let cell = &ecosystem[target];
+        // recently burned cells cannot burn again
+        if cell.burn_severity > 0.0 {
+            return 0.0;
+        }
+        let fuel = Self::estimate_fuel_load(cell);
+        if fuel == 0.0 {
+            return 0.0;
+        }
+        let dryness = Self::estimate_dryness(ecosystem, target);
+
+        // fire spreads faster downwind
+        let wind_vec = get_wind_vector(wind_dir);
+        let spread_x = target.x as f32 - origin.x as f32;
+        let spread_y = target.y as f32 - origin.y as f32;
+        let spread_length = f32::sqrt(spread_x * spread_x + spread_y * spread_y);
+        let cos_angle = (wind_vec.0 * spread_x + wind_vec.1 * spread_y) / spread_length;
+        let wind_factor = f32::exp(WIND_SPREAD_CONSTANT_ONE * wind_str)
+            * f32::exp(wind_str * WIND_SPREAD_CONSTANT_TWO * (cos_angle - 1.0));
+
+        // fire spreads faster uphill
+        let slope_angle = -Ecosystem::get_angle(ecosystem.get_slope_between_points(origin, target));
+        let slope_factor = f32::exp(SLOPE_SPREAD_CONSTANT * slope_angle);
+
+        f32::min(
+            BASE_SPREAD_PROBABILITY * fuel * dryness * wind_factor * slope_factor,
+            1.0,
+        )
+    }
+
+    // returns amount of burnable biomass in the cell relative to a fully fueled cell, between 0 and 1
+    pub fn estimate_fuel_load(cell: &Cell) -> f32 {
+        let saturation =
+            FUEL_LOAD_SATURATION * constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+        f32::min(Self::estimate_fuel_biomass(cell) / saturation, 1.0)
+    }
+
+    // returns kg of burnable biomass in the cell
+    fn estimate_fuel_biomass(cell: &Cell) -> f32 {
+        let grasses_coverage = cell.grasses.as_ref().map_or(0.0, |g| g.coverage_density);
+        cell.estimate_tree_biomass()
+            + cell.estimate_bush_biomass()
+            + convert_grass_coverage_to_fuel(grasses_coverage)
+            + cell.get_dead_vegetation_biomass()
+    }
+
+    // returns how dry the cell is over the time step, between 0 (too wet to burn) and 1
+    pub fn estimate_dryness(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        let months = ecosystem.get_months_of_time_step();
+        let covered: f32 = months.iter().map(|(_, fraction)| fraction).sum();
+        let moisture = months
+            .into_iter()
+            .map(|(month, fraction)| Self::compute_moisture(ecosystem, index, month) * fraction)
+            .sum::<f32>()
+            / covered;
+        let dryness = f32::max(1.0 - moisture / MOISTURE_OF_EXTINCTION, 0.0);
+
+        // cured grass is dry whatever the soil moisture, so it dries the fuel it is part of
+        let cell = &ecosystem[index];
+        let fuel_biomass = Self::estimate_fuel_biomass(cell);
+        let cured_biomass = cell.grasses.as_ref().map_or(0.0, |g| {
+            convert_grass_coverage_to_fuel(g.get_cured_coverage())
+        });
+        if fuel_biomass == 0.0 {
+            return dryness;
+        }
+        dryness + (1.0 - dryness) * cured_biomass / fuel_biomass
+    }
+
+    // returns height of humus produced from the given ash biomass
+    fn convert_ash_to_humus(biomass: f32) -> f32 {
+        (Kilograms(biomass) / HUMUS_DENSITY).spread_over_cell().0
+    }
+
+    fn get_fire_wind(ecosystem: &Ecosystem, index: CellIndex) -> (f32, f32) {
+        if let Some(wind_state) = &ecosystem.wind_state {
+            get_local_wind(
+                ecosystem,
+                index,
+                wind_state.wind_direction,
+                wind_state.wind_strength,
+            )
+        } else {
+            (
+                ecosystem.config.wind.direction,
+                ecosystem.config.wind.strength,
+            )
+        }
+    }
+}
+
+// burn scars fade over time so cells can eventually burn again
+pub fn decay_burn_severity(ecosystem: &mut Ecosystem) {
+    for cell in ecosystem.cells.iter_mut() {
+        cell.burn_severity *= BURN_SEVERITY_DECAY;
+        if cell.burn_severity < MIN_BURN_SEVERITY {
+            cell.burn_severity = 0.0;
+        }
+    }
+}
+
+// returns kg of fuel in grass of the given coverage
+fn convert_grass_coverage_to_fuel(coverage: f32) -> f32 {
+    (units::CELL_AREA * constants::GRASS_DENSITY * coverage).0
+}
+
+// same convention as the wind event, where the vector points in the direction the wind is blowing
+fn get_wind_vector(wind_dir: f32) -> (f32, f32) {
+    let wind_dir = wind_dir.to_radians();
+    (wind_dir.sin(), wind_dir.cos())
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{decay_burn_severity, ASH_RATE, FUEL_CONSUMPTION_RATE};
+    use crate::{
+        ecology::{CellIndex, Ecosystem, Grasses, Trees},
+        events::Events,
+    };
+
+    #[test]
+    fn test_apply_fire_event() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(2, 2);
+        let trees = Trees::with_plants(0, 10, 100.0, 100.0);
+        let biomass = trees.estimate_biomass();
+        let cell = &mut ecosystem[index];
+        cell.trees = vec![trees];
+
+        Events::apply_fire_event(&mut ecosystem, index);
+
+        let cell = &ecosystem[index];
+        assert!(cell.trees.is_empty());
+        assert_eq!(cell.burn_severity, 1.0);
+        let expected = biomass * (1.0 - FUEL_CONSUMPTION_RATE);
+        let actual = cell.get_dead_vegetation_biomass();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.01),
+            "Expected {expected}, actual {actual}"
+        );
+        let expected = Events::convert_ash_to_humus(biomass * FUEL_CONSUMPTION_RATE * ASH_RATE);
+        let actual = cell.get_humus_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_apply_fire_event_without_fuel() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(2, 2);
+        let result = Events::apply_fire_event(&mut ecosystem, index);
+        assert!(result.is_none());
+        assert_eq!(ecosystem[index].burn_severity, 0.0);
+    }
+
+    #[test]
+    fn test_compute_fire_spread_probability() {
+        let mut ecosystem = Ecosystem::init();
+        let origin = CellIndex::new(2, 2);
+        let east = CellIndex::new(3, 2);
+        let west = CellIndex::new(1, 2);
+        for index in [east, west] {
+            ecosystem[index].grasses = Some(Grasses {
+                coverage_density: 1.0,
+                green_fraction: 1.0,
+            });
+        }
+
+        // no wind means equal spread on flat terrain
+        let p_east = Events::compute_fire_spread_probability(&ecosystem, origin, east, 90.0, 0.0);
+        let p_west = Events::compute_fire_spread_probability(&ecosystem, origin, west, 90.0, 0.0);
+        assert!(p_east > 0.0);
+        assert_eq!(p_east, p_west);
+
+        // wind blowing east pushes fire downwind
+        let p_east = Events::compute_fire_spread_probability(&ecosystem, origin, east, 90.0, 10.0);
+        let p_west = Events::compute_fire_spread_probability(&ecosystem, origin, west, 90.0, 10.0);
+        assert!(p_east > p_west, "east {p_east}, west {p_west}");
+
+        // fire spreads faster uphill
+        ecosystem[east].add_bedrock(0.5);
+        let p_uphill = Events::compute_fire_spread_probability(&ecosystem, origin, east, 90.0, 0.0);
+        let p_flat = Events::compute_fire_spread_probability(&ecosystem, origin, west, 90.0, 0.0);
+        assert!(p_uphill > p_flat, "uphill {p_uphill}, flat {p_flat}");
+
+        // burned cells do not catch fire again
+        ecosystem[west].burn_severity = 0.5;
+        let p_west = Events::compute_fire_spread_probability(&ecosystem, origin, west, 90.0, 0.0);
+        assert_eq!(p_west, 0.0);
+    }
+
+    #[test]
+    fn test_estimate_dryness_cured_grass() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(2, 2);
+        ecosystem[index].add_humus(0.5);
+        ecosystem[index].soil_moisture = 1.0E6;
+        ecosystem[index].grasses = Some(Grasses {
+            coverage_density: 1.0,
+            green_fraction: 1.0,
+        });
+        // green grass on wet soil does not burn
+        assert_eq!(Events::estimate_dryness(&ecosystem, index), 0.0);
+
+        // cured grass burns regardless
+        ecosystem[index].grasses.as_mut().unwrap().green_fraction = 0.0;
+        let actual = Events::estimate_dryness(&ecosystem, index);
+        assert!(
+            approx_eq!(f32, actual, 1.0, epsilon = 0.0001),
+            "Expected 1, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_decay_burn_severity() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(2, 2);
+        ecosystem[index].burn_severity = 1.0;
+        decay_burn_severity(&mut ecosystem);
+        assert_eq!(ecosystem[index].burn_severity, 0.5);
+        for _ in 0..10 {
+            decay_burn_severity(&mut ecosystem);
+        }
+        assert_eq!(ecosystem[index].burn_severity, 0.0);
+    }
+}
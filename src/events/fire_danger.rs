@@ -0,0 +1,107 @@
+// FIRE DANGER
+// based on the simplified drought index from
+// "A drought index for forest fire control" by Keetch, J. J. and Byram, G. M.
+// the index is the soil moisture deficit (in mm) of the upper soil, from 0 when saturated to 203.2 (8 inches)
+// and is updated once per time step over the whole grid, for each month of the time step:
+// 1) evapotranspiration raises the index, faster in hot months and in wet climates with lush vegetation
+// 2) rainfall beyond what the canopy and litter intercept lowers the index
+// fire danger combines the drought index with the dryness of the soil moisture of the cell
+// and scales the chance that lightning ignites a fire
+
+// deficit (in mm) of completely dry soil
+pub const MAX_DROUGHT_INDEX: f32 = 203.2;
+// mm of rainfall per month caught by the canopy and litter before it reaches the soil
+const INTERCEPTED_RAINFALL: f32 = 25.0;
+
+use crate::{
+    config::{Climate, DAYS_IN_MONTH},
+    ecology::{Cell, CellIndex, Ecosystem},
+    events::Events,
+};
+
+pub fn update_drought_index(ecosystem: &mut Ecosystem) {
+    let months = ecosystem.get_months_of_time_step();
+    let climate = &ecosystem.config.climate;
+    for cell in ecosystem.cells.iter_mut() {
+        let mut drought_index = cell.drought_index;
+        for &(month, fraction) in &months {
+            let days = DAYS_IN_MONTH[month] as f32 * fraction;
+            drought_index += compute_drought_factor(cell, climate, month, drought_index) * days;
+            let net_rainfall = (climate.monthly_rainfall[month] - INTERCEPTED_RAINFALL) * fraction;
+            drought_index =
+                (drought_index - f32::max(net_rainfall, 0.0)).clamp(0.0, MAX_DROUGHT_INDEX);
+        }
+        cell.drought_index = drought_index;
+    }
+}
+
+// returns mm the drought index rises per day in the given month
+// the monthly temperature stands in for the daily maximum of the original index
+fn compute_drought_factor(cell: &Cell, climate: &Climate, month: usize, drought_index: f32) -> f32 {
+    let temperature = cell.get_monthly_temperature(climate, month);
+    let annual_rainfall: f32 = climate.monthly_rainfall.iter().sum();
+    let evapotranspiration = f32::max(0.968 * f32::exp(0.0875 * temperature + 1.5552) - 8.3, 0.0);
+    (MAX_DROUGHT_INDEX - drought_index) * evapotranspiration * 1.0E-3
+        / (1.0 + 10.88 * f32::exp(-0.001736 * annual_rainfall))
+}
+
+// returns how likely fuel in the cell is to ignite, between 0 and 1
+// a drought dries out the fuel even where the soil moisture of the time step is high
+pub fn estimate_fire_danger(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+    let dryness = Events::estimate_dryness(ecosystem, index);
+    let drought = ecosystem[index].drought_index / MAX_DROUGHT_INDEX;
+    dryness + (1.0 - dryness) * drought
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{estimate_fire_danger, update_drought_index, MAX_DROUGHT_INDEX};
+    use crate::{
+        config::TimeResolution,
+        ecology::{CellIndex, Ecosystem},
+    };
+
+    #[test]
+    fn test_update_drought_index() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.config.time_resolution = TimeResolution::Month;
+        let index = CellIndex::new(2, 2);
+
+        // a hot month without rain dries out the soil
+        ecosystem.time_step = 7;
+        ecosystem.config.climate.monthly_rainfall = [0.0; 12];
+        update_drought_index(&mut ecosystem);
+        let dry = ecosystem[index].drought_index;
+        assert!(dry > 0.0, "Expected a drought, actual {dry}");
+
+        // a cold month does not dry it out any further
+        ecosystem.time_step = 1;
+        update_drought_index(&mut ecosystem);
+        assert_eq!(ecosystem[index].drought_index, dry);
+
+        // heavy rain refills the soil
+        ecosystem.config.climate.monthly_rainfall = [MAX_DROUGHT_INDEX; 12];
+        update_drought_index(&mut ecosystem);
+        assert_eq!(ecosystem[index].drought_index, 0.0);
+    }
+
+    #[test]
+    fn test_estimate_fire_danger() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(2, 2);
+        ecosystem[index].add_humus(0.5);
+        ecosystem[index].soil_moisture = 1.0E6;
+        // wet soil without a drought is not in danger
+        assert_eq!(estimate_fire_danger(&ecosystem, index), 0.0);
+
+        // a drought raises the danger even though the soil moisture is high
+        ecosystem[index].drought_index = MAX_DROUGHT_INDEX / 2.0;
+        let actual = estimate_fire_danger(&ecosystem, index);
+        assert!(
+            approx_eq!(f32, actual, 0.5, epsilon = 0.0001),
+            "Expected 0.5, actual {actual}"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+// FREEZE-THAW WEATHERING
+// water in cracks and pores expands as it freezes, so every time the temperature crosses 0°C
+// wet bedrock is wedged apart and the soil above it heaves
+// 1) freeze-thaw cycles are counted from the monthly temperatures at the cell's elevation,
+//    with the daily temperature range letting days of months close to freezing cross it
+// 2) the probability of weathering grows with the number of cycles and how wet the cell is,
+//    and is dampened by loose material insulating the bedrock
+// 3) a weathering event fractures bedrock into rocks, and frost heave lifts the humus
+//    perpendicular to the slope so part of it creeps to the lowest neighbor when it settles
+
+// difference between the warmest and coldest temperatures of a day in celsius
+const DAILY_TEMPERATURE_RANGE: f32 = 10.0;
+const DAYS_PER_MONTH: f32 = 30.0;
+// probability of weathering per freeze-thaw cycle of a saturated cell
+const FROST_WEDGING_CONSTANT: f32 = 0.002;
+// depth (in m) of loose material over which the frost reaching the bedrock drops by a factor of e
+const FROST_PENETRATION_DEPTH: f32 = 1.0;
+// monthly rainfall (in mm) that saturates the cracks of bare bedrock
+const SATURATING_RAINFALL: f32 = 100.0;
+// amount of bedrock fractured into rock per successful event
+const BEDROCK_FRACTURE_HEIGHT: f32 = 0.5;
+// % of the humus that creeps to the lowest neighbor per unit of slope
+const FROST_CREEP_RATE: f32 = 0.05;
+
+use rand::Rng;
+
+use super::Events;
+use crate::ecology::{provenance::TaggedLayer, Cell, CellIndex, Ecosystem};
+
+impl Events {
+    pub fn apply_freeze_thaw_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+    ) -> Option<(Events, CellIndex)> {
+        let weathering_probability = Self::compute_freeze_thaw_probability(ecosystem, index);
+        let rand: f32 = ecosystem.rng.gen();
+        Self::apply_precomputed_freeze_thaw_event(ecosystem, index, rand < weathering_probability)
+    }
+
+    // applies a freeze-thaw event whose outcome was already rolled by `roll_freeze_thaw`
+    pub fn apply_precomputed_freeze_thaw_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+        weathered: bool,
+    ) -> Option<(Events, CellIndex)> {
+        if !weathered {
+            return None;
+        }
+
+        // frost wedging fractures some bedrock into rocks
+        let cell = &mut ecosystem[index];
+        let fractured = cell.remove_bedrock(BEDROCK_FRACTURE_HEIGHT);
+        cell.add_rocks(fractured);
+
+        // frost heave moves humus downhill
+        if ecosystem[index].get_humus_height() > 0.0 {
+            let mut lowest = (index, 0.0);
+            for neighbor in Cell::get_neighbors(&index, ecosystem.config.boundary)
+                .as_array()
+                .into_iter()
+                .flatten()
+            {
+                let slope = ecosystem.get_slope_between_points(index, neighbor);
+                if slope > lowest.1 {
+                    lowest = (neighbor, slope);
+                }
+            }
+            let (neighbor, slope) = lowest;
+            if slope > 0.0 {
+                let humus_height = ecosystem[index].get_humus_height();
+                let crept = humus_height * f32::min(FROST_CREEP_RATE * slope, 1.0);
+                ecosystem.track_transport(TaggedLayer::Humus, index, neighbor, crept);
+                ecosystem[index].remove_humus(crept);
+                ecosystem[neighbor].add_humus(crept);
+            }
+        }
+
+        // does not propagate
+        None
+    }
+
+    // returns whether the cell is weathered by freezing and thawing this time step
+    pub fn roll_freeze_thaw(ecosystem: &Ecosystem, index: CellIndex, rng: &mut impl Rng) -> bool {
+        let weathering_probability = Self::compute_freeze_thaw_probability(ecosystem, index);
+        let rand: f32 = rng.gen();
+        rand < weathering_probability
+    }
+
+    fn compute_freeze_thaw_probability(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        // each wet freeze-thaw cycle is a chance of weathering
+        let wet_cycles: f32 = ecosystem
+            .get_months_of_time_step()
+            .into_iter()
+            .map(|(month, fraction)| {
+                Self::count_freeze_thaw_cycles(ecosystem, index, month)
+                    * Self::compute_crack_wetness(ecosystem, index, month)
+                    * fraction
+            })
+            .sum();
+
+        // frost reaches bedrock buried under loose material less often
+        let cell = &ecosystem[index];
+        let loose_height =
+            cell.get_rock_height() + cell.get_sand_height() + cell.get_humus_height();
+        let insulation = f32::exp(-loose_height / FROST_PENETRATION_DEPTH);
+
+        1.0 - f32::exp(-FROST_WEDGING_CONSTANT * wet_cycles * insulation)
+    }
+
+    // returns number of days of the month whose temperature crosses 0°C at the cell's elevation
+    // assuming daily temperatures spread evenly around the monthly mean
+    fn count_freeze_thaw_cycles(ecosystem: &Ecosystem, index: CellIndex, month: usize) -> f32 {
+        let temperature =
+            ecosystem[index].get_monthly_temperature(&ecosystem.config.climate, month);
+        let half_range = DAILY_TEMPERATURE_RANGE / 2.0;
+        DAYS_PER_MONTH * f32::max(1.0 - temperature.abs() / half_range, 0.0)
+    }
+
+    // returns how wet the cracks of the cell are in the month, between 0 and 1
+    fn compute_crack_wetness(ecosystem: &Ecosystem, index: CellIndex, month: usize) -> f32 {
+        if ecosystem[index].get_humus_height() > 0.0 {
+            Self::compute_moisture(ecosystem, index, month)
+        } else {
+            // bare ground only holds the rain that falls on it
+            let rainfall = ecosystem.config.climate.monthly_rainfall[month];
+            f32::min(rainfall / SATURATING_RAINFALL, 1.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{BEDROCK_FRACTURE_HEIGHT, DAYS_PER_MONTH};
+    use crate::{
+        config::Climate,
+        ecology::{CellIndex, Ecosystem},
+        events::Events,
+    };
+
+    #[test]
+    fn test_count_freeze_thaw_cycles() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(5, 5);
+        ecosystem[index].set_height_of_bedrock(0.0);
+        ecosystem.config.climate = Climate {
+            monthly_temperatures: [
+                0.0, 2.5, -2.5, 10.0, -10.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+            ],
+            ..Climate::default()
+        };
+
+        // every day of a month averaging 0°C crosses freezing
+        let actual = Events::count_freeze_thaw_cycles(&ecosystem, index, 0);
+        assert!(
+            approx_eq!(f32, actual, DAYS_PER_MONTH, epsilon = 0.001),
+            "Expected {DAYS_PER_MONTH}, actual {actual}"
+        );
+        // half of the days of months a quarter of the range away
+        let expected = DAYS_PER_MONTH / 2.0;
+        for month in [1, 2] {
+            let actual = Events::count_freeze_thaw_cycles(&ecosystem, index, month);
+            assert!(
+                approx_eq!(f32, actual, expected, epsilon = 0.001),
+                "Expected {expected}, actual {actual}"
+            );
+        }
+        // and none for months that stay thawed or frozen
+        assert_eq!(Events::count_freeze_thaw_cycles(&ecosystem, index, 3), 0.0);
+        assert_eq!(Events::count_freeze_thaw_cycles(&ecosystem, index, 4), 0.0);
+    }
+
+    #[test]
+    fn test_compute_freeze_thaw_probability() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(5, 5);
+        ecosystem[index].set_height_of_bedrock(0.0);
+
+        // a climate that never freezes does not weather
+        ecosystem.config.climate.monthly_temperatures = [20.0; 12];
+        assert_eq!(
+            Events::compute_freeze_thaw_probability(&ecosystem, index),
+            0.0
+        );
+
+        // wet months around freezing do
+        ecosystem.config.climate.monthly_temperatures = [0.0; 12];
+        ecosystem.config.climate.monthly_rainfall = [100.0; 12];
+        let bare = Events::compute_freeze_thaw_probability(&ecosystem, index);
+        assert!(bare > 0.0);
+
+        // and loose material insulates the bedrock
+        ecosystem[index].add_rocks(1.0);
+        let covered = Events::compute_freeze_thaw_probability(&ecosystem, index);
+        assert!(
+            covered < bare,
+            "Expected less than {bare}, actual {covered}"
+        );
+    }
+
+    #[test]
+    fn test_apply_precomputed_freeze_thaw_event() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(5, 5);
+        let lower = CellIndex::new(6, 5);
+        ecosystem[index].add_humus(1.0);
+        ecosystem[lower].set_height_of_bedrock(90.0);
+        let bedrock_height = ecosystem[index].get_bedrock_height();
+
+        // nothing happens without weathering
+        Events::apply_precomputed_freeze_thaw_event(&mut ecosystem, index, false);
+        assert_eq!(ecosystem[index].get_bedrock_height(), bedrock_height);
+
+        Events::apply_precomputed_freeze_thaw_event(&mut ecosystem, index, true);
+        let cell = &ecosystem[index];
+        let expected = bedrock_height - BEDROCK_FRACTURE_HEIGHT;
+        let actual = cell.get_bedrock_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        assert_eq!(cell.get_rock_height(), BEDROCK_FRACTURE_HEIGHT);
+        // humus creeps to the lowest neighbor without being lost
+        assert!(cell.get_humus_height() < 1.0);
+        let expected = 1.0;
+        let actual = cell.get_humus_height() + ecosystem[lower].get_humus_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+}
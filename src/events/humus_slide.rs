@@ -1,22 +1,20 @@
-use super::Events;
-use crate::{
-    constants,
-    ecology::{Cell, CellIndex, Ecosystem},
-};
+use super::{soil_moisture::estimate_saturation, Events};
+use crate::ecology::{provenance::TaggedLayer, Cell, CellIndex, Ecosystem};
 use rand::Rng;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 impl Events {
-    pub(crate) fn apply_humus_slide_event(
+    pub fn apply_humus_slide_event(
         ecosystem: &mut Ecosystem,
         index: CellIndex,
     ) -> Option<(Events, CellIndex)> {
-        let mut critical_neighbors: HashMap<CellIndex, f32> = HashMap::new();
-        let neighbors = Cell::get_neighbors(&index);
+        let mut critical_neighbors: BTreeMap<CellIndex, f32> = BTreeMap::new();
+        let neighbors = Cell::get_neighbors(&index, ecosystem.config.boundary);
+        let critical_angle = Self::compute_humus_critical_angle(ecosystem, index);
         for neighbor_index in neighbors.as_array().into_iter().flatten() {
             let slope = ecosystem.get_slope_between_points(index, neighbor_index);
             let angle = Ecosystem::get_angle(slope);
-            if angle >= constants::CRITICAL_ANGLE_HUMUS {
+            if angle >= critical_angle {
                 critical_neighbors.insert(neighbor_index, slope);
             }
         }
@@ -25,14 +23,13 @@ impl Events {
             return None;
         } else {
             // else randomly select neighbor weighted by slope
-            let mut neighbor_probabilities: HashMap<CellIndex, f32> = HashMap::new();
+            let mut neighbor_probabilities: BTreeMap<CellIndex, f32> = BTreeMap::new();
             let slope_sum: f32 = critical_neighbors.values().sum();
             for (neighbor, slope) in critical_neighbors {
                 let prob = slope / slope_sum;
                 neighbor_probabilities.insert(neighbor, prob);
             }
-            let mut rng = rand::thread_rng();
-            let mut rand: f32 = rng.gen();
+            let mut rand: f32 = ecosystem.rng.gen();
             for (neighbor, prob) in neighbor_probabilities {
                 rand -= prob;
                 if rand < 0.0 {
@@ -40,8 +37,9 @@ impl Events {
                     let humus_height =
                         Events::compute_humus_height_to_slide(ecosystem, index, neighbor);
                     // println!("Humus of height {humus_height} sliding from {index} to {neighbor}");
+                    ecosystem.track_transport(TaggedLayer::Humus, index, neighbor, humus_height);
                     let cell = &mut ecosystem[index];
-                    cell.remove_humus(humus_height);
+                    let humus_height = cell.remove_humus(humus_height);
 
                     let neighbor_cell = &mut ecosystem[neighbor];
                     neighbor_cell.add_humus(humus_height);
@@ -62,30 +60,41 @@ impl Events {
         let humus_height = cell.get_humus_height();
         if humus_height > 0.0 {
             let origin_pos = ecosystem.get_position_of_cell(&origin);
-            let target_pos = ecosystem.get_position_of_cell(&target);
-            let ideal_height = Events::compute_ideal_slide_height(
-                origin_pos,
-                target_pos,
-                constants::CRITICAL_ANGLE_HUMUS,
-            );
+            let target_pos = ecosystem.get_position_of_neighbor(&origin, &target);
+            let critical_angle = Self::compute_humus_critical_angle(ecosystem, origin);
+            let ideal_height =
+                Events::compute_ideal_slide_height(origin_pos, target_pos, critical_angle);
 
             let non_humus_height = cell.get_height() - humus_height;
 
             // simplifying assumption: half of the excess slides away
+            // nothing slides when the cell is already below the ideal height
             if non_humus_height >= ideal_height {
                 humus_height / 2.0
             } else {
-                ((non_humus_height + humus_height) - ideal_height) / 2.0
+                f32::max(
+                    ((non_humus_height + humus_height) - ideal_height) / 2.0,
+                    0.0,
+                )
             }
         } else {
             0.0
         }
     }
+
+    // saturated humus fails at a lower angle than dry humus
+    pub fn compute_humus_critical_angle(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        let critical_angles = &ecosystem.config.critical_angles;
+        let saturation = estimate_saturation(&ecosystem[index]);
+        critical_angles.humus * (1.0 - critical_angles.saturated_loss * saturation)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
+        config::BoundaryMode,
+        constants,
         ecology::{CellIndex, Ecosystem},
         events::Events,
     };
@@ -97,6 +106,8 @@ mod tests {
         let center = &mut ecosystem[CellIndex::new(3, 3)];
         center.set_height_of_bedrock(0.0);
         center.add_humus(1.0);
+        // dry humus holds at its full critical angle
+        center.soil_moisture = 0.0;
 
         let up = &mut ecosystem[CellIndex::new(3, 2)];
         up.set_height_of_bedrock(0.0);
@@ -124,4 +135,31 @@ mod tests {
             "Expected {expected}, actual {humus_height}"
         );
     }
+
+    #[test]
+    fn test_apply_humus_slide_event_across_torus_edge() {
+        // the neighbor across the edge of a torus is as close as a neighbor within the map,
+        // so as much humus slides to it
+        let slide_west = |x: usize, boundary: BoundaryMode| {
+            let mut ecosystem = Ecosystem::init();
+            ecosystem.config.boundary = boundary;
+            let origin = CellIndex::new(x, 3);
+            let target = CellIndex::new(
+                (x + constants::AREA_SIDE_LENGTH - 1) % constants::AREA_SIDE_LENGTH,
+                3,
+            );
+            ecosystem[origin].set_height_of_bedrock(0.0);
+            ecosystem[origin].add_humus(1.0);
+            ecosystem[origin].soil_moisture = 0.0;
+            ecosystem[target].set_height_of_bedrock(0.0);
+
+            let propagation = Events::apply_humus_slide_event(&mut ecosystem, origin);
+            assert_eq!(propagation, Some((Events::HumusSlide, target)));
+            ecosystem[target].get_humus_height()
+        };
+        let expected = slide_west(3, BoundaryMode::Open);
+        let actual = slide_west(0, BoundaryMode::Torus);
+        assert!(expected > 0.0);
+        assert_eq!(actual, expected);
+    }
 }
@@ -0,0 +1,426 @@
+// HYDROLOGY
+// surface water is updated once per time step over the whole grid:
+// 1) rainfall that gets through the canopy and does not soak into the ground becomes surface water,
+//    and springs add their flow
+// 2) water is routed downhill over the water surface, filling depressions into ponds and lakes
+// 3) standing water evaporates based on the monthly temperatures and some soaks into the soil
+// how much rain and standing water soaks in depends on the conductivity of the top of the ground,
+// so sand takes in nearly everything while bare bedrock sheds all of it
+// cells below the sea level of the config are kept flooded up to it, taking in the water routed to them
+
+// % of rainfall that becomes surface runoff on humus
+pub const RUNOFF_COEFFICIENT: f32 = 0.1;
+// hydraulic conductivity (in mm per hour) of each material
+// https://en.wikipedia.org/wiki/Hydraulic_conductivity
+const HUMUS_CONDUCTIVITY: f32 = 10.0;
+const SAND_CONDUCTIVITY: f32 = 100.0;
+const ROCK_CONDUCTIVITY: f32 = 50.0;
+const BEDROCK_CONDUCTIVITY: f32 = 0.0;
+// conductivity of the humus, sand, and rock layers relative to humus, in the order of the soil profile
+pub const SOIL_LAYER_CONDUCTIVITIES: [f32; 3] = [
+    1.0,
+    SAND_CONDUCTIVITY / HUMUS_CONDUCTIVITY,
+    ROCK_CONDUCTIVITY / HUMUS_CONDUCTIVITY,
+];
+// depth (in m) of the top of the ground whose conductivity decides how much water soaks in
+const INFILTRATION_DEPTH: f32 = 0.1;
+// number of routing passes per time step
+const FLOW_ITERATIONS: usize = 50;
+// open water evaporation in meters per month per degree celsius above freezing
+// roughly 0.5 m per year for a temperate climate
+const EVAPORATION_RATE: f32 = 0.004;
+// % of standing water that infiltrates into the soil each time step
+const INFILTRATION_RATE: f32 = 0.1;
+// standing water shallower than this (in m) is considered dry
+const MIN_WATER_HEIGHT: f32 = 1e-4;
+
+use super::interception::partition_rainfall;
+use crate::{
+    config::{BoundaryMode, Climate},
+    constants,
+    ecology::{Cell, CellIndex, Ecosystem},
+    units::{Meters, Millimeters},
+};
+
+pub fn update_surface_water(ecosystem: &mut Ecosystem) {
+    let months = ecosystem.get_months_of_time_step();
+    let rainfall = ecosystem.config.climate.get_rainfall(&months);
+    let evaporation_height = compute_evaporation(&ecosystem.config.climate, &months);
+    let sea_level = ecosystem.config.sea.map(|sea| sea.level);
+
+    let ground_heights = ecosystem.get_heights();
+    let mut water_heights = vec![0.0; constants::NUM_CELLS];
+    for (i, (ground, water)) in ground_heights
+        .iter()
+        .zip(water_heights.iter_mut())
+        .enumerate()
+    {
+        let index = CellIndex::get_from_flat_index(i);
+        let cell = &ecosystem[index];
+        let rainfall = ecosystem.get_local_rainfall(index, rainfall);
+        let runoff = Millimeters(partition_rainfall(cell, rainfall).runoff).to_meters();
+        *water = cell.get_water_height() + runoff.0;
+        if let Some(sea_level) = sea_level {
+            *water = f32::max(*water, sea_level - *ground);
+        }
+    }
+    add_spring_water(ecosystem, &months, &mut water_heights);
+
+    let flow = route_surface_water(
+        &ground_heights,
+        &mut water_heights,
+        ecosystem.config.boundary,
+    );
+
+    let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+    for (i, water_height) in water_heights.into_iter().enumerate() {
+        let index = CellIndex::get_from_flat_index(i);
+        let cell = &mut ecosystem[index];
+        let previous_water_height = cell.get_water_height();
+        let mut water_height = f32::max(water_height - evaporation_height, 0.0);
+        let sea_depth = sea_level.map_or(0.0, |sea_level| sea_level - ground_heights[i]);
+        if sea_depth > 0.0 {
+            // the sea does not rise with the water running into it
+            water_height = sea_depth;
+        } else {
+            // some standing water soaks into the soil
+            let rate = INFILTRATION_RATE * compute_relative_conductivity(cell);
+            let infiltrated = water_height * f32::min(rate, 1.0);
+            water_height -= infiltrated;
+            cell.soil_moisture += Meters(infiltrated).over_cell().to_liters().0;
+        }
+
+        cell.remove_water(cell.get_water_height());
+        if water_height > MIN_WATER_HEIGHT {
+            cell.add_water(water_height);
+        }
+        cell.water_flow = flow[i] * cell_area;
+        if cell.get_water_height() != previous_water_height {
+            ecosystem.changed_cells.insert(index);
+        }
+    }
+}
+
+// returns hydraulic conductivity of the top of the ground relative to humus,
+// averaged over the infiltration depth with bedrock making up what the soil does not cover
+pub fn compute_relative_conductivity(cell: &Cell) -> f32 {
+    let mut remaining = INFILTRATION_DEPTH;
+    let mut conductivity = 0.0;
+    for (height, layer_conductivity) in cell
+        .get_soil_layer_heights()
+        .into_iter()
+        .zip(SOIL_LAYER_CONDUCTIVITIES)
+    {
+        let depth = f32::min(height, remaining);
+        conductivity += depth * layer_conductivity;
+        remaining -= depth;
+    }
+    conductivity += remaining * BEDROCK_CONDUCTIVITY / HUMUS_CONDUCTIVITY;
+    conductivity / INFILTRATION_DEPTH
+}
+
+// returns % of the rainfall reaching the ground of the cell that runs off over the surface
+pub fn compute_runoff_coefficient(cell: &Cell) -> f32 {
+    let conductivity = compute_relative_conductivity(cell);
+    if conductivity <= 0.0 {
+        1.0
+    } else {
+        f32::min(RUNOFF_COEFFICIENT / conductivity, 1.0)
+    }
+}
+
+// moves water towards neighbors with a lower water surface until the surface levels out
+// returns the accumulated outflow height of each cell
+fn route_surface_water(
+    ground_heights: &[f32],
+    water_heights: &mut [f32],
+    boundary: BoundaryMode,
+) -> Vec<f32> {
+    let mut flow = vec![0.0; constants::NUM_CELLS];
+    let mut deltas = vec![0.0; constants::NUM_CELLS];
+    for _ in 0..FLOW_ITERATIONS {
+        deltas.iter_mut().for_each(|delta| *delta = 0.0);
+        for i in 0..constants::NUM_CELLS {
+            let water = water_heights[i];
+            if water <= 0.0 {
+                continue;
+            }
+            let surface = ground_heights[i] + water;
+            let index = CellIndex::get_from_flat_index(i);
+            let neighbors = Cell::get_neighbors(&index, boundary);
+
+            // height difference to each lower neighbor
+            let mut differences = [(None, 0.0); 8];
+            let mut difference_sum = 0.0;
+            let mut max_difference: f32 = 0.0;
+            for (j, neighbor) in neighbors.as_array().into_iter().enumerate() {
+                let difference = if let Some(neighbor) = neighbor {
+                    let n = neighbor.get_flat_index();
+                    surface - (ground_heights[n] + water_heights[n])
+                } else if boundary == BoundaryMode::Open {
+                    // water drains off the edges of the map as if the terrain continued flat
+                    water
+                } else {
+                    // a wall holds the water in
+                    0.0
+                };
+                if difference > 0.0 {
+                    differences[j] = (neighbor, difference);
+                    difference_sum += difference;
+                    max_difference = max_difference.max(difference);
+                }
+            }
+            if difference_sum == 0.0 {
+                continue;
+            }
+
+            // move at most half of the largest difference so water does not oscillate
+            let outflow = f32::min(water, max_difference / 2.0);
+            deltas[i] -= outflow;
+            flow[i] += outflow;
+            for (neighbor, difference) in differences {
+                if let Some(neighbor) = neighbor {
+                    deltas[neighbor.get_flat_index()] += outflow * difference / difference_sum;
+                }
+            }
+        }
+        for (water, delta) in water_heights.iter_mut().zip(deltas.iter()) {
+            *water = f32::max(*water + delta, 0.0);
+        }
+    }
+    flow
+}
+
+// adds the water the springs of the config give off over the given months to their cells
+pub fn add_spring_water(ecosystem: &Ecosystem, months: &[(usize, f32)], water_heights: &mut [f32]) {
+    let num_months: f32 = months.iter().map(|(_, fraction)| fraction).sum();
+    let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+    for spring in &ecosystem.config.springs {
+        let index = CellIndex::new(spring.cell[0], spring.cell[1]);
+        water_heights[index.get_flat_index()] += spring.flow * num_months / cell_area;
+    }
+}
+
+// returns height of open water evaporated over the given months, weighted by the fraction of each month
+fn compute_evaporation(climate: &Climate, months: &[(usize, f32)]) -> f32 {
+    months
+        .iter()
+        .map(|(month, fraction)| {
+            f32::max(climate.monthly_temperatures[*month], 0.0) * EVAPORATION_RATE * fraction
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{
+        compute_evaporation, compute_runoff_coefficient, route_surface_water, update_surface_water,
+        RUNOFF_COEFFICIENT,
+    };
+    use crate::{
+        config::{BoundaryMode, Climate, Sea, Spring, TimeResolution},
+        constants,
+        ecology::{Cell, CellIndex, Ecosystem},
+    };
+
+    #[test]
+    fn test_compute_runoff_coefficient() {
+        // bare bedrock sheds everything
+        let mut cell = Cell::init();
+        assert_eq!(compute_runoff_coefficient(&cell), 1.0);
+
+        // a thin layer of humus takes in some of it
+        cell.add_humus(0.05);
+        let thin = compute_runoff_coefficient(&cell);
+        assert!(thin < 1.0 && thin > RUNOFF_COEFFICIENT, "{thin}");
+
+        // and a deep one the usual share
+        cell.add_humus(1.0);
+        let actual = compute_runoff_coefficient(&cell);
+        assert!(
+            approx_eq!(f32, actual, RUNOFF_COEFFICIENT, epsilon = 0.0001),
+            "Expected {RUNOFF_COEFFICIENT}, actual {actual}"
+        );
+
+        // sand drains faster than humus
+        let mut cell = Cell::init();
+        cell.add_sand(1.0);
+        let sand = compute_runoff_coefficient(&cell);
+        assert!(sand < RUNOFF_COEFFICIENT, "{sand}");
+    }
+
+    #[test]
+    fn test_route_surface_water_fills_depression() {
+        let mut ground_heights = vec![100.0; constants::NUM_CELLS];
+        let pit = CellIndex::new(50, 50);
+        let pit_flat = pit.x + pit.y * constants::AREA_SIDE_LENGTH;
+        ground_heights[pit_flat] = 99.0;
+
+        // water next to the pit flows into it
+        let mut water_heights = vec![0.0; constants::NUM_CELLS];
+        let neighbor_flat = pit_flat + 1;
+        water_heights[neighbor_flat] = 0.5;
+
+        let flow = route_surface_water(&ground_heights, &mut water_heights, BoundaryMode::Open);
+        // most of the water ends up in the pit, with a thin film left on the flat ground
+        let actual = water_heights[pit_flat];
+        assert!(actual > 0.4, "Expected at least 0.4, actual {actual}");
+        let actual: f32 = water_heights.iter().sum();
+        assert!(
+            approx_eq!(f32, actual, 0.5, epsilon = 0.001),
+            "Expected 0.5, actual {actual}"
+        );
+        assert!(flow[neighbor_flat] > 0.0);
+    }
+
+    #[test]
+    fn test_route_surface_water_conserves_interior_water() {
+        let mut ground_heights = vec![0.0; constants::NUM_CELLS];
+        for (i, height) in ground_heights.iter_mut().enumerate() {
+            let index = CellIndex::get_from_flat_index(i);
+            // bowl shape keeps water away from the edges
+            let dx = index.x as f32 - 50.0;
+            let dy = index.y as f32 - 50.0;
+            *height = (dx * dx + dy * dy).sqrt();
+        }
+        let mut water_heights = vec![0.0; constants::NUM_CELLS];
+        water_heights[45 + 45 * constants::AREA_SIDE_LENGTH] = 1.0;
+        water_heights[55 + 52 * constants::AREA_SIDE_LENGTH] = 2.0;
+
+        route_surface_water(&ground_heights, &mut water_heights, BoundaryMode::Open);
+        let actual: f32 = water_heights.iter().sum();
+        assert!(
+            approx_eq!(f32, actual, 3.0, epsilon = 0.001),
+            "Expected 3.0, actual {actual}"
+        );
+        // water collects at the bottom of the bowl
+        assert!(water_heights[50 + 50 * constants::AREA_SIDE_LENGTH] > 0.0);
+    }
+
+    #[test]
+    fn test_route_surface_water_boundary() {
+        let ground_heights = vec![0.0; constants::NUM_CELLS];
+        let mut initial = vec![0.0; constants::NUM_CELLS];
+        initial[0] = 1.0;
+
+        // water at the corner drains off an open map
+        let mut water_heights = initial.clone();
+        route_surface_water(&ground_heights, &mut water_heights, BoundaryMode::Open);
+        let actual: f32 = water_heights.iter().sum();
+        assert!(actual < 0.5, "Expected less than 0.5, actual {actual}");
+
+        // but is held in by walls, or spreads across the edges of a torus
+        for boundary in [BoundaryMode::Reflective, BoundaryMode::Torus] {
+            let mut water_heights = initial.clone();
+            route_surface_water(&ground_heights, &mut water_heights, boundary);
+            let actual: f32 = water_heights.iter().sum();
+            assert!(
+                approx_eq!(f32, actual, 1.0, epsilon = 0.001),
+                "Expected 1.0, actual {actual}"
+            );
+        }
+        let mut water_heights = initial;
+        route_surface_water(&ground_heights, &mut water_heights, BoundaryMode::Torus);
+        assert!(water_heights[constants::NUM_CELLS - 1] > 0.0);
+    }
+
+    #[test]
+    fn test_compute_evaporation() {
+        let climate = Climate {
+            monthly_temperatures: [-5.0, 0.0, 10.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 5.0],
+            ..Climate::default()
+        };
+        let actual = compute_evaporation(&climate, &TimeResolution::Year.get_months(1));
+        let expected = 15.0 * super::EVAPORATION_RATE;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // a day of the third month only evaporates a day's worth
+        let actual = compute_evaporation(&climate, &TimeResolution::Day.get_months(60));
+        let expected = 10.0 * super::EVAPORATION_RATE / 31.0;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_update_surface_water() {
+        let mut ecosystem = Ecosystem::init();
+        let pit = CellIndex::new(50, 50);
+        ecosystem[pit].remove_bedrock(5.0);
+        ecosystem[pit].add_humus(1.0);
+        let moisture = ecosystem[pit].soil_moisture;
+
+        update_surface_water(&mut ecosystem);
+
+        // rain collects in the pit while flat terrain drains off the map
+        let cell = &ecosystem[pit];
+        assert!(cell.get_water_height() > 0.0);
+        assert!(cell.soil_moisture > moisture);
+        assert_eq!(ecosystem[CellIndex::new(0, 0)].get_water_height(), 0.0);
+        // terrain height does not include water
+        assert_eq!(cell.get_height(), constants::DEFAULT_BEDROCK_HEIGHT - 4.0);
+    }
+
+    #[test]
+    fn test_update_surface_water_spring() {
+        // valley sloping away from the spring
+        let init_valley_ecosystem = || {
+            let mut ecosystem = Ecosystem::init();
+            for y in 0..51 {
+                ecosystem[CellIndex::new(50, y)].remove_bedrock(1.0 + (50 - y) as f32 * 0.1);
+            }
+            ecosystem
+        };
+        let source = CellIndex::new(50, 50);
+        let downstream = CellIndex::new(50, 45);
+        let mut ecosystem = init_valley_ecosystem();
+        update_surface_water(&mut ecosystem);
+        let without_spring = ecosystem[downstream].water_flow;
+
+        let mut ecosystem = init_valley_ecosystem();
+        ecosystem.config.springs = vec![Spring {
+            cell: [source.x, source.y],
+            flow: 1000.0,
+        }];
+        update_surface_water(&mut ecosystem);
+
+        // the spring water runs down the valley
+        let actual = ecosystem[downstream].water_flow;
+        assert!(
+            actual > without_spring,
+            "Expected more than {without_spring}, actual {actual}"
+        );
+        assert!(ecosystem[source].water_flow > 0.0);
+    }
+
+    #[test]
+    fn test_update_surface_water_sea() {
+        let mut ecosystem = Ecosystem::init();
+        let sea = CellIndex::new(50, 20);
+        for x in 0..constants::AREA_SIDE_LENGTH {
+            for y in 0..50 {
+                ecosystem[CellIndex::new(x, y)].remove_bedrock(5.0);
+            }
+        }
+        let sea_level = constants::DEFAULT_BEDROCK_HEIGHT - 1.0;
+        ecosystem.config.sea = Some(Sea {
+            level: sea_level,
+            ..Sea::default()
+        });
+
+        update_surface_water(&mut ecosystem);
+
+        // the sea is filled to its level and stays there as the land drains into it
+        let expected = sea_level - ecosystem[sea].get_height();
+        assert_eq!(ecosystem[sea].get_water_height(), expected);
+
+        update_surface_water(&mut ecosystem);
+        assert_eq!(ecosystem[sea].get_water_height(), expected);
+    }
+}
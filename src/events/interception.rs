@@ -0,0 +1,83 @@
+// CANOPY INTERCEPTION
+// rainfall is split by the canopy of each cell before it reaches the ground
+// 1) leaves and branches catch a share of the rainfall that grows with the leaf area of the trees and bushes
+// 2) most of the caught water evaporates from the canopy, but part of it runs down the stems
+//    as stemflow and soaks into the soil at their base
+// 3) the rest falls through the canopy as throughfall, of which a share set by the conductivity
+//    of the ground runs off over the surface and the rest soaks into the soil
+// so forests shed less surface water, and erode less, than bare ground after a clearcut or a fire
+
+// % of rainfall caught by the densest canopy
+const MAX_INTERCEPTION: f32 = 0.3;
+// % of the caught rainfall that reaches the soil as stemflow instead of evaporating
+const STEMFLOW_FRACTION: f32 = 0.2;
+
+use super::{hydrology::compute_runoff_coefficient, vegetation};
+use crate::ecology::Cell;
+
+// mm of rainfall that runs off over the surface and that soaks into the soil of the cell
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RainfallPartition {
+    pub runoff: f32,
+    pub infiltration: f32,
+}
+
+// splits mm of rainfall falling on the cell
+pub fn partition_rainfall(cell: &Cell, rainfall: f32) -> RainfallPartition {
+    let intercepted = rainfall * compute_interception_fraction(cell);
+    let stemflow = intercepted * STEMFLOW_FRACTION;
+    let throughfall = rainfall - intercepted;
+    let runoff_coefficient = compute_runoff_coefficient(cell);
+    RainfallPartition {
+        runoff: throughfall * runoff_coefficient,
+        infiltration: throughfall * (1.0 - runoff_coefficient) + stemflow,
+    }
+}
+
+// returns % of rainfall caught by the canopy, which covers the ground like it shades it
+pub fn compute_interception_fraction(cell: &Cell) -> f32 {
+    let leaf_area_index = vegetation::estimate_leaf_area_index(cell);
+    MAX_INTERCEPTION * (1.0 - vegetation::compute_light_transmission(leaf_area_index))
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{partition_rainfall, MAX_INTERCEPTION, STEMFLOW_FRACTION};
+    use crate::{
+        ecology::{Cell, Trees},
+        events::hydrology::RUNOFF_COEFFICIENT,
+    };
+
+    #[test]
+    fn test_partition_rainfall() {
+        // bare bedrock sheds all rainfall
+        let mut cell = Cell::init();
+        let actual = partition_rainfall(&cell, 100.0);
+        assert_eq!(actual.runoff, 100.0);
+
+        // bare humus sheds the usual share
+        cell.add_humus(1.0);
+        let actual = partition_rainfall(&cell, 100.0);
+        let expected = 100.0 * RUNOFF_COEFFICIENT;
+        assert!(
+            approx_eq!(f32, actual.runoff, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {}",
+            actual.runoff
+        );
+        let bare = actual;
+
+        // a forest catches part of it, sheds less, and loses what evaporates from the canopy
+        cell.set_trees(Trees::with_plants(0, 20, 400.0, 600.0));
+        let actual = partition_rainfall(&cell, 100.0);
+        assert!(
+            actual.runoff < bare.runoff,
+            "Expected less than {}, actual {}",
+            bare.runoff,
+            actual.runoff
+        );
+        let lost = 100.0 - actual.runoff - actual.infiltration;
+        assert!(lost > 0.0 && lost < 100.0 * MAX_INTERCEPTION * (1.0 - STEMFLOW_FRACTION));
+    }
+}
@@ -0,0 +1,271 @@
+// LANDSLIDE
+// saturated soil on a steep slope fails as a whole, unlike the slides of single materials
+// 1) water in the pores of the loose material lowers the angle at which it fails
+// 2) all the loose material of the failing cell is mobilized together, leaving a bare scar
+// 3) the mass runs downhill along the steepest descent, scouring the cells it crosses,
+//    until the slope is too gentle for it to keep moving
+// 4) it spreads over the cell where it stops and its neighbors, burying their vegetation
+
+// angle (in degrees) at which dry loose material fails
+const DRY_FAILURE_ANGLE: f32 = 40.0;
+// how much the failure angle drops when the loose material is saturated
+const PORE_PRESSURE_FACTOR: f32 = 0.5;
+// the moving mass stops on slopes gentler than this angle (in degrees)
+const RUNOUT_ANGLE: f32 = 10.0;
+// maximum number of cells the mass runs over
+pub const MAX_RUNOUT_LENGTH: usize = 20;
+// % of the loose material of the cells along the runout path that is carried away
+const ENTRAINMENT_RATE: f32 = 0.2;
+// height (in m) of deposit that buries bushes and grasses
+const BURIAL_HEIGHT: f32 = 0.2;
+// height (in m) of deposit that buries trees
+const TREE_BURIAL_HEIGHT: f32 = 1.0;
+
+use super::{soil_moisture::estimate_saturation, Events};
+use crate::ecology::{
+    provenance::{scale, TaggedLayer, Tags},
+    Cell, CellIndex, Ecosystem,
+};
+
+// heights (in m) of the loose material moved by the landslide, and the biomass and water it carries
+#[derive(Debug, Default, PartialEq)]
+struct Mass {
+    humus: f32,
+    sand: f32,
+    // of each rock size
+    rocks: [f32; 3],
+    dead_vegetation: f32,
+    soil_moisture: f32,
+    // regions the sand and humus came from, when provenance is tracked
+    sand_tags: Tags,
+    humus_tags: Tags,
+}
+
+impl Events {
+    pub fn apply_landslide_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+    ) -> Option<(Events, CellIndex)> {
+        let (first, angle) = Self::get_steepest_descent(ecosystem, index, &[])?;
+        if angle < Self::compute_failure_angle(&ecosystem[index]) {
+            return None;
+        }
+
+        // the failing cell is scoured down to bedrock
+        let mut mass = Mass::default();
+        Self::entrain(ecosystem, index, &mut mass, 1.0);
+
+        // the mass runs downhill until the slope is too gentle, never back into its own path
+        let mut path = vec![index];
+        let mut end = first;
+        for _ in 1..MAX_RUNOUT_LENGTH {
+            match Self::get_steepest_descent(ecosystem, end, &path) {
+                Some((next, angle)) if angle >= RUNOUT_ANGLE => {
+                    // cells the mass runs through are scoured
+                    Self::entrain(ecosystem, end, &mut mass, ENTRAINMENT_RATE);
+                    path.push(end);
+                    end = next;
+                }
+                _ => break,
+            }
+        }
+
+        Self::deposit(ecosystem, end, &mass);
+
+        // does not propagate
+        None
+    }
+
+    // returns the failure angle (in degrees) of the loose material of the cell
+    fn compute_failure_angle(cell: &Cell) -> f32 {
+        let loose_height =
+            cell.get_humus_height() + cell.get_sand_height() + cell.get_rock_height();
+        if loose_height == 0.0 {
+            // bare bedrock does not slide
+            return 90.0;
+        }
+        DRY_FAILURE_ANGLE * (1.0 - PORE_PRESSURE_FACTOR * estimate_saturation(cell))
+    }
+
+    // returns the lowest neighbor not in `excluded` and the angle (in degrees) down to it, if any
+    pub fn get_steepest_descent(
+        ecosystem: &Ecosystem,
+        index: CellIndex,
+        excluded: &[CellIndex],
+    ) -> Option<(CellIndex, f32)> {
+        let mut steepest = None;
+        let mut max_slope = 0.0;
+        for neighbor in Cell::get_neighbors(&index, ecosystem.config.boundary)
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            if excluded.contains(&neighbor) {
+                continue;
+            }
+            let slope = ecosystem.get_slope_between_points(index, neighbor);
+            if slope > max_slope {
+                max_slope = slope;
+                steepest = Some(neighbor);
+            }
+        }
+        steepest.map(|neighbor| (neighbor, Ecosystem::get_angle(f32::min(max_slope, 1.0))))
+    }
+
+    // moves the given % of the loose material of the cell into the mass, killing its vegetation
+    fn entrain(ecosystem: &mut Ecosystem, index: CellIndex, mass: &mut Mass, rate: f32) {
+        ecosystem.mark_dirty(index);
+        let cell = &mut ecosystem[index];
+        Self::kill_trees(cell);
+        Self::kill_bushes(cell);
+        Self::kill_grasses(cell);
+
+        let humus = cell.get_humus_height() * rate;
+        let sand = cell.get_sand_height() * rate;
+        let dead_vegetation = cell.get_dead_vegetation_biomass() * rate;
+        let soil_moisture = cell.soil_moisture * rate;
+        let humus_tags = ecosystem.take_provenance(TaggedLayer::Humus, index, humus);
+        let sand_tags = ecosystem.take_provenance(TaggedLayer::Sand, index, sand);
+        let cell = &mut ecosystem[index];
+        cell.remove_humus(humus);
+        cell.remove_sand(sand);
+        let rocks = cell.take_rocks(cell.get_rock_height() * rate);
+        cell.remove_dead_vegetation(dead_vegetation);
+        cell.soil_moisture -= soil_moisture;
+
+        mass.humus += humus;
+        mass.sand += sand;
+        mass.rocks = std::array::from_fn(|k| mass.rocks[k] + rocks[k]);
+        mass.dead_vegetation += dead_vegetation;
+        mass.soil_moisture += soil_moisture;
+        mass.sand_tags = std::array::from_fn(|r| mass.sand_tags[r] + sand_tags[r]);
+        mass.humus_tags = std::array::from_fn(|r| mass.humus_tags[r] + humus_tags[r]);
+    }
+
+    // spreads the mass evenly over the cell and its neighbors, burying their vegetation
+    fn deposit(ecosystem: &mut Ecosystem, index: CellIndex, mass: &Mass) {
+        let zone: Vec<CellIndex> = std::iter::once(index)
+            .chain(
+                Cell::get_neighbors(&index, ecosystem.config.boundary)
+                    .as_array()
+                    .into_iter()
+                    .flatten(),
+            )
+            .collect();
+        let share = 1.0 / zone.len() as f32;
+        let height = (mass.humus + mass.sand + mass.rocks.iter().sum::<f32>()) * share;
+
+        for index in zone {
+            ecosystem.mark_dirty(index);
+            ecosystem.give_provenance(TaggedLayer::Sand, index, &scale(&mass.sand_tags, share));
+            ecosystem.give_provenance(TaggedLayer::Humus, index, &scale(&mass.humus_tags, share));
+            let cell = &mut ecosystem[index];
+            if height >= BURIAL_HEIGHT {
+                Self::kill_bushes(cell);
+                Self::kill_grasses(cell);
+            }
+            if height >= TREE_BURIAL_HEIGHT {
+                Self::kill_trees(cell);
+            }
+            // rocks settle first and humus ends up on top
+            cell.add_rock_heights(mass.rocks.map(|rocks| rocks * share));
+            cell.add_sand(mass.sand * share);
+            cell.add_humus(mass.humus * share);
+            cell.add_dead_vegetation(mass.dead_vegetation * share);
+            cell.soil_moisture += mass.soil_moisture * share;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use crate::{
+        constants,
+        ecology::{CellIndex, Ecosystem, Grasses, Trees},
+        events::{landslide::DRY_FAILURE_ANGLE, Events},
+    };
+
+    // a slope rising towards x = 0 with a flat valley floor from x = 10
+    fn init_hillside(humus_height: f32) -> Ecosystem {
+        let mut ecosystem = Ecosystem::init();
+        for x in 0..constants::AREA_SIDE_LENGTH {
+            for y in 0..constants::AREA_SIDE_LENGTH {
+                let cell = &mut ecosystem[CellIndex::new(x, y)];
+                cell.set_height_of_bedrock(100.0 + 0.5 * f32::max(10.0 - x as f32, 0.0));
+                cell.add_humus(humus_height);
+            }
+        }
+        ecosystem
+    }
+
+    fn sum_loose_material(ecosystem: &Ecosystem) -> f32 {
+        ecosystem
+            .cells
+            .iter()
+            .map(|cell| cell.get_humus_height() + cell.get_sand_height() + cell.get_rock_height())
+            .sum()
+    }
+
+    #[test]
+    fn test_compute_failure_angle() {
+        let mut ecosystem = init_hillside(1.0);
+        let cell = &mut ecosystem[CellIndex::new(5, 5)];
+        cell.soil_moisture = 0.0;
+        assert_eq!(Events::compute_failure_angle(cell), DRY_FAILURE_ANGLE);
+
+        // saturated soil fails at a gentler angle
+        cell.soil_moisture = 1.0E6;
+        let actual = Events::compute_failure_angle(cell);
+        let expected = DRY_FAILURE_ANGLE / 2.0;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // bare bedrock never fails
+        cell.remove_humus(1.0);
+        assert_eq!(Events::compute_failure_angle(cell), 90.0);
+    }
+
+    #[test]
+    fn test_apply_landslide_event() {
+        let mut ecosystem = init_hillside(1.0);
+        let source = CellIndex::new(3, 50);
+        let end = CellIndex::new(10, 50);
+        ecosystem[end].grasses = Some(Grasses {
+            coverage_density: 1.0,
+            green_fraction: 1.0,
+        });
+        ecosystem[source].trees = vec![Trees::with_plants(0, 5, 50.0, 100.0)];
+        let loose_material = sum_loose_material(&ecosystem);
+
+        // dry soil holds
+        ecosystem[source].soil_moisture = 0.0;
+        Events::apply_landslide_event(&mut ecosystem, source);
+        assert_eq!(ecosystem[source].get_humus_height(), 1.0);
+
+        // saturated soil fails and leaves a bare scar
+        ecosystem[source].soil_moisture = 1.0E6;
+        Events::apply_landslide_event(&mut ecosystem, source);
+        let scar = &ecosystem[source];
+        assert_eq!(scar.get_humus_height(), 0.0);
+        assert!(scar.trees.is_empty());
+        assert_eq!(scar.get_dead_vegetation_biomass(), 0.0);
+
+        // the runout path is scoured and the deposit at the foot of the slope buries the grass
+        assert!(ecosystem[CellIndex::new(6, 50)].get_humus_height() < 1.0);
+        assert!(ecosystem[end].get_humus_height() > 1.0);
+        assert!(ecosystem[end].grasses.is_none());
+        assert!(ecosystem[end].get_dead_vegetation_biomass() > 0.0);
+
+        // without losing any material
+        let expected = loose_material;
+        let actual = sum_loose_material(&ecosystem);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.01),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+}
@@ -1,36 +1,49 @@
 // LIGHTNING
-// based on ~10 lightning strikes per km per year
-// https://www.sciencedirect.com/science/article/pii/S0169555X13003929
-const DESIRED_MAX_STRIKES: f32 = 20.0; // strikes per squar kilometer
-const MAX_LIGHTNING_PROBABILITY: f32 =
-    constants::AREA * DESIRED_MAX_STRIKES / constants::NUM_CELLS as f32;
+// strikes per year are set by the climate, and fall under the storms of the time step if there are any
 const LIGHTNING_BEDROCK_DISPLACEMENT_VOLUME: f32 = 4.0; // m^3
 
-use super::Events;
+// chance that a strike on a fully fueled, dry cell starts a fire
+const LIGHTNING_IGNITION_PROBABILITY: f32 = 0.5;
+
+use super::{fire_danger, Events};
 use crate::{
     constants,
-    ecology::{Cell, CellIndex, Ecosystem},
+    ecology::{Cell, CellIndex, Ecosystem, RockSize},
 };
 use rand::Rng;
 
 impl Events {
-    pub(crate) fn apply_lightning_event(
+    pub fn apply_lightning_event(
         ecosystem: &mut Ecosystem,
         index: CellIndex,
     ) -> Option<(Events, CellIndex)> {
         let strike_probability = Self::compute_lightning_damage_probability(ecosystem, index);
-        Self::apply_lightning_event_helper(ecosystem, index, strike_probability)
+        Self::apply_lightning_event_helper(
+            ecosystem,
+            index,
+            strike_probability,
+            LIGHTNING_IGNITION_PROBABILITY,
+        )
+    }
+
+    // applies a lightning event whose strike was already sampled by the scheduler
+    pub fn apply_scheduled_lightning_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+    ) -> Option<(Events, CellIndex)> {
+        Self::apply_lightning_event_helper(ecosystem, index, 1.0, LIGHTNING_IGNITION_PROBABILITY)
     }
 
     fn apply_lightning_event_helper(
         ecosystem: &mut Ecosystem,
         index: CellIndex,
         strike_probability: f32,
+        ignition_probability: f32,
     ) -> Option<(Events, CellIndex)> {
-        let mut rng = rand::thread_rng();
-        let rand: f32 = rng.gen();
+        let rand: f32 = ecosystem.rng.gen();
         if rand < strike_probability {
             // println!("Lightning at {index}");
+            let boundary = ecosystem.config.boundary;
             let cell = &mut ecosystem[index];
 
             // kill all vegetation in the cell
@@ -39,35 +52,44 @@ impl Events {
             Self::kill_grasses(cell);
 
             // destroy some bedrock and scatter as rocks and sand to nearby cells
-            let lost_height = LIGHTNING_BEDROCK_DISPLACEMENT_VOLUME
-                / (constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH);
-            cell.remove_bedrock(lost_height);
+            // a cell with thin bedrock loses less than the full volume
+            let lost_height = cell.remove_bedrock(
+                LIGHTNING_BEDROCK_DISPLACEMENT_VOLUME
+                    / (constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH),
+            );
 
-            // simplifying assumption 1: half of the volume becomes rock and the other half sand
+            // simplifying assumption 1: half of the volume becomes boulders and the other half sand
             // simplifying assumption 2: distribute volume evenly to 8 neighbors and cell (instead of being based on slope and relative elevation)
-            let neighbors = Cell::get_neighbors(&index);
+            let neighbors = Cell::get_neighbors(&index, boundary);
             let num_affected_cells = neighbors.len() + 1;
-            let volume_per_cell = LIGHTNING_BEDROCK_DISPLACEMENT_VOLUME / num_affected_cells as f32;
-            let height_per_cell =
-                volume_per_cell / (constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH);
+            let height_per_cell = lost_height / num_affected_cells as f32;
 
             // add to cell
-            cell.add_rocks(height_per_cell / 2.0);
+            cell.add_rocks_of_size(RockSize::Boulders, height_per_cell / 2.0);
             cell.add_sand(height_per_cell / 2.0);
 
             // add to neighbors
             for index in neighbors.as_array().into_iter().flatten() {
                 let neighbor = &mut ecosystem[index];
-                neighbor.add_rocks(height_per_cell / 2.0);
+                neighbor.add_rocks_of_size(RockSize::Boulders, height_per_cell / 2.0);
                 neighbor.add_sand(height_per_cell / 2.0);
             }
+
+            // strike may ignite the remaining fuel depending on the fire danger of the cell
+            let ignition_probability = ignition_probability
+                * Self::estimate_fuel_load(&ecosystem[index])
+                * fire_danger::estimate_fire_danger(ecosystem, index);
+            let rand: f32 = ecosystem.rng.gen();
+            if rand < ignition_probability {
+                return Some((Events::Fire, index));
+            }
         }
 
         // does not propagate
         None
     }
 
-    fn compute_lightning_damage_probability(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+    pub fn compute_lightning_damage_probability(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
         //l(p)=k_L min(1,e^(k_lc * (∇E(p)−k_ls))
         // k_L is maximum probability
         // k_lc is scaling factor
@@ -79,7 +101,12 @@ impl Events {
         let min_curve = 4.0;
         let exp = scaling_factor * ((-curvature) - min_curve);
         // println!("exp {exp}");
-        MAX_LIGHTNING_PROBABILITY * f32::min(1.0, (std::f32::consts::E).powf(exp))
+        // strikes are counted per year
+        let max_probability = constants::AREA * ecosystem.config.climate.lightning_strikes
+            / constants::NUM_CELLS as f32;
+        max_probability
+            * f32::min(1.0, (std::f32::consts::E).powf(exp))
+            * ecosystem.get_lightning_share(index)
     }
 }
 
@@ -103,23 +130,33 @@ mod tests {
         test_lightning_event_helper(index);
     }
 
+    #[test]
+    fn test_lightning_ignition() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(2, 2);
+        let result = Events::apply_lightning_event_helper(&mut ecosystem, index, 1.0, 1.0);
+        // no fuel to ignite
+        assert!(result.is_none());
+
+        let trees = Trees::with_plants(0, 10, 100.0, 100.0);
+        ecosystem[index].trees = vec![trees];
+        let result = Events::apply_lightning_event_helper(&mut ecosystem, index, 1.0, 1.0);
+        assert_eq!(result, Some((Events::Fire, index)));
+    }
+
     fn test_lightning_event_helper(index: CellIndex) {
         let mut ecosystem = Ecosystem::init();
-        let trees = Trees {
-            number_of_plants: 1,
-            plant_height_sum: 30.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 1, 30.0, 10.0);
         let cell = &mut ecosystem[index];
-        cell.trees = Some(trees);
+        cell.trees = vec![trees];
 
-        let result = Events::apply_lightning_event_helper(&mut ecosystem, index, 1.0);
+        let result = Events::apply_lightning_event_helper(&mut ecosystem, index, 1.0, 0.0);
         assert!(result.is_none());
 
         // verify trees are dead
         let cell = &ecosystem[index];
         let trees = &cell.trees;
-        assert!(trees.is_none());
+        assert!(trees.is_empty());
 
         // assert bedrock is decreased
         let expected_height = constants::DEFAULT_BEDROCK_HEIGHT
@@ -129,7 +166,7 @@ mod tests {
         assert_eq!(actual_height, expected_height,);
 
         // assert neighbors and self have increase in rocks and sand
-        let neighbors = Cell::get_neighbors(&index);
+        let neighbors = Cell::get_neighbors(&index, ecosystem.config.boundary);
         let num_neighbors = neighbors.len() + 1;
         let volume_per_cell = LIGHTNING_BEDROCK_DISPLACEMENT_VOLUME / (num_neighbors + 1) as f32;
         let height_per_cell =
@@ -0,0 +1,181 @@
+// EVENT LOG
+// counts what the events did during the current time step
+// each event is compared against a snapshot of its cell taken before it was applied,
+// so the events themselves do not need to report anything
+
+use super::{vegetation::Individualized, Events};
+use crate::{
+    constants,
+    ecology::{Cell, CellIndex, Ecosystem},
+};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventLog {
+    pub lightning_strikes: u32,
+    pub thermal_fractures: u32,
+    pub freeze_thaw_fractures: u32,
+    pub slides: u32,
+    // m of material that left the cells where slides started
+    pub slide_material: f32,
+    // m^2
+    pub fire_area: f32,
+    // trees and bushes killed, by cause
+    pub stress_deaths: u32,
+    pub fire_deaths: u32,
+    pub lightning_deaths: u32,
+    pub slide_deaths: u32,
+    pub windthrow_deaths: u32,
+    pub other_deaths: u32,
+}
+
+// the parts of a cell the log compares before and after an event
+pub struct CellSnapshot {
+    height: f32,
+    bedrock_height: f32,
+    number_of_plants: u32,
+    biomass: f32,
+}
+
+impl CellSnapshot {
+    pub fn take(cell: &Cell) -> Self {
+        CellSnapshot {
+            height: cell.get_height(),
+            bedrock_height: cell.get_bedrock_height(),
+            number_of_plants: cell
+                .trees
+                .iter()
+                .map(|t| t.get_number_of_plants())
+                .sum::<u32>()
+                + cell.bushes.as_ref().map_or(0, |b| b.get_number_of_plants()),
+            biomass: cell.estimate_tree_biomass()
+                + cell.estimate_bush_biomass()
+                + cell.estimate_grasses_biomass()
+                + cell.get_dead_vegetation_biomass(),
+        }
+    }
+}
+
+impl EventLog {
+    pub const CSV_HEADER: &'static str = "time_step,lightning_strikes,thermal_fractures,\
+        freeze_thaw_fractures,slides,slide_material,fire_area,stress_deaths,fire_deaths,\
+        lightning_deaths,slide_deaths,windthrow_deaths,other_deaths";
+
+    // records what the event changed in the cell since the snapshot was taken
+    pub fn record(&mut self, event: Events, before: &CellSnapshot, cell: &Cell) {
+        let after = CellSnapshot::take(cell);
+        let lost_bedrock = before.bedrock_height - after.bedrock_height;
+        let lost_height = before.height - after.height;
+        match event {
+            Events::Lightning if lost_bedrock > 0.0 => self.lightning_strikes += 1,
+            Events::ThermalStress if lost_bedrock > 0.0 => self.thermal_fractures += 1,
+            Events::FreezeThaw if lost_bedrock > 0.0 => self.freeze_thaw_fractures += 1,
+            Events::RockSlide
+            | Events::SandSlide
+            | Events::HumusSlide
+            | Events::Landslide
+            | Events::Avalanche
+                if lost_height > 0.0 =>
+            {
+                self.slides += 1;
+                self.slide_material += lost_height;
+            }
+            Events::Fire if after.biomass < before.biomass => {
+                self.fire_area += constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+            }
+            _ => {}
+        }
+
+        // vegetation events can also add plants, so only net losses are counted
+        let deaths = before
+            .number_of_plants
+            .saturating_sub(after.number_of_plants);
+        if deaths > 0 {
+            let cause = match event {
+                Events::VegetationTrees | Events::VegetationBushes | Events::VegetationGrasses => {
+                    &mut self.stress_deaths
+                }
+                Events::Fire => &mut self.fire_deaths,
+                Events::Lightning => &mut self.lightning_deaths,
+                Events::RockSlide
+                | Events::SandSlide
+                | Events::HumusSlide
+                | Events::Landslide
+                | Events::Avalanche => &mut self.slide_deaths,
+                Events::Windthrow => &mut self.windthrow_deaths,
+                _ => &mut self.other_deaths,
+            };
+            *cause += deaths;
+        }
+    }
+
+    // returns a line of the csv with the counts of the given time step
+    pub fn to_csv_row(&self, time_step: u32) -> String {
+        format!(
+            "{time_step},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.lightning_strikes,
+            self.thermal_fractures,
+            self.freeze_thaw_fractures,
+            self.slides,
+            self.slide_material,
+            self.fire_area,
+            self.stress_deaths,
+            self.fire_deaths,
+            self.lightning_deaths,
+            self.slide_deaths,
+            self.windthrow_deaths,
+            self.other_deaths
+        )
+    }
+}
+
+impl Events {
+    // records what the event changed in the cell in the event log of the ecosystem
+    pub fn log(self, ecosystem: &mut Ecosystem, index: CellIndex, before: &CellSnapshot) {
+        let mut event_log = std::mem::take(&mut ecosystem.event_log);
+        event_log.record(self, before, &ecosystem[index]);
+        ecosystem.event_log = event_log;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{CellSnapshot, EventLog};
+    use crate::{
+        ecology::{Cell, Trees},
+        events::Events,
+    };
+
+    #[test]
+    fn test_record() {
+        let mut cell = Cell::init();
+        cell.add_sand(1.0);
+        cell.trees = vec![Trees::with_plants(0, 5, 50.0, 100.0)];
+        let mut log = EventLog::default();
+
+        // a slide that moved sand away and buried two trees
+        let before = CellSnapshot::take(&cell);
+        cell.remove_sand(0.25);
+        cell.trees[0].cohorts[0].number_of_plants = 3;
+        log.record(Events::SandSlide, &before, &cell);
+        assert_eq!(log.slides, 1);
+        assert_eq!(log.slide_deaths, 2);
+        let actual = log.slide_material;
+        assert!(
+            approx_eq!(f32, actual, 0.25, epsilon = 0.0001),
+            "Expected 0.25, actual {actual}"
+        );
+
+        // events that change nothing are not counted
+        let before = CellSnapshot::take(&cell);
+        log.record(Events::Lightning, &before, &cell);
+        log.record(Events::Fire, &before, &cell);
+        assert_eq!(log.lightning_strikes, 0);
+        assert_eq!(log.fire_area, 0.0);
+        assert_eq!(
+            log.to_csv_row(4),
+            "4,0,0,0,1,0.25,0,0,0,0,2,0,0".to_string()
+        );
+    }
+}
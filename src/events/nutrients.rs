@@ -0,0 +1,194 @@
+// NUTRIENT CYCLING
+// each cell holds a pool of plant-available nitrogen (in kg per cell) that closes the biomass loop:
+// 1) dead vegetation decomposes faster in warm and moist soil, releasing the nitrogen it contains
+// 2) growing vegetation takes nitrogen out of the pool to build new biomass
+// 3) a little nitrogen is added from the atmosphere each year, and water leaches some out of the soil
+// vegetation is limited by the nitrogen available to it like by temperature, moisture, and sunlight
+
+// kg of nitrogen per kg of dry biomass, averaged over wood and leaves
+const NITROGEN_CONTENT: f32 = 0.003;
+// kg of available nitrogen per m^2 above which vegetation is not limited by it
+pub const IDEAL_NUTRIENT_DENSITY: f32 = 0.02;
+// kg of nitrogen per m^2 added per year by deposition and fixation
+const NITROGEN_DEPOSITION: f32 = 0.001;
+// % of available nitrogen leached out of saturated soil per year
+const LEACHING_RATE: f32 = 0.1;
+// decomposition doubles for every 10°C above the reference temperature (and halves below)
+const DECOMPOSITION_Q10: f32 = 2.0;
+const DECOMPOSITION_REFERENCE_TEMPERATURE: f32 = 10.0;
+// % of soil moisture by volume above which decomposers are not limited by water
+const DECOMPOSITION_OPTIMAL_MOISTURE: f32 = 0.2;
+// decomposition rate of litter lying on dry or bare ground relative to moist soil
+const DRY_DECOMPOSITION: f32 = 0.25;
+
+use super::Events;
+use crate::{
+    constants,
+    ecology::{Cell, CellIndex, Ecosystem},
+};
+
+// adds deposition to and leaches nitrogen out of the pools of all cells
+pub fn update_nutrients(ecosystem: &mut Ecosystem) {
+    let fraction_of_year = ecosystem.get_fraction_of_year();
+    let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+    let deposition = NITROGEN_DEPOSITION * cell_area * fraction_of_year;
+
+    let leaching_rates: Vec<f32> = (0..constants::NUM_CELLS)
+        .map(|i| {
+            let index = CellIndex::get_from_flat_index(i);
+            let saturation = ecosystem
+                .get_months_of_time_step()
+                .into_iter()
+                .map(|(month, fraction)| {
+                    Events::compute_moisture(ecosystem, index, month) * fraction
+                })
+                .sum::<f32>()
+                / fraction_of_year;
+            f32::min(LEACHING_RATE * saturation * fraction_of_year, 1.0)
+        })
+        .collect();
+
+    for (i, leaching_rate) in leaching_rates.into_iter().enumerate() {
+        let cell = &mut ecosystem[CellIndex::get_from_flat_index(i)];
+        cell.nutrients = cell.nutrients * (1.0 - leaching_rate) + deposition;
+    }
+}
+
+impl Events {
+    // returns how fast dead vegetation decomposes relative to the base rates,
+    // averaged over the months covered by the time step
+    pub fn compute_decomposition_factor(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        let months = ecosystem.get_months_of_time_step();
+        let total_fraction: f32 = months.iter().map(|(_, fraction)| fraction).sum();
+        months
+            .into_iter()
+            .map(|(month, fraction)| {
+                // decomposers are dormant in frozen ground
+                let temperature = ecosystem.get_local_temperature(index, month);
+                let temperature_factor = if temperature <= 0.0 {
+                    0.0
+                } else {
+                    DECOMPOSITION_Q10
+                        .powf((temperature - DECOMPOSITION_REFERENCE_TEMPERATURE) / 10.0)
+                };
+                let moisture = Self::compute_moisture(ecosystem, index, month);
+                let wetness = f32::min(moisture / DECOMPOSITION_OPTIMAL_MOISTURE, 1.0);
+                let moisture_factor = DRY_DECOMPOSITION + (1.0 - DRY_DECOMPOSITION) * wetness;
+                temperature_factor * moisture_factor * fraction
+            })
+            .sum::<f32>()
+            / total_fraction
+    }
+
+    // adds the nitrogen contained in the decomposed biomass to the pool of the cell
+    pub fn release_nutrients(cell: &mut Cell, decomposed_biomass: f32) {
+        cell.nutrients += decomposed_biomass * NITROGEN_CONTENT;
+    }
+
+    // takes the nitrogen needed to build the new biomass out of the pool of the cell
+    // growth is not blocked by an empty pool since its limitation is part of viability
+    pub fn take_up_nutrients(cell: &mut Cell, new_biomass: f32) {
+        cell.nutrients = f32::max(cell.nutrients - new_biomass * NITROGEN_CONTENT, 0.0);
+    }
+
+    // returns viability from -1 when no nitrogen is left to 1 when there is enough
+    pub fn compute_nutrient_viability(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+        let availability = ecosystem[index].nutrients / (IDEAL_NUTRIENT_DENSITY * cell_area);
+        f32::min(2.0 * availability - 1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{update_nutrients, IDEAL_NUTRIENT_DENSITY, NITROGEN_DEPOSITION};
+    use crate::{
+        constants,
+        ecology::{CellIndex, Ecosystem},
+        events::Events,
+    };
+
+    #[test]
+    fn test_compute_decomposition_factor() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(5, 5);
+        ecosystem[index].add_humus(0.5);
+        ecosystem[index].soil_moisture = 1.8E5;
+        ecosystem.config.climate.monthly_temperatures = [10.0; 12];
+        let reference = Events::compute_decomposition_factor(&ecosystem, index);
+
+        // twice as fast 10°C warmer
+        ecosystem.config.climate.monthly_temperatures = [20.0; 12];
+        let actual = Events::compute_decomposition_factor(&ecosystem, index);
+        let expected = 2.0 * reference;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // slower in dry soil
+        ecosystem[index].soil_moisture = 0.0;
+        let dry = Events::compute_decomposition_factor(&ecosystem, index);
+        assert!(dry < actual, "Expected less than {actual}, actual {dry}");
+
+        // and not at all in frozen ground
+        ecosystem.config.climate.monthly_temperatures = [-20.0; 12];
+        let actual = Events::compute_decomposition_factor(&ecosystem, index);
+        assert_eq!(actual, 0.0);
+    }
+
+    #[test]
+    fn test_compute_nutrient_viability() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(5, 5);
+        let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+
+        // cells start with enough nitrogen
+        let actual = Events::compute_nutrient_viability(&ecosystem, index);
+        assert!(
+            approx_eq!(f32, actual, 1.0, epsilon = 0.0001),
+            "Expected 1, actual {actual}"
+        );
+
+        ecosystem[index].nutrients = IDEAL_NUTRIENT_DENSITY * cell_area / 2.0;
+        let actual = Events::compute_nutrient_viability(&ecosystem, index);
+        assert!(
+            approx_eq!(f32, actual, 0.0, epsilon = 0.0001),
+            "Expected 0, actual {actual}"
+        );
+
+        // taking up more than is left empties the pool
+        Events::take_up_nutrients(&mut ecosystem[index], 1.0E6);
+        assert_eq!(ecosystem[index].nutrients, 0.0);
+        assert_eq!(Events::compute_nutrient_viability(&ecosystem, index), -1.0);
+    }
+
+    #[test]
+    fn test_update_nutrients() {
+        let mut ecosystem = Ecosystem::init();
+        let dry = CellIndex::new(5, 5);
+        let wet = CellIndex::new(6, 6);
+        for index in [dry, wet] {
+            ecosystem[index].add_humus(0.5);
+            ecosystem[index].nutrients = 1.0;
+        }
+        ecosystem[dry].soil_moisture = 0.0;
+        ecosystem[wet].soil_moisture = 1.0E6;
+        update_nutrients(&mut ecosystem);
+
+        // dry soil only gains deposition
+        let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+        let expected = 1.0 + NITROGEN_DEPOSITION * cell_area;
+        let actual = ecosystem[dry].nutrients;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // while water leaches nitrogen out of wet soil
+        let actual = ecosystem[wet].nutrients;
+        assert!(actual < 1.0, "Expected less than 1, actual {actual}");
+    }
+}
@@ -0,0 +1,185 @@
+// OUTBREAK
+// a pest or disease, such as bark beetles or blight, sweeping through the trees
+// 1) outbreaks start at random in forested cells, more likely the denser the trees
+// 2) the trees of an infested cell die at a raised rate for several years, until the outbreak burns out
+// 3) each year the outbreak may spread to neighbors, more likely the more host trees they have
+// killed trees are left standing as dead vegetation, which feeds fires and rots into humus
+
+// chance per year that an outbreak starts in a cell fully stocked with trees
+const START_PROBABILITY: f32 = 1e-4;
+// years the trees of a cell stay infested
+pub const OUTBREAK_DURATION: f32 = 3.0;
+// % of the trees of an infested cell killed per year
+const MORTALITY_RATE: f32 = 0.3;
+// chance per year that the outbreak spreads to a neighbor fully stocked with trees
+const SPREAD_PROBABILITY: f32 = 0.5;
+
+use rand::Rng;
+
+use super::{vegetation::Individualized, Events};
+use crate::ecology::{Cell, CellIndex, Ecosystem};
+
+impl Events {
+    // infests the cell if it was not already, then kills part of its trees and spreads to its neighbors
+    pub fn apply_outbreak_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+    ) -> Option<(Events, CellIndex)> {
+        let fraction_of_year = ecosystem.get_fraction_of_year();
+        let cell = &mut ecosystem[index];
+        if cell.trees.is_empty() {
+            // the outbreak dies out with its hosts
+            cell.outbreak = 0.0;
+            return None;
+        }
+        if cell.outbreak <= 0.0 {
+            cell.outbreak = OUTBREAK_DURATION;
+        }
+
+        // killed trees are left standing
+        let biomass = cell.estimate_tree_biomass();
+        let mut hosts = std::mem::take(&mut cell.trees);
+        for trees in hosts.iter_mut() {
+            for cohort in trees.cohorts.iter_mut() {
+                let deaths = Self::prorate_deaths(
+                    &mut ecosystem.rng,
+                    MORTALITY_RATE * cohort.number_of_plants as f32,
+                    fraction_of_year,
+                );
+                cohort.remove_plants(deaths.round() as u32);
+            }
+            trees.cohorts.retain(|c| c.number_of_plants > 0);
+        }
+        hosts.retain(|t| t.get_number_of_plants() > 0);
+        let cell = &mut ecosystem[index];
+        cell.trees = hosts;
+        let killed_biomass = biomass - cell.estimate_tree_biomass();
+        if killed_biomass > 0.0 {
+            cell.add_dead_vegetation(killed_biomass);
+        }
+        cell.outbreak = f32::max(cell.outbreak - fraction_of_year, 0.0);
+
+        // infested neighbors only start killing trees on the next time step
+        for neighbor in Cell::get_neighbors(&index, ecosystem.config.boundary)
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let neighbor_cell = &ecosystem[neighbor];
+            if neighbor_cell.outbreak > 0.0 {
+                continue;
+            }
+            let probability = SPREAD_PROBABILITY
+                * f32::min(neighbor_cell.estimate_total_tree_density(), 1.0)
+                * fraction_of_year;
+            if ecosystem.rng.gen::<f32>() < probability {
+                ecosystem[neighbor].outbreak = OUTBREAK_DURATION;
+            }
+        }
+
+        // does not propagate within the time step
+        None
+    }
+
+    // returns chance that an outbreak starts in the cell this time step
+    pub fn compute_outbreak_probability(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        // infested cells were already hit by update_outbreaks this time step
+        if ecosystem[index].outbreak > 0.0 {
+            return 0.0;
+        }
+        let density = ecosystem[index].estimate_total_tree_density();
+        START_PROBABILITY * f32::min(density, 1.0) * ecosystem.get_fraction_of_year()
+    }
+}
+
+// runs a time step of every outbreak that was going on at the start of it
+pub fn update_outbreaks(ecosystem: &mut Ecosystem) {
+    let infested: Vec<CellIndex> = (0..crate::constants::NUM_CELLS)
+        .map(CellIndex::get_from_flat_index)
+        .filter(|&index| ecosystem[index].outbreak > 0.0)
+        .collect();
+    for index in infested {
+        Events::Outbreak.apply_event(ecosystem, index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{update_outbreaks, OUTBREAK_DURATION};
+    use crate::{
+        ecology::{CellIndex, Ecosystem, Trees},
+        events::{vegetation::Individualized, Events},
+    };
+
+    // a forest of the given number of trees per cell
+    fn init_forest(trees: u32) -> Ecosystem {
+        let mut ecosystem = Ecosystem::init();
+        for cell in ecosystem.cells.iter_mut() {
+            cell.set_trees(Trees::with_plants(
+                0,
+                trees,
+                10.0 * trees as f32,
+                30.0 * trees as f32,
+            ));
+        }
+        ecosystem
+    }
+
+    #[test]
+    fn test_apply_outbreak_event() {
+        let mut ecosystem = init_forest(10);
+        let index = CellIndex::new(50, 50);
+
+        Events::apply_event(Events::Outbreak, &mut ecosystem, index);
+
+        // trees die but are left standing
+        let cell = &ecosystem[index];
+        assert!(cell.trees[0].get_number_of_plants() < 10);
+        assert!(cell.get_dead_vegetation_biomass() > 0.0);
+        assert_eq!(cell.outbreak, OUTBREAK_DURATION - 1.0);
+
+        // a treeless cell cannot host an outbreak
+        let mut ecosystem = Ecosystem::init();
+        Events::apply_event(Events::Outbreak, &mut ecosystem, index);
+        assert_eq!(ecosystem[index].outbreak, 0.0);
+    }
+
+    #[test]
+    fn test_compute_outbreak_probability() {
+        let mut ecosystem = init_forest(10);
+        let index = CellIndex::new(50, 50);
+        assert!(Events::compute_outbreak_probability(&ecosystem, index) > 0.0);
+        ecosystem[index].outbreak = OUTBREAK_DURATION;
+        assert_eq!(Events::compute_outbreak_probability(&ecosystem, index), 0.0);
+    }
+
+    #[test]
+    fn test_update_outbreaks() {
+        // the outbreak spreads through a forest
+        let mut ecosystem = init_forest(10);
+        let index = CellIndex::new(50, 50);
+        ecosystem[index].outbreak = OUTBREAK_DURATION;
+        for _ in 0..OUTBREAK_DURATION as usize {
+            update_outbreaks(&mut ecosystem);
+        }
+        let infested = ecosystem
+            .cells
+            .iter()
+            .filter(|cell| cell.outbreak > 0.0)
+            .count();
+        assert!(infested > 1, "{infested}");
+
+        // but burns out in a stand without neighboring hosts
+        let mut ecosystem = Ecosystem::init();
+        ecosystem[index].set_trees(Trees::with_plants(0, 10, 100.0, 300.0));
+        ecosystem[index].outbreak = OUTBREAK_DURATION;
+        for _ in 0..OUTBREAK_DURATION as usize {
+            update_outbreaks(&mut ecosystem);
+        }
+        assert_eq!(ecosystem[index].outbreak, 0.0);
+        assert!(ecosystem[index].get_dead_vegetation_biomass() > 0.0);
+        let survivors = ecosystem[index].trees[0].get_number_of_plants();
+        update_outbreaks(&mut ecosystem);
+        assert_eq!(ecosystem[index].trees[0].get_number_of_plants(), survivors);
+    }
+}
@@ -0,0 +1,204 @@
+use rand::{rngs::StdRng, SeedableRng};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::{log::CellSnapshot, Events};
+use crate::{
+    constants,
+    ecology::{Bushes, CellIndex, Ecosystem, Grasses},
+};
+
+// outcome of the parts of each cell's events that only read the ecosystem
+// these are computed for every cell in parallel before any event mutates the ecosystem
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrecomputedEvents {
+    pub thermal_fracture: bool,
+    pub freeze_thaw: bool,
+    // indexed by tree species
+    pub trees_vigor_and_stress: Vec<(f32, f32)>,
+    pub bushes_vigor_and_stress: (f32, f32),
+    pub grasses_vigor_and_stress: (f32, f32),
+}
+
+// returns the precomputed events of every cell, indexed by flat index
+// each cell gets its own rng derived from the seed so results do not depend on thread scheduling
+pub fn precompute_events(ecosystem: &Ecosystem, seed: u64) -> Vec<PrecomputedEvents> {
+    (0..constants::NUM_CELLS)
+        .into_par_iter()
+        .map(|i| {
+            let mut rng = StdRng::seed_from_u64(seed.wrapping_add(i as u64));
+            PrecomputedEvents::compute(ecosystem, CellIndex::get_from_flat_index(i), &mut rng)
+        })
+        .collect()
+}
+
+impl PrecomputedEvents {
+    pub fn compute(ecosystem: &Ecosystem, index: CellIndex, rng: &mut StdRng) -> Self {
+        let cell = &ecosystem[index];
+        PrecomputedEvents {
+            thermal_fracture: Events::roll_thermal_fracture(ecosystem, index, rng),
+            freeze_thaw: Events::roll_freeze_thaw(ecosystem, index, rng),
+            trees_vigor_and_stress: Events::compute_trees_vigor_and_stress(ecosystem, index),
+            bushes_vigor_and_stress: Events::compute_vigor_and_stress(
+                ecosystem,
+                index,
+                &Bushes::clone_from_cell(cell),
+            ),
+            grasses_vigor_and_stress: Events::compute_vigor_and_stress(
+                ecosystem,
+                index,
+                &Grasses::clone_from_cell(cell),
+            ),
+        }
+    }
+}
+
+impl Events {
+    // performs and propagates the event, using the precomputed outcome of its first step where available
+    pub fn apply_precomputed_event(
+        self,
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+        precomputed: &PrecomputedEvents,
+    ) {
+        let before = CellSnapshot::take(&ecosystem[index]);
+        let audited = self.audit_before(ecosystem, index);
+        let event_option = match self {
+            Events::ThermalStress => Self::apply_precomputed_thermal_stress_event(
+                ecosystem,
+                index,
+                precomputed.thermal_fracture,
+            ),
+            Events::FreezeThaw => {
+                Self::apply_precomputed_freeze_thaw_event(ecosystem, index, precomputed.freeze_thaw)
+            }
+            // lightning is only scheduled on the cells it strikes
+            Events::Lightning => Self::apply_scheduled_lightning_event(ecosystem, index),
+            Events::VegetationTrees => Self::apply_precomputed_trees_event(
+                ecosystem,
+                index,
+                &precomputed.trees_vigor_and_stress,
+            ),
+            Events::VegetationBushes => Self::apply_precomputed_bushes_event(
+                ecosystem,
+                index,
+                precomputed.bushes_vigor_and_stress,
+            ),
+            Events::VegetationGrasses => Self::apply_precomputed_grasses_event(
+                ecosystem,
+                index,
+                precomputed.grasses_vigor_and_stress,
+            ),
+            // the remaining events depend on the results of their neighbors so are applied as usual
+            event => {
+                event.apply_event(ecosystem, index);
+                return;
+            }
+        };
+        ecosystem[index].last_events.push(self);
+        ecosystem.changed_cells.insert(index);
+        self.audit_after(ecosystem, index, audited);
+        self.log(ecosystem, index, &before);
+        self.mark_terrain_change(ecosystem, index);
+        if let Some((event, index)) = event_option {
+            event.apply_event(ecosystem, index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{precompute_events, PrecomputedEvents};
+    use crate::{
+        constants,
+        ecology::{Bushes, CellIndex, Ecosystem, Grasses, Trees},
+        events::Events,
+    };
+
+    fn init_vegetated_ecosystem() -> Ecosystem {
+        let mut ecosystem = Ecosystem::init();
+        for i in 0..constants::AREA_SIDE_LENGTH {
+            for j in 0..constants::AREA_SIDE_LENGTH {
+                let cell = &mut ecosystem[CellIndex::new(i, j)];
+                cell.add_humus(0.1 * (i % 5) as f32);
+                cell.add_bedrock(0.5 * (j % 3) as f32);
+                cell.trees = vec![Trees::with_plants(0, (i % 4) as u32 + 1, 10.0, 20.0)];
+                cell.grasses = Some(Grasses {
+                    coverage_density: 0.1 * (j % 10) as f32,
+                    green_fraction: 1.0,
+                });
+            }
+        }
+        ecosystem
+    }
+
+    #[test]
+    fn test_precompute_events() {
+        let ecosystem = init_vegetated_ecosystem();
+        let seed = 3;
+        let precomputed = precompute_events(&ecosystem, seed);
+        assert_eq!(precomputed.len(), constants::NUM_CELLS);
+
+        // parallel results match computing each cell serially, rolling with the rng of the cell
+        for (i, actual) in precomputed.iter().enumerate() {
+            let index = CellIndex::get_from_flat_index(i);
+            let cell = &ecosystem[index];
+            let mut rng = StdRng::seed_from_u64(seed.wrapping_add(i as u64));
+            let expected = PrecomputedEvents {
+                thermal_fracture: Events::roll_thermal_fracture(&ecosystem, index, &mut rng),
+                freeze_thaw: Events::roll_freeze_thaw(&ecosystem, index, &mut rng),
+                trees_vigor_and_stress: vec![Events::compute_vigor_and_stress(
+                    &ecosystem,
+                    index,
+                    &Trees::clone_from_cell(cell, 0),
+                )],
+                bushes_vigor_and_stress: Events::compute_vigor_and_stress(
+                    &ecosystem,
+                    index,
+                    &Bushes::clone_from_cell(cell),
+                ),
+                grasses_vigor_and_stress: Events::compute_vigor_and_stress(
+                    &ecosystem,
+                    index,
+                    &Grasses::clone_from_cell(cell),
+                ),
+            };
+            assert_eq!(*actual, expected, "{index}");
+        }
+    }
+
+    #[test]
+    fn test_apply_precomputed_event() {
+        // grasses only depend on their own cell, so precomputing must give the same ecosystem
+        // as applying the events serially
+        let mut serial = init_vegetated_ecosystem();
+        let mut parallel = init_vegetated_ecosystem();
+        let precomputed = precompute_events(&parallel, 0);
+        for (i, cell_precomputed) in precomputed.iter().enumerate() {
+            let index = CellIndex::get_from_flat_index(i);
+            Events::apply_event(Events::VegetationGrasses, &mut serial, index);
+            Events::VegetationGrasses.apply_precomputed_event(
+                &mut parallel,
+                index,
+                cell_precomputed,
+            );
+        }
+
+        for i in 0..constants::NUM_CELLS {
+            let index = CellIndex::get_from_flat_index(i);
+            let expected = &serial[index];
+            let actual = &parallel[index];
+            assert_eq!(
+                actual.grasses.as_ref().map(|g| g.coverage_density),
+                expected.grasses.as_ref().map(|g| g.coverage_density),
+                "{index}"
+            );
+            assert_eq!(
+                actual.get_dead_vegetation_biomass(),
+                expected.get_dead_vegetation_biomass(),
+                "{index}"
+            );
+        }
+    }
+}
@@ -0,0 +1,151 @@
+// RAIN CELLS
+// rain falls unevenly over the map within a time step, when the config has rain cells
+// 1) perlin noise the size of the rain cells gives each cell a share of the rainfall of the months,
+//    from none where the noise is low to several times the average where it is high
+// 2) the noise drifts downwind from one time step to the next and changes as it goes,
+//    so some regions flood while others stay dry
+// 3) the shares average to 1 over the map, so the rainfall of the months is kept
+// the rain of thunderstorms falls on top, see storm.rs
+
+use nalgebra::Vector2;
+use noise::{NoiseFn, Perlin};
+
+use super::wind::get_wind_direction_vector;
+use crate::{
+    config::RainCells,
+    constants,
+    ecology::{CellIndex, Ecosystem},
+};
+
+// how far the noise moves along its time axis per time step, so cells change as they drift
+const RAIN_CELL_CHANGE: f64 = 0.3;
+
+// builds the share of the rainfall of each cell this time step, none if the config has no rain cells
+pub fn update_rainfall_field(ecosystem: &mut Ecosystem) {
+    let Some(rain_cells) = ecosystem.config.rain_cells else {
+        ecosystem.rainfall_field.clear();
+        return;
+    };
+
+    // 2) drift downwind
+    let (direction, strength) = match &ecosystem.wind_state {
+        Some(wind_state) => (wind_state.wind_direction, wind_state.wind_strength),
+        None => (
+            ecosystem.config.wind.direction,
+            ecosystem.config.wind.strength,
+        ),
+    };
+    let drift = get_wind_direction_vector(direction)
+        * rain_cells.drift
+        * (strength / constants::WIND_STRENGTH)
+        * ecosystem.time_step as f32;
+    let time = ecosystem.time_step as f64 * RAIN_CELL_CHANGE;
+    ecosystem.rainfall_field = build_rainfall_field(&rain_cells, ecosystem.seed, drift, time);
+}
+
+// share of the rainfall of each cell by flat index, for noise moved by drift (in m) and along time
+pub fn build_rainfall_field(
+    rain_cells: &RainCells,
+    seed: u64,
+    drift: Vector2<f32>,
+    time: f64,
+) -> Vec<f32> {
+    // 1) share by the noise
+    let noise = Perlin::new(seed as u32);
+    let mut field: Vec<f32> = (0..constants::NUM_CELLS)
+        .map(|i| {
+            let index = CellIndex::get_from_flat_index(i);
+            let position =
+                Vector2::new(index.x as f32, index.y as f32) * constants::CELL_SIDE_LENGTH - drift;
+            let x = (position.x / rain_cells.size) as f64;
+            let y = (position.y / rain_cells.size) as f64;
+            f32::max(
+                0.0,
+                1.0 + rain_cells.contrast * noise.get([x, y, time]) as f32,
+            )
+        })
+        .collect();
+
+    // 3) keep the rainfall of the months
+    let mean = field.iter().sum::<f32>() / constants::NUM_CELLS as f32;
+    if mean <= 0.0 {
+        return vec![1.0; constants::NUM_CELLS];
+    }
+    for share in field.iter_mut() {
+        *share /= mean;
+    }
+    field
+}
+
+impl Ecosystem {
+    // mm of rain falling on the cell this time step, given the rainfall (in mm) of the months
+    pub fn get_local_rainfall(&self, index: CellIndex, rainfall: f32) -> f32 {
+        let share = self
+            .rainfall_field
+            .get(index.get_flat_index())
+            .copied()
+            .unwrap_or(1.0);
+        rainfall * share + self.get_storm_rainfall(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+    use nalgebra::Vector2;
+
+    use super::{build_rainfall_field, update_rainfall_field};
+    use crate::{
+        config::RainCells,
+        constants,
+        ecology::{CellIndex, Ecosystem},
+    };
+
+    #[test]
+    fn test_build_rainfall_field() {
+        let rain_cells = RainCells::default();
+        let field = build_rainfall_field(&rain_cells, 3, Vector2::zeros(), 0.5);
+
+        // the rainfall of the months is kept, but some cells stay dry while others flood
+        let mean = field.iter().sum::<f32>() / constants::NUM_CELLS as f32;
+        assert!(
+            approx_eq!(f32, mean, 1.0, epsilon = 0.001),
+            "Expected 1, actual {mean}"
+        );
+        assert!(field.contains(&0.0));
+        assert!(field.iter().any(|share| *share > 1.5));
+
+        // drifting the cells east by two cells shifts the field with them
+        let drift = Vector2::new(2.0 * constants::CELL_SIDE_LENGTH, 0.0);
+        let drifted = build_rainfall_field(&rain_cells, 3, drift, 0.5);
+        let x = constants::AREA_SIDE_LENGTH / 2;
+        let y = constants::AREA_SIDE_LENGTH / 3;
+        let expected = field[CellIndex::new(x, y).get_flat_index()];
+        let actual = drifted[CellIndex::new(x + 2, y).get_flat_index()];
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.01),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // no contrast is even rainfall
+        let even = RainCells {
+            contrast: 0.0,
+            ..rain_cells
+        };
+        let field = build_rainfall_field(&even, 3, Vector2::zeros(), 0.5);
+        assert!(field.iter().all(|share| *share == 1.0));
+    }
+
+    #[test]
+    fn test_get_local_rainfall() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(5, 5);
+        update_rainfall_field(&mut ecosystem);
+        assert_eq!(ecosystem.get_local_rainfall(index, 80.0), 80.0);
+
+        ecosystem.config.rain_cells = Some(RainCells::default());
+        update_rainfall_field(&mut ecosystem);
+        let share = ecosystem.rainfall_field[index.get_flat_index()];
+        assert_eq!(ecosystem.get_local_rainfall(index, 80.0), 80.0 * share);
+    }
+}
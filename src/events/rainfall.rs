@@ -1,147 +1,687 @@
-use super::Events;
+// HYDRAULIC EROSION
+// running water erodes and deposits sediment once per time step over the whole grid:
+// 1) rainfall runoff and the flow of springs are added on top of the standing water of each cell
+// 2) water flows to neighbors with a lower water surface, carrying its suspended sediment
+// 3) water that carries less sediment than it can hold erodes loose material and then bedrock,
+//    while water that carries more deposits part of the excess
+//    concentrated flow also incises bedrock following the stream power law, so rivers cut valleys
+//    and drop the material where they slow down, building deltas
+//    only gravel is light enough to be carried, and the bedrock the water breaks up becomes gravel
+// 4) sediment still suspended after the last pass settles where it is
+// when provenance is tracked, the regions the sand and humus came from move in the same proportions
+// the water here only drives erosion, standing water itself is tracked by hydrology
+// under the sea the water is still, so it only deposits what rivers carry into it
+// harder layers of stratified bedrock erode more slowly than the softer layers around them
+// the water and sediment each cell passed on are recorded in a trace, kept until the next time step
+
+// number of flow passes per time step
+const EROSION_ITERATIONS: usize = 20;
+// exponents of discharge and slope in the stream power law
+const DISCHARGE_EXPONENT: f32 = 0.5;
+const SLOPE_EXPONENT: f32 = 1.0;
+// discharge (in m^3 per pass) below which flow spreads over the slope instead of forming a channel
+const CHANNEL_DISCHARGE: f32 = 10.0;
+
+use noise::Perlin;
+
+use super::{hydrology::add_spring_water, interception::partition_rainfall};
 use crate::{
+    config::{BoundaryMode, Sediment, Strata},
     constants,
-    ecology::{Cell, CellIndex, Ecosystem},
+    ecology::{
+        provenance::{scale, TaggedLayer, Tags, NUM_REGIONS},
+        Cell, CellIndex, Ecosystem, RockSize,
+    },
+    units::Millimeters,
 };
 
-use rand::distributions::Distribution;
-use rand::distributions::WeightedIndex;
-
-impl Events {
-    pub(crate) fn apply_rainfall_event(ecosystem: &mut Ecosystem, index: CellIndex) -> Option<(Events, CellIndex)> {
-        let water_level: f32 = 0.00001*ecosystem[index].get_height();
+// heights (in m) of [humus, gravel, sand]
+type Materials = [f32; 3];
+// heights (in m) of [humus, gravel, sand] from each region, gravel is not tagged
+type MaterialTags = [Tags; 3];
+const HUMUS: usize = 0;
+const GRAVEL: usize = 1;
+const SAND: usize = 2;
+
+// what the passes of one time step moved, per cell stored row by row like the cells
+#[derive(Clone, Debug, Default)]
+pub struct RunoffTrace {
+    // height (in m) of water that left each cell over all passes
+    pub discharge: Vec<f32>,
+    // height (in m) of loose material and bedrock the water picked up and deposited
+    pub picked_up: Vec<f32>,
+    pub deposited: Vec<f32>,
+}
+
+impl RunoffTrace {
+    fn init() -> Self {
+        RunoffTrace {
+            discharge: vec![0.0; constants::NUM_CELLS],
+            picked_up: vec![0.0; constants::NUM_CELLS],
+            deposited: vec![0.0; constants::NUM_CELLS],
+        }
+    }
 
-        //TODO: Account for plants intercepting rainfall
+    // cells water flowed out of, in the order they are stored
+    pub fn get_visited_cells(&self) -> Vec<CellIndex> {
+        self.discharge
+            .iter()
+            .enumerate()
+            .filter(|(_, discharge)| **discharge > 0.0)
+            .map(|(i, _)| CellIndex::get_from_flat_index(i))
+            .collect()
+    }
 
-        Self::runoff(ecosystem, index, water_level, [0.0, 0.0, 0.0], 0);
+    pub fn get_max_discharge(&self) -> f32 {
+        self.discharge
+            .iter()
+            .fold(0.0, |max, discharge| max.max(*discharge))
+    }
 
-        None
+    // height (in m) the cell gained, negative where it was worn down
+    pub fn get_net_deposition(&self, index: CellIndex) -> f32 {
+        let i = index.get_flat_index();
+        self.deposited[i] - self.picked_up[i]
     }
+}
 
-    fn runoff(ecosystem: &mut Ecosystem, index: CellIndex, water_level: f32, lifted_material: [f32; 3], steps: usize) -> () {
-        let neighbors: [Option<CellIndex>; 8] = Cell::get_neighbors(&index).as_array();
-        const NUM_NEIGHBORS: usize = 8;
+impl Ecosystem {
+    // replaces the trace of the last time step, redrawing the cells of both
+    pub fn set_runoff_trace(&mut self, trace: RunoffTrace) {
+        if let Some(last) = &self.runoff_trace {
+            self.changed_cells.extend(last.get_visited_cells());
+        }
+        self.changed_cells.extend(trace.get_visited_cells());
+        self.runoff_trace = Some(trace);
+    }
+}
 
-        let mut slopes: Vec<f32> = Vec::new();
-        let mut existing_neighbors: Vec<CellIndex> = Vec::new();
+pub fn update_hydraulic_erosion(ecosystem: &mut Ecosystem) -> RunoffTrace {
+    let months = ecosystem.get_months_of_time_step();
+    let rainfall = ecosystem.config.climate.get_rainfall(&months);
+    let sediment = ecosystem.config.sediment;
 
-        for i in 0..NUM_NEIGHBORS {
-            let neighbor_option: Option<CellIndex> = neighbors[i];
-            let neighbor: CellIndex;
+    let mut grid = ErosionGrid::init(ecosystem, rainfall, &months);
+    for _ in 0..EROSION_ITERATIONS {
+        grid.flow();
+        grid.erode_and_deposit(&sediment);
+    }
+    grid.settle();
+    grid.apply(ecosystem);
+    grid.trace
+}
+
+// flat copies of the terrain that the erosion passes work on
+struct ErosionGrid {
+    ground: Vec<f32>,
+    water: Vec<f32>,
+    bedrock: Vec<f32>,
+    loose: Vec<Materials>,
+    suspended: Vec<Materials>,
+    // height of water that left each cell during the last pass
+    outflow: Vec<f32>,
+    sea_level: Option<f32>,
+    boundary: BoundaryMode,
+    strata: Option<(Strata, Perlin)>,
+    // regions the loose and suspended materials came from, when provenance is tracked
+    loose_tags: Option<Vec<MaterialTags>>,
+    suspended_tags: Option<Vec<MaterialTags>>,
+    trace: RunoffTrace,
+}
+
+impl ErosionGrid {
+    // rainfall is in mm, part of which the canopy catches
+    fn init(ecosystem: &Ecosystem, rainfall: f32, months: &[(usize, f32)]) -> Self {
+        let mut grid = ErosionGrid {
+            ground: vec![0.0; constants::NUM_CELLS],
+            water: vec![0.0; constants::NUM_CELLS],
+            bedrock: vec![0.0; constants::NUM_CELLS],
+            loose: vec![[0.0; 3]; constants::NUM_CELLS],
+            suspended: vec![[0.0; 3]; constants::NUM_CELLS],
+            outflow: vec![0.0; constants::NUM_CELLS],
+            sea_level: ecosystem.config.sea.map(|sea| sea.level),
+            boundary: ecosystem.config.boundary,
+            strata: ecosystem.config.strata.clone().map(|strata| {
+                let noise = strata.get_noise();
+                (strata, noise)
+            }),
+            loose_tags: None,
+            suspended_tags: None,
+            trace: RunoffTrace::init(),
+        };
+        for i in 0..constants::NUM_CELLS {
+            let index = CellIndex::get_from_flat_index(i);
+            let cell = &ecosystem[index];
+            grid.ground[i] = cell.get_height();
+            let rainfall = ecosystem.get_local_rainfall(index, rainfall);
+            let runoff = Millimeters(partition_rainfall(cell, rainfall).runoff).to_meters();
+            grid.water[i] = cell.get_water_height() + runoff.0;
+            grid.bedrock[i] = cell.get_bedrock_height();
+            grid.loose[i] = [
+                cell.get_humus_height(),
+                cell.get_rock_height_of_size(RockSize::Gravel),
+                cell.get_sand_height(),
+            ];
+        }
+        add_spring_water(ecosystem, months, &mut grid.water);
+        if let Some(provenance) = &ecosystem.provenance {
+            let loose_tags = (0..constants::NUM_CELLS)
+                .map(|i| {
+                    let index = CellIndex::get_from_flat_index(i);
+                    let mut tags = [[0.0; NUM_REGIONS]; 3];
+                    for (k, layer) in [(HUMUS, TaggedLayer::Humus), (SAND, TaggedLayer::Sand)] {
+                        tags[k] = provenance.get_reconciled_tags(layer, index, grid.loose[i][k]);
+                    }
+                    tags
+                })
+                .collect();
+            grid.loose_tags = Some(loose_tags);
+            grid.suspended_tags = Some(vec![[[0.0; NUM_REGIONS]; 3]; constants::NUM_CELLS]);
+        }
+        grid
+    }
 
-            match neighbor_option {
-                Some(x) => neighbor = x,
-                None => continue
+    // moves water and its suspended sediment towards neighbors with a lower water surface
+    fn flow(&mut self) {
+        let mut water_deltas = vec![0.0; constants::NUM_CELLS];
+        let mut sediment_deltas = vec![[0.0; 3]; constants::NUM_CELLS];
+        let mut tag_deltas = vec![[[0.0; NUM_REGIONS]; 3]; constants::NUM_CELLS];
+        for i in 0..constants::NUM_CELLS {
+            self.outflow[i] = 0.0;
+            let water = self.water[i];
+            if water <= 0.0 {
+                continue;
+            }
+            let surface = self.ground[i] + water;
+            let neighbors = Cell::get_neighbors(&CellIndex::get_from_flat_index(i), self.boundary);
+
+            // height difference to each lower neighbor
+            let mut differences = [(None, 0.0); 8];
+            let mut difference_sum = 0.0;
+            let mut max_difference: f32 = 0.0;
+            for (j, neighbor) in neighbors.as_array().into_iter().enumerate() {
+                let difference = if let Some(neighbor) = neighbor {
+                    let n = neighbor.get_flat_index();
+                    surface - (self.ground[n] + self.water[n])
+                } else if self.boundary == BoundaryMode::Open {
+                    // water and sediment drain off the edges of the map
+                    water
+                } else {
+                    // a wall holds them in
+                    0.0
+                };
+                if difference > 0.0 {
+                    differences[j] = (neighbor, difference);
+                    difference_sum += difference;
+                    max_difference = max_difference.max(difference);
+                }
+            }
+            if difference_sum == 0.0 {
+                continue;
             }
 
-            let slope: f32 = ecosystem.get_slope_between_points(index, neighbor);
-
-            if (slope > 0.0) {
-                slopes.push(slope);
-                existing_neighbors.push(neighbor);
+            // move at most half of the largest difference so water does not oscillate
+            let outflow = f32::min(water, max_difference / 2.0);
+            self.outflow[i] = outflow;
+            self.trace.discharge[i] += outflow;
+            water_deltas[i] -= outflow;
+            // sediment leaves in proportion to the water leaving
+            let carried = self.suspended[i].map(|material| material * outflow / water);
+            let carried_tags = self
+                .suspended_tags
+                .as_ref()
+                .map(|tags| tags[i].map(|material| scale(&material, outflow / water)));
+            for k in 0..3 {
+                sediment_deltas[i][k] -= carried[k];
+                if let Some(carried_tags) = &carried_tags {
+                    add_tags(&mut tag_deltas[i][k], &carried_tags[k], -1.0);
+                }
+            }
+            for (neighbor, difference) in differences {
+                if let Some(neighbor) = neighbor {
+                    let n = neighbor.get_flat_index();
+                    let share = difference / difference_sum;
+                    water_deltas[n] += outflow * share;
+                    for k in 0..3 {
+                        sediment_deltas[n][k] += carried[k] * share;
+                        if let Some(carried_tags) = &carried_tags {
+                            add_tags(&mut tag_deltas[n][k], &carried_tags[k], share);
+                        }
+                    }
+                }
             }
         }
+        for i in 0..constants::NUM_CELLS {
+            self.water[i] = f32::max(self.water[i] + water_deltas[i], 0.0);
+            for (material, delta) in self.suspended[i].iter_mut().zip(sediment_deltas[i]) {
+                *material = f32::max(*material + delta, 0.0);
+            }
+            if let Some(suspended_tags) = &mut self.suspended_tags {
+                for k in 0..3 {
+                    add_tags(&mut suspended_tags[i][k], &tag_deltas[i][k], 1.0);
+                    suspended_tags[i][k] = suspended_tags[i][k].map(|tag| f32::max(tag, 0.0));
+                }
+            }
+        }
+    }
 
-        let cur_cell = &mut ecosystem[index];
-
-        //Soil absorption
-        // if (steps == 0) {
-        //     cur_cell.soil_moisture = cur_cell.get_humus_height()*1000.0;
-        // }
-
-        if slopes.len() != 0 {
-
-            //Decide which cell the water will flow to
-            let chosen_slope: f32;
-            let next_cell_index: CellIndex;
-
-            let dist = WeightedIndex::new(&slopes).unwrap();
-            let mut rng = rand::thread_rng();
-
-            let choice: usize = dist.sample(&mut rng);
-
-            chosen_slope = slopes[choice];
-            next_cell_index = existing_neighbors[choice];
-
-            //Erosion
+    // moves the given fraction of the tags of a material of the cell between loose and suspended
+    fn move_tags(&mut self, i: usize, k: usize, fraction: f32, to_suspended: bool) {
+        let (Some(loose_tags), Some(suspended_tags)) =
+            (&mut self.loose_tags, &mut self.suspended_tags)
+        else {
+            return;
+        };
+        let (from, to) = if to_suspended {
+            (&mut loose_tags[i][k], &mut suspended_tags[i][k])
+        } else {
+            (&mut suspended_tags[i][k], &mut loose_tags[i][k])
+        };
+        let moved = scale(from, fraction);
+        add_tags(from, &moved, -1.0);
+        add_tags(to, &moved, 1.0);
+    }
 
-            let mut lifted = lifted_material; //**SUM OF THIS** is STV
+    // picks up or drops sediment depending on how much the flowing water can carry
+    fn erode_and_deposit(&mut self, sediment: &Sediment) {
+        let slopes: Vec<f32> = (0..constants::NUM_CELLS)
+            .map(|i| self.compute_slope(i))
+            .collect();
+        for (i, slope) in slopes.into_iter().enumerate() {
+            let under_sea = self
+                .sea_level
+                .is_some_and(|sea_level| self.ground[i] < sea_level);
+            // faster and deeper flows carry more sediment
+            let capacity = if under_sea {
+                0.0
+            } else {
+                sediment.kc * self.outflow[i] * slope
+            };
+            let suspended: f32 = self.suspended[i].iter().sum();
+            if suspended > capacity {
+                // deposit part of the excess, keeping the mix of materials
+                let deposited = sediment.kd * (suspended - capacity);
+                let fraction = deposited / suspended;
+                for k in 0..3 {
+                    let amount = self.suspended[i][k] * fraction;
+                    self.suspended[i][k] -= amount;
+                    self.loose[i][k] += amount;
+                    self.move_tags(i, k, fraction, false);
+                }
+                self.ground[i] += deposited;
+                self.trace.deposited[i] += deposited;
+            } else {
+                let eroded = sediment.ks * (capacity - suspended);
+                // loose material is picked up first
+                let loose: f32 = self.loose[i].iter().sum();
+                let from_loose = f32::min(eroded, loose);
+                if from_loose > 0.0 {
+                    let fraction = from_loose / loose;
+                    for k in 0..3 {
+                        let amount = self.loose[i][k] * fraction;
+                        self.loose[i][k] -= amount;
+                        self.suspended[i][k] += amount;
+                        self.move_tags(i, k, fraction, true);
+                    }
+                }
+                // then bedrock is broken up into gravel
+                let from_bedrock = f32::min(
+                    (eroded - from_loose) / self.get_bedrock_hardness(i),
+                    self.bedrock[i],
+                );
+                self.bedrock[i] -= from_bedrock;
+                self.suspended[i][GRAVEL] += from_bedrock;
+                self.ground[i] -= from_loose + from_bedrock;
+                self.trace.picked_up[i] += from_loose + from_bedrock;
+            }
 
-            if (chosen_slope > 0.2) { //LIFT HAPPENS
+            // rivers wear down the bedrock under them regardless of their load
+            let discharge =
+                self.outflow[i] * constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+            if discharge > CHANNEL_DISCHARGE && !under_sea {
+                let incision =
+                    sediment.ki * discharge.powf(DISCHARGE_EXPONENT) * slope.powf(SLOPE_EXPONENT);
+                let incision = f32::min(incision / self.get_bedrock_hardness(i), self.bedrock[i]);
+                self.bedrock[i] -= incision;
+                self.suspended[i][GRAVEL] += incision;
+                self.ground[i] -= incision;
+                self.trace.picked_up[i] += incision;
+            }
+        }
+    }
 
-                //Soil 2
-                cur_cell.soil_moisture += (0.2/chosen_slope)*cur_cell.get_humus_height()*70000.0;
-                
-                //Lift
-                let sediment_capacity: f32 = constants::KC*water_level; //CS
+    // hardness of the top of the bedrock, which changes as erosion cuts through the strata
+    fn get_bedrock_hardness(&self, i: usize) -> f32 {
+        match &self.strata {
+            Some((strata, noise)) => {
+                strata.get_hardness(noise, CellIndex::get_from_flat_index(i), self.bedrock[i])
+            }
+            None => 1.0,
+        }
+    }
 
-                let remaining_capacity = sediment_capacity-(lifted[0]+lifted[1]+lifted[2]);
+    // returns the steepest downhill gradient of the ground from the cell
+    fn compute_slope(&self, i: usize) -> f32 {
+        let neighbors =
+            Cell::get_neighbors(&CellIndex::get_from_flat_index(i), self.boundary).as_array();
+        let mut slope: f32 = 0.0;
+        for (j, neighbor) in neighbors.into_iter().enumerate() {
+            if let Some(neighbor) = neighbor {
+                // diagonal neighbors are further away
+                let distance = if matches!(j, 0 | 2 | 5 | 7) {
+                    constants::CELL_SIDE_LENGTH * std::f32::consts::SQRT_2
+                } else {
+                    constants::CELL_SIDE_LENGTH
+                };
+                let drop = self.ground[i] - self.ground[neighbor.get_flat_index()];
+                slope = slope.max(drop / distance);
+            }
+        }
+        slope
+    }
 
-                let h_amt = cur_cell.get_humus_height();
-                let r_amt = cur_cell.get_rock_height();
-                let s_amt = cur_cell.get_sand_height();
+    // drops all sediment still suspended in the water
+    fn settle(&mut self) {
+        for i in 0..constants::NUM_CELLS {
+            for k in 0..3 {
+                self.loose[i][k] += self.suspended[i][k];
+                self.ground[i] += self.suspended[i][k];
+                self.trace.deposited[i] += self.suspended[i][k];
+                self.move_tags(i, k, 1.0, false);
+            }
+            self.suspended[i] = [0.0; 3];
+        }
+    }
 
-                let cur_cell_sediment: f32 = h_amt+r_amt+s_amt;
+    // writes the eroded and deposited layers back to the ecosystem
+    fn apply(&self, ecosystem: &mut Ecosystem) {
+        for i in 0..constants::NUM_CELLS {
+            let index = CellIndex::get_from_flat_index(i);
+            let cell = &mut ecosystem[index];
+            let height = cell.get_height();
+            let removed_bedrock = cell.get_bedrock_height() - self.bedrock[i];
+            if removed_bedrock > 0.0 {
+                cell.remove_bedrock(removed_bedrock);
+            }
 
-                let percent_humus: f32 = h_amt/cur_cell_sediment;
-                let percent_rock: f32 = r_amt/cur_cell_sediment;
-                let percent_sand: f32 = s_amt/cur_cell_sediment;
+            let change = self.loose[i][HUMUS] - cell.get_humus_height();
+            if change > 0.0 {
+                cell.add_humus(change);
+            } else if change < 0.0 {
+                cell.remove_humus(-change);
+            }
+            let change = self.loose[i][GRAVEL] - cell.get_rock_height_of_size(RockSize::Gravel);
+            if change > 0.0 {
+                cell.add_rocks_of_size(RockSize::Gravel, change);
+            } else if change < 0.0 {
+                cell.remove_rocks_of_size(RockSize::Gravel, -change);
+            }
+            let change = self.loose[i][SAND] - cell.get_sand_height();
+            if change > 0.0 {
+                cell.add_sand(change);
+            } else if change < 0.0 {
+                cell.remove_sand(-change);
+            }
+            if cell.get_height() != height {
+                ecosystem.mark_dirty(index);
+            }
+            if let (Some(provenance), Some(loose_tags)) =
+                (&mut ecosystem.provenance, &self.loose_tags)
+            {
+                provenance.set_tags(TaggedLayer::Humus, index, loose_tags[i][HUMUS]);
+                provenance.set_tags(TaggedLayer::Sand, index, loose_tags[i][SAND]);
+            }
+        }
+    }
+}
 
-                if cur_cell_sediment >= remaining_capacity && cur_cell_sediment != 0.0 { //SEDIMENT FILLS CAPACITY
-                    cur_cell.remove_humus(remaining_capacity*percent_humus);
-                    cur_cell.remove_rocks(remaining_capacity*percent_rock);
-                    cur_cell.remove_sand(remaining_capacity*percent_sand);
+fn add_tags(tags: &mut Tags, other: &Tags, factor: f32) {
+    for (tag, other) in tags.iter_mut().zip(other) {
+        *tag += other * factor;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::update_hydraulic_erosion;
+    use crate::{
+        config::{Spring, Strata, Stratum},
+        constants,
+        ecology::{provenance::Provenance, CellIndex, Ecosystem, RockSize, Trees},
+    };
+
+    // cone shaped hill in the middle of flat terrain, under a thin soil that takes in most of the rain
+    fn init_hill_ecosystem() -> Ecosystem {
+        let mut ecosystem = Ecosystem::init();
+        for cell in ecosystem.cells.iter_mut() {
+            cell.add_humus(0.1);
+        }
+        for x in 40..61 {
+            for y in 40..61 {
+                let dx = x as f32 - 50.0;
+                let dy = y as f32 - 50.0;
+                let height = f32::max(10.0 - (dx * dx + dy * dy).sqrt(), 0.0);
+                ecosystem[CellIndex::new(x, y)].add_bedrock(height);
+            }
+        }
+        ecosystem
+    }
 
-                    lifted[0] += remaining_capacity*percent_humus;
-                    lifted[1] += remaining_capacity*percent_rock;
-                    lifted[2] += remaining_capacity*percent_sand;
-                } else { //ERODE
-                    //Equation 3: Pick up all sediment
-                    cur_cell.remove_humus(h_amt);
-                    cur_cell.remove_rocks(r_amt);
-                    cur_cell.remove_sand(s_amt);
+    #[test]
+    fn test_update_hydraulic_erosion_erodes_slopes() {
+        let mut ecosystem = init_hill_ecosystem();
+        let flank = CellIndex::new(50, 45);
+        let flank_height = ecosystem[flank].get_height();
+        // heights above the flat terrain, so the small changes are not lost to rounding
+        let get_relative_heights = |ecosystem: &Ecosystem| -> Vec<f32> {
+            ecosystem
+                .cells
+                .iter()
+                .map(|cell| {
+                    (cell.get_bedrock_height() - constants::DEFAULT_BEDROCK_HEIGHT)
+                        + cell.get_rock_height()
+                        + cell.get_sand_height()
+                        + cell.get_humus_height()
+                })
+                .collect()
+        };
+        let before = get_relative_heights(&ecosystem);
+
+        update_hydraulic_erosion(&mut ecosystem);
+
+        let after = get_relative_heights(&ecosystem);
+        // the flank of the hill is lowered and the material ends up at its foot
+        assert!(ecosystem[flank].get_height() < flank_height);
+        let foot = CellIndex::new(50, 39);
+        assert!(ecosystem[foot].get_rock_height() > 0.0);
+
+        // no material is lost since the flow never reaches the edges of the map
+        let actual: f32 = before.iter().zip(after.iter()).map(|(b, a)| a - b).sum();
+        assert!(
+            approx_eq!(f32, actual, 0.0, epsilon = 0.001),
+            "Expected 0.0, actual {actual}"
+        );
+    }
 
-                    lifted[0] += h_amt;
-                    lifted[1] += r_amt;
-                    lifted[2] += s_amt;
+    #[test]
+    fn test_update_hydraulic_erosion_carries_only_gravel() {
+        let mut ecosystem = init_hill_ecosystem();
+        let flank = CellIndex::new(50, 45);
+        ecosystem[flank].add_rocks_of_size(RockSize::Boulders, 0.1);
+        ecosystem[flank].add_rocks_of_size(RockSize::Scree, 0.1);
+        ecosystem[flank].add_rocks_of_size(RockSize::Gravel, 0.1);
+
+        update_hydraulic_erosion(&mut ecosystem);
+
+        // the boulders and scree stay behind while the gravel is washed down the hill
+        let cell = &ecosystem[flank];
+        assert_eq!(cell.get_rock_height_of_size(RockSize::Boulders), 0.1);
+        assert_eq!(cell.get_rock_height_of_size(RockSize::Scree), 0.1);
+        assert!(cell.get_rock_height_of_size(RockSize::Gravel) < 0.1);
+        let foot = CellIndex::new(50, 39);
+        assert_eq!(
+            ecosystem[foot].get_rock_height_of_size(RockSize::Scree),
+            0.0
+        );
+        assert!(ecosystem[foot].get_rock_height_of_size(RockSize::Gravel) > 0.0);
+    }
 
-                    //Now, erode an amount equal to K_s*(the difference between capacity and current amount held)
+    #[test]
+    fn test_update_hydraulic_erosion_traces_runoff() {
+        let mut ecosystem = init_hill_ecosystem();
+        let before: Vec<f32> = ecosystem
+            .cells
+            .iter()
+            .map(|cell| cell.get_height())
+            .collect();
+
+        let trace = update_hydraulic_erosion(&mut ecosystem);
+
+        // water runs off the hill, while only a film of it spreads over the flat terrain
+        let flank = CellIndex::new(50, 45);
+        assert!(trace.get_visited_cells().contains(&flank));
+        let far = trace.discharge[CellIndex::new(10, 10).get_flat_index()];
+        let actual = trace.discharge[flank.get_flat_index()];
+        assert!(
+            actual > 10.0 * far,
+            "Expected more than {}, actual {actual}",
+            10.0 * far
+        );
+
+        // the trace accounts for every change of height
+        for (i, height) in before.iter().enumerate() {
+            let index = CellIndex::get_from_flat_index(i);
+            let expected = ecosystem[index].get_height() - height;
+            let actual = trace.get_net_deposition(index);
+            assert!(
+                approx_eq!(f32, actual, expected, epsilon = 0.001),
+                "Expected {expected}, actual {actual}"
+            );
+        }
+    }
 
-                    let mut eroded = constants::KS*(sediment_capacity-(lifted[0]+lifted[1]+lifted[2]));
+    #[test]
+    fn test_update_hydraulic_erosion_moves_provenance() {
+        let mut ecosystem = init_hill_ecosystem();
+        for x in 40..61 {
+            for y in 40..61 {
+                ecosystem[CellIndex::new(x, y)].add_sand(0.5);
+            }
+        }
+        ecosystem.enable_provenance();
+
+        update_hydraulic_erosion(&mut ecosystem);
+
+        // the tags follow the sand, which runs off the hill across the borders between regions
+        let provenance = ecosystem.provenance.as_ref().unwrap();
+        let mut mixed = false;
+        for i in 0..constants::NUM_CELLS {
+            let index = CellIndex::get_from_flat_index(i);
+            let expected = ecosystem[index].get_sand_height();
+            let actual: f32 = provenance.sand[i].iter().sum();
+            assert!(
+                approx_eq!(f32, actual, expected, epsilon = 0.001),
+                "Expected {expected}, actual {actual} at {index}"
+            );
+            let own = provenance.sand[i][Provenance::get_region(index)];
+            mixed |= actual - own > 0.0001;
+        }
+        assert!(mixed);
+    }
 
-                    if (eroded > cur_cell.get_bedrock_height()) {
-                        eroded = cur_cell.get_bedrock_height();
-                    }
+    #[test]
+    fn test_update_hydraulic_erosion_forest_erodes_less() {
+        let flank = CellIndex::new(50, 45);
+        let mut bare = init_hill_ecosystem();
+        let mut forest = init_hill_ecosystem();
+        for x in 40..61 {
+            for y in 40..61 {
+                forest[CellIndex::new(x, y)].set_trees(Trees::with_plants(0, 20, 400.0, 600.0));
+            }
+        }
+        let before = bare[flank].get_height();
+
+        update_hydraulic_erosion(&mut bare);
+        update_hydraulic_erosion(&mut forest);
+
+        // the canopy catches part of the rain, so less water runs down the flank
+        let bare_loss = before - bare[flank].get_height();
+        let forest_loss = before - forest[flank].get_height();
+        assert!(
+            forest_loss < bare_loss,
+            "Expected less than {bare_loss}, actual {forest_loss}"
+        );
+    }
 
-                    //Equation 2
-                    cur_cell.remove_bedrock(eroded);
+    #[test]
+    fn test_update_hydraulic_erosion_caprock_erodes_less() {
+        let flank = CellIndex::new(50, 45);
+        let mut uniform = init_hill_ecosystem();
+        let mut caprock = init_hill_ecosystem();
+        // everything above the flat terrain is a hard layer over softer rock
+        caprock.config.strata = Some(Strata {
+            layers: vec![
+                Stratum {
+                    thickness: 100.0,
+                    hardness: 1.0,
+                },
+                Stratum {
+                    thickness: 100.0,
+                    hardness: 10.0,
+                },
+            ],
+            ..Strata::default()
+        });
+        let before = uniform[flank].get_bedrock_height();
+
+        update_hydraulic_erosion(&mut uniform);
+        update_hydraulic_erosion(&mut caprock);
+
+        let uniform_loss = before - uniform[flank].get_bedrock_height();
+        let caprock_loss = before - caprock[flank].get_bedrock_height();
+        assert!(
+            caprock_loss < uniform_loss,
+            "Expected less than {uniform_loss}, actual {caprock_loss}"
+        );
+    }
 
-                    //Equation 1
-                    lifted[1] += eroded;
-                }
-            } else { //DEPOSIT
-                let deposited_humus = constants::KD*lifted[0];
-                let deposited_rock = constants::KD*lifted[1];
-                let deposited_sand = constants::KD*lifted[2];
+    #[test]
+    fn test_update_hydraulic_erosion_flat_terrain() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(50, 50);
+        let expected = ecosystem[index].get_height();
 
-                cur_cell.add_humus(deposited_humus);
-                cur_cell.add_rocks(deposited_rock);
-                cur_cell.add_sand(deposited_sand);
+        update_hydraulic_erosion(&mut ecosystem);
 
-                lifted[0] -= deposited_humus;
-                lifted[1] -= deposited_rock;
-                lifted[2] -= deposited_sand;
-            }
+        // water without a slope cannot carry sediment
+        let actual = ecosystem[index].get_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+    }
 
-            if steps < 1000 {
-                Self::runoff(ecosystem, next_cell_index, water_level, lifted, steps + 1);
-            } else {
-                println!("1k steps");
-            }
-        } else {
-            cur_cell.add_humus(lifted_material[0]);
-            cur_cell.add_rocks(lifted_material[1]);
-            cur_cell.add_sand(lifted_material[2]);
-        }
+    #[test]
+    fn test_update_hydraulic_erosion_spring_incises_bedrock() {
+        let flank = CellIndex::new(50, 45);
+        let mut ecosystem = init_hill_ecosystem();
+        update_hydraulic_erosion(&mut ecosystem);
+        let without_spring = ecosystem[flank].get_bedrock_height();
+
+        let mut ecosystem = init_hill_ecosystem();
+        ecosystem.config.springs = vec![Spring {
+            cell: [50, 50],
+            flow: 1000.0,
+        }];
+        update_hydraulic_erosion(&mut ecosystem);
+
+        // the spring water cuts into the flank below it
+        let actual = ecosystem[flank].get_bedrock_height();
+        assert!(
+            actual < without_spring,
+            "Expected less than {without_spring}, actual {actual}"
+        );
     }
-}
\ No newline at end of file
+}
@@ -1,22 +1,22 @@
 use super::Events;
-use crate::{
-    constants,
-    ecology::{Cell, CellIndex, Ecosystem},
-};
+use crate::ecology::{Cell, CellIndex, Ecosystem, RockSize, ROCK_SIZES};
 use rand::Rng;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 impl Events {
-    pub(crate) fn apply_rock_slide_event(
+    // rocks of each size slide once the slope exceeds the critical angle of the size,
+    // so gravel runs out on slopes that still hold boulders
+    pub fn apply_rock_slide_event(
         ecosystem: &mut Ecosystem,
         index: CellIndex,
     ) -> Option<(Events, CellIndex)> {
-        let mut critical_neighbors: HashMap<CellIndex, f32> = HashMap::new();
-        let neighbors = Cell::get_neighbors(&index);
+        let critical_angle = Self::compute_rock_critical_angle(ecosystem, index);
+        let mut critical_neighbors: BTreeMap<CellIndex, f32> = BTreeMap::new();
+        let neighbors = Cell::get_neighbors(&index, ecosystem.config.boundary);
         for neighbor_index in neighbors.as_array().into_iter().flatten() {
             let slope = ecosystem.get_slope_between_points(index, neighbor_index);
             let angle = Ecosystem::get_angle(slope);
-            if angle >= constants::CRITICAL_ANGLE_ROCK {
+            if angle >= critical_angle {
                 critical_neighbors.insert(neighbor_index, slope);
             }
         }
@@ -25,25 +25,29 @@ impl Events {
             return None;
         } else {
             // else randomly select neighbor weighted by slope
-            let mut neighbor_probabilities: HashMap<CellIndex, f32> = HashMap::new();
+            let mut neighbor_probabilities: BTreeMap<CellIndex, f32> = BTreeMap::new();
             let slope_sum: f32 = critical_neighbors.values().sum();
             for (neighbor, slope) in critical_neighbors {
                 let prob = slope / slope_sum;
                 neighbor_probabilities.insert(neighbor, prob);
             }
-            let mut rng = rand::thread_rng();
-            let mut rand: f32 = rng.gen();
+            let mut rand: f32 = ecosystem.rng.gen();
             for (neighbor, prob) in neighbor_probabilities {
                 rand -= prob;
                 if rand < 0.0 {
-                    // to propagate, reduce appropriate amount of material and move it to neighbor
-                    let rock_height =
-                        Events::compute_rock_height_to_slide(ecosystem, index, neighbor);
-                    let cell = &mut ecosystem[index];
-                    cell.remove_rocks(rock_height);
+                    // to propagate, reduce appropriate amount of material of each size and move it to neighbor
+                    for size in ROCK_SIZES {
+                        let rock_height =
+                            Events::compute_rock_height_to_slide(ecosystem, index, neighbor, size);
+                        if rock_height <= 0.0 {
+                            continue;
+                        }
+                        let cell = &mut ecosystem[index];
+                        let rock_height = cell.remove_rocks_of_size(size, rock_height);
 
-                    let neighbor_cell = &mut ecosystem[neighbor];
-                    neighbor_cell.add_rocks(rock_height);
+                        let neighbor_cell = &mut ecosystem[neighbor];
+                        neighbor_cell.add_rocks_of_size(size, rock_height);
+                    }
 
                     return Some((Events::RockSlide, neighbor));
                 }
@@ -52,29 +56,57 @@ impl Events {
         None
     }
 
+    // returns whether the cell has rocks and a neighbor below it at least at the critical angle
+    pub fn can_rock_slide(ecosystem: &Ecosystem, index: CellIndex) -> bool {
+        let critical_angle = Self::compute_rock_critical_angle(ecosystem, index);
+        ecosystem[index].get_rock_height() > 0.0
+            && Cell::get_neighbors(&index, ecosystem.config.boundary)
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|neighbor| {
+                    let slope = ecosystem.get_slope_between_points(index, neighbor);
+                    Ecosystem::get_angle(slope) >= critical_angle
+                })
+    }
+
+    // returns the gentlest critical angle of the rock sizes in the cell, that of scree if it has no rocks
+    pub fn compute_rock_critical_angle(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        let critical_angles = &ecosystem.config.critical_angles;
+        let cell = &ecosystem[index];
+        ROCK_SIZES
+            .into_iter()
+            .filter(|&size| cell.get_rock_height_of_size(size) > 0.0)
+            .map(|size| critical_angles.get_rock(size))
+            .reduce(f32::min)
+            .unwrap_or(critical_angles.rock)
+    }
+
     fn compute_rock_height_to_slide(
         ecosystem: &Ecosystem,
         origin: CellIndex,
         target: CellIndex,
+        size: RockSize,
     ) -> f32 {
         let cell = &ecosystem[origin];
-        let rock_height = cell.get_rock_height();
+        let rock_height = cell.get_rock_height_of_size(size);
         if rock_height > 0.0 {
             let origin_pos = ecosystem.get_position_of_cell(&origin);
-            let target_pos = ecosystem.get_position_of_cell(&target);
+            let target_pos = ecosystem.get_position_of_neighbor(&origin, &target);
             let ideal_height = Events::compute_ideal_slide_height(
                 origin_pos,
                 target_pos,
-                constants::CRITICAL_ANGLE_ROCK,
+                ecosystem.config.critical_angles.get_rock(size),
             );
 
             let non_rock_height = cell.get_height() - rock_height;
 
             // simplifying assumption: half of the excess slides away
+            // nothing slides when the cell is already below the ideal height
             if non_rock_height >= ideal_height {
                 rock_height / 2.0
             } else {
-                ((non_rock_height + rock_height) - ideal_height) / 2.0
+                f32::max(((non_rock_height + rock_height) - ideal_height) / 2.0, 0.0)
             }
         } else {
             0.0
@@ -85,7 +117,9 @@ impl Events {
 #[cfg(test)]
 mod tests {
     use crate::{
-        ecology::{CellIndex, Ecosystem},
+        config::BoundaryMode,
+        constants,
+        ecology::{CellIndex, Ecosystem, RockSize},
         events::Events,
     };
     use float_cmp::approx_eq;
@@ -123,4 +157,59 @@ mod tests {
             "Expected {expected}, actual {rock_height}"
         );
     }
+
+    #[test]
+    fn test_rock_sizes_slide_at_their_critical_angles() {
+        let mut ecosystem = Ecosystem::init();
+        let center = CellIndex::new(3, 3);
+        for x in 2..5 {
+            for y in 2..5 {
+                ecosystem[CellIndex::new(x, y)].set_height_of_bedrock(0.0);
+            }
+        }
+        // steeper than gravel rests but gentler than boulders
+        ecosystem[center].set_height_of_bedrock(0.2);
+        ecosystem[center].add_rocks_of_size(RockSize::Boulders, 0.5);
+        ecosystem[center].add_rocks_of_size(RockSize::Gravel, 0.5);
+        let critical_angle = Events::compute_rock_critical_angle(&ecosystem, center);
+        assert_eq!(critical_angle, ecosystem.config.critical_angles.gravel);
+
+        let propagation = Events::apply_rock_slide_event(&mut ecosystem, center);
+        assert!(propagation.is_some());
+        let (_, target) = propagation.unwrap();
+        let cell = &ecosystem[center];
+        assert_eq!(cell.get_rock_height_of_size(RockSize::Boulders), 0.5);
+        assert!(cell.get_rock_height_of_size(RockSize::Gravel) < 0.5);
+        assert!(ecosystem[target].get_rock_height_of_size(RockSize::Gravel) > 0.0);
+        assert_eq!(
+            ecosystem[target].get_rock_height_of_size(RockSize::Boulders),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_apply_rock_slide_event_across_torus_edge() {
+        // the neighbor across the edge of a torus is as close as a neighbor within the map,
+        // so as much rock slides to it
+        let slide_west = |x: usize, boundary: BoundaryMode| {
+            let mut ecosystem = Ecosystem::init();
+            ecosystem.config.boundary = boundary;
+            let origin = CellIndex::new(x, 3);
+            let target = CellIndex::new(
+                (x + constants::AREA_SIDE_LENGTH - 1) % constants::AREA_SIDE_LENGTH,
+                3,
+            );
+            ecosystem[origin].set_height_of_bedrock(0.0);
+            ecosystem[origin].add_rocks(1.0);
+            ecosystem[target].set_height_of_bedrock(0.0);
+
+            let propagation = Events::apply_rock_slide_event(&mut ecosystem, origin);
+            assert_eq!(propagation, Some((Events::RockSlide, target)));
+            ecosystem[target].get_rock_height()
+        };
+        let expected = slide_west(3, BoundaryMode::Open);
+        let actual = slide_west(0, BoundaryMode::Torus);
+        assert!(expected > 0.0);
+        assert_eq!(actual, expected);
+    }
 }
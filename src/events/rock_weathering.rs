@@ -0,0 +1,193 @@
+// ROCK WEATHERING
+// rocks slowly break down into sand, completing the bedrock -> rocks -> sand cascade
+// 1) rocks crack as they expand and contract with the swing between the warmest and coldest months
+// 2) water flowing over the cell tumbles the rocks against each other, abrading them
+// 3) sand and humus over the rocks shelter them from both
+// smaller rocks have more surface for their volume, so gravel weathers faster than scree and boulders slowest
+// the weathered rocks become sand in the same cell, so no material is lost
+
+// % of rocks weathered per year per °C of swing between the warmest and coldest months
+const TEMPERATURE_SWING_RATE: f32 = 0.0005;
+// % of rocks abraded per m of water flowing over the cell
+const ABRASION_RATE: f32 = 0.01;
+// how much sand and humus dampen the weathering of the rocks under them
+const GRANULAR_DAMPENING_CONSTANT: f32 = 2.0;
+// how many times faster than scree boulders and gravel weather
+const BOULDER_WEATHERING_FACTOR: f32 = 0.25;
+const GRAVEL_WEATHERING_FACTOR: f32 = 4.0;
+
+use super::{delta::CellDelta, Events};
+use crate::{
+    constants,
+    ecology::{CellIndex, Ecosystem, RockSize, ROCK_SIZES},
+};
+
+impl Events {
+    pub fn apply_rock_weathering_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+    ) -> Option<(Events, CellIndex)> {
+        Self::compute_rock_weathering_delta(ecosystem, index).apply(&mut ecosystem[index]);
+
+        // does not propagate
+        None
+    }
+
+    // returns the change to the cell as its rocks weather into sand
+    pub fn compute_rock_weathering_delta(ecosystem: &Ecosystem, index: CellIndex) -> CellDelta {
+        let rate = Self::compute_rock_weathering_rate(ecosystem, index);
+        let cell = &ecosystem[index];
+        let mut delta = CellDelta::default();
+        for size in ROCK_SIZES {
+            let size_rate = f32::min(rate * Self::get_rock_weathering_factor(size), 1.0);
+            let weathered = cell.get_rock_height_of_size(size) * size_rate;
+            delta.rocks[size as usize] = -weathered;
+            delta.sand += weathered;
+        }
+        delta
+    }
+
+    fn get_rock_weathering_factor(size: RockSize) -> f32 {
+        match size {
+            RockSize::Boulders => BOULDER_WEATHERING_FACTOR,
+            RockSize::Scree => 1.0,
+            RockSize::Gravel => GRAVEL_WEATHERING_FACTOR,
+        }
+    }
+
+    // returns the % of the scree of the cell that weathers into sand this time step
+    fn compute_rock_weathering_rate(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        let cell = &ecosystem[index];
+        if cell.get_rock_height() == 0.0 {
+            return 0.0;
+        }
+
+        // temperature swings act over the year, so are prorated for shorter time steps
+        let temperatures = (0..12).map(|month| ecosystem.get_local_temperature(index, month));
+        let (coldest, warmest) = temperatures.fold((f32::MAX, f32::MIN), |(min, max), t| {
+            (f32::min(min, t), f32::max(max, t))
+        });
+        let cracking =
+            TEMPERATURE_SWING_RATE * (warmest - coldest) * ecosystem.get_fraction_of_year();
+
+        // water flow is already the flow of this time step
+        let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+        let abrasion = ABRASION_RATE * cell.water_flow / cell_area;
+
+        let granular_height = cell.get_sand_height() + cell.get_humus_height();
+        let shelter = 1.0 + GRANULAR_DAMPENING_CONSTANT * granular_height;
+        f32::min((cracking + abrasion) / shelter, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{
+        ABRASION_RATE, BOULDER_WEATHERING_FACTOR, GRAVEL_WEATHERING_FACTOR, TEMPERATURE_SWING_RATE,
+    };
+    use crate::{
+        config::TimeResolution,
+        constants,
+        ecology::{CellIndex, Ecosystem, RockSize},
+        events::Events,
+    };
+
+    fn init_rocks() -> Ecosystem {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.config.climate.monthly_temperatures =
+            [0.0, 0.0, 0.0, 0.0, 0.0, 10.0, 20.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        ecosystem[CellIndex::new(5, 5)].add_rocks(1.0);
+        ecosystem
+    }
+
+    #[test]
+    fn test_compute_rock_weathering_rate() {
+        let mut ecosystem = init_rocks();
+        let index = CellIndex::new(5, 5);
+
+        // rocks crack with the swing between the warmest and coldest months
+        let exposed = Events::compute_rock_weathering_rate(&ecosystem, index);
+        let expected = TEMPERATURE_SWING_RATE * 20.0;
+        assert!(
+            approx_eq!(f32, exposed, expected, epsilon = 0.00001),
+            "Expected {expected}, actual {exposed}"
+        );
+
+        // more slowly over a shorter time step
+        ecosystem.config.time_resolution = TimeResolution::Month;
+        let actual = Events::compute_rock_weathering_rate(&ecosystem, index);
+        let expected = exposed / 12.0;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.00001),
+            "Expected {expected}, actual {actual}"
+        );
+        ecosystem.config.time_resolution = TimeResolution::Year;
+
+        // flowing water abrades them
+        let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+        ecosystem[index].water_flow = cell_area;
+        let actual = Events::compute_rock_weathering_rate(&ecosystem, index);
+        let expected = exposed + ABRASION_RATE;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.00001),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // and buried rocks are sheltered
+        ecosystem[index].add_sand(1.0);
+        let buried = Events::compute_rock_weathering_rate(&ecosystem, index);
+        assert!(
+            buried < actual,
+            "Expected less than {actual}, actual {buried}"
+        );
+    }
+
+    #[test]
+    fn test_apply_rock_weathering_event() {
+        let mut ecosystem = init_rocks();
+        let index = CellIndex::new(5, 5);
+        let rate = Events::compute_rock_weathering_rate(&ecosystem, index);
+
+        Events::apply_rock_weathering_event(&mut ecosystem, index);
+        let cell = &ecosystem[index];
+        let expected = 1.0 - rate;
+        let actual = cell.get_rock_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.00001),
+            "Expected {expected}, actual {actual}"
+        );
+        // the weathered rocks become sand
+        let expected = rate;
+        let actual = cell.get_sand_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.00001),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_rock_sizes_weather_at_different_rates() {
+        let mut ecosystem = init_rocks();
+        let index = CellIndex::new(5, 5);
+        ecosystem[index].add_rocks_of_size(RockSize::Boulders, 1.0);
+        ecosystem[index].add_rocks_of_size(RockSize::Gravel, 1.0);
+        let rate = Events::compute_rock_weathering_rate(&ecosystem, index);
+
+        Events::apply_rock_weathering_event(&mut ecosystem, index);
+        let cell = &ecosystem[index];
+        for (size, factor) in [
+            (RockSize::Boulders, BOULDER_WEATHERING_FACTOR),
+            (RockSize::Scree, 1.0),
+            (RockSize::Gravel, GRAVEL_WEATHERING_FACTOR),
+        ] {
+            let expected = 1.0 - rate * factor;
+            let actual = cell.get_rock_height_of_size(size);
+            assert!(
+                approx_eq!(f32, actual, expected, epsilon = 0.00001),
+                "Expected {expected}, actual {actual}"
+            );
+        }
+    }
+}
@@ -1,22 +1,20 @@
-use super::Events;
-use crate::{
-    constants,
-    ecology::{Cell, CellIndex, Ecosystem},
-};
+use super::{soil_moisture::estimate_saturation, Events};
+use crate::ecology::{provenance::TaggedLayer, Cell, CellIndex, Ecosystem};
 use rand::Rng;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 impl Events {
-    pub(crate) fn apply_sand_slide_event(
+    pub fn apply_sand_slide_event(
         ecosystem: &mut Ecosystem,
         index: CellIndex,
     ) -> Option<(Events, CellIndex)> {
-        let mut critical_neighbors: HashMap<CellIndex, f32> = HashMap::new();
-        let neighbors = Cell::get_neighbors(&index);
+        let mut critical_neighbors: BTreeMap<CellIndex, f32> = BTreeMap::new();
+        let neighbors = Cell::get_neighbors(&index, ecosystem.config.boundary);
+        let critical_angle = Self::compute_sand_critical_angle(ecosystem, index);
         for neighbor_index in neighbors.as_array().into_iter().flatten() {
             let slope = ecosystem.get_slope_between_points(index, neighbor_index);
             let angle = Ecosystem::get_angle(slope);
-            if angle >= constants::CRITICAL_ANGLE_SAND {
+            if angle >= critical_angle {
                 critical_neighbors.insert(neighbor_index, slope);
             }
         }
@@ -25,14 +23,13 @@ impl Events {
             return None;
         } else {
             // else randomly select neighbor weighted by slope
-            let mut neighbor_probabilities: HashMap<CellIndex, f32> = HashMap::new();
+            let mut neighbor_probabilities: BTreeMap<CellIndex, f32> = BTreeMap::new();
             let slope_sum: f32 = critical_neighbors.values().sum();
             for (neighbor, slope) in critical_neighbors {
                 let prob = slope / slope_sum;
                 neighbor_probabilities.insert(neighbor, prob);
             }
-            let mut rng = rand::thread_rng();
-            let mut rand: f32 = rng.gen();
+            let mut rand: f32 = ecosystem.rng.gen();
             for (neighbor, prob) in neighbor_probabilities {
                 rand -= prob;
                 if rand < 0.0 {
@@ -40,8 +37,9 @@ impl Events {
                     let sand_height =
                         Events::compute_sand_height_to_slide(ecosystem, index, neighbor);
                     // println!("Sand of height {sand_height} sliding from {index} to {neighbor}");
+                    ecosystem.track_transport(TaggedLayer::Sand, index, neighbor, sand_height);
                     let cell = &mut ecosystem[index];
-                    cell.remove_sand(sand_height);
+                    let sand_height = cell.remove_sand(sand_height);
 
                     let neighbor_cell = &mut ecosystem[neighbor];
                     neighbor_cell.add_sand(sand_height);
@@ -62,32 +60,49 @@ impl Events {
         let sand_height = cell.get_sand_height();
         if sand_height > 0.0 {
             let origin_pos = ecosystem.get_position_of_cell(&origin);
-            let target_pos = ecosystem.get_position_of_cell(&target);
-            // vegetation increases critical angle
-            let vegetation_density = f32::min(cell.estimate_vegetation_density() / 3.0, 1.0);
-            let critical_angle = constants::CRITICAL_ANGLE_SAND * (1.0 - vegetation_density)
-                + constants::CRITICAL_ANGLE_SAND_WITH_VEGETATION * vegetation_density;
+            let target_pos = ecosystem.get_position_of_neighbor(&origin, &target);
+            let critical_angle = Self::compute_sand_critical_angle(ecosystem, origin);
             let ideal_height =
                 Events::compute_ideal_slide_height(origin_pos, target_pos, critical_angle);
 
             let non_sand_height = cell.get_height() - sand_height;
 
             // simplifying assumption: half of the excess slides away
+            // nothing slides when the cell is already below the ideal height
             if non_sand_height >= ideal_height {
                 sand_height / 2.0
             } else {
-                ((non_sand_height + sand_height) - ideal_height) / 2.0
+                f32::max(((non_sand_height + sand_height) - ideal_height) / 2.0, 0.0)
             }
         } else {
             0.0
         }
     }
+
+    // vegetation increases critical angle
+    pub fn compute_sand_critical_angle(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        let stabilization = Self::estimate_sand_stabilization(&ecosystem[index]);
+        let critical_angles = &ecosystem.config.critical_angles;
+        let angle = critical_angles.sand * (1.0 - stabilization)
+            + critical_angles.sand_with_vegetation * stabilization;
+        // water in the pores pushes the grains apart
+        let saturation = estimate_saturation(&ecosystem[index]);
+        angle * (1.0 - critical_angles.saturated_loss * saturation)
+    }
+
+    // returns how much the roots of the cell's vegetation bind its sand, from 0 to 1
+    pub fn estimate_sand_stabilization(cell: &Cell) -> f32 {
+        // average density of three types of vegetation
+        f32::min(cell.estimate_vegetation_density() / 3.0, 1.0)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        ecology::{CellIndex, Ecosystem},
+        config::BoundaryMode,
+        constants,
+        ecology::{CellIndex, Ecosystem, Grasses},
         events::Events,
     };
     use float_cmp::approx_eq;
@@ -98,6 +113,8 @@ mod tests {
         let center = &mut ecosystem[CellIndex::new(3, 3)];
         center.set_height_of_bedrock(0.0);
         center.add_sand(1.0);
+        // dry sand holds at its full critical angle
+        center.soil_moisture = 0.0;
 
         let up = &mut ecosystem[CellIndex::new(3, 2)];
         up.set_height_of_bedrock(0.0);
@@ -125,4 +142,94 @@ mod tests {
             "Expected {expected}, actual {sand_height}"
         );
     }
+
+    #[test]
+    fn test_apply_sand_slide_event_vegetated() {
+        // a bare slope of about 39° is steeper than the critical angle of sand
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(3, 3);
+        for x in 2..=4 {
+            for y in 2..=4 {
+                ecosystem[CellIndex::new(x, y)].set_height_of_bedrock(0.0);
+            }
+        }
+        ecosystem[index].add_sand(0.8);
+        ecosystem[index].soil_moisture = 0.0;
+        let bare = Events::compute_sand_critical_angle(&ecosystem, index);
+        assert_eq!(bare, ecosystem.config.critical_angles.sand);
+
+        // but grass binds the sand so it holds
+        ecosystem.config.critical_angles.sand_with_vegetation = 60.0;
+        ecosystem[index].grasses = Some(Grasses {
+            coverage_density: 1.0,
+            green_fraction: 1.0,
+        });
+        let expected = bare + (60.0 - bare) / 3.0;
+        let actual = Events::compute_sand_critical_angle(&ecosystem, index);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        assert!(Events::apply_sand_slide_event(&mut ecosystem, index).is_none());
+        assert_eq!(ecosystem[index].get_sand_height(), 0.8);
+
+        ecosystem[index].grasses = None;
+        assert!(Events::apply_sand_slide_event(&mut ecosystem, index).is_some());
+        assert!(ecosystem[index].get_sand_height() < 0.8);
+    }
+
+    #[test]
+    fn test_apply_sand_slide_event_saturated() {
+        // a slope of about 31° is gentler than the critical angle of dry sand
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(3, 3);
+        for x in 2..=4 {
+            for y in 2..=4 {
+                ecosystem[CellIndex::new(x, y)].set_height_of_bedrock(0.0);
+            }
+        }
+        ecosystem[index].add_sand(0.6);
+        ecosystem[index].soil_moisture = 0.0;
+        assert!(Events::apply_sand_slide_event(&mut ecosystem, index).is_none());
+        assert_eq!(ecosystem[index].get_sand_height(), 0.6);
+
+        // but steeper than that of saturated sand
+        ecosystem[index].soil_moisture = 1.0E5;
+        let critical_angles = ecosystem.config.critical_angles;
+        let expected = critical_angles.sand * (1.0 - critical_angles.saturated_loss);
+        let actual = Events::compute_sand_critical_angle(&ecosystem, index);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        assert!(Events::apply_sand_slide_event(&mut ecosystem, index).is_some());
+        assert!(ecosystem[index].get_sand_height() < 0.6);
+    }
+
+    #[test]
+    fn test_apply_sand_slide_event_across_torus_edge() {
+        // the neighbor across the edge of a torus is as close as a neighbor within the map,
+        // so as much sand slides to it
+        let slide_west = |x: usize, boundary: BoundaryMode| {
+            let mut ecosystem = Ecosystem::init();
+            ecosystem.config.boundary = boundary;
+            let origin = CellIndex::new(x, 3);
+            let target = CellIndex::new(
+                (x + constants::AREA_SIDE_LENGTH - 1) % constants::AREA_SIDE_LENGTH,
+                3,
+            );
+            ecosystem[origin].set_height_of_bedrock(0.0);
+            ecosystem[origin].add_sand(1.0);
+            ecosystem[origin].soil_moisture = 0.0;
+            ecosystem[target].set_height_of_bedrock(0.0);
+
+            let propagation = Events::apply_sand_slide_event(&mut ecosystem, origin);
+            assert_eq!(propagation, Some((Events::SandSlide, target)));
+            ecosystem[target].get_sand_height()
+        };
+        let expected = slide_west(3, BoundaryMode::Open);
+        let actual = slide_west(0, BoundaryMode::Torus);
+        assert!(expected > 0.0);
+        assert_eq!(actual, expected);
+    }
 }
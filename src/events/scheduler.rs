@@ -0,0 +1,163 @@
+// SCHEDULER
+// decides which events are applied to which cells during a time step
+// 1) rare events, which only happen on a few cells per time step, are sampled up front:
+//    the number of occurrences is the sum of the chances of every cell, rounded up or down at random,
+//    and each occurrence is dispatched to a cell drawn in proportion to its chance
+//    cells certain to have the event skip the draw and get it once for each whole chance,
+//    so e.g. every steep cell slides once rather than some twice and others not at all
+// 2) dense events, which happen on nearly every cell, run as passes over the whole grid,
+//    with the events and the cells of each pass in a shuffled order so none of them always goes first
+// rare events are applied before the dense passes so vegetation regrows in the gaps they open
+// dense events written as cell deltas are computed in parallel and then applied, see delta.rs
+
+use rand::{seq::SliceRandom, Rng};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::{delta::apply_delta_pass, parallel::PrecomputedEvents, Events};
+use crate::{
+    constants,
+    ecology::{CellIndex, Ecosystem},
+};
+
+// events sampled by their expected number per time step
+pub const RARE_EVENTS: [Events; 3] = [Events::Lightning, Events::RockSlide, Events::Outbreak];
+
+// events applied to every cell in a pass over the grid
+pub const DENSE_EVENTS: [Events; 11] = [
+    Events::ThermalStress,
+    Events::FreezeThaw,
+    Events::RockWeathering,
+    Events::SandSlide,
+    Events::HumusSlide,
+    Events::Landslide,
+    Events::VegetationTrees,
+    Events::VegetationBushes,
+    Events::VegetationGrasses,
+    Events::SnowFall,
+    Events::Windthrow,
+];
+
+pub fn apply_scheduled_events(ecosystem: &mut Ecosystem, precomputed: &[PrecomputedEvents]) {
+    // 1) rare events
+    for event in RARE_EVENTS {
+        for index in sample_rare_event(ecosystem, event) {
+            event.apply_precomputed_event(ecosystem, index, &precomputed[index.get_flat_index()]);
+        }
+    }
+
+    // 2) dense events
+    let mut events = DENSE_EVENTS;
+    events.shuffle(&mut ecosystem.rng);
+    let mut cells: Vec<usize> = (0..constants::NUM_CELLS).collect();
+    for event in events {
+        // events written as deltas only read the ecosystem, so the order of the cells does not matter
+        if apply_delta_pass(ecosystem, event, precomputed) {
+            continue;
+        }
+        cells.shuffle(&mut ecosystem.rng);
+        for &i in &cells {
+            let index = CellIndex::get_from_flat_index(i);
+            event.apply_precomputed_event(ecosystem, index, &precomputed[i]);
+        }
+    }
+}
+
+// returns the cells the rare event happens on this time step in a random order, which may repeat
+pub fn sample_rare_event(ecosystem: &mut Ecosystem, event: Events) -> Vec<CellIndex> {
+    let mut chances: Vec<f32> = (0..constants::NUM_CELLS)
+        .into_par_iter()
+        .map(|i| estimate_event_chance(ecosystem, event, CellIndex::get_from_flat_index(i)))
+        .collect();
+    let mut sampled = vec![];
+    for (i, chance) in chances.iter_mut().enumerate() {
+        if *chance >= 1.0 {
+            let index = CellIndex::get_from_flat_index(i);
+            sampled.extend(std::iter::repeat_n(index, chance.floor() as usize));
+            // only what is left of the chance is drawn
+            *chance = chance.fract();
+        }
+    }
+    let cumulative: Vec<f32> = chances
+        .iter()
+        .scan(0.0, |sum, chance| {
+            *sum += chance;
+            Some(*sum)
+        })
+        .collect();
+    let expected = cumulative.last().copied().unwrap_or(0.0);
+    let rng = &mut ecosystem.rng;
+    if expected > 0.0 {
+        let mut count = expected.floor() as usize;
+        if rng.gen::<f32>() < expected.fract() {
+            count += 1;
+        }
+        for _ in 0..count {
+            let target = rng.gen::<f32>() * expected;
+            let i = cumulative
+                .partition_point(|&sum| sum <= target)
+                .min(constants::NUM_CELLS - 1);
+            sampled.push(CellIndex::get_from_flat_index(i));
+        }
+    }
+    sampled.shuffle(rng);
+    sampled
+}
+
+// returns chance that the rare event happens on the cell this time step
+fn estimate_event_chance(ecosystem: &Ecosystem, event: Events, index: CellIndex) -> f32 {
+    match event {
+        Events::Lightning => Events::compute_lightning_damage_probability(ecosystem, index),
+        Events::Outbreak => Events::compute_outbreak_probability(ecosystem, index),
+        // slides are certain wherever the rocks are steep enough
+        Events::RockSlide => {
+            if Events::can_rock_slide(ecosystem, index) {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        _ => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sample_rare_event, DENSE_EVENTS, RARE_EVENTS};
+    use crate::{
+        ecology::{CellIndex, Ecosystem},
+        events::Events,
+    };
+
+    #[test]
+    fn test_events_are_scheduled_once() {
+        for event in RARE_EVENTS {
+            assert!(!DENSE_EVENTS.contains(&event), "{event:?}");
+        }
+    }
+
+    #[test]
+    fn test_sample_rare_event() {
+        // flat ground has no rock slides
+        let mut ecosystem = Ecosystem::init();
+        assert!(sample_rare_event(&mut ecosystem, Events::RockSlide).is_empty());
+
+        // rocks on the edge of a pit slide into it and are the only cells sampled
+        let pit = CellIndex::new(3, 2);
+        let edge = CellIndex::new(3, 3);
+        ecosystem[pit].set_height_of_bedrock(0.0);
+        ecosystem[edge].add_rocks(1.0);
+        let sampled = sample_rare_event(&mut ecosystem, Events::RockSlide);
+        assert_eq!(sampled, vec![edge]);
+
+        // every steep cell slides exactly once per time step
+        let other_pit = CellIndex::new(20, 30);
+        let other_edge = CellIndex::new(20, 31);
+        ecosystem[other_pit].set_height_of_bedrock(0.0);
+        ecosystem[other_edge].add_rocks(1.0);
+        for _ in 0..10 {
+            let mut sampled = sample_rare_event(&mut ecosystem, Events::RockSlide);
+            sampled.sort_by_key(|index| index.get_flat_index());
+            assert_eq!(sampled, vec![edge, other_edge]);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+// SNOW
+// precipitation in months below freezing falls as snow and accumulates into a snowpack
+// the snowpack compacts over time and melts in months above freezing using a degree-day model
+// snow left after melting may release as an avalanche on steep slopes
+// density of freshly fallen snow in kg/m^3
+pub const FRESH_SNOW_DENSITY: f32 = 100.0;
+// density a seasonal snowpack approaches as it settles
+pub const MAX_SNOW_DENSITY: f32 = 500.0;
+// % of the difference to the max density closed over a year
+const COMPACTION_RATE: f32 = 0.3;
+// orographic increase in snowfall per meter of elevation
+const OROGRAPHIC_FACTOR: f32 = 0.0005;
+// meters of water equivalent melted per degree celsius above freezing per day
+const DEGREE_DAY_FACTOR: f32 = 0.003;
+const DAYS_PER_MONTH: f32 = 30.0;
+// % of meltwater that soaks into the soil, while the rest runs off
+const MELT_INFILTRATION_RATE: f32 = 0.5;
+
+use super::{avalanche::MIN_RELEASE_HEIGHT, Events};
+use crate::{
+    ecology::{CellIndex, Ecosystem},
+    units::{Meters, Millimeters},
+};
+
+impl Events {
+    pub fn apply_snowfall_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+    ) -> Option<(Events, CellIndex)> {
+        let snowfall = Self::compute_snowfall(ecosystem, index);
+        let compaction = 1.0 - (1.0 - COMPACTION_RATE).powf(ecosystem.get_fraction_of_year());
+        let cell = &mut ecosystem[index];
+
+        // existing snowpack settles before new snow falls on top
+        if cell.get_snow_height() > 0.0 {
+            let density = cell.get_snow_density();
+            cell.set_snow_density(density + (MAX_SNOW_DENSITY - density) * compaction);
+        }
+        if snowfall > 0.0 {
+            cell.add_snow(snowfall, FRESH_SNOW_DENSITY);
+        }
+
+        // snow that fell in winter melts in spring
+        Some((Events::SnowMelt, index))
+    }
+
+    pub fn apply_snow_melt_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+    ) -> Option<(Events, CellIndex)> {
+        let melt = Self::compute_snow_melt(ecosystem, index);
+        let cell = &mut ecosystem[index];
+        if melt > 0.0 {
+            let melt = cell.remove_snow(melt);
+
+            // part of the meltwater soaks into the soil
+            let infiltrated = melt * MELT_INFILTRATION_RATE;
+            cell.soil_moisture += Meters(infiltrated).over_cell().to_liters().0;
+
+            // and the rest becomes surface water which erodes the terrain in the next time step
+            cell.add_water(melt - infiltrated);
+        }
+
+        // the snow that is left may slide
+        if cell.get_snow_height() >= MIN_RELEASE_HEIGHT {
+            Some((Events::Avalanche, index))
+        } else {
+            None
+        }
+    }
+
+    // returns water equivalent height (in m) of snow falling on the cell over the time step
+    pub fn compute_snowfall(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        let cell = &ecosystem[index];
+        let climate = &ecosystem.config.climate;
+        let elevation_factor = 1.0 + OROGRAPHIC_FACTOR * cell.get_height();
+        ecosystem
+            .get_months_of_time_step()
+            .into_iter()
+            .filter(|(month, _)| ecosystem.get_local_temperature(index, *month) < 0.0)
+            .map(|(month, fraction)| {
+                let rainfall = Millimeters(climate.monthly_rainfall[month]).to_meters();
+                rainfall.0 * elevation_factor * fraction
+            })
+            .sum()
+    }
+
+    // returns water equivalent height (in m) of snow melted over the time step
+    pub fn compute_snow_melt(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        let potential_melt: f32 = ecosystem
+            .get_months_of_time_step()
+            .into_iter()
+            .map(|(month, fraction)| {
+                f32::max(ecosystem.get_local_temperature(index, month), 0.0)
+                    * DEGREE_DAY_FACTOR
+                    * DAYS_PER_MONTH
+                    * fraction
+            })
+            .sum();
+        f32::min(potential_melt, ecosystem[index].get_snow_water_equivalent())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{FRESH_SNOW_DENSITY, MELT_INFILTRATION_RATE};
+    use crate::{
+        config::Climate,
+        constants,
+        ecology::{CellIndex, Ecosystem},
+        events::Events,
+        units::Meters,
+    };
+
+    #[test]
+    fn test_apply_snowfall_event() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.config.climate = Climate {
+            monthly_temperatures: [-10.0; 12],
+            monthly_rainfall: [10.0; 12],
+            ..Climate::default()
+        };
+        let index = CellIndex::new(2, 2);
+        let result = Events::apply_snowfall_event(&mut ecosystem, index);
+        assert_eq!(result, Some((Events::SnowMelt, index)));
+
+        let cell = &ecosystem[index];
+        // 120 mm of precipitation increased by elevation
+        let expected = 0.12 * (1.0 + super::OROGRAPHIC_FACTOR * cell.get_height());
+        let actual = cell.get_snow_water_equivalent();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+        let expected = expected * constants::WATER_DENSITY.0 / FRESH_SNOW_DENSITY;
+        let actual = cell.get_snow_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // snowpack compacts but keeps its mass
+        let water_equivalent = cell.get_snow_water_equivalent();
+        let height = cell.get_snow_height();
+        ecosystem.config.climate.monthly_rainfall = [0.0; 12];
+        Events::apply_snowfall_event(&mut ecosystem, index);
+        let cell = &ecosystem[index];
+        assert!(cell.get_snow_height() < height);
+        let actual = cell.get_snow_water_equivalent();
+        assert!(
+            approx_eq!(f32, actual, water_equivalent, epsilon = 0.0001),
+            "Expected {water_equivalent}, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_apply_snow_melt_event() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.config.climate.monthly_temperatures = [20.0; 12];
+        let index = CellIndex::new(2, 2);
+        let cell = &mut ecosystem[index];
+        cell.add_snow(0.1, FRESH_SNOW_DENSITY);
+        let moisture = cell.soil_moisture;
+
+        let result = Events::apply_snow_melt_event(&mut ecosystem, index);
+
+        // warm climate melts all the snow, leaving none to slide
+        assert_eq!(result, None);
+        let cell = &ecosystem[index];
+        assert_eq!(cell.get_snow_height(), 0.0);
+        let expected = moisture
+            + Meters(0.1 * MELT_INFILTRATION_RATE)
+                .over_cell()
+                .to_liters()
+                .0;
+        let actual = cell.soil_moisture;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.1),
+            "Expected {expected}, actual {actual}"
+        );
+        let expected = 0.1 * (1.0 - MELT_INFILTRATION_RATE);
+        let actual = cell.get_water_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+}
@@ -0,0 +1,461 @@
+// SOIL MOISTURE
+// soil moisture (in L per cell) is updated once per time step over the whole grid:
+// 1) rainfall that gets through the canopy, or runs down the stems, and does not run off
+//    infiltrates into the soil
+// 2) vegetation transpires water in proportion to its leaf area and the temperature of each month,
+//    so dense canopies dry their own soil and leave less for the grasses and bushes below
+// 3) moisture evaporates based on the temperature and sunlight of the cell
+// 4) moisture diffuses to neighbors with a lower hydraulic head
+// 5) the moisture profile is updated, with water gained entering the topmost soil layer,
+// water lost leaving every layer alike, and part of each layer percolating into the layer below
+// each step only reads the results of the previous one so all cells are updated in parallel
+// the cells that got wetter are returned, since wet sand and humus slide at lower angles
+
+// % of soil moisture evaporated over a year per degree celsius of mean temperature above freezing
+const EVAPORATION_COEFFICIENT: f32 = 0.03;
+// mm of water transpired per month per unit of leaf area index per degree celsius above freezing
+// roughly 500 mm per year for a dense forest in the default climate
+const TRANSPIRATION_RATE: f32 = 0.8;
+// % of soil volume that can hold water
+pub const POROSITY: f32 = 0.4;
+// % of soil moisture moved to a neighbor per unit of hydraulic gradient
+const HYDRAULIC_CONDUCTIVITY: f32 = 0.5;
+// at most this % of soil moisture moves to neighbors each time step
+const MAX_LATERAL_FLOW: f32 = 0.5;
+// % of the water of a humus layer that percolates into the layer below over a year,
+// layers that conduct water faster drain faster
+const PERCOLATION_RATE: f32 = 0.3;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::{
+    hydrology::SOIL_LAYER_CONDUCTIVITIES, interception::partition_rainfall,
+    vegetation::estimate_transpiring_leaf_area_index, Events,
+};
+use crate::{
+    config::{BoundaryMode, Climate},
+    constants,
+    ecology::{Cell, CellIndex, Ecosystem},
+    units::{Liters, Meters, Millimeters, CELL_AREA},
+};
+
+pub fn update_soil_moisture(ecosystem: &mut Ecosystem) -> Vec<CellIndex> {
+    let climate = &ecosystem.config.climate;
+    let months = ecosystem.get_months_of_time_step();
+    let fraction_of_year = ecosystem.get_fraction_of_year();
+    let rainfall = climate.get_rainfall(&months);
+    let boundary = ecosystem.config.boundary;
+
+    // moisture and hydraulic head of each cell after its own gains and losses
+    let local: Vec<(f32, f32)> = (0..constants::NUM_CELLS)
+        .into_par_iter()
+        .map(|i| {
+            let index = CellIndex::get_from_flat_index(i);
+            let cell = &ecosystem[index];
+            let rainfall = ecosystem.get_local_rainfall(index, rainfall);
+            let infiltration =
+                CELL_AREA * Millimeters(partition_rainfall(cell, rainfall).infiltration);
+            let moisture =
+                compute_local_moisture(cell, climate, &months, fraction_of_year, infiltration.0);
+            (moisture, compute_hydraulic_head(cell, moisture))
+        })
+        .collect();
+
+    let outflows: Vec<[f32; 8]> = (0..constants::NUM_CELLS)
+        .into_par_iter()
+        .map(|i| compute_lateral_outflows(&local, i, boundary))
+        .collect();
+
+    let moistures: Vec<f32> = (0..constants::NUM_CELLS)
+        .into_par_iter()
+        .map(|i| {
+            let neighbors =
+                Cell::get_neighbors(&CellIndex::get_from_flat_index(i), boundary).as_array();
+            // neighbors are ordered so that the opposite of direction j is 7 - j
+            let inflow: f32 = neighbors
+                .into_iter()
+                .enumerate()
+                .filter_map(|(j, neighbor)| Some(outflows[neighbor?.get_flat_index()][7 - j]))
+                .sum();
+            local[i].0 - outflows[i].iter().sum::<f32>() + inflow
+        })
+        .collect();
+
+    let mut wetted = vec![];
+    for (i, moisture) in moistures.into_iter().enumerate() {
+        let index = CellIndex::get_from_flat_index(i);
+        let cell = &mut ecosystem[index];
+        if moisture > cell.soil_moisture {
+            wetted.push(index);
+        }
+        cell.moisture_profile = compute_moisture_profile(cell, moisture, fraction_of_year);
+        cell.soil_moisture = moisture;
+    }
+    wetted
+}
+
+// checks the sand and humus of the cells that got wetter for slides, in the order given
+pub fn apply_rain_triggered_slides(ecosystem: &mut Ecosystem, wetted: &[CellIndex]) {
+    for &index in wetted {
+        if ecosystem[index].get_sand_height() > 0.0 {
+            Events::SandSlide.apply_event(ecosystem, index);
+        }
+        if ecosystem[index].get_humus_height() > 0.0 {
+            Events::HumusSlide.apply_event(ecosystem, index);
+        }
+    }
+}
+
+// returns the share of the pores of the loose material of the cell filled by water, from 0 to 1
+pub fn estimate_saturation(cell: &Cell) -> f32 {
+    let loose_height = cell.get_humus_height() + cell.get_sand_height() + cell.get_rock_height();
+    if loose_height <= 0.0 {
+        return 0.0;
+    }
+    let pore_volume = Meters(loose_height).over_cell() * POROSITY;
+    let water_volume = Liters(cell.soil_moisture).to_cubic_meters();
+    f32::min(water_volume / pore_volume, 1.0)
+}
+
+// returns share of the given soil moisture held in each soil layer of the cell
+fn compute_moisture_profile(cell: &Cell, moisture: f32, fraction_of_year: f32) -> [f32; 3] {
+    let heights = cell.get_soil_layer_heights();
+    let mut layers = cell
+        .moisture_profile
+        .map(|share| share * cell.soil_moisture);
+    // layers that have eroded away hand their water to the next layer below, or else above
+    for i in 0..3 {
+        if heights[i] == 0.0 {
+            let next = (i + 1..3)
+                .find(|&j| heights[j] > 0.0)
+                .or_else(|| (0..i).rev().find(|&j| heights[j] > 0.0));
+            if let Some(next) = next {
+                layers[next] += layers[i];
+                layers[i] = 0.0;
+            }
+        }
+    }
+
+    let total: f32 = layers.iter().sum();
+    if moisture <= 0.0 {
+        return [0.0; 3];
+    } else if moisture < total {
+        layers
+            .iter_mut()
+            .for_each(|layer| *layer *= moisture / total);
+    } else {
+        let top = heights.iter().position(|&height| height > 0.0).unwrap_or(0);
+        layers[top] += moisture - total;
+    }
+
+    // from the bottom up so water percolates at most one layer down per time step
+    for i in (0..2).rev() {
+        if let Some(below) = (i + 1..3).find(|&j| heights[j] > 0.0) {
+            let rate = f32::min(
+                PERCOLATION_RATE * SOIL_LAYER_CONDUCTIVITIES[i] * fraction_of_year,
+                1.0,
+            );
+            let percolated = layers[i] * rate;
+            layers[i] -= percolated;
+            layers[below] += percolated;
+        }
+    }
+    layers.map(|layer| layer / moisture)
+}
+
+// returns soil moisture of the cell after infiltration, transpiration by vegetation, and evaporation
+// over a time step covering the given months
+fn compute_local_moisture(
+    cell: &Cell,
+    climate: &Climate,
+    months: &[(usize, f32)],
+    fraction_of_year: f32,
+    infiltration: f32,
+) -> f32 {
+    let moisture = cell.soil_moisture + infiltration;
+    let moisture = f32::max(moisture - compute_transpiration(cell, climate, months), 0.0);
+    moisture * (1.0 - compute_evaporation_fraction(cell, climate, months, fraction_of_year))
+}
+
+// returns L of water the vegetation of the cell transpires over a time step covering the given months
+pub fn compute_transpiration(cell: &Cell, climate: &Climate, months: &[(usize, f32)]) -> f32 {
+    let leaf_area_index = estimate_transpiring_leaf_area_index(cell);
+    if leaf_area_index <= 0.0 {
+        return 0.0;
+    }
+    let transpiration: f32 = months
+        .iter()
+        .map(|(month, fraction)| {
+            let temperature = f32::max(cell.get_monthly_temperature(climate, *month), 0.0);
+            TRANSPIRATION_RATE * leaf_area_index * temperature * fraction
+        })
+        .sum();
+    (CELL_AREA * Millimeters(transpiration)).0
+}
+
+// returns % of soil moisture evaporated over a time step covering the given months
+fn compute_evaporation_fraction(
+    cell: &Cell,
+    climate: &Climate,
+    months: &[(usize, f32)],
+    fraction_of_year: f32,
+) -> f32 {
+    let covered: f32 = months.iter().map(|(_, fraction)| fraction).sum();
+    let mean_temperature = months
+        .iter()
+        .map(|(month, fraction)| {
+            f32::max(cell.get_monthly_temperature(climate, *month), 0.0) * fraction
+        })
+        .sum::<f32>()
+        / covered;
+    // shaded cells dry out slower than exposed ones
+    let climate_sunlight: f32 = climate.monthly_sunlight_hours.iter().sum();
+    let sunlight_factor = if climate_sunlight == 0.0 {
+        0.0
+    } else {
+        cell.hours_of_sunlight.iter().sum::<f32>() / climate_sunlight
+    };
+    f32::min(
+        EVAPORATION_COEFFICIENT * mean_temperature * sunlight_factor * fraction_of_year,
+        1.0,
+    )
+}
+
+// returns height (in m) of the water table, which is the ground height
+// plus the height of the column of pore space filled by the soil moisture
+fn compute_hydraulic_head(cell: &Cell, moisture: f32) -> f32 {
+    let water_column = Liters(moisture).to_cubic_meters().spread_over_cell();
+    cell.get_height() + water_column.0 / POROSITY
+}
+
+// returns soil moisture moved from the cell to each of its neighbors
+fn compute_lateral_outflows(local: &[(f32, f32)], i: usize, boundary: BoundaryMode) -> [f32; 8] {
+    let (moisture, head) = local[i];
+    let mut outflows = [0.0; 8];
+    if moisture <= 0.0 {
+        return outflows;
+    }
+    let neighbors = Cell::get_neighbors(&CellIndex::get_from_flat_index(i), boundary).as_array();
+    for (j, neighbor) in neighbors.into_iter().enumerate() {
+        if let Some(neighbor) = neighbor {
+            // diagonal neighbors are further away
+            let distance = if matches!(j, 0 | 2 | 5 | 7) {
+                constants::CELL_SIDE_LENGTH * std::f32::consts::SQRT_2
+            } else {
+                constants::CELL_SIDE_LENGTH
+            };
+            let gradient = (head - local[neighbor.get_flat_index()].1) / distance;
+            if gradient > 0.0 {
+                outflows[j] = moisture * HYDRAULIC_CONDUCTIVITY * gradient;
+            }
+        }
+    }
+
+    // limit the total outflow so a cell cannot give away more than it has
+    let total: f32 = outflows.iter().sum();
+    let max_outflow = moisture * MAX_LATERAL_FLOW;
+    if total > max_outflow {
+        outflows
+            .iter_mut()
+            .for_each(|outflow| *outflow *= max_outflow / total);
+    }
+    outflows
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{
+        apply_rain_triggered_slides, compute_transpiration, estimate_saturation,
+        update_soil_moisture,
+    };
+    use crate::{
+        config::{Climate, TimeResolution},
+        constants,
+        ecology::{CellIndex, Ecosystem, Grasses, Trees},
+        events::{vegetation::estimate_transpiring_leaf_area_index, Events},
+        units::{Liters, CELL_AREA},
+    };
+
+    // climate without rainfall or evaporation so only lateral flow and uptake change moisture
+    fn init_dry_frozen_ecosystem() -> Ecosystem {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.config.climate = Climate {
+            monthly_temperatures: [-10.0; 12],
+            monthly_rainfall: [0.0; 12],
+            ..Climate::default()
+        };
+        for cell in ecosystem.cells.iter_mut() {
+            cell.soil_moisture = 0.0;
+        }
+        ecosystem
+    }
+
+    #[test]
+    fn test_update_soil_moisture_diffuses() {
+        let mut ecosystem = init_dry_frozen_ecosystem();
+        let index = CellIndex::new(50, 50);
+        ecosystem[index].soil_moisture = 1.0E5;
+
+        let wetted = update_soil_moisture(&mut ecosystem);
+
+        // wet cell gives moisture to all its neighbors
+        assert!(ecosystem[index].soil_moisture < 1.0E5);
+        assert!(ecosystem[CellIndex::new(51, 50)].soil_moisture > 0.0);
+        assert!(ecosystem[CellIndex::new(49, 49)].soil_moisture > 0.0);
+        // cardinal neighbors are closer than diagonal ones
+        assert!(
+            ecosystem[CellIndex::new(51, 50)].soil_moisture
+                > ecosystem[CellIndex::new(51, 51)].soil_moisture
+        );
+        assert!(wetted.contains(&CellIndex::new(51, 50)));
+        assert!(!wetted.contains(&index));
+        assert!(!wetted.contains(&CellIndex::new(10, 10)));
+
+        // and no moisture is lost
+        let expected = 1.0E5;
+        let actual: f32 = ecosystem.cells.iter().map(|cell| cell.soil_moisture).sum();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 1.0),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_update_soil_moisture_uptake_and_evaporation() {
+        let mut bare = Ecosystem::init();
+        let mut forested = Ecosystem::init();
+        for (cell, forested_cell) in bare.cells.iter_mut().zip(forested.cells.iter_mut()) {
+            cell.add_humus(1.0);
+            forested_cell.add_humus(1.0);
+            forested_cell.trees = vec![Trees::with_plants(0, 10, 100.0, 200.0)];
+        }
+        let index = CellIndex::new(20, 20);
+        let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+        let annual_rainfall: f32 = bare.config.climate.monthly_rainfall.iter().sum();
+        let moisture = bare[index].soil_moisture;
+
+        update_soil_moisture(&mut bare);
+        update_soil_moisture(&mut forested);
+
+        // evaporation removes part of the infiltrated rainfall
+        let actual = bare[index].soil_moisture;
+        assert!(actual > 0.0);
+        assert!(actual < moisture + annual_rainfall * cell_area);
+        // trees take up water
+        assert!(forested[index].soil_moisture < actual);
+    }
+
+    #[test]
+    fn test_compute_transpiration() {
+        let ecosystem = Ecosystem::init();
+        let climate = &ecosystem.config.climate;
+        let year = TimeResolution::Year.get_months(0);
+        let mut cell = ecosystem[CellIndex::new(20, 20)].clone();
+        assert_eq!(compute_transpiration(&cell, climate, &year), 0.0);
+
+        // trees transpire in proportion to their leaf area, about 500 mm per year for a dense forest
+        cell.trees = vec![Trees::with_plants(0, 10, 100.0, 200.0)];
+        let leaf_area_index = estimate_transpiring_leaf_area_index(&cell);
+        let annual = compute_transpiration(&cell, climate, &year);
+        let actual = (Liters(annual) / CELL_AREA).0 / leaf_area_index * 5.0;
+        assert!((400.0..600.0).contains(&actual), "{actual}");
+
+        // more in summer than in winter, and none while frozen
+        let january = compute_transpiration(&cell, climate, &[(0, 1.0)]);
+        let july = compute_transpiration(&cell, climate, &[(6, 1.0)]);
+        assert_eq!(january, 0.0);
+        assert!(july > 0.0);
+
+        // only green grass transpires
+        cell.trees = vec![];
+        cell.grasses = Some(Grasses {
+            coverage_density: 1.0,
+            green_fraction: 0.0,
+        });
+        assert_eq!(compute_transpiration(&cell, climate, &year), 0.0);
+        cell.grasses.as_mut().unwrap().green_fraction = 1.0;
+        assert!(compute_transpiration(&cell, climate, &year) > 0.0);
+
+        // without rain, a forest dries its soil faster than the grasses below it alone
+        let mut forested = Ecosystem::init();
+        let mut grassland = Ecosystem::init();
+        for ecosystem in [&mut forested, &mut grassland] {
+            ecosystem.config.climate.monthly_rainfall = [0.0; 12];
+            for other in ecosystem.cells.iter_mut() {
+                other.add_humus(1.0);
+                other.grasses = cell.grasses.clone();
+            }
+        }
+        for other in forested.cells.iter_mut() {
+            other.trees = vec![Trees::with_plants(0, 10, 100.0, 200.0)];
+        }
+        let index = CellIndex::new(20, 20);
+        update_soil_moisture(&mut forested);
+        update_soil_moisture(&mut grassland);
+        assert!(forested[index].soil_moisture < grassland[index].soil_moisture);
+    }
+
+    #[test]
+    fn test_update_soil_moisture_profile() {
+        let mut ecosystem = init_dry_frozen_ecosystem();
+        let index = CellIndex::new(50, 50);
+        let cell = &mut ecosystem[index];
+        cell.add_humus(0.5);
+        cell.add_sand(2.0);
+        cell.soil_moisture = 1.0E5;
+        cell.moisture_profile = [1.0, 0.0, 0.0];
+        let sandy = CellIndex::new(20, 20);
+        let cell = &mut ecosystem[sandy];
+        cell.add_sand(0.5);
+        cell.add_rocks(2.0);
+        cell.soil_moisture = 1.0E5;
+        cell.moisture_profile = [0.0, 1.0, 0.0];
+
+        update_soil_moisture(&mut ecosystem);
+
+        // sand drains into the layer below faster than humus holding the same water
+        let humus_kept = ecosystem[index].moisture_profile[0];
+        let sand_kept = ecosystem[sandy].moisture_profile[1];
+        assert!(sand_kept < humus_kept, "{sand_kept} {humus_kept}");
+
+        // water percolates from the humus into the sand, but not into the missing rock layer
+        let profile = ecosystem[index].moisture_profile;
+        assert!(profile[0] < 1.0);
+        assert!(profile[1] > 0.0);
+        assert_eq!(profile[2], 0.0);
+        let actual: f32 = profile.iter().sum();
+        assert!(
+            approx_eq!(f32, actual, 1.0, epsilon = 0.0001),
+            "Expected 1, actual {actual}"
+        );
+
+        // once the humus is eroded its water goes to the sand below
+        ecosystem[index].remove_humus(0.5);
+        update_soil_moisture(&mut ecosystem);
+        let actual = ecosystem[index].moisture_profile[1];
+        assert!(
+            approx_eq!(f32, actual, 1.0, epsilon = 0.0001),
+            "Expected 1, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_apply_rain_triggered_slides() {
+        // a pile of sand a little gentler than the critical angle of dry sand
+        let mut ecosystem = init_dry_frozen_ecosystem();
+        let index = CellIndex::new(50, 50);
+        ecosystem[index].add_sand(0.6);
+        assert_eq!(estimate_saturation(&ecosystem[index]), 0.0);
+        apply_rain_triggered_slides(&mut ecosystem, &[index]);
+        assert_eq!(ecosystem[index].get_sand_height(), 0.6);
+
+        // gives way once soaked
+        ecosystem[index].soil_moisture = 1.0E5;
+        assert_eq!(estimate_saturation(&ecosystem[index]), 1.0);
+        apply_rain_triggered_slides(&mut ecosystem, &[index]);
+        assert!(ecosystem[index].get_sand_height() < 0.6);
+        assert!(ecosystem[index].last_events.contains(&Events::SandSlide));
+    }
+}
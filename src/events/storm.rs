@@ -0,0 +1,197 @@
+// STORMS
+// thunderstorms cluster the lightning and heavy rain of the year, when the config has storms
+// 1) the number of storms in a time step is the number of thunderstorm days of its months,
+//    rounded up or down at random
+// 2) each storm crosses the whole map along the wind of the time step through a random point,
+//    and its track covers the cells within half of the track width of that line
+// 3) lightning only strikes under storms: each storm over a cell brings it one storm's share of
+//    the strikes of a year, so the strikes per year stay as set by the climate
+// 4) the cells under a storm get its rain on top of the rainfall of the months
+
+use nalgebra::Vector2;
+use rand::Rng;
+
+use super::wind::get_wind_direction_vector;
+use crate::{
+    constants,
+    ecology::{CellIndex, Ecosystem},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Storm {
+    // point the track passes through, in m
+    pub origin: Vector2<f32>,
+    // degrees from north, like the wind
+    pub direction: f32,
+}
+
+impl Storm {
+    // returns whether the track of the storm covers the center of the cell
+    pub fn covers(&self, index: CellIndex, track_width: f32) -> bool {
+        let position =
+            Vector2::new(index.x as f32 + 0.5, index.y as f32 + 0.5) * constants::CELL_SIDE_LENGTH;
+        let direction = get_wind_direction_vector(self.direction);
+        let offset = position - self.origin;
+        // distance from the line the storm moves along
+        let distance = (offset.x * direction.y - offset.y * direction.x).abs();
+        distance <= track_width / 2.0
+    }
+}
+
+// samples the storms of the time step, none if the config has no storms
+pub fn sample_storms(ecosystem: &mut Ecosystem) {
+    ecosystem.storms.clear();
+    let Some(storms) = ecosystem.config.storms else {
+        return;
+    };
+
+    // 1) number of storms
+    let expected: f32 = ecosystem
+        .get_months_of_time_step()
+        .iter()
+        .map(|(month, fraction)| storms.monthly_storm_days[*month] * fraction)
+        .sum();
+    let mut count = expected.floor() as usize;
+    if ecosystem.rng.gen::<f32>() < expected.fract() {
+        count += 1;
+    }
+
+    // 2) tracks along the wind
+    let direction = match &ecosystem.wind_state {
+        Some(wind_state) => wind_state.wind_direction,
+        None => ecosystem.config.wind.direction,
+    };
+    let side = constants::AREA_SIDE_LENGTH as f32 * constants::CELL_SIDE_LENGTH;
+    for _ in 0..count {
+        let origin = Vector2::new(
+            ecosystem.rng.gen::<f32>() * side,
+            ecosystem.rng.gen::<f32>() * side,
+        );
+        ecosystem.storms.push(Storm { origin, direction });
+    }
+}
+
+impl Ecosystem {
+    pub fn count_storms_over(&self, index: CellIndex) -> usize {
+        let Some(storms) = &self.config.storms else {
+            return 0;
+        };
+        self.storms
+            .iter()
+            .filter(|storm| storm.covers(index, storms.track_width))
+            .count()
+    }
+
+    // 3) share of the yearly lightning strikes of the cell that fall on it this time step
+    pub fn get_lightning_share(&self, index: CellIndex) -> f32 {
+        let Some(storms) = &self.config.storms else {
+            return self.get_fraction_of_year();
+        };
+        // a track covers about its width over the side of the map of the cells
+        let side = constants::AREA_SIDE_LENGTH as f32 * constants::CELL_SIDE_LENGTH;
+        let yearly_storms_over_cell = storms.monthly_storm_days.iter().sum::<f32>()
+            * f32::min(1.0, storms.track_width / side);
+        if yearly_storms_over_cell <= 0.0 {
+            return 0.0;
+        }
+        self.count_storms_over(index) as f32 / yearly_storms_over_cell
+    }
+
+    // 4) mm of rain the storms of the time step bring to the cell
+    pub fn get_storm_rainfall(&self, index: CellIndex) -> f32 {
+        match &self.config.storms {
+            Some(storms) => self.count_storms_over(index) as f32 * storms.rainfall,
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+    use nalgebra::Vector2;
+
+    use super::{sample_storms, Storm};
+    use crate::{
+        config::{Storms, TimeResolution},
+        constants,
+        ecology::{CellIndex, Ecosystem},
+    };
+
+    #[test]
+    fn test_storm_covers() {
+        // moving north through the middle of column 10
+        let storm = Storm {
+            origin: Vector2::new(105.0, 0.0),
+            direction: 0.0,
+        };
+        assert!(storm.covers(CellIndex::new(10, 50), 20.0));
+        assert!(storm.covers(CellIndex::new(11, 90), 20.0));
+        assert!(!storm.covers(CellIndex::new(12, 50), 20.0));
+
+        // moving east through the middle of row 3
+        let storm = Storm {
+            origin: Vector2::new(0.0, 35.0),
+            direction: 90.0,
+        };
+        assert!(storm.covers(CellIndex::new(70, 3), 10.0));
+        assert!(!storm.covers(CellIndex::new(70, 4), 10.0));
+    }
+
+    #[test]
+    fn test_sample_storms() {
+        let mut ecosystem = Ecosystem::init();
+        sample_storms(&mut ecosystem);
+        assert!(ecosystem.storms.is_empty());
+
+        // a year with 3 storms
+        let mut monthly_storm_days = [0.0; 12];
+        monthly_storm_days[6] = 3.0;
+        ecosystem.config.storms = Some(Storms {
+            monthly_storm_days,
+            ..Storms::default()
+        });
+        ecosystem.config.time_resolution = TimeResolution::Year;
+        sample_storms(&mut ecosystem);
+        assert_eq!(ecosystem.storms.len(), 3);
+        for storm in &ecosystem.storms {
+            assert_eq!(storm.direction, ecosystem.config.wind.direction);
+        }
+    }
+
+    #[test]
+    fn test_storm_lightning_and_rainfall() {
+        let mut ecosystem = Ecosystem::init();
+        let storms = Storms {
+            monthly_storm_days: [1.0; 12],
+            track_width: 100.0,
+            rainfall: 30.0,
+        };
+        ecosystem.config.storms = Some(storms);
+        ecosystem.storms = vec![Storm {
+            origin: Vector2::new(505.0, 0.0),
+            direction: 0.0,
+        }];
+        let under = CellIndex::new(50, 20);
+        let away = CellIndex::new(10, 20);
+
+        // a cell sees 12 storms a year over a map 10 tracks wide, so about 1.2 pass over it
+        let side = constants::AREA_SIDE_LENGTH as f32 * constants::CELL_SIDE_LENGTH;
+        let expected = 1.0 / (12.0 * 100.0 / side);
+        let actual = ecosystem.get_lightning_share(under);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+        assert_eq!(ecosystem.get_lightning_share(away), 0.0);
+        assert_eq!(ecosystem.get_storm_rainfall(under), 30.0);
+        assert_eq!(ecosystem.get_storm_rainfall(away), 0.0);
+
+        // without storms lightning strikes evenly through the year
+        ecosystem.config.storms = None;
+        assert_eq!(
+            ecosystem.get_lightning_share(away),
+            ecosystem.get_fraction_of_year()
+        );
+    }
+}
@@ -10,28 +10,64 @@ const BEDROCK_FRACTURE_HEIGHT: f32 = 1.0;
 
 use rand::Rng;
 
-use super::Events;
-use crate::ecology::{Cell, CellIndex, Ecosystem};
+use super::{delta::CellDelta, Events};
+use crate::ecology::{Cell, CellIndex, Ecosystem, RockSize};
 
 impl Events {
-    pub(crate) fn apply_thermal_stress_event(
+    pub fn apply_thermal_stress_event(
         ecosystem: &mut Ecosystem,
         index: CellIndex,
     ) -> Option<(Events, CellIndex)> {
         let fracture_probability = Self::compute_thermal_fracture_probability(ecosystem, index);
         // println!("fracture_probability {fracture_probability}");
-        let mut rng = rand::thread_rng();
-        let rand: f32 = rng.gen();
+        let rand: f32 = ecosystem.rng.gen();
+        Self::apply_precomputed_thermal_stress_event(ecosystem, index, rand < fracture_probability)
+    }
 
-        if rand < fracture_probability {
-            // println!("fracture!");
-            // fracture some bedrock and convert to rocks
-            let cell = &mut ecosystem[index];
-            cell.remove_bedrock(BEDROCK_FRACTURE_HEIGHT);
-            cell.add_rocks(BEDROCK_FRACTURE_HEIGHT);
+    // applies a thermal stress event whose outcome was already rolled by `roll_thermal_fracture`
+    pub fn apply_precomputed_thermal_stress_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+        fractured: bool,
+    ) -> Option<(Events, CellIndex)> {
+        Self::compute_thermal_stress_delta(ecosystem, index, fractured)
+            .apply(&mut ecosystem[index]);
+        None
+    }
+
+    // returns the change to the cell of a thermal stress event whose outcome was already rolled
+    pub fn compute_thermal_stress_delta(
+        ecosystem: &Ecosystem,
+        index: CellIndex,
+        fractured: bool,
+    ) -> CellDelta {
+        if !fractured {
+            return CellDelta::default();
+        }
+        // println!("fracture!");
+        // fracture some bedrock and convert to rocks
+        let fractured = f32::min(
+            BEDROCK_FRACTURE_HEIGHT,
+            ecosystem[index].get_bedrock_height(),
+        );
+        let mut rocks = [0.0; 3];
+        rocks[RockSize::Scree as usize] = fractured;
+        CellDelta {
+            bedrock: -fractured,
+            rocks,
+            ..CellDelta::default()
         }
+    }
 
-        None
+    // returns whether bedrock in the cell fractures this time step
+    pub fn roll_thermal_fracture(
+        ecosystem: &Ecosystem,
+        index: CellIndex,
+        rng: &mut impl Rng,
+    ) -> bool {
+        let fracture_probability = Self::compute_thermal_fracture_probability(ecosystem, index);
+        let rand: f32 = rng.gen();
+        rand < fracture_probability
     }
 
     fn compute_thermal_fracture_probability(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
@@ -42,10 +78,11 @@ impl Events {
         // dampen Δt with vegetation density V(p), and sand + humus height G(p)
         // k, kG, and kV are constants
         // s(p) is maximum local slope
-        // f(p) = k * ∆T * s(p) / (1 + kG * G(p) + kV * V(p))
+        // H(p) is the hardness of the exposed bedrock
+        // f(p) = k * ∆T * s(p) / (H(p) * (1 + kG * G(p) + kV * V(p)))
 
         let mut max_slope = 0.0;
-        let neighbors = Cell::get_neighbors(&index);
+        let neighbors = Cell::get_neighbors(&index, ecosystem.config.boundary);
         for neighbor_index in neighbors.as_array().into_iter().flatten() {
             let slope = f32::abs(ecosystem.get_slope_between_points(index, neighbor_index));
             if slope > max_slope {
@@ -55,10 +92,12 @@ impl Events {
         let cell = &ecosystem[index];
         let vegetation_density = cell.estimate_vegetation_density();
         let granular_height = cell.get_sand_height() + cell.get_humus_height();
-        FRACTURE_CONSTANT * delta_t * max_slope
-            / (1.0
-                + GRANULAR_DAMPENING_CONSTANT * granular_height
-                + VEGETATION_DAMPENING_CONSTANT * vegetation_density)
+        // fractures accumulate over a year of temperature cycles
+        FRACTURE_CONSTANT * delta_t * max_slope * ecosystem.get_fraction_of_year()
+            / (ecosystem.get_bedrock_hardness(index)
+                * (1.0
+                    + GRANULAR_DAMPENING_CONSTANT * granular_height
+                    + VEGETATION_DAMPENING_CONSTANT * vegetation_density))
     }
 }
 
@@ -67,6 +106,7 @@ mod tests {
     use float_cmp::approx_eq;
 
     use crate::{
+        config::{Strata, Stratum},
         ecology::{Bushes, Cell, CellIndex, Ecosystem, Grasses, Trees},
         events::{
             thermal_stress::{GRANULAR_DAMPENING_CONSTANT, VEGETATION_DAMPENING_CONSTANT},
@@ -121,15 +161,11 @@ mod tests {
         );
 
         // add some trees
-        let trees = Trees {
-            number_of_plants: 5,
-            plant_height_sum: 50.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 5, 50.0, 10.0);
         let expected_trees_density = Cell::estimate_tree_density(&trees);
         println!("expected_trees_density {expected_trees_density}");
         let cell = &mut ecosystem[CellIndex::new(2, 2)];
-        cell.trees = Some(trees);
+        cell.trees = vec![trees];
 
         let prob = Events::compute_thermal_fracture_probability(&ecosystem, index);
         let expected = 0.0707
@@ -142,11 +178,7 @@ mod tests {
         );
 
         // add some bushes
-        let bushes = Bushes {
-            number_of_plants: 20,
-            plant_height_sum: 40.0,
-            plant_age_sum: 10.0,
-        };
+        let bushes = Bushes::with_plants(20, 40.0, 10.0);
         let expected_bushes_density = Cell::estimate_bushes_density(&bushes);
         println!("expected_bushes_density {expected_bushes_density}");
         let cell = &mut ecosystem[CellIndex::new(2, 2)];
@@ -166,6 +198,7 @@ mod tests {
         let grass_density = 0.3;
         let grasses = Grasses {
             coverage_density: grass_density,
+            green_fraction: 1.0,
         };
         let cell = &mut ecosystem[CellIndex::new(2, 2)];
         cell.grasses = Some(grasses);
@@ -179,5 +212,20 @@ mod tests {
             approx_eq!(f32, prob, expected, epsilon = 0.0001),
             "Expected {expected}, actual {prob}"
         );
+
+        // harder bedrock fractures less often
+        ecosystem.config.strata = Some(Strata {
+            layers: vec![Stratum {
+                thickness: 10.0,
+                hardness: 2.0,
+            }],
+            ..Strata::default()
+        });
+        let prob = Events::compute_thermal_fracture_probability(&ecosystem, index);
+        let expected = expected / 2.0;
+        assert!(
+            approx_eq!(f32, prob, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {prob}"
+        );
     }
 }
@@ -1,81 +1,177 @@
 use itertools::Itertools;
 use rand::Rng;
 
-use super::Events;
+use super::{coast, Events};
 use crate::{
+    config::{Config, Photosynthesis, SpeciesParams},
     constants,
-    ecology::{Bushes, Cell, CellIndex, Ecosystem, Grasses, Trees},
+    ecology::{Bushes, Cell, CellIndex, Cohort, Ecosystem, Grasses, Trees},
+    units::{Kilograms, KilogramsPerCubicMeter},
 };
 
-// % of dead vegetation that is converted to humus while the rest rots away (disappears)
+// % of dead vegetation that is converted to humus per year while the rest rots away (disappears)
 const DEAD_VEGETATION_TO_HUMUS_RATE: f32 = 0.15;
 const DEAD_VEGETATION_TO_CO2_RATE: f32 = 0.15;
 // https://link.springer.com/referenceworkentry/10.1007/978-1-4020-3995-9_406
-const HUMUS_DENSITY: f32 = 1500.0; // in kg per cubic meter
+pub const HUMUS_DENSITY: KilogramsPerCubicMeter = KilogramsPerCubicMeter(1500.0);
 
-// how vigor and stress affects grass coverage
+// how vigor and stress affects grass coverage over a year
 const GRASSES_VIGOR_GROWTH: f32 = 0.5;
 const GRASSES_STRESS_DEATH: f32 = 1.0;
-
-// viability constants for vegetation
-pub(crate) trait Vegetation {
-    // temperature in celsius
-    const TEMPERATURE_LIMIT_MIN: f32;
-    const TEMPERATURE_LIMIT_MAX: f32;
-    const TEMPERATURE_IDEAL_MIN: f32;
-    const TEMPERATURE_IDEAL_MAX: f32;
-    // % soil moisture, which is the % by weight or volume of soil
-    // e.g. 10% moisture means 10% volume (or weight) of soil is water
-    const MOISTURE_LIMIT_MIN: f32;
-    const MOISTURE_LIMIT_MAX: f32;
-    const MOISTURE_IDEAL_MIN: f32;
-    const MOISTURE_IDEAL_MAX: f32;
-    // hours of daily sunlight
-    const ILLUMINATION_LIMIT_MIN: f32;
-    const ILLUMINATION_LIMIT_MAX: f32;
-    const ILLUMINATION_IDEAL_MIN: f32;
-    const ILLUMINATION_IDEAL_MAX: f32;
-
-    // if cell contains this plant, return it, otherwise init an empty one
-    fn clone_from_cell(cell: &Cell) -> Self;
-
+// % of trees or bushes that die per month of drought past the tolerance of their species
+const DROUGHT_DEATH_RATE: f32 = 0.3;
+// years of age spanned by the seedlings joining the youngest age class of trees and bushes
+const AGE_CLASS_WIDTH: f32 = 5.0;
+// canopy light attenuation after the beer-lambert law, I = I_0 * e^(-k * LAI)
+// leaf area index (m^2 of leaves per m^2 of ground) of a cell fully covered by crowns
+const TREES_LEAF_AREA_INDEX: f32 = 5.0;
+const BUSHES_LEAF_AREA_INDEX: f32 = 3.0;
+const GRASSES_LEAF_AREA_INDEX: f32 = 2.0;
+// extinction coefficient of broadleaf canopies
+const LIGHT_EXTINCTION_COEFFICIENT: f32 = 0.5;
+// how much of the way to its seasonal green fraction grass turns each month
+const GRASSES_PHENOLOGY_RATE: f32 = 0.5;
+
+pub trait Vegetation {
     fn estimate_biomass(&self) -> f32;
 
-    // returns how much of the illumination of the cell should be applied to this vegetation layer based on coverage from other vegetation
+    // returns how much of the illumination of the cell reaches this vegetation layer through the layers above
     // e.g. bushes and grasses will be partially shaded by trees
     fn get_illumination_coverage_constant(cell: &Cell) -> f32;
+
+    // the parameters of this vegetation's species in the species table of the run
+    fn get_species_params<'a>(&self, config: &'a Config) -> &'a SpeciesParams;
+
+    // whether drought kills the plants once it lasts too long, rather than stressing them
+    // grasses die back and regrow within a season, so drought only stresses them
+    const DIES_OF_DROUGHT: bool = false;
 }
 
-impl Vegetation for Trees {
-    // source: https://www.picturethisai.com/care/temperature/Acer_rubrum.html
-    const TEMPERATURE_LIMIT_MIN: f32 = -10.0;
-    const TEMPERATURE_IDEAL_MIN: f32 = 0.0;
-    const TEMPERATURE_IDEAL_MAX: f32 = 35.0;
-    const TEMPERATURE_LIMIT_MAX: f32 = 38.0;
-
-    // sources:
-    // https://www.acurite.com/blog/soil-moisture-guide-for-plants-and-vegetables.html
-    // https://www.nature.com/articles/s41598-021-01804-3#Sec2
-    // https://www.srs.fs.usda.gov/pubs/misc/ag_654/volume_2/acer/rubrum.htm
-    const MOISTURE_LIMIT_MIN: f32 = 0.1;
-    const MOISTURE_IDEAL_MIN: f32 = 0.2;
-    const MOISTURE_IDEAL_MAX: f32 = 0.4;
-    const MOISTURE_LIMIT_MAX: f32 = 0.6;
-
-    // very rough estimates since numbers are hard to find
-    const ILLUMINATION_LIMIT_MIN: f32 = 1.0;
-    const ILLUMINATION_IDEAL_MIN: f32 = 4.0;
-    const ILLUMINATION_IDEAL_MAX: f32 = 10.0;
-    const ILLUMINATION_LIMIT_MAX: f32 = 14.0;
-
-    fn clone_from_cell(cell: &Cell) -> Self {
-        if let Some(trees) = &cell.trees {
-            trees.clone()
-        } else {
-            Trees::new()
+impl Photosynthesis {
+    // returns the % of grass that is green at the given temperature (in celsius) once it has settled
+    pub fn get_green_fraction(&self, temperature: f32) -> f32 {
+        let ramp = |low: f32, high: f32| ((temperature - low) / (high - low)).clamp(0.0, 1.0);
+        match self {
+            Photosynthesis::C3 => ramp(0.0, 5.0) * (1.0 - ramp(25.0, 30.0)),
+            Photosynthesis::C4 => ramp(10.0, 20.0),
+        }
+    }
+}
+
+// default species table
+impl SpeciesParams {
+    pub fn red_maple() -> Self {
+        SpeciesParams {
+            name: "red maple".to_string(),
+            // source: https://www.picturethisai.com/care/temperature/Acer_rubrum.html
+            temperature_limit_min: -10.0,
+            temperature_ideal_min: 0.0,
+            temperature_ideal_max: 35.0,
+            temperature_limit_max: 38.0,
+
+            // sources:
+            // https://www.acurite.com/blog/soil-moisture-guide-for-plants-and-vegetables.html
+            // https://www.nature.com/articles/s41598-021-01804-3#Sec2
+            // https://www.srs.fs.usda.gov/pubs/misc/ag_654/volume_2/acer/rubrum.htm
+            moisture_limit_min: 0.1,
+            moisture_ideal_min: 0.2,
+            moisture_ideal_max: 0.4,
+            moisture_limit_max: 0.6,
+
+            // very rough estimates since numbers are hard to find
+            illumination_limit_min: 1.0,
+            illumination_ideal_min: 4.0,
+            illumination_ideal_max: 10.0,
+            illumination_limit_max: 14.0,
+            shade_tolerance: 0.5,
+            rooting_depth: 2.0,
+
+            establishment_rate: 0.24,
+            seedling_density_constant: 0.05,
+            seedling_vigor_constant: 0.5,
+            growth_rate: 0.3,
+            life_expectancy: 80.0,
+            stress_death_constant: 5.0,
+            // red maple is sensitive to drought
+            drought_tolerance: 3.0,
+            senescence_death_constant: 0.05,
+            windthrow_threshold: 30.0,
+            photosynthesis: Photosynthesis::C3,
         }
     }
 
+    pub fn rhododendron() -> Self {
+        SpeciesParams {
+            name: "rhododendron".to_string(),
+            temperature_limit_min: -30.0,
+            temperature_ideal_min: 4.0,
+            temperature_ideal_max: 16.0,
+            temperature_limit_max: 30.0,
+
+            // sources:
+            // https://www.acurite.com/blog/soil-moisture-guide-for-plants-and-vegetables.html
+            moisture_limit_min: 0.2,
+            moisture_ideal_min: 0.4,
+            moisture_ideal_max: 0.6,
+            moisture_limit_max: 0.8,
+
+            illumination_limit_min: 2.0,
+            illumination_ideal_min: 4.0,
+            illumination_ideal_max: 6.0,
+            illumination_limit_max: 12.0,
+            shade_tolerance: 0.8,
+            rooting_depth: 0.6,
+
+            establishment_rate: 0.24,
+            seedling_density_constant: 0.05,
+            seedling_vigor_constant: 0.5,
+            growth_rate: 0.2,
+            life_expectancy: 20.0,
+            stress_death_constant: 5.0,
+            // shallow roots leave rhododendron little to draw on
+            drought_tolerance: 2.0,
+            senescence_death_constant: 0.05,
+            windthrow_threshold: 30.0,
+            photosynthesis: Photosynthesis::C3,
+        }
+    }
+
+    pub fn switchgrass() -> Self {
+        SpeciesParams {
+            name: "switchgrass".to_string(),
+            temperature_limit_min: -5.0,
+            temperature_ideal_max: 20.0,
+            temperature_limit_max: 30.0,
+            temperature_ideal_min: 38.0,
+
+            moisture_limit_min: 0.05,
+            moisture_ideal_min: 0.2,
+            moisture_ideal_max: 0.6,
+            moisture_limit_max: 0.8,
+
+            illumination_limit_min: 4.0,
+            illumination_ideal_min: 6.0,
+            illumination_ideal_max: 8.0,
+            illumination_limit_max: 14.0,
+            shade_tolerance: 0.1,
+            rooting_depth: 0.3,
+
+            // grasses are not individualized
+            establishment_rate: 0.0,
+            seedling_density_constant: 0.0,
+            seedling_vigor_constant: 0.0,
+            growth_rate: 0.0,
+            life_expectancy: 0.0,
+            stress_death_constant: 0.0,
+            drought_tolerance: 0.0,
+            senescence_death_constant: 0.0,
+            windthrow_threshold: 30.0,
+            photosynthesis: Photosynthesis::C4,
+        }
+    }
+}
+
+impl Vegetation for Trees {
     fn estimate_biomass(&self) -> f32 {
         self.estimate_biomass()
     }
@@ -84,211 +180,168 @@ impl Vegetation for Trees {
     fn get_illumination_coverage_constant(_: &Cell) -> f32 {
         1.0
     }
-}
 
-impl Vegetation for Bushes {
-    const TEMPERATURE_LIMIT_MIN: f32 = -30.0;
-    const TEMPERATURE_IDEAL_MIN: f32 = 4.0;
-    const TEMPERATURE_IDEAL_MAX: f32 = 16.0;
-    const TEMPERATURE_LIMIT_MAX: f32 = 30.0;
-
-    // sources:
-    // https://www.acurite.com/blog/soil-moisture-guide-for-plants-and-vegetables.html
-    const MOISTURE_LIMIT_MIN: f32 = 0.2;
-    const MOISTURE_IDEAL_MIN: f32 = 0.4;
-    const MOISTURE_IDEAL_MAX: f32 = 0.6;
-    const MOISTURE_LIMIT_MAX: f32 = 0.8;
-
-    const ILLUMINATION_LIMIT_MIN: f32 = 2.0;
-    const ILLUMINATION_IDEAL_MIN: f32 = 4.0;
-    const ILLUMINATION_IDEAL_MAX: f32 = 6.0;
-    const ILLUMINATION_LIMIT_MAX: f32 = 12.0;
-
-    fn clone_from_cell(cell: &Cell) -> Self {
-        if let Some(bushes) = &cell.bushes {
-            bushes.clone()
-        } else {
-            Bushes::new()
-        }
+    fn get_species_params<'a>(&self, config: &'a Config) -> &'a SpeciesParams {
+        &config.species.trees[self.species]
     }
 
+    const DIES_OF_DROUGHT: bool = true;
+}
+
+impl Vegetation for Bushes {
     fn estimate_biomass(&self) -> f32 {
         self.estimate_biomass()
     }
 
     fn get_illumination_coverage_constant(cell: &Cell) -> f32 {
-        if let Some(trees) = &cell.trees {
-            let tree_density = Cell::estimate_tree_density(trees);
-            // todo placeholder value
-            1.0 - (tree_density * 0.5)
-        } else {
-            1.0
-        }
+        compute_light_transmission(estimate_trees_leaf_area_index(cell))
     }
-}
 
-impl Vegetation for Grasses {
-    // based on switchgrass
-    const TEMPERATURE_LIMIT_MIN: f32 = -5.0;
-    const TEMPERATURE_IDEAL_MAX: f32 = 20.0;
-    const TEMPERATURE_LIMIT_MAX: f32 = 30.0;
-    const TEMPERATURE_IDEAL_MIN: f32 = 38.0;
-
-    const MOISTURE_LIMIT_MIN: f32 = 0.05;
-    const MOISTURE_IDEAL_MIN: f32 = 0.2;
-    const MOISTURE_IDEAL_MAX: f32 = 0.6;
-    const MOISTURE_LIMIT_MAX: f32 = 0.8;
-
-    const ILLUMINATION_LIMIT_MIN: f32 = 4.0;
-    const ILLUMINATION_IDEAL_MIN: f32 = 6.0;
-    const ILLUMINATION_IDEAL_MAX: f32 = 8.0;
-    const ILLUMINATION_LIMIT_MAX: f32 = 14.0;
-
-    fn clone_from_cell(cell: &Cell) -> Self {
-        if let Some(grasses) = &cell.grasses {
-            grasses.clone()
-        } else {
-            Grasses::new()
-        }
+    fn get_species_params<'a>(&self, config: &'a Config) -> &'a SpeciesParams {
+        &config.species.bushes
     }
 
+    const DIES_OF_DROUGHT: bool = true;
+}
+
+impl Vegetation for Grasses {
     fn estimate_biomass(&self) -> f32 {
         self.estimate_biomass()
     }
 
     fn get_illumination_coverage_constant(cell: &Cell) -> f32 {
-        let mut modifier = 1.0;
-        if let Some(trees) = &cell.trees {
-            let tree_density = Cell::estimate_tree_density(trees);
-            // todo placeholder value
-            modifier -= 0.25 * tree_density;
-        }
-        if let Some(bushes) = &cell.bushes {
-            let bushes_density = Cell::estimate_bushes_density(bushes);
-            // todo placeholder value
-            modifier -= 0.25 * bushes_density;
-        }
-        modifier
+        compute_light_transmission(estimate_leaf_area_index(cell))
+    }
+
+    fn get_species_params<'a>(&self, config: &'a Config) -> &'a SpeciesParams {
+        &config.species.grasses
     }
 }
 
-pub(crate) trait Individualized {
-    // number of new plants per square meter per year
-    const ESTABLISHMENT_RATE: f32;
-    // impact of density on seedling count
-    const SEEDLING_DENSITY_CONSTANT: f32;
-    // impact of vigor on seedlign count
-    const SEEDLING_VIGOR_CONSTANT: f32;
-    // meter per plant per year
-    const GROWTH_RATE: f32;
-    const LIFE_EXPECTANCY: f32;
-    // impact of stress on number of plants
-    const STRESS_DEATH_CONSTANT: f32;
-    // impact of age on number of plants
-    const SENESCENCE_DEATH_CONSTANT: f32;
-
-    fn init(number_of_plants: u32, plant_height_sum: f32, plant_age_sum: f32) -> Self;
-    fn set_in_cell(self, cell: &mut Cell);
-    fn estimate_density(&self) -> f32;
-    fn get_number_of_plants(&self) -> u32;
-    fn get_plant_height_sum(&self) -> f32;
-    fn get_plant_age_sum(&self) -> f32;
-    fn update_number_of_plants(&mut self, amount: i32);
-    fn update_plant_height_sum(&mut self, amount: f32);
-    fn update_plant_age_sum(&mut self, amount: f32);
-    fn kill_plants(&mut self, amount: u32);
+fn estimate_trees_leaf_area_index(cell: &Cell) -> f32 {
+    cell.estimate_total_tree_density() * TREES_LEAF_AREA_INDEX
 }
 
-impl Individualized for Trees {
-    const ESTABLISHMENT_RATE: f32 = 0.24;
-    const SEEDLING_DENSITY_CONSTANT: f32 = 0.05;
-    const SEEDLING_VIGOR_CONSTANT: f32 = 0.5;
-    const GROWTH_RATE: f32 = 0.3;
-    const LIFE_EXPECTANCY: f32 = 80.0;
-    const STRESS_DEATH_CONSTANT: f32 = 5.0;
-    const SENESCENCE_DEATH_CONSTANT: f32 = 0.05;
-
-    fn init(number_of_plants: u32, plant_height_sum: f32, plant_age_sum: f32) -> Self {
-        Trees {
-            number_of_plants,
-            plant_height_sum,
-            plant_age_sum,
-        }
-    }
+// leaf area index of the trees and bushes of the cell together
+pub fn estimate_leaf_area_index(cell: &Cell) -> f32 {
+    let bushes_leaf_area_index = cell.bushes.as_ref().map_or(0.0, |bushes| {
+        Cell::estimate_bushes_density(bushes) * BUSHES_LEAF_AREA_INDEX
+    });
+    estimate_trees_leaf_area_index(cell) + bushes_leaf_area_index
+}
 
-    fn set_in_cell(self, cell: &mut Cell) {
-        if self.get_number_of_plants() > 0 {
-            cell.trees = Some(self);
-        } else {
-            cell.trees = None;
-        }
-    }
+// leaf area index of all the green leaves of the cell, which transpire water
+// dormant grass does not count
+pub fn estimate_transpiring_leaf_area_index(cell: &Cell) -> f32 {
+    let grasses_leaf_area_index = cell.grasses.as_ref().map_or(0.0, |grasses| {
+        grasses.coverage_density * grasses.green_fraction * GRASSES_LEAF_AREA_INDEX
+    });
+    estimate_leaf_area_index(cell) + grasses_leaf_area_index
+}
 
-    fn estimate_density(&self) -> f32 {
-        Cell::estimate_tree_density(self)
-    }
+// returns % of light passing through leaves of the given leaf area index
+pub fn compute_light_transmission(leaf_area_index: f32) -> f32 {
+    f32::exp(-LIGHT_EXTINCTION_COEFFICIENT * leaf_area_index)
+}
+
+pub trait Individualized {
+    fn set_in_cell(self, cell: &mut Cell);
+    fn estimate_density(&self) -> f32;
+    // density of this vegetation plus all other vegetation in the cell competing for the same space
+    fn estimate_competing_density(&self, cell: &Cell) -> f32;
+    // age classes, from the youngest to the oldest
+    fn get_cohorts(&self) -> &[Cohort];
+    fn get_cohorts_mut(&mut self) -> &mut Vec<Cohort>;
+    fn get_dry_months_mut(&mut self) -> &mut f32;
 
     fn get_number_of_plants(&self) -> u32 {
-        self.number_of_plants
+        self.get_cohorts().iter().map(|c| c.number_of_plants).sum()
     }
 
     fn get_plant_height_sum(&self) -> f32 {
-        self.plant_height_sum
+        self.get_cohorts().iter().map(|c| c.plant_height_sum).sum()
     }
 
     fn get_plant_age_sum(&self) -> f32 {
-        self.plant_age_sum
+        self.get_cohorts().iter().map(|c| c.plant_age_sum).sum()
     }
 
-    fn update_number_of_plants(&mut self, amount: i32) {
-        if amount > 0 {
-            self.number_of_plants += amount as u32;
-        } else {
-            self.number_of_plants -= (-amount) as u32;
+    // seedlings join the youngest age class until it grows too old, then start a new one
+    fn add_seedlings(&mut self, amount: u32) {
+        if amount == 0 {
+            return;
+        }
+        let cohorts = self.get_cohorts_mut();
+        match cohorts.first_mut() {
+            Some(youngest) if youngest.get_average_age() < AGE_CLASS_WIDTH => {
+                youngest.number_of_plants += amount;
+            }
+            _ => cohorts.insert(
+                0,
+                Cohort {
+                    number_of_plants: amount,
+                    plant_height_sum: 0.0,
+                    plant_age_sum: 0.0,
+                },
+            ),
         }
     }
 
-    fn update_plant_height_sum(&mut self, amount: f32) {
-        self.plant_height_sum += amount;
-    }
-
-    fn update_plant_age_sum(&mut self, amount: f32) {
-        self.plant_age_sum += amount;
+    // every plant grows and ages by the same amount
+    fn grow_plants(&mut self, growth_rate: f32, years: f32) {
+        for cohort in self.get_cohorts_mut() {
+            cohort.plant_height_sum += cohort.number_of_plants as f32 * growth_rate * years;
+            cohort.plant_age_sum += cohort.number_of_plants as f32 * years;
+        }
     }
 
+    // kills from the youngest age class up, as seedlings are the first to be crowded out or stressed
     fn kill_plants(&mut self, amount: u32) {
-        if amount >= self.number_of_plants {
-            self.number_of_plants = 0;
-            self.plant_height_sum = 0.0;
-            self.plant_age_sum = 0.0;
-        } else {
-            let average_plant_height =
-                self.get_plant_height_sum() / self.get_number_of_plants() as f32;
-            let average_plant_age = self.get_plant_age_sum() / self.get_number_of_plants() as f32;
-            self.update_number_of_plants(-(amount as i32));
-            self.update_plant_height_sum(-(amount as f32) * average_plant_height);
-            self.update_plant_age_sum(-(amount as f32) * average_plant_age);
+        let mut remaining = amount;
+        let cohorts = self.get_cohorts_mut();
+        for cohort in cohorts.iter_mut() {
+            let killed = remaining.min(cohort.number_of_plants);
+            cohort.remove_plants(killed);
+            remaining -= killed;
         }
+        cohorts.retain(|c| c.number_of_plants > 0);
     }
 }
 
-impl Individualized for Bushes {
-    const ESTABLISHMENT_RATE: f32 = 0.24;
-    const SEEDLING_DENSITY_CONSTANT: f32 = 0.05;
-    const SEEDLING_VIGOR_CONSTANT: f32 = 0.5;
-    const GROWTH_RATE: f32 = 0.2;
-    const LIFE_EXPECTANCY: f32 = 20.0;
-    const STRESS_DEATH_CONSTANT: f32 = 5.0;
-    const SENESCENCE_DEATH_CONSTANT: f32 = 0.05;
-
-    fn init(number_of_plants: u32, plant_height_sum: f32, plant_age_sum: f32) -> Self {
-        Bushes {
-            number_of_plants,
-            plant_height_sum,
-            plant_age_sum,
-        }
+impl Individualized for Trees {
+    fn set_in_cell(self, cell: &mut Cell) {
+        cell.set_trees(self);
+    }
+
+    fn estimate_density(&self) -> f32 {
+        Cell::estimate_tree_density(self)
+    }
+
+    // trees of all species share the canopy
+    fn estimate_competing_density(&self, cell: &Cell) -> f32 {
+        let other_species_density: f32 = cell
+            .trees
+            .iter()
+            .filter(|trees| trees.species != self.species)
+            .map(Cell::estimate_tree_density)
+            .sum();
+        self.estimate_density() + other_species_density
+    }
+
+    fn get_cohorts(&self) -> &[Cohort] {
+        &self.cohorts
     }
 
+    fn get_cohorts_mut(&mut self) -> &mut Vec<Cohort> {
+        &mut self.cohorts
+    }
+
+    fn get_dry_months_mut(&mut self) -> &mut f32 {
+        &mut self.dry_months
+    }
+}
+
+impl Individualized for Bushes {
     fn set_in_cell(self, cell: &mut Cell) {
         if self.get_number_of_plants() > 0 {
             cell.bushes = Some(self);
@@ -301,96 +354,157 @@ impl Individualized for Bushes {
         Cell::estimate_bushes_density(self)
     }
 
-    fn get_number_of_plants(&self) -> u32 {
-        self.number_of_plants
+    // bushes grow under the canopy so only compete with each other
+    fn estimate_competing_density(&self, _: &Cell) -> f32 {
+        self.estimate_density()
     }
 
-    fn get_plant_height_sum(&self) -> f32 {
-        self.plant_height_sum
+    fn get_cohorts(&self) -> &[Cohort] {
+        &self.cohorts
     }
 
-    fn get_plant_age_sum(&self) -> f32 {
-        self.plant_age_sum
+    fn get_cohorts_mut(&mut self) -> &mut Vec<Cohort> {
+        &mut self.cohorts
     }
 
-    fn update_number_of_plants(&mut self, amount: i32) {
-        if amount > 0 {
-            self.number_of_plants += amount as u32;
-        } else {
-            self.number_of_plants -= (-amount) as u32;
-        }
+    fn get_dry_months_mut(&mut self) -> &mut f32 {
+        &mut self.dry_months
     }
+}
 
-    fn update_plant_height_sum(&mut self, amount: f32) {
-        self.plant_height_sum += amount;
+impl Events {
+    pub fn apply_trees_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+    ) -> Option<(Events, CellIndex)> {
+        let vigor_and_stress = Self::compute_trees_vigor_and_stress(ecosystem, index);
+        Self::apply_precomputed_trees_event(ecosystem, index, &vigor_and_stress)
     }
 
-    fn update_plant_age_sum(&mut self, amount: f32) {
-        self.plant_age_sum += amount;
+    // returns vigor and stress of every tree species, indexed by species
+    pub fn compute_trees_vigor_and_stress(
+        ecosystem: &Ecosystem,
+        index: CellIndex,
+    ) -> Vec<(f32, f32)> {
+        (0..ecosystem.config.species.trees.len())
+            .map(|species| {
+                let trees = Trees::clone_from_cell(&ecosystem[index], species);
+                Self::compute_vigor_and_stress(ecosystem, index, &trees)
+            })
+            .collect()
     }
 
-    fn kill_plants(&mut self, amount: u32) {
-        if amount >= self.number_of_plants {
-            self.number_of_plants = 0;
-            self.plant_height_sum = 0.0;
-            self.plant_age_sum = 0.0;
-        } else {
-            let average_plant_height =
-                self.get_plant_height_sum() / self.get_number_of_plants() as f32;
-            let average_plant_age = self.get_plant_age_sum() / self.get_number_of_plants() as f32;
-            self.update_number_of_plants(-(amount as i32));
-            self.update_plant_height_sum(-(amount as f32) * average_plant_height);
-            self.update_plant_age_sum(-(amount as f32) * average_plant_age);
+    // applies a trees event to every species using vigor and stress from `compute_trees_vigor_and_stress`
+    pub fn apply_precomputed_trees_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+        vigor_and_stress: &[(f32, f32)],
+    ) -> Option<(Events, CellIndex)> {
+        let mut new_dead_biomass = 0.0;
+        // each species competes with the species updated before it
+        for (species, vigor_and_stress) in vigor_and_stress.iter().enumerate() {
+            let trees = Trees::clone_from_cell(&ecosystem[index], species);
+            new_dead_biomass +=
+                Self::update_individualized_vegetation(ecosystem, index, trees, *vigor_and_stress);
         }
+        Self::decompose_dead_vegetation(ecosystem, index, new_dead_biomass);
+
+        // does not propagate
+        None
     }
-}
 
-impl Events {
-    pub(crate) fn apply_trees_event(
+    pub fn apply_bushes_event(
         ecosystem: &mut Ecosystem,
         index: CellIndex,
     ) -> Option<(Events, CellIndex)> {
-        let cell = &ecosystem[index];
-        let trees = Trees::clone_from_cell(cell);
-        Self::apply_individualized_vegetation_event(ecosystem, index, trees)
+        let bushes = Bushes::clone_from_cell(&ecosystem[index]);
+        let vigor_and_stress = Self::compute_vigor_and_stress(ecosystem, index, &bushes);
+        Self::apply_precomputed_bushes_event(ecosystem, index, vigor_and_stress)
+    }
+
+    // applies a bushes event using vigor and stress from `compute_vigor_and_stress`
+    pub fn apply_precomputed_bushes_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+        vigor_and_stress: (f32, f32),
+    ) -> Option<(Events, CellIndex)> {
+        let bushes = Bushes::clone_from_cell(&ecosystem[index]);
+        let new_dead_biomass =
+            Self::update_individualized_vegetation(ecosystem, index, bushes, vigor_and_stress);
+        Self::decompose_dead_vegetation(ecosystem, index, new_dead_biomass);
+
+        // does not propagate
+        None
     }
 
-    pub(crate) fn apply_bushes_event(
+    pub fn apply_grasses_event(
         ecosystem: &mut Ecosystem,
         index: CellIndex,
     ) -> Option<(Events, CellIndex)> {
         let cell = &ecosystem[index];
-        let bushes = Bushes::clone_from_cell(cell);
-        Self::apply_individualized_vegetation_event(ecosystem, index, bushes)
+        let grasses = Grasses::clone_from_cell(cell);
+        let vigor_and_stress = Self::compute_vigor_and_stress(ecosystem, index, &grasses);
+        Self::apply_precomputed_grasses_event(ecosystem, index, vigor_and_stress)
     }
 
-    pub(crate) fn apply_grasses_event(
+    // applies a grasses event using vigor and stress from `compute_vigor_and_stress`
+    pub fn apply_precomputed_grasses_event(
         ecosystem: &mut Ecosystem,
         index: CellIndex,
+        (vigor, stress): (f32, f32),
     ) -> Option<(Events, CellIndex)> {
         // treat grasses as a collective over the entire cell
         let cell = &ecosystem[index];
         let grasses = Grasses::clone_from_cell(cell);
-        let (vigor, stress) = Self::compute_vigor_and_stress(ecosystem, index, &grasses);
         // if index == CellIndex::new(30,30) {
         //     println!("vigor {vigor} stress {stress}, density {}", grasses.coverage_density);
         // }
         // directly modify coverage based on vigor and stress
+        let fraction_of_year = ecosystem.get_fraction_of_year();
         let mut new_coverage = grasses.coverage_density;
+        let mut green_coverage = grasses.coverage_density * grasses.green_fraction;
         if stress < 0.0 {
-            let death_coverage = (-stress) * GRASSES_STRESS_DEATH;
+            let death_coverage = (-stress) * GRASSES_STRESS_DEATH * fraction_of_year;
             new_coverage -= death_coverage;
+            green_coverage -= death_coverage * grasses.green_fraction;
 
             // convert to dead_vegetation
             let dead_biomass = Grasses::estimate_biomass_for_coverage_density(death_coverage);
             assert!(dead_biomass > 0.0, "{dead_biomass}");
             let cell = &mut ecosystem[index];
             cell.add_dead_vegetation(dead_biomass);
-        } else if vigor > 0.0 {
-            // growth only if no stress
-            new_coverage += vigor * GRASSES_VIGOR_GROWTH;
+        } else if vigor > 0.0
+            && (grasses.coverage_density > 0.0 || !coast::is_in_salt_spray(ecosystem, index))
+        {
+            // growth only if no stress, and new grass does not take root in salt spray
+            let growth_coverage = vigor * GRASSES_VIGOR_GROWTH * fraction_of_year;
+            new_coverage += growth_coverage;
+            // new growth is green
+            green_coverage += growth_coverage;
+
+            // new grass takes up nitrogen
+            let new_biomass = Grasses::estimate_biomass_for_coverage_density(growth_coverage);
+            Self::take_up_nutrients(&mut ecosystem[index], new_biomass);
+        }
+
+        // browsers eat the grass, which leaves the cell with them instead of dying back into it
+        let eaten_coverage = ecosystem.config.browsing.as_ref().map_or(0.0, |browsing| {
+            browsing.get_pressure(index) * browsing.grass_consumption * fraction_of_year
+        });
+        if eaten_coverage > 0.0 {
+            let eaten_coverage = f32::min(eaten_coverage, f32::max(new_coverage, 0.0));
+            new_coverage -= eaten_coverage;
+            // green blades are eaten first
+            green_coverage = f32::max(green_coverage - eaten_coverage, 0.0);
         }
 
+        let green_fraction = if new_coverage > 0.0 {
+            let green_fraction = (green_coverage / new_coverage).clamp(0.0, 1.0);
+            Self::update_green_fraction(ecosystem, index, green_fraction)
+        } else {
+            1.0
+        };
+
         // handle overpopulation
         if new_coverage > 1.0 {
             let death_coverage = new_coverage - 1.0;
@@ -406,6 +520,7 @@ impl Events {
         let new_grasses = if new_coverage > 0.0 {
             Some(Grasses {
                 coverage_density: new_coverage,
+                green_fraction,
             })
         } else {
             None
@@ -416,98 +531,203 @@ impl Events {
         None
     }
 
-    pub(crate) fn apply_individualized_vegetation_event<
-        T: Vegetation + Individualized + std::fmt::Debug,
-    >(
+    // greens up or cures grass following the temperature of each month of the time step
+    // returns the green fraction averaged over the time step, like soil moisture for fires
+    fn update_green_fraction(ecosystem: &Ecosystem, index: CellIndex, green_fraction: f32) -> f32 {
+        let cell = &ecosystem[index];
+        let photosynthesis = ecosystem.config.species.grasses.photosynthesis;
+        let mut green_fraction = green_fraction;
+        let mut green_sum = 0.0;
+        let mut covered = 0.0;
+        for (month, fraction) in ecosystem.get_months_of_time_step() {
+            let temperature = cell.get_monthly_temperature(&ecosystem.config.climate, month);
+            let target = photosynthesis.get_green_fraction(temperature);
+            let rate = f32::min(GRASSES_PHENOLOGY_RATE * fraction, 1.0);
+            green_fraction += (target - green_fraction) * rate;
+            green_sum += green_fraction * fraction;
+            covered += fraction;
+        }
+        green_sum / covered
+    }
+
+    // germinates, grows, and kills plants of the vegetation and stores it in the cell
+    // returns biomass of the plants that died
+    fn update_individualized_vegetation<T: Vegetation + Individualized + std::fmt::Debug>(
         ecosystem: &mut Ecosystem,
         index: CellIndex,
         mut vegetation: T,
-    ) -> Option<(Events, CellIndex)> {
+        (vigor, stress): (f32, f32),
+    ) -> f32 {
         let mut new_dead_biomass = 0.0;
+        let params = vegetation.get_species_params(&ecosystem.config).clone();
+        // rates are per year, so shorter time steps only get part of them
+        let fraction_of_year = ecosystem.get_fraction_of_year();
 
-        let (vigor, stress) = Self::compute_vigor_and_stress(ecosystem, index, &vegetation);
+        // biomass before germination and growth, to take up nitrogen for what is added
+        let initial_biomass = vegetation.estimate_biomass();
 
         // Germination
-        let mut density = vegetation.estimate_density();
+        let mut density = vegetation.estimate_competing_density(&ecosystem[index]);
         // println!("vigor {vigor}, stress {stress}, density {density}");
-        if stress == 0.0 && density < 1.0 {
+        // salt spray kills seedlings on the lowest ground of the coast
+        if stress == 0.0 && density < 1.0 && !coast::is_in_salt_spray(ecosystem, index) {
             // convert establishment rate from plants per square meter to plants per cell
-            let mut seedling_count =
-                (T::ESTABLISHMENT_RATE * constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH)
-                    * (T::SEEDLING_DENSITY_CONSTANT * (1.0 - density))
-                    * T::SEEDLING_VIGOR_CONSTANT
-                    * vigor;
+            let mut seedling_count = (params.establishment_rate
+                * constants::CELL_SIDE_LENGTH
+                * constants::CELL_SIDE_LENGTH)
+                * (params.seedling_density_constant * (1.0 - density))
+                * params.seedling_vigor_constant
+                * vigor
+                * fraction_of_year
+                * (1.0 - Self::get_browsing_pressure(ecosystem, index));
             // if seedling count is < 0, use it as probability of new seedling
             if seedling_count > 0.0 && seedling_count < 1.0 {
-                let mut rng = rand::thread_rng();
-                let rand: f32 = rng.gen();
+                let rand: f32 = ecosystem.rng.gen();
                 if rand < seedling_count {
                     seedling_count = 1.0;
                 }
             }
-            vegetation.update_number_of_plants(seedling_count as i32);
+            vegetation.add_seedlings(seedling_count as u32);
         }
         // println!("Vegetation initial {vegetation:?}");
 
         // need non-zero vegetation from here on
         if vegetation.get_number_of_plants() > 0 {
             // Growth
-            vegetation
-                .update_plant_height_sum(vegetation.get_number_of_plants() as f32 * T::GROWTH_RATE);
-            vegetation.update_plant_age_sum(vegetation.get_number_of_plants() as f32);
+            vegetation.grow_plants(params.growth_rate, fraction_of_year);
+            let pre_death_biomass = vegetation.estimate_biomass();
+            let new_biomass = f32::max(pre_death_biomass - initial_biomass, 0.0);
+            Self::take_up_nutrients(&mut ecosystem[index], new_biomass);
 
-            // Death from three factors
-            let pre_death_count = vegetation.get_number_of_plants();
-            let pre_death_average_height =
-                vegetation.get_plant_height_sum() / pre_death_count as f32;
+            // Death from four factors
 
             // 1) overpopulation
             while density > 1.0 && vegetation.get_number_of_plants() > 1 {
                 vegetation.kill_plants(1);
-                density = vegetation.estimate_density();
+                density = vegetation.estimate_competing_density(&ecosystem[index]);
             }
 
             // 2) stress (non-positive real number)
-            let stress_deaths = ((-stress) * T::STRESS_DEATH_CONSTANT) as u32;
+            let stress_deaths = Self::prorate_deaths(
+                &mut ecosystem.rng,
+                (-stress) * params.stress_death_constant,
+                fraction_of_year,
+            ) as u32;
             // println!("stress_deaths {stress_deaths}");
             vegetation.kill_plants(stress_deaths);
 
-            // 3) old age
-            let average_age =
-                vegetation.get_plant_age_sum() / vegetation.get_number_of_plants() as f32;
-            let old_age_deaths = if average_age > T::LIFE_EXPECTANCY {
-                f32::ceil(
-                    (1.0 - T::SENESCENCE_DEATH_CONSTANT) * vegetation.get_number_of_plants() as f32,
-                ) as u32
-            } else {
-                0
-            };
-            // println!("old_age_deaths {old_age_deaths}");
-            vegetation.kill_plants(old_age_deaths);
-
-            // create temporary new plant struct to calculate biomass
-            let total_dead = pre_death_count - vegetation.get_number_of_plants();
-            let dead_vegetation = T::init(
-                total_dead,
-                total_dead as f32 * pre_death_average_height,
-                0.0,
-            );
+            // 3) drought, once it lasts longer than the species gets through
+            let drought_deaths = Self::update_drought(ecosystem, index, &mut vegetation, &params);
+            vegetation.kill_plants(drought_deaths);
+
+            // 4) old age, only in the age classes past their life expectancy
+            for cohort in vegetation.get_cohorts_mut() {
+                if cohort.get_average_age() > params.life_expectancy {
+                    let old_age_deaths = f32::ceil(Self::prorate_deaths(
+                        &mut ecosystem.rng,
+                        (1.0 - params.senescence_death_constant) * cohort.number_of_plants as f32,
+                        fraction_of_year,
+                    )) as u32;
+                    cohort.remove_plants(old_age_deaths);
+                }
+            }
+            vegetation
+                .get_cohorts_mut()
+                .retain(|c| c.number_of_plants > 0);
 
             // conversion to dead vegetation
-            new_dead_biomass += dead_vegetation.estimate_biomass();
+            new_dead_biomass += f32::max(pre_death_biomass - vegetation.estimate_biomass(), 0.0);
+        }
+
+        vegetation.set_in_cell(&mut ecosystem[index]);
+        new_dead_biomass
+    }
+
+    // counts the months in a row the plants are in drought over the time step,
+    // and returns how many of them die of the months past the tolerance of their species
+    fn update_drought<T: Vegetation + Individualized>(
+        ecosystem: &Ecosystem,
+        index: CellIndex,
+        vegetation: &mut T,
+        params: &SpeciesParams,
+    ) -> u32 {
+        let mut surviving = 1.0;
+        for (month, fraction) in ecosystem.get_months_of_time_step() {
+            let in_drought = Self::is_in_drought(ecosystem, index, vegetation, month);
+            let dry_months = vegetation.get_dry_months_mut();
+            if !in_drought {
+                *dry_months = 0.0;
+                continue;
+            }
+            let past_tolerance =
+                (*dry_months + fraction - params.drought_tolerance).clamp(0.0, fraction);
+            *dry_months += fraction;
+            surviving *= (1.0 - DROUGHT_DEATH_RATE).powf(past_tolerance);
         }
+        (vegetation.get_number_of_plants() as f32 * (1.0 - surviving)).round() as u32
+    }
 
+    // returns whether the soil the roots of the plant reach is too dry for it in the month
+    pub fn is_in_drought<T: Vegetation>(
+        ecosystem: &Ecosystem,
+        index: CellIndex,
+        vegetation: &T,
+        month: usize,
+    ) -> bool {
+        let params = vegetation.get_species_params(&ecosystem.config);
+        Self::compute_available_moisture(ecosystem, index, month, params.rooting_depth)
+            < params.moisture_limit_min
+    }
+
+    // browsing pressure on the cell, 0 when the config has no browsing
+    fn get_browsing_pressure(ecosystem: &Ecosystem, index: CellIndex) -> f32 {
+        ecosystem
+            .config
+            .browsing
+            .as_ref()
+            .map_or(0.0, |browsing| browsing.get_pressure(index))
+    }
+
+    // scales deaths per year to the time step
+    // shorter time steps round the deaths up or down at random so that they still add up over a year
+    pub fn prorate_deaths(rng: &mut impl Rng, deaths_per_year: f32, fraction_of_year: f32) -> f32 {
+        if fraction_of_year >= 1.0 {
+            return deaths_per_year;
+        }
+        let deaths = deaths_per_year * fraction_of_year;
+        let rand: f32 = rng.gen();
+        if rand < deaths.fract() {
+            deaths.ceil()
+        } else {
+            deaths.floor()
+        }
+    }
+
+    // rots away and converts to humus part of the dead vegetation from the previous time steps,
+    // releasing its nitrogen, then adds the newly dead biomass
+    fn decompose_dead_vegetation(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+        new_dead_biomass: f32,
+    ) {
+        // the base rates are scaled by how warm and moist the soil is
+        let decomposed_fraction =
+            ecosystem.get_fraction_of_year() * Self::compute_decomposition_factor(ecosystem, index);
         let cell = &mut ecosystem[index];
-        vegetation.set_in_cell(cell);
 
         // let some dead vegetation rot away into CO2
         let disappeared_dead_biomass =
-            cell.get_dead_vegetation_biomass() * DEAD_VEGETATION_TO_CO2_RATE;
+            cell.get_dead_vegetation_biomass() * DEAD_VEGETATION_TO_CO2_RATE * decomposed_fraction;
 
         // convert dead vegetation (from last year) to humus
-        let new_humus = Self::convert_dead_vegetation_to_humus(cell.get_dead_vegetation_biomass());
+        let humified_biomass = cell.get_dead_vegetation_biomass() * decomposed_fraction;
+        let new_humus = Self::convert_dead_vegetation_to_humus(humified_biomass);
 
-        cell.remove_dead_vegetation(disappeared_dead_biomass);
+        let disappeared_dead_biomass = cell.remove_dead_vegetation(disappeared_dead_biomass);
+        Self::release_nutrients(
+            cell,
+            disappeared_dead_biomass + DEAD_VEGETATION_TO_HUMUS_RATE * humified_biomass,
+        );
         // cell.remove_all_dead_vegetation();
         assert!(new_humus >= 0.0, "{new_humus}");
         cell.add_humus(new_humus);
@@ -518,39 +738,41 @@ impl Events {
             "new_dead_biomass {new_dead_biomass}"
         );
         cell.add_dead_vegetation(new_dead_biomass);
-
-        // does not propagate
-        None
     }
 
     // given an amount of biomass, determine the height of humus to be produced
     fn convert_dead_vegetation_to_humus(biomass: f32) -> f32 {
-        let converted_biomass = DEAD_VEGETATION_TO_HUMUS_RATE * biomass;
-        converted_biomass
-            / (constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH * HUMUS_DENSITY)
+        let converted_biomass = Kilograms(DEAD_VEGETATION_TO_HUMUS_RATE * biomass);
+        (converted_biomass / HUMUS_DENSITY).spread_over_cell().0
     }
 
-    // returns tuple of vigor and stress
+    // returns tuple of vigor and stress over the months covered by the time step
     // vigor is average viability during growing season (T > 5°C)
-    // stress is average of 4 worst negative viabilities
-    fn compute_vigor_and_stress<T: Vegetation>(
+    // stress is average of 4 worst negative viabilities, leaving out drought for plants that die of it
+    pub fn compute_vigor_and_stress<T: Vegetation>(
         ecosystem: &Ecosystem,
         index: CellIndex,
         vegetation: &T,
     ) -> (f32, f32) {
-        let mut viabilities = [0.0; 12];
+        let mut viabilities = vec![];
         let mut growing_viabilities = vec![];
-        for (i, value) in viabilities.iter_mut().enumerate() {
-            let viability = Self::compute_viability(ecosystem, index, vegetation, i);
-            *value = viability;
-            if constants::AVERAGE_MONTHLY_TEMPERATURES[i] > 5.0 {
+        for (month, _) in ecosystem.get_months_of_time_step() {
+            let (viability, stress_viability) =
+                Self::compute_viabilities(ecosystem, index, vegetation, month);
+            viabilities.push(stress_viability);
+            if ecosystem.config.climate.monthly_temperatures[month] > 5.0 {
                 growing_viabilities.push(viability);
             }
         }
 
         // vigor is average viability during growing season (T > 5°C)
+        // and there is no growth in time steps outside of it
         let num_months = growing_viabilities.len();
-        let vigor = growing_viabilities.into_iter().sum::<f32>() / num_months as f32;
+        let vigor = if num_months == 0 {
+            0.0
+        } else {
+            growing_viabilities.into_iter().sum::<f32>() / num_months as f32
+        };
 
         // stress is average of 4 worst negative viabilities
         let mut negative_viabilities = viabilities.into_iter().filter(|v| *v < 0.0).collect_vec();
@@ -563,20 +785,22 @@ impl Events {
         (vigor, stress)
     }
 
-    // returns viability for a given plant for a given month
-    fn compute_viability<T: Vegetation>(
+    // returns viability for a given plant for a given month, and the viability that stresses it,
+    // which leaves out drought for plants that die of drought instead
+    fn compute_viabilities<T: Vegetation>(
         ecosystem: &Ecosystem,
         index: CellIndex,
         vegetation: &T,
         month: usize,
-    ) -> f32 {
-        // determines viability from piecewise function evaluating all three of temperature, moisture, and sunlight
+    ) -> (f32, f32) {
+        // determines viability from piecewise function evaluating all four of temperature, moisture, sunlight, and nitrogen
         let temperature_viability =
             Self::compute_temperature_viability(ecosystem, index, vegetation, month);
         let moisture_viability = //0.5; // todo remove
             Self::compute_moisture_viability(ecosystem, index, vegetation, month);
         let illumination_viability =
             Self::compute_illumination_viability(ecosystem, index, vegetation, month);
+        let nutrient_viability = Self::compute_nutrient_viability(ecosystem, index);
         // if index == CellIndex::new(30,30) && std::any::type_name::<T>() == "vegetables_and_hummus::ecology::Grasses"{
         //     println!("type {}", std::any::type_name::<T>());
         //     println!("temperature_viability {temperature_viability}");
@@ -586,78 +810,90 @@ impl Events {
         // }
 
         // viability is lowest of the the sub-values (Leibig’s law of the minimum)
-        f32::min(
-            temperature_viability,
-            f32::min(moisture_viability, illumination_viability),
-        )
+        let viability_except_moisture = [illumination_viability, nutrient_viability]
+            .into_iter()
+            .fold(temperature_viability, f32::min);
+        let viability = f32::min(moisture_viability, viability_except_moisture);
+        if T::DIES_OF_DROUGHT && Self::is_in_drought(ecosystem, index, vegetation, month) {
+            (viability, viability_except_moisture)
+        } else {
+            (viability, viability)
+        }
     }
 
     fn compute_temperature_viability<T: Vegetation>(
         ecosystem: &Ecosystem,
         index: CellIndex,
-        _: &T,
+        vegetation: &T,
         month: usize,
     ) -> f32 {
-        let cell = &ecosystem[index];
-        let temperature = cell.get_monthly_temperature(month);
+        let params = vegetation.get_species_params(&ecosystem.config);
+        let temperature = ecosystem.get_local_temperature(index, month);
         match temperature {
-            temperature if temperature < T::TEMPERATURE_LIMIT_MIN => -1.0,
-            temperature if temperature < T::TEMPERATURE_IDEAL_MIN => {
-                (temperature - T::TEMPERATURE_LIMIT_MIN)
-                    / (T::TEMPERATURE_IDEAL_MIN - T::TEMPERATURE_LIMIT_MIN)
+            temperature if temperature < params.temperature_limit_min => -1.0,
+            temperature if temperature < params.temperature_ideal_min => {
+                (temperature - params.temperature_limit_min)
+                    / (params.temperature_ideal_min - params.temperature_limit_min)
             }
-            temperature if temperature <= T::TEMPERATURE_IDEAL_MAX => 1.0,
-            temperature if temperature <= T::TEMPERATURE_LIMIT_MAX => {
-                (temperature - T::TEMPERATURE_LIMIT_MAX)
-                    / (T::TEMPERATURE_IDEAL_MAX - T::TEMPERATURE_LIMIT_MAX)
+            temperature if temperature <= params.temperature_ideal_max => 1.0,
+            temperature if temperature <= params.temperature_limit_max => {
+                (temperature - params.temperature_limit_max)
+                    / (params.temperature_ideal_max - params.temperature_limit_max)
             }
             _ => -1.0,
         }
     }
 
-    pub(crate) fn compute_moisture(ecosystem: &Ecosystem, index: CellIndex, month: usize) -> f32 {
+    // returns % soil moisture of the topsoil, which is the topmost soil layer of the cell
+    pub fn compute_moisture(ecosystem: &Ecosystem, index: CellIndex, month: usize) -> f32 {
+        Self::compute_available_moisture(ecosystem, index, month, 0.0)
+    }
+
+    // returns % soil moisture of the wettest soil layer that roots of the given depth reach
+    // bedrock holds no water, so plants on shallow soil only draw on the topsoil
+    pub fn compute_available_moisture(
+        ecosystem: &Ecosystem,
+        index: CellIndex,
+        month: usize,
+        rooting_depth: f32,
+    ) -> f32 {
         let cell = &ecosystem[index];
-        // convert moisture in terms of volume to % by volume
-        let moisture_volume = cell.get_monthly_soil_moisture(month);
-        // in L
-        // bedrock, rock, sand, and humus can all hold water, but make simplifying assumption that all water makes it to humus layer
-        // so each cell is 10x10xheight m, where height is height of humus
-        // 1 cubic meter = 1000 liters
-
-        let height = cell.get_humus_height();
-        let cell_volume =
-            constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH * height * 1000.0; // in L
-                                                                                         // if index == CellIndex::new(5,5) {
-                                                                                         //     println!("moisture_volume {moisture_volume}");
-                                                                                         //     println!("height {height}");
-                                                                                         //     println!("cell_volume {cell_volume}");
-                                                                                         // }
-        if cell_volume == 0.0 {
-            0.0
-        } else {
-            f32::min(moisture_volume / cell_volume, 1.0)
+        let moistures = cell.get_monthly_moisture_profile(&ecosystem.config.climate, month);
+        let mut depth = 0.0;
+        let mut available = 0.0;
+        for (height, moisture) in cell.get_soil_layer_heights().into_iter().zip(moistures) {
+            if height == 0.0 {
+                continue;
+            }
+            if depth > rooting_depth {
+                break;
+            }
+            available = f32::max(available, moisture);
+            depth += height;
         }
+        available
     }
 
     fn compute_moisture_viability<T: Vegetation>(
         ecosystem: &Ecosystem,
         index: CellIndex,
-        _: &T,
+        vegetation: &T,
         month: usize,
     ) -> f32 {
-        let moisture = Self::compute_moisture(ecosystem, index, month);
-        // if index == CellIndex::new(5,5) {
-        //     println!("moisture {moisture}");
-        // }
+        let params = vegetation.get_species_params(&ecosystem.config);
+        let moisture =
+            Self::compute_available_moisture(ecosystem, index, month, params.rooting_depth);
 
         match moisture {
-            moisture if moisture < T::MOISTURE_LIMIT_MIN => -1.0,
-            moisture if moisture < T::MOISTURE_IDEAL_MIN => {
-                (moisture - T::MOISTURE_LIMIT_MIN) / (T::MOISTURE_IDEAL_MIN - T::MOISTURE_LIMIT_MIN)
+            moisture if moisture < params.moisture_limit_min => -1.0,
+            moisture if moisture < params.moisture_ideal_min => {
+                (moisture - params.moisture_limit_min)
+                    / (params.moisture_ideal_min - params.moisture_limit_min)
             }
-            moisture if moisture <= T::MOISTURE_IDEAL_MAX => 1.0,
-            moisture if moisture <= T::MOISTURE_LIMIT_MAX => {
-                (moisture - T::MOISTURE_LIMIT_MAX) / (T::MOISTURE_IDEAL_MAX - T::MOISTURE_LIMIT_MAX)
+            moisture if moisture <= params.moisture_ideal_max => 1.0,
+            moisture if moisture <= params.moisture_limit_max => {
+                (moisture - params.moisture_limit_max)
+                    / (params.moisture_ideal_max - params.moisture_limit_max)
             }
             _ => -1.0,
         }
@@ -666,11 +902,14 @@ impl Events {
     fn compute_illumination_viability<T: Vegetation>(
         ecosystem: &Ecosystem,
         index: CellIndex,
-        _: &T,
+        vegetation: &T,
         month: usize,
     ) -> f32 {
         let cell = &ecosystem[index];
-        let modifier = T::get_illumination_coverage_constant(cell);
+        let params = vegetation.get_species_params(&ecosystem.config);
+        // shade tolerant species make up for part of the light lost to the canopy
+        let transmission = T::get_illumination_coverage_constant(cell);
+        let modifier = transmission + (1.0 - transmission) * params.shade_tolerance;
         let illumination =
             ecosystem.get_precomputed_illumination_ray_traced(&index, month) * modifier;
         // if index == CellIndex::new(30, 30)
@@ -679,15 +918,15 @@ impl Events {
         //     println!("modifier {modifier} illumination {illumination}");
         // }
         match illumination {
-            illumination if illumination < T::ILLUMINATION_LIMIT_MIN => -1.0,
-            illumination if illumination < T::ILLUMINATION_IDEAL_MIN => {
-                (illumination - T::ILLUMINATION_LIMIT_MIN)
-                    / (T::ILLUMINATION_IDEAL_MIN - T::ILLUMINATION_LIMIT_MIN)
+            illumination if illumination < params.illumination_limit_min => -1.0,
+            illumination if illumination < params.illumination_ideal_min => {
+                (illumination - params.illumination_limit_min)
+                    / (params.illumination_ideal_min - params.illumination_limit_min)
             }
-            illumination if illumination <= T::ILLUMINATION_IDEAL_MAX => 1.0,
-            illumination if illumination <= T::ILLUMINATION_LIMIT_MAX => {
-                (illumination - T::ILLUMINATION_LIMIT_MAX)
-                    / (T::ILLUMINATION_IDEAL_MAX - T::ILLUMINATION_LIMIT_MAX)
+            illumination if illumination <= params.illumination_ideal_max => 1.0,
+            illumination if illumination <= params.illumination_limit_max => {
+                (illumination - params.illumination_limit_max)
+                    / (params.illumination_ideal_max - params.illumination_limit_max)
             }
             _ => -1.0,
         }
@@ -699,21 +938,21 @@ mod tests {
     use float_cmp::approx_eq;
 
     use crate::{
-        ecology::{Bushes, CellIndex, Ecosystem, Grasses, Trees},
-        events::Events,
+        config::{Browsing, BrowsingRegion, Photosynthesis, SpeciesParams, TimeResolution},
+        ecology::{Bushes, CellIndex, Cohort, Ecosystem, Grasses, Trees},
+        events::{
+            vegetation::{Individualized, Vegetation},
+            Events,
+        },
     };
 
     #[test]
     fn test_tree_compute_viability() {
         let mut ecosystem = Ecosystem::init();
         let index = CellIndex::new(2, 2);
-        let trees = Trees {
-            number_of_plants: 1,
-            plant_height_sum: 10.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 1, 10.0, 10.0);
         let cell = &mut ecosystem[index];
-        cell.trees = Some(trees.clone());
+        cell.trees = vec![trees.clone()];
         // 50 cm of humus/soil
         cell.remove_bedrock(0.5);
         cell.add_humus(0.5);
@@ -730,7 +969,7 @@ mod tests {
         assert_eq!(illumination_viability, 1.0);
 
         // viability is min of the sub-components
-        let viability = Events::compute_viability(&ecosystem, index, &trees, 0);
+        let viability = Events::compute_viabilities(&ecosystem, index, &trees, 0).0;
         assert_eq!(viability, -1.0);
 
         // boost moisture content to within ideal range
@@ -738,7 +977,7 @@ mod tests {
         cell.soil_moisture = 1.8E5;
         let moisture_viability = Events::compute_moisture_viability(&ecosystem, index, &trees, 0);
         assert_eq!(moisture_viability, 1.0);
-        let viability = Events::compute_viability(&ecosystem, index, &trees, 0);
+        let viability = Events::compute_viabilities(&ecosystem, index, &trees, 0).0;
         assert_eq!(viability, 0.735);
 
         // remove some humus, which will boost soil moisture
@@ -751,7 +990,7 @@ mod tests {
             approx_eq!(f32, moisture_viability, expected, epsilon = 0.01),
             "Expected {expected}, actual {moisture_viability}"
         );
-        let viability = Events::compute_viability(&ecosystem, index, &trees, 0);
+        let viability = Events::compute_viabilities(&ecosystem, index, &trees, 0).0;
         let expected = 0.735; // temperature limited
         assert!(
             approx_eq!(f32, viability, expected, epsilon = 0.01),
@@ -763,7 +1002,7 @@ mod tests {
         cell.soil_moisture = 3E5;
         let moisture_viability = Events::compute_moisture_viability(&ecosystem, index, &trees, 0);
         assert_eq!(moisture_viability, -1.0);
-        let viability = Events::compute_viability(&ecosystem, index, &trees, 0);
+        let viability = Events::compute_viabilities(&ecosystem, index, &trees, 0).0;
         assert_eq!(viability, -1.0);
     }
 
@@ -771,13 +1010,9 @@ mod tests {
     fn test_tree_compute_vigor_and_stress() {
         let mut ecosystem = Ecosystem::init();
         let index = CellIndex::new(2, 2);
-        let trees = Trees {
-            number_of_plants: 1,
-            plant_height_sum: 10.0,
-            plant_age_sum: 10.0,
-        };
+        let trees = Trees::with_plants(0, 1, 10.0, 10.0);
         let cell = &mut ecosystem[index];
-        cell.trees = Some(trees.clone());
+        cell.trees = vec![trees.clone()];
         // 50 cm of humus/soil
         cell.remove_bedrock(0.5);
         cell.add_humus(0.5);
@@ -791,7 +1026,7 @@ mod tests {
             //     Events::compute_moisture_viability(&ecosystem, index, &trees, i);
             // let illumination_viability =
             //     Events::compute_illumination_viability(&ecosystem, index, &trees, i);
-            let viability = Events::compute_viability(&ecosystem, index, &trees, i);
+            let viability = Events::compute_viabilities(&ecosystem, index, &trees, i).0;
             viabilities.push(viability);
         }
 
@@ -805,19 +1040,59 @@ mod tests {
         assert_eq!(stress, 0.0);
     }
 
+    #[test]
+    fn test_drought_mortality() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.config.time_resolution = TimeResolution::Year;
+        let index = CellIndex::new(2, 2);
+        let cell = &mut ecosystem[index];
+        cell.remove_bedrock(0.5);
+        cell.add_humus(0.5);
+        cell.soil_moisture = 0.0;
+        let mut trees = Trees::with_plants(0, 100, 1000.0, 1000.0);
+        let grasses = Grasses {
+            coverage_density: 0.5,
+            green_fraction: 1.0,
+        };
+
+        // drought does not stress the trees, unlike the grasses
+        let (_, stress) = Events::compute_vigor_and_stress(&ecosystem, index, &trees);
+        assert_eq!(stress, 0.0);
+        let (_, stress) = Events::compute_vigor_and_stress(&ecosystem, index, &grasses);
+        assert!(stress < 0.0);
+
+        // a year of drought kills off the trees after the 3 months they get through
+        let params = SpeciesParams::red_maple();
+        let deaths = Events::update_drought(&ecosystem, index, &mut trees, &params);
+        let expected = (100.0 * (1.0 - 0.7_f32.powi(9))).round() as u32;
+        assert_eq!(deaths, expected);
+        assert_eq!(trees.dry_months, 12.0);
+
+        // while a species tolerating a year of drought survives it
+        let tolerant = SpeciesParams {
+            drought_tolerance: 24.0,
+            ..SpeciesParams::red_maple()
+        };
+        let mut trees = Trees::with_plants(0, 100, 1000.0, 1000.0);
+        let deaths = Events::update_drought(&ecosystem, index, &mut trees, &tolerant);
+        assert_eq!(deaths, 0);
+
+        // and rain ends the drought
+        ecosystem[index].soil_moisture = 1.8E5;
+        let deaths = Events::update_drought(&ecosystem, index, &mut trees, &tolerant);
+        assert_eq!(deaths, 0);
+        assert_eq!(trees.dry_months, 0.0);
+    }
+
     #[test]
     fn test_apply_trees_event() {
         let mut ecosystem = Ecosystem::init();
         let index = CellIndex::new(0, 0);
 
         // case 1: simple growth
-        let trees = Trees {
-            number_of_plants: 1,
-            plant_height_sum: 10.0,
-            plant_age_sum: 20.0,
-        };
+        let trees = Trees::with_plants(0, 1, 10.0, 20.0);
         let cell = &mut ecosystem[index];
-        cell.trees = Some(trees);
+        cell.trees = vec![trees];
         // 50 cm of humus/soil
         cell.remove_bedrock(0.5);
         cell.add_humus(0.5);
@@ -826,30 +1101,26 @@ mod tests {
         Events::apply_trees_event(&mut ecosystem, index);
 
         let cell = &mut ecosystem[index];
-        assert!(cell.trees.is_some());
-        let new_trees = cell.trees.as_ref().unwrap();
-        assert!(new_trees.number_of_plants >= 1);
-        assert!(new_trees.plant_height_sum > 10.0);
-        assert!(new_trees.plant_age_sum > 20.0);
+        assert!(cell.get_trees(0).is_some());
+        let new_trees = cell.get_trees(0).unwrap();
+        assert!(new_trees.get_number_of_plants() >= 1);
+        assert!(new_trees.get_plant_height_sum() > 10.0);
+        assert!(new_trees.get_plant_age_sum() > 20.0);
         assert_eq!(cell.get_humus_height(), 0.5);
         assert_eq!(cell.get_dead_vegetation_biomass(), 0.0);
 
         // case 2: overpopulation
-        let trees = Trees {
-            number_of_plants: 5,
-            plant_height_sum: 100.0,
-            plant_age_sum: 100.0,
-        };
+        let trees = Trees::with_plants(0, 5, 100.0, 100.0);
         let cell = &mut ecosystem[index];
-        cell.trees = Some(trees);
+        cell.trees = vec![trees];
 
         Events::apply_trees_event(&mut ecosystem, index);
         let cell = &mut ecosystem[index];
-        assert!(cell.trees.is_some());
-        let new_trees = cell.trees.as_ref().unwrap();
-        assert!(new_trees.number_of_plants < 5);
-        assert!(new_trees.plant_height_sum < 100.0);
-        assert!(new_trees.plant_age_sum < 100.0);
+        assert!(cell.get_trees(0).is_some());
+        let new_trees = cell.get_trees(0).unwrap();
+        assert!(new_trees.get_number_of_plants() < 5);
+        assert!(new_trees.get_plant_height_sum() < 100.0);
+        assert!(new_trees.get_plant_age_sum() < 100.0);
         assert_eq!(cell.get_humus_height(), 0.5);
         let dead_biomass = cell.get_dead_vegetation_biomass();
         assert!(cell.get_dead_vegetation_biomass() > 0.0);
@@ -857,24 +1128,112 @@ mod tests {
         // let another year pass so dead trees get converted to humus
         Events::apply_trees_event(&mut ecosystem, index);
         let cell = &mut ecosystem[index];
-        assert!(cell.trees.is_some());
+        assert!(cell.get_trees(0).is_some());
         assert!(cell.get_humus_height() > 0.5);
         // less dead vegetation but not 0
         assert!(cell.get_dead_vegetation_biomass() > 0.0);
         assert!(cell.get_dead_vegetation_biomass() < dead_biomass);
     }
 
+    #[test]
+    fn test_age_classes() {
+        let mut trees = Trees::with_plants(0, 4, 80.0, 8.0);
+        // seedlings join the youngest age class while it is young enough
+        trees.add_seedlings(2);
+        assert_eq!(trees.cohorts.len(), 1);
+        assert_eq!(trees.get_number_of_plants(), 6);
+
+        // and otherwise start a new one
+        trees.grow_plants(1.0, 10.0);
+        trees.add_seedlings(3);
+        assert_eq!(trees.cohorts.len(), 2);
+        assert_eq!(trees.cohorts[0].number_of_plants, 3);
+        assert_eq!(trees.cohorts[0].plant_age_sum, 0.0);
+
+        // the youngest plants die first
+        trees.kill_plants(4);
+        assert_eq!(trees.cohorts.len(), 1);
+        assert_eq!(trees.get_number_of_plants(), 5);
+    }
+
+    #[test]
+    fn test_apply_trees_event_senescence_by_age_class() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(0, 0);
+        let life_expectancy = SpeciesParams::red_maple().life_expectancy;
+        let mut trees = Trees::with_plants(0, 1, 20.0, life_expectancy + 10.0);
+        trees.cohorts.insert(
+            0,
+            Cohort {
+                number_of_plants: 4,
+                plant_height_sum: 20.0,
+                plant_age_sum: 40.0,
+            },
+        );
+        // moist soil so none die of drought
+        let cell = &mut ecosystem[index];
+        cell.remove_bedrock(0.5);
+        cell.add_humus(0.5);
+        cell.soil_moisture = 1.8E5;
+
+        let dead_biomass =
+            Events::update_individualized_vegetation(&mut ecosystem, index, trees, (1.0, 0.0));
+
+        // only the old age class dies of old age
+        let trees = ecosystem[index].get_trees(0).unwrap();
+        let young: u32 = trees
+            .cohorts
+            .iter()
+            .filter(|c| c.get_average_age() < life_expectancy)
+            .map(|c| c.number_of_plants)
+            .sum();
+        assert!(
+            young >= 4,
+            "Expected the young trees to survive, actual {young}"
+        );
+        assert_eq!(trees.get_number_of_plants(), young);
+        assert!(dead_biomass > 0.0);
+    }
+
+    #[test]
+    fn test_apply_trees_event_multiple_species() {
+        let mut ecosystem = Ecosystem::init();
+        let second_species = SpeciesParams {
+            name: "sugar maple".to_string(),
+            ..SpeciesParams::red_maple()
+        };
+        ecosystem.config.species.trees.push(second_species);
+        let index = CellIndex::new(0, 0);
+
+        // each species alone fits in the cell, but together they are overpopulated
+        let cell = &mut ecosystem[index];
+        for species in 0..2 {
+            cell.set_trees(Trees::with_plants(species, 8, 80.0, 80.0));
+        }
+        // 50 cm of humus/soil
+        cell.remove_bedrock(0.5);
+        cell.add_humus(0.5);
+        cell.soil_moisture = 1.8E5;
+        assert!(cell.estimate_total_tree_density() > 1.0);
+
+        Events::apply_trees_event(&mut ecosystem, index);
+
+        // both species survive while competing for the same space
+        let cell = &ecosystem[index];
+        assert!(cell.get_trees(0).is_some());
+        assert!(cell.get_trees(1).is_some());
+        let actual = cell.estimate_total_tree_density();
+        assert!(actual <= 1.0, "Expected at most 1.0, actual {actual}");
+        assert!(cell.get_dead_vegetation_biomass() > 0.0);
+    }
+
     #[test]
     fn test_apply_bushes_event() {
         let mut ecosystem = Ecosystem::init();
         let index = CellIndex::new(0, 0);
 
         // case 1: simple growth
-        let bushes = Bushes {
-            number_of_plants: 1,
-            plant_height_sum: 2.0,
-            plant_age_sum: 10.0,
-        };
+        let bushes = Bushes::with_plants(1, 2.0, 10.0);
         let cell = &mut ecosystem[index];
         cell.bushes = Some(bushes);
         // 50 cm of humus/soil
@@ -887,18 +1246,14 @@ mod tests {
         let cell = &mut ecosystem[index];
         assert!(cell.bushes.is_some());
         let new_bushes = cell.bushes.as_ref().unwrap();
-        assert!(new_bushes.number_of_plants >= 1);
-        assert!(new_bushes.plant_height_sum > 2.0);
-        assert!(new_bushes.plant_age_sum > 10.0);
+        assert!(new_bushes.get_number_of_plants() >= 1);
+        assert!(new_bushes.get_plant_height_sum() > 2.0);
+        assert!(new_bushes.get_plant_age_sum() > 10.0);
         assert_eq!(cell.get_humus_height(), 0.5);
         assert_eq!(cell.get_dead_vegetation_biomass(), 0.0);
 
         // case 2: overpopulation
-        let bushes = Bushes {
-            number_of_plants: 100,
-            plant_height_sum: 200.0,
-            plant_age_sum: 1000.0,
-        };
+        let bushes = Bushes::with_plants(100, 200.0, 1000.0);
         let cell = &mut ecosystem[index];
         cell.bushes = Some(bushes);
 
@@ -906,9 +1261,9 @@ mod tests {
         let cell = &mut ecosystem[index];
         assert!(cell.bushes.is_some());
         let new_bushes = cell.bushes.as_ref().unwrap();
-        assert!(new_bushes.number_of_plants < 100);
-        assert!(new_bushes.plant_height_sum < 200.0);
-        assert!(new_bushes.plant_age_sum < 1000.0);
+        assert!(new_bushes.get_number_of_plants() < 100);
+        assert!(new_bushes.get_plant_height_sum() < 200.0);
+        assert!(new_bushes.get_plant_age_sum() < 1000.0);
         assert_eq!(cell.get_humus_height(), 0.5);
         let dead_biomass = cell.get_dead_vegetation_biomass();
         assert!(cell.get_dead_vegetation_biomass() > 0.0);
@@ -923,6 +1278,92 @@ mod tests {
         assert!(cell.get_dead_vegetation_biomass() < dead_biomass);
     }
 
+    #[test]
+    fn test_understory_light() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(2, 2);
+        // open ground gets full sun
+        assert_eq!(
+            Bushes::get_illumination_coverage_constant(&ecosystem[index]),
+            1.0
+        );
+        assert_eq!(
+            Grasses::get_illumination_coverage_constant(&ecosystem[index]),
+            1.0
+        );
+
+        // light fades continuously as the canopy closes
+        let mut previous = 1.0;
+        for number_of_plants in 1..5 {
+            let n = number_of_plants as f32;
+            ecosystem[index].set_trees(Trees::with_plants(0, number_of_plants, 10.0 * n, 20.0 * n));
+            let actual = Bushes::get_illumination_coverage_constant(&ecosystem[index]);
+            assert!(
+                actual < previous,
+                "Expected less than {previous}, actual {actual}"
+            );
+            previous = actual;
+        }
+
+        // grasses are also shaded by the bushes above them
+        ecosystem[index].bushes = Some(Bushes::with_plants(5, 7.5, 10.0));
+        let bushes_light = Bushes::get_illumination_coverage_constant(&ecosystem[index]);
+        let grasses_light = Grasses::get_illumination_coverage_constant(&ecosystem[index]);
+        assert!(grasses_light < bushes_light);
+
+        // shade tolerant bushes make up for the light lost to a closed canopy
+        ecosystem[index].set_trees(Trees::with_plants(0, 20, 400.0, 400.0));
+        let bushes = Bushes::clone_from_cell(&ecosystem[index]);
+        ecosystem.config.species.bushes.shade_tolerance = 0.0;
+        let intolerant = Events::compute_illumination_viability(&ecosystem, index, &bushes, 0);
+        ecosystem.config.species.bushes.shade_tolerance = 1.0;
+        let tolerant = Events::compute_illumination_viability(&ecosystem, index, &bushes, 0);
+        assert!(
+            tolerant > intolerant,
+            "tolerant {tolerant}, intolerant {intolerant}"
+        );
+    }
+
+    #[test]
+    fn test_rooting_depth() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(2, 2);
+        let mut rainfall = [100.0; 12];
+        rainfall[6] = 0.0;
+        ecosystem.config.climate.monthly_rainfall = rainfall;
+        let cell = &mut ecosystem[index];
+        cell.add_humus(0.5);
+        cell.add_sand(2.0);
+        cell.soil_moisture = 6.0E5;
+        cell.moisture_profile = [0.1, 0.9, 0.0];
+
+        // a dry month dries out the topsoil but not the sand below
+        assert_eq!(Events::compute_moisture(&ecosystem, index, 6), 0.0);
+        let expected = 6.0E5 / 12.0 * 0.9 / (100.0 * 2.0 * 1000.0);
+        let actual = Events::compute_available_moisture(&ecosystem, index, 6, 2.0);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // so deep rooted trees get through the drought that kills the grasses
+        ecosystem[index].set_trees(Trees::with_plants(0, 1, 10.0, 20.0));
+        let trees = Trees::clone_from_cell(&ecosystem[index], 0);
+        let grasses = Grasses::clone_from_cell(&ecosystem[index]);
+        let grasses_viability = Events::compute_moisture_viability(&ecosystem, index, &grasses, 6);
+        let trees_viability = Events::compute_moisture_viability(&ecosystem, index, &trees, 6);
+        assert_eq!(grasses_viability, -1.0);
+        assert!(
+            trees_viability > grasses_viability,
+            "trees {trees_viability}, grasses {grasses_viability}"
+        );
+
+        // roots cannot reach water below bedrock on a shallow ridge
+        ecosystem[index].remove_sand(2.0);
+        let actual = Events::compute_available_moisture(&ecosystem, index, 6, 2.0);
+        assert_eq!(actual, 0.0);
+    }
+
     #[test]
     fn test_apply_grasses_event() {
         let mut ecosystem = Ecosystem::init();
@@ -931,6 +1372,7 @@ mod tests {
         // case 1: simple growth
         let grasses = Grasses {
             coverage_density: 0.0,
+            green_fraction: 1.0,
         };
         let cell = &mut ecosystem[index];
         cell.grasses = Some(grasses);
@@ -951,6 +1393,7 @@ mod tests {
         // case 2: overpopulation
         let grasses = Grasses {
             coverage_density: 1.5,
+            green_fraction: 1.0,
         };
         let cell = &mut ecosystem[index];
         cell.grasses = Some(grasses);
@@ -964,4 +1407,151 @@ mod tests {
         assert_eq!(cell.get_humus_height(), 0.5);
         assert!(cell.get_dead_vegetation_biomass() > 0.0);
     }
+
+    #[test]
+    fn test_nutrient_cycling() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(0, 0);
+        let cell = &mut ecosystem[index];
+        cell.grasses = Some(Grasses {
+            coverage_density: 0.2,
+            green_fraction: 1.0,
+        });
+        // 50 cm of humus/soil
+        cell.remove_bedrock(0.5);
+        cell.add_humus(0.5);
+        cell.soil_moisture = 1.8E5;
+
+        // growing grass takes up nitrogen
+        let nutrients = ecosystem[index].nutrients;
+        Events::apply_grasses_event(&mut ecosystem, index);
+        let actual = ecosystem[index].nutrients;
+        assert!(
+            actual < nutrients,
+            "Expected less than {nutrients}, actual {actual}"
+        );
+
+        // and decomposing dead vegetation releases it
+        ecosystem[index].add_dead_vegetation(1000.0);
+        let nutrients = ecosystem[index].nutrients;
+        Events::decompose_dead_vegetation(&mut ecosystem, index, 0.0);
+        let actual = ecosystem[index].nutrients;
+        assert!(
+            actual > nutrients,
+            "Expected more than {nutrients}, actual {actual}"
+        );
+
+        // without nitrogen left, grass is stressed instead of growing
+        ecosystem[index].nutrients = 0.0;
+        let grasses = Grasses::clone_from_cell(&ecosystem[index]);
+        let (vigor, stress) = Events::compute_vigor_and_stress(&ecosystem, index, &grasses);
+        assert!(vigor < 0.0, "Expected negative vigor, actual {vigor}");
+        assert!(stress < 0.0, "Expected stress, actual {stress}");
+    }
+
+    #[test]
+    fn test_browsing() {
+        let mut ecosystem = Ecosystem::init();
+        let exclosure = CellIndex::new(0, 0);
+        let pasture = CellIndex::new(5, 5);
+        ecosystem.config.browsing = Some(Browsing {
+            pressure: 1.0,
+            regions: vec![BrowsingRegion {
+                min: [0, 0],
+                max: [1, 1],
+                pressure: 0.0,
+            }],
+            ..Browsing::default()
+        });
+
+        // enough seedlings a year that they establish without a random draw
+        ecosystem.config.species.bushes.establishment_rate = 1.0;
+
+        // seedlings only establish where the browsers are fenced out
+        for index in [exclosure, pasture] {
+            let cell = &mut ecosystem[index];
+            cell.grasses = Some(Grasses {
+                coverage_density: 0.5,
+                green_fraction: 1.0,
+            });
+            // moist soil so the seedlings do not die of drought
+            cell.remove_bedrock(0.5);
+            cell.add_humus(0.5);
+            cell.soil_moisture = 1.8E5;
+            Events::apply_precomputed_bushes_event(&mut ecosystem, index, (1.0, 0.0));
+            Events::apply_precomputed_grasses_event(&mut ecosystem, index, (0.0, 0.0));
+        }
+        assert!(ecosystem[exclosure].bushes.is_some());
+        assert!(ecosystem[pasture].bushes.is_none());
+
+        // and grass is eaten without adding dead vegetation
+        let grasses =
+            |index: CellIndex| ecosystem[index].grasses.as_ref().unwrap().coverage_density;
+        assert_eq!(grasses(exclosure), 0.5);
+        let expected = 0.5 - Browsing::default().grass_consumption;
+        let actual = grasses(pasture);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+        assert_eq!(ecosystem[pasture].get_dead_vegetation_biomass(), 0.0);
+    }
+
+    #[test]
+    fn test_apply_grasses_event_prorated() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(0, 0);
+        ecosystem[index].grasses = Some(Grasses {
+            coverage_density: 0.1,
+            green_fraction: 1.0,
+        });
+        Events::apply_precomputed_grasses_event(&mut ecosystem, index, (1.0, 0.0));
+        let yearly_growth = ecosystem[index].grasses.as_ref().unwrap().coverage_density - 0.1;
+
+        // a monthly time step grows a twelfth of a year
+        ecosystem.config.time_resolution = TimeResolution::Month;
+        ecosystem[index].grasses = Some(Grasses {
+            coverage_density: 0.1,
+            green_fraction: 1.0,
+        });
+        Events::apply_precomputed_grasses_event(&mut ecosystem, index, (1.0, 0.0));
+        let expected = yearly_growth / 12.0;
+        let actual = ecosystem[index].grasses.as_ref().unwrap().coverage_density - 0.1;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_apply_grasses_event_phenology() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.config.time_resolution = TimeResolution::Month;
+        let index = CellIndex::new(0, 0);
+        ecosystem[index].grasses = Some(Grasses {
+            coverage_density: 0.5,
+            green_fraction: 1.0,
+        });
+        let green_fraction_after = |ecosystem: &mut Ecosystem, month: u32| {
+            ecosystem.time_step = month;
+            for _ in 0..10 {
+                Events::apply_precomputed_grasses_event(ecosystem, index, (0.0, 0.0));
+            }
+            ecosystem[index].grasses.as_ref().unwrap().green_fraction
+        };
+
+        // warm-season grass is brown in january and green in july
+        ecosystem.config.species.grasses.photosynthesis = Photosynthesis::C4;
+        let actual = green_fraction_after(&mut ecosystem, 1);
+        assert!(actual < 0.01, "Expected cured grass, actual {actual}");
+        let actual = green_fraction_after(&mut ecosystem, 7);
+        assert!(actual > 0.99, "Expected green grass, actual {actual}");
+
+        // in april only cool-season grass has greened up
+        let actual = green_fraction_after(&mut ecosystem, 4);
+        assert!(actual < 0.01, "Expected cured grass, actual {actual}");
+        ecosystem.config.species.grasses.photosynthesis = Photosynthesis::C3;
+        let actual = green_fraction_after(&mut ecosystem, 4);
+        assert!(actual > 0.99, "Expected green grass, actual {actual}");
+    }
 }
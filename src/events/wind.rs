@@ -1,18 +1,19 @@
 use itertools::Itertools;
 use nalgebra::Vector2;
 use rand::Rng;
-use stackblur_iter::{
-    blur_argb,
-    imgref::{Img, ImgExtMut},
-    par_blur_argb,
-};
+use serde::{Deserialize, Serialize};
+use stackblur_iter::{blur_argb, imgref::Img, par_blur_argb};
 
 use crate::{
+    config::BoundaryMode,
     constants,
-    ecology::{Cell, CellIndex, Ecosystem},
+    ecology::{
+        provenance::{scale, TaggedLayer},
+        Cell, CellIndex, Ecosystem,
+    },
 };
 
-use super::Events;
+use super::{coast, Events};
 
 const USE_SIMPLE_WIND: bool = false;
 const SALTATION_DISTANCE_FACTOR: f32 = 0.5;
@@ -26,42 +27,99 @@ const LOW_FREQ_DEVIATION: f32 = 30.0;
 const HIGH_FREQ_WEIGHT: f32 = 0.2;
 const LOW_FREQ_WEIGHT: f32 = 0.8;
 
-pub(crate) struct WindState {
-    pub(crate) wind_rose: WindRose,
-    pub(crate) wind_direction: f32,
-    pub(crate) wind_strength: f32,
-    pub(crate) high_freq_convolution: Vec<f32>,
-    pub(crate) low_freq_convolution: Vec<f32>,
+#[derive(Serialize, Deserialize)]
+pub struct WindState {
+    pub wind_rose: WindRose,
+    // rose of each month, 0 is January, or empty to use `wind_rose` all year
+    pub monthly_wind_roses: Vec<WindRose>,
+    pub wind_direction: f32,
+    pub wind_strength: f32,
+    // terrain blurred at two scales, cached between time steps and saved with checkpoints
+    pub high_freq_convolution: Vec<f32>,
+    pub low_freq_convolution: Vec<f32>,
+    // heights of the terrain the convolutions were computed from, empty if they never were
+    pub convolved_heights: Vec<f32>,
+}
+
+impl Default for WindState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WindState {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         WindState {
             wind_rose: WindRose::new(
                 constants::WIND_DIRECTION,
                 constants::WIND_STRENGTH,
                 constants::WIND_STRENGTH,
             ),
+            monthly_wind_roses: vec![],
             wind_direction: constants::WIND_DIRECTION,
             wind_strength: constants::WIND_STRENGTH,
             high_freq_convolution: vec![0.0; constants::NUM_CELLS],
             low_freq_convolution: vec![0.0; constants::NUM_CELLS],
+            convolved_heights: vec![],
+        }
+    }
+
+    // whether the terrain changed enough since the last convolution to change the blurred terrain,
+    // which only has 256 levels between the lowest and highest cells
+    pub fn is_convolution_stale(&self, heights: &[f32]) -> bool {
+        if self.convolved_heights.len() != heights.len() {
+            return true;
+        }
+        let min_height = heights.iter().copied().fold(f32::MAX, f32::min);
+        let max_height = heights.iter().copied().fold(f32::MIN, f32::max);
+        let level = (max_height - min_height) / 256.0;
+        heights
+            .iter()
+            .zip(&self.convolved_heights)
+            .any(|(height, convolved)| (height - convolved).abs() > level)
+    }
+
+    // returns the wind rose of the month, 0 is January
+    pub fn get_wind_rose(&self, month: usize) -> &WindRose {
+        self.monthly_wind_roses
+            .get(month)
+            .unwrap_or(&self.wind_rose)
+    }
+
+    // samples the wind of one of the given months, chosen by the fraction of it the time step covers
+    pub fn sample_wind(&self, months: &[(usize, f32)], rng: &mut impl Rng) -> (f32, f32) {
+        if self.monthly_wind_roses.is_empty() || months.is_empty() {
+            return self.wind_rose.sample_wind(rng);
+        }
+        let mut month = months[months.len() - 1].0;
+        if months.len() > 1 {
+            let fraction_sum: f32 = months.iter().map(|(_, fraction)| fraction).sum();
+            let mut rand = rng.gen::<f32>() * fraction_sum;
+            for &(candidate, fraction) in months {
+                rand -= fraction;
+                if rand < 0.0 {
+                    month = candidate;
+                    break;
+                }
+            }
         }
+        self.get_wind_rose(month).sample_wind(rng)
     }
 }
 
 // 8 slices of 45° each
 // each slice has a min and max wind speed
-pub(crate) struct WindRose {
-    pub(crate) min_speed: [f32; 8],
-    pub(crate) max_speed: [f32; 8],
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WindRose {
+    pub min_speed: [f32; 8],
+    pub max_speed: [f32; 8],
     // the weight for the given slice being sampled
-    pub(crate) weights: [f32; 8],
+    pub weights: [f32; 8],
 }
 
 impl WindRose {
     // init based on default wind direction and speed
-    pub(crate) fn new(direction: f32, min_strength: f32, max_strength: f32) -> Self {
+    pub fn new(direction: f32, min_strength: f32, max_strength: f32) -> Self {
         let mut min_speed = [0.0; 8];
         let mut max_speed = [0.0; 8];
         let mut weights = [0.0; 8];
@@ -78,7 +136,7 @@ impl WindRose {
         }
     }
 
-    pub(crate) fn update_wind(
+    pub fn update_wind(
         &mut self,
         direction: f32,
         min_strength: f32,
@@ -92,18 +150,18 @@ impl WindRose {
     }
 
     // probabilistically samples the wind distribution
-    pub(crate) fn sample_wind(&self) -> (f32, f32) {
+    pub fn sample_wind(&self, rng: &mut impl Rng) -> (f32, f32) {
         let weight_sum: f32 = self.weights.iter().sum();
         if weight_sum == 0.0 {
             return (0.0, 0.0);
         }
 
         // get direction
-        let mut rng = rand::thread_rng();
         let rand: f32 = rng.gen();
         let mut weight_acc = 0.0;
-        let mut bucket = 0;
-        for i in 0..7 {
+        // rounding can leave rand above the last accumulated weight
+        let mut bucket = self.weights.iter().rposition(|&w| w > 0.0).unwrap_or(0);
+        for i in 0..8 {
             weight_acc += self.weights[i] / weight_sum;
             if rand < weight_acc {
                 bucket = i;
@@ -122,34 +180,32 @@ impl WindRose {
 }
 
 impl Events {
-    pub(crate) fn apply_wind_event(
+    pub fn apply_wind_event(
         ecosystem: &mut Ecosystem,
         index: CellIndex,
     ) -> Option<(Events, CellIndex)> {
         // Saltation
-        // 1) lift a small amount of sand
+        // 1) lift a small amount of sand, which waves keep wet under the sea
+        if coast::is_under_sea(ecosystem, index) {
+            return None;
+        }
         let cell = &mut ecosystem[index];
         let sand_height = cell.get_sand_height();
         if sand_height == 0.0 {
             return None;
         }
-        let vegetation_density = f32::min(cell.estimate_vegetation_density() / 3.0, 1.0);
+        let stabilization = Events::estimate_sand_stabilization(cell);
+        // grass slows the wind near the ground so it carries less sand
+        let grass_coverage = cell.grasses.as_ref().map_or(0.0, |g| g.coverage_density);
+        let carrying_capacity = CARRYING_CAPACITY * (1.0 - f32::min(grass_coverage, 1.0));
         let moved_height = f32::max(
-            f32::min(CARRYING_CAPACITY, sand_height) * (1.0 - vegetation_density),
+            f32::min(carrying_capacity, sand_height) * (1.0 - stabilization),
             0.0,
         );
-        cell.remove_sand(moved_height);
-
-        let (wind_dir, wind_str) = if let Some(wind_state) = &ecosystem.wind_state {
-            get_local_wind(
-                ecosystem,
-                index,
-                wind_state.wind_direction,
-                wind_state.wind_strength,
-            )
-        } else {
-            (constants::WIND_DIRECTION, constants::WIND_STRENGTH)
-        };
+        let tags = ecosystem.take_provenance(TaggedLayer::Sand, index, moved_height);
+        ecosystem[index].remove_sand(moved_height);
+
+        let (wind_dir, wind_str) = sample_local_wind(ecosystem, index);
 
         // 2) transport sand to target cell
         let wind_shadowing = get_wind_shadowing(ecosystem, index, wind_dir);
@@ -159,26 +215,22 @@ impl Events {
         let distance = get_saltation_distance(wind_str);
         let direction = get_wind_direction_vector(wind_dir);
         let target_vec = direction * distance;
-        // the area is topologically a torus so wrap around edges
-        // note: want modulus, not remainder, so ((a % b) + b) % b
-        let target_x = (((index.x as i32 + target_vec.x as i32)
-            % constants::AREA_SIDE_LENGTH as i32)
-            + constants::AREA_SIDE_LENGTH as i32)
-            % constants::AREA_SIDE_LENGTH as i32;
-        let target_y = (((index.y as i32 + target_vec.y as i32)
-            % constants::AREA_SIDE_LENGTH as i32)
-            + constants::AREA_SIDE_LENGTH as i32)
-            % constants::AREA_SIDE_LENGTH as i32;
-
-        // println!("({target_x}, {target_y})");
-        let target_index = CellIndex::new(target_x as usize, target_y as usize);
+        let target_x = index.x as i32 + target_vec.x as i32;
+        let target_y = index.y as i32 + target_vec.y as i32;
+        let Some(target_index) = get_landing_cell(target_x, target_y, ecosystem.config.boundary)
+        else {
+            // the sand is blown off the map
+            return None;
+        };
+        ecosystem.give_provenance(TaggedLayer::Sand, target_index, &tags);
         let target = &mut ecosystem[target_index];
         target.add_sand(moved_height);
+        // sand can land beyond the neighbors marked by the event
+        ecosystem.mark_dirty(target_index);
 
         // 3) on landing, sand can bounce or be deposited
         let bounce_probability = get_bounce_probability(ecosystem, index, wind_shadowing);
-        let mut rng = rand::thread_rng();
-        let rand: f32 = rng.gen();
+        let rand: f32 = ecosystem.rng.gen();
 
         let result = if rand > bounce_probability {
             // bounce
@@ -195,13 +247,37 @@ impl Events {
     }
 }
 
+// returns the cell that sand blown to (x, y) lands in, or none if it leaves the map
+fn get_landing_cell(x: i32, y: i32, boundary: BoundaryMode) -> Option<CellIndex> {
+    let side = constants::AREA_SIDE_LENGTH as i32;
+    let place = |coordinate: i32| match boundary {
+        // want modulus, not remainder
+        BoundaryMode::Torus => Some(coordinate.rem_euclid(side)),
+        BoundaryMode::Open => (0..side).contains(&coordinate).then_some(coordinate),
+        // bounces back off the edges, as many times as it takes
+        BoundaryMode::Reflective => {
+            let period = 2 * (side - 1);
+            let folded = coordinate.rem_euclid(period);
+            Some(if folded < side {
+                folded
+            } else {
+                period - folded
+            })
+        }
+    };
+    Some(CellIndex::new(place(x)? as usize, place(y)? as usize))
+}
+
 fn perform_reptation(ecosystem: &mut Ecosystem, target_index: CellIndex, moved_height: f32) {
     // transport sand to 2 steepest neighbors (proportionally)
     let target = &mut ecosystem[target_index];
     let usable_sand = f32::max(target.get_sand_height() - moved_height, 0.0);
-    let reptation_height = f32::min(REPTATION_HEIGHT, usable_sand);
+    // sand bound by vegetation is not knocked loose by the landing sand
+    let reptation_height = f32::min(REPTATION_HEIGHT, usable_sand)
+        * (1.0 - Events::estimate_sand_stabilization(target));
     let (neighbor_1, neighbor_2) = get_two_steepest_neighbors(ecosystem, target_index);
     if let Some((slope_1, neighbor_1)) = neighbor_1 {
+        let tags = ecosystem.take_provenance(TaggedLayer::Sand, target_index, reptation_height);
         let target = &mut ecosystem[target_index];
         target.remove_sand(reptation_height);
 
@@ -214,16 +290,38 @@ fn perform_reptation(ecosystem: &mut Ecosystem, target_index: CellIndex, moved_h
             };
             let reptation_for_one = reptation_ratio * reptation_height;
             let reptation_for_two = reptation_height - reptation_for_one;
+            let tags_for_one = scale(&tags, reptation_ratio);
+            let tags_for_two = scale(&tags, 1.0 - reptation_ratio);
+            ecosystem.give_provenance(TaggedLayer::Sand, neighbor_1, &tags_for_one);
+            ecosystem.give_provenance(TaggedLayer::Sand, neighbor_2, &tags_for_two);
             ecosystem[neighbor_1].add_sand(reptation_for_one);
             ecosystem[neighbor_2].add_sand(reptation_for_two);
         } else {
             // only one neighbor so move all sand to it
+            ecosystem.give_provenance(TaggedLayer::Sand, neighbor_1, &tags);
             ecosystem[neighbor_1].add_sand(reptation_height);
         }
     }
 }
 
-fn get_wind_direction_vector(wind_angle: f32) -> Vector2<f32> {
+// returns wind direction and strength at the cell for the current time step
+pub fn sample_local_wind(ecosystem: &Ecosystem, index: CellIndex) -> (f32, f32) {
+    if let Some(wind_state) = &ecosystem.wind_state {
+        get_local_wind(
+            ecosystem,
+            index,
+            wind_state.wind_direction,
+            wind_state.wind_strength,
+        )
+    } else {
+        (
+            ecosystem.config.wind.direction,
+            ecosystem.config.wind.strength,
+        )
+    }
+}
+
+pub fn get_wind_direction_vector(wind_angle: f32) -> Vector2<f32> {
     let wind_dir = wind_angle.to_radians();
     let x = wind_dir.sin();
     let y = wind_dir.cos();
@@ -234,7 +332,7 @@ fn get_wind_direction_angle(wind_vec: Vector2<f32>) -> f32 {
     f32::atan2(wind_vec.y, wind_vec.x).to_degrees()
 }
 
-pub(crate) fn get_local_wind(
+pub fn get_local_wind(
     ecosystem: &Ecosystem,
     index: CellIndex,
     wind_dir: f32,
@@ -287,22 +385,17 @@ pub(crate) fn get_local_wind(
     )
 }
 
-pub(crate) fn convolve_terrain(ecosystem: &mut Ecosystem) {
-    let mut heights = vec![0.0; constants::NUM_CELLS];
-    let mut min_height = f32::MAX;
-    let mut max_height = f32::MIN;
-    for i in 0..constants::AREA_SIDE_LENGTH {
-        for j in 0..constants::AREA_SIDE_LENGTH {
-            let height = ecosystem[CellIndex::new(i, j)].get_height();
-            heights[i + j * constants::AREA_SIDE_LENGTH] = height;
-            if height > max_height {
-                max_height = height;
-            }
-            if height < min_height {
-                min_height = height;
-            }
-        }
+// blurs the terrain for the wind field, unless it barely changed since the last time
+// returns whether the convolutions were recomputed
+pub fn convolve_terrain(ecosystem: &mut Ecosystem) -> bool {
+    let mut heights = ecosystem.get_heights();
+    let wind_state = ecosystem.wind_state.as_mut().unwrap();
+    if !wind_state.is_convolution_stale(&heights) {
+        return false;
     }
+    wind_state.convolved_heights = heights.clone();
+    let min_height = heights.iter().copied().fold(f32::MAX, f32::min);
+    let max_height = heights.iter().copied().fold(f32::MIN, f32::max);
     // normalize heights to fit within 256 values
     let norm_factor = 256.0 / (max_height - min_height);
     heights = heights
@@ -310,55 +403,57 @@ pub(crate) fn convolve_terrain(ecosystem: &mut Ecosystem) {
         .map(|v| (v - min_height) * norm_factor)
         .collect_vec();
 
-    let mut argb_heights: [u32; constants::NUM_CELLS] = [0; constants::NUM_CELLS];
-    for (i, height) in heights.iter().enumerate() {
-        let height = *height;
-        let argb = (255 << 24) | ((height as u32) << 16) | ((height as u32) << 8) | (height as u32);
-        argb_heights[i] = argb;
-    }
-
-    // high frequency blur
-    let mut img = Img::new(
-        argb_heights,
-        constants::AREA_SIDE_LENGTH,
-        constants::AREA_SIDE_LENGTH,
+    let argb_heights = heights
+        .iter()
+        .map(|&height| {
+            (255 << 24) | ((height as u32) << 16) | ((height as u32) << 8) | (height as u32)
+        })
+        .collect_vec();
+    // the terrain is square, with as many cells as there are heights
+    let side_length = (argb_heights.len() as f32).sqrt() as usize;
+
+    let high_freq_terrain = blur_heights(
+        argb_heights.clone(),
+        side_length,
+        HIGH_FREQ_KERNEL_RADIUS,
+        norm_factor,
     );
-    blur_argb(&mut img.as_mut(), HIGH_FREQ_KERNEL_RADIUS);
-
-    // convert back to f32 heights
-    let mut high_freq_terrain = vec![0.0; constants::NUM_CELLS];
-    for (i, pixel) in img.buf().iter().enumerate() {
-        high_freq_terrain[i] = (*pixel as u8) as f32 * (1.0 / norm_factor);
-    }
-    let wind_state = ecosystem.wind_state.as_mut().unwrap();
-    wind_state.high_freq_convolution = high_freq_terrain;
-
-    // low frequency blur
-    let mut img = Img::new(
+    let low_freq_terrain = blur_heights(
         argb_heights,
-        constants::AREA_SIDE_LENGTH,
-        constants::AREA_SIDE_LENGTH,
+        side_length,
+        LOW_FREQ_KERNEL_RADIUS,
+        norm_factor,
     );
-    blur_argb(&mut img.as_mut(), LOW_FREQ_KERNEL_RADIUS);
-
-    // convert back to f32 heights
-    let mut low_freq_terrain = vec![0.0; constants::NUM_CELLS];
-    for (i, pixel) in img.buf().iter().enumerate() {
-        low_freq_terrain[i] = (*pixel as u8) as f32 * (1.0 / norm_factor);
-    }
     let wind_state = ecosystem.wind_state.as_mut().unwrap();
+    wind_state.high_freq_convolution = high_freq_terrain;
     wind_state.low_freq_convolution = low_freq_terrain;
+    true
+}
+
+// blurs the normalized heights, packed into argb pixels, and converts them back to heights
+fn blur_heights(
+    argb_heights: Vec<u32>,
+    side_length: usize,
+    radius: usize,
+    norm_factor: f32,
+) -> Vec<f32> {
+    let mut img = Img::new(argb_heights, side_length, side_length);
+    blur_argb(&mut img.as_mut(), radius);
+    img.buf()
+        .iter()
+        .map(|pixel| (*pixel as u8) as f32 * (1.0 / norm_factor))
+        .collect()
 }
 
 // gradient at this point
-pub(crate) fn get_slope_at_point_blurred(
+pub fn get_slope_at_point_blurred(
     ecosystem: &Ecosystem,
     index: CellIndex,
     high_freq: bool,
 ) -> (f32, Vector2<i32>) {
     // negative slope between points means point 1 is lower than point 2
     // looking for largest slope
-    let neighbors = Cell::get_neighbors(&index);
+    let neighbors = Cell::get_neighbors(&index, ecosystem.config.boundary);
     let mut max_slope = f32::MIN;
     let mut dir = (0, 0);
     for neighbor_index in neighbors.as_array().into_iter().flatten() {
@@ -374,7 +469,7 @@ pub(crate) fn get_slope_at_point_blurred(
     (max_slope, Vector2::new(dir.0, dir.1))
 }
 
-pub(crate) fn get_slope_between_points_blurred(
+pub fn get_slope_between_points_blurred(
     ecosystem: &Ecosystem,
     i1: CellIndex,
     i2: CellIndex,
@@ -396,7 +491,7 @@ pub(crate) fn get_slope_between_points_blurred(
         )
     };
     let pos_1 = ecosystem.get_position_of_cell(&i1);
-    let pos_2 = ecosystem.get_position_of_cell(&i2);
+    let pos_2 = ecosystem.get_position_of_neighbor(&i1, &i2);
     (height_1 - height_2) / (pos_1 - pos_2).norm()
 }
 
@@ -471,7 +566,7 @@ fn get_two_steepest_neighbors(
     ecosystem: &Ecosystem,
     index: CellIndex,
 ) -> (Option<(f32, CellIndex)>, Option<(f32, CellIndex)>) {
-    let neighbors = Cell::get_neighbors(&index);
+    let neighbors = Cell::get_neighbors(&index, ecosystem.config.boundary);
     let mut slopes: Vec<(f32, CellIndex)> = vec![];
     for neighbor_index in neighbors.as_array().into_iter().flatten() {
         let slope = ecosystem.get_slope_between_points(index, neighbor_index);
@@ -493,15 +588,18 @@ fn get_two_steepest_neighbors(
 #[cfg(test)]
 mod tests {
     use super::{
-        get_bounce_probability, get_local_sand_strength, get_two_steepest_neighbors,
-        perform_reptation, WindRose, CARRYING_CAPACITY,
+        convolve_terrain, get_bounce_probability, get_landing_cell, get_local_sand_strength,
+        get_two_steepest_neighbors, perform_reptation, WindRose, WindState, CARRYING_CAPACITY,
+        REPTATION_HEIGHT,
     };
     use crate::{
+        config::BoundaryMode,
         constants,
         ecology::{Bushes, CellIndex, Ecosystem, Grasses, Trees},
-        events::wind::get_wind_shadowing,
+        events::{wind::get_wind_shadowing, Events},
     };
     use float_cmp::approx_eq;
+    use rand::{rngs::StdRng, SeedableRng};
 
     #[test]
     fn test_get_local_sand_strength() {
@@ -550,6 +648,34 @@ mod tests {
         assert_eq!(wind_strength, constants::WIND_STRENGTH);
     }
 
+    #[test]
+    fn test_convolve_terrain() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.wind_state = Some(WindState::new());
+        for i in 0..constants::NUM_CELLS {
+            let index = CellIndex::get_from_flat_index(i);
+            ecosystem[index].set_height_of_bedrock(100.0 + index.x as f32);
+        }
+        assert!(convolve_terrain(&mut ecosystem));
+
+        // the cached convolutions are kept while the terrain barely changes
+        let index = CellIndex::new(50, 50);
+        ecosystem[index].add_sand(0.01);
+        assert!(!convolve_terrain(&mut ecosystem));
+
+        // and recomputed once it does
+        for x in 40..60 {
+            for y in 40..60 {
+                ecosystem[CellIndex::new(x, y)].add_sand(20.0);
+            }
+        }
+        let before =
+            ecosystem.wind_state.as_ref().unwrap().high_freq_convolution[index.get_flat_index()];
+        assert!(convolve_terrain(&mut ecosystem));
+        let wind_state = ecosystem.wind_state.as_ref().unwrap();
+        assert!(wind_state.high_freq_convolution[index.get_flat_index()] > before);
+    }
+
     #[test]
     fn test_get_bounce_probability() {
         let mut ecosystem = Ecosystem::init();
@@ -559,20 +685,13 @@ mod tests {
 
         // vegetation reduces bouncing
         let cell = &mut ecosystem[index];
-        cell.trees = Some(Trees {
-            number_of_plants: 2,
-            plant_height_sum: 45.0,
-            plant_age_sum: 40.0,
-        });
+        cell.trees = vec![Trees::with_plants(0, 2, 45.0, 40.0)];
 
-        cell.bushes = Some(Bushes {
-            number_of_plants: 20,
-            plant_height_sum: 70.0,
-            plant_age_sum: 40.0,
-        });
+        cell.bushes = Some(Bushes::with_plants(20, 70.0, 40.0));
 
         cell.grasses = Some(Grasses {
             coverage_density: 1.0,
+            green_fraction: 1.0,
         });
         let prob = get_bounce_probability(&ecosystem, index, 0.0);
         assert_eq!(prob, 0.4);
@@ -643,6 +762,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_vegetation_stabilizes_sand() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(50, 50);
+        ecosystem[index].add_sand(1.0);
+        ecosystem[index].grasses = Some(Grasses {
+            coverage_density: 1.0,
+            green_fraction: 1.0,
+        });
+
+        // grass keeps the wind from lifting sand
+        Events::apply_wind_event(&mut ecosystem, index);
+        assert_eq!(ecosystem[index].get_sand_height(), 1.0);
+
+        // and from knocking it loose, in proportion to the vegetation density
+        ecosystem[CellIndex::new(51, 50)].remove_bedrock(1.0);
+        perform_reptation(&mut ecosystem, index, 0.0);
+        let expected = 1.0 - REPTATION_HEIGHT * (1.0 - 1.0 / 3.0);
+        let actual = ecosystem[index].get_sand_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+
     #[test]
     fn test_wind_rose() {
         let wind_rose = WindRose::new(0.0, 10.0, 10.0);
@@ -656,12 +800,12 @@ mod tests {
     #[test]
     fn test_sample_wind() {
         let mut wind_rose = WindRose::new(0.0, 10.0, 10.0);
-        let (dir, str) = wind_rose.sample_wind();
+        let (dir, str) = wind_rose.sample_wind(&mut rand::thread_rng());
         assert_eq!(dir, 0.0);
         assert_eq!(str, 10.0);
 
         wind_rose.max_speed[0] = 15.0;
-        let (dir, str) = wind_rose.sample_wind();
+        let (dir, str) = wind_rose.sample_wind(&mut rand::thread_rng());
         assert_eq!(dir, 0.0);
         assert!((10.0..=15.0).contains(&str));
 
@@ -669,7 +813,7 @@ mod tests {
         wind_rose.max_speed[4] = 10.0;
         wind_rose.weights[4] = 1.0;
 
-        let (dir, str) = wind_rose.sample_wind();
+        let (dir, str) = wind_rose.sample_wind(&mut rand::thread_rng());
         assert!(dir == 0.0 || dir == 180.0);
         if dir == 0.0 {
             assert!((10.0..=15.0).contains(&str));
@@ -677,4 +821,64 @@ mod tests {
             assert!((5.0..=10.0).contains(&str));
         }
     }
+
+    #[test]
+    fn test_sample_seasonal_wind() {
+        // northerly winter storms and southerly summer breezes
+        let mut wind_state = WindState::new();
+        wind_state.monthly_wind_roses = (0..12)
+            .map(|month| {
+                if (3..9).contains(&month) {
+                    WindRose::new(180.0, 5.0, 5.0)
+                } else {
+                    WindRose::new(0.0, 20.0, 20.0)
+                }
+            })
+            .collect();
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(wind_state.sample_wind(&[(0, 1.0)], &mut rng), (0.0, 20.0));
+        assert_eq!(wind_state.sample_wind(&[(6, 0.5)], &mut rng), (180.0, 5.0));
+
+        // a time step covering the whole year samples both seasons
+        let year: Vec<(usize, f32)> = (0..12).map(|month| (month, 1.0)).collect();
+        let directions: Vec<f32> = (0..100)
+            .map(|_| wind_state.sample_wind(&year, &mut rng).0)
+            .collect();
+        assert!(directions.contains(&0.0));
+        assert!(directions.contains(&180.0));
+
+        // without monthly roses the yearly one is used
+        wind_state.monthly_wind_roses.clear();
+        let expected = (constants::WIND_DIRECTION, constants::WIND_STRENGTH);
+        assert_eq!(wind_state.sample_wind(&[(6, 1.0)], &mut rng), expected);
+    }
+
+    #[test]
+    fn test_sample_wind_last_bucket() {
+        let wind_rose = WindRose::new(315.0, 10.0, 10.0);
+        let (dir, _) = wind_rose.sample_wind(&mut rand::thread_rng());
+        assert_eq!(dir, 315.0);
+    }
+
+    #[test]
+    fn test_get_landing_cell() {
+        let last = constants::AREA_SIDE_LENGTH as i32 - 1;
+        for boundary in [
+            BoundaryMode::Torus,
+            BoundaryMode::Open,
+            BoundaryMode::Reflective,
+        ] {
+            assert_eq!(get_landing_cell(3, 4, boundary), Some(CellIndex::new(3, 4)));
+        }
+        // sand blown 2 cells past the edge
+        assert_eq!(
+            get_landing_cell(last + 2, -2, BoundaryMode::Torus),
+            Some(CellIndex::new(1, last as usize - 1))
+        );
+        assert_eq!(get_landing_cell(last + 2, 5, BoundaryMode::Open), None);
+        assert_eq!(
+            get_landing_cell(last + 2, -2, BoundaryMode::Reflective),
+            Some(CellIndex::new(last as usize - 2, 2))
+        );
+    }
 }
@@ -0,0 +1,223 @@
+// WINDTHROW
+// strong winds uproot or snap trees, which become dead vegetation
+// trees are felled when the local wind exceeds a critical speed given by their species,
+// which is lower for taller trees and for trees rooted in shallow soil
+// part of the felled biomass falls into the downwind neighbors
+
+// height (in m) of trees for which the species threshold applies as is
+const REFERENCE_TREE_HEIGHT: f32 = 20.0;
+// depth (in m) of soil needed for roots to fully anchor a tree
+const ROOTING_DEPTH: f32 = 1.0;
+// critical wind speed of trees without soil relative to trees rooted in deep soil
+const MIN_ANCHORING: f32 = 0.5;
+// % of felled biomass that falls into downwind neighbors
+const SCATTER_FRACTION: f32 = 0.5;
+// neighbors at most 45 degrees away from the wind direction are downwind
+const MIN_DOWNWIND_ALIGNMENT: f32 = 0.7;
+
+use nalgebra::Vector2;
+
+use super::{
+    wind::{get_wind_direction_vector, sample_local_wind},
+    Events,
+};
+use crate::{
+    config::SpeciesParams,
+    ecology::{Cell, CellIndex, Cohort, Ecosystem},
+};
+
+impl Events {
+    pub fn apply_windthrow_event(
+        ecosystem: &mut Ecosystem,
+        index: CellIndex,
+    ) -> Option<(Events, CellIndex)> {
+        if ecosystem[index].trees.is_empty() {
+            return None;
+        }
+        let (wind_dir, wind_str) = sample_local_wind(ecosystem, index);
+
+        // trees left standing of each species that was hit
+        let cell = &ecosystem[index];
+        let mut survivors = vec![];
+        for trees in &cell.trees {
+            let params = &ecosystem.config.species.trees[trees.species];
+            let mut remaining = trees.clone();
+            let mut hit = false;
+            // tall age classes are felled before the young trees below them
+            for cohort in remaining.cohorts.iter_mut() {
+                let critical_speed = compute_critical_wind_speed(cell, cohort, params);
+                if wind_str <= critical_speed {
+                    continue;
+                }
+                // stronger winds fell a larger share of the trees
+                let fraction = f32::min(wind_str / critical_speed - 1.0, 1.0);
+                let felled = (cohort.number_of_plants as f32 * fraction).ceil() as u32;
+                cohort.remove_plants(felled);
+                hit = true;
+            }
+            if hit {
+                remaining.cohorts.retain(|c| c.number_of_plants > 0);
+                survivors.push(remaining);
+            }
+        }
+        if survivors.is_empty() {
+            return None;
+        }
+
+        let cell = &mut ecosystem[index];
+        let biomass = cell.estimate_tree_biomass();
+        for trees in survivors {
+            cell.set_trees(trees);
+        }
+        let felled_biomass = biomass - cell.estimate_tree_biomass();
+
+        let scattered = scatter_downwind(ecosystem, index, wind_dir, felled_biomass);
+        ecosystem[index].add_dead_vegetation(felled_biomass - scattered);
+        None
+    }
+}
+
+// returns wind speed (in m/s) above which the trees of the age class are felled
+fn compute_critical_wind_speed(cell: &Cell, cohort: &Cohort, params: &SpeciesParams) -> f32 {
+    // roots hold better in deeper soil
+    let soil_depth = cell.get_humus_height() + cell.get_sand_height();
+    let anchoring =
+        MIN_ANCHORING + (1.0 - MIN_ANCHORING) * f32::min(soil_depth / ROOTING_DEPTH, 1.0);
+    // taller trees catch more wind
+    let average_height = cohort.get_average_height();
+    let height_factor = f32::min(REFERENCE_TREE_HEIGHT / average_height, 1.0);
+    params.windthrow_threshold * anchoring * height_factor
+}
+
+// spreads part of the biomass over the downwind neighbors, returning how much was spread
+fn scatter_downwind(
+    ecosystem: &mut Ecosystem,
+    index: CellIndex,
+    wind_dir: f32,
+    biomass: f32,
+) -> f32 {
+    let direction = get_wind_direction_vector(wind_dir);
+    let downwind: Vec<(CellIndex, f32)> = Cell::get_neighbors(&index, ecosystem.config.boundary)
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|neighbor| {
+            let offset = Vector2::new(
+                neighbor.x as f32 - index.x as f32,
+                neighbor.y as f32 - index.y as f32,
+            )
+            .normalize();
+            let alignment = offset.dot(&direction);
+            (alignment >= MIN_DOWNWIND_ALIGNMENT).then_some((neighbor, alignment))
+        })
+        .collect();
+    // trees at the edge of the map may have nowhere to fall
+    let alignment_sum: f32 = downwind.iter().map(|(_, alignment)| alignment).sum();
+    if alignment_sum == 0.0 {
+        return 0.0;
+    }
+
+    let scattered = biomass * SCATTER_FRACTION;
+    for (neighbor, alignment) in downwind {
+        ecosystem[neighbor].add_dead_vegetation(scattered * alignment / alignment_sum);
+    }
+    scattered
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::compute_critical_wind_speed;
+    use crate::{
+        config::SpeciesParams,
+        ecology::{Cell, CellIndex, Cohort, Ecosystem, Trees},
+        events::{vegetation::Individualized, Events},
+    };
+
+    fn init_forested_ecosystem(wind_direction: f32, wind_strength: f32) -> Ecosystem {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.config.wind.direction = wind_direction;
+        ecosystem.config.wind.strength = wind_strength;
+        ecosystem[CellIndex::new(50, 50)].set_trees(Trees::with_plants(0, 4, 80.0, 200.0));
+        ecosystem
+    }
+
+    #[test]
+    fn test_apply_windthrow_event_calm() {
+        let mut ecosystem = init_forested_ecosystem(90.0, 5.0);
+        let index = CellIndex::new(50, 50);
+
+        Events::apply_windthrow_event(&mut ecosystem, index);
+
+        let cell = &ecosystem[index];
+        assert_eq!(cell.get_trees(0).unwrap().get_number_of_plants(), 4);
+        assert_eq!(cell.get_dead_vegetation_biomass(), 0.0);
+    }
+
+    #[test]
+    fn test_apply_windthrow_event_storm() {
+        // wind blowing towards increasing x
+        let mut ecosystem = init_forested_ecosystem(90.0, 100.0);
+        let index = CellIndex::new(50, 50);
+        let expected = ecosystem[index].estimate_tree_biomass();
+
+        Events::apply_windthrow_event(&mut ecosystem, index);
+
+        // all trees are felled and part of them lands downwind
+        assert!(ecosystem[index].trees.is_empty());
+        assert!(ecosystem[index].get_dead_vegetation_biomass() > 0.0);
+        assert!(ecosystem[CellIndex::new(51, 50)].get_dead_vegetation_biomass() > 0.0);
+        assert_eq!(
+            ecosystem[CellIndex::new(49, 50)].get_dead_vegetation_biomass(),
+            0.0
+        );
+        let actual: f32 = ecosystem
+            .cells
+            .iter()
+            .map(|cell| cell.get_dead_vegetation_biomass())
+            .sum();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.01),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_compute_critical_wind_speed() {
+        let params = SpeciesParams::red_maple();
+        let short_trees = Cohort {
+            number_of_plants: 2,
+            plant_height_sum: 20.0,
+            plant_age_sum: 20.0,
+        };
+        let tall_trees = Cohort {
+            number_of_plants: 2,
+            plant_height_sum: 80.0,
+            plant_age_sum: 20.0,
+        };
+        let mut deep_soil = Cell::init();
+        deep_soil.add_humus(2.0);
+        // cells start out as bare bedrock
+        let bare_rock = Cell::init();
+
+        let expected = params.windthrow_threshold;
+        let actual = compute_critical_wind_speed(&deep_soil, &short_trees, &params);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        // tall trees and shallow soil are both easier to fell
+        let actual = compute_critical_wind_speed(&deep_soil, &tall_trees, &params);
+        assert!(
+            actual < expected,
+            "Expected less than {expected}, actual {actual}"
+        );
+        let expected = params.windthrow_threshold * super::MIN_ANCHORING;
+        let actual = compute_critical_wind_speed(&bare_rock, &short_trees, &params);
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+}
@@ -1,24 +1,414 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use image::{codecs::gif::GifEncoder, Delay, Frame, RgbaImage};
 use itertools::Itertools;
+use nalgebra::Vector3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tiff::{
+    encoder::{colortype, TiffEncoder},
+    tags::Tag,
+};
 
 use crate::{
     constants,
-    ecology::{CellIndex, Ecosystem},
-    render::EcosystemRenderable,
+    ecology::{
+        biome::{Biome, BIOMES},
+        diff::{DiffLayer, EcosystemDiff},
+        Cell, CellIndex, Ecosystem,
+    },
+    events::{
+        log::EventLog,
+        vegetation::Individualized,
+        wind::{get_wind_direction_vector, sample_local_wind},
+    },
+    render_data::{self, ColorMode},
 };
 
+// text chunks of the 16-bit height map
+pub const ELEVATION_MIN_KEYWORD: &str = "elevation_min";
+pub const ELEVATION_MAX_KEYWORD: &str = "elevation_max";
+pub const CELL_SIZE_KEYWORD: &str = "cell_size";
+
+// columns of the aggregate metrics csv
+// biomass in kg, depths in m, volumes in m^3
+pub const METRICS_CSV_HEADER: &str = "time_step,tree_biomass,bush_biomass,grass_biomass,\
+    dead_vegetation,average_humus_depth,sand_volume,mean_soil_moisture,trees,bushes";
+
+// texels of the ripple map along each side of a cell
+pub const RIPPLE_TEXELS_PER_CELL: usize = 8;
+// texels between the crests of neighboring ripples
+// real ripples are centimeters apart, but these are spaced to show at the resolution of the map
+const RIPPLE_PERIOD: f32 = 3.0;
+// m of sand needed for fully formed ripples, as thinner sand is broken up by the ground below it
+const RIPPLE_SAND_DEPTH: f32 = 0.5;
+// slope of the flanks of fully formed ripples
+const RIPPLE_STEEPNESS: f32 = 0.5;
+
+// files of the scene bundle
+const SCENE_HEIGHT_MAP: &str = "terrain-16.png";
+const SCENE_COLOR_MAP: &str = "color.png";
+const SCENE_SPLAT_MAP: &str = "splat.png";
+const SCENE_TREES: &str = "trees.csv";
+// species indexes the tree species of the manifest, positions and heights in m
+pub const SCENE_TREES_CSV_HEADER: &str = "species,x,y,z,height";
+
+// milliseconds each frame of a time-lapse is shown for
+const TIMELAPSE_FRAME_DELAY: u32 = 200;
+// pixels per cell along each side of the maps in a time-lapse frame
+const TIMELAPSE_SCALE: u32 = 3;
+// speed of color quantization, from 1 (best quality) to 30 (fastest)
+const TIMELAPSE_QUANTIZATION_SPEED: i32 = 10;
+
+// creates a timestamped directory under ./output and returns its path
+pub fn create_export_directory() -> String {
+    let now = chrono::Local::now();
+    let today = now.date_naive().format("%Y_%m_%d").to_string();
+    let time = now.time().format("%H_%M_%S").to_string();
+    let path = format!("./output/{today}-{time}");
+    println!("{path}");
+    std::fs::create_dir_all(path.clone()).unwrap();
+    path
+}
+
 /// process:
 /// generate height map and density maps for all layers
 /// in blender, blend colors together, add textures, instantiate geometry
 
-pub(crate) fn export_maps(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+pub fn export_maps(ecosystem: &Ecosystem, time_step: u32, path: &str) {
     export_height_map(ecosystem, time_step, path);
+    export_height_map_16(ecosystem, time_step, path);
+    export_geotiff(ecosystem, time_step, path);
     export_color_map(ecosystem, time_step, path);
     // todo make more efficient
-    export_hypsometric_color_map(build_height_map(ecosystem), time_step, path);
+    export_hypsometric_color_map(ecosystem, time_step, path);
     export_vegetation_map(ecosystem, time_step, path);
+    export_normal_map(ecosystem, time_step, path);
+    export_slope_map(ecosystem, time_step, path);
+    export_material_mask(ecosystem, time_step, path);
+    export_ripple_map(ecosystem, time_step, path);
+    export_biome_map(ecosystem, time_step, path);
+    export_succession_maps(ecosystem, time_step, path);
+    export_mesh(ecosystem, time_step, path);
+}
+
+// writes everything the blender import needs into one directory named after the time step
+// 1) 16-bit height map, with the elevation range in the manifest as well as its text chunks
+// 2) color map and splat map of the ground materials
+// 3) grayscale density maps of trees, bushes, and grasses, for scattering them over the terrain
+// 4) a csv of every tree with its position (in m) scattered within its cell and its height
+// 5) a json manifest with the files and the scales needed to put them back together
+pub fn export_scene_bundle(ecosystem: &Ecosystem, time_step: u32, path: &str) -> String {
+    let path = format!("{path}/{time_step}-scene");
+    println!("{path}");
+    std::fs::create_dir_all(&path).unwrap();
+    let side = constants::AREA_SIDE_LENGTH as u32;
+
+    // 1) height map
+    let elevations = build_elevations(ecosystem);
+    write_height_map_16(&elevations, &format!("{path}/{SCENE_HEIGHT_MAP}"));
+
+    // 2) ground
+    let color_map = build_color_map(ecosystem);
+    image::save_buffer(
+        format!("{path}/{SCENE_COLOR_MAP}"),
+        &color_map,
+        side,
+        side,
+        image::ColorType::Rgb8,
+    )
+    .unwrap();
+    let splat_map = build_material_mask(ecosystem);
+    image::save_buffer(
+        format!("{path}/{SCENE_SPLAT_MAP}"),
+        &splat_map,
+        side,
+        side,
+        image::ColorType::Rgba8,
+    )
+    .unwrap();
+
+    // 3) vegetation densities
+    for (name, densities) in build_density_maps(ecosystem) {
+        image::save_buffer(
+            format!("{path}/{name}-density.png"),
+            &densities,
+            side,
+            side,
+            image::ColorType::L8,
+        )
+        .unwrap();
+    }
+
+    // 4) tree instances
+    std::fs::write(
+        format!("{path}/{SCENE_TREES}"),
+        build_tree_instances_csv(ecosystem),
+    )
+    .unwrap();
+
+    // 5) manifest
+    std::fs::write(
+        format!("{path}/manifest.json"),
+        build_scene_manifest(ecosystem, time_step, &elevations),
+    )
+    .unwrap();
+    path
+}
+
+// grayscale share of each cell covered by trees, bushes, and grasses, from 0 to 255 for full cover
+pub fn build_density_maps(ecosystem: &Ecosystem) -> [(&'static str, Vec<u8>); 3] {
+    let to_pixel = |density: f32| (density.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let map = |density: &dyn Fn(&Cell) -> f32| {
+        (0..constants::NUM_CELLS)
+            .map(|i| to_pixel(density(&ecosystem[CellIndex::get_from_flat_index(i)])))
+            .collect()
+    };
+    [
+        ("trees", map(&|cell| cell.estimate_total_tree_density())),
+        (
+            "bushes",
+            map(&|cell| {
+                cell.bushes
+                    .as_ref()
+                    .map_or(0.0, Cell::estimate_bushes_density)
+            }),
+        ),
+        (
+            "grasses",
+            map(&|cell| cell.grasses.as_ref().map_or(0.0, |g| g.coverage_density)),
+        ),
+    ]
+}
+
+// one row per tree, scattered at random within its cell with the average height of its age class
+// positions are in m from the corner of cell (0, 0), with z the ground under the tree
+pub fn build_tree_instances_csv(ecosystem: &Ecosystem) -> String {
+    // seeded so the same ecosystem always scatters its trees the same way
+    let mut rng = StdRng::seed_from_u64(ecosystem.seed);
+    let mut csv = format!("{SCENE_TREES_CSV_HEADER}\n");
+    for i in 0..constants::NUM_CELLS {
+        let index = CellIndex::get_from_flat_index(i);
+        let cell = &ecosystem[index];
+        let z = cell.get_height();
+        for trees in &cell.trees {
+            for cohort in &trees.cohorts {
+                let height = cohort.get_average_height();
+                for _ in 0..cohort.number_of_plants {
+                    let x = (index.x as f32 + rng.gen::<f32>()) * constants::CELL_SIDE_LENGTH;
+                    let y = (index.y as f32 + rng.gen::<f32>()) * constants::CELL_SIDE_LENGTH;
+                    csv.push_str(&format!("{},{x},{y},{z},{height}\n", trees.species));
+                }
+            }
+        }
+    }
+    csv
 }
 
-pub(crate) fn export_height_map(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+pub fn build_scene_manifest(ecosystem: &Ecosystem, time_step: u32, elevations: &[f32]) -> String {
+    let min = elevations.iter().copied().fold(f32::MAX, f32::min);
+    let max = elevations.iter().copied().fold(f32::MIN, f32::max);
+    let species = quote_json_list(
+        ecosystem
+            .config
+            .species
+            .trees
+            .iter()
+            .map(|params| params.name.as_str()),
+    );
+    let columns = quote_json_list(SCENE_TREES_CSV_HEADER.split(','));
+    let channels = quote_json_list(["rock", "sand", "humus", "water_or_snow"]);
+    format!(
+        r#"{{
+  "time_step": {time_step},
+  "cells_per_side": {},
+  "cell_size": {},
+  "elevation_min": {min},
+  "elevation_max": {max},
+  "height_map": {},
+  "color_map": {},
+  "splat_map": {{
+    "file": {},
+    "channels": [{channels}]
+  }},
+  "density_maps": {{
+    "trees": {},
+    "bushes": {},
+    "grasses": {}
+  }},
+  "trees": {{
+    "file": {},
+    "columns": [{columns}],
+    "species": [{species}]
+  }}
+}}
+"#,
+        constants::AREA_SIDE_LENGTH,
+        constants::CELL_SIDE_LENGTH,
+        quote_json(SCENE_HEIGHT_MAP),
+        quote_json(SCENE_COLOR_MAP),
+        quote_json(SCENE_SPLAT_MAP),
+        quote_json("trees-density.png"),
+        quote_json("bushes-density.png"),
+        quote_json("grasses-density.png"),
+        quote_json(SCENE_TREES),
+    )
+}
+
+fn quote_json_list<'a>(strings: impl IntoIterator<Item = &'a str>) -> String {
+    strings.into_iter().map(quote_json).join(", ")
+}
+
+// quotes the string for json, escaping quotes, backslashes, and control characters
+pub fn quote_json(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// saves the rgb pixels of a rendered frame with a timestamped name so screenshots never overwrite
+// each other
+pub fn export_screenshot(pixels: &[u8], width: u32, height: u32, time_step: u32, path: &str) {
+    let now = chrono::Local::now().format("%Y_%m_%d-%H_%M_%S_%3f");
+    let path = format!("{path}/{time_step}-screenshot-{now}.png");
+    println!("{path}");
+    image::save_buffer(path, pixels, width, height, image::ColorType::Rgb8).unwrap();
+}
+
+pub fn export_checkpoint(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+    let path = format!("{path}/{}-checkpoint.bin", time_step);
+    // a checkpoint that cannot be written should not stop the run it was taken of
+    if let Err(error) = ecosystem.save(&path) {
+        println!("{error}");
+    }
+}
+
+// appends the event log of the time step to the csv at path, writing the header if the file is new
+pub fn append_event_log(event_log: &EventLog, time_step: u32, path: &str) {
+    append_csv_row(path, EventLog::CSV_HEADER, &event_log.to_csv_row(time_step));
+}
+
+// appends the totals of the ecosystem to the csv at path, writing the header if the file is new
+pub fn append_metrics(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+    append_csv_row(
+        path,
+        METRICS_CSV_HEADER,
+        &build_metrics_row(ecosystem, time_step),
+    );
+}
+
+// appends the row to the csv at path, writing the header first if the file is new
+pub fn append_csv_row(path: &str, header: &str, row: &str) {
+    let is_new = !Path::new(path).exists();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap();
+    if is_new {
+        writeln!(file, "{header}").unwrap();
+    }
+    writeln!(file, "{row}").unwrap();
+}
+
+pub fn build_metrics_row(ecosystem: &Ecosystem, time_step: u32) -> String {
+    let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+    let mut tree_biomass = 0.0;
+    let mut bush_biomass = 0.0;
+    let mut grass_biomass = 0.0;
+    let mut dead_vegetation = 0.0;
+    let mut humus_depth = 0.0;
+    let mut sand_depth = 0.0;
+    let mut soil_moisture = 0.0;
+    let mut trees = 0;
+    let mut bushes = 0;
+    for cell in ecosystem.cells.iter() {
+        tree_biomass += cell.estimate_tree_biomass();
+        bush_biomass += cell.estimate_bush_biomass();
+        grass_biomass += cell.estimate_grasses_biomass();
+        dead_vegetation += cell.get_dead_vegetation_biomass();
+        humus_depth += cell.get_humus_height();
+        sand_depth += cell.get_sand_height();
+        soil_moisture += cell.soil_moisture;
+        trees += cell
+            .trees
+            .iter()
+            .map(|t| t.get_number_of_plants())
+            .sum::<u32>();
+        bushes += cell.bushes.as_ref().map_or(0, |b| b.get_number_of_plants());
+    }
+    let num_cells = constants::NUM_CELLS as f32;
+    let average_humus_depth = humus_depth / num_cells;
+    let sand_volume = sand_depth * cell_area;
+    let mean_soil_moisture = soil_moisture / num_cells;
+    format!(
+        "{time_step},{tree_biomass},{bush_biomass},{grass_biomass},{dead_vegetation},\
+        {average_humus_depth},{sand_volume},{mean_soil_moisture},{trees},{bushes}"
+    )
+}
+
+// writes the terrain triangulation used by the renderer as a Wavefront OBJ
+// each vertex carries its color and a uv into the color map written by export_color_map
+pub fn export_mesh(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+    let obj_path = format!("{path}/{}-terrain.obj", time_step);
+    let mtl_name = format!("{}-terrain.mtl", time_step);
+    println!("{obj_path}");
+
+    let mut obj = BufWriter::new(File::create(obj_path).unwrap());
+    write_mesh(ecosystem, &mtl_name, &mut obj).unwrap();
+
+    let mut mtl = BufWriter::new(File::create(format!("{path}/{mtl_name}")).unwrap());
+    writeln!(mtl, "newmtl terrain").unwrap();
+    writeln!(mtl, "Kd 1.0 1.0 1.0").unwrap();
+    writeln!(mtl, "map_Kd {}-color.png", time_step).unwrap();
+}
+
+fn write_mesh(ecosystem: &Ecosystem, mtl_name: &str, out: &mut impl Write) -> std::io::Result<()> {
+    writeln!(out, "mtllib {mtl_name}")?;
+    writeln!(out, "usemtl terrain")?;
+
+    let side = constants::AREA_SIDE_LENGTH as f32;
+    let verts = render_data::build_terrain_vertices(ecosystem);
+    for vert in &verts {
+        // vertices are placed at cell indices
+        let color =
+            render_data::get_color(ecosystem, CellIndex::new(vert.x as usize, vert.y as usize));
+        writeln!(
+            out,
+            "v {} {} {} {} {} {}",
+            vert.x, vert.y, vert.z, color.x, color.y, color.z
+        )?;
+    }
+    for vert in &verts {
+        // sample the center of the pixel, with v pointing up the image
+        let u = (vert.x + 0.5) / side;
+        let v = 1.0 - (vert.y + 0.5) / side;
+        writeln!(out, "vt {u} {v}")?;
+    }
+    // obj indices start at 1
+    for face in render_data::build_terrain_faces() {
+        let (a, b, c) = (face.x + 1, face.y + 1, face.z + 1);
+        writeln!(out, "f {a}/{a} {b}/{b} {c}/{c}")?;
+    }
+    out.flush()
+}
+
+pub fn export_height_map(ecosystem: &Ecosystem, time_step: u32, path: &str) {
     let new_path = format!("{path}/{}-terrain.png", time_step);
     println!("{new_path}");
 
@@ -60,26 +450,101 @@ pub(crate) fn export_height_map(ecosystem: &Ecosystem, time_step: u32, path: &st
     // .unwrap();
 }
 
-pub(crate) fn build_height_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 3] {
-    let mut heights = [0.0; constants::NUM_CELLS];
-    let mut min_height = f32::MAX;
-    let mut max_height = f32::MIN;
-    for (i, row) in ecosystem.cells.iter().enumerate() {
-        for (j, cell) in row.iter().enumerate() {
-            let flat_index = i + j * constants::AREA_SIDE_LENGTH;
-            let height = cell.get_height();
-            heights[flat_index] = height;
-            if height > max_height {
-                max_height = height;
-            }
-            if height < min_height {
-                min_height = height;
-            }
-        }
+// writes heights as a 16-bit grayscale png, storing the elevation range (in m) in text chunks
+// so that import_height_map can restore the real elevations
+pub fn export_height_map_16(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+    let new_path = format!("{path}/{}-terrain-16.png", time_step);
+    println!("{new_path}");
+    write_height_map_16(&build_elevations(ecosystem), &new_path);
+}
+
+pub fn write_height_map_16(elevations: &[f32], path: &str) {
+    let min = elevations.iter().copied().fold(f32::MAX, f32::min);
+    let max = elevations.iter().copied().fold(f32::MIN, f32::max);
+    // use the full 16 bits for the range of the terrain
+    let range = max - min;
+    let mut data = Vec::with_capacity(elevations.len() * 2);
+    for elevation in elevations {
+        let value = if range > 0.0 {
+            ((elevation - min) / range * u16::MAX as f32).round() as u16
+        } else {
+            0
+        };
+        // png stores 16-bit samples as big endian
+        data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    let file = BufWriter::new(File::create(path).unwrap());
+    let side = constants::AREA_SIDE_LENGTH as u32;
+    let mut encoder = png::Encoder::new(file, side, side);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Sixteen);
+    for (keyword, value) in [
+        (ELEVATION_MIN_KEYWORD, min),
+        (ELEVATION_MAX_KEYWORD, max),
+        (CELL_SIZE_KEYWORD, constants::CELL_SIDE_LENGTH),
+    ] {
+        encoder
+            .add_text_chunk(keyword.to_string(), value.to_string())
+            .unwrap();
     }
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&data).unwrap();
+}
+
+// writes heights (in m) as a 32-bit float GeoTIFF with the cell size as its pixel scale
+pub fn export_geotiff(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+    let new_path = format!("{path}/{}-terrain.tif", time_step);
+    println!("{new_path}");
+
+    let file = BufWriter::new(File::create(new_path).unwrap());
+    let mut encoder = TiffEncoder::new(file).unwrap();
+    let side = constants::AREA_SIDE_LENGTH as u32;
+    let mut image = encoder
+        .new_image::<colortype::Gray32Float>(side, side)
+        .unwrap();
+    let cell_size = constants::CELL_SIDE_LENGTH as f64;
+    image
+        .encoder()
+        .write_tag(Tag::ModelPixelScaleTag, &[cell_size, cell_size, 0.0][..])
+        .unwrap();
+    // pin the top left corner of the raster to the origin of the model
+    image
+        .encoder()
+        .write_tag(Tag::ModelTiepointTag, &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0][..])
+        .unwrap();
+    // geokey directory v1.1.0 with a projected model of raster type pixel is area,
+    // since the terrain is not tied to a coordinate reference system
+    image
+        .encoder()
+        .write_tag(
+            Tag::GeoKeyDirectoryTag,
+            &[1u16, 1, 0, 2, 1024, 0, 1, 1, 1025, 0, 1, 1][..],
+        )
+        .unwrap();
+    image.write_data(&build_elevations(ecosystem)).unwrap();
+}
+
+// returns height (in m) of each cell, ordered like the pixels of the exported maps
+pub fn build_elevations(ecosystem: &Ecosystem) -> Vec<f32> {
+    (0..constants::NUM_CELLS)
+        .map(|i| {
+            let x = i % constants::AREA_SIDE_LENGTH;
+            let y = i / constants::AREA_SIDE_LENGTH;
+            ecosystem[CellIndex::new(x, y)].get_height()
+        })
+        .collect()
+}
+
+pub fn build_height_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 3] {
+    let mut heights = ecosystem.get_heights();
+    let min_height = heights.iter().copied().fold(f32::MAX, f32::min);
+    let max_height = heights.iter().copied().fold(f32::MIN, f32::max);
     // normalize heights to fit within 256 values
     let norm_factor = 256.0 / (max_height - min_height);
-    heights = heights.map(|v| (v - min_height) * norm_factor);
+    heights
+        .iter_mut()
+        .for_each(|v| *v = (*v - min_height) * norm_factor);
 
     // convert to greyscale rgb
     let mut buffer = [0; constants::NUM_CELLS * 3];
@@ -92,7 +557,7 @@ pub(crate) fn build_height_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CEL
     buffer
 }
 
-pub(crate) fn build_conv_terrain_map(
+pub fn build_conv_terrain_map(
     ecosystem: &Ecosystem,
     high_freq: bool,
 ) -> [u8; constants::NUM_CELLS * 3] {
@@ -130,7 +595,7 @@ pub(crate) fn build_conv_terrain_map(
     buffer
 }
 
-pub(crate) fn export_color_map(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+pub fn export_color_map(ecosystem: &Ecosystem, time_step: u32, path: &str) {
     let path = format!("{path}/{}-color.png", time_step);
     println!("{path}");
 
@@ -145,12 +610,12 @@ pub(crate) fn export_color_map(ecosystem: &Ecosystem, time_step: u32, path: &str
     .unwrap();
 }
 
-pub(crate) fn build_color_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 3] {
+pub fn build_color_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 3] {
     let mut buffer = [0; constants::NUM_CELLS * 3];
     for i in 0..constants::AREA_SIDE_LENGTH {
         for j in 0..constants::AREA_SIDE_LENGTH {
             let flat_index = i + j * constants::AREA_SIDE_LENGTH;
-            let color = EcosystemRenderable::get_color(ecosystem, CellIndex::new(i, j));
+            let color = render_data::get_color(ecosystem, CellIndex::new(i, j));
             buffer[flat_index * 3] = (color[0] * 255.0) as u8;
             buffer[flat_index * 3 + 1] = (color[1] * 255.0) as u8;
             buffer[flat_index * 3 + 2] = (color[2] * 255.0) as u8;
@@ -159,15 +624,42 @@ pub(crate) fn build_color_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELL
     buffer
 }
 
-pub(crate) fn export_hypsometric_color_map(
-    height_map: [u8; constants::NUM_CELLS * 3],
-    time_step: u32,
-    path: &str,
-) {
+// the cells in the color mode, as the terrain is drawn in it
+// the standard colors and hypsometric tint are the same as their exported maps
+pub fn build_color_mode_map(
+    ecosystem: &Ecosystem,
+    color_mode: &ColorMode,
+) -> [u8; constants::NUM_CELLS * 3] {
+    match color_mode {
+        ColorMode::Standard => build_color_map(ecosystem),
+        ColorMode::HypsometricTint => build_hypsometrically_tinted_map(ecosystem),
+        _ => {
+            let height_range = render_data::get_height_range(ecosystem);
+            let mut buffer = [0; constants::NUM_CELLS * 3];
+            for i in 0..constants::AREA_SIDE_LENGTH {
+                for j in 0..constants::AREA_SIDE_LENGTH {
+                    let flat_index = i + j * constants::AREA_SIDE_LENGTH;
+                    let color = render_data::get_mode_color(
+                        ecosystem,
+                        CellIndex::new(i, j),
+                        color_mode,
+                        height_range,
+                    );
+                    buffer[flat_index * 3] = (color[0] * 255.0) as u8;
+                    buffer[flat_index * 3 + 1] = (color[1] * 255.0) as u8;
+                    buffer[flat_index * 3 + 2] = (color[2] * 255.0) as u8;
+                }
+            }
+            buffer
+        }
+    }
+}
+
+pub fn export_hypsometric_color_map(ecosystem: &Ecosystem, time_step: u32, path: &str) {
     let path = format!("{path}/{}-hypsometric.png", time_step);
     println!("{path}");
 
-    let buf = build_hypsometrically_tinted_map(height_map);
+    let buf = build_hypsometrically_tinted_map(ecosystem);
     image::save_buffer(
         path,
         &buf,
@@ -178,21 +670,24 @@ pub(crate) fn export_hypsometric_color_map(
     .unwrap();
 }
 
-pub(crate) fn build_hypsometrically_tinted_map(
-    height_map: [u8; constants::NUM_CELLS * 3],
-) -> [u8; constants::NUM_CELLS * 3] {
+// heights tinted by the hypsometric ramp of the config, from the lowest to the highest cell
+pub fn build_hypsometrically_tinted_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 3] {
+    let height_range = render_data::get_height_range(ecosystem);
     let mut buffer = [0; constants::NUM_CELLS * 3];
-    for i in (0..height_map.len()).step_by(3) {
-        let height = height_map[i] as f32;
-        let color = EcosystemRenderable::get_hypsometric_color_helper(height, false);
-        buffer[i] = (color[0] * 255.0) as u8;
-        buffer[i + 1] = (color[1] * 255.0) as u8;
-        buffer[i + 2] = (color[2] * 255.0) as u8;
+    for i in 0..constants::AREA_SIDE_LENGTH {
+        for j in 0..constants::AREA_SIDE_LENGTH {
+            let flat_index = i + j * constants::AREA_SIDE_LENGTH;
+            let color =
+                render_data::get_hypsometric_color(ecosystem, CellIndex::new(i, j), height_range);
+            buffer[flat_index * 3] = (color[0] * 255.0) as u8;
+            buffer[flat_index * 3 + 1] = (color[1] * 255.0) as u8;
+            buffer[flat_index * 3 + 2] = (color[2] * 255.0) as u8;
+        }
     }
     buffer
 }
 
-pub(crate) fn export_vegetation_map(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+pub fn export_vegetation_map(ecosystem: &Ecosystem, time_step: u32, path: &str) {
     let path = format!("{path}/{}-vegetation.png", time_step);
     println!("{path}");
 
@@ -207,7 +702,7 @@ pub(crate) fn export_vegetation_map(ecosystem: &Ecosystem, time_step: u32, path:
     .unwrap();
 }
 
-pub(crate) fn build_vegetation_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 3] {
+pub fn build_vegetation_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 3] {
     // r channel is for trees
     // g channel is for bushes
     let mut buffer = [0; constants::NUM_CELLS * 3];
@@ -217,14 +712,10 @@ pub(crate) fn build_vegetation_map(ecosystem: &Ecosystem) -> [u8; constants::NUM
         for j in 0..constants::AREA_SIDE_LENGTH {
             let index = CellIndex::new(i, j);
             let flat_index = i + j * constants::AREA_SIDE_LENGTH;
-            let trees_color = if let Some(trees) = ecosystem[index].trees.as_ref() {
-                let avg_height = trees.plant_height_sum / trees.number_of_plants as f32;
-                (avg_height * 8.0) as u8
-            } else {
-                0
-            };
+            let trees_color = (ecosystem[index].get_height_of_trees() * 8.0) as u8;
             let bushes_color = if let Some(bushes) = ecosystem[index].bushes.as_ref() {
-                let avg_height = bushes.plant_height_sum / bushes.number_of_plants as f32;
+                let avg_height =
+                    bushes.get_plant_height_sum() / bushes.get_number_of_plants() as f32;
                 (avg_height * 60.0) as u8
             } else {
                 0
@@ -237,3 +728,697 @@ pub(crate) fn build_vegetation_map(ecosystem: &Ecosystem) -> [u8; constants::NUM
 
     buffer
 }
+
+pub fn export_normal_map(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+    let path = format!("{path}/{}-normal.png", time_step);
+    println!("{path}");
+
+    let buf = build_normal_map(ecosystem);
+    image::save_buffer(
+        path,
+        &buf,
+        constants::AREA_SIDE_LENGTH as u32,
+        constants::AREA_SIDE_LENGTH as u32,
+        image::ColorType::Rgb8,
+    )
+    .unwrap();
+}
+
+// tangent-space normals of the mesh written by export_mesh, in the OpenGL convention
+// the tangent follows u (+x) and the bitangent follows v up the image (-y), so y is flipped
+pub fn build_normal_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 3] {
+    let mut buffer = [0; constants::NUM_CELLS * 3];
+    for i in 0..constants::AREA_SIDE_LENGTH {
+        for j in 0..constants::AREA_SIDE_LENGTH {
+            let flat_index = i + j * constants::AREA_SIDE_LENGTH;
+            let normal = ecosystem.get_normal(CellIndex::new(i, j));
+            let tangent_normal = [normal.x, -normal.y, normal.z];
+            for (k, component) in tangent_normal.into_iter().enumerate() {
+                buffer[flat_index * 3 + k] = ((component * 0.5 + 0.5) * 255.0).round() as u8;
+            }
+        }
+    }
+    buffer
+}
+
+pub fn export_slope_map(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+    let path = format!("{path}/{}-slope.png", time_step);
+    println!("{path}");
+
+    let buf = build_slope_map(ecosystem);
+    image::save_buffer(
+        path,
+        &buf,
+        constants::AREA_SIDE_LENGTH as u32,
+        constants::AREA_SIDE_LENGTH as u32,
+        image::ColorType::Rgb8,
+    )
+    .unwrap();
+}
+
+// greyscale angle between the surface and the horizontal, from black when flat to white when vertical
+pub fn build_slope_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 3] {
+    let mut buffer = [0; constants::NUM_CELLS * 3];
+    for i in 0..constants::AREA_SIDE_LENGTH {
+        for j in 0..constants::AREA_SIDE_LENGTH {
+            let flat_index = i + j * constants::AREA_SIDE_LENGTH;
+            let normal = ecosystem.get_normal(CellIndex::new(i, j));
+            let angle = normal.z.clamp(-1.0, 1.0).acos().to_degrees();
+            let slope = (angle / 90.0 * 255.0).round() as u8;
+            buffer[flat_index * 3] = slope;
+            buffer[flat_index * 3 + 1] = slope;
+            buffer[flat_index * 3 + 2] = slope;
+        }
+    }
+    buffer
+}
+
+pub fn export_material_mask(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+    let path = format!("{path}/{}-material-mask.png", time_step);
+    println!("{path}");
+
+    let buf = build_material_mask(ecosystem);
+    image::save_buffer(
+        path,
+        &buf,
+        constants::AREA_SIDE_LENGTH as u32,
+        constants::AREA_SIDE_LENGTH as u32,
+        image::ColorType::Rgba8,
+    )
+    .unwrap();
+}
+
+// splat map of the materials blended by export_color_map before vegetation is drawn over them,
+// with rock (and bare bedrock) in r, sand in g, humus in b, and standing water or snow in a
+// the channels of each pixel add up to 255, give or take rounding
+pub fn build_material_mask(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 4] {
+    let mut buffer = [0; constants::NUM_CELLS * 4];
+    for i in 0..constants::AREA_SIDE_LENGTH {
+        for j in 0..constants::AREA_SIDE_LENGTH {
+            let index = CellIndex::new(i, j);
+            let flat_index = i + j * constants::AREA_SIDE_LENGTH;
+            let (soil_height, mut weights) = render_data::get_soil_weights(ecosystem, index);
+            if soil_height == 0.0 {
+                weights = Vector3::new(1.0, 0.0, 0.0);
+            }
+            // water and snow cover the soil like in the color map
+            let (water_alpha, snow_alpha) =
+                render_data::get_water_and_snow_alphas(ecosystem, index);
+            let cover = 1.0 - (1.0 - water_alpha) * (1.0 - snow_alpha);
+            let weights = [
+                weights.x * (1.0 - cover),
+                weights.y * (1.0 - cover),
+                weights.z * (1.0 - cover),
+                cover,
+            ];
+            for (k, weight) in weights.into_iter().enumerate() {
+                buffer[flat_index * 4 + k] = (weight * 255.0).round() as u8;
+            }
+        }
+    }
+    buffer
+}
+
+pub fn export_ripple_map(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+    let path = format!("{path}/{}-ripples.png", time_step);
+    println!("{path}");
+
+    let buf = build_ripple_map(ecosystem);
+    let size = (constants::AREA_SIDE_LENGTH * RIPPLE_TEXELS_PER_CELL) as u32;
+    image::save_buffer(path, &buf, size, size, image::ColorType::Rgb8).unwrap();
+}
+
+// tangent-space normals of wind ripples on the sand, in the convention of build_normal_map,
+// with RIPPLE_TEXELS_PER_CELL texels along each side of a cell to detail the mesh up close
+// the crests run across the local wind and are higher the deeper the sand, while bare ground,
+// water, and snow stay flat
+pub fn build_ripple_map(ecosystem: &Ecosystem) -> Vec<u8> {
+    let side_length = constants::AREA_SIDE_LENGTH * RIPPLE_TEXELS_PER_CELL;
+    let mut buffer = vec![0; side_length * side_length * 3];
+    for i in 0..constants::AREA_SIDE_LENGTH {
+        for j in 0..constants::AREA_SIDE_LENGTH {
+            let index = CellIndex::new(i, j);
+            let (wind_dir, _) = sample_local_wind(ecosystem, index);
+            let wind = get_wind_direction_vector(wind_dir);
+            let (water_alpha, snow_alpha) =
+                render_data::get_water_and_snow_alphas(ecosystem, index);
+            let exposed = (1.0 - water_alpha) * (1.0 - snow_alpha);
+            let depth = f32::min(ecosystem[index].get_sand_height() / RIPPLE_SAND_DEPTH, 1.0);
+            let steepness = RIPPLE_STEEPNESS * depth * exposed;
+
+            for u in i * RIPPLE_TEXELS_PER_CELL..(i + 1) * RIPPLE_TEXELS_PER_CELL {
+                for v in j * RIPPLE_TEXELS_PER_CELL..(j + 1) * RIPPLE_TEXELS_PER_CELL {
+                    // distance along the wind, continuous across cells blowing the same way
+                    let distance = (u as f32 + 0.5) * wind.x + (v as f32 + 0.5) * wind.y;
+                    let phase = distance / RIPPLE_PERIOD * std::f32::consts::TAU;
+                    let gradient = wind * (steepness * phase.cos());
+                    let normal = Vector3::new(-gradient.x, -gradient.y, 1.0).normalize();
+                    let tangent_normal = [normal.x, -normal.y, normal.z];
+                    let flat_index = u + v * side_length;
+                    for (k, component) in tangent_normal.into_iter().enumerate() {
+                        buffer[flat_index * 3 + k] =
+                            ((component * 0.5 + 0.5) * 255.0).round() as u8;
+                    }
+                }
+            }
+        }
+    }
+    buffer
+}
+
+// writes one heat map per layer of the diff, see build_diff_heat_map
+// one color per biome, listed with the cells of each biome in a legend next to the map
+pub fn export_biome_map(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+    let map_path = format!("{path}/{}-biome.png", time_step);
+    println!("{map_path}");
+
+    let biomes: Vec<Biome> = (0..constants::NUM_CELLS)
+        .map(|i| ecosystem.classify_biome(CellIndex::get_from_flat_index(i)))
+        .collect();
+    let buf = build_biome_map(&biomes);
+    image::save_buffer(
+        map_path,
+        &buf,
+        constants::AREA_SIDE_LENGTH as u32,
+        constants::AREA_SIDE_LENGTH as u32,
+        image::ColorType::Rgb8,
+    )
+    .unwrap();
+    std::fs::write(
+        format!("{path}/{}-biome-legend.csv", time_step),
+        build_biome_legend(&biomes),
+    )
+    .unwrap();
+}
+
+// biomes of the cells by flat index
+pub fn build_biome_map(biomes: &[Biome]) -> [u8; constants::NUM_CELLS * 3] {
+    let mut buffer = [0; constants::NUM_CELLS * 3];
+    for (i, biome) in biomes.iter().enumerate() {
+        buffer[i * 3..i * 3 + 3].copy_from_slice(&biome.get_rgb());
+    }
+    buffer
+}
+
+// color of each biome in the map and how many cells it covers
+pub fn build_biome_legend(biomes: &[Biome]) -> String {
+    let mut legend = String::from("biome,r,g,b,cells\n");
+    for biome in BIOMES {
+        let [r, g, b] = biome.get_rgb();
+        let cells = biomes.iter().filter(|other| **other == biome).count();
+        legend.push_str(&format!("{},{r},{g},{b},{cells}\n", biome.get_name()));
+    }
+    legend
+}
+
+pub fn export_succession_maps(ecosystem: &Ecosystem, time_step: u32, path: &str) {
+    for (name, buf) in [
+        (
+            "time-since-disturbance",
+            build_time_since_disturbance_map(ecosystem),
+        ),
+        ("succession-stage", build_succession_stage_map(ecosystem)),
+    ] {
+        let path = format!("{path}/{time_step}-{name}.png");
+        println!("{path}");
+        image::save_buffer(
+            path,
+            &buf,
+            constants::AREA_SIDE_LENGTH as u32,
+            constants::AREA_SIDE_LENGTH as u32,
+            image::ColorType::Rgb8,
+        )
+        .unwrap();
+    }
+}
+
+// brighter where the cell has gone longer without a disturbance, white for the whole run
+pub fn build_time_since_disturbance_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 3] {
+    let mut buffer = [0; constants::NUM_CELLS * 3];
+    if ecosystem.time_step == 0 {
+        return buffer;
+    }
+    for i in 0..constants::NUM_CELLS {
+        let index = CellIndex::get_from_flat_index(i);
+        let since = ecosystem
+            .succession
+            .get_time_since_disturbance(index, ecosystem.time_step);
+        let value = (since as f32 / ecosystem.time_step as f32 * 255.0).round() as u8;
+        buffer[i * 3..i * 3 + 3].fill(value);
+    }
+    buffer
+}
+
+// cells colored by the biome of their stage of succession, black where the biome has no stage
+pub fn build_succession_stage_map(ecosystem: &Ecosystem) -> [u8; constants::NUM_CELLS * 3] {
+    let stages = [
+        Biome::BareRock,
+        Biome::Grassland,
+        Biome::Shrubland,
+        Biome::DeciduousForest,
+    ];
+    let mut buffer = [0; constants::NUM_CELLS * 3];
+    for i in 0..constants::NUM_CELLS {
+        let index = CellIndex::get_from_flat_index(i);
+        let biome = ecosystem
+            .succession
+            .get_current_biome(index)
+            .unwrap_or_else(|| ecosystem.classify_biome(index));
+        if let Some(stage) = biome.get_successional_stage() {
+            buffer[i * 3..i * 3 + 3].copy_from_slice(&stages[stage as usize].get_rgb());
+        }
+    }
+    buffer
+}
+
+pub fn export_diff_maps(diff: &EcosystemDiff, path: &str) {
+    for layer in DiffLayer::ALL {
+        let path = format!("{path}/diff-{}.png", layer.get_name());
+        println!("{path}");
+
+        let buf = build_diff_heat_map(diff.get_deltas(layer));
+        image::save_buffer(
+            path,
+            &buf,
+            constants::AREA_SIDE_LENGTH as u32,
+            constants::AREA_SIDE_LENGTH as u32,
+            image::ColorType::Rgb8,
+        )
+        .unwrap();
+    }
+}
+
+// signed changes from red for the largest loss through white for no change to blue for the largest gain
+// both sides share the same scale so their colors can be compared
+pub fn build_diff_heat_map(deltas: &[f32]) -> [u8; constants::NUM_CELLS * 3] {
+    let max_change = deltas
+        .iter()
+        .fold(0.0, |max: f32, delta| max.max(delta.abs()));
+    let mut buffer = [255; constants::NUM_CELLS * 3];
+    if max_change == 0.0 {
+        return buffer;
+    }
+    for (flat_index, delta) in deltas.iter().enumerate() {
+        let fade = ((1.0 - delta.abs() / max_change) * 255.0).round() as u8;
+        let color = if *delta < 0.0 {
+            [255, fade, fade]
+        } else {
+            [fade, fade, 255]
+        };
+        buffer[flat_index * 3..flat_index * 3 + 3].copy_from_slice(&color);
+    }
+    buffer
+}
+
+// animated gif of the color, vegetation, and hypsometric maps side by side over a run
+// frames are encoded as they are captured and the gif is finished when it is dropped
+pub struct TimeLapse {
+    encoder: GifEncoder<BufWriter<File>>,
+    // time steps between frames
+    every: u32,
+}
+
+impl TimeLapse {
+    pub fn create(path: &str, every: u32) -> Result<Self, String> {
+        println!("{path}");
+        let file = File::create(path)
+            .map_err(|error| format!("cannot create time lapse {path}: {error}"))?;
+        let mut encoder =
+            GifEncoder::new_with_speed(BufWriter::new(file), TIMELAPSE_QUANTIZATION_SPEED);
+        encoder
+            .set_repeat(image::codecs::gif::Repeat::Infinite)
+            .unwrap();
+        Ok(TimeLapse {
+            encoder,
+            every: u32::max(every, 1),
+        })
+    }
+
+    // adds a frame every `every` time steps
+    pub fn capture_if_due(&mut self, ecosystem: &Ecosystem, time_step: u32) {
+        if time_step.is_multiple_of(self.every) {
+            self.capture(ecosystem);
+        }
+    }
+
+    pub fn capture(&mut self, ecosystem: &Ecosystem) {
+        let delay = Delay::from_numer_denom_ms(TIMELAPSE_FRAME_DELAY, 1);
+        let frame = Frame::from_parts(build_timelapse_frame(ecosystem), 0, 0, delay);
+        self.encoder.encode_frame(frame).unwrap();
+    }
+}
+
+pub fn build_timelapse_frame(ecosystem: &Ecosystem) -> RgbaImage {
+    let maps = [
+        build_color_map(ecosystem),
+        build_vegetation_map(ecosystem),
+        build_hypsometrically_tinted_map(ecosystem),
+    ];
+    let side = constants::AREA_SIDE_LENGTH as u32;
+    let scale = TIMELAPSE_SCALE;
+    RgbaImage::from_fn(side * scale * maps.len() as u32, side * scale, |x, y| {
+        let map = &maps[(x / (side * scale)) as usize];
+        let flat_index = ((x % (side * scale)) / scale + (y / scale) * side) as usize;
+        image::Rgba([
+            map[flat_index * 3],
+            map[flat_index * 3 + 1],
+            map[flat_index * 3 + 2],
+            255,
+        ])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use image::AnimationDecoder;
+
+    use super::{
+        append_event_log, build_biome_legend, build_biome_map, build_color_map,
+        build_color_mode_map, build_diff_heat_map, build_material_mask, build_metrics_row,
+        build_normal_map, build_ripple_map, build_slope_map, build_succession_stage_map,
+        build_time_since_disturbance_map, build_tree_instances_csv, export_scene_bundle,
+        quote_json, write_mesh, TimeLapse, RIPPLE_TEXELS_PER_CELL, SCENE_TREES_CSV_HEADER,
+        TIMELAPSE_SCALE,
+    };
+    use crate::{
+        constants,
+        ecology::{biome::Biome, Bushes, CellIndex, Ecosystem, Trees},
+        events::log::EventLog,
+        render_data::ColorMode,
+    };
+
+    #[test]
+    fn test_write_mesh() {
+        let ecosystem = Ecosystem::init();
+        let mut buf = vec![];
+        write_mesh(&ecosystem, "0-terrain.mtl", &mut buf).unwrap();
+        let obj = String::from_utf8(buf).unwrap();
+
+        let count = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count();
+        assert_eq!(count("v "), constants::NUM_CELLS);
+        assert_eq!(count("vt "), constants::NUM_CELLS);
+        let expected = 2 * (constants::AREA_SIDE_LENGTH - 1) * (constants::AREA_SIDE_LENGTH - 1);
+        assert_eq!(count("f "), expected);
+        assert!(obj.starts_with("mtllib 0-terrain.mtl"));
+
+        // every face references a valid vertex
+        for line in obj.lines().filter(|line| line.starts_with("f ")) {
+            for vertex in line.split_whitespace().skip(1) {
+                let index: usize = vertex.split('/').next().unwrap().parse().unwrap();
+                assert!((1..=constants::NUM_CELLS).contains(&index));
+            }
+        }
+    }
+
+    #[test]
+    fn test_export_scene_bundle() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(3, 4);
+        ecosystem[index].set_trees(Trees::with_plants(0, 5, 50.0, 50.0));
+
+        // every tree stands within its cell at the height of its age class
+        let csv = build_tree_instances_csv(&ecosystem);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], SCENE_TREES_CSV_HEADER);
+        assert_eq!(lines.len(), 6);
+        for line in &lines[1..] {
+            let columns: Vec<f32> = line.split(',').map(|c| c.parse().unwrap()).collect();
+            assert_eq!(columns[0], 0.0);
+            assert!((30.0..=40.0).contains(&columns[1]), "x {}", columns[1]);
+            assert!((40.0..=50.0).contains(&columns[2]), "y {}", columns[2]);
+            assert_eq!(columns[3], ecosystem[index].get_height());
+            assert_eq!(columns[4], 10.0);
+        }
+        // and is scattered the same way every time
+        assert_eq!(build_tree_instances_csv(&ecosystem), csv);
+
+        let path = std::env::temp_dir().join("test_export_scene_bundle");
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        let bundle = export_scene_bundle(&ecosystem, 2, path.to_str().unwrap());
+        for file in [
+            "terrain-16.png",
+            "color.png",
+            "splat.png",
+            "trees-density.png",
+            "bushes-density.png",
+            "grasses-density.png",
+            "trees.csv",
+            "manifest.json",
+        ] {
+            assert!(Path::new(&format!("{bundle}/{file}")).exists(), "{file}");
+        }
+        let trees = image::open(format!("{bundle}/trees-density.png"))
+            .unwrap()
+            .into_luma8();
+        assert!(trees.get_pixel(3, 4).0[0] > 0);
+        assert_eq!(trees.get_pixel(4, 4).0[0], 0);
+        let manifest = std::fs::read_to_string(format!("{bundle}/manifest.json")).unwrap();
+        assert!(manifest.contains(&format!("\"cell_size\": {}", constants::CELL_SIDE_LENGTH)));
+        assert!(manifest.contains("\"time_step\": 2"));
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_quote_json() {
+        assert_eq!(quote_json("red maple"), "\"red maple\"");
+        assert_eq!(quote_json("say \"ah\"\\\n"), "\"say \\\"ah\\\"\\\\\\n\"");
+        // control characters take json's escapes, not rust's
+        assert_eq!(quote_json("\u{1}\u{7f}"), "\"\\u0001\\u007f\"");
+    }
+
+    #[test]
+    fn test_append_event_log() {
+        let path = std::env::temp_dir().join("test_append_event_log.csv");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        let event_log = EventLog {
+            lightning_strikes: 2,
+            ..Default::default()
+        };
+        append_event_log(&event_log, 1, path);
+        append_event_log(&EventLog::default(), 2, path);
+
+        let csv = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], EventLog::CSV_HEADER);
+        assert!(lines[1].starts_with("1,2,0"));
+        assert!(lines[2].starts_with("2,0,0"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_build_metrics_row() {
+        let mut ecosystem = Ecosystem::init();
+        for cell in ecosystem.cells.iter_mut() {
+            cell.add_humus(0.5);
+            cell.soil_moisture = 0.25;
+        }
+        ecosystem.cells[0].add_sand(1.0);
+        ecosystem.cells[0].bushes = Some(Bushes::with_plants(3, 3.0, 6.0));
+
+        let row = build_metrics_row(&ecosystem, 7);
+        let columns: Vec<&str> = row.split(',').collect();
+        assert_eq!(columns.len(), super::METRICS_CSV_HEADER.split(',').count());
+        assert_eq!(columns[0], "7");
+        assert_eq!(columns[5], "0.5");
+        let expected = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+        assert_eq!(columns[6], expected.to_string());
+        assert_eq!(columns[7], "0.25");
+        assert_eq!(columns[8], "0");
+        assert_eq!(columns[9], "3");
+    }
+
+    #[test]
+    fn test_time_lapse() {
+        let path = std::env::temp_dir().join("test_time_lapse.gif");
+        let path = path.to_str().unwrap();
+        let mut ecosystem = Ecosystem::init();
+        let mut time_lapse = TimeLapse::create(path, 2).unwrap();
+        for time_step in 1..=4 {
+            ecosystem.cells[0].add_bedrock(10.0);
+            time_lapse.capture_if_due(&ecosystem, time_step);
+        }
+        // finishes the gif
+        drop(time_lapse);
+
+        // one frame every two time steps, with the three maps side by side
+        let file = std::io::BufReader::new(std::fs::File::open(path).unwrap());
+        let decoder = image::codecs::gif::GifDecoder::new(file).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 2);
+        let side = constants::AREA_SIDE_LENGTH as u32 * TIMELAPSE_SCALE;
+        assert_eq!(frames[0].buffer().dimensions(), (3 * side, side));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_build_normal_and_slope_maps() {
+        // flat terrain faces straight up
+        let mut ecosystem = Ecosystem::init();
+        let flat_index = 50 + 50 * constants::AREA_SIDE_LENGTH;
+        let normal_map = build_normal_map(&ecosystem);
+        assert_eq!(
+            normal_map[flat_index * 3..flat_index * 3 + 3],
+            [128, 128, 255]
+        );
+        assert_eq!(build_slope_map(&ecosystem)[flat_index * 3], 0);
+
+        // ramp rising one meter per cell towards +x and flat along y
+        for x in 0..constants::AREA_SIDE_LENGTH {
+            for y in 0..constants::AREA_SIDE_LENGTH {
+                ecosystem[CellIndex::new(x, y)].add_bedrock(x as f32);
+            }
+        }
+        let normal_map = build_normal_map(&ecosystem);
+        let expected = ((-std::f32::consts::FRAC_1_SQRT_2 * 0.5 + 0.5) * 255.0).round() as u8;
+        assert_eq!(normal_map[flat_index * 3], expected);
+        assert_eq!(normal_map[flat_index * 3 + 1], 128);
+        // at 45°, halfway to vertical
+        assert_eq!(build_slope_map(&ecosystem)[flat_index * 3], 128);
+    }
+
+    #[test]
+    fn test_build_ripple_map() {
+        let mut ecosystem = Ecosystem::init();
+        // wind blowing along x
+        ecosystem.config.wind.direction = 90.0;
+        let side_length = constants::AREA_SIDE_LENGTH * RIPPLE_TEXELS_PER_CELL;
+        let texel = |map: &[u8], u: usize, v: usize| {
+            let flat_index = u + v * side_length;
+            [
+                map[flat_index * 3],
+                map[flat_index * 3 + 1],
+                map[flat_index * 3 + 2],
+            ]
+        };
+
+        // bare ground has no ripples
+        let ripple_map = build_ripple_map(&ecosystem);
+        assert_eq!(ripple_map.len(), side_length * side_length * 3);
+        assert!(ripple_map.chunks(3).all(|normal| normal == [128, 128, 255]));
+
+        // sand ripples across the wind, so the normals change along x and not along y
+        let index = CellIndex::new(50, 50);
+        ecosystem[index].add_sand(1.0);
+        let ripple_map = build_ripple_map(&ecosystem);
+        let (u, v) = (
+            index.x * RIPPLE_TEXELS_PER_CELL,
+            index.y * RIPPLE_TEXELS_PER_CELL,
+        );
+        assert_eq!(texel(&ripple_map, u, v), texel(&ripple_map, u, v + 5));
+        assert_ne!(texel(&ripple_map, u, v), texel(&ripple_map, u + 1, v));
+        assert_eq!(texel(&ripple_map, u - 1, v), [128, 128, 255]);
+
+        // until snow buries them
+        ecosystem[index].add_snow(1.0, 300.0);
+        let ripple_map = build_ripple_map(&ecosystem);
+        assert_eq!(texel(&ripple_map, u + 1, v), [128, 128, 255]);
+    }
+
+    #[test]
+    fn test_build_material_mask() {
+        let mut ecosystem = Ecosystem::init();
+        let flat_index = |index: CellIndex| index.x + index.y * constants::AREA_SIDE_LENGTH;
+
+        // bare bedrock counts as rock
+        let bare = CellIndex::new(10, 10);
+        // equal sand and humus, which is weighted like in the color map
+        let soil = CellIndex::new(20, 20);
+        ecosystem[soil].add_sand(0.5);
+        ecosystem[soil].add_humus(0.1);
+        // half a meter of water over the soil
+        let flooded = CellIndex::new(30, 30);
+        ecosystem[flooded].add_sand(1.0);
+        ecosystem[flooded].add_water(0.5);
+
+        let mask = build_material_mask(&ecosystem);
+        let pixel = |index: CellIndex| &mask[flat_index(index) * 4..flat_index(index) * 4 + 4];
+        assert_eq!(pixel(bare), [255, 0, 0, 0]);
+        assert_eq!(pixel(soil), [0, 128, 128, 0]);
+        assert_eq!(pixel(flooded), [0, 128, 0, 128]);
+    }
+
+    #[test]
+    fn test_build_diff_heat_map() {
+        let mut deltas = vec![0.0; constants::NUM_CELLS];
+        deltas[0] = -2.0;
+        deltas[1] = 1.0;
+        deltas[2] = 2.0;
+        let heat_map = build_diff_heat_map(&deltas);
+        assert_eq!(heat_map[0..3], [255, 0, 0]);
+        assert_eq!(heat_map[3..6], [128, 128, 255]);
+        assert_eq!(heat_map[6..9], [0, 0, 255]);
+        // no change is white
+        assert_eq!(heat_map[9..12], [255, 255, 255]);
+        assert!(build_diff_heat_map(&vec![0.0; constants::NUM_CELLS])
+            .iter()
+            .all(|channel| *channel == 255));
+    }
+
+    #[test]
+    fn test_build_color_mode_map() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem[CellIndex::new(3, 4)].add_sand(1.0);
+        assert_eq!(
+            build_color_mode_map(&ecosystem, &ColorMode::Standard),
+            build_color_map(&ecosystem)
+        );
+
+        let map = build_color_mode_map(&ecosystem, &ColorMode::OnlyBedrock);
+        let flat_index = 3 + 4 * constants::AREA_SIDE_LENGTH;
+        let expected = constants::BEDROCK_COLOR.map(|c| (c * 255.0) as u8);
+        assert_eq!(
+            map[flat_index * 3..flat_index * 3 + 3],
+            [expected.x, expected.y, expected.z]
+        );
+    }
+
+    #[test]
+    fn test_build_biome_map_and_legend() {
+        let mut biomes = vec![Biome::BareRock; constants::NUM_CELLS];
+        biomes[1] = Biome::Wetland;
+        biomes[2] = Biome::Wetland;
+        let map = build_biome_map(&biomes);
+        assert_eq!(map[0..3], Biome::BareRock.get_rgb());
+        assert_eq!(map[3..6], Biome::Wetland.get_rgb());
+
+        let legend = build_biome_legend(&biomes);
+        let lines: Vec<&str> = legend.lines().collect();
+        assert_eq!(lines[0], "biome,r,g,b,cells");
+        assert_eq!(
+            lines[1],
+            format!("bare rock,128,128,128,{}", constants::NUM_CELLS - 2)
+        );
+        assert!(lines.contains(&"wetland,60,130,170,2"));
+        assert!(lines.contains(&"alpine,240,240,250,0"));
+    }
+
+    #[test]
+    fn test_build_succession_maps() {
+        let mut ecosystem = Ecosystem::init();
+        let forest = CellIndex::new(0, 0);
+        let burnt = CellIndex::new(1, 0);
+        let flooded = CellIndex::new(2, 0);
+        let succession = &mut ecosystem.succession;
+        succession.record(forest, 0, Biome::Grassland);
+        succession.record(forest, 4, Biome::DeciduousForest);
+        succession.record(burnt, 0, Biome::DeciduousForest);
+        succession.record(burnt, 6, Biome::BareRock);
+        succession.record(flooded, 0, Biome::Wetland);
+        ecosystem.time_step = 8;
+
+        // undisturbed for the whole run, and burnt a quarter of the run ago
+        let map = build_time_since_disturbance_map(&ecosystem);
+        assert_eq!(map[0..3], [255; 3]);
+        assert_eq!(map[3..6], [64; 3]);
+
+        let map = build_succession_stage_map(&ecosystem);
+        assert_eq!(map[0..3], Biome::DeciduousForest.get_rgb());
+        assert_eq!(map[3..6], Biome::BareRock.get_rgb());
+        assert_eq!(map[6..9], [0; 3]);
+    }
+}
@@ -0,0 +1,79 @@
+// HISTORY
+// keeps compressed snapshots of the ecosystem before each of the last time steps so the
+// simulation can be rewound to inspect transitions that flashed past
+// only the ecosystem is rewound, exports and csv logs already written are left as they are
+
+use std::collections::VecDeque;
+
+use crate::ecology::Ecosystem;
+
+pub struct History {
+    // oldest first
+    snapshots: VecDeque<Vec<u8>>,
+    // number of time steps that can be undone
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    // remembers the ecosystem, forgetting the oldest snapshot once full
+    pub fn push(&mut self, ecosystem: &Ecosystem) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.push_snapshot(ecosystem.to_snapshot());
+    }
+
+    // remembers an ecosystem already taken with `Ecosystem::to_snapshot`
+    pub fn push_snapshot(&mut self, snapshot: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    // returns the most recent ecosystem and forgets it
+    pub fn pop(&mut self) -> Option<Ecosystem> {
+        let snapshot = self.snapshots.pop_back()?;
+        Some(Ecosystem::from_snapshot(&snapshot))
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::History;
+    use crate::ecology::Ecosystem;
+
+    #[test]
+    fn test_history() {
+        let mut history = History::new(2);
+        let mut ecosystem = Ecosystem::init();
+        for time_step in 0..3 {
+            ecosystem.time_step = time_step;
+            history.push(&ecosystem);
+        }
+
+        // only the last two are kept, newest first
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.pop().unwrap().time_step, 2);
+        assert_eq!(history.pop().unwrap().time_step, 1);
+        assert!(history.pop().is_none());
+    }
+}
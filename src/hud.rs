@@ -0,0 +1,309 @@
+use std::time::Instant;
+
+use egui_sdl2_gl::{
+    egui::{self, load::SizedTexture, Color32, FullOutput, Stroke, TextureHandle, TextureOptions},
+    painter::Painter,
+    sdl2::{event::Event, video::Window},
+    DpiScaling, EguiStateHandler, ShaderVersion,
+};
+
+use crate::{
+    camera::Camera, constants, ecology::Ecosystem, events::Events, export::build_color_mode_map,
+    render_data::ColorMode,
+};
+
+// weight of the latest frame in the smoothed frame rate
+const FPS_SMOOTHING: f32 = 0.1;
+// points along each side of the minimap in the bottom right corner
+const MINIMAP_SIZE: f32 = 200.0;
+// points from the marker of the camera on the minimap to the tip of its arrow
+const MINIMAP_ARROW_LENGTH: f32 = 15.0;
+const MINIMAP_MARKER_COLOR: Color32 = Color32::RED;
+
+// totals over the whole ecosystem shown in the overlay
+#[derive(Debug, PartialEq)]
+pub struct HudStats {
+    pub time_step: u32,
+    pub total_biomass: f32,  // in kg
+    pub sand_volume: f32,    // in m^3
+    pub humus_volume: f32,   // in m^3
+    pub wind_direction: f32, // in degrees
+    pub wind_strength: f32,  // in m/s
+}
+
+impl HudStats {
+    pub fn compute(ecosystem: &Ecosystem) -> Self {
+        let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+        let mut total_biomass = 0.0;
+        let mut sand_volume = 0.0;
+        let mut humus_volume = 0.0;
+        for cell in ecosystem.cells.iter() {
+            total_biomass += cell.estimate_tree_biomass()
+                + cell.estimate_bush_biomass()
+                + cell.estimate_grasses_biomass();
+            sand_volume += cell.get_sand_height() * cell_area;
+            humus_volume += cell.get_humus_height() * cell_area;
+        }
+        let (wind_direction, wind_strength) = match &ecosystem.wind_state {
+            Some(wind_state) => (wind_state.wind_direction, wind_state.wind_strength),
+            None => (
+                ecosystem.config.wind.direction,
+                ecosystem.config.wind.strength,
+            ),
+        };
+        HudStats {
+            time_step: ecosystem.time_step,
+            total_biomass,
+            sand_volume,
+            humus_volume,
+            wind_direction,
+            wind_strength,
+        }
+    }
+}
+
+// buttons clicked in the overlay this frame
+#[derive(Default)]
+pub struct HudActions {
+    pub toggle_pause: bool,
+    pub step: bool,
+    pub export: bool,
+    pub next_debug_event: bool,
+}
+
+// egui overlay drawn on top of the terrain
+pub struct Hud {
+    painter: Painter,
+    state: EguiStateHandler,
+    context: egui::Context,
+    start_time: Instant,
+    last_frame: Instant,
+    fps: f32,
+    // stats are only recomputed when the time step changes
+    stats: Option<HudStats>,
+    // map of the color mode, rebuilt when the time step or color mode changes
+    minimap: Option<(u32, ColorMode, TextureHandle)>,
+}
+
+impl Hud {
+    // requires a current OpenGL context
+    pub fn init(window: &Window) -> Self {
+        let (painter, state) =
+            egui_sdl2_gl::with_sdl2(window, ShaderVersion::Default, DpiScaling::Default);
+        let now = Instant::now();
+        Hud {
+            painter,
+            state,
+            context: egui::Context::default(),
+            start_time: now,
+            last_frame: now,
+            fps: 0.0,
+            stats: None,
+            minimap: None,
+        }
+    }
+
+    pub fn process_event(&mut self, window: &Window, event: Event) {
+        self.state.process_input(window, event, &mut self.painter);
+    }
+
+    // whether the mouse is over the overlay, in which case clicks should not reach the terrain
+    pub fn wants_pointer(&self) -> bool {
+        self.context.wants_pointer_input() || self.context.is_pointer_over_area()
+    }
+
+    pub fn draw(
+        &mut self,
+        window: &Window,
+        ecosystem: &Ecosystem,
+        color_mode: &ColorMode,
+        paused: bool,
+        debug_event: Events,
+        camera: &Camera,
+    ) -> HudActions {
+        let now = Instant::now();
+        let frame_secs = (now - self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        if frame_secs > 0.0 {
+            self.fps += (1.0 / frame_secs - self.fps) * FPS_SMOOTHING;
+        }
+
+        if self
+            .stats
+            .as_ref()
+            .is_none_or(|stats| stats.time_step != ecosystem.time_step)
+        {
+            self.stats = Some(HudStats::compute(ecosystem));
+        }
+        let stats = self.stats.as_ref().unwrap();
+        if self.minimap.as_ref().is_none_or(|(time_step, mode, _)| {
+            *time_step != ecosystem.time_step || mode != color_mode
+        }) {
+            let side = constants::AREA_SIDE_LENGTH;
+            let map = build_color_mode_map(ecosystem, color_mode);
+            let image = egui::ColorImage::from_rgb([side, side], &map);
+            let texture = self
+                .context
+                .load_texture("minimap", image, TextureOptions::NEAREST);
+            self.minimap = Some((ecosystem.time_step, *color_mode, texture));
+        }
+        let (_, _, minimap) = self.minimap.as_ref().unwrap();
+
+        self.state.input.time = Some(self.start_time.elapsed().as_secs_f64());
+        self.context.begin_frame(self.state.input.take());
+
+        let mut actions = HudActions::default();
+        egui::Window::new("Simulation")
+            .resizable(false)
+            .show(&self.context, |ui| {
+                ui.label(format!("Time step: {}", stats.time_step));
+                ui.label(format!("Color mode: {color_mode:?}"));
+                ui.label(format!("Total biomass: {:.0} kg", stats.total_biomass));
+                ui.label(format!("Sand volume: {:.1} m^3", stats.sand_volume));
+                ui.label(format!("Humus volume: {:.1} m^3", stats.humus_volume));
+                ui.label(format!(
+                    "Wind: {:.0} deg, {:.1} m/s",
+                    stats.wind_direction, stats.wind_strength
+                ));
+                ui.label(format!("FPS: {:.0}", self.fps));
+                ui.horizontal(|ui| {
+                    let pause_label = if paused { "Run" } else { "Pause" };
+                    actions.toggle_pause = ui.button(pause_label).clicked();
+                    actions.step = ui.button("Step").clicked();
+                    actions.export = ui.button("Export").clicked();
+                });
+                // the event is forced on the cell under the cursor with E
+                ui.horizontal(|ui| {
+                    ui.label(format!("Debug event: {debug_event:?}"));
+                    actions.next_debug_event = ui.button("Next").clicked();
+                });
+            });
+        egui::Area::new(egui::Id::new("minimap"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+            .show(&self.context, |ui| {
+                let size = egui::vec2(MINIMAP_SIZE, MINIMAP_SIZE);
+                let rect = ui
+                    .add(egui::Image::new(SizedTexture::new(minimap.id(), size)))
+                    .rect;
+                let ((u, v), direction) = get_minimap_marker(camera);
+                let center = rect.min + egui::vec2(u, v) * size;
+                ui.painter()
+                    .circle_filled(center, 4.0, MINIMAP_MARKER_COLOR);
+                if let Some((dx, dy)) = direction {
+                    ui.painter().arrow(
+                        center,
+                        egui::vec2(dx, dy) * MINIMAP_ARROW_LENGTH,
+                        Stroke::new(2.0, MINIMAP_MARKER_COLOR),
+                    );
+                }
+            });
+
+        let FullOutput {
+            platform_output,
+            textures_delta,
+            shapes,
+            pixels_per_point,
+            ..
+        } = self.context.end_frame();
+        self.state.process_output(window, &platform_output);
+        let paint_jobs = self.context.tessellate(shapes, pixels_per_point);
+
+        // the overlay is always drawn over the terrain
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+        }
+        self.painter.paint_jobs(None, textures_delta, paint_jobs);
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+        }
+        actions
+    }
+}
+
+// where the camera is over the minimap, as fractions of its sides from the top left,
+// and which way it looks across the map, unless it looks straight up or down
+// the map has one pixel per cell, and cells are one render unit apart
+pub fn get_minimap_marker(camera: &Camera) -> ((f32, f32), Option<(f32, f32)>) {
+    let side = constants::AREA_SIDE_LENGTH as f32;
+    let position = (
+        ((camera.m_position.x + 0.5) / side).clamp(0.0, 1.0),
+        ((camera.m_position.y + 0.5) / side).clamp(0.0, 1.0),
+    );
+    let length = camera.m_look.xy().norm();
+    let direction = (length > 0.001).then(|| (camera.m_look.x / length, camera.m_look.y / length));
+    (position, direction)
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+    use nalgebra::Vector3;
+
+    use super::{get_minimap_marker, HudStats};
+    use crate::{
+        camera::Camera,
+        constants,
+        ecology::{CellIndex, Ecosystem, Grasses},
+    };
+
+    #[test]
+    fn test_compute_hud_stats() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(3, 4);
+        let cell = &mut ecosystem[index];
+        cell.add_sand(0.5);
+        cell.add_humus(0.2);
+        cell.grasses = Some(Grasses {
+            coverage_density: 0.5,
+            green_fraction: 1.0,
+        });
+        let expected_biomass = cell.estimate_grasses_biomass();
+
+        let stats = HudStats::compute(&ecosystem);
+        assert_eq!(stats.time_step, 0);
+        let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+
+        let expected = 0.5 * cell_area;
+        let actual = stats.sand_volume;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        let expected = 0.2 * cell_area;
+        let actual = stats.humus_volume;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        let expected = expected_biomass;
+        let actual = stats.total_biomass;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        assert_eq!(stats.wind_direction, ecosystem.config.wind.direction);
+    }
+
+    #[test]
+    fn test_get_minimap_marker() {
+        let mut camera = Camera::init();
+        camera.look_at(
+            Vector3::new(49.5, 24.5, 50.0),
+            Vector3::new(49.5, 74.5, 0.0),
+        );
+        let ((u, v), direction) = get_minimap_marker(&camera);
+        assert_eq!((u, v), (0.5, 0.25));
+        let (dx, dy) = direction.unwrap();
+        assert!(
+            approx_eq!(f32, dx, 0.0, epsilon = 0.001) && approx_eq!(f32, dy, 1.0, epsilon = 0.001),
+            "Expected (0, 1), actual ({dx}, {dy})"
+        );
+
+        // cameras off the terrain are shown on its edge, and looking straight down has no direction
+        camera.look_at(
+            Vector3::new(-20.0, 200.0, 50.0),
+            Vector3::new(-20.0, 200.0, 0.0),
+        );
+        assert_eq!(get_minimap_marker(&camera), ((0.0, 1.0), None));
+    }
+}
@@ -1,23 +1,566 @@
-use crate::{constants, ecology::Ecosystem, render::EcosystemRenderable};
+use std::{fs::File, io::BufReader, path::Path};
+
+use crate::{
+    config::Location,
+    constants,
+    ecology::Ecosystem,
+    export::{ELEVATION_MAX_KEYWORD, ELEVATION_MIN_KEYWORD},
+};
 use image::io::Reader as ImageReader;
+use tiff::{
+    decoder::{Decoder, DecodingResult},
+    tags::Tag,
+};
+
+// meters per degree of latitude
+const METERS_PER_DEGREE: f32 = 111_320.0;
+// missing samples in SRTM tiles
+const HGT_VOID: i16 = -32768;
+// GTModelTypeGeoKey and its value for rasters in latitude and longitude
+const MODEL_TYPE_GEO_KEY: u16 = 1024;
+const MODEL_TYPE_GEOGRAPHIC: u16 = 2;
+
+// elevation raster of a real-world DEM tile
+pub struct Dem {
+    // in m, row by row starting from the north west corner
+    pub elevations: Vec<f32>,
+    pub width: usize,
+    pub height: usize,
+    // distance (in m) between samples along x and y
+    pub spacing: (f32, f32),
+    // latitude and longitude of the center of the tile, if the tile is geographic
+    pub center: Option<(f32, f32)>,
+}
+
+// terrain built from a height map or DEM tile
+pub struct ImportedTerrain {
+    pub ecosystem: Ecosystem,
+    // latitude and longitude of the terrain, for DEM tiles in latitude and longitude
+    pub location: Option<Location>,
+    // m the terrain was raised by so none of it is below 0, e.g. for tiles below sea level
+    pub elevation_offset: f32,
+}
+
+impl ImportedTerrain {
+    fn from_elevations(elevations: &[f32], location: Option<Location>) -> Self {
+        let (ecosystem, elevation_offset) = Ecosystem::init_with_elevations(elevations);
+        ImportedTerrain {
+            ecosystem,
+            location,
+            elevation_offset,
+        }
+    }
+}
 
-pub fn import_height_map(path: &str) -> EcosystemRenderable {
+pub fn import_height_map(path: &str) -> Result<ImportedTerrain, String> {
     println!("Reading height map at {path}");
+    // 16-bit height maps written by export_height_map_16 carry their real elevations
+    if let Some(elevations) = read_height_map_16(path)? {
+        return Ok(ImportedTerrain::from_elevations(&elevations, None));
+    }
+
     // read png image as height map
-    let img = ImageReader::open(path).unwrap().decode().unwrap();
-    let rgb8_vec = img.into_rgb8();
+    let img = ImageReader::open(path)
+        .map_err(|error| error.to_string())
+        .and_then(|reader| reader.decode().map_err(|error| error.to_string()))
+        .map_err(|error| format!("cannot read height map {path}: {error}"))?;
+    check_height_map_size(path, img.width(), img.height())?;
+    // 8-bit images are widened so both 8 and 16-bit images can be read
+    let rgb16_vec = img.into_rgb16();
 
     // create ecosystem terrain based on the height map
     let mut heights = [0.0; constants::AREA_SIDE_LENGTH * constants::AREA_SIDE_LENGTH];
-    // input is a u8, so a scaling factor of 0.1 means max height is 25.5m
+    // input is scaled to a u8, so a scaling factor of 0.1 means max height is 25.5m
     let height_scaling_factor = constants::HEIGHT_SCALING_FACTOR;
     println!("height_scaling_factor {height_scaling_factor}");
-    for (i, pixel) in rgb8_vec.pixels().enumerate() {
-        let height = pixel.0[0] as f32 * height_scaling_factor;
+    for (i, pixel) in rgb16_vec.pixels().enumerate() {
+        let height = pixel.0[0] as f32 / 257.0 * height_scaling_factor;
         heights[i] = height;
     }
     // println!("heights {heights:?}");
-    let ecosystem = Ecosystem::init_with_heights(heights);
+    Ok(ImportedTerrain {
+        ecosystem: Ecosystem::init_with_heights(heights),
+        location: None,
+        elevation_offset: 0.0,
+    })
+}
+
+// height maps have a pixel for every cell
+fn check_height_map_size(path: &str, width: u32, height: u32) -> Result<(), String> {
+    let side = constants::AREA_SIDE_LENGTH as u32;
+    if (width, height) != (side, side) {
+        return Err(format!(
+            "height map {path} is {width}x{height}, but the terrain is {side}x{side} cells"
+        ));
+    }
+    Ok(())
+}
+
+// returns elevations (in m) stored in a 16-bit grayscale png with an elevation range,
+// or None if the image is in any other format
+pub fn read_height_map_16(path: &str) -> Result<Option<Vec<f32>>, String> {
+    let read_error = |error: std::io::Error| format!("cannot read height map {path}: {error}");
+    let decoder = png::Decoder::new(File::open(path).map_err(read_error)?);
+    let Ok(mut reader) = decoder.read_info() else {
+        return Ok(None);
+    };
+    let info = reader.info();
+    if info.bit_depth != png::BitDepth::Sixteen || info.color_type != png::ColorType::Grayscale {
+        return Ok(None);
+    }
+    let get_text = |keyword: &str| {
+        info.uncompressed_latin1_text
+            .iter()
+            .find(|chunk| chunk.keyword == keyword)
+            .and_then(|chunk| chunk.text.parse::<f32>().ok())
+    };
+    let (Some(min), Some(max)) = (
+        get_text(ELEVATION_MIN_KEYWORD),
+        get_text(ELEVATION_MAX_KEYWORD),
+    ) else {
+        return Ok(None);
+    };
+    check_height_map_size(path, info.width, info.height)?;
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    reader
+        .next_frame(&mut buf)
+        .map_err(|error| format!("cannot read height map {path}: {error}"))?;
+    let elevations = buf
+        .chunks_exact(2)
+        .map(|bytes| {
+            let value = u16::from_be_bytes([bytes[0], bytes[1]]);
+            min + value as f32 / u16::MAX as f32 * (max - min)
+        })
+        .collect();
+    Ok(Some(elevations))
+}
+
+// whether the file is a DEM tile rather than a height map image
+pub fn is_dem(path: &str) -> bool {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    matches!(extension.as_deref(), Some("hgt" | "tif" | "tiff"))
+}
+
+// builds terrain from a window of the DEM tile centered on the tile
+// along with the location of the window for tiles in latitude and longitude
+pub fn import_dem(path: &str) -> Result<ImportedTerrain, String> {
+    println!("Reading DEM at {path}");
+    let dem = if path.to_lowercase().ends_with(".hgt") {
+        read_hgt(path)?
+    } else {
+        read_geotiff_dem(path)?
+    };
+    let location = dem.center.map(|(latitude, longitude)| Location {
+        latitude,
+        longitude,
+        // solar time zone of the longitude
+        timezone: (longitude / 15.0).round() as i32,
+    });
+    Ok(ImportedTerrain::from_elevations(&dem.resample(), location))
+}
+
+// reads an SRTM tile, which is a square grid of big endian i16 samples
+// without a header, so its position comes from a name like N41W072.hgt
+pub fn read_hgt(path: &str) -> Result<Dem, String> {
+    let bytes = std::fs::read(path).map_err(|error| format!("cannot read DEM {path}: {error}"))?;
+    let side = ((bytes.len() / 2) as f32).sqrt() as usize;
+    if side < 2 || side * side * 2 != bytes.len() {
+        return Err(format!("{path} is not a square SRTM tile"));
+    }
+    let samples: Vec<Option<f32>> = bytes
+        .chunks_exact(2)
+        .map(|bytes| {
+            let sample = i16::from_be_bytes([bytes[0], bytes[1]]);
+            (sample != HGT_VOID).then_some(sample as f32)
+        })
+        .collect();
+
+    // neighboring tiles share their edge samples
+    let degrees_per_sample = 1.0 / (side - 1) as f32;
+    let (south, west) = Path::new(path)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .and_then(parse_hgt_name)
+        .ok_or(format!(
+            "{path} is not named after the corner of its SRTM tile, like N41W072.hgt"
+        ))?;
+    let center_latitude = south + 0.5;
+    Ok(Dem {
+        elevations: fill_voids(samples),
+        width: side,
+        height: side,
+        spacing: (
+            degrees_per_sample * METERS_PER_DEGREE * center_latitude.to_radians().cos(),
+            degrees_per_sample * METERS_PER_DEGREE,
+        ),
+        center: Some((center_latitude, west + 0.5)),
+    })
+}
+
+// returns latitude and longitude of the south west corner of an SRTM tile
+fn parse_hgt_name(name: &str) -> Option<(f32, f32)> {
+    let name = name.to_uppercase();
+    let latitude: f32 = name.get(1..3)?.parse().ok()?;
+    let longitude: f32 = name.get(4..7)?.parse().ok()?;
+    let latitude = match name.get(0..1)? {
+        "N" => latitude,
+        "S" => -latitude,
+        _ => return None,
+    };
+    let longitude = match name.get(3..4)? {
+        "E" => longitude,
+        "W" => -longitude,
+        _ => return None,
+    };
+    Some((latitude, longitude))
+}
+
+// reads the first band of a GeoTIFF DEM
+pub fn read_geotiff_dem(path: &str) -> Result<Dem, String> {
+    let tiff_error = |error: tiff::TiffError| format!("cannot read DEM {path}: {error}");
+    let file = BufReader::new(
+        File::open(path).map_err(|error| format!("cannot read DEM {path}: {error}"))?,
+    );
+    let mut decoder = Decoder::new(file).map_err(tiff_error)?;
+    let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+    let (width, height) = (width as usize, height as usize);
+    // plain images have no pixel scale, so the distance between samples is unknown
+    let scale = decoder
+        .get_tag_f64_vec(Tag::ModelPixelScaleTag)
+        .ok()
+        .filter(|scale| scale.len() >= 2 && scale[0] > 0.0 && scale[1] > 0.0)
+        .ok_or(format!(
+            "{path} is not a GeoTIFF DEM, it has no pixel scale"
+        ))?;
+    let tiepoint = decoder
+        .get_tag_f64_vec(Tag::ModelTiepointTag)
+        .ok()
+        .filter(|tiepoint| tiepoint.len() >= 6)
+        .unwrap_or(vec![0.0; 6]);
+    let geo_keys = decoder
+        .get_tag_u16_vec(Tag::GeoKeyDirectoryTag)
+        .unwrap_or_default();
+    let no_data: Option<f32> = decoder
+        .find_tag(Tag::GdalNodata)
+        .map_err(tiff_error)?
+        .and_then(|value| value.into_string().ok())
+        .and_then(|value| value.trim_matches(char::from(0)).trim().parse().ok());
+
+    let samples: Vec<f32> = match decoder.read_image().map_err(tiff_error)? {
+        DecodingResult::U8(samples) => samples.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::U16(samples) => samples.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::U32(samples) => samples.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::U64(samples) => samples.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::I8(samples) => samples.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::I16(samples) => samples.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::I32(samples) => samples.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::I64(samples) => samples.into_iter().map(|s| s as f32).collect(),
+        DecodingResult::F32(samples) => samples,
+        DecodingResult::F64(samples) => samples.into_iter().map(|s| s as f32).collect(),
+    };
+    if width == 0 || height == 0 || samples.len() < width * height {
+        return Err(format!("{path} has no samples"));
+    }
+    // extra bands are interleaved after the first
+    let bands = samples.len() / (width * height);
+    let samples = samples
+        .into_iter()
+        .step_by(bands)
+        .map(|sample| (Some(sample) != no_data && sample.is_finite()).then_some(sample))
+        .collect();
+
+    // the geokey directory is a header of 4 values followed by keys of 4 values each
+    let geographic = geo_keys
+        .chunks_exact(4)
+        .skip(1)
+        .any(|key| key[0] == MODEL_TYPE_GEO_KEY && key[3] == MODEL_TYPE_GEOGRAPHIC);
+    let (scale_x, scale_y) = (scale[0] as f32, scale[1] as f32);
+    let (spacing, center) = if geographic {
+        // the tiepoint maps a raster position to a longitude and latitude
+        let west = tiepoint[3] as f32 - tiepoint[0] as f32 * scale_x;
+        let north = tiepoint[4] as f32 + tiepoint[1] as f32 * scale_y;
+        let latitude = north - height as f32 / 2.0 * scale_y;
+        let longitude = west + width as f32 / 2.0 * scale_x;
+        let spacing = (
+            scale_x * METERS_PER_DEGREE * latitude.to_radians().cos(),
+            scale_y * METERS_PER_DEGREE,
+        );
+        (spacing, Some((latitude, longitude)))
+    } else {
+        // projected rasters are assumed to be in meters
+        ((scale_x, scale_y), None)
+    };
+    Ok(Dem {
+        elevations: fill_voids(samples),
+        width,
+        height,
+        spacing,
+        center,
+    })
+}
 
-    EcosystemRenderable::init(ecosystem)
+// replaces missing samples with the lowest elevation of the tile
+fn fill_voids(samples: Vec<Option<f32>>) -> Vec<f32> {
+    let lowest = samples.iter().flatten().copied().fold(f32::MAX, f32::min);
+    let lowest = if lowest == f32::MAX { 0.0 } else { lowest };
+    samples
+        .into_iter()
+        .map(|sample| sample.unwrap_or(lowest))
+        .collect()
+}
+
+impl Dem {
+    // returns elevations of a grid of cells centered on the tile, ordered like height map pixels
+    // each cell is sampled bilinearly at its real-world distance from the center
+    pub fn resample(&self) -> Vec<f32> {
+        let center_x = (self.width - 1) as f32 / 2.0;
+        let center_y = (self.height - 1) as f32 / 2.0;
+        let offset = (constants::AREA_SIDE_LENGTH - 1) as f32 / 2.0;
+        (0..constants::NUM_CELLS)
+            .map(|i| {
+                let x = (i % constants::AREA_SIDE_LENGTH) as f32 - offset;
+                let y = (i / constants::AREA_SIDE_LENGTH) as f32 - offset;
+                self.sample(
+                    center_x + x * constants::CELL_SIDE_LENGTH / self.spacing.0,
+                    center_y + y * constants::CELL_SIDE_LENGTH / self.spacing.1,
+                )
+            })
+            .collect()
+    }
+
+    // bilinear interpolation between the four surrounding samples, clamped to the tile
+    fn sample(&self, x: f32, y: f32) -> f32 {
+        let x = x.clamp(0.0, (self.width - 1) as f32);
+        let y = y.clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = (
+            usize::min(x0 + 1, self.width - 1),
+            usize::min(y0 + 1, self.height - 1),
+        );
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+        let get = |x: usize, y: usize| self.elevations[x + y * self.width];
+        let top = get(x0, y0) * (1.0 - tx) + get(x1, y0) * tx;
+        let bottom = get(x0, y1) * (1.0 - tx) + get(x1, y1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{
+        import_height_map, parse_hgt_name, read_geotiff_dem, read_height_map_16, read_hgt, Dem,
+    };
+    use crate::{
+        constants,
+        ecology::{CellIndex, Ecosystem},
+        export::{
+            build_elevations, export_geotiff, write_height_map_16, ELEVATION_MAX_KEYWORD,
+            ELEVATION_MIN_KEYWORD,
+        },
+    };
+
+    #[test]
+    fn test_height_map_16_round_trip() {
+        // smooth slope that would be terraced by an 8-bit height map
+        let elevations: Vec<f32> = (0..constants::NUM_CELLS)
+            .map(|i| 350.0 + i as f32 * 0.0123)
+            .collect();
+        let path = std::env::temp_dir().join("test_height_map_16_round_trip.png");
+        let path = path.to_str().unwrap();
+        write_height_map_16(&elevations, path);
+
+        let actual = read_height_map_16(path).unwrap().unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(actual.len(), elevations.len());
+        // within one step of the 16-bit range
+        let max_error = (elevations[constants::NUM_CELLS - 1] - elevations[0]) / 65535.0;
+        for (actual, expected) in actual.into_iter().zip(elevations) {
+            assert!(
+                approx_eq!(f32, actual, expected, epsilon = max_error),
+                "Expected {expected}, actual {actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_height_map_16_wrong_size() {
+        // a 16-bit height map with fewer pixels than there are cells
+        let path = std::env::temp_dir().join("test_read_height_map_16_wrong_size.png");
+        let file = std::fs::File::create(&path).unwrap();
+        let mut encoder = png::Encoder::new(file, 50, 50);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Sixteen);
+        for keyword in [ELEVATION_MIN_KEYWORD, ELEVATION_MAX_KEYWORD] {
+            encoder
+                .add_text_chunk(keyword.to_string(), "0".to_string())
+                .unwrap();
+        }
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0; 50 * 50 * 2]).unwrap();
+        writer.finish().unwrap();
+
+        let path = path.to_str().unwrap();
+        let result = read_height_map_16(path);
+        let imported = import_height_map(path);
+        std::fs::remove_file(path).unwrap();
+        assert!(result.is_err());
+        assert!(imported.is_err());
+    }
+
+    #[test]
+    fn test_init_with_elevations() {
+        let elevations: Vec<f32> = (0..constants::NUM_CELLS)
+            .map(|i| 200.0 + (i % constants::AREA_SIDE_LENGTH) as f32)
+            .collect();
+        let (ecosystem, offset) = Ecosystem::init_with_elevations(&elevations);
+        assert_eq!(offset, 0.0);
+
+        // the cell at (x, y) takes the elevation of pixel x + y * side
+        let index = CellIndex::new(7, 3);
+        let expected = elevations[7 + 3 * constants::AREA_SIDE_LENGTH];
+        let actual = ecosystem[index].get_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        assert!(ecosystem[index].get_humus_height() > 0.0);
+    }
+
+    #[test]
+    fn test_init_with_elevations_below_sea_level() {
+        // a basin whose floor is 20 m below sea level
+        let elevations: Vec<f32> = (0..constants::NUM_CELLS)
+            .map(|i| (i % constants::AREA_SIDE_LENGTH) as f32 - 20.0)
+            .collect();
+        let (ecosystem, offset) = Ecosystem::init_with_elevations(&elevations);
+        assert_eq!(offset, 20.0);
+
+        // the whole terrain is raised so the floor is at 0
+        let index = CellIndex::new(7, 3);
+        let expected = elevations[7 + 3 * constants::AREA_SIDE_LENGTH] + offset;
+        let actual = ecosystem[index].get_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        assert!(ecosystem
+            .cells
+            .iter()
+            .all(|cell| cell.get_bedrock_height() >= 0.0));
+    }
+
+    #[test]
+    fn test_parse_hgt_name() {
+        assert_eq!(parse_hgt_name("N41W072"), Some((41.0, -72.0)));
+        assert_eq!(parse_hgt_name("s05e120"), Some((-5.0, 120.0)));
+        assert_eq!(parse_hgt_name("terrain"), None);
+    }
+
+    #[test]
+    fn test_read_hgt() {
+        // 3x3 tile with a void in the middle
+        let samples: [i16; 9] = [100, 200, 300, 400, -32768, 600, 700, 800, 900];
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_be_bytes()).collect();
+        let dir = std::env::temp_dir().join("test_read_hgt");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("N41W072.hgt");
+        std::fs::write(&path, bytes).unwrap();
+
+        let dem = read_hgt(path.to_str().unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!((dem.width, dem.height), (3, 3));
+        assert_eq!(dem.elevations[1], 200.0);
+        assert_eq!(dem.elevations[4], 100.0);
+        assert_eq!(dem.center, Some((41.5, -71.5)));
+        // samples are half a degree apart
+        let expected = 0.5 * super::METERS_PER_DEGREE;
+        let actual = dem.spacing.1;
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.01),
+            "Expected {expected}, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_read_dem_errors() {
+        let dir = std::env::temp_dir().join("test_read_dem_errors");
+        std::fs::create_dir_all(&dir).unwrap();
+        let samples: Vec<u8> = [100i16; 9].iter().flat_map(|s| s.to_be_bytes()).collect();
+        // renamed tiles have no position
+        let renamed = dir.join("terrain.hgt");
+        std::fs::write(&renamed, &samples).unwrap();
+        // and cut tiles are not square
+        let cut = dir.join("N41W072.hgt");
+        std::fs::write(&cut, &samples[..10]).unwrap();
+        // plain images have no pixel scale
+        let plain = dir.join("plain.tif");
+        image::GrayImage::new(4, 4).save(&plain).unwrap();
+
+        let renamed = read_hgt(renamed.to_str().unwrap());
+        let cut = read_hgt(cut.to_str().unwrap());
+        let plain = read_geotiff_dem(plain.to_str().unwrap());
+        let missing = read_geotiff_dem(dir.join("missing.tif").to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(renamed.is_err());
+        assert!(cut.is_err());
+        assert!(plain.is_err());
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_resample_dem() {
+        // ramp rising 1 m every 5 m to the east
+        let side = 1001;
+        let dem = Dem {
+            elevations: (0..side * side).map(|i| (i % side) as f32).collect(),
+            width: side,
+            height: side,
+            spacing: (5.0, 5.0),
+            center: None,
+        };
+        let elevations = dem.resample();
+
+        // cells are further apart than samples, so the ramp is steeper per cell
+        let expected = constants::CELL_SIDE_LENGTH / 5.0;
+        let actual = elevations[1] - elevations[0];
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        // and centered on the tile
+        let middle = constants::AREA_SIDE_LENGTH / 2;
+        let actual = (elevations[middle - 1] + elevations[middle]) / 2.0;
+        assert!(
+            approx_eq!(f32, actual, 500.0, epsilon = 0.001),
+            "Expected 500.0, actual {actual}"
+        );
+    }
+
+    #[test]
+    fn test_geotiff_round_trip() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem[CellIndex::new(12, 34)].add_sand(3.5);
+        let dir = std::env::temp_dir().join("test_geotiff_round_trip");
+        std::fs::create_dir_all(&dir).unwrap();
+        export_geotiff(&ecosystem, 0, dir.to_str().unwrap());
+
+        let dem = read_geotiff_dem(dir.join("0-terrain.tif").to_str().unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        // exported rasters are in meters without a geographic position
+        assert_eq!(dem.center, None);
+        let expected = build_elevations(&ecosystem);
+        let actual = dem.resample();
+        for (actual, expected) in actual.into_iter().zip(expected) {
+            assert!(
+                approx_eq!(f32, actual, expected, epsilon = 0.001),
+                "Expected {expected}, actual {actual}"
+            );
+        }
+    }
 }
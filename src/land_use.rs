@@ -0,0 +1,236 @@
+// LAND USE
+// interventions people make on the terrain, for asking how the land responds to its management
+// each works on a single cell, and is applied over areas by the script and along drags by the brush:
+// 1) logging fells all trees, leaving them behind as slash or hauling their biomass away
+// 2) planting adds young trees of a species at a given density
+// 3) terracing cuts the bedrock down into flat steps, which hold soil and water on slopes
+// 4) compaction presses the humus under paths and tracks, trampling the plants growing in it
+
+use crate::{
+    constants,
+    ecology::{Cell, CellIndex, Cohort, Trees},
+    units,
+};
+
+// height (in m) and age (in years) of planted saplings
+pub const SAPLING_HEIGHT: f32 = 1.0;
+pub const SAPLING_AGE: f32 = 2.0;
+// % of the height of humus lost when fully compacted, as its pores are pressed shut
+pub const MAX_COMPACTION: f32 = 0.3;
+
+// fells all the trees of the cell, leaving their biomass as dead vegetation if `leave_slash`
+pub fn clear_cut(cell: &mut Cell, leave_slash: bool) {
+    if leave_slash {
+        let biomass = cell.estimate_tree_biomass();
+        if biomass > 0.0 {
+            cell.add_dead_vegetation(biomass);
+        }
+    }
+    cell.trees.clear();
+}
+
+// plants saplings of the species at the density (per m^2), next to the trees already there
+pub fn plant(cell: &mut Cell, species: usize, density: f32) {
+    let number_of_plants = (density * units::CELL_AREA.0).round() as u32;
+    if number_of_plants == 0 {
+        return;
+    }
+    let mut trees = Trees::clone_from_cell(cell, species);
+    // age classes are kept from youngest to oldest
+    let position = trees
+        .cohorts
+        .partition_point(|cohort| cohort.get_average_age() < SAPLING_AGE);
+    trees.cohorts.insert(
+        position,
+        Cohort {
+            number_of_plants,
+            plant_height_sum: SAPLING_HEIGHT * number_of_plants as f32,
+            plant_age_sum: SAPLING_AGE * number_of_plants as f32,
+        },
+    );
+    cell.set_trees(trees);
+}
+
+// cuts the bedrock down to the step below it, returning the height (in m) of bedrock removed
+// the loose layers on top settle onto the step
+pub fn terrace(cell: &mut Cell, step_height: f32) -> f32 {
+    assert!(
+        step_height > 0.0,
+        "Terracing with step height {step_height}"
+    );
+    let bedrock = cell.get_bedrock_height();
+    let step = (bedrock / step_height).floor() * step_height;
+    cell.remove_bedrock(bedrock - step)
+}
+
+// compacts the humus of the cell by the given amount, from 0 to 1,
+// killing the grasses and bushes trampled into it
+pub fn compact_soil(cell: &mut Cell, amount: f32) {
+    let amount = amount.clamp(0.0, 1.0);
+    let humus = cell.get_humus_height();
+    if humus > 0.0 {
+        cell.remove_humus(humus * MAX_COMPACTION * amount);
+    }
+    if amount > 0.0 {
+        if let Some(grasses) = cell.grasses.take() {
+            let biomass = units::CELL_AREA * constants::GRASS_DENSITY * grasses.coverage_density;
+            cell.add_dead_vegetation(biomass.0);
+        }
+        if let Some(bushes) = cell.bushes.take() {
+            cell.add_dead_vegetation(bushes.estimate_biomass());
+        }
+    }
+}
+
+// cells between the corners (inclusive), row by row
+pub fn get_cells_in_area(min: [usize; 2], max: [usize; 2]) -> Vec<CellIndex> {
+    (min[1]..=max[1])
+        .flat_map(|y| (min[0]..=max[0]).map(move |x| CellIndex::new(x, y)))
+        .collect()
+}
+
+// cells along the straight lines between consecutive waypoints, each once and in order
+pub fn get_cells_along_path(waypoints: &[[usize; 2]]) -> Vec<CellIndex> {
+    let mut cells: Vec<CellIndex> = vec![];
+    let mut visit = |index: CellIndex| {
+        if !cells.contains(&index) {
+            cells.push(index);
+        }
+    };
+    if let Some(first) = waypoints.first() {
+        visit(CellIndex::new(first[0], first[1]));
+    }
+    for pair in waypoints.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        let dx = to[0] as f32 - from[0] as f32;
+        let dy = to[1] as f32 - from[1] as f32;
+        // one cell per step along the longer axis
+        let steps = dx.abs().max(dy.abs()) as usize;
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let x = (from[0] as f32 + dx * t).round() as usize;
+            let y = (from[1] as f32 + dy * t).round() as usize;
+            visit(CellIndex::new(x, y));
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{
+        clear_cut, compact_soil, get_cells_along_path, get_cells_in_area, plant, terrace,
+        MAX_COMPACTION, SAPLING_HEIGHT,
+    };
+    use crate::{
+        ecology::{Cell, CellIndex, Grasses, Trees},
+        events::vegetation::Individualized,
+    };
+
+    #[test]
+    fn test_clear_cut() {
+        let mut cell = Cell::init();
+        cell.set_trees(Trees::with_plants(0, 4, 40.0, 80.0));
+        let biomass = cell.estimate_tree_biomass();
+        let mut logged = cell.clone();
+
+        // slash is left to decompose
+        clear_cut(&mut cell, true);
+        assert!(cell.trees.is_empty());
+        assert_eq!(cell.get_dead_vegetation_biomass(), biomass);
+
+        // or hauled away
+        clear_cut(&mut logged, false);
+        assert!(logged.trees.is_empty());
+        assert_eq!(logged.get_dead_vegetation_biomass(), 0.0);
+    }
+
+    #[test]
+    fn test_plant() {
+        let mut cell = Cell::init();
+        cell.set_trees(Trees::with_plants(0, 3, 60.0, 90.0));
+
+        // 0.05 saplings per m^2 over a cell of 100 m^2
+        plant(&mut cell, 0, 0.05);
+        let trees = cell.get_trees(0).unwrap();
+        assert_eq!(trees.get_number_of_plants(), 8);
+        // the saplings are the youngest age class
+        assert_eq!(trees.cohorts[0].number_of_plants, 5);
+        assert_eq!(trees.cohorts[0].get_average_height(), SAPLING_HEIGHT);
+
+        // too sparse to plant a single tree
+        plant(&mut cell, 1, 0.001);
+        assert!(cell.get_trees(1).is_none());
+    }
+
+    #[test]
+    fn test_terrace() {
+        let mut cell = Cell::init();
+        cell.set_height_of_bedrock(12.7);
+        cell.add_sand(0.5);
+
+        let removed = terrace(&mut cell, 2.0);
+        let expected = 0.7;
+        assert!(
+            approx_eq!(f32, removed, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {removed}"
+        );
+        assert_eq!(cell.get_bedrock_height(), 12.0);
+        assert_eq!(cell.get_sand_height(), 0.5);
+
+        // a step is already flat
+        assert_eq!(terrace(&mut cell, 2.0), 0.0);
+    }
+
+    #[test]
+    fn test_compact_soil() {
+        let mut cell = Cell::init();
+        cell.add_humus(1.0);
+        cell.grasses = Some(Grasses {
+            coverage_density: 1.0,
+            green_fraction: 1.0,
+        });
+
+        compact_soil(&mut cell, 1.0);
+        let expected = 1.0 - MAX_COMPACTION;
+        let actual = cell.get_humus_height();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+        assert!(cell.grasses.is_none());
+        assert!(cell.get_dead_vegetation_biomass() > 0.0);
+    }
+
+    #[test]
+    fn test_get_cells() {
+        let area = get_cells_in_area([1, 2], [2, 3]);
+        assert_eq!(
+            area,
+            vec![
+                CellIndex::new(1, 2),
+                CellIndex::new(2, 2),
+                CellIndex::new(1, 3),
+                CellIndex::new(2, 3)
+            ]
+        );
+
+        // a path bends at its waypoints without gaps or repeats
+        let path = get_cells_along_path(&[[0, 0], [3, 0], [3, 2]]);
+        assert_eq!(
+            path,
+            vec![
+                CellIndex::new(0, 0),
+                CellIndex::new(1, 0),
+                CellIndex::new(2, 0),
+                CellIndex::new(3, 0),
+                CellIndex::new(3, 1),
+                CellIndex::new(3, 2)
+            ]
+        );
+        let diagonal = get_cells_along_path(&[[0, 0], [2, 1]]);
+        assert_eq!(diagonal.len(), 3);
+    }
+}
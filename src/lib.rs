@@ -0,0 +1,34 @@
+// core of the ecosystem simulation, shared by the viewer and batch binaries
+// the window and renderer of the viewer are behind the viewer feature, so the core builds without SDL2
+// and the renderer built on wgpu and winit is behind the wgpu feature
+pub mod args;
+pub mod brush;
+pub mod camera;
+pub mod config;
+pub mod constants;
+pub mod ecology; // apparently naming this "ecosystem" breaks rust analyzer :(
+pub mod events;
+pub mod export;
+pub mod history;
+#[cfg(feature = "viewer")]
+pub mod hud;
+pub mod import;
+pub mod land_use;
+pub mod pacing;
+#[cfg(feature = "viewer")]
+pub mod render;
+pub mod render_backend;
+pub mod render_data;
+#[cfg(feature = "viewer")]
+pub mod render_gl;
+#[cfg(feature = "wgpu")]
+pub mod render_wgpu;
+pub mod script;
+pub mod simulation;
+pub mod units;
+pub mod worker;
+
+pub use config::Config;
+pub use ecology::{Cell, CellIndex, Ecosystem};
+pub use events::Events;
+pub use simulation::Simulation;
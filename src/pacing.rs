@@ -0,0 +1,112 @@
+// PACING
+// keeps the viewer taking time steps at a target rate, independent of how fast frames are drawn
+// 1) every frame adds the time it took to an accumulator
+// 2) a time step is due once the accumulator holds a full step interval
+// 3) the accumulator holds at most one interval, so slow time steps do not pile up steps to catch up on
+
+use crate::constants;
+
+pub struct Pacing {
+    pub steps_per_second: f32,
+    // seconds since the last time step that have not been spent on one yet
+    accumulated: f32,
+}
+
+impl Pacing {
+    pub fn new(steps_per_second: f32) -> Self {
+        Pacing {
+            steps_per_second: steps_per_second.clamp(
+                constants::MIN_STEPS_PER_SECOND,
+                constants::MAX_STEPS_PER_SECOND,
+            ),
+            accumulated: 0.0,
+        }
+    }
+
+    // doubles the target rate, up to the maximum
+    pub fn speed_up(&mut self) {
+        self.steps_per_second =
+            f32::min(self.steps_per_second * 2.0, constants::MAX_STEPS_PER_SECOND);
+    }
+
+    // halves the target rate, down to the minimum
+    pub fn slow_down(&mut self) {
+        self.steps_per_second =
+            f32::max(self.steps_per_second / 2.0, constants::MIN_STEPS_PER_SECOND);
+    }
+
+    pub fn advance(&mut self, elapsed_secs: f32) {
+        let interval = 1.0 / self.steps_per_second;
+        self.accumulated = f32::min(self.accumulated + elapsed_secs, interval);
+    }
+
+    // seconds until the next time step is due
+    pub fn get_time_until_due_step(&self) -> f32 {
+        f32::max(1.0 / self.steps_per_second - self.accumulated, 0.0)
+    }
+
+    // returns whether a time step is due, spending its interval if it is
+    pub fn take_due_step(&mut self) -> bool {
+        let interval = 1.0 / self.steps_per_second;
+        if self.accumulated < interval {
+            return false;
+        }
+        self.accumulated -= interval;
+        true
+    }
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Pacing::new(constants::DEFAULT_STEPS_PER_SECOND)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pacing;
+    use crate::constants;
+    use float_cmp::approx_eq;
+
+    #[test]
+    fn test_pacing() {
+        let mut pacing = Pacing::new(4.0);
+        // steps are due every quarter of a second, however the frames are split
+        pacing.advance(0.1);
+        assert!(!pacing.take_due_step());
+        let expected = 0.15;
+        let actual = pacing.get_time_until_due_step();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+        pacing.advance(0.15);
+        assert!(pacing.take_due_step());
+        assert!(!pacing.take_due_step());
+
+        // a slow frame only makes one step due
+        pacing.advance(2.0);
+        assert!(pacing.take_due_step());
+        assert!(!pacing.take_due_step());
+    }
+
+    #[test]
+    fn test_speed_controls() {
+        let mut pacing = Pacing::new(4.0);
+        pacing.speed_up();
+        assert_eq!(pacing.steps_per_second, 8.0);
+        pacing.slow_down();
+        pacing.slow_down();
+        assert_eq!(pacing.steps_per_second, 2.0);
+
+        // the rate stays within its limits
+        for _ in 0..20 {
+            pacing.speed_up();
+        }
+        assert_eq!(pacing.steps_per_second, constants::MAX_STEPS_PER_SECOND);
+        for _ in 0..20 {
+            pacing.slow_down();
+        }
+        assert_eq!(pacing.steps_per_second, constants::MIN_STEPS_PER_SECOND);
+    }
+}
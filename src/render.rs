@@ -1,28 +1,28 @@
+pub mod backend;
+pub mod lod;
+pub mod sunlight_gpu;
+
 use gl::types::GLuint;
-use nalgebra::{Matrix3, Matrix4, Vector2, Vector3};
-use rand::Rng;
+use nalgebra::{Matrix3, Matrix4, Point3, Vector2, Vector3};
 use std::ffi::CString;
 
+use self::lod::TerrainLod;
 use crate::{
     camera::Camera,
-    constants::{self, TINTS, TINT_THRESHOLD},
-    ecology::{Bushes, CellIndex, Ecosystem, Trees},
-    events::{wind::get_local_wind, Events},
+    config::Location,
+    constants,
+    ecology::{illumination::get_sun_direction, CellIndex, Ecosystem},
+    events::wind::{get_wind_direction_vector, sample_local_wind},
+    export::{build_ripple_map, RIPPLE_TEXELS_PER_CELL},
+    render_data::{
+        self, get_vertex_index, pick_cell_along_ray, CellVertex, ColorMode, PlantInstance,
+        RenderMode, WaterVertex,
+    },
+    render_gl::{Framebuffer, ShadowMap, Texture},
 };
 
-#[derive(PartialEq)]
-pub(crate) enum ColorMode {
-    Standard,
-    HypsometricTint,
-    Sunlight,
-    SoilMoisture,
-    WindField,
-    OnlyBedrock,
-}
-
-pub(crate) struct EcosystemRenderable {
-    pub(crate) ecosystem: Ecosystem,
-    pub(crate) m_camera: Camera,
+pub struct EcosystemRenderable {
+    pub m_camera: Camera,
     m_vao: GLuint,
     m_lines_vao: GLuint,
     m_vbo: GLuint,
@@ -35,85 +35,76 @@ pub(crate) struct EcosystemRenderable {
     m_model_matrix: Matrix4<f32>,
     m_vertices: Vec<Vector3<f32>>,
     m_tree_positions: Vec<Vector2<f32>>,
+    // trees and dead vegetation are instances of one cylinder and bushes of one hemisphere,
+    // with one instance of each per cell
+    m_cylinder_vbo: GLuint,
+    m_cylinder_ibo: GLuint,
+    m_num_cylinder_indices: GLuint,
+    m_hemisphere_vbo: GLuint,
+    m_hemisphere_ibo: GLuint,
+    m_num_hemisphere_indices: GLuint,
+    m_trees_vao: GLuint,
+    m_trees_instance_vbo: GLuint,
+    m_dead_vao: GLuint,
+    m_dead_instance_vbo: GLuint,
+    m_bushes_vao: GLuint,
+    m_bushes_instance_vbo: GLuint,
+    // height between contour lines in render units, none are drawn if 0
+    m_contour_interval: f32,
+    // translucent water surface over the terrain, with one vertex per cell like the terrain
+    // its faces are sorted back to front every frame, so they are kept to sort
+    m_water_vao: GLuint,
+    m_water_vbo: GLuint,
+    m_water_ibo: GLuint,
+    m_water_vertices: Vec<WaterVertex>,
+    m_terrain_faces: Vec<Vector3<i32>>,
+    // patches of the terrain drawn coarser away from the camera, whose faces fill the ibo
+    m_terrain_lod: TerrainLod,
+    // direction towards the sun casting shadows, if shown and above the horizon
+    m_sun_direction: Option<Vector3<f32>>,
+    // whether the terrain is in the dark because the sun shown is below the horizon
+    m_night: bool,
+    // created the first time shadows are drawn
+    m_shadow_map: Option<ShadowMap>,
+    // normals of the sand ripples drawn over the terrain, if shown
+    m_ripple_map: Option<Texture>,
+    // the sun is one instance of the hemisphere of the bushes
+    m_sun_vao: GLuint,
+    m_sun_instance_vbo: GLuint,
+    // arrows of the local wind drawn over the terrain, as line segments like the grid lines
+    m_wind_vao: GLuint,
+    m_wind_vbo: GLuint,
+    m_num_wind_vertices: u32,
 }
 
-impl EcosystemRenderable {
-    pub fn init(mut ecosystem: Ecosystem) -> Self {
-        println!("Computing sunlight...");
-        ecosystem.recompute_sunlight();
+// vertices, normals, and faces
+pub type Mesh = (Vec<Vector3<f32>>, Vec<Vector3<f32>>, Vec<Vector3<i32>>);
 
+impl EcosystemRenderable {
+    pub fn init(ecosystem: &Ecosystem) -> Self {
         // initialize based on the cell grid of the ecosystem
         let num_cells = constants::AREA_SIDE_LENGTH * constants::AREA_SIDE_LENGTH;
-        let mut verts: Vec<Vector3<f32>> = vec![];
+        let verts = render_data::build_terrain_vertices(ecosystem);
         let mut normals: Vec<Vector3<f32>> = vec![];
-        let mut faces: Vec<Vector3<i32>> = vec![];
+        let faces = render_data::build_terrain_faces();
         let mut colors: Vec<Vector3<f32>> = vec![];
-        let mut lines: Vec<Vector2<i32>> = vec![];
-        verts.reserve(num_cells);
+        let mut grass: Vec<f32> = vec![];
+        let lines = render_data::build_grid_lines();
         normals.reserve(num_cells);
 
         for i in 0..constants::AREA_SIDE_LENGTH {
             for j in 0..constants::AREA_SIDE_LENGTH {
                 let index = CellIndex::new(i, j);
-                let cell = &ecosystem[index];
-                let height = cell.get_height() * (1.0 - constants::HEIGHT_SCALING_FACTOR)
-                    / constants::HEIGHT_RENDER_SCALE;
-                verts.push(Vector3::new(i as f32, j as f32, height));
                 normals.push(ecosystem.get_normal(index));
-                colors.push(Self::get_color(&ecosystem, index));
-            }
-        }
-        // simple tessellation of square grid
-        for i in 0i32..constants::AREA_SIDE_LENGTH as i32 - 1 {
-            for j in 0i32..constants::AREA_SIDE_LENGTH as i32 - 1 {
-                // build two triangles
-                let index = get_flat_index(i, j);
-                let right = get_flat_index(i + 1, j);
-                let bottom = get_flat_index(i, j + 1);
-                let bottom_right = get_flat_index(i + 1, j + 1);
-                faces.push(Vector3::new(index, bottom, right));
-                faces.push(Vector3::new(bottom, bottom_right, right));
-
-                lines.push(Vector2::new(index, right));
-                lines.push(Vector2::new(index, bottom));
-                lines.push(Vector2::new(right, bottom_right));
-                lines.push(Vector2::new(bottom, bottom_right));
-            }
-        }
-
-        // add trees and bushes
-        for i in 0..constants::AREA_SIDE_LENGTH {
-            for j in 0..constants::AREA_SIDE_LENGTH {
-                let index = CellIndex::new(i, j);
-                let cell = &ecosystem[index];
-                let center: Vector3<f32> = Vector3::new(
-                    i as f32,
-                    j as f32,
-                    cell.get_height() * (1.0 - constants::HEIGHT_SCALING_FACTOR)
-                        / constants::HEIGHT_RENDER_SCALE,
-                );
-                Self::add_tree(
-                    center,
-                    cell.get_height_of_trees(),
-                    &mut verts,
-                    &mut normals,
-                    &mut colors,
-                    &mut faces,
-                );
-                Self::add_dead(
-                    center,
-                    cell.get_dead_vegetation_biomass() / 500.0,
-                    &mut verts,
-                    &mut normals,
-                    &mut colors,
-                    &mut faces,
-                );
-                // Self::add_bush(center, cell.estimate_bush_biomass(), &mut verts, &mut normals, &mut colors, &mut faces);
+                colors.push(render_data::get_color(ecosystem, index));
+                grass.push(render_data::get_grass_coverage(
+                    ecosystem,
+                    index,
+                    &ColorMode::Standard,
+                ));
             }
         }
-
         let mut ecosystem_render = EcosystemRenderable {
-            ecosystem,
             m_vao: 0,
             m_vbo: 0,
             m_ibo: 0,
@@ -127,39 +118,41 @@ impl EcosystemRenderable {
             m_lines_ibo: 0,
             m_num_line_vertices: 0,
             m_tree_positions: vec![],
+            m_cylinder_vbo: 0,
+            m_cylinder_ibo: 0,
+            m_num_cylinder_indices: 0,
+            m_hemisphere_vbo: 0,
+            m_hemisphere_ibo: 0,
+            m_num_hemisphere_indices: 0,
+            m_trees_vao: 0,
+            m_trees_instance_vbo: 0,
+            m_dead_vao: 0,
+            m_dead_instance_vbo: 0,
+            m_bushes_vao: 0,
+            m_bushes_instance_vbo: 0,
+            m_contour_interval: 0.0,
+            m_water_vao: 0,
+            m_water_vbo: 0,
+            m_water_ibo: 0,
+            m_water_vertices: vec![],
+            m_terrain_faces: vec![],
+            m_terrain_lod: TerrainLod::new(&verts),
+            m_sun_direction: None,
+            m_night: false,
+            m_shadow_map: None,
+            m_ripple_map: None,
+            m_sun_vao: 0,
+            m_sun_instance_vbo: 0,
+            m_wind_vao: 0,
+            m_wind_vbo: 0,
+            m_num_wind_vertices: 0,
         };
 
-        // initialize tree positions
-        for _ in 0..num_cells {
-            let mut rng = rand::thread_rng();
-            let x_rand: f32 = rng.gen::<f32>() * 0.7 - 0.5;
-            let y_rand: f32 = rng.gen::<f32>() * 0.7 - 0.5;
-            ecosystem_render
-                .m_tree_positions
-                .push(Vector2::new(x_rand, y_rand));
-        }
+        ecosystem_render.m_tree_positions = render_data::build_tree_positions(ecosystem.seed);
 
         // Initialize camera in reasonable location
-        let near_plane = 0.001;
-        let far_plane = 10000.0;
-        let middle = constants::AREA_SIDE_LENGTH as f32 / 2.0;
-        let center = Vector3::new(middle, middle, constants::DEFAULT_BEDROCK_HEIGHT);
-        let eye: Vector3<f32> = center // Vector3::new(0.0, 15.0, 15.0);
-        + Vector3::new(
-            0.0,
-            1.0 * constants::AREA_SIDE_LENGTH as f32,
-            2.0 * constants::AREA_SIDE_LENGTH as f32,
-        );
-        let target: Vector3<f32> = center;
-        // println!("center {center:?}");
-        // println!("eye {eye:?}");
-        ecosystem_render.m_camera.look_at(eye, target);
-        ecosystem_render.m_camera.set_orbit_point(target);
-        ecosystem_render.m_camera.set_perspective(
-            120.0,
+        ecosystem_render.m_camera = render_data::build_camera(
             constants::SCREEN_WIDTH as f32 / constants::SCREEN_HEIGHT as f32,
-            near_plane,
-            far_plane,
         );
 
         unsafe {
@@ -167,17 +160,24 @@ impl EcosystemRenderable {
             gl::GenBuffers(1, &mut ecosystem_render.m_ibo);
             gl::GenVertexArrays(1, &mut ecosystem_render.m_vao);
 
-            EcosystemRenderable::populate_vbo(ecosystem_render.m_vbo, &verts, &normals, &colors);
+            EcosystemRenderable::populate_vbo(
+                ecosystem_render.m_vbo,
+                &verts,
+                &normals,
+                &colors,
+                &grass,
+            );
         }
 
-        // set up IBO
+        // set up IBO, starting at full resolution until the first draw picks the levels of detail
+        let lod_faces = ecosystem_render.m_terrain_lod.get_faces();
         unsafe {
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ecosystem_render.m_ibo);
             gl::BufferData(
                 gl::ELEMENT_ARRAY_BUFFER,
-                (std::mem::size_of::<i32>() * 3 * faces.len()) as gl::types::GLsizeiptr,
-                faces.as_ptr() as *const gl::types::GLvoid,
-                gl::STATIC_DRAW,
+                std::mem::size_of_val(lod_faces.as_slice()) as gl::types::GLsizeiptr,
+                lod_faces.as_ptr() as *const gl::types::GLvoid,
+                gl::DYNAMIC_DRAW,
             );
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
             let mut err: gl::types::GLenum = gl::GetError();
@@ -221,6 +221,17 @@ impl EcosystemRenderable {
                 (std::mem::size_of::<f32>() * (verts.len() * 3 + colors.len() * 3))
                     as *const gl::types::GLvoid,
             );
+            gl::EnableVertexAttribArray(6);
+            gl::VertexAttribPointer(
+                6,
+                1,
+                gl::FLOAT,
+                gl::FALSE,
+                0,
+                (std::mem::size_of::<f32>()
+                    * (verts.len() * 3 + normals.len() * 3 + colors.len() * 3))
+                    as *const gl::types::GLvoid,
+            );
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ecosystem_render.m_ibo);
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
             gl::BindVertexArray(0);
@@ -280,138 +291,415 @@ impl EcosystemRenderable {
 
         ecosystem_render.m_vertices = verts;
         ecosystem_render.m_num_vertices = num_cells as u32;
-        ecosystem_render.m_num_drawable_vertices = faces.len() as u32 * 3;
+        ecosystem_render.m_num_drawable_vertices = lod_faces.len() as u32 * 3;
         ecosystem_render.m_num_line_vertices = lines.len() as u32 * 2;
+        ecosystem_render.init_plants(ecosystem);
+        ecosystem_render.m_terrain_faces = faces;
+        ecosystem_render.init_water(ecosystem);
+        ecosystem_render.init_wind_arrows();
         ecosystem_render
     }
 
-    fn add_tree(
-        center: Vector3<f32>,
-        height: f32,
-        verts: &mut Vec<Vector3<f32>>,
-        normals: &mut Vec<Vector3<f32>>,
-        colors: &mut Vec<Vector3<f32>>,
-        faces: &mut Vec<Vector3<i32>>,
-    ) {
-        let diameter = Trees::estimate_diameter_from_height(height);
+    // cylinder of unit diameter and length standing on the origin, which every plant is an instance of
+    pub fn build_cylinder() -> Mesh {
         let resolution: i32 = 16; // Number of sides in the cylinder
-
-        // Calculate vertices and normals for the cylinder
-        let mut cylinder_verts: Vec<Vector3<f32>> = vec![];
-        let mut cylinder_normals: Vec<Vector3<f32>> = Vec::new();
-        for i in 0..resolution {
-            let phi: f32 = 4.0 * std::f32::consts::PI * (i as f32) / (resolution as f32);
-            let x: f32 = center.x + diameter * 0.5 * phi.cos();
-            let y: f32 = center.y + diameter * 0.5 * phi.sin();
-            let z: f32 = center.z;
-            cylinder_verts.push(Vector3::new(x, y, z));
-            cylinder_verts.push(Vector3::new(x, y, z + height));
-            cylinder_normals.push(Vector3::new(-phi.cos(), 0.0, -phi.sin()));
-            cylinder_normals.push(Vector3::new(-phi.cos(), 0.0, -phi.sin()));
-        }
-
-        // Add vertices, normals, and colors to the existing vectors
-        let start_index: i32 = verts.len() as i32;
-        verts.extend_from_slice(&cylinder_verts);
-        normals.extend_from_slice(&cylinder_normals);
-        colors.extend_from_slice(&vec![constants::TREES_COLOR; (resolution * 2) as usize]);
-
-        // Add faces to connect the vertices
+        let mut verts = vec![];
+        let mut normals = vec![];
+        let mut faces = vec![];
         for i in 0..resolution {
-            let a = start_index + i;
-            let b = start_index + (i + 1) % resolution;
-            let c = start_index + (i + 2) % resolution;
-            let d = start_index + (i + 3) % resolution;
-            faces.push(Vector3::new(a, b, c));
-            faces.push(Vector3::new(b, c, d));
-        }
-    }
-
-    fn add_dead(
-        center: Vector3<f32>,
-        height: f32,
-        verts: &mut Vec<Vector3<f32>>,
-        normals: &mut Vec<Vector3<f32>>,
-        colors: &mut Vec<Vector3<f32>>,
-        faces: &mut Vec<Vector3<i32>>,
-    ) {
-        let diameter = Trees::estimate_diameter_from_height(height);
-        let resolution: i32 = 16; // Number of sides in the cylinder
+            let phi: f32 = 2.0 * std::f32::consts::PI * (i as f32) / (resolution as f32);
+            let (x, y) = (0.5 * phi.cos(), 0.5 * phi.sin());
+            verts.push(Vector3::new(x, y, 0.0));
+            verts.push(Vector3::new(x, y, 1.0));
+            normals.push(Vector3::new(phi.cos(), phi.sin(), 0.0));
+            normals.push(Vector3::new(phi.cos(), phi.sin(), 0.0));
 
-        // Calculate vertices and normals for the cylinder
-        let mut cylinder_verts: Vec<Vector3<f32>> = vec![];
-        let mut cylinder_normals: Vec<Vector3<f32>> = Vec::new();
-        for i in 0..resolution {
-            let phi: f32 = 4.0 * std::f32::consts::PI * (i as f32) / (resolution as f32);
-            let x = center.x - 0.5;
-            let y = center.y + diameter * 0.5 * phi.cos();
-            let z = center.z + diameter * 0.5 * (1.0 + phi.sin());
-            cylinder_verts.push(Vector3::new(x, y, z));
-            cylinder_verts.push(Vector3::new(x + height, y, z));
-            cylinder_normals.push(Vector3::new(phi.cos(), 0.0, phi.sin()));
-            cylinder_normals.push(Vector3::new(phi.cos(), 0.0, phi.sin()));
-        }
-
-        // Add vertices, normals, and colors to the existing vectors
-        let start_index: i32 = verts.len() as i32;
-        verts.extend_from_slice(&cylinder_verts);
-        normals.extend_from_slice(&cylinder_normals);
-        colors.extend_from_slice(&vec![constants::DEAD_COLOR; (resolution * 2) as usize]);
-
-        // Add faces to connect the vertices
-        for i in 0..resolution {
-            let a = start_index + i;
-            let b = start_index + (i + 1) % resolution;
-            let c = start_index + (i + 2) % resolution;
-            let d = start_index + (i + 3) % resolution;
-            faces.push(Vector3::new(a, b, c));
-            faces.push(Vector3::new(b, c, d));
-        }
-    }
-
-    fn add_bush(
-        center: Vector3<f32>,
-        biomass: f32,
-        verts: &mut Vec<Vector3<f32>>,
-        normals: &mut Vec<Vector3<f32>>,
-        colors: &mut Vec<Vector3<f32>>,
-        faces: &mut Vec<Vector3<i32>>,
-    ) {
-        let diameter = Bushes::estimate_crown_area_from_biomass(biomass);
-        let resolution: i32 = 16;
+            // two triangles between this side and the next
+            let bottom = 2 * i;
+            let top = bottom + 1;
+            let next_bottom = 2 * ((i + 1) % resolution);
+            let next_top = next_bottom + 1;
+            faces.push(Vector3::new(bottom, next_bottom, top));
+            faces.push(Vector3::new(top, next_bottom, next_top));
+        }
+        (verts, normals, faces)
+    }
 
-        let mut hsphere_verts: Vec<Vector3<f32>> = vec![];
-        let mut hsphere_normals: Vec<Vector3<f32>> = Vec::new();
-        for i in 0..resolution {
-            let phi: f32 = 4.0 * std::f32::consts::PI * (i as f32) / (resolution as f32);
-            for j in 0..resolution {
-                let theta = 2.0 * std::f32::consts::PI * (j as f32) / (resolution as f32);
-                let x = center.x + diameter * 0.5 * phi.sin() * theta.cos();
-                let y = center.y + diameter * 0.5 * phi.sin() * theta.sin();
-                let z = center.z + diameter * 0.5 * phi.cos();
-                hsphere_verts.push(Vector3::new(x, y, z));
-                hsphere_normals.push(Vector3::new(
-                    phi.sin() * theta.cos(),
-                    phi.sin() * theta.sin(),
-                    phi.cos(),
-                ));
+    // hemisphere of unit diameter and height sitting on the origin, which every bush is an instance of
+    pub fn build_hemisphere() -> Mesh {
+        let rings: i32 = 8; // Number of rings from the top to the base
+        let segments: i32 = 16; // Number of vertices around each ring
+        let mut verts = vec![];
+        let mut normals = vec![];
+        let mut faces = vec![];
+        for i in 0..=rings {
+            let phi = std::f32::consts::FRAC_PI_2 * (i as f32) / (rings as f32);
+            for j in 0..segments {
+                let theta = 2.0 * std::f32::consts::PI * (j as f32) / (segments as f32);
+                // the base ring lies exactly on the ground
+                let z = phi.cos().max(0.0);
+                let normal = Vector3::new(phi.sin() * theta.cos(), phi.sin() * theta.sin(), z);
+                verts.push(Vector3::new(0.5 * normal.x, 0.5 * normal.y, normal.z));
+                normals.push(normal);
+            }
+        }
+        // two triangles between each pair of neighboring rings and segments
+        for i in 0..rings {
+            for j in 0..segments {
+                let a = i * segments + j;
+                let b = i * segments + (j + 1) % segments;
+                let c = (i + 1) * segments + j;
+                let d = (i + 1) * segments + (j + 1) % segments;
+                faces.push(Vector3::new(a, c, b));
+                faces.push(Vector3::new(b, c, d));
             }
         }
+        (verts, normals, faces)
+    }
 
-        // Add vertices, normals, and colors to the existing vectors
-        let start_index: i32 = verts.len() as i32;
-        verts.extend_from_slice(&hsphere_verts);
-        normals.extend_from_slice(&hsphere_normals);
-        colors.extend_from_slice(&vec![constants::BUSHES_COLOR; hsphere_verts.len()]);
+    // uploads the plant meshes and sets up a vao for each kind of plant
+    fn init_plants(&mut self, ecosystem: &Ecosystem) {
+        let cylinder = Self::build_cylinder();
+        (self.m_cylinder_vbo, self.m_cylinder_ibo) = Self::upload_mesh(&cylinder);
+        self.m_num_cylinder_indices = cylinder.2.len() as u32 * 3;
+        let hemisphere = Self::build_hemisphere();
+        (self.m_hemisphere_vbo, self.m_hemisphere_ibo) = Self::upload_mesh(&hemisphere);
+        self.m_num_hemisphere_indices = hemisphere.2.len() as u32 * 3;
 
-        // Add faces to connect the vertices
-        for i in 0..resolution {
-            let a = start_index + i;
-            let b = start_index + (i + 1) % resolution;
-            let c = start_index + (i + 2) % resolution;
-            let d = start_index + (i + 3) % resolution;
-            faces.push(Vector3::new(a, b, c));
-            faces.push(Vector3::new(b, c, d));
+        (self.m_trees_vao, self.m_trees_instance_vbo) =
+            Self::init_instance_vao(self.m_cylinder_vbo, self.m_cylinder_ibo, cylinder.0.len());
+        (self.m_dead_vao, self.m_dead_instance_vbo) =
+            Self::init_instance_vao(self.m_cylinder_vbo, self.m_cylinder_ibo, cylinder.0.len());
+        (self.m_bushes_vao, self.m_bushes_instance_vbo) = Self::init_instance_vao(
+            self.m_hemisphere_vbo,
+            self.m_hemisphere_ibo,
+            hemisphere.0.len(),
+        );
+        (self.m_sun_vao, self.m_sun_instance_vbo) = Self::init_instance_vao(
+            self.m_hemisphere_vbo,
+            self.m_hemisphere_ibo,
+            hemisphere.0.len(),
+        );
+        self.update_plant_instances(ecosystem);
+    }
+
+    // sets up the vao of the water surface and uploads its vertices
+    fn init_water(&mut self, ecosystem: &Ecosystem) {
+        let vertex_size = std::mem::size_of::<WaterVertex>();
+        unsafe {
+            gl::GenVertexArrays(1, &mut self.m_water_vao);
+            gl::GenBuffers(1, &mut self.m_water_vbo);
+            gl::GenBuffers(1, &mut self.m_water_ibo);
+            gl::BindVertexArray(self.m_water_vao);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.m_water_vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (vertex_size * constants::NUM_CELLS) as gl::types::GLsizeiptr,
+                std::ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            let vector_size = std::mem::size_of::<Vector3<f32>>();
+            let offsets = [
+                (0, 3, 0),
+                (1, 3, vector_size),
+                (2, 3, 2 * vector_size),
+                (7, 1, 3 * vector_size),
+            ];
+            for (location, components, offset) in offsets {
+                gl::EnableVertexAttribArray(location);
+                gl::VertexAttribPointer(
+                    location,
+                    components,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    vertex_size as gl::types::GLsizei,
+                    offset as *const gl::types::GLvoid,
+                );
+            }
+
+            // filled with the sorted faces before every draw
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.m_water_ibo);
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        }
+        let data =
+            render_data::build_vertex_data(&self.m_tree_positions, ecosystem, &ColorMode::Standard);
+        self.m_water_vertices = vec![data[0].water; constants::NUM_CELLS];
+        for cell in &data {
+            self.m_water_vertices[get_vertex_index(cell.index)] = cell.water;
+        }
+        Self::update_water_range(self.m_water_vbo, 0, &self.m_water_vertices);
+    }
+
+    // the arrows only have positions, like the grid lines, and are filled once shown
+    fn init_wind_arrows(&mut self) {
+        unsafe {
+            gl::GenVertexArrays(1, &mut self.m_wind_vao);
+            gl::GenBuffers(1, &mut self.m_wind_vbo);
+            gl::BindVertexArray(self.m_wind_vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.m_wind_vbo);
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    // shows the arrows of the current wind over the terrain
+    pub fn update_wind_arrows(&mut self, ecosystem: &Ecosystem) {
+        let verts = Self::build_wind_arrows(ecosystem);
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.m_wind_vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(verts.as_slice()) as gl::types::GLsizeiptr,
+                verts.as_ptr() as *const gl::types::GLvoid,
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        self.m_num_wind_vertices = verts.len() as u32;
+    }
+
+    pub fn hide_wind_arrows(&mut self) {
+        self.m_num_wind_vertices = 0;
+    }
+
+    // shows the ripples of the sand over the terrain, see export::build_ripple_map
+    pub fn update_ripple_map(&mut self, ecosystem: &Ecosystem) {
+        let pixels = build_ripple_map(ecosystem);
+        match &self.m_ripple_map {
+            Some(ripple_map) => ripple_map.update(&pixels),
+            None => {
+                let size = (constants::AREA_SIDE_LENGTH * RIPPLE_TEXELS_PER_CELL) as u32;
+                self.m_ripple_map = Some(Texture::new(size, size, &pixels));
+            }
+        }
+    }
+
+    pub fn hide_ripples(&mut self) {
+        self.m_ripple_map = None;
+    }
+
+    // pairs of end points of the line segments of one arrow per block of cells,
+    // centered on the block and pointing where the local wind blows, longer the stronger it is
+    pub fn build_wind_arrows(ecosystem: &Ecosystem) -> Vec<Vector3<f32>> {
+        let block = constants::WIND_ARROW_BLOCK_SIZE;
+        let mut verts = vec![];
+        for x in (0..constants::AREA_SIDE_LENGTH).step_by(block) {
+            for y in (0..constants::AREA_SIDE_LENGTH).step_by(block) {
+                let index = CellIndex::new(
+                    usize::min(x + block / 2, constants::AREA_SIDE_LENGTH - 1),
+                    usize::min(y + block / 2, constants::AREA_SIDE_LENGTH - 1),
+                );
+                let (wind_dir, wind_str) = sample_local_wind(ecosystem, index);
+                let length = (wind_str / constants::MAX_DISPLAYED_WIND_STRENGTH).min(1.0)
+                    * block as f32
+                    * 0.9;
+                if length <= 0.0 {
+                    continue;
+                }
+                let direction = get_wind_direction_vector(wind_dir);
+                let height = ecosystem[index].get_height()
+                    * (1.0 - constants::HEIGHT_SCALING_FACTOR)
+                    / constants::HEIGHT_RENDER_SCALE;
+                let center = Vector3::new(
+                    index.x as f32,
+                    index.y as f32,
+                    height + constants::WIND_ARROW_OFFSET,
+                );
+                let shaft = Vector3::new(direction.x, direction.y, 0.0) * length;
+                let tail = center - shaft / 2.0;
+                let tip = center + shaft / 2.0;
+                // the head is two barbs swept back from the tip
+                let barb = Vector3::new(-direction.y, direction.x, 0.0) * length * 0.2;
+                let back = tip - shaft * 0.3;
+                verts.extend([tail, tip, tip, back + barb, tip, back - barb]);
+            }
+        }
+        verts
+    }
+
+    // overwrites the water vertices starting at `first`
+    fn update_water_range(water_vbo: GLuint, first: usize, vertices: &[WaterVertex]) {
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, water_vbo);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                (std::mem::size_of::<WaterVertex>() * first) as gl::types::GLsizeiptr,
+                std::mem::size_of_val(vertices) as gl::types::GLsizeiptr,
+                vertices.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    // faces of the water surface that touch a wet cell, ordered from the farthest to the closest to the eye
+    // so translucent faces are blended over the ones behind them
+    pub fn sort_water_faces(
+        faces: &[Vector3<i32>],
+        vertices: &[WaterVertex],
+        eye: Vector3<f32>,
+    ) -> Vec<Vector3<i32>> {
+        let mut wet: Vec<(f32, Vector3<i32>)> = faces
+            .iter()
+            .filter(|face| face.iter().any(|&k| vertices[k as usize].alpha > 0.0))
+            .map(|face| {
+                let center = face
+                    .iter()
+                    .map(|&k| vertices[k as usize].position)
+                    .sum::<Vector3<f32>>()
+                    / 3.0;
+                ((center - eye).norm_squared(), *face)
+            })
+            .collect();
+        wet.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        wet.into_iter().map(|(_, face)| face).collect()
+    }
+
+    // uploads the positions and normals of a mesh into a vbo and its faces into an ibo
+    fn upload_mesh((verts, normals, faces): &Mesh) -> (GLuint, GLuint) {
+        let vertex_size = std::mem::size_of::<Vector3<f32>>();
+        let mut vbo = 0;
+        let mut ibo = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (vertex_size * (verts.len() + normals.len())) as gl::types::GLsizeiptr,
+                std::ptr::null(),
+                gl::STATIC_DRAW,
+            );
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                (vertex_size * verts.len()) as gl::types::GLsizeiptr,
+                verts.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                (vertex_size * verts.len()) as gl::types::GLsizeiptr,
+                (vertex_size * normals.len()) as gl::types::GLsizeiptr,
+                normals.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+
+            gl::GenBuffers(1, &mut ibo);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (std::mem::size_of::<i32>() * 3 * faces.len()) as gl::types::GLsizeiptr,
+                faces.as_ptr() as *const gl::types::GLvoid,
+                gl::STATIC_DRAW,
+            );
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        }
+        (vbo, ibo)
+    }
+
+    // sets up a vao drawing the mesh once per instance, returning it and its instance vbo
+    fn init_instance_vao(
+        mesh_vbo: GLuint,
+        mesh_ibo: GLuint,
+        num_vertices: usize,
+    ) -> (GLuint, GLuint) {
+        let vertex_size = std::mem::size_of::<Vector3<f32>>();
+        let instance_size = std::mem::size_of::<PlantInstance>();
+        let mut vao = 0;
+        let mut instance_vbo = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut instance_vbo);
+            gl::BindVertexArray(vao);
+
+            // mesh positions and normals
+            gl::BindBuffer(gl::ARRAY_BUFFER, mesh_vbo);
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(
+                1,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                0,
+                (vertex_size * num_vertices) as *const gl::types::GLvoid,
+            );
+
+            // one position, size, and color per instance
+            gl::BindBuffer(gl::ARRAY_BUFFER, instance_vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (instance_size * constants::NUM_CELLS) as gl::types::GLsizeiptr,
+                std::ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            let offsets = [
+                (3, 3, 0),
+                (4, 2, std::mem::size_of::<Vector3<f32>>()),
+                (
+                    5,
+                    3,
+                    std::mem::size_of::<Vector3<f32>>() + std::mem::size_of::<Vector2<f32>>(),
+                ),
+            ];
+            for (location, components, offset) in offsets {
+                gl::EnableVertexAttribArray(location);
+                gl::VertexAttribPointer(
+                    location,
+                    components,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    instance_size as gl::types::GLsizei,
+                    offset as *const gl::types::GLvoid,
+                );
+                gl::VertexAttribDivisor(location, 1);
+            }
+
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, mesh_ibo);
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        }
+        (vao, instance_vbo)
+    }
+
+    // uploads the plant instances of every cell
+    fn update_plant_instances(&mut self, ecosystem: &Ecosystem) {
+        let mut trees = Vec::with_capacity(constants::NUM_CELLS);
+        let mut dead = Vec::with_capacity(constants::NUM_CELLS);
+        let mut bushes = Vec::with_capacity(constants::NUM_CELLS);
+        for i in 0..constants::AREA_SIDE_LENGTH {
+            for j in 0..constants::AREA_SIDE_LENGTH {
+                let [tree, dead_vegetation, bush] = render_data::build_plant_instances(
+                    &self.m_tree_positions,
+                    ecosystem,
+                    CellIndex::new(i, j),
+                );
+                trees.push(tree);
+                dead.push(dead_vegetation);
+                bushes.push(bush);
+            }
+        }
+        Self::update_instance_range(self.m_trees_instance_vbo, 0, &trees);
+        Self::update_instance_range(self.m_dead_instance_vbo, 0, &dead);
+        Self::update_instance_range(self.m_bushes_instance_vbo, 0, &bushes);
+    }
+
+    // overwrites the instances starting at `first`
+    fn update_instance_range(instance_vbo: GLuint, first: usize, instances: &[PlantInstance]) {
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, instance_vbo);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                (std::mem::size_of::<PlantInstance>() * first) as gl::types::GLsizeiptr,
+                std::mem::size_of_val(instances) as gl::types::GLsizeiptr,
+                instances.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
     }
 
@@ -420,13 +708,14 @@ impl EcosystemRenderable {
         verts: &[Vector3<f32>],
         normals: &[Vector3<f32>],
         colors: &[Vector3<f32>],
+        grass: &[f32],
     ) {
         unsafe {
             gl::BindBuffer(gl::ARRAY_BUFFER, m_vbo);
             gl::BufferData(
                 gl::ARRAY_BUFFER,
                 (std::mem::size_of::<f32>()
-                    * ((verts.len() * 3) + (normals.len() * 3) + (colors.len() * 3)))
+                    * ((verts.len() * 3) + (normals.len() * 3) + (colors.len() * 3) + grass.len()))
                     as gl::types::GLsizeiptr,
                 std::ptr::null(),
                 gl::DYNAMIC_DRAW,
@@ -450,6 +739,14 @@ impl EcosystemRenderable {
                 (std::mem::size_of::<f32>() * colors.len() * 3) as gl::types::GLsizeiptr,
                 colors.as_ptr() as *const gl::types::GLvoid,
             );
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                (std::mem::size_of::<f32>()
+                    * ((verts.len() * 3) + (normals.len() * 3) + (colors.len() * 3)))
+                    as gl::types::GLsizeiptr,
+                std::mem::size_of_val(grass) as gl::types::GLsizeiptr,
+                grass.as_ptr() as *const gl::types::GLvoid,
+            );
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
             let mut err: gl::types::GLenum = gl::GetError();
             while err != gl::NO_ERROR {
@@ -460,95 +757,329 @@ impl EcosystemRenderable {
         }
     }
 
-    pub fn update_vertices(&mut self, color_mode: &ColorMode) {
-        let mut verts: Vec<Vector3<f32>> = vec![];
-        let mut normals: Vec<Vector3<f32>> = vec![];
-        let mut colors: Vec<Vector3<f32>> = vec![];
-        let mut faces: Vec<Vector3<i32>> = vec![];
-        for i in 0..constants::AREA_SIDE_LENGTH {
-            for j in 0..constants::AREA_SIDE_LENGTH {
-                let index = CellIndex::new(i, j);
-                let cell = &self.ecosystem[index];
-                // make uniform cube cells
-                let height = if *color_mode == ColorMode::OnlyBedrock {
-                    cell.get_bedrock_height() * (1.0 - constants::HEIGHT_SCALING_FACTOR)
-                        / constants::HEIGHT_RENDER_SCALE
-                } else {
-                    cell.get_height() * (1.0 - constants::HEIGHT_SCALING_FACTOR)
-                        / constants::HEIGHT_RENDER_SCALE
-                };
-                verts.push(Vector3::new(i as f32, j as f32, height));
-                normals.push(self.ecosystem.get_normal(index));
-                match color_mode {
-                    ColorMode::Standard => colors.push(Self::get_color(&self.ecosystem, index)),
-                    ColorMode::HypsometricTint => {
-                        colors.push(Self::get_hypsometric_color(&self.ecosystem, index))
-                    }
-                    ColorMode::Sunlight => {
-                        colors.push(Self::get_sunlight_color(&self.ecosystem, index))
-                    }
-                    ColorMode::SoilMoisture => colors.push(
-                        Self::get_normalize_soil_moisture_color(&self.ecosystem, index),
-                    ),
-                    ColorMode::WindField => {
-                        colors.push(Self::get_wind_field_color(&self.ecosystem, index))
-                    }
-                    ColorMode::OnlyBedrock => colors.push(constants::BEDROCK_COLOR),
-                }
+    pub fn update_vertices(&mut self, ecosystem: &Ecosystem, color_mode: &ColorMode) {
+        let data = render_data::build_vertex_data(&self.m_tree_positions, ecosystem, color_mode);
+        self.upload_vertex_data(&data);
+    }
+
+    // updates only the vertices and plants of the cells changed since the last update
+    // falls back to a full update if the color mode depends on values updated over the whole grid
+    pub fn update_changed_vertices(&mut self, ecosystem: &mut Ecosystem, color_mode: &ColorMode) {
+        let data =
+            render_data::build_changed_vertex_data(&self.m_tree_positions, ecosystem, color_mode);
+        self.upload_vertex_data(&data);
+    }
+
+    // uploads vertex data built by `build_vertex_data` or `build_changed_vertex_data`
+    // requires the OpenGL context of the renderer
+    pub fn upload_vertex_data(&mut self, data: &[CellVertex]) {
+        if data.len() < constants::NUM_CELLS {
+            for cell in data {
+                self.upload_cell_vertex(cell);
             }
+            return;
         }
 
-        // add trees and bushes
-        for i in 0..constants::AREA_SIDE_LENGTH {
-            for j in 0..constants::AREA_SIDE_LENGTH {
-                let index = CellIndex::new(i, j);
-                let cell = &self.ecosystem[index];
-                // let center: Vector3<f32> = Vector3::new(i as f32, j as f32, cell.get_height());
-                let tree_pos = self.m_tree_positions[i + j * constants::AREA_SIDE_LENGTH];
-                let center = Vector3::new(
-                    tree_pos.x + i as f32,
-                    tree_pos.y + j as f32,
-                    cell.get_height() * (1.0 - constants::HEIGHT_SCALING_FACTOR)/ constants::HEIGHT_RENDER_SCALE,
-                );
-                Self::add_tree(
-                    center,
-                    cell.get_height_of_trees() / 10.0,
-                    &mut verts,
-                    &mut normals,
-                    &mut colors,
-                    &mut faces,
-                );
-                Self::add_dead(
-                    center,
-                    cell.get_dead_vegetation_biomass() / 500.0,
-                    &mut verts,
-                    &mut normals,
-                    &mut colors,
-                    &mut faces,
+        // replacing whole buffers is much faster than a range per cell
+        let mut verts = vec![Vector3::zeros(); constants::NUM_CELLS];
+        let mut normals = vec![Vector3::zeros(); constants::NUM_CELLS];
+        let mut colors = vec![Vector3::zeros(); constants::NUM_CELLS];
+        let mut grass = vec![0.0; constants::NUM_CELLS];
+        let mut plants = vec![data[0].plants; constants::NUM_CELLS];
+        let mut water = vec![data[0].water; constants::NUM_CELLS];
+        for cell in data {
+            let k = get_vertex_index(cell.index);
+            verts[k] = cell.position;
+            normals[k] = cell.normal;
+            colors[k] = cell.color;
+            grass[k] = cell.grass_coverage;
+            plants[k] = cell.plants;
+            water[k] = cell.water;
+        }
+        let [trees, dead, bushes] = [0, 1, 2].map(|kind| {
+            plants
+                .iter()
+                .map(|p| p[kind])
+                .collect::<Vec<PlantInstance>>()
+        });
+
+        EcosystemRenderable::populate_vbo(self.m_vbo, &verts, &normals, &colors, &grass);
+
+        // move the grid lines with the terrain
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.m_lines_vbo);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                (std::mem::size_of::<f32>() * constants::NUM_CELLS * 3) as gl::types::GLsizeiptr,
+                verts.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        self.m_vertices = verts;
+        self.m_terrain_lod.mark_all_changed();
+        Self::update_instance_range(self.m_trees_instance_vbo, 0, &trees);
+        Self::update_instance_range(self.m_dead_instance_vbo, 0, &dead);
+        Self::update_instance_range(self.m_bushes_instance_vbo, 0, &bushes);
+        Self::update_water_range(self.m_water_vbo, 0, &water);
+        self.m_water_vertices = water;
+    }
+
+    fn upload_cell_vertex(&mut self, cell: &CellVertex) {
+        let num_vertices = self.m_vertices.len();
+        let k = get_vertex_index(cell.index);
+        self.m_vertices[k] = cell.position;
+        self.m_terrain_lod.mark_changed(cell.index);
+        Self::update_vbo_range(
+            self.m_vbo,
+            num_vertices,
+            k,
+            &[cell.position],
+            &[cell.normal],
+            &[cell.color],
+            &[cell.grass_coverage],
+        );
+        // the grid lines only have positions
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.m_lines_vbo);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                (std::mem::size_of::<Vector3<f32>>() * k) as gl::types::GLsizeiptr,
+                std::mem::size_of::<Vector3<f32>>() as gl::types::GLsizeiptr,
+                cell.position.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+
+        let [tree, dead, bush] = cell.plants;
+        Self::update_instance_range(self.m_trees_instance_vbo, k, &[tree]);
+        Self::update_instance_range(self.m_dead_instance_vbo, k, &[dead]);
+        Self::update_instance_range(self.m_bushes_instance_vbo, k, &[bush]);
+        self.m_water_vertices[k] = cell.water;
+        Self::update_water_range(self.m_water_vbo, k, &[cell.water]);
+    }
+
+    // overwrites the vertices starting at `first` in a vbo laid out by populate_vbo
+    fn update_vbo_range(
+        m_vbo: GLuint,
+        num_vertices: usize,
+        first: usize,
+        verts: &[Vector3<f32>],
+        normals: &[Vector3<f32>],
+        colors: &[Vector3<f32>],
+        grass: &[f32],
+    ) {
+        let size = std::mem::size_of::<Vector3<f32>>();
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, m_vbo);
+            for (section, data) in [verts, normals, colors].into_iter().enumerate() {
+                gl::BufferSubData(
+                    gl::ARRAY_BUFFER,
+                    (size * (section * num_vertices + first)) as gl::types::GLsizeiptr,
+                    std::mem::size_of_val(data) as gl::types::GLsizeiptr,
+                    data.as_ptr() as *const gl::types::GLvoid,
                 );
-                // Self::add_bush(center, cell.estimate_bush_biomass(), &mut verts, &mut normals, &mut colors, &mut faces);
             }
+            // grass coverage follows the three vector sections
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                (size * 3 * num_vertices + std::mem::size_of::<f32>() * first)
+                    as gl::types::GLsizeiptr,
+                std::mem::size_of_val(grass) as gl::types::GLsizeiptr,
+                grass.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
+    }
 
-        EcosystemRenderable::populate_vbo(self.m_vbo, &verts, &normals, &colors);
+    // draws contour lines over the terrain if the color mode has them
+    pub fn set_contours(&mut self, color_mode: &ColorMode) {
+        self.m_contour_interval = render_data::get_contour_interval(color_mode);
     }
 
-    pub fn draw(&mut self, program_id: GLuint, render_mode: gl::types::GLuint) {
-        if render_mode == gl::LINES {
-            unsafe {
-                let c_str = CString::new("wire").unwrap();
-                let wire_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
-                assert!(wire_loc != -1);
-                gl::Uniform1i(wire_loc, 1);
-            }
+    // places the sun of the color mode in the sky to cast shadows, if it has one
+    pub fn set_sun(&mut self, location: &Location, color_mode: &ColorMode) {
+        (self.m_sun_direction, self.m_night) = match color_mode {
+            ColorMode::Shadows(month, hour) => match get_sun_direction(location, *month, *hour) {
+                Some(direction) => (Some(direction), false),
+                None => (None, true),
+            },
+            _ => (None, false),
+        };
+        if let Some(direction) = self.m_sun_direction {
+            let sun = PlantInstance {
+                position: Self::get_terrain_center() + direction * constants::SUN_DISTANCE
+                    - Vector3::new(0.0, 0.0, constants::SUN_DIAMETER / 4.0),
+                size: Vector2::new(constants::SUN_DIAMETER, constants::SUN_DIAMETER / 2.0),
+                color: constants::SUN_COLOR,
+            };
+            Self::update_instance_range(self.m_sun_instance_vbo, 0, &[sun]);
+        }
+    }
+
+    // point the sun looks at when casting shadows
+    fn get_terrain_center() -> Vector3<f32> {
+        let middle = constants::AREA_SIDE_LENGTH as f32 / 2.0;
+        Vector3::new(middle, middle, 0.0)
+    }
+
+    // projection of the terrain as seen from the sun, whose depth is stored in the shadow map
+    // orthographic since the sun is so far away that its rays are parallel
+    pub fn get_light_space(sun_direction: Vector3<f32>) -> Matrix4<f32> {
+        let center = Self::get_terrain_center();
+        let eye = center + sun_direction * constants::SUN_DISTANCE;
+        // any up that is not parallel to the rays
+        let up = if sun_direction.z.abs() > 0.99 {
+            Vector3::new(0.0, 1.0, 0.0)
         } else {
-            unsafe {
-                let c_str = CString::new("wire").unwrap();
-                let wire_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
-                assert!(wire_loc != -1);
-                gl::Uniform1i(wire_loc, 0);
+            Vector3::new(0.0, 0.0, 1.0)
+        };
+        let view = Matrix4::look_at_rh(&Point3::from(eye), &Point3::from(center), &up);
+        // covers every cell whichever way the sun shines, and heights well above the bedrock
+        let radius = constants::AREA_SIDE_LENGTH as f32;
+        let proj = Matrix4::new_orthographic(
+            -radius,
+            radius,
+            -radius,
+            radius,
+            0.0,
+            2.0 * constants::SUN_DISTANCE,
+        );
+        proj * view
+    }
+
+    // renders the depth of the terrain from the sun into the shadow map
+    fn draw_shadow_map(&mut self, program_id: GLuint, light_space: &Matrix4<f32>) {
+        if self.m_shadow_map.is_none() {
+            match ShadowMap::new(constants::SHADOW_MAP_SIZE) {
+                Ok(shadow_map) => self.m_shadow_map = Some(shadow_map),
+                Err(error) => {
+                    println!("{error}, drawing without shadows");
+                    self.m_sun_direction = None;
+                    return;
+                }
             }
         }
+        let shadow_map = self.m_shadow_map.as_ref().unwrap();
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            shadow_map.bind();
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+            // push the depth back so lit slopes do not shadow themselves
+            gl::Enable(gl::POLYGON_OFFSET_FILL);
+            gl::PolygonOffset(2.0, 4.0);
+
+            let identity = Matrix4::<f32>::identity();
+            for (name, matrix) in [
+                ("model", &self.m_model_matrix),
+                ("view", &identity),
+                ("proj", light_space),
+            ] {
+                let c_str = CString::new(name).unwrap();
+                let loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+                assert!(loc != -1);
+                gl::UniformMatrix4fv(loc, 1, gl::FALSE, &matrix[0]);
+            }
+            gl::BindVertexArray(self.m_vao);
+            gl::DrawElements(
+                gl::TRIANGLES,
+                self.m_num_drawable_vertices as i32,
+                gl::UNSIGNED_INT,
+                std::ptr::null(),
+            );
+            gl::BindVertexArray(0);
+
+            gl::Disable(gl::POLYGON_OFFSET_FILL);
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            shadow_map.unbind();
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+    }
+
+    // points the camera at the terrain in the middle of the area
+    pub fn refocus_camera(&mut self, ecosystem: &Ecosystem) {
+        let middle = constants::AREA_SIDE_LENGTH / 2;
+        let height = ecosystem[CellIndex::new(middle, middle)].get_height()
+            * (1.0 - constants::HEIGHT_SCALING_FACTOR)
+            / constants::HEIGHT_RENDER_SCALE;
+        self.m_camera
+            .focus(Vector3::new(middle as f32, middle as f32, height));
+    }
+
+    // returns the cell under the given pixel of the window, if any
+    pub fn pick_cell(&mut self, screen_x: i32, screen_y: i32) -> Option<CellIndex> {
+        let ndc_x = 2.0 * screen_x as f32 / constants::SCREEN_WIDTH as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * screen_y as f32 / constants::SCREEN_HEIGHT as f32;
+        let direction = self.m_camera.get_ray_direction(ndc_x, ndc_y);
+        pick_cell_along_ray(&self.m_vertices, self.m_camera.m_position, direction)
+    }
+
+    // draws the scene into an off-screen framebuffer of the given size and returns its rgb pixels
+    // from the top row down, keeping the camera but fitting its view to the aspect of the framebuffer
+    pub fn render_offscreen(
+        &mut self,
+        program_id: GLuint,
+        render_mode: &RenderMode,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, String> {
+        let framebuffer = Framebuffer::new(width, height)?;
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        let aspect = self.m_camera.get_aspect();
+        self.m_camera.set_aspect(width as f32 / height as f32);
+
+        framebuffer.bind();
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::UseProgram(program_id);
+        }
+        self.draw(program_id, render_mode);
+        let pixels = framebuffer.read_pixels();
+        framebuffer.unbind();
+
+        // back to drawing the window
+        self.m_camera.set_aspect(aspect);
+        unsafe {
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+        Ok(pixels)
+    }
+
+    // picks the levels of detail of the terrain for the camera, uploading the faces if any changed
+    fn update_terrain_lod(&mut self) {
+        if !self
+            .m_terrain_lod
+            .update(&self.m_vertices, self.m_camera.m_position)
+        {
+            return;
+        }
+        let faces = self.m_terrain_lod.get_faces();
+        unsafe {
+            gl::BindVertexArray(self.m_vao);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                std::mem::size_of_val(faces.as_slice()) as gl::types::GLsizeiptr,
+                faces.as_ptr() as *const gl::types::GLvoid,
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindVertexArray(0);
+        }
+        self.m_num_drawable_vertices = faces.len() as u32 * 3;
+    }
+
+    pub fn draw(&mut self, program_id: GLuint, render_mode: &RenderMode) {
+        self.update_terrain_lod();
+
+        // the terrain is drawn from the sun first to know what it lights
+        let light_space = self.m_sun_direction.map(Self::get_light_space);
+        if let Some(light_space) = &light_space {
+            self.draw_shadow_map(program_id, light_space);
+        }
+
         // set view and proj matrices
         unsafe {
             let c_str = CString::new("view").unwrap();
@@ -586,14 +1117,161 @@ impl EcosystemRenderable {
             assert!(inv_model_loc != -1);
             gl::UniformMatrix3fv(inv_model_loc, 1, gl::FALSE, &inverse_transpose_model[0]);
 
-            gl::BindVertexArray(self.m_vao);
-            gl::Enable(gl::LINE_SMOOTH);
-            gl::DrawElements(
-                render_mode,
-                self.m_num_drawable_vertices as i32,
-                gl::UNSIGNED_INT,
-                std::ptr::null(),
-            );
+            let c_str = CString::new("wire").unwrap();
+            let wire_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+            assert!(wire_loc != -1);
+
+            let c_str = CString::new("contourInterval").unwrap();
+            let contour_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+            assert!(contour_loc != -1);
+
+            // 0 without shadows, 1 looked up in the shadow map, 2 in the dark of night
+            let c_str = CString::new("shadows").unwrap();
+            let shadows_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+            assert!(shadows_loc != -1);
+            match (&light_space, &self.m_shadow_map) {
+                (Some(light_space), Some(shadow_map)) => {
+                    gl::Uniform1i(shadows_loc, 1);
+                    let c_str = CString::new("lightSpace").unwrap();
+                    let light_space_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+                    assert!(light_space_loc != -1);
+                    gl::UniformMatrix4fv(light_space_loc, 1, gl::FALSE, &light_space[0]);
+                    let c_str = CString::new("shadowMap").unwrap();
+                    let shadow_map_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+                    assert!(shadow_map_loc != -1);
+                    shadow_map.bind_texture(constants::SHADOW_MAP_TEXTURE_UNIT);
+                    gl::Uniform1i(shadow_map_loc, constants::SHADOW_MAP_TEXTURE_UNIT as i32);
+                }
+                _ => gl::Uniform1i(shadows_loc, if self.m_night { 2 } else { 0 }),
+            }
+
+            // sand ripples are only drawn on the terrain
+            let c_str = CString::new("ripples").unwrap();
+            let ripples_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+            assert!(ripples_loc != -1);
+            if let Some(ripple_map) = &self.m_ripple_map {
+                let c_str = CString::new("rippleMap").unwrap();
+                let ripple_map_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+                assert!(ripple_map_loc != -1);
+                ripple_map.bind(constants::RIPPLE_MAP_TEXTURE_UNIT);
+                gl::Uniform1i(ripple_map_loc, constants::RIPPLE_MAP_TEXTURE_UNIT as i32);
+                // cells are one render unit apart
+                let c_str = CString::new("rippleScale").unwrap();
+                let ripple_scale_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+                assert!(ripple_scale_loc != -1);
+                gl::Uniform1f(ripple_scale_loc, 1.0 / constants::AREA_SIDE_LENGTH as f32);
+            }
+
+            if *render_mode != RenderMode::Wireframe {
+                gl::Uniform1i(wire_loc, 0);
+                gl::Uniform1f(contour_loc, self.m_contour_interval);
+                gl::Uniform1i(ripples_loc, self.m_ripple_map.is_some() as i32);
+                // push the faces back so the grid lines drawn over them are not hidden
+                if *render_mode == RenderMode::WireframeOverlay {
+                    gl::Enable(gl::POLYGON_OFFSET_FILL);
+                    gl::PolygonOffset(1.0, 1.0);
+                }
+                gl::BindVertexArray(self.m_vao);
+                gl::DrawElements(
+                    gl::TRIANGLES,
+                    self.m_num_drawable_vertices as i32,
+                    gl::UNSIGNED_INT,
+                    std::ptr::null(),
+                );
+                gl::Disable(gl::POLYGON_OFFSET_FILL);
+                // contour lines are only drawn on the terrain
+                gl::Uniform1f(contour_loc, 0.0);
+                gl::Uniform1i(ripples_loc, 0);
+
+                // trees and bushes stand up and dead vegetation lies on the ground
+                let c_str = CString::new("instanced").unwrap();
+                let instanced_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+                assert!(instanced_loc != -1);
+                let c_str = CString::new("lying").unwrap();
+                let lying_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+                assert!(lying_loc != -1);
+                gl::Uniform1i(instanced_loc, 1);
+                for (vao, num_indices, lying) in [
+                    (self.m_trees_vao, self.m_num_cylinder_indices, 0),
+                    (self.m_dead_vao, self.m_num_cylinder_indices, 1),
+                    (self.m_bushes_vao, self.m_num_hemisphere_indices, 0),
+                ] {
+                    gl::Uniform1i(lying_loc, lying);
+                    gl::BindVertexArray(vao);
+                    gl::DrawElementsInstanced(
+                        gl::TRIANGLES,
+                        num_indices as i32,
+                        gl::UNSIGNED_INT,
+                        std::ptr::null(),
+                        constants::NUM_CELLS as i32,
+                    );
+                }
+                // the sun itself is never in shadow
+                if self.m_sun_direction.is_some() {
+                    gl::Uniform1i(shadows_loc, 0);
+                    gl::Uniform1i(lying_loc, 0);
+                    gl::BindVertexArray(self.m_sun_vao);
+                    gl::DrawElementsInstanced(
+                        gl::TRIANGLES,
+                        self.m_num_hemisphere_indices as i32,
+                        gl::UNSIGNED_INT,
+                        std::ptr::null(),
+                        1,
+                    );
+                }
+                gl::Uniform1i(instanced_loc, 0);
+
+                // translucent water goes last, back to front and without hiding what is behind it
+                let faces = Self::sort_water_faces(
+                    &self.m_terrain_faces,
+                    &self.m_water_vertices,
+                    self.m_camera.m_position,
+                );
+                if !faces.is_empty() {
+                    let c_str = CString::new("translucent").unwrap();
+                    let translucent_loc = gl::GetUniformLocation(program_id, c_str.as_ptr());
+                    assert!(translucent_loc != -1);
+                    gl::Uniform1i(translucent_loc, 1);
+                    gl::Enable(gl::BLEND);
+                    gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+                    gl::DepthMask(gl::FALSE);
+                    gl::BindVertexArray(self.m_water_vao);
+                    gl::BufferData(
+                        gl::ELEMENT_ARRAY_BUFFER,
+                        std::mem::size_of_val(faces.as_slice()) as gl::types::GLsizeiptr,
+                        faces.as_ptr() as *const gl::types::GLvoid,
+                        gl::STREAM_DRAW,
+                    );
+                    gl::DrawElements(
+                        gl::TRIANGLES,
+                        faces.len() as i32 * 3,
+                        gl::UNSIGNED_INT,
+                        std::ptr::null(),
+                    );
+                    gl::DepthMask(gl::TRUE);
+                    gl::Disable(gl::BLEND);
+                    gl::Uniform1i(translucent_loc, 0);
+                }
+            }
+            if *render_mode != RenderMode::Solid {
+                gl::Uniform1i(wire_loc, 1);
+                gl::BindVertexArray(self.m_lines_vao);
+                gl::Enable(gl::LINE_SMOOTH);
+                gl::DrawElements(
+                    gl::LINES,
+                    self.m_num_line_vertices as i32,
+                    gl::UNSIGNED_INT,
+                    std::ptr::null(),
+                );
+            }
+            // wind arrows stay visible over the hills they blow across
+            if self.m_num_wind_vertices > 0 {
+                gl::Uniform1i(wire_loc, 1);
+                gl::Disable(gl::DEPTH_TEST);
+                gl::BindVertexArray(self.m_wind_vao);
+                gl::DrawArrays(gl::LINES, 0, self.m_num_wind_vertices as i32);
+                gl::Enable(gl::DEPTH_TEST);
+            }
 
             let mut err: gl::types::GLenum = gl::GetError();
             while err != gl::NO_ERROR {
@@ -605,180 +1283,148 @@ impl EcosystemRenderable {
             gl::BindVertexArray(0);
         }
     }
+}
 
-    pub fn get_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
-        // rock (gray), sand (pale yellow), humus (light brown), trees (dark green), bushes (medium green), grass (light green), dead (dark brown)
-        let mut color: Vector3<f32>;
-        let soil_height: f32;
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+    use nalgebra::Vector3;
 
-        (soil_height, color) = Self::get_soil_color(ecosystem, index);
-        // println!("color {color}");
-        if soil_height == 0.0 {
-            color = constants::BEDROCK_COLOR;
-        }
+    use super::{get_wind_direction_vector, Ecosystem};
+    use crate::{constants, render::EcosystemRenderable, render_data::WaterVertex};
+
+    #[test]
+    fn test_get_light_space() {
+        let middle = constants::AREA_SIDE_LENGTH as f32 / 2.0;
+        let sun = Vector3::new(1.0, -1.0, 1.0).normalize();
+        let light_space = EcosystemRenderable::get_light_space(sun);
+        let project = |p: Vector3<f32>| light_space.transform_point(&p.into());
 
-        if let Some(grass) = &ecosystem[index].grasses {
-            // use sigmoid interpolation
-            // 1/(1+e^-(7x+4))
-            let grass_constant = 1.0;
-            let alpha = 1.0
-                / (1.0
-                    + f32::powf(
-                        std::f32::consts::E,
-                        -7.0 * (grass.coverage_density * grass_constant) + 4.0,
-                    ));
-            color = color * (1.0 - alpha) + constants::GRASS_COLOR * alpha;
+        // the middle of the terrain is in the middle of the shadow map
+        let center = project(Vector3::new(middle, middle, 0.0));
+        assert!(
+            approx_eq!(f32, center.x, 0.0, epsilon = 0.0001)
+                && approx_eq!(f32, center.y, 0.0, epsilon = 0.0001),
+            "Expected 0 and 0, actual {} and {}",
+            center.x,
+            center.y
+        );
+        // every corner of the terrain fits in the shadow map
+        for (x, y) in [(0.0, 0.0), (0.0, 2.0), (2.0, 0.0), (2.0, 2.0)] {
+            let corner = project(Vector3::new(x * middle, y * middle, 0.0));
+            assert!(corner.x.abs() <= 1.0 && corner.y.abs() <= 1.0 && corner.z.abs() <= 1.0);
         }
+        // whatever is towards the sun is closer to it, so it shadows what is behind
+        let above = project(Vector3::new(middle, middle, 0.0) + sun * 10.0);
+        assert!(above.z < center.z);
+        // a sun straight overhead still has a view
+        let overhead = EcosystemRenderable::get_light_space(Vector3::new(0.0, 0.0, 1.0));
+        assert!(overhead.iter().all(|value| value.is_finite()));
+    }
 
-        // let mut top_biomass = self[index].estimate_bush_biomass() + self[index].estimate_tree_biomass();
-        // if let Some(dead) = &self[index].dead_vegetation {
-        //     top_biomass += dead.biomass;
-        // }
+    #[test]
+    fn test_sort_water_faces() {
+        let vertex = |x: f32, alpha: f32| WaterVertex {
+            position: Vector3::new(x, 0.0, 0.0),
+            normal: Vector3::new(0.0, 0.0, 1.0),
+            color: constants::WATER_COLOR,
+            alpha,
+        };
+        let vertices = [
+            vertex(0.0, 0.5),
+            vertex(1.0, 0.5),
+            vertex(2.0, 0.0),
+            vertex(3.0, 0.0),
+        ];
+        let near = Vector3::new(0, 1, 2);
+        let far = Vector3::new(1, 2, 3);
+        let dry = Vector3::new(2, 3, 3);
 
-        color
+        // dry faces are skipped and the farthest face is drawn first
+        let eye = Vector3::new(-10.0, 0.0, 0.0);
+        let actual = EcosystemRenderable::sort_water_faces(&[near, dry, far], &vertices, eye);
+        assert_eq!(actual, vec![far, near]);
     }
 
-    pub(crate) fn get_soil_color(ecosystem: &Ecosystem, index: CellIndex) -> (f32, Vector3<f32>) {
-        let cell = &ecosystem[index];
-        let mut rock_amt = cell.get_rock_height();
-        let mut sand_amt = cell.get_sand_height();
-        let mut humus_amt = cell.get_humus_height() * 5.0; // increase humus color weighting
-        let height = rock_amt + sand_amt + humus_amt;
-        // println!("rocks_height {rock_amt}");
-        // println!("sand_amt {sand_amt}");
-        // println!("humus_height {humsus_amt}");
+    #[test]
+    fn test_build_wind_arrows() {
+        let mut eco = Ecosystem::init();
+        eco.config.wind.direction = 90.0;
+        eco.config.wind.strength = constants::MAX_DISPLAYED_WIND_STRENGTH;
+        let verts = EcosystemRenderable::build_wind_arrows(&eco);
 
-        rock_amt /= height;
-        sand_amt /= height;
-        humus_amt /= height;
+        // one arrow of three segments per block
+        let blocks = constants::AREA_SIDE_LENGTH.div_ceil(constants::WIND_ARROW_BLOCK_SIZE);
+        assert_eq!(verts.len(), blocks * blocks * 6);
 
-        (
-            height,
-            rock_amt * constants::ROCK_COLOR
-                + sand_amt * constants::SAND_COLOR
-                + humus_amt * constants::HUMUS_COLOR,
-        )
-    }
+        // wind from the configured direction at full strength spans most of the block
+        let shaft = verts[1] - verts[0];
+        let expected = constants::WIND_ARROW_BLOCK_SIZE as f32 * 0.9;
+        let actual = shaft.norm();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
+        let expected = get_wind_direction_vector(90.0);
+        let actual = shaft.xy().normalize();
+        assert!(
+            approx_eq!(f32, actual.x, expected.x, epsilon = 0.001)
+                && approx_eq!(f32, actual.y, expected.y, epsilon = 0.001),
+            "Expected {expected}, actual {actual}"
+        );
 
-    pub(crate) fn get_hypsometric_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
-        let height = ecosystem[index].get_height();
-        Self::get_hypsometric_color_helper(height, true)
+        // no arrows without wind
+        eco.config.wind.strength = 0.0;
+        assert!(EcosystemRenderable::build_wind_arrows(&eco).is_empty());
     }
 
-    pub(crate) fn get_hypsometric_color_helper(height: f32, normalize: bool) -> Vector3<f32> {
-        // todo make programmatic
-        // readjust height by scaling factor
-        // todo improve hacky way of normalizing heights between 0 and 255 (10.0 is a magic number to have padding for bedrock erosion)
-        let adj_height = if normalize {
-            (height - constants::DEFAULT_BEDROCK_HEIGHT + 10.0)
-                * (1.0 / constants::HEIGHT_SCALING_FACTOR)
-        } else {
-            height
-        };
-        if adj_height < TINT_THRESHOLD[1] {
-            let relative = adj_height - TINT_THRESHOLD[0];
-            let threshold_range = TINT_THRESHOLD[1] - TINT_THRESHOLD[0];
-            let alpha = relative / threshold_range;
-            let r = (TINTS[0][0] as f32 * (1.0 - alpha) + TINTS[1][0] as f32 * alpha) / 255.0;
-            let g = (TINTS[0][1] as f32 * (1.0 - alpha) + TINTS[1][1] as f32 * alpha) / 255.0;
-            let b = (TINTS[0][2] as f32 * (1.0 - alpha) + TINTS[1][2] as f32 * alpha) / 255.0;
-            Vector3::new(r, g, b)
-        } else if adj_height < TINT_THRESHOLD[2] {
-            let relative = adj_height - TINT_THRESHOLD[1];
-            let threshold_range = TINT_THRESHOLD[2] - TINT_THRESHOLD[1];
-            let alpha = relative / threshold_range;
-            let r = (TINTS[1][0] as f32 * (1.0 - alpha) + TINTS[2][0] as f32 * alpha) / 255.0;
-            let g = (TINTS[1][1] as f32 * (1.0 - alpha) + TINTS[2][1] as f32 * alpha) / 255.0;
-            let b = (TINTS[1][2] as f32 * (1.0 - alpha) + TINTS[2][2] as f32 * alpha) / 255.0;
-            Vector3::new(r, g, b)
-        } else {
-            let relative = adj_height - TINT_THRESHOLD[2];
-            let threshold_range = TINT_THRESHOLD[3] - TINT_THRESHOLD[2];
-            let alpha = relative / threshold_range;
-            let r = (TINTS[2][0] as f32 * (1.0 - alpha) + TINTS[3][0] as f32 * alpha) / 255.0;
-            let g = (TINTS[2][1] as f32 * (1.0 - alpha) + TINTS[3][1] as f32 * alpha) / 255.0;
-            let b = (TINTS[2][2] as f32 * (1.0 - alpha) + TINTS[3][2] as f32 * alpha) / 255.0;
-            Vector3::new(r, g, b)
-        }
-    }
-
-    // returns a color based on the average sunlight of the cell
-    fn get_sunlight_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
-        let cell = &ecosystem[index];
-        let sunlight_hours = cell.hours_of_sunlight;
-        let average: f32 = sunlight_hours.into_iter().sum::<f32>() / 12.0;
-
-        let color = average / 16.0; // assumption: max hours is 16
-        Vector3::new(color, color, color)
-    }
-
-    fn get_normalize_soil_moisture_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
-        let moisture = Events::compute_moisture(ecosystem, index, 6);
-        // if index == CellIndex::new(35, 35) {
-        //     println!("moisture {moisture}");
-        // }
-        Vector3::new((moisture - 0.5) / 2.0, 0.0, moisture / 2.0)
-    }
-
-    fn get_wind_field_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
-        let (wind_dir, wind_str) = if let Some(wind_state) = &ecosystem.wind_state {
-            get_local_wind(
-                ecosystem,
-                index,
-                wind_state.wind_direction,
-                wind_state.wind_strength,
-            )
-        } else {
-            (constants::WIND_DIRECTION, constants::WIND_STRENGTH)
-        };
-        // convert wind_dir from 0-360 to 0-255
-        let wind_dir = wind_dir / 360.0 * 255.0;
-        // convert wind_str from 0~30 to 0-255
-        let wind_str = wind_str / 30.0 * 255.0;
-        Vector3::new(wind_dir, wind_str, 0.0)
+    #[test]
+    fn test_build_cylinder() {
+        let (verts, normals, faces) = EcosystemRenderable::build_cylinder();
+        assert_eq!(verts.len(), normals.len());
+        for (vert, normal) in verts.iter().zip(normals.iter()) {
+            // unit diameter and length, with normals pointing out of the side
+            let actual = vert.xy().norm();
+            assert!(
+                approx_eq!(f32, actual, 0.5, epsilon = 0.0001),
+                "Expected 0.5, actual {actual}"
+            );
+            assert!(vert.z == 0.0 || vert.z == 1.0);
+            let actual = normal.dot(&(vert.xy() * 2.0).push(0.0));
+            assert!(
+                approx_eq!(f32, actual, 1.0, epsilon = 0.0001),
+                "Expected 1.0, actual {actual}"
+            );
+        }
+        for face in faces {
+            assert!(face.iter().all(|&i| (i as usize) < verts.len()));
+        }
     }
-}
-
-// converts (x,y) index in 2D vec into an index into a flattened 1D vec
-fn get_flat_index(x: i32, y: i32) -> i32 {
-    y * constants::AREA_SIDE_LENGTH as i32 + x
-}
-
-#[cfg(test)]
-mod tests {
-    use float_cmp::approx_eq;
-    use nalgebra::Vector3;
-
-    use super::{CellIndex, Ecosystem};
-    use crate::{
-        constants,
-        ecology::{self, Bushes, Cell, Trees},
-        render::EcosystemRenderable,
-    };
 
     #[test]
-    fn test_get_color() {
-        let mut cell = Cell::init();
-        cell.add_rocks(1.0);
-        let mut eco = Ecosystem {
-            cells: vec![vec![cell.clone()]],
-            tets: vec![],
-            bvh: None,
-            wind_state: None,
-        };
-        let actual: Vector3<f32> = EcosystemRenderable::get_color(&eco, CellIndex::new(0, 0));
-        let expected: Vector3<f32> = constants::ROCK_COLOR;
-        assert!(
-            actual == expected,
-            "Expected color {expected}, actual color {actual}"
-        );
-        cell.add_sand(1.0);
-        eco[CellIndex::new(0, 0)] = cell;
-        let actual: Vector3<f32> = EcosystemRenderable::get_color(&eco, CellIndex::new(0, 0));
-        let expected: Vector3<f32> = (constants::SAND_COLOR + constants::ROCK_COLOR) / 2.0;
-        assert!(
-            actual == expected,
-            "Expected color {expected}, actual color {actual}"
-        );
+    fn test_build_hemisphere() {
+        let (verts, normals, faces) = EcosystemRenderable::build_hemisphere();
+        assert_eq!(verts.len(), normals.len());
+        for (vert, normal) in verts.iter().zip(normals.iter()) {
+            // unit diameter and height
+            let actual = Vector3::new(vert.x * 2.0, vert.y * 2.0, vert.z).norm();
+            assert!(
+                approx_eq!(f32, actual, 1.0, epsilon = 0.0001),
+                "Expected 1.0, actual {actual}"
+            );
+            assert!(vert.z >= 0.0);
+            assert!(normal.z >= 0.0);
+        }
+        // every vertex is used by a face, and faces span both rings and segments
+        let mut used = vec![false; verts.len()];
+        for face in &faces {
+            for &i in face.iter() {
+                used[i as usize] = true;
+            }
+        }
+        assert!(used.iter().all(|&used| used));
+        assert!(faces
+            .iter()
+            .any(|face| verts[face.x as usize].z != verts[face.y as usize].z));
     }
 }
@@ -0,0 +1,37 @@
+// OpenGL backend of the viewer, drawing with the shader program of the window's context
+
+use gl::types::GLuint;
+
+use super::EcosystemRenderable;
+use crate::{
+    camera::Camera,
+    ecology::{CellIndex, Ecosystem},
+    render_backend::RenderBackend,
+    render_data::{CellVertex, ColorMode, RenderMode},
+};
+
+// draws with the shader program of the given id, in the OpenGL context the renderer was created in
+impl RenderBackend for EcosystemRenderable {
+    type Target = GLuint;
+
+    fn camera(&mut self) -> &mut Camera {
+        &mut self.m_camera
+    }
+
+    fn upload_vertex_data(&mut self, data: &[CellVertex]) {
+        EcosystemRenderable::upload_vertex_data(self, data);
+    }
+
+    fn set_color_mode(&mut self, ecosystem: &Ecosystem, color_mode: &ColorMode) {
+        self.set_contours(color_mode);
+        self.set_sun(&ecosystem.config.location, color_mode);
+    }
+
+    fn draw(&mut self, program_id: &GLuint, render_mode: &RenderMode) {
+        EcosystemRenderable::draw(self, *program_id, render_mode);
+    }
+
+    fn pick_cell(&mut self, screen_x: i32, screen_y: i32) -> Option<CellIndex> {
+        EcosystemRenderable::pick_cell(self, screen_x, screen_y)
+    }
+}
@@ -0,0 +1,409 @@
+// TERRAIN LEVEL OF DETAIL
+// the terrain mesh is split into square patches of cells, each drawn with every 2^level-th vertex
+// 1) the level of a patch grows with its distance from the camera, more slowly over rough patches
+// 2) edges between patches of different levels use the vertices of the finer one, so there are no cracks
+// 3) only patches whose level, neighbors' levels, or cells changed are rebuilt
+// the vertices stay one per cell, so only the faces in the ibo change
+
+use nalgebra::Vector3;
+
+use crate::{constants, ecology::CellIndex, render_data::get_vertex_index};
+
+// square of cells drawn at one level of detail
+#[derive(Clone, Debug, PartialEq)]
+pub struct LodPatch {
+    // first and last vertex along x and y, the last shared with the next patch
+    pub min: (usize, usize),
+    pub max: (usize, usize),
+    pub level: usize,
+    // of the heights of its vertices, in render units squared
+    pub height_variance: f32,
+    // lowest and highest vertex, in render units
+    pub height_range: (f32, f32),
+    pub faces: Vec<Vector3<i32>>,
+    // whether its cells changed since its heights were measured
+    changed: bool,
+}
+
+pub struct TerrainLod {
+    // patches along each side
+    side: usize,
+    pub patches: Vec<LodPatch>,
+}
+
+impl TerrainLod {
+    // every patch starts at full resolution
+    pub fn new(vertices: &[Vector3<f32>]) -> Self {
+        let quads = constants::AREA_SIDE_LENGTH - 1;
+        let side = quads.div_ceil(constants::LOD_PATCH_SIZE);
+        let mut patches = Vec::with_capacity(side * side);
+        for py in 0..side {
+            for px in 0..side {
+                let min = (
+                    px * constants::LOD_PATCH_SIZE,
+                    py * constants::LOD_PATCH_SIZE,
+                );
+                let max = (
+                    usize::min(min.0 + constants::LOD_PATCH_SIZE, quads),
+                    usize::min(min.1 + constants::LOD_PATCH_SIZE, quads),
+                );
+                patches.push(LodPatch {
+                    min,
+                    max,
+                    level: 0,
+                    height_variance: 0.0,
+                    height_range: (0.0, 0.0),
+                    faces: vec![],
+                    changed: true,
+                });
+            }
+        }
+        let mut lod = TerrainLod { side, patches };
+        lod.measure_changed_patches(vertices);
+        for k in 0..lod.patches.len() {
+            lod.rebuild_patch(k);
+        }
+        lod
+    }
+
+    // the heights of the patches sharing the vertex of the cell are measured again on the next update
+    pub fn mark_changed(&mut self, index: CellIndex) {
+        let patches_of = |v: usize| {
+            let p = usize::min(v / constants::LOD_PATCH_SIZE, self.side - 1);
+            // vertices on the edge of a patch belong to the one before it too
+            if v.is_multiple_of(constants::LOD_PATCH_SIZE) && p > 0 {
+                p - 1..=p
+            } else {
+                p..=p
+            }
+        };
+        for py in patches_of(index.y) {
+            for px in patches_of(index.x) {
+                self.patches[px + py * self.side].changed = true;
+            }
+        }
+    }
+
+    pub fn mark_all_changed(&mut self) {
+        for patch in &mut self.patches {
+            patch.changed = true;
+        }
+    }
+
+    // picks the level of every patch for the camera at the eye and rebuilds the faces of those affected
+    // returns whether any faces changed
+    pub fn update(&mut self, vertices: &[Vector3<f32>], eye: Vector3<f32>) -> bool {
+        // 1) measure the heights of the patches whose cells changed
+        self.measure_changed_patches(vertices);
+
+        // 2) pick levels
+        let levels: Vec<usize> = self
+            .patches
+            .iter()
+            .map(|patch| select_lod_level(get_patch_distance(patch, eye), patch.height_variance))
+            .collect();
+
+        // 3) rebuild the patches whose level changed, and their neighbors to stitch the edges between them
+        let mut rebuild = vec![false; self.patches.len()];
+        for (k, level) in levels.iter().enumerate() {
+            if *level != self.patches[k].level {
+                self.patches[k].level = *level;
+                rebuild[k] = true;
+                for neighbor in self.get_neighbors(k).into_iter().flatten() {
+                    rebuild[neighbor] = true;
+                }
+            }
+        }
+        for (k, _) in rebuild.iter().enumerate().filter(|(_, rebuild)| **rebuild) {
+            self.rebuild_patch(k);
+        }
+        rebuild.contains(&true)
+    }
+
+    // faces of every patch, for the ibo
+    pub fn get_faces(&self) -> Vec<Vector3<i32>> {
+        self.patches
+            .iter()
+            .flat_map(|patch| patch.faces.iter().copied())
+            .collect()
+    }
+
+    fn measure_changed_patches(&mut self, vertices: &[Vector3<f32>]) {
+        for patch in self.patches.iter_mut().filter(|patch| patch.changed) {
+            let mut heights = vec![];
+            for x in patch.min.0..=patch.max.0 {
+                for y in patch.min.1..=patch.max.1 {
+                    heights.push(vertices[get_vertex_index(CellIndex::new(x, y))].z);
+                }
+            }
+            let mean = heights.iter().sum::<f32>() / heights.len() as f32;
+            patch.height_variance =
+                heights.iter().map(|h| (h - mean).powi(2)).sum::<f32>() / heights.len() as f32;
+            patch.height_range = heights.iter().fold((f32::MAX, f32::MIN), |(low, high), h| {
+                (f32::min(low, *h), f32::max(high, *h))
+            });
+            patch.changed = false;
+        }
+    }
+
+    // patches below, above, left, and right of the patch, if any
+    fn get_neighbors(&self, k: usize) -> [Option<usize>; 4] {
+        let (px, py) = (k % self.side, k / self.side);
+        [
+            (py > 0).then(|| k - self.side),
+            (py + 1 < self.side).then(|| k + self.side),
+            (px > 0).then(|| k - 1),
+            (px + 1 < self.side).then(|| k + 1),
+        ]
+    }
+
+    fn rebuild_patch(&mut self, k: usize) {
+        let level = self.patches[k].level;
+        // each edge follows the finer of the patch and its neighbor
+        let edge_levels = self
+            .get_neighbors(k)
+            .map(|neighbor| neighbor.map_or(level, |n| usize::min(level, self.patches[n].level)));
+        let patch = &mut self.patches[k];
+        patch.faces = build_patch_faces(patch.min, patch.max, level, edge_levels);
+    }
+}
+
+// level of a patch at the distance from the camera, in render units,
+// coarsening each time the distance doubles past LOD_DISTANCE
+pub fn select_lod_level(distance: f32, height_variance: f32) -> usize {
+    // rough patches stay fine for longer
+    let distance = distance / (1.0 + constants::LOD_VARIANCE_WEIGHT * height_variance);
+    let mut level = 0;
+    while level < constants::MAX_LOD_LEVEL
+        && distance >= constants::LOD_DISTANCE * (1 << level) as f32
+    {
+        level += 1;
+    }
+    level
+}
+
+// distance from the eye to the closest point of the box around the patch
+fn get_patch_distance(patch: &LodPatch, eye: Vector3<f32>) -> f32 {
+    let closest = Vector3::new(
+        eye.x.clamp(patch.min.0 as f32, patch.max.0 as f32),
+        eye.y.clamp(patch.min.1 as f32, patch.max.1 as f32),
+        eye.z.clamp(patch.height_range.0, patch.height_range.1),
+    );
+    (eye - closest).norm()
+}
+
+// faces covering the patch from vertex min to max, with every 2^level-th vertex inside it
+// and every 2^edge_level-th along its edges below, above, left, and right
+pub fn build_patch_faces(
+    min: (usize, usize),
+    max: (usize, usize),
+    level: usize,
+    edge_levels: [usize; 4],
+) -> Vec<Vector3<i32>> {
+    let step = 1 << level;
+    let [below_step, above_step, left_step, right_step] =
+        edge_levels.map(|edge_level| 1 << edge_level);
+    let xs = get_stops(min.0, max.0, step);
+    let ys = get_stops(min.1, max.1, step);
+    let mut faces = vec![];
+    for x in xs.windows(2) {
+        for y in ys.windows(2) {
+            let ((x0, x1), (y0, y1)) = ((x[0], x[1]), (y[0], y[1]));
+            // vertices along each side of the quad, finer where it lies on the edge of the patch
+            let bottom = get_stops(x0, x1, if y0 == min.1 { below_step } else { step });
+            let top = get_stops(x0, x1, if y1 == max.1 { above_step } else { step });
+            let left = get_stops(y0, y1, if x0 == min.0 { left_step } else { step });
+            let right = get_stops(y0, y1, if x1 == max.0 { right_step } else { step });
+
+            if bottom.len() == 2 && top.len() == 2 && left.len() == 2 && right.len() == 2 {
+                // simple tessellation like the full grid
+                push_face(&mut faces, [(x0, y0), (x1, y0), (x0, y1)]);
+                push_face(&mut faces, [(x1, y0), (x1, y1), (x0, y1)]);
+            } else if x1 - x0 >= 2 && y1 - y0 >= 2 {
+                // fan around a vertex inside the quad
+                let center = (x0 + (x1 - x0) / 2, y0 + (y1 - y0) / 2);
+                let mut boundary = vec![];
+                boundary.extend(bottom[..bottom.len() - 1].iter().map(|x| (*x, y0)));
+                boundary.extend(right[..right.len() - 1].iter().map(|y| (x1, *y)));
+                boundary.extend(top[1..].iter().rev().map(|x| (*x, y1)));
+                boundary.extend(left[1..].iter().rev().map(|y| (x0, *y)));
+                for i in 0..boundary.len() {
+                    let next = boundary[(i + 1) % boundary.len()];
+                    push_face(&mut faces, [center, boundary[i], next]);
+                }
+            } else if x1 - x0 == 1 {
+                // one cell wide, so zip the left and right sides together
+                let left: Vec<(usize, usize)> = left.iter().map(|y| (x0, *y)).collect();
+                let right: Vec<(usize, usize)> = right.iter().map(|y| (x1, *y)).collect();
+                zip_sides(&mut faces, &left, &right, |(_, y)| y);
+            } else {
+                let bottom: Vec<(usize, usize)> = bottom.iter().map(|x| (*x, y0)).collect();
+                let top: Vec<(usize, usize)> = top.iter().map(|x| (*x, y1)).collect();
+                zip_sides(&mut faces, &bottom, &top, |(x, _)| x);
+            }
+        }
+    }
+    faces
+}
+
+// every step-th vertex from start, and the end
+fn get_stops(start: usize, end: usize, step: usize) -> Vec<usize> {
+    let mut stops: Vec<usize> = (start..end).step_by(step).collect();
+    stops.push(end);
+    stops
+}
+
+// triangulates between two parallel sides, always advancing along the side whose next vertex comes first
+fn zip_sides(
+    faces: &mut Vec<Vector3<i32>>,
+    a: &[(usize, usize)],
+    b: &[(usize, usize)],
+    along: impl Fn((usize, usize)) -> usize,
+) {
+    let (mut i, mut j) = (0, 0);
+    while i + 1 < a.len() || j + 1 < b.len() {
+        if j + 1 >= b.len() || (i + 1 < a.len() && along(a[i + 1]) <= along(b[j + 1])) {
+            push_face(faces, [a[i], b[j], a[i + 1]]);
+            i += 1;
+        } else {
+            push_face(faces, [a[i], b[j], b[j + 1]]);
+            j += 1;
+        }
+    }
+}
+
+// adds the triangle counterclockwise seen from above, like the full grid, skipping it if it has no area
+fn push_face(faces: &mut Vec<Vector3<i32>>, [a, b, c]: [(usize, usize); 3]) {
+    let cross = (b.0 as i32 - a.0 as i32) * (c.1 as i32 - a.1 as i32)
+        - (b.1 as i32 - a.1 as i32) * (c.0 as i32 - a.0 as i32);
+    let vertex = |(x, y): (usize, usize)| get_vertex_index(CellIndex::new(x, y)) as i32;
+    if cross > 0 {
+        faces.push(Vector3::new(vertex(a), vertex(b), vertex(c)));
+    } else if cross < 0 {
+        faces.push(Vector3::new(vertex(a), vertex(c), vertex(b)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use nalgebra::Vector3;
+
+    use super::{build_patch_faces, select_lod_level, TerrainLod};
+    use crate::{constants, ecology::CellIndex, render::get_vertex_index};
+
+    fn get_area(faces: &[Vector3<i32>]) -> f32 {
+        let position = |k: i32| {
+            let k = k as usize;
+            let (x, y) = (
+                k / constants::AREA_SIDE_LENGTH,
+                k % constants::AREA_SIDE_LENGTH,
+            );
+            Vector3::new(x as f32, y as f32, 0.0)
+        };
+        faces
+            .iter()
+            .map(|face| {
+                let (a, b, c) = (position(face.x), position(face.y), position(face.z));
+                // counterclockwise faces have positive area
+                (b - a).cross(&(c - a)).z / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_select_lod_level() {
+        assert_eq!(select_lod_level(0.0, 0.0), 0);
+        assert_eq!(select_lod_level(constants::LOD_DISTANCE, 0.0), 1);
+        assert_eq!(select_lod_level(2.0 * constants::LOD_DISTANCE, 0.0), 2);
+        assert_eq!(select_lod_level(f32::MAX, 0.0), constants::MAX_LOD_LEVEL);
+        // rough patches stay finer
+        assert_eq!(select_lod_level(2.0 * constants::LOD_DISTANCE, 1.0), 1);
+    }
+
+    #[test]
+    fn test_build_patch_faces() {
+        let (min, max) = ((16, 32), (32, 48));
+        let full = build_patch_faces(min, max, 0, [0; 4]);
+        assert_eq!(full.len(), 2 * 16 * 16);
+        let coarse = build_patch_faces(min, max, 2, [2; 4]);
+        assert_eq!(coarse.len(), 2 * 4 * 4);
+
+        // every patch covers its whole area without overlapping, whatever its edges follow
+        for (level, edge_levels) in [
+            (0, [0; 4]),
+            (2, [2; 4]),
+            (3, [0, 1, 2, 3]),
+            (1, [0, 1, 0, 1]),
+        ] {
+            let faces = build_patch_faces(min, max, level, edge_levels);
+            let area = get_area(&faces);
+            assert_eq!(area, 16.0 * 16.0, "{level} {edge_levels:?}");
+        }
+        // including patches cut short by the end of the grid
+        let faces = build_patch_faces((96, 96), (99, 99), 3, [0, 3, 1, 3]);
+        assert_eq!(get_area(&faces), 9.0);
+        let faces = build_patch_faces((96, 0), (99, 16), 2, [2, 0, 0, 2]);
+        assert_eq!(get_area(&faces), 48.0);
+    }
+
+    #[test]
+    fn test_update_terrain_lod() {
+        let mut vertices = vec![Vector3::zeros(); constants::NUM_CELLS];
+        for x in 0..constants::AREA_SIDE_LENGTH {
+            for y in 0..constants::AREA_SIDE_LENGTH {
+                vertices[get_vertex_index(CellIndex::new(x, y))] =
+                    Vector3::new(x as f32, y as f32, 0.0);
+            }
+        }
+        let mut lod = TerrainLod::new(&vertices);
+        let quads = (constants::AREA_SIDE_LENGTH - 1).pow(2);
+        assert_eq!(lod.get_faces().len(), 2 * quads);
+
+        // far from the camera the terrain coarsens, close to it it stays fine
+        let eye = Vector3::new(-100.0, -100.0, 5.0);
+        assert!(lod.update(&vertices, eye));
+        assert_eq!(lod.patches[0].level, 0);
+        assert_eq!(lod.patches.last().unwrap().level, 1);
+        let faces = lod.get_faces();
+        assert!(faces.len() < 2 * quads);
+        assert_eq!(get_area(&faces), quads as f32);
+        // nothing to rebuild if nothing moved
+        assert!(!lod.update(&vertices, eye));
+
+        // the vertices along the edge between patches of different levels are the same on both sides
+        let used = |faces: &[Vector3<i32>]| -> HashSet<i32> {
+            faces
+                .iter()
+                .flat_map(|face| [face.x, face.y, face.z])
+                .collect()
+        };
+        for (k, patch) in lod.patches.iter().enumerate() {
+            if patch.max.0 == constants::AREA_SIDE_LENGTH - 1 {
+                continue;
+            }
+            let right = &lod.patches[k + 1];
+            let edge: Vec<i32> = (patch.min.1..=patch.max.1)
+                .map(|y| get_vertex_index(CellIndex::new(patch.max.0, y)) as i32)
+                .collect();
+            let (ours, theirs) = (used(&patch.faces), used(&right.faces));
+            for vertex in edge {
+                assert_eq!(
+                    ours.contains(&vertex),
+                    theirs.contains(&vertex),
+                    "{k} {vertex}"
+                );
+            }
+        }
+
+        // rough cells keep their patch fine once their change is measured
+        let index = CellIndex::new(97, 97);
+        let far = lod.patches.last().unwrap().level;
+        vertices[get_vertex_index(index)].z = 100.0;
+        assert!(!lod.update(&vertices, eye));
+        lod.mark_changed(index);
+        assert!(lod.update(&vertices, eye));
+        assert!(lod.patches.last().unwrap().level < far);
+    }
+}
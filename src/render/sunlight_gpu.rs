@@ -0,0 +1,242 @@
+// GPU SUNLIGHT
+// ray traces the hours of sunlight of all cells at once in a compute shader
+// 1) the heights of the cells and the directions towards the sun are uploaded to the gpu
+// 2) each invocation walks the rays of one cell over the tets below their paths,
+//    testing the same two triangles per tet as the bvh on the cpu
+// 3) the hours of sunlight are read back in the same order as `recompute_sunlight` expects
+// compute shaders need OpenGL 4.3, so the cpu is used instead on older contexts (e.g. on macOS)
+
+use std::ffi::CString;
+
+use gl::types::{GLint, GLsizeiptr, GLuint, GLvoid};
+
+use crate::{
+    config::Location,
+    constants,
+    ecology::{illumination::get_sun_direction, CellIndex, Ecosystem},
+    render_gl::{Program, Shader},
+};
+
+// must match local_size_x and local_size_y of the shader
+const WORK_GROUP_SIZE: usize = 8;
+
+pub struct GpuSunlight {
+    program: Program,
+    heights_buffer: GLuint,
+    sun_directions_buffer: GLuint,
+    hours_buffer: GLuint,
+}
+
+impl GpuSunlight {
+    // requires a current OpenGL context
+    // returns why the gpu can not be used if the context does not support compute shaders
+    pub fn init() -> Result<Self, String> {
+        let mut major: GLint = 0;
+        let mut minor: GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        }
+        if (major, minor) < (4, 3) || !gl::DispatchCompute::is_loaded() {
+            return Err(format!(
+                "Compute shaders need OpenGL 4.3, but the context is {major}.{minor}"
+            ));
+        }
+
+        let shader = Shader::from_comp_source(
+            &CString::new(include_str!("../../resources/shaders/sunlight.comp")).unwrap(),
+        )?;
+        let program = Program::from_shaders(&[shader])?;
+
+        let mut buffers = [0; 3];
+        unsafe {
+            gl::GenBuffers(buffers.len() as i32, buffers.as_mut_ptr());
+        }
+        Ok(GpuSunlight {
+            program,
+            heights_buffer: buffers[0],
+            sun_directions_buffer: buffers[1],
+            hours_buffer: buffers[2],
+        })
+    }
+
+    // returns the hours of sunlight of every cell with a tet of its own, ordered by rows
+    pub fn compute_hours_of_sunlight(&self, ecosystem: &Ecosystem) -> Vec<[f32; 12]> {
+        let side = constants::AREA_SIDE_LENGTH;
+        let num_tets = (side - 1) * (side - 1);
+
+        let mut heights = Vec::with_capacity(side * side);
+        for y in 0..side {
+            for x in 0..side {
+                heights.push(ecosystem[CellIndex::new(x, y)].get_height());
+            }
+        }
+        let max_height = heights.iter().copied().fold(f32::MIN, f32::max);
+        let sun_directions = get_sun_directions(&ecosystem.config.location);
+        let mut hours = vec![0.0_f32; num_tets * 12];
+
+        self.program.set_used();
+        unsafe {
+            upload_buffer(self.heights_buffer, 0, &heights);
+            upload_buffer(self.sun_directions_buffer, 1, &sun_directions);
+            upload_buffer(self.hours_buffer, 2, &hours);
+
+            let program_id = self.program.id();
+            let c_str = CString::new("side").unwrap();
+            let location = gl::GetUniformLocation(program_id, c_str.as_ptr());
+            gl::Uniform1i(location, side as i32);
+            let c_str = CString::new("maxHeight").unwrap();
+            let location = gl::GetUniformLocation(program_id, c_str.as_ptr());
+            gl::Uniform1f(location, max_height);
+            let c_str = CString::new("percentSunnyDays").unwrap();
+            let location = gl::GetUniformLocation(program_id, c_str.as_ptr());
+            gl::Uniform1f(location, ecosystem.config.climate.percent_sunny_days);
+
+            let num_groups = (side - 1).div_ceil(WORK_GROUP_SIZE) as u32;
+            gl::DispatchCompute(num_groups, num_groups, 1);
+            gl::MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.hours_buffer);
+            gl::GetBufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                0,
+                std::mem::size_of_val(hours.as_slice()) as GLsizeiptr,
+                hours.as_mut_ptr() as *mut GLvoid,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+
+        hours
+            .chunks_exact(12)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect()
+    }
+}
+
+impl Drop for GpuSunlight {
+    fn drop(&mut self) {
+        let buffers = [
+            self.heights_buffer,
+            self.sun_directions_buffer,
+            self.hours_buffer,
+        ];
+        unsafe {
+            gl::DeleteBuffers(buffers.len() as i32, buffers.as_ptr());
+        }
+    }
+}
+
+// returns the direction towards the sun for every hour of every month, at month * 24 + hour
+// the fourth component is 1 when the sun is above the horizon and 0 otherwise
+pub fn get_sun_directions(location: &Location) -> Vec<[f32; 4]> {
+    let mut directions = Vec::with_capacity(12 * 24);
+    for month in 0..12 {
+        for hour in 0..24 {
+            directions.push(match get_sun_direction(location, month, hour) {
+                Some(dir) => [dir.x, dir.y, dir.z, 1.0],
+                None => [0.0; 4],
+            });
+        }
+    }
+    directions
+}
+
+// replaces the data of the buffer and binds it to the given shader storage binding
+unsafe fn upload_buffer<T>(buffer: GLuint, binding: GLuint, data: &[T]) {
+    gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, buffer);
+    gl::BufferData(
+        gl::SHADER_STORAGE_BUFFER,
+        std::mem::size_of_val(data) as GLsizeiptr,
+        data.as_ptr() as *const GLvoid,
+        gl::DYNAMIC_DRAW,
+    );
+    gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, buffer);
+    gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_sun_directions, GpuSunlight};
+    use crate::{
+        config::Location,
+        constants,
+        ecology::{illumination::get_sun_direction, CellIndex, Ecosystem},
+    };
+
+    #[test]
+    fn test_get_sun_directions() {
+        let location = Location::default();
+        let directions = get_sun_directions(&location);
+        assert_eq!(directions.len(), 12 * 24);
+
+        // the sun is below the horizon at midnight and above it at noon
+        assert_eq!(directions[6 * 24], [0.0; 4]);
+        let noon = directions[6 * 24 + 12];
+        let expected = get_sun_direction(&location, 6, 12).unwrap();
+        assert_eq!(noon, [expected.x, expected.y, expected.z, 1.0]);
+    }
+
+    // hidden window with an OpenGL 4.3 context, if there is a display and a gpu for it
+    fn init_compute_context() -> Option<(sdl2::Sdl, sdl2::video::Window, sdl2::video::GLContext)> {
+        let sdl = sdl2::init().ok()?;
+        let video_subsystem = sdl.video().ok()?;
+        let gl_attr = video_subsystem.gl_attr();
+        gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
+        gl_attr.set_context_version(4, 3);
+        let window = video_subsystem
+            .window("Sunlight", 1, 1)
+            .opengl()
+            .hidden()
+            .build()
+            .ok()?;
+        let gl_context = window.gl_create_context().ok()?;
+        gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as *const std::os::raw::c_void);
+        Some((sdl, window, gl_context))
+    }
+
+    // skipped without a display and a gpu with OpenGL 4.3, unless REQUIRE_GPU is set
+    #[test]
+    fn test_compute_hours_of_sunlight_matches_cpu() {
+        let required = std::env::var_os("REQUIRE_GPU").is_some();
+        let Some(_context) = init_compute_context() else {
+            assert!(!required, "Expected an OpenGL 4.3 context");
+            println!("no OpenGL 4.3 context, skipping");
+            return;
+        };
+        let sunlight_gpu = match GpuSunlight::init() {
+            Ok(sunlight_gpu) => sunlight_gpu,
+            Err(error) => {
+                assert!(!required, "{error}");
+                println!("{error}, skipping");
+                return;
+            }
+        };
+
+        // hills casting shadows in every direction
+        let mut ecosystem = Ecosystem::init();
+        for x in 0..constants::AREA_SIDE_LENGTH {
+            for y in 0..constants::AREA_SIDE_LENGTH {
+                let (fx, fy) = (x as f32 / 10.0, y as f32 / 10.0);
+                let height = 30.0 * (fx.sin() * fy.cos()).abs();
+                ecosystem[CellIndex::new(x, y)].add_bedrock(height);
+            }
+        }
+        ecosystem.update_tets();
+        ecosystem.recompute_sunlight();
+        let cpu: Vec<[f32; 12]> = (0..constants::AREA_SIDE_LENGTH - 1)
+            .flat_map(|y| (0..constants::AREA_SIDE_LENGTH - 1).map(move |x| (x, y)))
+            .map(|(x, y)| ecosystem[CellIndex::new(x, y)].hours_of_sunlight)
+            .collect();
+
+        let gpu = sunlight_gpu.compute_hours_of_sunlight(&ecosystem);
+        assert_eq!(gpu.len(), cpu.len());
+
+        // rays grazing the edges of tets can go either way due to rounding
+        let mismatches = cpu.iter().zip(&gpu).filter(|(cpu, gpu)| cpu != gpu).count();
+        assert!(
+            mismatches * 100 < cpu.len(),
+            "Expected under 1% of cells to differ, actual {mismatches} of {}",
+            cpu.len()
+        );
+    }
+}
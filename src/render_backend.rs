@@ -0,0 +1,111 @@
+// RENDER BACKEND
+// what the viewer needs from whatever draws the ecosystem, so OpenGL is one backend among others
+// the vertex data of the cells is built away from any backend by `render_data::build_vertex_data`
+// and `build_changed_vertex_data`, and the faces by `build_terrain_faces` and the level of detail,
+// so a backend only has to
+// 1) upload the vertex data of the cells changed since the last frame
+// 2) set up the contours and sun of the color mode
+// 3) draw the frame with whatever it draws with, e.g. a shader program or a surface
+
+use crate::{
+    camera::Camera,
+    ecology::{CellIndex, Ecosystem},
+    render_data::{CellVertex, ColorMode, RenderMode},
+};
+
+pub trait RenderBackend {
+    // what a frame is drawn with
+    type Target;
+
+    fn camera(&mut self) -> &mut Camera;
+
+    fn upload_vertex_data(&mut self, data: &[CellVertex]);
+
+    fn set_color_mode(&mut self, ecosystem: &Ecosystem, color_mode: &ColorMode);
+
+    fn draw(&mut self, target: &Self::Target, render_mode: &RenderMode);
+
+    // returns the cell under the given pixel of the window, if any
+    fn pick_cell(&mut self, screen_x: i32, screen_y: i32) -> Option<CellIndex>;
+
+    // draws a frame of the ecosystem in the color mode
+    fn draw_frame(
+        &mut self,
+        target: &Self::Target,
+        ecosystem: &Ecosystem,
+        color_mode: &ColorMode,
+        render_mode: &RenderMode,
+    ) {
+        self.set_color_mode(ecosystem, color_mode);
+        self.draw(target, render_mode);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RenderBackend;
+    use crate::{
+        camera::Camera,
+        ecology::{CellIndex, Ecosystem},
+        render_data::{self, CellVertex, ColorMode, RenderMode},
+    };
+
+    // backend without a window that keeps what it was given
+    struct RecordingBackend {
+        camera: Camera,
+        uploaded: Vec<CellVertex>,
+        frames: Vec<(ColorMode, RenderMode)>,
+        color_mode: Option<ColorMode>,
+    }
+
+    impl RenderBackend for RecordingBackend {
+        type Target = ();
+
+        fn camera(&mut self) -> &mut Camera {
+            &mut self.camera
+        }
+
+        fn upload_vertex_data(&mut self, data: &[CellVertex]) {
+            self.uploaded.extend_from_slice(data);
+        }
+
+        fn set_color_mode(&mut self, _ecosystem: &Ecosystem, color_mode: &ColorMode) {
+            self.color_mode = Some(*color_mode);
+        }
+
+        fn draw(&mut self, _target: &(), render_mode: &RenderMode) {
+            self.frames.push((self.color_mode.unwrap(), *render_mode));
+        }
+
+        fn pick_cell(&mut self, _screen_x: i32, _screen_y: i32) -> Option<CellIndex> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_render_backend() {
+        // the vertex data is built without a backend and drawn by any of them
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(3, 4);
+        ecosystem.changed_cells.clear();
+        ecosystem[index].add_sand(1.0);
+        ecosystem.changed_cells.insert(index);
+        let color_mode = ColorMode::Standard;
+        let tree_positions = render_data::build_tree_positions(ecosystem.seed);
+        let data =
+            render_data::build_changed_vertex_data(&tree_positions, &mut ecosystem, &color_mode);
+
+        let mut backend = RecordingBackend {
+            camera: Camera::init(),
+            uploaded: vec![],
+            frames: vec![],
+            color_mode: None,
+        };
+        backend.upload_vertex_data(&data);
+        backend.draw_frame(&(), &ecosystem, &color_mode, &RenderMode::Solid);
+
+        assert_eq!(backend.uploaded.len(), 1);
+        assert_eq!(backend.uploaded[0].index, index);
+        assert_eq!(backend.frames, vec![(color_mode, RenderMode::Solid)]);
+    }
+}
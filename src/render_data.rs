@@ -0,0 +1,1139 @@
+// RENDER DATA
+// how the cells of the ecosystem look, built without any graphics api
+// so the worker, the exports, and every render backend share it in headless runs too
+// 1) the color of each cell in each color mode
+// 2) the vertex, plants, and water surface of each cell, uploaded by the renderer as they are
+
+use nalgebra::{Vector2, Vector3};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    camera::Camera,
+    constants,
+    ecology::{Bushes, Cell, CellIndex, Ecosystem, Grasses, Trees},
+    events::{fire_danger, vegetation::Individualized, wind::sample_local_wind, Events},
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorMode {
+    Standard,
+    HypsometricTint,
+    // hours of sunlight in the month shown, 0 is January
+    Sunlight(usize),
+    // month shown, 0 is January
+    SoilMoisture(usize),
+    WindField,
+    OnlyBedrock,
+    Fire,
+    // keetch-byram drought index combined with soil moisture, which drives ignitions
+    FireDanger,
+    VegetationDensity,
+    // month shown, 0 is January
+    Temperature(usize),
+    // how much warmer or colder aspect and shading make each cell in the month shown
+    Microclimate(usize),
+    // gray shading of the slopes by a single light, as on topographic maps
+    Hillshade(Hillshade),
+    // blend of the colors of the regions the sand and humus of each cell came from, when tracked
+    Provenance,
+    // month and hour of the sun drawn in the sky and casting shadows,
+    // over the ray traced hours of sunlight of the month to check them against
+    Shadows(usize, usize),
+    // paths the water ran along during the last time step, brighter where more of it flowed
+    Runoff,
+    // biome each cell is classified into
+    Biome,
+}
+
+// light and contour lines of the hillshade color mode
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hillshade {
+    // degrees clockwise from north the light comes from
+    pub azimuth: f32,
+    // degrees above the horizon
+    pub elevation: f32,
+    // m between contour lines, none are drawn if 0
+    pub contour_interval: f32,
+}
+
+impl Default for Hillshade {
+    fn default() -> Self {
+        Hillshade {
+            azimuth: constants::DEFAULT_HILLSHADE_AZIMUTH,
+            elevation: constants::DEFAULT_HILLSHADE_ELEVATION,
+            contour_interval: 0.0,
+        }
+    }
+}
+
+impl ColorMode {
+    // month the color mode shows, if it changes with the seasons
+    pub fn get_month(&self) -> Option<usize> {
+        match self {
+            ColorMode::Sunlight(month)
+            | ColorMode::SoilMoisture(month)
+            | ColorMode::Temperature(month)
+            | ColorMode::Microclimate(month)
+            | ColorMode::Shadows(month, _) => Some(*month),
+            _ => None,
+        }
+    }
+
+    // the same color mode showing another month, unchanged if it does not change with the seasons
+    pub fn with_month(self, month: usize) -> Self {
+        let month = month % 12;
+        match self {
+            ColorMode::Sunlight(_) => ColorMode::Sunlight(month),
+            ColorMode::SoilMoisture(_) => ColorMode::SoilMoisture(month),
+            ColorMode::Temperature(_) => ColorMode::Temperature(month),
+            ColorMode::Microclimate(_) => ColorMode::Microclimate(month),
+            ColorMode::Shadows(_, hour) => ColorMode::Shadows(month, hour),
+            _ => self,
+        }
+    }
+}
+
+impl std::str::FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "standard" => Ok(ColorMode::Standard),
+            "hypsometric" => Ok(ColorMode::HypsometricTint),
+            "sunlight" => Ok(ColorMode::Sunlight(0)),
+            "soil-moisture" => Ok(ColorMode::SoilMoisture(0)),
+            "wind" => Ok(ColorMode::WindField),
+            "bedrock" => Ok(ColorMode::OnlyBedrock),
+            "fire" => Ok(ColorMode::Fire),
+            "fire-danger" => Ok(ColorMode::FireDanger),
+            "vegetation" => Ok(ColorMode::VegetationDensity),
+            "temperature" => Ok(ColorMode::Temperature(0)),
+            "microclimate" => Ok(ColorMode::Microclimate(0)),
+            "hillshade" => Ok(ColorMode::Hillshade(Hillshade::default())),
+            "shadows" => Ok(ColorMode::Shadows(0, 12)),
+            "provenance" => Ok(ColorMode::Provenance),
+            "runoff" => Ok(ColorMode::Runoff),
+            "biome" => Ok(ColorMode::Biome),
+            _ => Err(format!("Unknown color mode {name}")),
+        }
+    }
+}
+
+// per instance attributes of a plant, matching locations 3 to 5 of the vertex shader
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlantInstance {
+    // center of the base of the plant
+    pub position: Vector3<f32>,
+    // diameter and length
+    pub size: Vector2<f32>,
+    pub color: Vector3<f32>,
+}
+
+// everything uploaded for the terrain vertex and plants of a cell,
+// so it can be built away from the thread that owns the OpenGL context
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CellVertex {
+    pub index: CellIndex,
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub grass_coverage: f32,
+    // standing tree, lying dead vegetation, and bushes
+    pub plants: [PlantInstance; 3],
+    pub water: WaterVertex,
+}
+
+// vertex of the translucent water surface, matching locations 0 to 2 and 7 of the vertex shader
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WaterVertex {
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub color: Vector3<f32>,
+    // the surface is not drawn over cells that are dry
+    pub alpha: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RenderMode {
+    Solid,
+    // grid lines drawn over the solid terrain
+    WireframeOverlay,
+    Wireframe,
+}
+
+impl RenderMode {
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Solid => RenderMode::WireframeOverlay,
+            RenderMode::WireframeOverlay => RenderMode::Wireframe,
+            RenderMode::Wireframe => RenderMode::Solid,
+        }
+    }
+}
+
+// height between the contour lines drawn over the terrain in render units, 0 for none
+pub fn get_contour_interval(color_mode: &ColorMode) -> f32 {
+    match color_mode {
+        ColorMode::Hillshade(hillshade) => {
+            hillshade.contour_interval * (1.0 - constants::HEIGHT_SCALING_FACTOR)
+                / constants::HEIGHT_RENDER_SCALE
+        }
+        _ => 0.0,
+    }
+}
+
+// one vertex per cell, ordered to match the faces of build_terrain_faces
+pub fn build_terrain_vertices(ecosystem: &Ecosystem) -> Vec<Vector3<f32>> {
+    let mut verts = Vec::with_capacity(constants::NUM_CELLS);
+    for i in 0..constants::AREA_SIDE_LENGTH {
+        for j in 0..constants::AREA_SIDE_LENGTH {
+            let height = ecosystem[CellIndex::new(i, j)].get_height()
+                * (1.0 - constants::HEIGHT_SCALING_FACTOR)
+                / constants::HEIGHT_RENDER_SCALE;
+            verts.push(Vector3::new(i as f32, j as f32, height));
+        }
+    }
+    verts
+}
+
+// simple tessellation of square grid, which the water surface is drawn with
+pub fn build_terrain_faces() -> Vec<Vector3<i32>> {
+    let mut faces = vec![];
+    for i in 0i32..constants::AREA_SIDE_LENGTH as i32 - 1 {
+        for j in 0i32..constants::AREA_SIDE_LENGTH as i32 - 1 {
+            // build two triangles
+            let index = get_flat_index(i, j);
+            let right = get_flat_index(i + 1, j);
+            let bottom = get_flat_index(i, j + 1);
+            let bottom_right = get_flat_index(i + 1, j + 1);
+            faces.push(Vector3::new(index, bottom, right));
+            faces.push(Vector3::new(bottom, bottom_right, right));
+        }
+    }
+    faces
+}
+
+// outline of each grid square, as pairs of terrain vertices
+pub fn build_grid_lines() -> Vec<Vector2<i32>> {
+    let mut lines = vec![];
+    for i in 0i32..constants::AREA_SIDE_LENGTH as i32 - 1 {
+        for j in 0i32..constants::AREA_SIDE_LENGTH as i32 - 1 {
+            let index = get_flat_index(i, j);
+            let right = get_flat_index(i + 1, j);
+            let bottom = get_flat_index(i, j + 1);
+            let bottom_right = get_flat_index(i + 1, j + 1);
+            lines.push(Vector2::new(index, right));
+            lines.push(Vector2::new(index, bottom));
+            lines.push(Vector2::new(right, bottom_right));
+            lines.push(Vector2::new(bottom, bottom_right));
+        }
+    }
+    lines
+}
+
+// camera looking down at the middle of the terrain from above one of its sides
+pub fn build_camera(aspect: f32) -> Camera {
+    let near_plane = 0.001;
+    let far_plane = 10000.0;
+    let middle = constants::AREA_SIDE_LENGTH as f32 / 2.0;
+    let center = Vector3::new(middle, middle, constants::DEFAULT_BEDROCK_HEIGHT);
+    let eye: Vector3<f32> = center
+        + Vector3::new(
+            0.0,
+            1.0 * constants::AREA_SIDE_LENGTH as f32,
+            2.0 * constants::AREA_SIDE_LENGTH as f32,
+        );
+    let mut camera = Camera::init();
+    camera.look_at(eye, center);
+    camera.focus(center);
+    camera.set_perspective(120.0, aspect, near_plane, far_plane);
+    camera
+}
+
+// offset of the trees from the corner of each cell
+// jitter is seeded separately so rendering does not change the simulation's rng
+pub fn build_tree_positions(seed: u64) -> Vec<Vector2<f32>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..constants::NUM_CELLS)
+        .map(|_| {
+            let x_rand: f32 = rng.gen::<f32>() * 0.7 - 0.5;
+            let y_rand: f32 = rng.gen::<f32>() * 0.7 - 0.5;
+            Vector2::new(x_rand, y_rand)
+        })
+        .collect()
+}
+
+// standing tree, lying dead vegetation, and bushes of a cell, which have no size if the cell has none
+pub fn build_plant_instances(
+    tree_positions: &[Vector2<f32>],
+    ecosystem: &Ecosystem,
+    index: CellIndex,
+) -> [PlantInstance; 3] {
+    let cell = &ecosystem[index];
+    let ground = cell.get_height() * (1.0 - constants::HEIGHT_SCALING_FACTOR)
+        / constants::HEIGHT_RENDER_SCALE;
+    let tree_pos = tree_positions[index.get_flat_index()];
+    let center = Vector3::new(
+        tree_pos.x + index.x as f32,
+        tree_pos.y + index.y as f32,
+        ground,
+    );
+    let tree_height = cell.get_height_of_trees() / 10.0;
+    let dead_length = cell.get_dead_vegetation_biomass() / 500.0;
+
+    // one mound covering the crowns of all bushes in the cell
+    let bush_size = match &cell.bushes {
+        Some(bushes) if bushes.get_number_of_plants() > 0 => {
+            let number = bushes.get_number_of_plants() as f32;
+            let crown_area =
+                Bushes::estimate_crown_area_from_biomass(bushes.estimate_biomass() / number);
+            let cell_area = constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH;
+            let area = f32::min(crown_area * number, cell_area);
+            let diameter = 2.0 * f32::sqrt(area / std::f32::consts::PI);
+            Vector2::new(
+                diameter / constants::CELL_SIDE_LENGTH,
+                bushes.get_plant_height_sum() / number / 10.0,
+            )
+        }
+        _ => Vector2::zeros(),
+    };
+    [
+        PlantInstance {
+            position: center,
+            size: Vector2::new(
+                Trees::estimate_diameter_from_height(tree_height),
+                tree_height,
+            ),
+            color: constants::TREES_COLOR,
+        },
+        PlantInstance {
+            position: center,
+            size: Vector2::new(
+                Trees::estimate_diameter_from_height(dead_length),
+                dead_length,
+            ),
+            color: constants::DEAD_COLOR,
+        },
+        PlantInstance {
+            position: Vector3::new(index.x as f32, index.y as f32, ground),
+            size: bush_size,
+            color: constants::BUSHES_COLOR,
+        },
+    ]
+}
+
+// vertex data of every cell, in the order of the vbo
+pub fn build_vertex_data(
+    tree_positions: &[Vector2<f32>],
+    ecosystem: &Ecosystem,
+    color_mode: &ColorMode,
+) -> Vec<CellVertex> {
+    let height_range = get_height_range(ecosystem);
+    let mut data = Vec::with_capacity(constants::NUM_CELLS);
+    for i in 0..constants::AREA_SIDE_LENGTH {
+        for j in 0..constants::AREA_SIDE_LENGTH {
+            let index = CellIndex::new(i, j);
+            data.push(build_cell_vertex_data(
+                tree_positions,
+                ecosystem,
+                index,
+                color_mode,
+                height_range,
+            ));
+        }
+    }
+    data
+}
+
+// vertex data of the cells changed since the last update, clearing them,
+// or of every cell if the color mode depends on values updated over the whole grid
+pub fn build_changed_vertex_data(
+    tree_positions: &[Vector2<f32>],
+    ecosystem: &mut Ecosystem,
+    color_mode: &ColorMode,
+) -> Vec<CellVertex> {
+    let changed = std::mem::take(&mut ecosystem.changed_cells);
+    if !is_updated_per_cell(color_mode) {
+        return build_vertex_data(tree_positions, ecosystem, color_mode);
+    }
+    let height_range = get_height_range(ecosystem);
+    changed
+        .into_iter()
+        .map(|index| {
+            build_cell_vertex_data(tree_positions, ecosystem, index, color_mode, height_range)
+        })
+        .collect()
+}
+
+fn build_cell_vertex_data(
+    tree_positions: &[Vector2<f32>],
+    ecosystem: &Ecosystem,
+    index: CellIndex,
+    color_mode: &ColorMode,
+    height_range: (f32, f32),
+) -> CellVertex {
+    let (position, normal, color, grass_coverage) =
+        build_cell_vertex(ecosystem, index, color_mode, height_range);
+    CellVertex {
+        index,
+        position,
+        normal,
+        color,
+        grass_coverage,
+        plants: build_plant_instances(tree_positions, ecosystem, index),
+        water: build_water_vertex(ecosystem, index, color_mode),
+    }
+}
+
+// surface of the standing water of a cell, or a thin film over soil close to saturation
+// only drawn in the standard color mode, so it does not hide the other modes
+pub fn build_water_vertex(
+    ecosystem: &Ecosystem,
+    index: CellIndex,
+    color_mode: &ColorMode,
+) -> WaterVertex {
+    let cell = &ecosystem[index];
+    let depth = cell.get_water_height();
+    let alpha = if *color_mode != ColorMode::Standard {
+        0.0
+    } else if depth > 0.0 {
+        let opacity = f32::min(depth / constants::WATER_OPAQUE_DEPTH, 1.0);
+        constants::MIN_WATER_ALPHA
+            + (constants::MAX_WATER_ALPHA - constants::MIN_WATER_ALPHA) * opacity
+    } else {
+        let (month, _) = ecosystem.get_months_of_time_step()[0];
+        let moisture = Events::compute_moisture(ecosystem, index, month);
+        let wetness = (moisture - constants::WET_SOIL_MOISTURE)
+            / (constants::SATURATED_SOIL_MOISTURE - constants::WET_SOIL_MOISTURE);
+        constants::WET_SOIL_ALPHA * wetness.clamp(0.0, 1.0)
+    };
+    // raised a little so films over the ground do not flicker against it
+    let height = (cell.get_height() + depth) * (1.0 - constants::HEIGHT_SCALING_FACTOR)
+        / constants::HEIGHT_RENDER_SCALE
+        + constants::WATER_SURFACE_OFFSET;
+    WaterVertex {
+        position: Vector3::new(index.x as f32, index.y as f32, height),
+        normal: Vector3::new(0.0, 0.0, 1.0),
+        color: constants::WATER_COLOR,
+        alpha,
+    }
+}
+
+// whether the colors of the mode only change in cells touched by events
+// sunlight, soil moisture, wind, and burn scars are updated over the whole grid every time step,
+// and hypsometric tints span the heights of the whole terrain
+pub fn is_updated_per_cell(color_mode: &ColorMode) -> bool {
+    matches!(
+        color_mode,
+        ColorMode::Standard
+            | ColorMode::OnlyBedrock
+            | ColorMode::VegetationDensity
+            | ColorMode::Hillshade(_)
+    )
+}
+
+// position, normal, color, and grass coverage of the terrain vertex of a cell
+fn build_cell_vertex(
+    ecosystem: &Ecosystem,
+    index: CellIndex,
+    color_mode: &ColorMode,
+    height_range: (f32, f32),
+) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>, f32) {
+    let cell = &ecosystem[index];
+    // make uniform cube cells
+    let height = if *color_mode == ColorMode::OnlyBedrock {
+        cell.get_bedrock_height() * (1.0 - constants::HEIGHT_SCALING_FACTOR)
+            / constants::HEIGHT_RENDER_SCALE
+    } else {
+        cell.get_height() * (1.0 - constants::HEIGHT_SCALING_FACTOR)
+            / constants::HEIGHT_RENDER_SCALE
+    };
+    let vert = Vector3::new(index.x as f32, index.y as f32, height);
+    let color = get_mode_color(ecosystem, index, color_mode, height_range);
+    let grass = get_grass_coverage(ecosystem, index, color_mode);
+    (vert, ecosystem.get_normal(index), color, grass)
+}
+
+// density of the grass blades the fragment shader draws over the ground
+// only drawn in the standard color mode, and hidden under snow and standing water
+pub fn get_grass_coverage(ecosystem: &Ecosystem, index: CellIndex, color_mode: &ColorMode) -> f32 {
+    let cell = &ecosystem[index];
+    if *color_mode != ColorMode::Standard
+        || cell.get_snow_height() > 0.0
+        || cell.get_water_height() > 0.0
+    {
+        return 0.0;
+    }
+    cell.grasses.as_ref().map_or(0.0, |g| g.coverage_density)
+}
+
+// color of the cell in the color mode, as drawn on the terrain
+// height_range is the lowest and highest height of the terrain, from get_height_range
+pub fn get_mode_color(
+    ecosystem: &Ecosystem,
+    index: CellIndex,
+    color_mode: &ColorMode,
+    height_range: (f32, f32),
+) -> Vector3<f32> {
+    match color_mode {
+        ColorMode::Standard => get_color(ecosystem, index),
+        ColorMode::HypsometricTint => get_hypsometric_color(ecosystem, index, height_range),
+        ColorMode::Sunlight(month) => get_sunlight_color(ecosystem, index, *month),
+        ColorMode::SoilMoisture(month) => {
+            get_normalize_soil_moisture_color(ecosystem, index, *month)
+        }
+        ColorMode::WindField => get_wind_field_color(ecosystem, index),
+        ColorMode::OnlyBedrock => constants::BEDROCK_COLOR,
+        ColorMode::Fire => get_fire_color(ecosystem, index),
+        ColorMode::FireDanger => get_fire_danger_color(ecosystem, index),
+        ColorMode::VegetationDensity => get_vegetation_density_color(ecosystem, index),
+        ColorMode::Temperature(month) => get_temperature_color(ecosystem, index, *month),
+        ColorMode::Microclimate(month) => get_microclimate_color(ecosystem, index, *month),
+        ColorMode::Hillshade(hillshade) => get_hillshade_color(ecosystem, index, hillshade),
+        ColorMode::Shadows(month, _) => get_sunlight_color(ecosystem, index, *month),
+        ColorMode::Provenance => get_provenance_color(ecosystem, index),
+        ColorMode::Runoff => get_runoff_color(ecosystem, index),
+        ColorMode::Biome => ecosystem.classify_biome(index).get_color(),
+    }
+}
+
+pub fn get_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
+    // rock (gray), sand (pale yellow), humus (light brown), trees (dark green), bushes (medium green), grass (light green), dead (dark brown)
+    let mut color: Vector3<f32>;
+    let soil_height: f32;
+
+    (soil_height, color) = get_soil_color(ecosystem, index);
+    // println!("color {color}");
+    if soil_height == 0.0 {
+        color = constants::BEDROCK_COLOR;
+    }
+
+    if let Some(grass) = &ecosystem[index].grasses {
+        // use sigmoid interpolation
+        // 1/(1+e^-(7x+4))
+        let grass_constant = 1.0;
+        let alpha = 1.0
+            / (1.0
+                + f32::powf(
+                    std::f32::consts::E,
+                    -7.0 * (grass.coverage_density * grass_constant) + 4.0,
+                ));
+        color = color * (1.0 - alpha) + get_grass_color(grass) * alpha;
+    }
+
+    let (water_alpha, snow_alpha) = get_water_and_snow_alphas(ecosystem, index);
+    color = color * (1.0 - water_alpha) + constants::WATER_COLOR * water_alpha;
+    color = color * (1.0 - snow_alpha) + constants::SNOW_COLOR * snow_alpha;
+
+    // let mut top_biomass = self[index].estimate_bush_biomass() + self[index].estimate_tree_biomass();
+    // if let Some(dead) = &self[index].dead_vegetation {
+    //     top_biomass += dead.biomass;
+    // }
+
+    color
+}
+
+// returns how opaque standing water and snow over the ground are
+pub fn get_water_and_snow_alphas(ecosystem: &Ecosystem, index: CellIndex) -> (f32, f32) {
+    // standing water covers the ground, becoming opaque at 1 m deep
+    let water_alpha = f32::min(ecosystem[index].get_water_height(), 1.0);
+    // snow covers everything, becoming opaque at 0.5 m deep
+    let snow_alpha = f32::min(ecosystem[index].get_snow_height() / 0.5, 1.0);
+    (water_alpha, snow_alpha)
+}
+
+pub fn get_soil_color(ecosystem: &Ecosystem, index: CellIndex) -> (f32, Vector3<f32>) {
+    let (height, weights) = get_soil_weights(ecosystem, index);
+    (
+        height,
+        weights.x * get_rock_color(&ecosystem[index])
+            + weights.y * constants::SAND_COLOR
+            + weights.z * constants::HUMUS_COLOR,
+    )
+}
+
+// blends the colors of the boulders, scree, and gravel by their heights
+pub fn get_rock_color(cell: &Cell) -> Vector3<f32> {
+    let heights = cell.get_rock_heights();
+    let height: f32 = heights.iter().sum();
+    if height <= 0.0 {
+        return constants::ROCK_COLOR;
+    }
+    (heights[0] * constants::BOULDER_COLOR
+        + heights[1] * constants::ROCK_COLOR
+        + heights[2] * constants::GRAVEL_COLOR)
+        / height
+}
+
+// returns the weighted height of the soil and the weights of rock, sand, and humus in its color
+pub fn get_soil_weights(ecosystem: &Ecosystem, index: CellIndex) -> (f32, Vector3<f32>) {
+    let cell = &ecosystem[index];
+    let rock_amt = cell.get_rock_height();
+    let sand_amt = cell.get_sand_height();
+    let humus_amt = cell.get_humus_height() * 5.0; // increase humus color weighting
+    let height = rock_amt + sand_amt + humus_amt;
+    // println!("rocks_height {rock_amt}");
+    // println!("sand_amt {sand_amt}");
+    // println!("humus_height {humsus_amt}");
+
+    (height, Vector3::new(rock_amt, sand_amt, humus_amt) / height)
+}
+
+// lowest and highest height of the terrain, which hypsometric tints are normalized to
+pub fn get_height_range(ecosystem: &Ecosystem) -> (f32, f32) {
+    ecosystem
+        .cells
+        .iter()
+        .map(|cell| cell.get_height())
+        .fold((f32::MAX, f32::MIN), |(low, high), height| {
+            (f32::min(low, height), f32::max(high, height))
+        })
+}
+
+// height of the cell between the lowest and highest heights, on the ramp of the config
+pub fn get_hypsometric_color(
+    ecosystem: &Ecosystem,
+    index: CellIndex,
+    (low, high): (f32, f32),
+) -> Vector3<f32> {
+    let height = ecosystem[index].get_height();
+    let relative = if high > low {
+        (height - low) / (high - low)
+    } else {
+        0.0
+    };
+    ecosystem.config.color_ramps.hypsometric.sample(relative)
+}
+
+// returns a color based on the ray traced hours of sunlight of the cell in the month
+fn get_sunlight_color(ecosystem: &Ecosystem, index: CellIndex, month: usize) -> Vector3<f32> {
+    let color = ecosystem[index].hours_of_sunlight[month] / 16.0; // assumption: max hours is 16
+    Vector3::new(color, color, color)
+}
+
+fn get_normalize_soil_moisture_color(
+    ecosystem: &Ecosystem,
+    index: CellIndex,
+    month: usize,
+) -> Vector3<f32> {
+    let moisture = Events::compute_moisture(ecosystem, index, month);
+    ecosystem
+        .config
+        .color_ramps
+        .soil_moisture
+        .sample(moisture / constants::SATURATED_SOIL_MOISTURE)
+}
+
+// overlays recently burned cells in red on top of the standard colors
+fn get_fire_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
+    let burn_severity = ecosystem[index].burn_severity;
+    let color = get_color(ecosystem, index);
+    color * (1.0 - burn_severity) + constants::FIRE_COLOR * burn_severity
+}
+
+// overlays the flow paths of the last time step on top of the standard colors,
+// relative to the largest flow so small streams still show
+fn get_runoff_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
+    let color = get_color(ecosystem, index);
+    let Some(trace) = &ecosystem.runoff_trace else {
+        return color;
+    };
+    let max_discharge = trace.get_max_discharge();
+    if max_discharge <= 0.0 {
+        return color;
+    }
+    let discharge = trace.discharge[index.get_flat_index()];
+    color.lerp(&constants::RUNOFF_COLOR, (discharge / max_discharge).sqrt())
+}
+
+// green where fuel is too wet to ignite to the fire color at the highest danger
+fn get_fire_danger_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
+    let danger = fire_danger::estimate_fire_danger(ecosystem, index);
+    constants::GRASS_COLOR.lerp(&constants::FIRE_COLOR, danger)
+}
+
+// darkens from bare ground to dense canopy with the density of trees and bushes
+// grass tints the ground wherever the canopy is open
+fn get_vegetation_density_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
+    let cell = &ecosystem[index];
+    let (grass, grass_color) = cell
+        .grasses
+        .as_ref()
+        .map_or((0.0, constants::GRASS_COLOR), |grasses| {
+            (grasses.coverage_density.min(1.0), get_grass_color(grasses))
+        });
+    let woody = (cell.estimate_vegetation_density() - grass).clamp(0.0, 1.0);
+    let ground = constants::BARE_GROUND_COLOR.lerp(&grass_color, grass);
+    ground.lerp(&constants::DENSE_CANOPY_COLOR, woody)
+}
+
+// green in the growing season and brown once cured
+fn get_grass_color(grasses: &Grasses) -> Vector3<f32> {
+    constants::CURED_GRASS_COLOR.lerp(&constants::GRASS_COLOR, grasses.green_fraction)
+}
+
+// blue to red gradient of the monthly temperature, which drops with elevation
+fn get_temperature_color(ecosystem: &Ecosystem, index: CellIndex, month: usize) -> Vector3<f32> {
+    let temperature = ecosystem.get_local_temperature(index, month);
+    let relative = (temperature - constants::MIN_DISPLAYED_TEMPERATURE)
+        / (constants::MAX_DISPLAYED_TEMPERATURE - constants::MIN_DISPLAYED_TEMPERATURE);
+    ecosystem.config.color_ramps.temperature.sample(relative)
+}
+
+// brightness of the slope under the light, like cartographic hillshading
+fn get_hillshade_color(
+    ecosystem: &Ecosystem,
+    index: CellIndex,
+    hillshade: &Hillshade,
+) -> Vector3<f32> {
+    // positive X is east and positive Y is north
+    let azimuth = hillshade.azimuth.to_radians();
+    let elevation = hillshade.elevation.to_radians();
+    let light = Vector3::new(
+        azimuth.sin() * elevation.cos(),
+        azimuth.cos() * elevation.cos(),
+        elevation.sin(),
+    );
+    let shade = f32::max(ecosystem.get_normal(index).dot(&light), 0.0);
+    Vector3::new(shade, shade, shade)
+}
+
+// mix of the colors of the regions the sand and humus came from
+// cells without tracked sediment keep their standard colors
+fn get_provenance_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
+    let Some(fractions) = ecosystem.get_provenance_fractions(index) else {
+        return get_color(ecosystem, index);
+    };
+    fractions
+        .iter()
+        .zip(constants::PROVENANCE_COLORS)
+        .map(|(fraction, color)| color * *fraction)
+        .sum()
+}
+
+// white where the cell follows the lapse rate, red where it is warmer, and blue where colder
+fn get_microclimate_color(ecosystem: &Ecosystem, index: CellIndex, month: usize) -> Vector3<f32> {
+    let offset = ecosystem.get_microclimate_offset(index, month);
+    let alpha = (offset / constants::MAX_DISPLAYED_MICROCLIMATE_OFFSET).clamp(-1.0, 1.0);
+    let neutral = Vector3::new(1.0, 1.0, 1.0);
+    if alpha >= 0.0 {
+        neutral.lerp(&constants::HOT_COLOR, alpha)
+    } else {
+        neutral.lerp(&constants::COLD_COLOR, -alpha)
+    }
+}
+
+fn get_wind_field_color(ecosystem: &Ecosystem, index: CellIndex) -> Vector3<f32> {
+    let (wind_dir, wind_str) = sample_local_wind(ecosystem, index);
+    // convert wind_dir from 0-360 to 0-255
+    let wind_dir = wind_dir / 360.0 * 255.0;
+    // convert wind_str from 0~30 to 0-255
+    let wind_str = wind_str / 30.0 * 255.0;
+    Vector3::new(wind_dir, wind_str, 0.0)
+}
+
+// marches along the ray until it passes below the terrain vertices
+pub fn pick_cell_along_ray(
+    vertices: &[Vector3<f32>],
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+) -> Option<CellIndex> {
+    let step = 0.1;
+    let max_distance = 1000.0;
+    let mut distance = 0.0;
+    while distance < max_distance {
+        let point = origin + direction * distance;
+        let x = point.x.round();
+        let y = point.y.round();
+        if x >= 0.0
+            && y >= 0.0
+            && x < constants::AREA_SIDE_LENGTH as f32
+            && y < constants::AREA_SIDE_LENGTH as f32
+        {
+            let (x, y) = (x as usize, y as usize);
+            // terrain vertices are stored in the order of build_terrain_vertices
+            if point.z <= vertices[x * constants::AREA_SIDE_LENGTH + y].z {
+                return Some(CellIndex::new(x, y));
+            }
+        }
+        distance += step;
+    }
+    None
+}
+
+// position of the terrain vertex of a cell in the vbo, which follows the order of build_vertex_data
+pub fn get_vertex_index(index: CellIndex) -> usize {
+    index.x * constants::AREA_SIDE_LENGTH + index.y
+}
+
+// converts (x,y) index in 2D vec into an index into a flattened 1D vec
+pub fn get_flat_index(x: i32, y: i32) -> i32 {
+    y * constants::AREA_SIDE_LENGTH as i32 + x
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+    use nalgebra::Vector3;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{
+        build_terrain_vertices, build_water_vertex, get_color, get_grass_coverage,
+        get_height_range, get_hillshade_color, get_hypsometric_color, get_provenance_color,
+        get_rock_color, get_runoff_color, get_sunlight_color, get_temperature_color,
+        get_vegetation_density_color, get_vertex_index, pick_cell_along_ray, ColorMode, Hillshade,
+    };
+    use crate::{
+        config::Config,
+        constants,
+        ecology::{self, Cell, CellIndex, Ecosystem, Grasses, RockSize, Trees},
+        events::rainfall::RunoffTrace,
+    };
+
+    #[test]
+    fn test_get_color() {
+        let mut cell = Cell::init();
+        cell.add_rocks(1.0);
+        let mut eco = Ecosystem {
+            cells: vec![cell.clone()],
+            tets: vec![],
+            bvh: None,
+            dirty_cells: Default::default(),
+            changed_cells: Default::default(),
+            event_log: Default::default(),
+            mass_audit: None,
+            sunlight_from_gpu: false,
+            sunlight_stale: false,
+            progressive_sunlight: None,
+            runoff_trace: None,
+            storms: vec![],
+            rainfall_field: vec![],
+            strata_noise: Default::default(),
+            wind_state: None,
+            provenance: None,
+            succession: Default::default(),
+            grazers: vec![],
+            config: Config::default(),
+            time_step: 0,
+            seed: 0,
+            rng: StdRng::seed_from_u64(0),
+        };
+        let actual: Vector3<f32> = get_color(&eco, CellIndex::new(0, 0));
+        let expected: Vector3<f32> = constants::ROCK_COLOR;
+        assert!(
+            actual == expected,
+            "Expected color {expected}, actual color {actual}"
+        );
+        cell.add_sand(1.0);
+        eco[CellIndex::new(0, 0)] = cell;
+        let actual: Vector3<f32> = get_color(&eco, CellIndex::new(0, 0));
+        let expected: Vector3<f32> = (constants::SAND_COLOR + constants::ROCK_COLOR) / 2.0;
+        assert!(
+            actual == expected,
+            "Expected color {expected}, actual color {actual}"
+        );
+    }
+
+    #[test]
+    fn test_get_rock_color() {
+        let mut cell = Cell::init();
+        cell.add_rocks_of_size(RockSize::Boulders, 1.0);
+        let actual = get_rock_color(&cell);
+        let expected = constants::BOULDER_COLOR;
+        assert!(
+            actual == expected,
+            "Expected color {expected}, actual color {actual}"
+        );
+        cell.add_rocks_of_size(RockSize::Gravel, 1.0);
+        let actual = get_rock_color(&cell);
+        let expected = (constants::BOULDER_COLOR + constants::GRAVEL_COLOR) / 2.0;
+        assert!(
+            actual == expected,
+            "Expected color {expected}, actual color {actual}"
+        );
+    }
+
+    #[test]
+    fn test_get_vegetation_density_color() {
+        let mut eco = Ecosystem::init();
+        let index = CellIndex::new(0, 0);
+        let actual = get_vegetation_density_color(&eco, index);
+        let expected = constants::BARE_GROUND_COLOR;
+        assert!(
+            actual == expected,
+            "Expected color {expected}, actual color {actual}"
+        );
+
+        eco[index].grasses = Some(ecology::Grasses {
+            coverage_density: 1.0,
+            green_fraction: 1.0,
+        });
+        let actual = get_vegetation_density_color(&eco, index);
+        let expected = constants::GRASS_COLOR;
+        assert!(
+            actual == expected,
+            "Expected color {expected}, actual color {actual}"
+        );
+
+        // cured grass is brown
+        eco[index].grasses.as_mut().unwrap().green_fraction = 0.0;
+        let actual = get_vegetation_density_color(&eco, index);
+        let expected = constants::CURED_GRASS_COLOR;
+        assert!(
+            actual == expected,
+            "Expected color {expected}, actual color {actual}"
+        );
+
+        // a closed canopy hides the grass
+        eco[index].trees = vec![Trees::with_plants(0, 100, 2000.0, 5000.0)];
+        let actual = get_vegetation_density_color(&eco, index);
+        let expected = constants::DENSE_CANOPY_COLOR;
+        assert!(
+            actual == expected,
+            "Expected color {expected}, actual color {actual}"
+        );
+    }
+
+    #[test]
+    fn test_get_hillshade_color() {
+        let mut eco = Ecosystem::init();
+        let index = CellIndex::new(10, 10);
+        let hillshade = Hillshade::default();
+
+        // flat ground is lit by the elevation of the light alone
+        let actual = get_hillshade_color(&eco, index, &hillshade).x;
+        let expected = hillshade.elevation.to_radians().sin();
+        assert!(
+            approx_eq!(f32, actual, expected, epsilon = 0.0001),
+            "Expected {expected}, actual {actual}"
+        );
+
+        // a slope rising to the east faces a light in the northwest and turns away from one in the east
+        for x in 0..constants::AREA_SIDE_LENGTH {
+            for y in 0..constants::AREA_SIDE_LENGTH {
+                eco[CellIndex::new(x, y)].add_bedrock(x as f32);
+            }
+        }
+        let west_facing = get_hillshade_color(&eco, index, &hillshade).x;
+        let lit_from_east = Hillshade {
+            azimuth: 90.0,
+            ..hillshade
+        };
+        let east_lit = get_hillshade_color(&eco, index, &lit_from_east).x;
+        assert!(
+            west_facing > expected && east_lit < expected,
+            "Expected brighter and darker than {expected}, actual {west_facing} and {east_lit}"
+        );
+    }
+
+    #[test]
+    fn test_build_water_vertex() {
+        let mut eco = Ecosystem::init();
+        let index = CellIndex::new(10, 10);
+        // dry ground has no water surface
+        let dry = build_water_vertex(&eco, index, &ColorMode::Standard);
+        assert_eq!(dry.alpha, 0.0);
+
+        // deeper water is more opaque and the surface floats on top of it
+        eco[index].add_water(0.2);
+        let shallow = build_water_vertex(&eco, index, &ColorMode::Standard);
+        eco[index].add_water(2.0);
+        let deep = build_water_vertex(&eco, index, &ColorMode::Standard);
+        assert!(
+            shallow.alpha > 0.0 && deep.alpha > shallow.alpha,
+            "Expected more opaque than {}, actual {}",
+            shallow.alpha,
+            deep.alpha
+        );
+        assert_eq!(deep.alpha, constants::MAX_WATER_ALPHA);
+        assert!(deep.position.z > shallow.position.z);
+
+        // other color modes are not covered
+        let hidden = build_water_vertex(&eco, index, &ColorMode::Sunlight(0));
+        assert_eq!(hidden.alpha, 0.0);
+    }
+
+    #[test]
+    fn test_color_mode_month() {
+        // stepping the month wraps around the year and keeps the hour of the sun
+        let color_mode = ColorMode::Shadows(11, 9).with_month(12);
+        assert_eq!(color_mode, ColorMode::Shadows(0, 9));
+        assert_eq!(color_mode.get_month(), Some(0));
+        let color_mode = ColorMode::SoilMoisture(0).with_month(6);
+        assert_eq!(color_mode, ColorMode::SoilMoisture(6));
+
+        // color modes without seasons have no month to change
+        assert_eq!(ColorMode::Standard.get_month(), None);
+        assert_eq!(ColorMode::Standard.with_month(3), ColorMode::Standard);
+    }
+
+    #[test]
+    fn test_get_sunlight_color() {
+        let mut eco = Ecosystem::init();
+        let index = CellIndex::new(0, 0);
+        eco[index].hours_of_sunlight = [4.0; 12];
+        eco[index].hours_of_sunlight[6] = 16.0;
+        for (month, expected) in [(0, 0.25), (6, 1.0)] {
+            let actual = get_sunlight_color(&eco, index, month).x;
+            assert!(
+                approx_eq!(f32, actual, expected, epsilon = 0.0001),
+                "Expected {expected}, actual {actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_provenance_color() {
+        let mut eco = Ecosystem::init();
+        let index = CellIndex::new(0, 0);
+        eco[index].add_sand(1.0);
+        let untracked = get_provenance_color(&eco, index);
+        assert_eq!(untracked, get_color(&eco, index));
+
+        // sand that never moved has the color of its own region
+        eco.enable_provenance();
+        let actual = get_provenance_color(&eco, index);
+        let expected = constants::PROVENANCE_COLORS[0];
+        assert!(
+            (actual - expected).norm() < 0.0001,
+            "Expected color {expected}, actual color {actual}"
+        );
+    }
+
+    #[test]
+    fn test_get_runoff_color() {
+        let mut eco = Ecosystem::init();
+        let index = CellIndex::new(0, 0);
+        let dry = CellIndex::new(1, 0);
+        let standard = get_color(&eco, index);
+        assert_eq!(get_runoff_color(&eco, index), standard);
+
+        let mut trace = RunoffTrace {
+            discharge: vec![0.0; constants::NUM_CELLS],
+            ..Default::default()
+        };
+        trace.discharge[index.get_flat_index()] = 2.0;
+        eco.set_runoff_trace(trace);
+        assert!(eco.changed_cells.contains(&index));
+        assert_eq!(get_runoff_color(&eco, index), constants::RUNOFF_COLOR);
+        assert_eq!(get_runoff_color(&eco, dry), standard);
+    }
+
+    #[test]
+    fn test_get_hypsometric_color() {
+        // the lowest and highest cells take the ends of the ramp, whatever their heights
+        let mut eco = Ecosystem::init();
+        let (low, high) = (CellIndex::new(1, 2), CellIndex::new(3, 4));
+        eco[low].remove_bedrock(20.0);
+        eco[high].add_bedrock(5.0);
+        let height_range = get_height_range(&eco);
+        assert_eq!(
+            height_range,
+            (
+                constants::DEFAULT_BEDROCK_HEIGHT - 20.0,
+                constants::DEFAULT_BEDROCK_HEIGHT + 5.0
+            )
+        );
+        let ramp = &eco.config.color_ramps.hypsometric;
+        let actual = get_hypsometric_color(&eco, low, height_range);
+        let expected = ramp.sample(0.0);
+        assert_eq!(actual, expected);
+        let actual = get_hypsometric_color(&eco, high, height_range);
+        let expected = ramp.sample(1.0);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_get_temperature_color() {
+        let mut eco = Ecosystem::init();
+        eco.config.climate.monthly_temperatures = [constants::MIN_DISPLAYED_TEMPERATURE; 12];
+        eco.config.climate.monthly_temperatures[6] = constants::MAX_DISPLAYED_TEMPERATURE + 10.0;
+        let index = CellIndex::new(0, 0);
+
+        let actual = get_temperature_color(&eco, index, 0);
+        let expected = constants::COLD_COLOR;
+        assert!(
+            actual == expected,
+            "Expected color {expected}, actual color {actual}"
+        );
+        let actual = get_temperature_color(&eco, index, 6);
+        let expected = constants::HOT_COLOR;
+        assert!(
+            actual == expected,
+            "Expected color {expected}, actual color {actual}"
+        );
+
+        // higher cells are colder
+        eco.config.climate.monthly_temperatures[6] = 10.0;
+        let low = get_temperature_color(&eco, index, 6);
+        eco[index].add_bedrock(1000.0);
+        let high = get_temperature_color(&eco, index, 6);
+        assert!(
+            high.x < low.x,
+            "Expected less red than {low}, actual {high}"
+        );
+    }
+
+    #[test]
+    fn test_get_vertex_index() {
+        let vertices = build_terrain_vertices(&Ecosystem::init());
+        for index in [
+            CellIndex::new(0, 0),
+            CellIndex::new(3, 70),
+            CellIndex::new(70, 3),
+            CellIndex::new(
+                constants::AREA_SIDE_LENGTH - 1,
+                constants::AREA_SIDE_LENGTH - 1,
+            ),
+        ] {
+            let vertex = vertices[get_vertex_index(index)];
+            assert_eq!((vertex.x, vertex.y), (index.x as f32, index.y as f32));
+        }
+    }
+
+    #[test]
+    fn test_get_grass_coverage() {
+        let mut ecosystem = Ecosystem::init();
+        let index = CellIndex::new(5, 5);
+        ecosystem[index].grasses = Some(Grasses {
+            coverage_density: 0.4,
+            green_fraction: 1.0,
+        });
+        let coverage =
+            |ecosystem: &Ecosystem, color_mode| get_grass_coverage(ecosystem, index, &color_mode);
+        assert_eq!(coverage(&ecosystem, ColorMode::Standard), 0.4);
+        // only drawn over the standard colors
+        assert_eq!(coverage(&ecosystem, ColorMode::HypsometricTint), 0.0);
+        // and hidden under snow
+        ecosystem[index].add_snow(0.1, 300.0);
+        assert_eq!(coverage(&ecosystem, ColorMode::Standard), 0.0);
+    }
+
+    #[test]
+    fn test_pick_cell_along_ray() {
+        let mut vertices = vec![];
+        for i in 0..constants::AREA_SIDE_LENGTH {
+            for j in 0..constants::AREA_SIDE_LENGTH {
+                vertices.push(Vector3::new(i as f32, j as f32, 0.0));
+            }
+        }
+        // straight down
+        let origin = Vector3::new(10.0, 20.0, 50.0);
+        let actual = pick_cell_along_ray(&vertices, origin, Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(actual, Some(CellIndex::new(10, 20)));
+
+        // a raised cell blocks a slanted ray before it reaches the ground
+        let direction = Vector3::new(1.0, 0.0, -1.0).normalize();
+        let actual = pick_cell_along_ray(&vertices, origin, direction);
+        assert_eq!(actual, Some(CellIndex::new(60, 20)));
+        vertices[30 * constants::AREA_SIDE_LENGTH + 20].z = 35.0;
+        let actual = pick_cell_along_ray(&vertices, origin, direction);
+        assert_eq!(actual, Some(CellIndex::new(30, 20)));
+
+        // pointing away from the terrain
+        let actual = pick_cell_along_ray(&vertices, origin, Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(actual, None);
+    }
+}
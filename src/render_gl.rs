@@ -89,6 +89,11 @@ impl Shader {
         Shader::from_source(source, gl::FRAGMENT_SHADER)
     }
 
+    // compute shaders need OpenGL 4.3
+    pub fn from_comp_source(source: &CStr) -> Result<Shader, String> {
+        Shader::from_source(source, gl::COMPUTE_SHADER)
+    }
+
     pub fn id(&self) -> gl::types::GLuint {
         self.id
     }
@@ -102,6 +107,298 @@ impl Drop for Shader {
     }
 }
 
+// off-screen render target with a color and a depth buffer, e.g. for screenshots larger than the window
+pub struct Framebuffer {
+    id: gl::types::GLuint,
+    color: gl::types::GLuint,
+    depth: gl::types::GLuint,
+    width: u32,
+    height: u32,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Result<Framebuffer, String> {
+        let mut max_size: gl::types::GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_RENDERBUFFER_SIZE, &mut max_size);
+        }
+        if width == 0 || height == 0 || width.max(height) > max_size as u32 {
+            return Err(format!(
+                "Framebuffers must be between 1 and {max_size} pixels on each side, not {width}x{height}"
+            ));
+        }
+
+        let mut framebuffer = Framebuffer {
+            id: 0,
+            color: 0,
+            depth: 0,
+            width,
+            height,
+        };
+        unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer.id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.id);
+            for (renderbuffer, format, attachment) in [
+                (&mut framebuffer.color, gl::RGBA8, gl::COLOR_ATTACHMENT0),
+                (
+                    &mut framebuffer.depth,
+                    gl::DEPTH_COMPONENT24,
+                    gl::DEPTH_ATTACHMENT,
+                ),
+            ] {
+                gl::GenRenderbuffers(1, renderbuffer);
+                gl::BindRenderbuffer(gl::RENDERBUFFER, *renderbuffer);
+                gl::RenderbufferStorage(gl::RENDERBUFFER, format, width as i32, height as i32);
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    attachment,
+                    gl::RENDERBUFFER,
+                    *renderbuffer,
+                );
+            }
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!("Framebuffer is incomplete with status {status}"));
+            }
+        }
+        Ok(framebuffer)
+    }
+
+    // draws into this framebuffer instead of the window until it is unbound
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, self.width as i32, self.height as i32);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    // returns the rgb pixels of the color buffer, from the top row down
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let row_length = self.width as usize * 3;
+        let mut pixels = vec![0; row_length * self.height as usize];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                gl::RGB,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut gl::types::GLvoid,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+        // OpenGL starts from the bottom row
+        pixels
+            .chunks_exact(row_length)
+            .rev()
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(2, [self.color, self.depth].as_ptr());
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
+
+// rgb texture uploaded from the cpu, e.g. the ripple map drawn over the terrain
+pub struct Texture {
+    id: gl::types::GLuint,
+    width: u32,
+    height: u32,
+}
+
+impl Texture {
+    pub fn new(width: u32, height: u32, pixels: &[u8]) -> Texture {
+        assert_eq!(pixels.len(), (width * height * 3) as usize);
+        let mut texture = Texture {
+            id: 0,
+            width,
+            height,
+        };
+        unsafe {
+            gl::GenTextures(1, &mut texture.id);
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
+            // rows of rgb pixels are not padded to 4 bytes
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGB8 as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGB,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        texture
+    }
+
+    // replaces the pixels, which must be as many as the texture was created with
+    pub fn update(&self, pixels: &[u8]) {
+        assert_eq!(pixels.len(), (self.width * self.height * 3) as usize);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                gl::RGB,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    // makes the texture readable by samplers of the texture unit
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+// depth texture the terrain is rendered into from the sun, to look up which fragments it lights
+pub struct ShadowMap {
+    id: gl::types::GLuint,
+    depth: gl::types::GLuint,
+    size: u32,
+}
+
+impl ShadowMap {
+    pub fn new(size: u32) -> Result<ShadowMap, String> {
+        let mut shadow_map = ShadowMap {
+            id: 0,
+            depth: 0,
+            size,
+        };
+        unsafe {
+            gl::GenTextures(1, &mut shadow_map.depth);
+            gl::BindTexture(gl::TEXTURE_2D, shadow_map.depth);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::DEPTH_COMPONENT24 as i32,
+                size as i32,
+                size as i32,
+                0,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            // everything outside of the map is lit
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_BORDER as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_BORDER as i32,
+            );
+            gl::TexParameterfv(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_BORDER_COLOR,
+                [1.0, 1.0, 1.0, 1.0].as_ptr(),
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::GenFramebuffers(1, &mut shadow_map.id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, shadow_map.id);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::TEXTURE_2D,
+                shadow_map.depth,
+                0,
+            );
+            // only depth is written
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!("Shadow map is incomplete with status {status}"));
+            }
+        }
+        Ok(shadow_map)
+    }
+
+    // draws depth into the shadow map until it is unbound
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, self.size as i32, self.size as i32);
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    // makes the depth readable by samplers of the texture unit
+    pub fn bind_texture(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.depth);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+}
+
+impl Drop for ShadowMap {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.depth);
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
+
 fn shader_from_source(source: &CStr, kind: gl::types::GLenum) -> Result<gl::types::GLuint, String> {
     let id = unsafe { gl::CreateShader(kind) };
     unsafe {
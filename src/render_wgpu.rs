@@ -0,0 +1,500 @@
+// WGPU RENDERER
+// draws the ecosystem with wgpu into the surface of a winit window, as a backend of the viewer
+// built from the same vertex data, faces, grid lines, and camera as the OpenGL renderer
+// for now it only draws the terrain with its colors, grass, contours, and the dark of night,
+// without the plants, water surface, shadows, sand ripples, or levels of detail
+
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+use nalgebra::{Matrix4, Vector3};
+use wgpu::util::DeviceExt;
+use winit::{event_loop::OwnedDisplayHandle, window::Window};
+
+use crate::{
+    camera::Camera,
+    constants,
+    ecology::{illumination::get_sun_direction, CellIndex, Ecosystem},
+    render_backend::RenderBackend,
+    render_data::{self, get_vertex_index, pick_cell_along_ray, CellVertex, ColorMode, RenderMode},
+};
+
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct TerrainVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    color: [f32; 3],
+    grass: f32,
+}
+
+impl TerrainVertex {
+    fn from_cell(cell: &CellVertex) -> Self {
+        TerrainVertex {
+            position: cell.position.into(),
+            normal: cell.normal.into(),
+            color: cell.color.into(),
+            grass: cell.grass_coverage,
+        }
+    }
+}
+
+// laid out like the uniforms of terrain.wgsl
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Uniforms {
+    view_proj: [[f32; 4]; 4],
+    contour_interval: f32,
+    night: u32,
+    padding: [u32; 2],
+}
+
+pub struct WgpuRenderable {
+    pub m_camera: Camera,
+    m_surface: wgpu::Surface<'static>,
+    m_config: wgpu::SurfaceConfiguration,
+    m_device: wgpu::Device,
+    m_queue: wgpu::Queue,
+    m_solid_pipeline: wgpu::RenderPipeline,
+    // solid faces pushed back so the grid lines drawn over them are not hidden
+    m_overlay_pipeline: wgpu::RenderPipeline,
+    m_wire_pipeline: wgpu::RenderPipeline,
+    m_vertex_buffer: wgpu::Buffer,
+    m_face_buffer: wgpu::Buffer,
+    m_num_face_indices: u32,
+    m_line_buffer: wgpu::Buffer,
+    m_num_line_indices: u32,
+    m_uniform_buffer: wgpu::Buffer,
+    m_bind_group: wgpu::BindGroup,
+    m_depth_view: wgpu::TextureView,
+    // terrain vertices kept on the cpu to pick cells with
+    m_vertices: Vec<Vector3<f32>>,
+    m_contour_interval: f32,
+    m_night: bool,
+}
+
+impl WgpuRenderable {
+    // sets up a device that draws into the window, with the terrain of the ecosystem in standard colors
+    pub fn init(
+        window: Arc<Window>,
+        display: OwnedDisplayHandle,
+        ecosystem: &Ecosystem,
+    ) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(
+            wgpu::InstanceDescriptor::new_with_display_handle_from_env(Box::new(display)),
+        );
+        let size = window.inner_size();
+        let surface = instance
+            .create_surface(window)
+            .map_err(|error| format!("cannot create a wgpu surface: {error}"))?;
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: Some(&surface),
+            ..Default::default()
+        }))
+        .map_err(|error| format!("cannot find a wgpu adapter: {error}"))?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default()))
+                .map_err(|error| format!("cannot create a wgpu device: {error}"))?;
+
+        let width = size.width.max(1);
+        let height = size.height.max(1);
+        let mut config = surface
+            .get_default_config(&adapter, width, height)
+            .ok_or("the wgpu surface is not supported by the adapter")?;
+        // the colors are already what the OpenGL renderer shows, without gamma correction
+        if let Some(format) = surface
+            .get_capabilities(&adapter)
+            .formats
+            .into_iter()
+            .find(|format| !format.is_srgb())
+        {
+            config.format = format;
+        }
+        surface.configure(&device, &config);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("terrain"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("../resources/shaders/terrain.wgsl").into(),
+            ),
+        });
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("uniforms"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("uniforms"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("uniforms"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("terrain"),
+            bind_group_layouts: &[Some(&bind_group_layout)],
+            immediate_size: 0,
+        });
+        let build_pipeline = |topology, fragment_entry_point, bias| {
+            Self::build_pipeline(
+                &device,
+                &layout,
+                &shader,
+                config.format,
+                topology,
+                fragment_entry_point,
+                bias,
+            )
+        };
+        let solid_pipeline = build_pipeline(
+            wgpu::PrimitiveTopology::TriangleList,
+            "fs_main",
+            wgpu::DepthBiasState::default(),
+        );
+        let overlay_pipeline = build_pipeline(
+            wgpu::PrimitiveTopology::TriangleList,
+            "fs_main",
+            wgpu::DepthBiasState {
+                constant: 1,
+                slope_scale: 1.0,
+                clamp: 0.0,
+            },
+        );
+        let wire_pipeline = build_pipeline(
+            wgpu::PrimitiveTopology::LineList,
+            "fs_wire",
+            wgpu::DepthBiasState::default(),
+        );
+
+        let data = render_data::build_vertex_data(
+            &render_data::build_tree_positions(ecosystem.seed),
+            ecosystem,
+            &ColorMode::Standard,
+        );
+        let mut vertices = vec![TerrainVertex::zeroed(); constants::NUM_CELLS];
+        for cell in &data {
+            vertices[get_vertex_index(cell.index)] = TerrainVertex::from_cell(cell);
+        }
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("terrain vertices"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        let faces: Vec<u32> = render_data::build_terrain_faces()
+            .iter()
+            .flat_map(|face| [face.x as u32, face.y as u32, face.z as u32])
+            .collect();
+        let face_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("terrain faces"),
+            contents: bytemuck::cast_slice(&faces),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let lines: Vec<u32> = render_data::build_grid_lines()
+            .iter()
+            .flat_map(|line| [line.x as u32, line.y as u32])
+            .collect();
+        let line_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("grid lines"),
+            contents: bytemuck::cast_slice(&lines),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let depth_view = Self::build_depth_view(&device, width, height);
+
+        Ok(WgpuRenderable {
+            m_camera: render_data::build_camera(width as f32 / height as f32),
+            m_surface: surface,
+            m_config: config,
+            m_device: device,
+            m_queue: queue,
+            m_solid_pipeline: solid_pipeline,
+            m_overlay_pipeline: overlay_pipeline,
+            m_wire_pipeline: wire_pipeline,
+            m_vertex_buffer: vertex_buffer,
+            m_face_buffer: face_buffer,
+            m_num_face_indices: faces.len() as u32,
+            m_line_buffer: line_buffer,
+            m_num_line_indices: lines.len() as u32,
+            m_uniform_buffer: uniform_buffer,
+            m_bind_group: bind_group,
+            m_depth_view: depth_view,
+            m_vertices: render_data::build_terrain_vertices(ecosystem),
+            m_contour_interval: 0.0,
+            m_night: false,
+        })
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        topology: wgpu::PrimitiveTopology,
+        fragment_entry_point: &str,
+        bias: wgpu::DepthBiasState,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(fragment_entry_point),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[Some(wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<TerrainVertex>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x3,
+                        1 => Float32x3,
+                        2 => Float32x3,
+                        3 => Float32,
+                    ],
+                })],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: Some(true),
+                depth_compare: Some(wgpu::CompareFunction::LessEqual),
+                stencil: wgpu::StencilState::default(),
+                bias,
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some(fragment_entry_point),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview_mask: None,
+            cache: None,
+        })
+    }
+
+    fn build_depth_view(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("depth"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    // fits the surface, depth buffer, and camera to the new size of the window
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.m_config.width = width;
+        self.m_config.height = height;
+        self.m_surface.configure(&self.m_device, &self.m_config);
+        self.m_depth_view = Self::build_depth_view(&self.m_device, width, height);
+        self.m_camera.set_aspect(width as f32 / height as f32);
+    }
+
+    // returns the texture of the window to draw the next frame into and present, if it can show one
+    pub fn get_current_texture(&mut self) -> Option<wgpu::SurfaceTexture> {
+        match self.m_surface.get_current_texture() {
+            wgpu::CurrentSurfaceTexture::Success(texture)
+            | wgpu::CurrentSurfaceTexture::Suboptimal(texture) => Some(texture),
+            wgpu::CurrentSurfaceTexture::Outdated | wgpu::CurrentSurfaceTexture::Lost => {
+                self.m_surface.configure(&self.m_device, &self.m_config);
+                None
+            }
+            wgpu::CurrentSurfaceTexture::Timeout
+            | wgpu::CurrentSurfaceTexture::Occluded
+            | wgpu::CurrentSurfaceTexture::Validation => None,
+        }
+    }
+
+    // shows the frame drawn into the texture in the window
+    pub fn present(&self, texture: wgpu::SurfaceTexture) {
+        self.m_queue.present(texture);
+    }
+
+    pub fn update_vertices(&mut self, ecosystem: &Ecosystem, color_mode: &ColorMode) {
+        let tree_positions = render_data::build_tree_positions(ecosystem.seed);
+        let data = render_data::build_vertex_data(&tree_positions, ecosystem, color_mode);
+        self.upload_vertex_data(&data);
+    }
+
+    // projection of the camera with the depth of OpenGL's [-1, 1] mapped to wgpu's [0, 1]
+    fn get_view_projection(&mut self) -> Matrix4<f32> {
+        let depth_correction = Matrix4::new(
+            1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0, 0.0, 1.0,
+        );
+        depth_correction * self.m_camera.get_projection() * self.m_camera.get_view()
+    }
+}
+
+// draws into the view of the texture returned by get_current_texture, which the caller presents
+impl RenderBackend for WgpuRenderable {
+    type Target = wgpu::TextureView;
+
+    fn camera(&mut self) -> &mut Camera {
+        &mut self.m_camera
+    }
+
+    fn upload_vertex_data(&mut self, data: &[CellVertex]) {
+        let vertex_size = std::mem::size_of::<TerrainVertex>() as u64;
+        if data.len() < constants::NUM_CELLS {
+            for cell in data {
+                let k = get_vertex_index(cell.index);
+                self.m_vertices[k] = cell.position;
+                self.m_queue.write_buffer(
+                    &self.m_vertex_buffer,
+                    k as u64 * vertex_size,
+                    bytemuck::bytes_of(&TerrainVertex::from_cell(cell)),
+                );
+            }
+            return;
+        }
+
+        // replacing the whole buffer is much faster than a range per cell
+        let mut vertices = vec![TerrainVertex::zeroed(); constants::NUM_CELLS];
+        for cell in data {
+            let k = get_vertex_index(cell.index);
+            self.m_vertices[k] = cell.position;
+            vertices[k] = TerrainVertex::from_cell(cell);
+        }
+        self.m_queue
+            .write_buffer(&self.m_vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    fn set_color_mode(&mut self, ecosystem: &Ecosystem, color_mode: &ColorMode) {
+        self.m_contour_interval = render_data::get_contour_interval(color_mode);
+        self.m_night = match color_mode {
+            ColorMode::Shadows(month, hour) => {
+                get_sun_direction(&ecosystem.config.location, *month, *hour).is_none()
+            }
+            _ => false,
+        };
+    }
+
+    fn draw(&mut self, target: &wgpu::TextureView, render_mode: &RenderMode) {
+        let uniforms = Uniforms {
+            view_proj: self.get_view_projection().into(),
+            contour_interval: self.m_contour_interval,
+            night: self.m_night as u32,
+            padding: [0; 2],
+        };
+        self.m_queue
+            .write_buffer(&self.m_uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+
+        let mut encoder = self
+            .m_device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("terrain"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.m_depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+            pass.set_bind_group(0, &self.m_bind_group, &[]);
+            pass.set_vertex_buffer(0, self.m_vertex_buffer.slice(..));
+            if *render_mode != RenderMode::Wireframe {
+                pass.set_pipeline(if *render_mode == RenderMode::WireframeOverlay {
+                    &self.m_overlay_pipeline
+                } else {
+                    &self.m_solid_pipeline
+                });
+                pass.set_index_buffer(self.m_face_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..self.m_num_face_indices, 0, 0..1);
+            }
+            if *render_mode != RenderMode::Solid {
+                pass.set_pipeline(&self.m_wire_pipeline);
+                pass.set_index_buffer(self.m_line_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..self.m_num_line_indices, 0, 0..1);
+            }
+        }
+        self.m_queue.submit([encoder.finish()]);
+    }
+
+    fn pick_cell(&mut self, screen_x: i32, screen_y: i32) -> Option<CellIndex> {
+        let ndc_x = 2.0 * screen_x as f32 / self.m_config.width as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * screen_y as f32 / self.m_config.height as f32;
+        let direction = self.m_camera.get_ray_direction(ndc_x, ndc_y);
+        pick_cell_along_ray(&self.m_vertices, self.m_camera.m_position, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::naga;
+
+    use super::Uniforms;
+
+    #[test]
+    fn test_terrain_shader() {
+        // the shader is only compiled once a window has a device, so check it without one
+        let module =
+            naga::front::wgsl::parse_str(include_str!("../resources/shaders/terrain.wgsl"))
+                .unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap();
+
+        // the uniforms written by draw fill the uniform buffer of the shader
+        let (_, uniforms) = module
+            .types
+            .iter()
+            .find(|(_, ty)| ty.name.as_deref() == Some("Uniforms"))
+            .unwrap();
+        let expected = uniforms.inner.size(module.to_ctx()) as usize;
+        assert_eq!(std::mem::size_of::<Uniforms>(), expected);
+    }
+}
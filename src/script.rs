@@ -0,0 +1,295 @@
+// SCENARIO SCRIPT
+// interventions loaded from a TOML file and applied at the start of given time steps,
+// e.g. for disturbance and recovery experiments:
+//
+// [[interventions]]
+// time_step = 50
+// action = "clear_cut"
+// min = [10, 10]
+// max = [40, 40]
+//
+// [[interventions]]
+// time_step = 80
+// action = "ignite"
+// cell = [50, 50]
+//
+// [[interventions]]
+// time_step = 100
+// action = "set_wind_rose"
+// rose = [{ direction = 90.0, min_speed = 5.0, max_speed = 10.0 }]
+//
+// [[interventions]]
+// time_step = 120
+// action = "compact_soil"
+// path = [[0, 20], [50, 20], [50, 99]]
+// amount = 0.5
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{build_wind_rose, WindRoseEntry},
+    ecology::{CellIndex, Ecosystem},
+    events::{wind::WindState, Events},
+    land_use,
+};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Script {
+    pub interventions: Vec<Intervention>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Intervention {
+    pub time_step: u32,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    // removes all trees between the corners (inclusive), taking their biomass out of the area
+    // unless the slash is left behind as dead vegetation
+    ClearCut {
+        min: [usize; 2],
+        max: [usize; 2],
+        #[serde(default)]
+        leave_slash: bool,
+    },
+    // plants saplings of the species (index into the species table) at the density (per m^2)
+    Plant {
+        min: [usize; 2],
+        max: [usize; 2],
+        species: usize,
+        density: f32,
+    },
+    // cuts the bedrock between the corners into flat steps of the height (in m)
+    Terrace {
+        min: [usize; 2],
+        max: [usize; 2],
+        step_height: f32,
+    },
+    // compacts the soil along the straight lines between the waypoints, by an amount from 0 to 1
+    CompactSoil {
+        path: Vec<[usize; 2]>,
+        amount: f32,
+    },
+    // sets the cell on fire, which spreads like fire started by lightning
+    Ignite {
+        cell: [usize; 2],
+    },
+    // replaces the yearly and seasonal wind roses
+    SetWindRose {
+        rose: Vec<WindRoseEntry>,
+    },
+}
+
+impl Script {
+    pub fn load(path: &str) -> Result<Self, String> {
+        println!("Reading script at {path}");
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("cannot read script {path}: {error}"))?;
+        Self::parse(&contents).map_err(|error| format!("invalid script {path}: {error}"))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        toml::from_str(contents).map_err(|error| error.to_string())
+    }
+
+    // applies the interventions scheduled for the current time step, in the order they are listed
+    pub fn apply(&self, ecosystem: &mut Ecosystem) {
+        let time_step = ecosystem.time_step;
+        for intervention in &self.interventions {
+            if intervention.time_step == time_step {
+                println!("Applying {:?}", intervention.action);
+                intervention.action.apply(ecosystem);
+            }
+        }
+    }
+}
+
+impl Action {
+    pub fn apply(&self, ecosystem: &mut Ecosystem) {
+        match self {
+            Action::ClearCut {
+                min,
+                max,
+                leave_slash,
+            } => {
+                for index in land_use::get_cells_in_area(*min, *max) {
+                    land_use::clear_cut(&mut ecosystem[index], *leave_slash);
+                    ecosystem.changed_cells.insert(index);
+                }
+            }
+            Action::Plant {
+                min,
+                max,
+                species,
+                density,
+            } => {
+                assert!(
+                    *species < ecosystem.config.species.trees.len(),
+                    "Planting unknown species {species}"
+                );
+                for index in land_use::get_cells_in_area(*min, *max) {
+                    land_use::plant(&mut ecosystem[index], *species, *density);
+                    ecosystem.changed_cells.insert(index);
+                }
+            }
+            Action::Terrace {
+                min,
+                max,
+                step_height,
+            } => {
+                for index in land_use::get_cells_in_area(*min, *max) {
+                    land_use::terrace(&mut ecosystem[index], *step_height);
+                    ecosystem.mark_dirty(index);
+                }
+            }
+            Action::CompactSoil { path, amount } => {
+                for index in land_use::get_cells_along_path(path) {
+                    land_use::compact_soil(&mut ecosystem[index], *amount);
+                    ecosystem.mark_dirty(index);
+                }
+            }
+            Action::Ignite { cell } => {
+                Events::Fire.apply_event(ecosystem, CellIndex::new(cell[0], cell[1]));
+            }
+            Action::SetWindRose { rose } => {
+                let wind_state = ecosystem.wind_state.get_or_insert_with(WindState::new);
+                wind_state.wind_rose = build_wind_rose(rose);
+                wind_state.monthly_wind_roses.clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Script};
+    use crate::ecology::{CellIndex, Ecosystem, Trees};
+
+    #[test]
+    fn test_parse_script() {
+        let script = Script::parse(
+            r#"
+            [[interventions]]
+            time_step = 50
+            action = "clear_cut"
+            min = [10, 10]
+            max = [40, 40]
+
+            [[interventions]]
+            time_step = 80
+            action = "ignite"
+            cell = [50, 50]
+
+            [[interventions]]
+            time_step = 100
+            action = "set_wind_rose"
+            rose = [{ direction = 90.0, min_speed = 5.0, max_speed = 10.0 }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(script.interventions.len(), 3);
+        assert_eq!(script.interventions[0].time_step, 50);
+        assert!(matches!(
+            script.interventions[0].action,
+            Action::ClearCut {
+                min: [10, 10],
+                max: [40, 40],
+                leave_slash: false
+            }
+        ));
+        assert!(matches!(
+            script.interventions[1].action,
+            Action::Ignite { cell: [50, 50] }
+        ));
+        let Action::SetWindRose { rose } = &script.interventions[2].action else {
+            panic!("Expected a wind rose");
+        };
+        assert_eq!(rose[0].weight, 1.0);
+    }
+
+    #[test]
+    fn test_apply_land_use_script() {
+        let mut ecosystem = Ecosystem::init();
+        ecosystem.time_step = 1;
+        let trail = CellIndex::new(3, 1);
+        ecosystem[trail].add_humus(1.0);
+        let script = Script::parse(
+            r#"
+            [[interventions]]
+            time_step = 1
+            action = "plant"
+            min = [0, 0]
+            max = [1, 1]
+            species = 0
+            density = 0.1
+
+            [[interventions]]
+            time_step = 1
+            action = "clear_cut"
+            min = [1, 1]
+            max = [1, 1]
+            leave_slash = true
+
+            [[interventions]]
+            time_step = 1
+            action = "compact_soil"
+            path = [[0, 1], [5, 1]]
+            amount = 1.0
+            "#,
+        )
+        .unwrap();
+        script.apply(&mut ecosystem);
+
+        // planted, then logged where the areas overlap
+        assert_eq!(ecosystem[CellIndex::new(0, 0)].trees.len(), 1);
+        assert!(ecosystem[CellIndex::new(1, 1)].trees.is_empty());
+        assert!(ecosystem[CellIndex::new(1, 1)].get_dead_vegetation_biomass() > 0.0);
+        // the trail is compacted and marked for the terrain to catch up
+        assert!(ecosystem[trail].get_humus_height() < 1.0);
+        assert!(ecosystem.dirty_cells.contains(&trail));
+    }
+
+    #[test]
+    fn test_apply_script() {
+        let mut ecosystem = Ecosystem::init();
+        let trees = Trees::with_plants(0, 2, 20.0, 40.0);
+        let inside = CellIndex::new(5, 5);
+        let outside = CellIndex::new(6, 5);
+        ecosystem[inside].set_trees(trees.clone());
+        ecosystem[outside].set_trees(trees);
+        let script = Script::parse(
+            r#"
+            [[interventions]]
+            time_step = 2
+            action = "clear_cut"
+            min = [0, 0]
+            max = [5, 5]
+
+            [[interventions]]
+            time_step = 2
+            action = "set_wind_rose"
+            rose = [{ direction = 90.0, min_speed = 5.0, max_speed = 10.0 }]
+            "#,
+        )
+        .unwrap();
+
+        // nothing happens before the scheduled time step
+        ecosystem.time_step = 1;
+        script.apply(&mut ecosystem);
+        assert_eq!(ecosystem[inside].trees.len(), 1);
+        assert!(ecosystem.wind_state.is_none());
+
+        ecosystem.time_step = 2;
+        script.apply(&mut ecosystem);
+        assert!(ecosystem[inside].trees.is_empty());
+        assert_eq!(ecosystem[outside].trees.len(), 1);
+        // all of the wind blows from the east
+        let wind_state = ecosystem.wind_state.as_ref().unwrap();
+        assert_eq!(wind_state.wind_rose.weights[2], 1.0);
+        assert_eq!(wind_state.wind_rose.weights.iter().sum::<f32>(), 1.0);
+    }
+}
@@ -1,102 +1,285 @@
-use gl::types::GLuint;
-use rand::prelude::SliceRandom;
-use rand::thread_rng;
+use rand::Rng;
 
 use crate::{
-    constants,
-    ecology::{CellIndex, Ecosystem},
-    events::Events,
-    import::import_height_map,
-    render::{ColorMode, EcosystemRenderable},
+    config::Config,
+    ecology::{progressive_sunlight::ProgressiveSunlight, Ecosystem},
+    events::{audit::MassAudit, log::EventLog},
+    export::{append_event_log, append_metrics, export_checkpoint, export_maps, TimeLapse},
+    history::History,
+    script::Script,
 };
 
 pub struct Simulation {
-    pub ecosystem: EcosystemRenderable,
+    pub ecosystem: Ecosystem,
+    // csv the event log of every time step is appended to
+    pub event_log_path: Option<String>,
+    // csv the aggregate metrics of every time step are appended to
+    pub metrics_path: Option<String>,
+    // animated gif frames of the maps are added to while running
+    pub time_lapse: Option<TimeLapse>,
+    // snapshots of the last time steps to rewind to
+    pub history: Option<History>,
+    // interventions applied at the start of their time steps
+    pub script: Option<Script>,
 }
 
 impl Simulation {
     pub fn init() -> Self {
-        let ecosystem = Ecosystem::init_standard_ianterrain();
+        Self::init_with_ecosystem(Ecosystem::init_standard_ianterrain(rand::random()))
+    }
+
+    pub fn init_with_config(config: &Config) -> Result<Self, String> {
+        Ok(Self::init_with_ecosystem(config.build_ecosystem()?))
+    }
+
+    // resumes a simulation saved with `Ecosystem::save`
+    pub fn init_from_checkpoint(path: &str) -> Result<Self, String> {
+        Ok(Simulation {
+            ecosystem: Ecosystem::load(path)?,
+            event_log_path: None,
+            metrics_path: None,
+            time_lapse: None,
+            history: None,
+            script: None,
+        })
+    }
+
+    fn init_with_ecosystem(mut ecosystem: Ecosystem) -> Self {
+        println!("Computing sunlight...");
+        ecosystem.recompute_sunlight();
         Simulation {
-            ecosystem: EcosystemRenderable::init(ecosystem),
+            ecosystem,
+            event_log_path: None,
+            metrics_path: None,
+            time_lapse: None,
+            history: None,
+            script: None,
         }
     }
 
-    pub fn init_with_height_map(path: &str) -> Self {
-        Simulation {
-            ecosystem: import_height_map(path),
+    // checks that every event conserves the material it does not explicitly convert,
+    // panicking at the first event that creates or destroys material
+    pub fn with_mass_audit(mut self) -> Self {
+        self.ecosystem.mass_audit = Some(MassAudit::default());
+        self
+    }
+
+    // leaves ray traced sunlight to the render thread, which traces it on the gpu and sends it back
+    pub fn enable_gpu_sunlight(&mut self) {
+        self.ecosystem.sunlight_from_gpu = true;
+    }
+
+    // ray traces sunlight after terrain changes over the next frames instead of within the time step
+    // runs are no longer reproducible since sunlight then depends on how fast frames are drawn
+    pub fn enable_progressive_sunlight(&mut self) {
+        self.ecosystem.progressive_sunlight = Some(ProgressiveSunlight::default());
+    }
+
+    // keeps the ecosystem before each of the last `capacity` time steps so they can be undone
+    pub fn enable_history(&mut self, capacity: usize) {
+        self.history = Some(History::new(capacity));
+    }
+
+    // rewinds the ecosystem to before the last time step, if it was kept
+    // returns whether there was a time step to undo
+    pub fn step_back(&mut self) -> bool {
+        let Some(mut ecosystem) = self.history.as_mut().and_then(|history| history.pop()) else {
+            return false;
+        };
+        // state that is not saved belongs to the run rather than the time step
+        ecosystem.sunlight_from_gpu = self.ecosystem.sunlight_from_gpu;
+        ecosystem.mass_audit = self.ecosystem.mass_audit.take();
+        // sunlight queued for the newer terrain no longer applies
+        if self.ecosystem.progressive_sunlight.is_some() {
+            ecosystem.progressive_sunlight = Some(ProgressiveSunlight::default());
         }
+        self.ecosystem = ecosystem;
+        true
     }
 
-    pub fn draw(&mut self, program_id: GLuint, render_mode: gl::types::GLuint) {
-        self.ecosystem.draw(program_id, render_mode);
+    pub fn take_time_step(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.push(&self.ecosystem);
+        }
+        Self::advance_ecosystem(&mut self.ecosystem, self.script.as_ref());
+        self.finish_time_step();
     }
 
-    pub fn take_time_step(&mut self, color_mode: &ColorMode) {
-        // sample wind for this time step
-        if let Some(wind_state) = &mut self.ecosystem.ecosystem.wind_state {
-            let (wind_dir, wind_str) = wind_state.wind_rose.sample_wind();
-            println!("dir {wind_dir}, str {wind_str}");
+    // the part of a time step that only changes the ecosystem
+    fn advance_ecosystem(ecosystem: &mut Ecosystem, script: Option<&Script>) {
+        ecosystem.reseed_for_time_step();
+
+        ecosystem.time_step += 1;
+        ecosystem.event_log = EventLog::default();
+
+        // forget the events applied during the previous time step
+        for cell in ecosystem.cells.iter_mut() {
+            cell.last_events.clear();
+        }
+
+        // apply the interventions scripted for this time step
+        if let Some(script) = script {
+            script.apply(ecosystem);
+        }
+
+        // sample wind for this time step from the wind roses of the months it covers
+        let months = ecosystem.get_months_of_time_step();
+        if let Some(wind_state) = &mut ecosystem.wind_state {
+            let (wind_dir, wind_str) = wind_state.sample_wind(&months, &mut ecosystem.rng);
             wind_state.wind_direction = wind_dir;
             wind_state.wind_strength = wind_str;
-            crate::events::wind::convolve_terrain(&mut self.ecosystem.ecosystem);
+            crate::events::wind::convolve_terrain(ecosystem);
         }
 
-        // iterate over all cells
-        let num_cells = constants::AREA_SIDE_LENGTH * constants::AREA_SIDE_LENGTH;
-
-        let mut vec: Vec<usize> = (0..num_cells).collect();
-        vec.shuffle(&mut thread_rng());
-
-        for i in vec {
-            // apply random event
-            let mut events = [
-                Events::Lightning,
-                Events::ThermalStress,
-                Events::SandSlide,
-                Events::RockSlide,
-                Events::HumusSlide,
-                Events::VegetationTrees,
-                Events::VegetationBushes,
-                Events::VegetationGrasses,
-                Events::Rainfall,
-                // Events::Wind,
-            ];
-            events.shuffle(&mut thread_rng());
-            // println!("Events {events:?}");
+        // sample the thunderstorms crossing the map along the wind
+        crate::events::storm::sample_storms(ecosystem);
 
-            let index = CellIndex::get_from_flat_index(i);
-            for event in events {
-                Events::apply_event(event, &mut self.ecosystem.ecosystem, index);
+        // share the rainfall out over the map in cells drifting with the wind
+        crate::events::rain_cells::update_rainfall_field(ecosystem);
+
+        // fade burn scars from previous fires
+        crate::events::fire::decay_burn_severity(ecosystem);
+
+        // kill trees in the cells still infested by pests and diseases, and spread to their neighbors
+        crate::events::outbreak::update_outbreaks(ecosystem);
+
+        // erode and deposit sediment with the water running off the terrain
+        let trace = crate::events::rainfall::update_hydraulic_erosion(ecosystem);
+        ecosystem.set_runoff_trace(trace);
+
+        // route surface water into ponds and lakes
+        crate::events::hydrology::update_surface_water(ecosystem);
+
+        // wash sand ashore and break up the shore with waves
+        crate::events::coast::update_coast(ecosystem);
+
+        // infiltrate, take up, evaporate, and diffuse soil moisture
+        let wetted = crate::events::soil_moisture::update_soil_moisture(ecosystem);
+
+        // wet sand and humus may no longer hold on steep slopes
+        crate::events::soil_moisture::apply_rain_triggered_slides(ecosystem, &wetted);
+
+        // dry out the soil over rainless and hot months, raising the danger of fires
+        crate::events::fire_danger::update_drought_index(ecosystem);
+
+        // add and leach plant-available nitrogen
+        crate::events::nutrients::update_nutrients(ecosystem);
+
+        // compute the parts of the events that only read the ecosystem for all cells in parallel
+        let seed = ecosystem.rng.gen();
+        let precomputed = crate::events::parallel::precompute_events(ecosystem, seed);
+
+        // move and feed grazers before the vegetation grows back
+        crate::events::fauna::update_fauna(ecosystem, &precomputed);
+
+        // sample the rare events and pass over the grid with the dense ones
+        crate::events::scheduler::apply_scheduled_events(ecosystem, &precomputed);
+
+        // log the cells whose biome changed
+        ecosystem.update_succession();
+    }
+
+    // updates sunlight and the logs after the ecosystem advanced a time step
+    fn finish_time_step(&mut self) {
+        // update ray tracing geometry and sunlight where the terrain changed
+        self.ecosystem.update_dirty_terrain();
+
+        if let Some(path) = &self.event_log_path {
+            append_event_log(&self.ecosystem.event_log, self.ecosystem.time_step, path);
+        }
+        if let Some(path) = &self.metrics_path {
+            append_metrics(&self.ecosystem, self.ecosystem.time_step, path);
+        }
+        if let Some(time_lapse) = &mut self.time_lapse {
+            time_lapse.capture_if_due(&self.ecosystem, self.ecosystem.time_step);
+        }
+    }
+
+    // what the events did during the last time step
+    pub fn stats(&self) -> &EventLog {
+        &self.ecosystem.event_log
+    }
+
+    // runs the simulation without a window, exporting maps and a checkpoint every `export_every` steps
+    pub fn run_headless(&mut self, steps: u32, export_every: u32, path: &str) {
+        for _ in 0..steps {
+            println!("\nTime step {}", self.ecosystem.time_step);
+            self.take_time_step();
+            let time_step = self.ecosystem.time_step;
+            if export_every > 0 && time_step.is_multiple_of(export_every) {
+                export_maps(&self.ecosystem, time_step, path);
+                export_checkpoint(&self.ecosystem, time_step, path);
             }
-            // let cell = &self.ecosystem.ecosystem[index];
-            // humus_heights.push(cell.get_humus_height());
-            // println!("{index} sunlight {:?}", cell.hours_of_sunlight);
-            // println!("{index} height {} sand {}", cell.get_height(), cell.get_sand_height());
         }
+    }
+}
 
-        // println!("humus heights {humus_heights:?}");
-        let index = CellIndex::new(10, 10);
-        // let cell = &self.ecosystem.ecosystem[index];
-        let (wind_dir, wind_str) = if let Some(wind_state) = &self.ecosystem.ecosystem.wind_state {
-            crate::events::wind::get_local_wind(
-                &self.ecosystem.ecosystem,
-                index,
-                wind_state.wind_direction,
-                wind_state.wind_strength,
-            )
-        } else {
-            println!("default wind");
-            (constants::WIND_DIRECTION, constants::WIND_STRENGTH)
+#[cfg(test)]
+mod tests {
+    use super::Simulation;
+    use crate::{
+        constants,
+        ecology::{CellIndex, Ecosystem},
+    };
+
+    fn run_seeded(seed: u64) -> Ecosystem {
+        let mut simulation = Simulation {
+            ecosystem: Ecosystem::init_standard_ianterrain(seed),
+            event_log_path: None,
+            metrics_path: None,
+            time_lapse: None,
+            history: None,
+            script: None,
         };
-        println!("wind_dir {wind_dir}, wind_str {wind_str}");
-        // println!("rocks_height {}", cell.get_rock_height());
-        // println!("humus_height {}", cell.get_humus_height());
+        simulation.take_time_step();
+        simulation.take_time_step();
+        simulation.ecosystem
+    }
 
-        self.ecosystem.update_vertices(color_mode);
+    #[test]
+    fn test_seeded_runs_are_identical() {
+        let first = run_seeded(7);
+        let second = run_seeded(7);
+        for i in 0..constants::NUM_CELLS {
+            let index = CellIndex::get_from_flat_index(i);
+            let expected = &first[index];
+            let actual = &second[index];
+            assert_eq!(actual.get_height(), expected.get_height(), "{index}");
+            assert_eq!(
+                actual.get_sand_height(),
+                expected.get_sand_height(),
+                "{index}"
+            );
+            assert_eq!(
+                actual.get_dead_vegetation_biomass(),
+                expected.get_dead_vegetation_biomass(),
+                "{index}"
+            );
+            assert_eq!(actual.soil_moisture, expected.soil_moisture, "{index}");
+        }
     }
 
-    pub fn change_color_mode(&mut self, color_mode: &ColorMode) {
-        self.ecosystem.update_vertices(color_mode);
+    #[test]
+    fn test_step_back() {
+        let mut simulation = Simulation {
+            ecosystem: Ecosystem::init(),
+            event_log_path: None,
+            metrics_path: None,
+            time_lapse: None,
+            history: None,
+            script: None,
+        };
+        simulation.enable_history(1);
+        let index = CellIndex::new(10, 10);
+        simulation.ecosystem[index].add_sand(1.0);
+        let expected = simulation.ecosystem[index].get_sand_height();
+        simulation.take_time_step();
+        simulation.ecosystem[index].add_sand(1.0);
+
+        // back to the state before the time step, and only once
+        assert!(simulation.step_back());
+        assert!(!simulation.step_back());
+        assert_eq!(simulation.ecosystem.time_step, 0);
+        assert_eq!(simulation.ecosystem[index].get_sand_height(), expected);
     }
 }
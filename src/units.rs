@@ -0,0 +1,202 @@
+// UNITS
+// typed quantities for the conversions between heights, areas, volumes, and masses
+// cells store bare f32s in the units noted next to each field, and conversions wrap them
+// in these types so that mixing up e.g. liters and cubic meters does not compile
+// 1) each quantity supports adding, subtracting, and scaling by a plain f32
+// 2) dividing a quantity by another of the same kind gives a plain ratio
+// 3) products and quotients across kinds give the kind they make, e.g. m * m^2 = m^3
+
+use std::ops::{Add, AddAssign, Div, Mul, Sub, SubAssign};
+
+use crate::constants;
+
+macro_rules! quantity {
+    ($name:ident) => {
+        #[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+        pub struct $name(pub f32);
+
+        impl Add for $name {
+            type Output = $name;
+            fn add(self, other: $name) -> $name {
+                $name(self.0 + other.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: $name) {
+                self.0 += other.0;
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+            fn sub(self, other: $name) -> $name {
+                $name(self.0 - other.0)
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = $name;
+            fn mul(self, factor: f32) -> $name {
+                $name(self.0 * factor)
+            }
+        }
+
+        impl Div<f32> for $name {
+            type Output = $name;
+            fn div(self, divisor: f32) -> $name {
+                $name(self.0 / divisor)
+            }
+        }
+
+        impl Div for $name {
+            type Output = f32;
+            fn div(self, other: $name) -> f32 {
+                self.0 / other.0
+            }
+        }
+    };
+}
+
+// a * b = c, along with c / a = b and c / b = a
+macro_rules! product {
+    ($a:ident * $a2:ident = $c:ident, square) => {
+        impl Mul for $a {
+            type Output = $c;
+            fn mul(self, other: $a) -> $c {
+                $c(self.0 * other.0)
+            }
+        }
+
+        impl Div<$a> for $c {
+            type Output = $a;
+            fn div(self, other: $a) -> $a {
+                $a(self.0 / other.0)
+            }
+        }
+    };
+    ($a:ident * $b:ident = $c:ident) => {
+        impl Mul<$b> for $a {
+            type Output = $c;
+            fn mul(self, other: $b) -> $c {
+                $c(self.0 * other.0)
+            }
+        }
+
+        impl Div<$a> for $c {
+            type Output = $b;
+            fn div(self, other: $a) -> $b {
+                $b(self.0 / other.0)
+            }
+        }
+
+        impl Div<$b> for $c {
+            type Output = $a;
+            fn div(self, other: $b) -> $a {
+                $a(self.0 / other.0)
+            }
+        }
+    };
+}
+
+quantity!(Meters);
+quantity!(Millimeters);
+quantity!(SquareMeters);
+quantity!(CubicMeters);
+quantity!(Liters);
+quantity!(Kilograms);
+quantity!(KilogramsPerSquareMeter);
+quantity!(KilogramsPerCubicMeter);
+
+product!(Meters * Meters = SquareMeters, square);
+product!(SquareMeters * Meters = CubicMeters);
+product!(SquareMeters * KilogramsPerSquareMeter = Kilograms);
+product!(CubicMeters * KilogramsPerCubicMeter = Kilograms);
+// 1 mm of water over 1 m^2 is 1 L
+product!(SquareMeters * Millimeters = Liters);
+
+pub const LITERS_PER_CUBIC_METER: f32 = 1000.0;
+pub const MILLIMETERS_PER_METER: f32 = 1000.0;
+
+pub const CELL_AREA: SquareMeters =
+    SquareMeters(constants::CELL_SIDE_LENGTH * constants::CELL_SIDE_LENGTH);
+
+impl Millimeters {
+    pub fn to_meters(self) -> Meters {
+        Meters(self.0 / MILLIMETERS_PER_METER)
+    }
+}
+
+impl Meters {
+    pub fn to_millimeters(self) -> Millimeters {
+        Millimeters(self.0 * MILLIMETERS_PER_METER)
+    }
+
+    // volume of a layer of this height covering a whole cell
+    pub fn over_cell(self) -> CubicMeters {
+        CELL_AREA * self
+    }
+}
+
+impl CubicMeters {
+    pub fn to_liters(self) -> Liters {
+        Liters(self.0 * LITERS_PER_CUBIC_METER)
+    }
+
+    // height of a layer of this volume spread over a whole cell
+    pub fn spread_over_cell(self) -> Meters {
+        self / CELL_AREA
+    }
+}
+
+impl Liters {
+    pub fn to_cubic_meters(self) -> CubicMeters {
+        CubicMeters(self.0 / LITERS_PER_CUBIC_METER)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{
+        CubicMeters, Kilograms, KilogramsPerCubicMeter, Liters, Meters, Millimeters, SquareMeters,
+        CELL_AREA,
+    };
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(Millimeters(250.0).to_meters(), Meters(0.25));
+        assert_eq!(Meters(0.25).to_millimeters(), Millimeters(250.0));
+        assert_eq!(CubicMeters(2.0).to_liters(), Liters(2000.0));
+        assert_eq!(Liters(500.0).to_cubic_meters(), CubicMeters(0.5));
+
+        // a layer keeps its height when spread back over the cell
+        let height = Meters(0.3);
+        let actual = height.over_cell().spread_over_cell().0;
+        assert!(
+            approx_eq!(f32, actual, 0.3, epsilon = 0.0001),
+            "Expected 0.3, actual {actual}"
+        );
+        assert_eq!(Meters(2.0) * Meters(3.0), SquareMeters(6.0));
+        assert_eq!(CELL_AREA * Meters(1.0) / Meters(1.0), CELL_AREA);
+
+        // a cubic meter of humus
+        let density = KilogramsPerCubicMeter(1500.0);
+        let mass = CubicMeters(1.0) * density;
+        assert_eq!(mass, Kilograms(1500.0));
+        assert_eq!(mass / density, CubicMeters(1.0));
+        assert_eq!(Kilograms(3.0) / Kilograms(2.0), 1.5);
+        assert_eq!(Liters(1.0) + Liters(2.0) * 2.0 - Liters(1.0), Liters(4.0));
+
+        // rainfall over a cell
+        let expected = Millimeters(1.0).to_meters().over_cell().to_liters();
+        assert_eq!(CELL_AREA * Millimeters(1.0), expected);
+    }
+}
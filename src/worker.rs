@@ -0,0 +1,326 @@
+// WORKER
+// runs the simulation on its own thread so the window keeps drawing during long time steps
+// and sunlight updates, talking to the render thread only through channels
+// 1) the render thread sends commands: run, pause, step, step back, brush, forced event, pace,
+//    color mode, and sunlight it ray traced on the gpu
+// 2) the worker applies all queued commands, then takes a time step if one is due at its pace,
+//    and otherwise spends a slice of time ray tracing sunlight queued by terrain changes
+// 3) whenever the ecosystem changed, it sends back a copy of it along with the vertex data
+//    of the cells that changed, which the render thread only has to upload
+// the worker has no OpenGL context, so it ray traces sunlight on the cpu, unless the render thread
+// has compute shaders, in which case that thread retraces sunlight whenever an update says it is stale
+
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use nalgebra::Vector2;
+
+use crate::{
+    brush::Brush,
+    constants,
+    ecology::{CellIndex, Ecosystem},
+    events::Events,
+    pacing::Pacing,
+    render_data::{self, CellVertex, ColorMode},
+    Simulation,
+};
+
+pub enum WorkerCommand {
+    // keep taking time steps, stopping after the given time step if any
+    Run(Option<u32>),
+    Pause,
+    // take one time step
+    Step,
+    // undo the last time step, if it was kept
+    StepBack,
+    SetStepsPerSecond(f32),
+    ChangeColorMode(ColorMode),
+    ApplyBrush(Brush, CellIndex),
+    // apply the event at the cell for debugging
+    ForceEvent(Events, CellIndex),
+    // hours of sunlight the render thread ray traced on the gpu, ordered as by set_hours_of_sunlight
+    SetSunlight(Vec<[f32; 12]>),
+}
+
+pub struct WorkerUpdate {
+    // copy of the ecosystem to show, inspect, and export, without ray tracing geometry
+    pub ecosystem: Ecosystem,
+    // terrain vertices and plants of the cells that changed since the last update
+    pub vertex_data: Vec<CellVertex>,
+    // whether a time step was taken since the last update
+    pub stepped: bool,
+    // whether the worker stopped running at the time step it was asked to stop at
+    pub stopped: bool,
+    // whether the terrain changed so the render thread has to ray trace sunlight again
+    pub sunlight_stale: bool,
+}
+
+pub struct SimulationWorker {
+    // latest copy of the ecosystem sent by the worker
+    pub ecosystem: Ecosystem,
+    commands: Sender<WorkerCommand>,
+    updates: Receiver<WorkerUpdate>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SimulationWorker {
+    // moves the simulation onto a new thread, which should not have a renderer
+    pub fn spawn(simulation: Simulation, color_mode: ColorMode, steps_per_second: f32) -> Self {
+        let ecosystem = simulation.ecosystem.copy_state();
+        let (commands, command_receiver) = mpsc::channel();
+        let (update_sender, updates) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            let mut worker = Worker {
+                tree_positions: render_data::build_tree_positions(simulation.ecosystem.seed),
+                simulation,
+                color_mode,
+                pacing: Pacing::new(steps_per_second),
+                running: false,
+                stop_at: None,
+                requested_steps: 0,
+            };
+            worker.run(command_receiver, update_sender);
+        });
+        SimulationWorker {
+            ecosystem,
+            commands,
+            updates,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn send(&self, command: WorkerCommand) {
+        // the worker only stops once this side is dropped
+        self.commands.send(command).unwrap();
+    }
+
+    // handles the updates sent since the last call in order, then keeps the copy of the newest one
+    // returns whether there were any
+    pub fn poll(&mut self, mut handle: impl FnMut(&WorkerUpdate)) -> bool {
+        let mut newest = None;
+        for update in self.updates.try_iter() {
+            handle(&update);
+            newest = Some(update);
+        }
+        let Some(update) = newest else {
+            return false;
+        };
+        self.ecosystem = update.ecosystem;
+        true
+    }
+}
+
+impl Drop for SimulationWorker {
+    // lets the worker finish the time step it is taking so its logs are complete
+    fn drop(&mut self) {
+        let (closed, _) = mpsc::channel();
+        drop(std::mem::replace(&mut self.commands, closed));
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct Worker {
+    simulation: Simulation,
+    color_mode: ColorMode,
+    tree_positions: Vec<Vector2<f32>>,
+    pacing: Pacing,
+    running: bool,
+    stop_at: Option<u32>,
+    // single time steps asked for that have not been taken yet
+    requested_steps: u32,
+}
+
+impl Worker {
+    fn run(&mut self, commands: Receiver<WorkerCommand>, updates: Sender<WorkerUpdate>) {
+        let mut last_tick = Instant::now();
+        loop {
+            // 1) wait for a command unless a time step or sunlight is waiting to be worked on
+            let first = if self.requested_steps > 0 || !self.is_sunlight_converged() {
+                match commands.try_recv() {
+                    Ok(command) => Some(command),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            } else if self.running {
+                let timeout = Duration::from_secs_f32(self.pacing.get_time_until_due_step());
+                match commands.recv_timeout(timeout) {
+                    Ok(command) => Some(command),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            } else {
+                match commands.recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => return,
+                }
+            };
+            let mut redraw_all = false;
+            for command in first.into_iter().chain(commands.try_iter()) {
+                redraw_all |= self.apply_command(command);
+            }
+
+            // 2) take a time step if one is due, otherwise converge sunlight
+            let now = Instant::now();
+            self.pacing
+                .advance(now.duration_since(last_tick).as_secs_f32());
+            last_tick = now;
+            let mut stepped = false;
+            let mut stopped = false;
+            if self.requested_steps > 0 || (self.running && self.pacing.take_due_step()) {
+                self.requested_steps = self.requested_steps.saturating_sub(1);
+                self.take_time_step();
+                stepped = true;
+                let time_step = self.simulation.ecosystem.time_step;
+                if self.stop_at.is_some_and(|stop_at| time_step >= stop_at) {
+                    self.running = false;
+                    stopped = true;
+                }
+            } else {
+                self.simulation
+                    .ecosystem
+                    .advance_sunlight(Duration::from_millis(constants::SUNLIGHT_MILLIS_PER_FRAME));
+            }
+
+            // 3) send the changes back
+            let ecosystem = &mut self.simulation.ecosystem;
+            if !redraw_all && !stepped && ecosystem.changed_cells.is_empty() {
+                continue;
+            }
+            let vertex_data = if redraw_all {
+                ecosystem.changed_cells.clear();
+                render_data::build_vertex_data(&self.tree_positions, ecosystem, &self.color_mode)
+            } else {
+                render_data::build_changed_vertex_data(
+                    &self.tree_positions,
+                    ecosystem,
+                    &self.color_mode,
+                )
+            };
+            let update = WorkerUpdate {
+                ecosystem: ecosystem.copy_state(),
+                vertex_data,
+                stepped,
+                stopped,
+                sunlight_stale: std::mem::take(&mut ecosystem.sunlight_stale),
+            };
+            if updates.send(update).is_err() {
+                return;
+            }
+        }
+    }
+
+    // returns whether every vertex has to be rebuilt
+    fn apply_command(&mut self, command: WorkerCommand) -> bool {
+        match command {
+            WorkerCommand::Run(stop_at) => {
+                self.running = true;
+                self.stop_at = stop_at;
+            }
+            WorkerCommand::Pause => self.running = false,
+            WorkerCommand::Step => self.requested_steps += 1,
+            WorkerCommand::StepBack => {
+                if self.simulation.step_back() {
+                    println!(
+                        "\nRewound to time step {}",
+                        self.simulation.ecosystem.time_step
+                    );
+                    return true;
+                }
+                println!("\nNo earlier time steps kept");
+            }
+            WorkerCommand::SetStepsPerSecond(steps_per_second) => {
+                self.pacing = Pacing::new(steps_per_second);
+            }
+            WorkerCommand::ChangeColorMode(color_mode) => {
+                self.color_mode = color_mode;
+                return true;
+            }
+            WorkerCommand::ApplyBrush(brush, index) => {
+                brush.apply(&mut self.simulation.ecosystem, index);
+            }
+            WorkerCommand::ForceEvent(event, index) => {
+                event.force_event(&mut self.simulation.ecosystem, index);
+            }
+            WorkerCommand::SetSunlight(cell_hours) => {
+                self.simulation.ecosystem.set_hours_of_sunlight(&cell_hours);
+            }
+        }
+        false
+    }
+
+    fn take_time_step(&mut self) {
+        println!("\nTime step {}", self.simulation.ecosystem.time_step);
+        self.simulation.take_time_step();
+    }
+
+    fn is_sunlight_converged(&self) -> bool {
+        self.simulation
+            .ecosystem
+            .progressive_sunlight
+            .as_ref()
+            .is_none_or(|progressive| progressive.is_converged())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SimulationWorker, WorkerCommand};
+    use crate::{
+        constants,
+        ecology::{CellIndex, Ecosystem},
+        render_data::ColorMode,
+        Simulation,
+    };
+
+    #[test]
+    fn test_simulation_worker() {
+        let mut simulation = Simulation {
+            ecosystem: Ecosystem::init(),
+            event_log_path: None,
+            metrics_path: None,
+            time_lapse: None,
+            history: None,
+            script: None,
+        };
+        simulation.enable_history(1);
+        let mut worker = SimulationWorker::spawn(simulation, ColorMode::Standard, 10.0);
+
+        // a time step sends back the advanced ecosystem
+        worker.send(WorkerCommand::Step);
+        let mut stepped = false;
+        while !stepped {
+            worker.poll(|update| stepped |= update.stepped);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(worker.ecosystem.time_step, 1);
+
+        // changing the color mode rebuilds every vertex
+        worker.send(WorkerCommand::ChangeColorMode(ColorMode::Sunlight(0)));
+        let mut num_vertices = 0;
+        while !worker.poll(|update| num_vertices = update.vertex_data.len()) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(num_vertices, constants::NUM_CELLS);
+
+        // stepping back sends back the earlier ecosystem
+        worker.send(WorkerCommand::StepBack);
+        while !worker.poll(|_| {}) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(worker.ecosystem.time_step, 0);
+
+        // sunlight ray traced on the render thread is moved into the cells
+        let side = constants::AREA_SIDE_LENGTH - 1;
+        worker.send(WorkerCommand::SetSunlight(vec![[1.0; 12]; side * side]));
+        while !worker.poll(|_| {}) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let index = CellIndex::new(10, 10);
+        assert_eq!(worker.ecosystem[index].hours_of_sunlight, [1.0; 12]);
+    }
+}
@@ -0,0 +1,182 @@
+// runs of the whole simulation with a fixed seed, checking that the ecosystem stays plausible
+// the grid size is fixed at compile time, so the long run is slow in debug builds and ignored by
+// default:
+// cargo test --release --test plausibility -- --ignored
+
+use vegetables_and_hummus::{
+    config::Config, ecology::Ecosystem, events::vegetation::Individualized, simulation::Simulation,
+};
+
+// number of time steps of the long run
+const STEPS: u32 = 200;
+// number of time steps of the short run, which guards every plain cargo test
+const SHORT_STEPS: u32 = 10;
+// bounds no cell should ever leave, far outside what the events can produce on their own
+const MAX_HUMUS_HEIGHT: f32 = 20.0; // in m
+const MAX_CELL_BIOMASS: f32 = 1.0e7; // in kg
+const MAX_TREES_PER_CELL: u32 = 2000;
+// vegetation dies back in bad years, but must come back within this many time steps
+const MAX_BARREN_STEPS: usize = 20;
+// slack for float rounding when summing bedrock over the map
+const BEDROCK_EPSILON: f32 = 0.01; // in m
+
+const SEED: u64 = 11;
+// deep enough for the soil to hold a moderate share of the yearly rainfall
+const INITIAL_HUMUS_HEIGHT: f32 = 1.0; // in m
+
+const CONFIG: &str = r#"
+    time_resolution = "year"
+    illumination_method = "constant"
+"#;
+
+struct Totals {
+    bedrock: f32,
+    trees: u32,
+    bushes: u32,
+    grass_coverage: f32,
+}
+
+fn compute_totals(ecosystem: &Ecosystem) -> Totals {
+    let mut totals = Totals {
+        bedrock: 0.0,
+        trees: 0,
+        bushes: 0,
+        grass_coverage: 0.0,
+    };
+    for cell in ecosystem.cells.iter() {
+        totals.bedrock += cell.get_bedrock_height();
+        totals.trees += cell
+            .trees
+            .iter()
+            .map(|trees| trees.get_number_of_plants())
+            .sum::<u32>();
+        totals.bushes += cell
+            .bushes
+            .as_ref()
+            .map_or(0, |bushes| bushes.get_number_of_plants());
+        totals.grass_coverage += cell
+            .grasses
+            .as_ref()
+            .map_or(0.0, |grasses| grasses.coverage_density);
+    }
+    totals
+}
+
+// panics at the first cell with a negative layer or a layer outside its bounds
+fn check_cells(ecosystem: &Ecosystem) {
+    let time_step = ecosystem.time_step;
+    for (i, cell) in ecosystem.cells.iter().enumerate() {
+        let layers = [
+            ("bedrock", cell.get_bedrock_height()),
+            ("rocks", cell.get_rock_height()),
+            ("sand", cell.get_sand_height()),
+            ("humus", cell.get_humus_height()),
+            ("water", cell.get_water_height()),
+            ("snow", cell.get_snow_height()),
+            ("dead vegetation", cell.get_dead_vegetation_biomass()),
+            ("soil moisture", cell.soil_moisture),
+        ];
+        for (name, value) in layers {
+            assert!(
+                value >= 0.0 && value.is_finite(),
+                "{name} of cell {i} is {value} at time step {time_step}"
+            );
+        }
+
+        let humus = cell.get_humus_height();
+        assert!(
+            humus < MAX_HUMUS_HEIGHT,
+            "humus of cell {i} is {humus} at time step {time_step}"
+        );
+        let biomass = cell.estimate_tree_biomass()
+            + cell.estimate_bush_biomass()
+            + cell.estimate_grasses_biomass();
+        assert!(
+            (0.0..MAX_CELL_BIOMASS).contains(&biomass),
+            "biomass of cell {i} is {biomass} at time step {time_step}"
+        );
+        let trees: u32 = cell
+            .trees
+            .iter()
+            .map(|trees| trees.get_number_of_plants())
+            .sum();
+        assert!(
+            trees < MAX_TREES_PER_CELL,
+            "cell {i} has {trees} trees at time step {time_step}"
+        );
+    }
+}
+
+#[test]
+fn test_short_run_stays_plausible() {
+    run_stays_plausible(SHORT_STEPS);
+}
+
+#[test]
+#[ignore]
+fn test_long_run_stays_plausible() {
+    run_stays_plausible(STEPS);
+}
+
+fn run_stays_plausible(steps: u32) {
+    // hills with a forest over half of the map
+    let mut ecosystem = Ecosystem::init_standard_ianterrain(SEED);
+    for cell in ecosystem.cells.iter_mut() {
+        cell.add_humus(INITIAL_HUMUS_HEIGHT);
+    }
+    ecosystem.config = Config::parse(CONFIG).unwrap();
+    ecosystem.set_seed(SEED);
+    ecosystem.recompute_sunlight();
+    let mut simulation = Simulation {
+        ecosystem,
+        event_log_path: None,
+        metrics_path: None,
+        time_lapse: None,
+        history: None,
+        script: None,
+    };
+    let initial = compute_totals(&simulation.ecosystem);
+    assert!(initial.trees > 0);
+
+    let mut bedrock = initial.bedrock;
+    let mut vegetated_steps = 0;
+    let mut barren_steps = 0;
+    let mut max_trees = 0;
+    for _ in 0..steps {
+        simulation.take_time_step();
+        let ecosystem = &simulation.ecosystem;
+        check_cells(ecosystem);
+
+        // bedrock is only ever broken up, never created
+        let totals = compute_totals(ecosystem);
+        assert!(
+            totals.bedrock <= bedrock + BEDROCK_EPSILON,
+            "bedrock grew from {bedrock} to {} at time step {}",
+            totals.bedrock,
+            ecosystem.time_step
+        );
+        bedrock = totals.bedrock;
+
+        // vegetation does not stay extinct across the whole map
+        if totals.trees + totals.bushes > 0 || totals.grass_coverage > 0.0 {
+            vegetated_steps += 1;
+            barren_steps = 0;
+        } else {
+            barren_steps += 1;
+        }
+        assert!(
+            barren_steps < MAX_BARREN_STEPS,
+            "vegetation went extinct at time step {}",
+            ecosystem.time_step
+        );
+        max_trees = max_trees.max(totals.trees);
+    }
+
+    // vegetation covers the map most of the time without filling every cell to the bound
+    assert!(
+        vegetated_steps > steps / 2,
+        "vegetation in only {vegetated_steps} of {steps} time steps"
+    );
+    let tree_bound = MAX_TREES_PER_CELL * simulation.ecosystem.cells.len() as u32 / 2;
+    assert!(max_trees < tree_bound, "trees exploded to {max_trees}");
+}